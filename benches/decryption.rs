@@ -0,0 +1,108 @@
+//! Benchmarks decrypting a backfill's worth of note ciphertexts on a single
+//! worker against decrypting them on a pool of one worker per CPU
+//!
+//! Run with `cargo bench --bench decryption -- [<num_notes>]`
+
+use std::env;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use ethers::core::rand::{thread_rng, Rng};
+use fee_sweeper::decryption::{DecryptionPool, NoteCiphertext};
+use num_bigint::BigUint;
+use renegade_circuit_types::elgamal::{DecryptionKey, EncryptionKey};
+use renegade_circuit_types::native_helpers::elgamal_encrypt;
+use renegade_circuit_types::note::Note;
+use renegade_circuit_types::wallet::NoteCommitment;
+use renegade_constants::Scalar;
+use renegade_crypto::fields::biguint_to_scalar;
+
+/// The default number of notes decrypted
+const DEFAULT_NUM_NOTES: usize = 4096;
+/// The number of fee keys notes are decrypted under, one per kind of fee
+const NUM_KEYS: usize = 2;
+
+/// Decrypt a batch of random notes on each pool, reporting the speedup
+fn main() {
+    let num_notes = env::args()
+        .skip(1)
+        .find_map(|arg| arg.parse().ok())
+        .unwrap_or(DEFAULT_NUM_NOTES);
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+
+    let mut rng = thread_rng();
+    let keys: Vec<DecryptionKey> = (0..NUM_KEYS)
+        .map(|_| DecryptionKey::random_pair(&mut rng).0)
+        .collect();
+    let keys = Arc::new(keys);
+    let (_, foreign_key) = DecryptionKey::random_pair(&mut rng);
+
+    // Most notes posted on-chain are paid to other receivers, so one in two is
+    // encrypted under a key that is not ours
+    let notes: Vec<(NoteCiphertext, NoteCommitment)> = (0..num_notes)
+        .map(|i| {
+            let receiver = match i % (2 * NUM_KEYS) {
+                j if j < NUM_KEYS => keys[j].public_key(),
+                _ => foreign_key,
+            };
+            random_note(&mut rng, receiver)
+        })
+        .collect();
+
+    println!("decrypting {num_notes} notes under {NUM_KEYS} keys");
+    let pool = DecryptionPool::new(0);
+    let (serial, serial_time) = runtime.block_on(time(DecryptionPool::new(1), &notes, &keys));
+    let (parallel, parallel_time) = runtime.block_on(time(pool, &notes, &keys));
+
+    let found = |decrypted: &[Option<(usize, Note)>]| -> Vec<Option<usize>> {
+        decrypted
+            .iter()
+            .map(|d| d.as_ref().map(|(i, _)| *i))
+            .collect()
+    };
+    assert_eq!(
+        found(&serial),
+        found(&parallel),
+        "pools disagree on decrypted notes"
+    );
+    let ours = serial.iter().filter(|d| d.is_some()).count();
+
+    println!("found {ours} of {num_notes} notes to be ours");
+    println!("1 worker: {serial_time:.2?}");
+    println!("{} workers: {parallel_time:.2?}", pool.workers());
+    println!(
+        "speedup: {:.2}x",
+        serial_time.as_secs_f64() / parallel_time.as_secs_f64()
+    );
+}
+
+/// Decrypt the notes on the given pool, returning the results and the time
+/// taken
+async fn time(
+    pool: DecryptionPool,
+    notes: &[(NoteCiphertext, NoteCommitment)],
+    keys: &Arc<Vec<DecryptionKey>>,
+) -> (Vec<Option<(usize, Note)>>, Duration) {
+    let start = Instant::now();
+    let decrypted = pool.decrypt(notes.to_vec(), keys.clone()).await.unwrap();
+    (decrypted, start.elapsed())
+}
+
+/// Encrypt a random note to the given receiver, returning its ciphertext and
+/// commitment
+fn random_note<R: Rng>(rng: &mut R, receiver: EncryptionKey) -> (NoteCiphertext, NoteCommitment) {
+    let note = Note {
+        mint: BigUint::from(rng.gen::<u64>()),
+        amount: rng.gen::<u64>() as u128,
+        receiver,
+        blinder: Scalar::random(rng),
+    };
+    let plaintext = [
+        biguint_to_scalar(&note.mint),
+        Scalar::from(note.amount),
+        note.blinder,
+    ];
+    let (ciphertext, _) = elgamal_encrypt(&plaintext, &receiver);
+
+    (ciphertext, note.commitment())
+}
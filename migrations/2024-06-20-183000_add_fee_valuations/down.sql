@@ -0,0 +1,5 @@
+-- Remove block information and USD valuations from the fees table
+ALTER TABLE fees DROP COLUMN usd_value;
+ALTER TABLE fees DROP COLUMN usd_price;
+ALTER TABLE fees DROP COLUMN block_timestamp;
+ALTER TABLE fees DROP COLUMN block_number;
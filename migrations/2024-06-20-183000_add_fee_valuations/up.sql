@@ -0,0 +1,6 @@
+-- Add block information and USD valuations to the fees table
+-- Valuations are backfilled from a historical price source, so all columns are nullable
+ALTER TABLE fees ADD COLUMN block_number BIGINT;
+ALTER TABLE fees ADD COLUMN block_timestamp TIMESTAMP;
+ALTER TABLE fees ADD COLUMN usd_price NUMERIC;
+ALTER TABLE fees ADD COLUMN usd_value NUMERIC;
@@ -0,0 +1,4 @@
+-- Drop the composite indices on the fees table
+DROP INDEX IF EXISTS idx_fees_block_number;
+DROP INDEX IF EXISTS idx_fees_redeemed_block_number;
+DROP INDEX IF EXISTS idx_fees_redeemed_mint_amount;
@@ -0,0 +1,12 @@
+-- Composite indices supporting the hot queries on the fees table
+-- The `redeemed` flag is the fee's status, and the block number orders fees by creation
+
+-- Supports the keyset-paginated redemption scan, which walks each mint's
+-- unredeemed fees in descending order of amount
+CREATE INDEX idx_fees_redeemed_mint_amount ON fees(redeemed, mint, amount DESC, id DESC);
+
+-- Supports queries over unredeemed fees by age
+CREATE INDEX idx_fees_redeemed_block_number ON fees(redeemed, block_number);
+
+-- Supports lookups of fees by the block range they were settled in
+CREATE INDEX idx_fees_block_number ON fees(block_number);
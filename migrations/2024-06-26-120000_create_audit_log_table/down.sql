@@ -0,0 +1,3 @@
+-- Drop the audit log table and indexes
+DROP TABLE IF EXISTS audit_log;
+DROP INDEX IF EXISTS idx_audit_log_event_type;
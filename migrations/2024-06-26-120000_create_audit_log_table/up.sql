@@ -0,0 +1,11 @@
+-- Create a table recording sensitive actions taken by the sweeper, e.g. token approvals
+-- The `details` column holds a human readable description of the action
+CREATE TABLE audit_log (
+    id SERIAL PRIMARY KEY,
+    event_type TEXT NOT NULL,
+    tx_hash TEXT,
+    details TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_audit_log_event_type ON audit_log(event_type, created_at);
@@ -0,0 +1,3 @@
+-- Drop the bridge transfers table and indexes
+DROP TABLE IF EXISTS bridge_transfers;
+DROP INDEX IF EXISTS idx_bridge_transfers_status;
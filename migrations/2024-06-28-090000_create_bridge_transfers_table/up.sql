@@ -0,0 +1,16 @@
+-- Create a table tracking transfers of redeemed funds to L1 through the canonical bridge
+-- A transfer moves through `initiated` -> `confirmed` -> `claimed`, or `failed` if the L2 tx reverts
+CREATE TABLE bridge_transfers (
+    id SERIAL PRIMARY KEY,
+    mint TEXT NOT NULL,
+    l1_token TEXT NOT NULL,
+    amount NUMERIC NOT NULL,
+    destination TEXT NOT NULL,
+    l2_tx_hash TEXT NOT NULL UNIQUE,
+    l1_tx_hash TEXT,
+    status TEXT NOT NULL DEFAULT 'initiated',
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_bridge_transfers_status ON bridge_transfers(status);
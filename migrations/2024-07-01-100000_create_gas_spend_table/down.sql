@@ -0,0 +1,3 @@
+-- Drop the gas spend table and indexes
+DROP TABLE IF EXISTS gas_spend;
+DROP INDEX IF EXISTS idx_gas_spend_purpose;
@@ -0,0 +1,13 @@
+-- Create a ledger of the gas spent by transactions the sweeper submits directly
+-- `wei_spent` is `gas_used * effective_gas_price`, stored so aggregates need no arithmetic
+CREATE TABLE gas_spend (
+    id SERIAL PRIMARY KEY,
+    tx_hash TEXT NOT NULL UNIQUE,
+    purpose TEXT NOT NULL,
+    gas_used NUMERIC NOT NULL,
+    effective_gas_price NUMERIC NOT NULL,
+    wei_spent NUMERIC NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_gas_spend_purpose ON gas_spend(purpose, created_at);
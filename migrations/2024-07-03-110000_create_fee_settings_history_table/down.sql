@@ -0,0 +1,3 @@
+-- Drop the fee settings history table and indexes
+DROP TABLE IF EXISTS fee_settings_history;
+DROP INDEX IF EXISTS idx_fee_settings_history_block;
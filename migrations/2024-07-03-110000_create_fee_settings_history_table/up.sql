@@ -0,0 +1,16 @@
+-- Create a table recording changes to the protocol's fee settings, as emitted by the darkpool
+-- `raw_value` is the fixed point representation in the event, `rate` its decimal value
+CREATE TABLE fee_settings_history (
+    id SERIAL PRIMARY KEY,
+    setting TEXT NOT NULL,
+    asset TEXT,
+    raw_value NUMERIC NOT NULL,
+    rate NUMERIC NOT NULL,
+    block_number BIGINT NOT NULL,
+    tx_hash TEXT NOT NULL,
+    log_index BIGINT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    UNIQUE (tx_hash, log_index)
+);
+
+CREATE INDEX idx_fee_settings_history_block ON fee_settings_history(setting, block_number);
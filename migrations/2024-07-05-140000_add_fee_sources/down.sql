@@ -0,0 +1,3 @@
+-- Remove fee sources from the fees table
+DROP INDEX IF EXISTS idx_fees_source;
+ALTER TABLE fees DROP COLUMN source;
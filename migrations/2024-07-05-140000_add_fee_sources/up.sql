@@ -0,0 +1,5 @@
+-- Tag each fee with the type of match it was earned in
+-- Fees indexed before sources were recorded were all earned in internal matches
+ALTER TABLE fees ADD COLUMN source TEXT NOT NULL DEFAULT 'internal_match';
+
+CREATE INDEX idx_fees_source ON fees(source);
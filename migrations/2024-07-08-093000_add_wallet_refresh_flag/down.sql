@@ -0,0 +1,2 @@
+-- Remove the refresh flag from the wallets table
+ALTER TABLE wallets DROP COLUMN needs_refresh;
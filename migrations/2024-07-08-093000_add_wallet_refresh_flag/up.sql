@@ -0,0 +1,3 @@
+-- Flag wallets whose relayer-side state may be stale after a failed task
+-- A flagged wallet is re-synced from the chain before its next redemption
+ALTER TABLE wallets ADD COLUMN needs_refresh BOOLEAN NOT NULL DEFAULT FALSE;
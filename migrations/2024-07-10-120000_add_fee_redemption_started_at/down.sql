@@ -0,0 +1,3 @@
+-- Remove redemption start times from the fees table
+DROP INDEX IF EXISTS idx_fees_redemption_started_at;
+ALTER TABLE fees DROP COLUMN redemption_started_at;
@@ -0,0 +1,6 @@
+-- Record when a redemption of each fee was started, cleared once it finishes
+-- A fee whose redemption started long ago and never finished was orphaned by a crashed run
+ALTER TABLE fees ADD COLUMN redemption_started_at TIMESTAMP;
+
+CREATE INDEX idx_fees_redemption_started_at ON fees(redemption_started_at)
+    WHERE redemption_started_at IS NOT NULL;
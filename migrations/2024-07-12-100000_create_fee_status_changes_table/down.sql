@@ -0,0 +1,2 @@
+-- Drop the fee status history
+DROP TABLE IF EXISTS fee_status_changes;
@@ -0,0 +1,13 @@
+-- Record every change to a fee's redemption status so that balances may be reconstructed as of any date
+-- Redemptions made before this history was kept are recorded as of the migration
+CREATE TABLE fee_status_changes (
+    id SERIAL PRIMARY KEY,
+    tx_hash TEXT NOT NULL,
+    status TEXT NOT NULL,
+    changed_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_fee_status_changes_tx_hash ON fee_status_changes(tx_hash, changed_at);
+
+INSERT INTO fee_status_changes (tx_hash, status)
+SELECT tx_hash, 'redeemed' FROM fees WHERE redeemed;
@@ -0,0 +1,3 @@
+-- Remove redemption attempt counts and dead letters from the fees table
+ALTER TABLE fees DROP COLUMN dead_lettered_at;
+ALTER TABLE fees DROP COLUMN redemption_attempts;
@@ -0,0 +1,4 @@
+-- Count failed redemption attempts of each fee, dead-lettering fees that fail too often
+-- Dead-lettered fees are no longer selected for redemption
+ALTER TABLE fees ADD COLUMN redemption_attempts INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE fees ADD COLUMN dead_lettered_at TIMESTAMP;
@@ -0,0 +1,2 @@
+-- Remove the dust flag from the fees table
+ALTER TABLE fees DROP COLUMN dust;
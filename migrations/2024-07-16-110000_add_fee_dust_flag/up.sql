@@ -0,0 +1,2 @@
+-- Flag notes below their mint's dust floor, which are never redeemed
+ALTER TABLE fees ADD COLUMN dust BOOLEAN NOT NULL DEFAULT FALSE;
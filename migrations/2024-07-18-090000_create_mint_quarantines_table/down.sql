@@ -0,0 +1,2 @@
+-- Drop the mint quarantines table
+DROP TABLE IF EXISTS mint_quarantines;
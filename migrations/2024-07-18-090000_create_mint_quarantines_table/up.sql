@@ -0,0 +1,8 @@
+-- Record mints quarantined as suspected spam, whose fees are not redeemed until released by an operator
+-- A released mint is never quarantined again
+CREATE TABLE mint_quarantines (
+    mint TEXT PRIMARY KEY,
+    reason TEXT NOT NULL,
+    quarantined_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    released_at TIMESTAMP
+);
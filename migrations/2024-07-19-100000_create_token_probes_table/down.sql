@@ -0,0 +1,2 @@
+-- Drop the token probes table
+DROP TABLE IF EXISTS token_probes;
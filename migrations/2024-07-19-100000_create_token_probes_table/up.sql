@@ -0,0 +1,7 @@
+-- Record the verdict of the safety probe run against each mint before its first redemption
+CREATE TABLE token_probes (
+    mint TEXT PRIMARY KEY,
+    passed BOOLEAN NOT NULL,
+    details TEXT NOT NULL,
+    probed_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,2 @@
+-- Drop the instance leases table
+DROP TABLE IF EXISTS instance_leases;
@@ -0,0 +1,9 @@
+-- Record the sweeper instance allowed to run, so that a newly deployed instance takes over from the old one safely
+-- The lease is free once released or expired; a successor awaiting handover takes precedence over other instances
+CREATE TABLE instance_leases (
+    name TEXT PRIMARY KEY,
+    holder UUID NOT NULL,
+    successor UUID,
+    acquired_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMP NOT NULL
+);
@@ -0,0 +1,2 @@
+-- Remove the abandonment marker from the fees table
+ALTER TABLE fees DROP COLUMN abandoned_at;
@@ -0,0 +1,2 @@
+-- Mark dust fees left unredeemed past the expiration policy as abandoned, excluding them from unredeemed value
+ALTER TABLE fees ADD COLUMN abandoned_at TIMESTAMP;
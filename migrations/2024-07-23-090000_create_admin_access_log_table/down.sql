@@ -0,0 +1,2 @@
+-- Drop the admin access log table
+DROP TABLE IF EXISTS admin_access_log;
@@ -0,0 +1,14 @@
+-- Record every request to the admin API, with the caller, route, parameters, and result, for compliance audits
+CREATE TABLE admin_access_log (
+    id SERIAL PRIMARY KEY,
+    caller TEXT NOT NULL,
+    method TEXT NOT NULL,
+    route TEXT NOT NULL,
+    params TEXT NOT NULL,
+    status INT NOT NULL,
+    result TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+-- Serve retention pruning and time-ranged audits
+CREATE INDEX admin_access_log_created_at_idx ON admin_access_log (created_at);
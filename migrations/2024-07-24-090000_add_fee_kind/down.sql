@@ -0,0 +1,3 @@
+-- Remove the fee kind labels
+ALTER TABLE wallets DROP COLUMN fee_kind;
+ALTER TABLE fees DROP COLUMN fee_kind;
@@ -0,0 +1,4 @@
+-- Label fees and redemption wallets with the kind of fee, protocol or relayer, they hold
+-- Fees and wallets recorded before the distinction was made were protocol fees
+ALTER TABLE fees ADD COLUMN fee_kind TEXT NOT NULL DEFAULT 'protocol';
+ALTER TABLE wallets ADD COLUMN fee_kind TEXT NOT NULL DEFAULT 'protocol';
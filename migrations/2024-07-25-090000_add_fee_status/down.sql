@@ -0,0 +1,2 @@
+-- Remove the dual-written fee status; the legacy status columns remain authoritative
+ALTER TABLE fees DROP COLUMN status;
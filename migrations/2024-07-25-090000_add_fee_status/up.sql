@@ -0,0 +1,3 @@
+-- Add a single status column to fees, the replacement for the redeemed flag and the redemption, dead letter, and abandonment timestamps
+-- The column is dual-written alongside the legacy columns, and NULL for fees written while dual-writing was disabled
+ALTER TABLE fees ADD COLUMN status TEXT;
@@ -0,0 +1,2 @@
+-- Remove the price routes
+DROP TABLE price_routes;
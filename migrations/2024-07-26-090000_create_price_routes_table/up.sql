@@ -0,0 +1,6 @@
+-- Price mints with no USDC pair at the price source through an intermediate mint, read at each redemption pass so routes apply without a restart
+CREATE TABLE price_routes (
+    mint TEXT PRIMARY KEY,
+    via_mint TEXT NOT NULL,
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
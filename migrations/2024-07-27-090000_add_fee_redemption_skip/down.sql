@@ -0,0 +1,3 @@
+-- Remove the record of skipped redemptions
+ALTER TABLE fees DROP COLUMN skip_reason;
+ALTER TABLE fees DROP COLUMN skipped_at;
@@ -0,0 +1,4 @@
+-- Record why an open fee was last skipped by a redemption pass, e.g. because its value did not cover the gas cost of redeeming it
+-- Both columns are cleared once a pass no longer skips the fee
+ALTER TABLE fees ADD COLUMN skipped_at TIMESTAMP;
+ALTER TABLE fees ADD COLUMN skip_reason TEXT;
@@ -0,0 +1,2 @@
+-- Remove the relayer task failures
+DROP TABLE task_failures;
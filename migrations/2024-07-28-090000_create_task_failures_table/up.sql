@@ -0,0 +1,12 @@
+-- Keep the relayer's payload for each failed redemption, so that a batch of failures may be triaged without querying the relayer for each task
+CREATE TABLE task_failures (
+    id SERIAL PRIMARY KEY,
+    tx_hash TEXT NOT NULL,
+    wallet_id UUID NOT NULL,
+    task_id UUID,
+    reason TEXT NOT NULL,
+    payload TEXT,
+    failed_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX task_failures_tx_hash_idx ON task_failures (tx_hash);
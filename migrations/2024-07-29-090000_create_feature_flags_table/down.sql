@@ -0,0 +1,2 @@
+-- Remove the feature flags
+DROP TABLE feature_flags;
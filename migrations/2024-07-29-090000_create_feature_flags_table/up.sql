@@ -0,0 +1,6 @@
+-- Feature flags gating risky subsystems, read at the start of each sweep so that a subsystem may be enabled per environment without a rebuild
+CREATE TABLE feature_flags (
+    name TEXT PRIMARY KEY,
+    enabled BOOLEAN NOT NULL,
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,3 @@
+-- Remove the relayer of each fee
+DROP INDEX fees_relayer_idx;
+ALTER TABLE fees DROP COLUMN relayer;
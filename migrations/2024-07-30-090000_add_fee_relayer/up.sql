@@ -0,0 +1,4 @@
+-- Record the address of the relayer that settled each fee, for per-relayer fee attribution
+ALTER TABLE fees ADD COLUMN relayer TEXT;
+
+CREATE INDEX fees_relayer_idx ON fees (relayer);
@@ -0,0 +1,8 @@
+-- Remove the redemption state machine columns
+DROP INDEX fees_redemption_state_idx;
+ALTER TABLE fees
+    DROP CONSTRAINT fees_redemption_state_check,
+    DROP COLUMN redemption_state,
+    DROP COLUMN redemption_state_changed_at,
+    DROP COLUMN redemptions_started,
+    DROP COLUMN last_attempted_at;
@@ -0,0 +1,25 @@
+-- Track each fee through the redemption state machine, with the time of its
+-- last transition and the redemptions attempted on it
+ALTER TABLE fees
+    ADD COLUMN redemption_state TEXT NOT NULL DEFAULT 'indexed',
+    ADD COLUMN redemption_state_changed_at TIMESTAMP NOT NULL DEFAULT now(),
+    ADD COLUMN redemptions_started INT4 NOT NULL DEFAULT 0,
+    ADD COLUMN last_attempted_at TIMESTAMP,
+    ADD CONSTRAINT fees_redemption_state_check CHECK (
+        redemption_state IN ('indexed', 'eligible', 'in_flight', 'redeemed', 'failed', 'quarantined')
+    );
+
+-- Derive the state of existing fees from their legacy status columns
+UPDATE fees SET
+    redemption_state = CASE
+        WHEN redeemed THEN 'redeemed'
+        WHEN dead_lettered_at IS NOT NULL THEN 'quarantined'
+        WHEN redemption_started_at IS NOT NULL THEN 'in_flight'
+        WHEN redemption_attempts > 0 THEN 'failed'
+        ELSE 'indexed'
+    END,
+    redemptions_started = redemption_attempts
+        + CASE WHEN redemption_started_at IS NOT NULL THEN 1 ELSE 0 END,
+    last_attempted_at = redemption_started_at;
+
+CREATE INDEX fees_redemption_state_idx ON fees (redemption_state);
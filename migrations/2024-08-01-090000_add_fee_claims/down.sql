@@ -0,0 +1,4 @@
+-- Remove fee claims
+ALTER TABLE fees
+    DROP COLUMN claimed_by,
+    DROP COLUMN claimed_until;
@@ -0,0 +1,6 @@
+-- Let a run claim fees for redemption, so that sweeper instances sharing the
+-- database never redeem the same fee. A claim lapses at `claimed_until`, so
+-- that fees claimed by a crashed run are claimable again
+ALTER TABLE fees
+    ADD COLUMN claimed_by UUID,
+    ADD COLUMN claimed_until TIMESTAMP;
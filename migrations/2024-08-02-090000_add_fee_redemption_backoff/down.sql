@@ -0,0 +1,4 @@
+-- Remove redemption backoff
+ALTER TABLE fees
+    DROP COLUMN last_redemption_error,
+    DROP COLUMN next_redemption_at;
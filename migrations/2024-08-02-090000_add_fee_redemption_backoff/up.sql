@@ -0,0 +1,5 @@
+-- Record why a fee's last redemption failed, and back off retrying it: a
+-- failed fee is not selected for redemption again until `next_redemption_at`
+ALTER TABLE fees
+    ADD COLUMN last_redemption_error TEXT,
+    ADD COLUMN next_redemption_at TIMESTAMP;
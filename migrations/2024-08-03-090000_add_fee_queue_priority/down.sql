@@ -0,0 +1,2 @@
+-- Remove redemption queue priorities
+ALTER TABLE fees DROP COLUMN queue_priority;
@@ -0,0 +1,4 @@
+-- An operator's override of a fee's place in the redemption queue: fees with a
+-- positive priority are redeemed before all others, highest first, and fees
+-- with a negative priority after all others
+ALTER TABLE fees ADD COLUMN queue_priority INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,2 @@
+-- Remove wallet divergence tracking
+ALTER TABLE wallets DROP COLUMN diverged_since;
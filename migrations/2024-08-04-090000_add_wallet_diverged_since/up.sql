@@ -0,0 +1,4 @@
+-- Record since when the relayer's view of a wallet has not been found
+-- committed on-chain, so that a divergence lasting past the expected delay of
+-- a wallet update is alerted on; null while the two agree
+ALTER TABLE wallets ADD COLUMN diverged_since TIMESTAMP;
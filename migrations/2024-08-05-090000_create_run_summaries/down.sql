@@ -0,0 +1,2 @@
+-- Drop the run summaries
+DROP TABLE run_summaries;
@@ -0,0 +1,11 @@
+-- Record the summary of each sweep's changes to the fees, so that recent runs
+-- may be reviewed without their logs, e.g. in a support bundle
+CREATE TABLE run_summaries (
+    id SERIAL PRIMARY KEY,
+    run_id UUID NOT NULL,
+    started_at TIMESTAMP NOT NULL,
+    finished_at TIMESTAMP NOT NULL,
+    summary TEXT NOT NULL
+);
+
+CREATE INDEX run_summaries_finished_at_idx ON run_summaries (finished_at);
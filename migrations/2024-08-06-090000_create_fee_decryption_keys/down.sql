@@ -0,0 +1,2 @@
+-- Drop the registered decryption keys
+DROP TABLE fee_decryption_keys;
@@ -0,0 +1,11 @@
+-- Register decryption keys of fee notes beyond those an instance is
+-- configured with, so that a rotated key may be added without a redeploy.
+-- Each key is held in a Secrets Manager secret, of which only the name is
+-- stored, under the key's public key: the receiver recorded for the fees it
+-- decrypts
+CREATE TABLE fee_decryption_keys (
+    receiver TEXT PRIMARY KEY,
+    fee_kind TEXT NOT NULL,
+    secret_name TEXT NOT NULL,
+    added_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
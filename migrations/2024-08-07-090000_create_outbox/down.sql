@@ -0,0 +1,2 @@
+-- Drop the outbox of notifications
+DROP TABLE outbox;
@@ -0,0 +1,17 @@
+-- Persist outgoing notifications before they are posted. A notification
+-- announcing a change of state is written in the same transaction as the
+-- change, and delivered from here, so that a crash between the two cannot
+-- lose it
+CREATE TABLE outbox (
+    id SERIAL PRIMARY KEY,
+    channel TEXT NOT NULL,
+    notice TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    last_error TEXT,
+    delivered_at TIMESTAMP
+);
+
+-- Delivery scans the notifications that have yet to be delivered
+CREATE INDEX outbox_next_attempt_at_idx ON outbox (next_attempt_at) WHERE delivered_at IS NULL;
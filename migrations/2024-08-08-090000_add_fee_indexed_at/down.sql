@@ -0,0 +1,2 @@
+-- Remove the indexing time of fees
+ALTER TABLE fees DROP COLUMN indexed_at;
@@ -0,0 +1,8 @@
+-- Record when each fee was indexed, from which the latency of its redemption
+-- is measured against the redemption SLO. Fees indexed before the column
+-- existed are taken to have been indexed at their block's time
+ALTER TABLE fees ADD COLUMN indexed_at TIMESTAMP NOT NULL DEFAULT NOW();
+UPDATE fees SET indexed_at = block_timestamp WHERE block_timestamp IS NOT NULL;
+
+-- The SLO scans the fees indexed within its windows
+CREATE INDEX fees_indexed_at_idx ON fees (indexed_at);
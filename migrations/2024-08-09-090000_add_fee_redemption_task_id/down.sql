@@ -0,0 +1,2 @@
+-- Remove the relayer task redeeming each fee
+ALTER TABLE fees DROP COLUMN redemption_task_id;
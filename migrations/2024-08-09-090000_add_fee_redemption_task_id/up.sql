@@ -0,0 +1,3 @@
+-- Record the relayer task redeeming each fee as soon as the relayer starts
+-- it, so that a run killed while awaiting the task leaves the task to check
+ALTER TABLE fees ADD COLUMN redemption_task_id UUID;
@@ -0,0 +1,4 @@
+-- Key fees on their transaction alone
+ALTER TABLE fees DROP CONSTRAINT fees_tx_hash_log_index_key;
+ALTER TABLE fees ADD CONSTRAINT fees_tx_hash_key UNIQUE (tx_hash);
+ALTER TABLE fees DROP COLUMN log_index;
@@ -0,0 +1,5 @@
+-- Key fees on the log that paid them rather than on the transaction alone, so
+-- that an external match paying several fee transfers records each of them
+ALTER TABLE fees ADD COLUMN log_index BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE fees DROP CONSTRAINT fees_tx_hash_key;
+ALTER TABLE fees ADD CONSTRAINT fees_tx_hash_log_index_key UNIQUE (tx_hash, log_index);
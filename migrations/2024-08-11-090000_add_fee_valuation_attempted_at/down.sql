@@ -0,0 +1,2 @@
+-- Remove the record of failed valuation attempts
+ALTER TABLE fees DROP COLUMN valuation_attempted_at;
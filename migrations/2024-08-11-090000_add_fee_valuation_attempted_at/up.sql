@@ -0,0 +1,3 @@
+-- Record when each fee was last tried and failed to be valued, so that fees
+-- that cannot be valued yet back off rather than starving the rest
+ALTER TABLE fees ADD COLUMN valuation_attempted_at TIMESTAMP;
@@ -0,0 +1,4 @@
+-- Drop the fund movement ledger and its indexes
+DROP TABLE IF EXISTS fund_movements;
+DROP INDEX IF EXISTS idx_fund_movements_mint;
+DROP INDEX IF EXISTS idx_fund_movements_reference;
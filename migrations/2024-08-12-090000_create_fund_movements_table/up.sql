@@ -0,0 +1,15 @@
+-- Create a ledger of the funds the sweeper moves out of its holdings of each mint,
+-- so that the invariants account for funds already withdrawn or converted.
+-- `amount` is positive for funds moved out and negative for funds moved in, e.g.
+-- the proceeds of a conversion; `reference` is the tx hash or order id moving them
+CREATE TABLE fund_movements (
+    id SERIAL PRIMARY KEY,
+    kind TEXT NOT NULL,
+    mint TEXT NOT NULL,
+    amount NUMERIC NOT NULL,
+    reference TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_fund_movements_mint ON fund_movements(mint);
+CREATE INDEX idx_fund_movements_reference ON fund_movements(reference);
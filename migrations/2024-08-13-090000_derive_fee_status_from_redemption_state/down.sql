@@ -0,0 +1,18 @@
+-- Stop holding the legacy status columns and the dual-written status to the
+-- redemption state, and restore the state's former names
+ALTER TABLE fees
+    DROP CONSTRAINT fees_status_state_check,
+    DROP CONSTRAINT fees_abandoned_state_check,
+    DROP CONSTRAINT fees_dead_lettered_state_check,
+    DROP CONSTRAINT fees_redeemed_state_check,
+    DROP CONSTRAINT fees_redemption_state_check;
+
+UPDATE fees SET redemption_state = CASE redemption_state
+    WHEN 'exhausted' THEN 'quarantined'
+    WHEN 'abandoned' THEN CASE WHEN redemption_attempts > 0 THEN 'failed' ELSE 'indexed' END
+    ELSE redemption_state
+END;
+
+ALTER TABLE fees ADD CONSTRAINT fees_redemption_state_check CHECK (
+    redemption_state IN ('indexed', 'eligible', 'in_flight', 'redeemed', 'failed', 'quarantined')
+);
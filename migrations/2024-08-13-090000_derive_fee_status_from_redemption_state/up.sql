@@ -0,0 +1,54 @@
+-- Make the redemption state the one source of truth of a fee's redemption
+-- status. Fees that failed too many redemptions are `exhausted` rather than
+-- `quarantined`, so as not to be confused with quarantined mints, and dust
+-- fees abandoned under the expiration policy get a state of their own
+ALTER TABLE fees DROP CONSTRAINT fees_redemption_state_check;
+
+-- Reconcile each fee's state with its legacy status columns, then derive the
+-- legacy columns and the dual-written status from the state
+UPDATE fees SET redemption_state = CASE
+    WHEN redeemed THEN 'redeemed'
+    WHEN abandoned_at IS NOT NULL THEN 'abandoned'
+    WHEN redemption_state = 'quarantined' THEN 'exhausted'
+    ELSE redemption_state
+END;
+
+UPDATE fees SET
+    redeemed = redemption_state = 'redeemed',
+    dead_lettered_at = CASE
+        WHEN redemption_state = 'exhausted' THEN COALESCE(dead_lettered_at, redemption_state_changed_at)
+    END,
+    abandoned_at = CASE WHEN redemption_state = 'abandoned' THEN abandoned_at END,
+    status = CASE redemption_state
+        WHEN 'in_flight' THEN 'redeeming'
+        WHEN 'redeemed' THEN 'redeemed'
+        WHEN 'exhausted' THEN 'dead_lettered'
+        WHEN 'abandoned' THEN 'abandoned'
+        ELSE 'open'
+    END;
+
+-- Hold the derived columns to the state
+ALTER TABLE fees
+    ADD CONSTRAINT fees_redemption_state_check CHECK (
+        redemption_state IN (
+            'indexed', 'eligible', 'in_flight', 'redeemed', 'failed', 'exhausted', 'abandoned'
+        )
+    ),
+    ADD CONSTRAINT fees_redeemed_state_check CHECK (
+        redeemed = (redemption_state = 'redeemed')
+    ),
+    ADD CONSTRAINT fees_dead_lettered_state_check CHECK (
+        (dead_lettered_at IS NOT NULL) = (redemption_state = 'exhausted')
+    ),
+    ADD CONSTRAINT fees_abandoned_state_check CHECK (
+        (abandoned_at IS NOT NULL) = (redemption_state = 'abandoned')
+    ),
+    ADD CONSTRAINT fees_status_state_check CHECK (
+        status = CASE redemption_state
+            WHEN 'in_flight' THEN 'redeeming'
+            WHEN 'redeemed' THEN 'redeemed'
+            WHEN 'exhausted' THEN 'dead_lettered'
+            WHEN 'abandoned' THEN 'abandoned'
+            ELSE 'open'
+        END
+    );
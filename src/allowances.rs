@@ -0,0 +1,113 @@
+//! Management of ERC-20 allowances granted by the sweeper's submission key,
+//! e.g. to the router of a swap aggregator
+
+use std::sync::Arc;
+
+use clap::ValueEnum;
+use ethers::types::{Address, TransactionReceipt, U256};
+use tracing::info;
+
+use crate::erc20::{Erc20, SignerClient};
+use crate::error::FeeSweeperError;
+use crate::gas::FeeEstimator;
+
+/// The amount approved when an allowance is insufficient
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ApprovalMode {
+    /// Approve exactly the amount required
+    #[default]
+    Exact,
+    /// Approve the maximum amount, so that later spends need no approval
+    Max,
+}
+
+/// Checks, grants, and revokes ERC-20 allowances held by the submission key
+pub struct AllowanceManager {
+    /// The client used to query and submit transactions
+    client: Arc<SignerClient>,
+    /// The amount to approve when an allowance is insufficient
+    mode: ApprovalMode,
+    /// The estimator used to price approval transactions
+    fees: FeeEstimator,
+}
+
+impl AllowanceManager {
+    /// Constructor
+    pub fn new(client: Arc<SignerClient>, mode: ApprovalMode, fees: FeeEstimator) -> Self {
+        Self { client, mode, fees }
+    }
+
+    /// Get the allowance the submission key has granted to a spender
+    pub async fn allowance(
+        &self,
+        token: Address,
+        spender: Address,
+    ) -> Result<U256, FeeSweeperError> {
+        let owner = self.client.address();
+        Erc20::new(token, self.client.clone())
+            .allowance(owner, spender)
+            .call()
+            .await
+            .map_err(FeeSweeperError::rpc("failed to query allowance"))
+    }
+
+    /// Ensure a spender may spend at least `amount` of a token
+    ///
+    /// Returns the receipt of the approval transaction, if one was needed
+    pub async fn ensure_allowance(
+        &self,
+        token: Address,
+        spender: Address,
+        amount: U256,
+    ) -> Result<Option<TransactionReceipt>, FeeSweeperError> {
+        let current = self.allowance(token, spender).await?;
+        if current >= amount {
+            info!("allowance of {spender:#x} for {token:#x} is sufficient: {current}");
+            return Ok(None);
+        }
+
+        let approval = match self.mode {
+            ApprovalMode::Exact => amount,
+            ApprovalMode::Max => U256::MAX,
+        };
+        self.approve(token, spender, approval).await.map(Some)
+    }
+
+    /// Revoke a spender's allowance
+    pub async fn revoke(
+        &self,
+        token: Address,
+        spender: Address,
+    ) -> Result<TransactionReceipt, FeeSweeperError> {
+        self.approve(token, spender, U256::zero()).await
+    }
+
+    /// Set a spender's allowance, awaiting the approval transaction
+    async fn approve(
+        &self,
+        token: Address,
+        spender: Address,
+        amount: U256,
+    ) -> Result<TransactionReceipt, FeeSweeperError> {
+        info!("approving {spender:#x} to spend {amount} of {token:#x}");
+        let contract = Erc20::new(token, self.client.clone());
+        let call = self.fees.apply(contract.approve(spender, amount)).await?;
+        let pending = call
+            .send()
+            .await
+            .map_err(FeeSweeperError::rpc("failed to send approval"))?;
+
+        let receipt = pending
+            .await
+            .map_err(FeeSweeperError::rpc("failed to await approval"))?
+            .ok_or_else(|| FeeSweeperError::Rpc("approval transaction dropped".to_string()))?;
+        if receipt.status != Some(1u64.into()) {
+            return Err(FeeSweeperError::Rpc(format!(
+                "approval reverted: {:#x}",
+                receipt.transaction_hash
+            )));
+        }
+
+        Ok(receipt)
+    }
+}
@@ -0,0 +1,352 @@
+//! The authenticated admin API, through which operators act on the sweeper
+//!
+//! Callers authenticate with a bearer token, each token identifying a caller.
+//! Every request, authenticated or not, is recorded in the admin access log
+//! with its caller, route, parameters, and result; a request whose access
+//! cannot be recorded fails, so that no unlogged action goes unnoticed
+
+use std::{collections::HashMap, str::FromStr, sync::Arc};
+
+use diesel::result::Error as DieselError;
+use diesel::{Connection, PgConnection};
+use serde_json::{json, Value};
+use tracing::error;
+use warp::{
+    http::StatusCode,
+    reply::{self, Reply, Response},
+};
+
+use super::ApiState;
+use crate::db::{
+    abandonment::restore_fee,
+    access_log::record_admin_access,
+    fees::get_fee,
+    models::NewAdminAccess,
+    quarantine::{get_quarantines, release_mint},
+    queue::{get_redemption_queue, pin_fee, push_back_fee, unpin_fee, MAX_QUEUE_LISTING},
+};
+use crate::error::FeeSweeperError;
+
+/// The prefix of a bearer token in the `Authorization` header
+const BEARER_PREFIX: &str = "Bearer ";
+/// The caller recorded for requests without a valid token
+const UNAUTHENTICATED_CALLER: &str = "unauthenticated";
+
+/// A token granting a caller access to the admin API
+///
+/// Parsed from a string of the form `<caller>=<token>`
+#[derive(Clone, Debug)]
+pub struct AdminToken {
+    /// The identity of the caller the token belongs to
+    pub caller: String,
+    /// The bearer token
+    pub token: String,
+}
+
+impl FromStr for AdminToken {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (caller, token) = s
+            .split_once('=')
+            .ok_or_else(|| "expected <caller>=<token>".to_string())?;
+        if token.trim().is_empty() {
+            return Err(format!("empty token for {caller}"));
+        }
+
+        Ok(AdminToken {
+            caller: caller.trim().to_string(),
+            token: token.trim().to_string(),
+        })
+    }
+}
+
+/// An action requested through the admin API
+#[derive(Clone, Debug)]
+pub enum AdminAction {
+    /// List the quarantined mints
+    ListQuarantines,
+    /// Release a quarantined mint
+    ReleaseMint {
+        /// The mint to release
+        mint: String,
+    },
+    /// Get a fee and where it stands in redemption
+    GetFee {
+        /// The tx hash of the fee
+        tx: String,
+    },
+    /// Restore a fee abandoned by the dust expiration policy
+    RestoreFee {
+        /// The tx hash of the fee
+        tx: String,
+    },
+    /// List the fees at the front of the redemption queue, with their scores
+    ListQueue,
+    /// Pin a fee to the front of the redemption queue
+    PinFee {
+        /// The tx hash of the fee
+        tx: String,
+    },
+    /// Push a fee to the back of the redemption queue
+    PushBackFee {
+        /// The tx hash of the fee
+        tx: String,
+    },
+    /// Return a pinned or pushed back fee to its place by value in the queue
+    UnpinFee {
+        /// The tx hash of the fee
+        tx: String,
+    },
+}
+
+impl AdminAction {
+    /// The HTTP method of the action's route
+    fn method(&self) -> &'static str {
+        match self {
+            AdminAction::ListQuarantines | AdminAction::GetFee { .. } | AdminAction::ListQueue => {
+                "GET"
+            }
+            AdminAction::ReleaseMint { .. }
+            | AdminAction::RestoreFee { .. }
+            | AdminAction::PinFee { .. }
+            | AdminAction::PushBackFee { .. }
+            | AdminAction::UnpinFee { .. } => "POST",
+        }
+    }
+
+    /// The action's route, without its parameters
+    fn route(&self) -> &'static str {
+        match self {
+            AdminAction::ListQuarantines => "/v0/admin/quarantines",
+            AdminAction::ReleaseMint { .. } => "/v0/admin/quarantines/:mint/release",
+            AdminAction::GetFee { .. } => "/v0/admin/fees/:tx",
+            AdminAction::RestoreFee { .. } => "/v0/admin/fees/:tx/restore",
+            AdminAction::ListQueue => "/v0/admin/queue",
+            AdminAction::PinFee { .. } => "/v0/admin/queue/:tx/pin",
+            AdminAction::PushBackFee { .. } => "/v0/admin/queue/:tx/push-back",
+            AdminAction::UnpinFee { .. } => "/v0/admin/queue/:tx/unpin",
+        }
+    }
+
+    /// The action's parameters
+    fn params(&self) -> Value {
+        match self {
+            AdminAction::ListQuarantines | AdminAction::ListQueue => json!({}),
+            AdminAction::ReleaseMint { mint } => json!({ "mint": mint }),
+            AdminAction::GetFee { tx }
+            | AdminAction::RestoreFee { tx }
+            | AdminAction::PinFee { tx }
+            | AdminAction::PushBackFee { tx }
+            | AdminAction::UnpinFee { tx } => json!({ "tx": tx }),
+        }
+    }
+
+    /// Execute the action
+    fn execute(&self, conn: &mut PgConnection) -> Result<Value, FeeSweeperError> {
+        match self {
+            AdminAction::ListQuarantines => {
+                let quarantines: Vec<Value> = get_quarantines(conn)?
+                    .into_iter()
+                    .map(|q| {
+                        json!({
+                            "mint": q.mint,
+                            "reason": q.reason,
+                            "quarantined_at": q.quarantined_at,
+                            "released_at": q.released_at,
+                        })
+                    })
+                    .collect();
+                Ok(Value::Array(quarantines))
+            }
+            AdminAction::ReleaseMint { mint } => {
+                release_mint(conn, &mint.to_lowercase())?;
+                Ok(json!({ "released": mint }))
+            }
+            AdminAction::GetFee { tx } => {
+                let fee = get_fee(conn, tx)?
+                    .ok_or_else(|| FeeSweeperError::Invalid(format!("no fee from tx {tx}")))?;
+                Ok(json!({
+                    "tx_hash": fee.tx_hash,
+                    "mint": fee.mint,
+                    "amount": fee.amount,
+                    "source": fee.source,
+                    "fee_kind": fee.fee_kind,
+                    "block_number": fee.block_number,
+                    "block_timestamp": fee.block_timestamp,
+                    "usd_value": fee.usd_value,
+                    "relayer": fee.relayer,
+                    "redeemed": fee.redeemed,
+                    "redemption_state": fee.redemption_state,
+                    "redemptions_started": fee.redemptions_started,
+                    "failed_redemptions": fee.redemption_attempts,
+                    "last_attempted_at": fee.last_attempted_at,
+                    "last_redemption_error": fee.last_redemption_error,
+                    "next_redemption_at": fee.next_redemption_at,
+                    "dead_lettered_at": fee.dead_lettered_at,
+                    "abandoned_at": fee.abandoned_at,
+                    "queue_priority": fee.queue_priority,
+                }))
+            }
+            AdminAction::RestoreFee { tx } => {
+                restore_fee(conn, tx)?;
+                Ok(json!({ "restored": tx }))
+            }
+            AdminAction::ListQueue => {
+                let queue: Vec<Value> = get_redemption_queue(conn, MAX_QUEUE_LISTING)?
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, fee)| {
+                        json!({
+                            "position": i + 1,
+                            "tx_hash": fee.tx_hash,
+                            "mint": fee.mint,
+                            "amount": fee.amount,
+                            "source": fee.source,
+                            "fee_kind": fee.fee_kind,
+                            "score": fee.usd_value,
+                            "queue_priority": fee.queue_priority,
+                            "next_redemption_at": fee.next_redemption_at,
+                        })
+                    })
+                    .collect();
+                Ok(Value::Array(queue))
+            }
+            AdminAction::PinFee { tx } => {
+                let priority = pin_fee(conn, tx)?;
+                Ok(json!({ "pinned": tx, "queue_priority": priority }))
+            }
+            AdminAction::PushBackFee { tx } => {
+                let priority = push_back_fee(conn, tx)?;
+                Ok(json!({ "pushed_back": tx, "queue_priority": priority }))
+            }
+            AdminAction::UnpinFee { tx } => {
+                unpin_fee(conn, tx)?;
+                Ok(json!({ "unpinned": tx }))
+            }
+        }
+    }
+}
+
+/// Handle an admin API request, recording it in the access log
+pub async fn handle_admin(
+    state: Arc<ApiState>,
+    authorization: Option<String>,
+    action: AdminAction,
+) -> Result<Response, warp::Rejection> {
+    let caller = authorization
+        .as_deref()
+        .and_then(|header| header.strip_prefix(BEARER_PREFIX))
+        .and_then(|token| find_caller(&state.admin_tokens, token.trim()));
+
+    let res = state
+        .with_conn(move |conn| {
+            let caller = match caller {
+                Some(caller) => caller,
+                None => {
+                    let status = StatusCode::UNAUTHORIZED;
+                    let e = FeeSweeperError::Invalid("unauthorized".to_string());
+                    let access = new_access(UNAUTHENTICATED_CALLER, &action, status, Some(&e));
+                    record_admin_access(conn, access)?;
+                    return Ok((status, Err(e)));
+                }
+            };
+
+            // Apply the action and record its access together, so that an
+            // action is never committed without its access log entry
+            let committed = conn.transaction(|conn| {
+                let body = action.execute(conn).map_err(AdminTxError::Action)?;
+                let access = new_access(&caller, &action, StatusCode::OK, None);
+                record_admin_access(conn, access).map_err(AdminTxError::Record)?;
+                Ok(body)
+            });
+
+            match committed {
+                Ok(body) => Ok((StatusCode::OK, Ok(body))),
+                Err(AdminTxError::Action(e)) => {
+                    let status = match e {
+                        FeeSweeperError::Invalid(_) => StatusCode::BAD_REQUEST,
+                        _ => StatusCode::INTERNAL_SERVER_ERROR,
+                    };
+                    record_admin_access(conn, new_access(&caller, &action, status, Some(&e)))?;
+                    Ok((status, Err(e)))
+                }
+                Err(AdminTxError::Record(e)) => Err(e),
+                Err(AdminTxError::Transaction(e)) => Err(FeeSweeperError::Db(format!(
+                    "failed to commit admin action: {e}"
+                ))),
+            }
+        })
+        .await;
+
+    match res {
+        Ok((status, Ok(body))) => {
+            Ok(reply::with_status(reply::json(&body), status).into_response())
+        }
+        Ok((status, Err(e))) => {
+            let body = json!({ "error": e.to_string() });
+            Ok(reply::with_status(reply::json(&body), status).into_response())
+        }
+        Err(e) => {
+            error!("failed to serve admin request: {e}");
+            Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response())
+        }
+    }
+}
+
+/// The failure of an admin action's transaction
+enum AdminTxError {
+    /// The action itself failed
+    Action(FeeSweeperError),
+    /// The action's access could not be recorded
+    Record(FeeSweeperError),
+    /// The transaction failed
+    Transaction(DieselError),
+}
+
+impl From<DieselError> for AdminTxError {
+    fn from(e: DieselError) -> Self {
+        AdminTxError::Transaction(e)
+    }
+}
+
+/// Build the access log entry of a request
+fn new_access(
+    caller: &str,
+    action: &AdminAction,
+    status: StatusCode,
+    error: Option<&FeeSweeperError>,
+) -> NewAdminAccess {
+    NewAdminAccess {
+        caller: caller.to_string(),
+        method: action.method().to_string(),
+        route: action.route().to_string(),
+        params: action.params().to_string(),
+        status: status.as_u16() as i32,
+        result: error.map_or_else(|| "ok".to_string(), |e| e.to_string()),
+    }
+}
+
+/// Find the caller a bearer token belongs to
+///
+/// Every token is compared in constant time, so that response timing leaks
+/// neither which token nor how much of one a guess matched
+fn find_caller(tokens: &HashMap<String, String>, presented: &str) -> Option<String> {
+    let mut caller = None;
+    for (token, token_caller) in tokens {
+        if constant_time_eq(token.as_bytes(), presented.as_bytes()) {
+            caller = Some(token_caller.clone());
+        }
+    }
+
+    caller
+}
+
+/// Compare two byte strings in time independent of where they differ
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
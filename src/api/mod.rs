@@ -0,0 +1,157 @@
+//! The HTTP API served by the sweeper
+//!
+//! Every route reads the database through a fresh blocking connection on the
+//! blocking thread pool, as the sweeper's database access is synchronous
+
+pub mod admin;
+pub mod public_stats;
+
+use std::{collections::HashMap, net::SocketAddr, sync::Arc, time::Duration};
+
+use chrono::Utc;
+use diesel::{Connection, PgConnection};
+use tracing::{error, info};
+use warp::Filter;
+
+use self::admin::{AdminAction, AdminToken};
+use self::public_stats::PublicStatsCache;
+use crate::db::access_log::prune_admin_access_log;
+use crate::error::FeeSweeperError;
+
+/// The interval at which expired admin access log entries are pruned
+const ACCESS_LOG_PRUNE_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The shared state of the API's routes
+pub struct ApiState {
+    /// The database url
+    db_url: String,
+    /// The cached public stats
+    public_stats: PublicStatsCache,
+    /// The callers of the admin API, keyed by their tokens
+    admin_tokens: HashMap<String, String>,
+    /// How long admin access log entries are kept
+    access_log_retention: Duration,
+}
+
+impl ApiState {
+    /// Create the API state
+    pub fn new(
+        db_url: String,
+        public_stats_ttl: Duration,
+        admin_tokens: Vec<AdminToken>,
+        access_log_retention: Duration,
+    ) -> Self {
+        let admin_tokens = admin_tokens
+            .into_iter()
+            .map(|token| (token.token, token.caller))
+            .collect();
+
+        Self {
+            db_url,
+            public_stats: PublicStatsCache::new(public_stats_ttl),
+            admin_tokens,
+            access_log_retention,
+        }
+    }
+
+    /// Prune the admin access log entries older than the retention period
+    async fn prune_access_log(&self) -> Result<(), FeeSweeperError> {
+        let retention = chrono::Duration::from_std(self.access_log_retention)
+            .map_err(FeeSweeperError::config("invalid retention"))?;
+        let cutoff = Utc::now().naive_utc() - retention;
+        let pruned = self
+            .with_conn(move |conn| prune_admin_access_log(conn, cutoff))
+            .await?;
+
+        info!("pruned {pruned} admin access log entries before {cutoff}");
+        Ok(())
+    }
+
+    /// Run a query on a fresh DB connection on the blocking thread pool
+    pub(crate) async fn with_conn<T, F>(&self, f: F) -> Result<T, FeeSweeperError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut PgConnection) -> Result<T, FeeSweeperError> + Send + 'static,
+    {
+        let db_url = self.db_url.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut conn = PgConnection::establish(&db_url)
+                .map_err(FeeSweeperError::db("failed to connect to db"))?;
+            f(&mut conn)
+        })
+        .await
+        .map_err(FeeSweeperError::other("db task panicked"))?
+    }
+}
+
+/// Serve the API on the given port until the process exits
+pub async fn serve(state: ApiState, port: u16) -> Result<(), FeeSweeperError> {
+    let state = Arc::new(state);
+    let pruner_state = state.clone();
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(ACCESS_LOG_PRUNE_INTERVAL);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = pruner_state.prune_access_log().await {
+                error!("failed to prune admin access log: {e}");
+            }
+        }
+    });
+    let with_state = warp::any().map(move || state.clone());
+
+    let public_stats = warp::path!("v0" / "public" / "stats")
+        .and(warp::get())
+        .and(with_state.clone())
+        .and_then(public_stats::handle_public_stats);
+
+    // Admin routes
+    let list_quarantines = warp::path!("v0" / "admin" / "quarantines")
+        .and(warp::get())
+        .map(|| AdminAction::ListQuarantines);
+    let release_mint = warp::path!("v0" / "admin" / "quarantines" / String / "release")
+        .and(warp::post())
+        .map(|mint| AdminAction::ReleaseMint { mint });
+    let get_fee = warp::path!("v0" / "admin" / "fees" / String)
+        .and(warp::get())
+        .map(|tx| AdminAction::GetFee { tx });
+    let restore_fee = warp::path!("v0" / "admin" / "fees" / String / "restore")
+        .and(warp::post())
+        .map(|tx| AdminAction::RestoreFee { tx });
+    let list_queue = warp::path!("v0" / "admin" / "queue")
+        .and(warp::get())
+        .map(|| AdminAction::ListQueue);
+    let pin_fee = warp::path!("v0" / "admin" / "queue" / String / "pin")
+        .and(warp::post())
+        .map(|tx| AdminAction::PinFee { tx });
+    let push_back_fee = warp::path!("v0" / "admin" / "queue" / String / "push-back")
+        .and(warp::post())
+        .map(|tx| AdminAction::PushBackFee { tx });
+    let unpin_fee = warp::path!("v0" / "admin" / "queue" / String / "unpin")
+        .and(warp::post())
+        .map(|tx| AdminAction::UnpinFee { tx });
+    let admin = with_state
+        .and(warp::header::optional::<String>("authorization"))
+        .and(
+            list_quarantines
+                .or(release_mint)
+                .unify()
+                .or(get_fee)
+                .unify()
+                .or(restore_fee)
+                .unify()
+                .or(list_queue)
+                .unify()
+                .or(pin_fee)
+                .unify()
+                .or(push_back_fee)
+                .unify()
+                .or(unpin_fee)
+                .unify(),
+        )
+        .and_then(admin::handle_admin);
+
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    info!("serving api on {addr}");
+    warp::serve(public_stats.or(admin)).run(addr).await;
+    Ok(())
+}
@@ -0,0 +1,135 @@
+//! An unauthenticated endpoint serving aggregate, non-sensitive stats for the
+//! public protocol dashboard
+//!
+//! Only totals are exposed: the number and USD value of fees redeemed, all
+//! time and by month of redemption. The stats are cached in memory and served
+//! with a matching `Cache-Control` header, so the dashboard and any CDN in
+//! front of it may poll freely without loading the database
+
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use bigdecimal::BigDecimal;
+use chrono::NaiveDateTime;
+use diesel::{
+    sql_types::{BigInt, Nullable, Numeric, Timestamp},
+    PgConnection, QueryableByName, RunQueryDsl,
+};
+use serde::Serialize;
+use tracing::error;
+use warp::{
+    http::{header::CACHE_CONTROL, StatusCode},
+    reply::{self, Reply, Response},
+};
+
+use super::ApiState;
+use crate::error::FeeSweeperError;
+
+/// The redeemed fees by month of redemption
+///
+/// The month is that of the fee's redemption status change; redemptions made
+/// before status history was kept are attributed to the month it began
+const MONTHLY_REDEMPTIONS_QUERY: &str = "\
+    SELECT date_trunc('month', changes.changed_at) AS month, \
+        COUNT(*) AS fees_redeemed, \
+        SUM(fees.usd_value) AS usd_value \
+    FROM fee_status_changes changes \
+    JOIN fees ON fees.tx_hash = changes.tx_hash \
+    WHERE changes.status = 'redeemed' \
+    GROUP BY month \
+    ORDER BY month";
+
+/// The public stats served to the dashboard
+#[derive(Clone, Serialize)]
+pub struct PublicStats {
+    /// The number of fees redeemed all time
+    pub total_fees_redeemed: i64,
+    /// The USD value of fees redeemed all time
+    pub total_usd_redeemed: BigDecimal,
+    /// The redemptions of each month, oldest first
+    pub monthly: Vec<MonthlyRedemptions>,
+}
+
+/// The fees redeemed in a month
+#[derive(Clone, Serialize, QueryableByName)]
+pub struct MonthlyRedemptions {
+    /// The start of the month
+    #[diesel(sql_type = Timestamp)]
+    pub month: NaiveDateTime,
+    /// The number of fees redeemed in the month
+    #[diesel(sql_type = BigInt)]
+    pub fees_redeemed: i64,
+    /// The USD value of fees redeemed in the month, of those with a valuation
+    #[diesel(sql_type = Nullable<Numeric>)]
+    pub usd_value: Option<BigDecimal>,
+}
+
+/// The public stats, cached for a fixed time to live
+pub struct PublicStatsCache {
+    /// How long computed stats are served for
+    ttl: Duration,
+    /// The most recently computed stats and when they were computed
+    entry: Mutex<Option<(Instant, Arc<PublicStats>)>>,
+}
+
+impl PublicStatsCache {
+    /// Create an empty cache
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entry: Mutex::new(None),
+        }
+    }
+
+    /// Get the cached stats if they are fresh
+    fn get(&self) -> Option<Arc<PublicStats>> {
+        let entry = self.entry.lock().unwrap();
+        entry
+            .as_ref()
+            .filter(|(computed_at, _)| computed_at.elapsed() < self.ttl)
+            .map(|(_, stats)| stats.clone())
+    }
+
+    /// Replace the cached stats
+    fn set(&self, stats: Arc<PublicStats>) {
+        *self.entry.lock().unwrap() = Some((Instant::now(), stats));
+    }
+}
+
+/// Handle a request for the public stats
+pub async fn handle_public_stats(state: Arc<ApiState>) -> Result<Response, warp::Rejection> {
+    let stats = match state.public_stats.get() {
+        Some(stats) => stats,
+        None => match state.with_conn(query_public_stats).await {
+            Ok(stats) => {
+                let stats = Arc::new(stats);
+                state.public_stats.set(stats.clone());
+                stats
+            }
+            Err(e) => {
+                error!("failed to compute public stats: {e}");
+                return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+            }
+        },
+    };
+
+    let max_age = format!("public, max-age={}", state.public_stats.ttl.as_secs());
+    Ok(reply::with_header(reply::json(stats.as_ref()), CACHE_CONTROL, max_age).into_response())
+}
+
+/// Compute the public stats from the database
+fn query_public_stats(conn: &mut PgConnection) -> Result<PublicStats, FeeSweeperError> {
+    let monthly: Vec<MonthlyRedemptions> = diesel::sql_query(MONTHLY_REDEMPTIONS_QUERY)
+        .load(conn)
+        .map_err(FeeSweeperError::db("failed to query monthly redemptions"))?;
+
+    let total_fees_redeemed = monthly.iter().map(|m| m.fees_redeemed).sum();
+    let total_usd_redeemed = monthly.iter().filter_map(|m| m.usd_value.clone()).sum();
+    Ok(PublicStats {
+        total_fees_redeemed,
+        total_usd_redeemed,
+        monthly,
+    })
+}
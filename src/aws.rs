@@ -0,0 +1,88 @@
+//! Construction of the AWS SDK config and accounting of AWS API usage
+//!
+//! Every AWS request the sweeper makes carries a configurable app name in its
+//! user agent, resources the sweeper creates are tagged with configurable cost
+//! allocation tags, and each call is counted by service and operation, so that
+//! the sweeper's AWS spend is attributable in Cost Explorer
+
+use std::str::FromStr;
+
+use aws_config::{AppName, BehaviorVersion, Region, SdkConfig};
+use metrics::counter;
+
+use crate::error::FeeSweeperError;
+use crate::telemetry::{AWS_API_CALLS_METRIC, AWS_OPERATION_LABEL, AWS_SERVICE_LABEL};
+
+/// The default app name sent with AWS requests
+pub const DEFAULT_AWS_APP_NAME: &str = "renegade-fee-sweeper";
+/// The service name of Secrets Manager, as counted in usage metrics
+pub const SECRETS_MANAGER_SERVICE: &str = "secretsmanager";
+/// The service name of S3, as counted in usage metrics
+pub const S3_SERVICE: &str = "s3";
+/// The service name of KMS, as counted in usage metrics
+pub const KMS_SERVICE: &str = "kms";
+
+/// A cost allocation tag applied to AWS resources the sweeper creates
+///
+/// Parsed from a string of the form `<key>=<value>`
+#[derive(Clone, Debug)]
+pub struct CostTag {
+    /// The tag key
+    pub key: String,
+    /// The tag value
+    pub value: String,
+}
+
+impl FromStr for CostTag {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (key, value) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected <key>=<value>: {s}"))?;
+
+        Ok(CostTag {
+            key: key.trim().to_string(),
+            value: value.trim().to_string(),
+        })
+    }
+}
+
+/// The AWS config along with the attribution applied to AWS usage
+#[derive(Clone, Debug)]
+pub struct AwsContext {
+    /// The SDK config used to build clients
+    pub config: SdkConfig,
+    /// The cost allocation tags applied to created resources
+    pub cost_tags: Vec<CostTag>,
+}
+
+impl AwsContext {
+    /// Load the AWS config for the given region, identifying requests by the
+    /// given app name
+    pub async fn load(
+        region: &'static str,
+        app_name: String,
+        cost_tags: Vec<CostTag>,
+    ) -> Result<Self, FeeSweeperError> {
+        let app_name =
+            AppName::new(app_name).map_err(FeeSweeperError::config("invalid app name"))?;
+        let config = aws_config::defaults(BehaviorVersion::latest())
+            .region(Region::new(region))
+            .app_name(app_name)
+            .load()
+            .await;
+
+        Ok(Self { config, cost_tags })
+    }
+}
+
+/// Count a call to an AWS API
+pub fn record_aws_call(service: &'static str, operation: &'static str) {
+    counter!(
+        AWS_API_CALLS_METRIC,
+        AWS_SERVICE_LABEL => service,
+        AWS_OPERATION_LABEL => operation
+    )
+    .increment(1);
+}
@@ -0,0 +1,146 @@
+//! Bridging of funds from Arbitrum to an L1 treasury through the canonical
+//! token bridge
+//!
+//! A withdrawal through the bridge is initiated on L2 and becomes claimable on
+//! L1 once the rollup's challenge period has elapsed. Claims are executed
+//! against the L1 outbox out of band and recorded here once they land.
+//!
+//! Sending a withdrawal and awaiting its receipt are separate steps, so that
+//! the withdrawal is recorded as initiated before its receipt is awaited; a
+//! transfer whose receipt never arrives is left initiated rather than lost
+
+use std::{str::FromStr, sync::Arc};
+
+use chrono::{Duration, NaiveDateTime};
+use ethers::middleware::Middleware;
+use ethers::providers::PendingTransaction;
+use ethers::types::{Address, Bytes, TransactionReceipt, TxHash, U256};
+use tracing::info;
+
+use self::bindings::{L2GatewayRouter, L2Token};
+use crate::erc20::SignerClient;
+use crate::error::FeeSweeperError;
+use crate::gas::FeeEstimator;
+
+/// The address of the L2 gateway router on Arbitrum One
+pub const ARBITRUM_ONE_GATEWAY_ROUTER: &str = "0x5288c571Fd7aD117beA99bF60FE0846C4E84F933";
+/// The challenge period after which an L2 -> L1 withdrawal may be claimed
+pub const CHALLENGE_PERIOD_DAYS: i64 = 7;
+
+// -------------------
+// | Transfer Status |
+// -------------------
+
+/// The status of a transfer that has been submitted on L2
+pub const BRIDGE_STATUS_INITIATED: &str = "initiated";
+/// The status of a transfer whose L2 transaction has succeeded
+pub const BRIDGE_STATUS_CONFIRMED: &str = "confirmed";
+/// The status of a transfer that has been claimed on L1
+pub const BRIDGE_STATUS_CLAIMED: &str = "claimed";
+/// The status of a transfer whose L2 transaction reverted
+pub const BRIDGE_STATUS_FAILED: &str = "failed";
+
+/// The generated contract bindings
+#[allow(missing_docs, clippy::missing_docs_in_private_items)]
+mod bindings {
+    ethers::contract::abigen!(
+        L2GatewayRouter,
+        r#"[
+            function outboundTransfer(address _l1Token, address _to, uint256 _amount, bytes _data) external payable returns (bytes)
+        ]"#
+    );
+
+    ethers::contract::abigen!(
+        L2Token,
+        r#"[
+            function l1Address() external view returns (address)
+        ]"#
+    );
+}
+
+/// A bridge transfer sent on L2, whose receipt has not yet been awaited
+pub struct SentTransfer {
+    /// The L1 counterpart of the bridged token
+    pub l1_token: Address,
+    /// The hash of the L2 transaction
+    pub tx_hash: TxHash,
+}
+
+/// Whether the L2 transaction of a transfer succeeded
+pub fn transfer_succeeded(receipt: &TransactionReceipt) -> bool {
+    receipt.status == Some(1u64.into())
+}
+
+/// A client for withdrawing tokens through the canonical bridge
+pub struct BridgeClient {
+    /// The client used to submit L2 transactions
+    client: Arc<SignerClient>,
+    /// The address of the L2 gateway router
+    gateway_router: Address,
+    /// The estimator used to price bridge transactions
+    fees: FeeEstimator,
+}
+
+impl BridgeClient {
+    /// Constructor
+    pub fn new(
+        client: Arc<SignerClient>,
+        gateway_router: &str,
+        fees: FeeEstimator,
+    ) -> Result<Self, FeeSweeperError> {
+        let gateway_router = Address::from_str(gateway_router)
+            .map_err(FeeSweeperError::rpc("invalid gateway router"))?;
+        Ok(Self {
+            client,
+            gateway_router,
+            fees,
+        })
+    }
+
+    /// Send a withdrawal of the given amount of an L2 token to an L1
+    /// destination, without awaiting its receipt
+    pub async fn send_transfer(
+        &self,
+        mint: Address,
+        amount: U256,
+        destination: Address,
+    ) -> Result<SentTransfer, FeeSweeperError> {
+        let l1_token = L2Token::new(mint, self.client.clone())
+            .l1_address()
+            .call()
+            .await
+            .map_err(FeeSweeperError::rpc("failed to query l1 token"))?;
+
+        info!("bridging {amount} of {mint:#x} (l1: {l1_token:#x}) to {destination:#x}");
+        let router = L2GatewayRouter::new(self.gateway_router, self.client.clone());
+        let call = router.outbound_transfer(l1_token, destination, amount, Bytes::default());
+        let call = self.fees.apply(call).await?;
+        let pending = call
+            .send()
+            .await
+            .map_err(FeeSweeperError::rpc("failed to send bridge tx"))?;
+
+        Ok(SentTransfer {
+            l1_token,
+            tx_hash: pending.tx_hash(),
+        })
+    }
+
+    /// Await the receipt of a sent withdrawal
+    pub async fn await_transfer(
+        &self,
+        tx_hash: TxHash,
+    ) -> Result<TransactionReceipt, FeeSweeperError> {
+        PendingTransaction::new(tx_hash, self.client.provider())
+            .await
+            .map_err(FeeSweeperError::rpc("failed to await bridge tx"))?
+            .ok_or_else(|| {
+                FeeSweeperError::Rpc(format!("bridge transaction dropped: {tx_hash:#x}"))
+            })
+    }
+}
+
+/// The time after which a transfer initiated at the given time may be claimed
+pub fn claimable_at(initiated_at: NaiveDateTime) -> NaiveDateTime {
+    initiated_at + Duration::days(CHALLENGE_PERIOD_DAYS)
+}
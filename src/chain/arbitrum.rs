@@ -0,0 +1,109 @@
+//! The Arbitrum adapter
+//!
+//! Arbitrum's sequencer ignores priority fees and prices gas itself, and a
+//! block's L1 finality is reported by the Node Interface as the number of L1
+//! confirmations of the batch containing it
+
+use arbitrum_client::client::ArbitrumClient;
+use async_trait::async_trait;
+use ethers::middleware::Middleware;
+use ethers::types::{Address, BlockNumber};
+
+use self::bindings::NodeInterface;
+use super::ChainAdapter;
+use crate::error::FeeSweeperError;
+use crate::gas::FeeStrategy;
+
+/// The chain ids of Arbitrum One and Arbitrum Sepolia
+pub const CHAIN_IDS: [u64; 2] = [42161, 421614];
+/// The default fee strategy on Arbitrum
+const DEFAULT_FEE_STRATEGY: FeeStrategy = FeeStrategy::Provider;
+
+/// The address of the Arbitrum Node Interface precompile, `0x...c8`
+const NODE_INTERFACE_ADDRESS: u64 = 0xc8;
+
+/// The generated contract bindings
+#[allow(missing_docs, clippy::missing_docs_in_private_items)]
+mod bindings {
+    ethers::contract::abigen!(
+        NodeInterface,
+        r#"[
+            function getL1Confirmations(bytes32 blockHash) external view returns (uint64)
+        ]"#
+    );
+}
+
+/// The Arbitrum adapter
+pub struct ArbitrumAdapter;
+
+#[async_trait]
+impl ChainAdapter for ArbitrumAdapter {
+    fn default_fee_strategy(&self) -> FeeStrategy {
+        DEFAULT_FEE_STRATEGY
+    }
+
+    async fn get_l1_finalized_block(
+        &self,
+        client: &ArbitrumClient,
+        from_block: u64,
+        latest: u64,
+        min_l1_confirmations: u64,
+    ) -> Result<Option<u64>, FeeSweeperError> {
+        get_l1_finalized_block(client, from_block, latest, min_l1_confirmations).await
+    }
+}
+
+/// Find the most recent block in `[from_block, latest]` whose batch has the
+/// required number of L1 confirmations
+///
+/// L1 confirmations never increase with block number, so the range is binary
+/// searched
+async fn get_l1_finalized_block(
+    client: &ArbitrumClient,
+    from_block: u64,
+    latest: u64,
+    min_confirmations: u64,
+) -> Result<Option<u64>, FeeSweeperError> {
+    let (mut lo, mut hi) = (from_block, latest);
+    if !is_l1_final(client, lo, min_confirmations).await? {
+        return Ok(None);
+    }
+
+    // Invariant: `lo` is final
+    while lo < hi {
+        let mid = lo + (hi - lo + 1) / 2;
+        if is_l1_final(client, mid, min_confirmations).await? {
+            lo = mid;
+        } else {
+            hi = mid - 1;
+        }
+    }
+
+    Ok(Some(lo))
+}
+
+/// Whether the batch containing a block has the required number of L1
+/// confirmations
+async fn is_l1_final(
+    client: &ArbitrumClient,
+    block: u64,
+    min_confirmations: u64,
+) -> Result<bool, FeeSweeperError> {
+    let client = client.get_darkpool_client().client();
+    let block_hash = client
+        .get_block(BlockNumber::Number(block.into()))
+        .await
+        .map_err(FeeSweeperError::rpc("failed to query block"))?
+        .and_then(|block| block.hash)
+        .ok_or_else(|| FeeSweeperError::Rpc(format!("block {block} not found")))?;
+
+    let address = Address::from_low_u64_be(NODE_INTERFACE_ADDRESS);
+    let node_interface = NodeInterface::new(address, client);
+    let confirmations = node_interface
+        .get_l1_confirmations(block_hash.into())
+        .call()
+        .await
+        .map_err(FeeSweeperError::rpc("failed to query L1 confirmations"))?;
+
+    Ok(confirmations >= min_confirmations)
+}
@@ -0,0 +1,41 @@
+//! The Base adapter
+//!
+//! Base prices gas with a conventional EIP-1559 market, so priority fees are
+//! sampled from recent blocks. As an OP Stack rollup its node tracks which L2
+//! blocks have been derived from finalized L1 data, exposed through the
+//! `finalized` block tag
+
+use arbitrum_client::client::ArbitrumClient;
+use async_trait::async_trait;
+
+use super::generic::get_finalized_tag_block;
+use super::ChainAdapter;
+use crate::error::FeeSweeperError;
+use crate::gas::FeeStrategy;
+
+/// The chain ids of Base and Base Sepolia
+pub const CHAIN_IDS: [u64; 2] = [8453, 84532];
+/// The default fee strategy on Base
+const DEFAULT_FEE_STRATEGY: FeeStrategy = FeeStrategy::Percentile;
+
+/// The Base adapter
+pub struct BaseAdapter;
+
+#[async_trait]
+impl ChainAdapter for BaseAdapter {
+    fn default_fee_strategy(&self) -> FeeStrategy {
+        DEFAULT_FEE_STRATEGY
+    }
+
+    /// The node only reports finality once the L1 has finalized, so no
+    /// confirmation count applies
+    async fn get_l1_finalized_block(
+        &self,
+        client: &ArbitrumClient,
+        from_block: u64,
+        latest: u64,
+        _min_l1_confirmations: u64,
+    ) -> Result<Option<u64>, FeeSweeperError> {
+        get_finalized_tag_block(client, from_block, latest).await
+    }
+}
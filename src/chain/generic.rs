@@ -0,0 +1,56 @@
+//! The generic EVM adapter, serving chains without an adapter of their own
+//!
+//! Gas is assumed to be priced by a conventional EIP-1559 market, so priority
+//! fees are sampled from recent blocks, and finality is read from the
+//! `finalized` block tag, which post-merge L1s and most rollups report
+
+use arbitrum_client::client::ArbitrumClient;
+use async_trait::async_trait;
+use ethers::middleware::Middleware;
+use ethers::types::BlockNumber;
+
+use super::ChainAdapter;
+use crate::error::FeeSweeperError;
+use crate::gas::FeeStrategy;
+
+/// The default fee strategy on a generic EVM chain
+const DEFAULT_FEE_STRATEGY: FeeStrategy = FeeStrategy::Percentile;
+
+/// The generic EVM adapter
+pub struct GenericAdapter;
+
+#[async_trait]
+impl ChainAdapter for GenericAdapter {
+    fn default_fee_strategy(&self) -> FeeStrategy {
+        DEFAULT_FEE_STRATEGY
+    }
+
+    async fn get_l1_finalized_block(
+        &self,
+        client: &ArbitrumClient,
+        from_block: u64,
+        latest: u64,
+        _min_l1_confirmations: u64,
+    ) -> Result<Option<u64>, FeeSweeperError> {
+        get_finalized_tag_block(client, from_block, latest).await
+    }
+}
+
+/// Get the most recent block in `[from_block, latest]` at or below the
+/// `finalized` block tag
+pub(crate) async fn get_finalized_tag_block(
+    client: &ArbitrumClient,
+    from_block: u64,
+    latest: u64,
+) -> Result<Option<u64>, FeeSweeperError> {
+    let finalized = client
+        .get_darkpool_client()
+        .client()
+        .get_block(BlockNumber::Finalized)
+        .await
+        .map_err(FeeSweeperError::rpc("failed to query finalized block"))?
+        .and_then(|block| block.number)
+        .map(|number| number.as_u64().min(latest));
+
+    Ok(finalized.filter(|block| *block >= from_block))
+}
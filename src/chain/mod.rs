@@ -0,0 +1,145 @@
+//! A chain-agnostic client for the darkpool on an EVM chain
+//!
+//! The darkpool contracts are the same on every EVM deployment, but chains
+//! differ in how finality is observed and how gas is priced. Those quirks are
+//! isolated behind the [`ChainAdapter`] trait, implemented once per chain;
+//! supporting a new chain means adding its adapter and a variant of
+//! [`EvmChain`]. Chains without an adapter of their own are served by the
+//! generic EVM adapter
+
+use arbitrum_client::client::ArbitrumClient;
+use async_trait::async_trait;
+use ethers::middleware::Middleware;
+
+use crate::error::FeeSweeperError;
+use crate::gas::FeeStrategy;
+
+pub mod arbitrum;
+pub mod base;
+pub mod generic;
+
+/// The behavior of the sweeper that differs between EVM chains
+#[async_trait]
+pub trait ChainAdapter: Send + Sync {
+    /// The fee estimation strategy used on the chain by default
+    fn default_fee_strategy(&self) -> FeeStrategy;
+
+    /// Get the most recent block in `[from_block, latest]` that the L1 has
+    /// finalized, if any
+    ///
+    /// `min_l1_confirmations` applies only on chains that report the L1
+    /// confirmations of a block
+    async fn get_l1_finalized_block(
+        &self,
+        client: &ArbitrumClient,
+        from_block: u64,
+        latest: u64,
+        min_l1_confirmations: u64,
+    ) -> Result<Option<u64>, FeeSweeperError>;
+}
+
+/// An EVM chain the darkpool is deployed to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EvmChain {
+    /// Arbitrum One or Arbitrum Sepolia
+    Arbitrum,
+    /// Base or Base Sepolia
+    Base,
+    /// Any other EVM chain
+    Generic,
+}
+
+impl EvmChain {
+    /// The chain with the given chain id, the generic chain if it has no
+    /// adapter of its own
+    pub fn from_chain_id(chain_id: u64) -> Self {
+        if arbitrum::CHAIN_IDS.contains(&chain_id) {
+            EvmChain::Arbitrum
+        } else if base::CHAIN_IDS.contains(&chain_id) {
+            EvmChain::Base
+        } else {
+            EvmChain::Generic
+        }
+    }
+
+    /// The adapter implementing the chain's quirks
+    pub fn adapter(&self) -> &'static dyn ChainAdapter {
+        match self {
+            EvmChain::Arbitrum => &arbitrum::ArbitrumAdapter,
+            EvmChain::Base => &base::BaseAdapter,
+            EvmChain::Generic => &generic::GenericAdapter,
+        }
+    }
+}
+
+/// A client for the darkpool on any EVM chain
+///
+/// Contract interactions, which are shared by all EVM deployments, go through
+/// the underlying darkpool client; chain-specific behavior is dispatched to
+/// the chain's adapter
+pub struct EvmDarkpoolClient {
+    /// The underlying darkpool client
+    client: ArbitrumClient,
+    /// The chain the client is connected to
+    chain: EvmChain,
+    /// The id of the chain the client is connected to
+    chain_id: u64,
+}
+
+impl EvmDarkpoolClient {
+    /// Wrap a darkpool client, resolving the chain it is connected to
+    pub async fn new(client: ArbitrumClient) -> Result<Self, FeeSweeperError> {
+        let chain_id = client
+            .chain_id()
+            .await
+            .map_err(FeeSweeperError::rpc("Error fetching chain ID"))?;
+        let chain = EvmChain::from_chain_id(chain_id);
+
+        Ok(Self {
+            client,
+            chain,
+            chain_id,
+        })
+    }
+
+    /// The underlying darkpool client, through which the darkpool contracts
+    /// are interacted with
+    pub fn darkpool(&self) -> &ArbitrumClient {
+        &self.client
+    }
+
+    /// The chain the client is connected to
+    pub fn chain(&self) -> EvmChain {
+        self.chain
+    }
+
+    /// The id of the chain the client is connected to
+    pub fn chain_id(&self) -> u64 {
+        self.chain_id
+    }
+
+    /// Get the latest block number
+    pub async fn latest_block(&self) -> Result<u64, FeeSweeperError> {
+        self.client
+            .get_darkpool_client()
+            .client()
+            .get_block_number()
+            .await
+            .map(|block| block.as_u64())
+            .map_err(FeeSweeperError::rpc("failed to query latest block"))
+    }
+
+    /// Get the most recent block in `[from_block, latest]` that the L1 has
+    /// finalized, if any
+    pub async fn get_l1_finalized_block(
+        &self,
+        from_block: u64,
+        latest: u64,
+        min_l1_confirmations: u64,
+    ) -> Result<Option<u64>, FeeSweeperError> {
+        self.chain
+            .adapter()
+            .get_l1_finalized_block(&self.client, from_block, latest, min_l1_confirmations)
+            .await
+    }
+}
@@ -0,0 +1,208 @@
+//! A typed client for the sweeper's HTTP API
+//!
+//! Lets Rust services read sweeper data without hand-rolling its HTTP types:
+//! the public redemption stats, and, given an admin token, individual fees, the
+//! redemption queue, and the mint quarantine. Admin reads are recorded in the
+//! sweeper's admin access log under the token's caller, as any admin request is
+
+use bigdecimal::BigDecimal;
+use chrono::NaiveDateTime;
+use renegade_util::raw_err_str;
+use reqwest::{Client, Response};
+use serde::{de::DeserializeOwned, Deserialize};
+
+/// The route of the public stats
+const PUBLIC_STATS_ROUTE: &str = "/v0/public/stats";
+/// The route of a fee, by its tx hash
+const FEE_ROUTE: &str = "/v0/admin/fees/:tx";
+/// The route of the mint quarantine
+const QUARANTINES_ROUTE: &str = "/v0/admin/quarantines";
+/// The route of the redemption queue
+const QUEUE_ROUTE: &str = "/v0/admin/queue";
+
+/// The aggregate redemption stats served publicly
+#[derive(Clone, Debug, Deserialize)]
+pub struct PublicStats {
+    /// The number of fees redeemed all time
+    pub total_fees_redeemed: i64,
+    /// The USD value of fees redeemed all time
+    pub total_usd_redeemed: BigDecimal,
+    /// The redemptions of each month, oldest first
+    pub monthly: Vec<MonthlyRedemptions>,
+}
+
+/// The fees redeemed in a month
+#[derive(Clone, Debug, Deserialize)]
+pub struct MonthlyRedemptions {
+    /// The start of the month
+    pub month: NaiveDateTime,
+    /// The number of fees redeemed in the month
+    pub fees_redeemed: i64,
+    /// The USD value of fees redeemed in the month, of those with a valuation
+    pub usd_value: Option<BigDecimal>,
+}
+
+/// A fee and where it stands in redemption
+#[derive(Clone, Debug, Deserialize)]
+pub struct Fee {
+    /// The hash of the tx settling the fee
+    pub tx_hash: String,
+    /// The mint of the fee
+    pub mint: String,
+    /// The amount of the fee, in raw units of the mint
+    pub amount: BigDecimal,
+    /// The type of match the fee was earned in
+    pub source: String,
+    /// The kind of the fee, by the key it was paid to
+    pub fee_kind: String,
+    /// The block in which the fee was settled, if known
+    pub block_number: Option<i64>,
+    /// The time at which the fee was settled, if known
+    pub block_timestamp: Option<NaiveDateTime>,
+    /// The USD value of the fee, if valued
+    pub usd_value: Option<BigDecimal>,
+    /// The relayer that settled the fee, if known
+    pub relayer: Option<String>,
+    /// Whether the fee has been redeemed
+    pub redeemed: bool,
+    /// The state of the fee in the redemption state machine
+    pub redemption_state: String,
+    /// The number of redemptions of the fee started
+    pub redemptions_started: i32,
+    /// The number of redemptions of the fee that failed
+    pub failed_redemptions: i32,
+    /// The time the last redemption of the fee started, if any did
+    pub last_attempted_at: Option<NaiveDateTime>,
+    /// The error the last failed redemption of the fee reported, if any
+    pub last_redemption_error: Option<String>,
+    /// The time before which a failed fee is not retried, if it is backed off
+    pub next_redemption_at: Option<NaiveDateTime>,
+    /// The time the fee was dead-lettered, if it was
+    pub dead_lettered_at: Option<NaiveDateTime>,
+    /// The time the fee was abandoned as dust, if it was
+    pub abandoned_at: Option<NaiveDateTime>,
+    /// The operator's override of the fee's place in the redemption queue:
+    /// positive if pinned to the front, negative if pushed to the back
+    pub queue_priority: i32,
+}
+
+/// A fee in the redemption queue
+#[derive(Clone, Debug, Deserialize)]
+pub struct QueuedFee {
+    /// The fee's position in the queue, from 1 at the front
+    pub position: usize,
+    /// The hash of the tx settling the fee
+    pub tx_hash: String,
+    /// The mint of the fee
+    pub mint: String,
+    /// The amount of the fee, in raw units of the mint
+    pub amount: BigDecimal,
+    /// The type of match the fee was earned in
+    pub source: String,
+    /// The kind of the fee, by the key it was paid to
+    pub fee_kind: String,
+    /// The score ordering the fee between overrides, its USD value if valued
+    pub score: Option<BigDecimal>,
+    /// The operator's override of the fee's place in the queue
+    pub queue_priority: i32,
+    /// The time before which a failed fee is not retried, if it is backed off
+    pub next_redemption_at: Option<NaiveDateTime>,
+}
+
+/// A mint quarantined as suspected spam
+#[derive(Clone, Debug, Deserialize)]
+pub struct MintQuarantine {
+    /// The quarantined mint
+    pub mint: String,
+    /// Why the mint was quarantined
+    pub reason: String,
+    /// The time the mint was quarantined
+    pub quarantined_at: NaiveDateTime,
+    /// The time the mint was released, if it was
+    pub released_at: Option<NaiveDateTime>,
+}
+
+/// The body of a failed admin request
+#[derive(Deserialize)]
+struct ErrorBody {
+    /// The error the request failed with
+    error: String,
+}
+
+/// A client of the sweeper's HTTP API
+#[derive(Clone, Debug)]
+pub struct SweeperClient {
+    /// The base URL of the API
+    base_url: String,
+    /// The HTTP client sending requests
+    http_client: Client,
+    /// The bearer token authenticating admin requests, if any
+    admin_token: Option<String>,
+}
+
+impl SweeperClient {
+    /// Create a client of the API served at the given base URL
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            http_client: Client::new(),
+            admin_token: None,
+        }
+    }
+
+    /// Authenticate admin requests with the given token
+    pub fn with_admin_token(mut self, token: &str) -> Self {
+        self.admin_token = Some(token.to_string());
+        self
+    }
+
+    /// Get the public redemption stats
+    pub async fn get_public_stats(&self) -> Result<PublicStats, String> {
+        self.get(PUBLIC_STATS_ROUTE).await
+    }
+
+    /// Get the fee settled in a tx
+    pub async fn get_fee(&self, tx_hash: &str) -> Result<Fee, String> {
+        self.get(&FEE_ROUTE.replace(":tx", tx_hash)).await
+    }
+
+    /// Get the fees at the front of the redemption queue, in queue order
+    pub async fn get_redemption_queue(&self) -> Result<Vec<QueuedFee>, String> {
+        self.get(QUEUE_ROUTE).await
+    }
+
+    /// List the quarantined mints, including those released
+    pub async fn list_quarantines(&self) -> Result<Vec<MintQuarantine>, String> {
+        self.get(QUARANTINES_ROUTE).await
+    }
+
+    /// Get a route of the API, authenticating the request if a token is set
+    async fn get<T: DeserializeOwned>(&self, route: &str) -> Result<T, String> {
+        let mut req = self.http_client.get(format!("{}{route}", self.base_url));
+        if let Some(token) = &self.admin_token {
+            req = req.bearer_auth(token);
+        }
+        let resp = req
+            .send()
+            .await
+            .map_err(raw_err_str!("failed to send request: {}"))?;
+
+        parse_response(resp).await
+    }
+}
+
+/// Parse a response, surfacing the error reported by a failed request
+async fn parse_response<T: DeserializeOwned>(resp: Response) -> Result<T, String> {
+    let status = resp.status();
+    if !status.is_success() {
+        let reason = match resp.json::<ErrorBody>().await {
+            Ok(body) => body.error,
+            Err(_) => status.to_string(),
+        };
+        return Err(format!("request failed ({status}): {reason}"));
+    }
+
+    resp.json()
+        .await
+        .map_err(raw_err_str!("failed to parse response: {}"))
+}
@@ -0,0 +1,144 @@
+//! The clock and randomness read by the sweeper's time-dependent logic
+//!
+//! Scheduling, retry backoff, task deadlines, and the expiration of leases,
+//! claims, and stale fees all read the time through a `Clock`, and jitter is
+//! drawn from a `SharedRng`, rather than from the system directly. In
+//! production these are the system clock and an entropy-seeded RNG; a
+//! `ManualClock` and a seeded RNG instead make timeouts, expirations, and
+//! retries deterministic, as a manual clock's sleeps advance its time at once
+//! rather than waiting on it
+
+use std::fmt::Debug;
+use std::ops::RangeInclusive;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use ethers::core::rand::{rngs::StdRng, Rng, SeedableRng};
+use futures::future::{self, BoxFuture, FutureExt};
+
+/// A clock shared by the components of a run
+pub type SharedClock = Arc<dyn Clock>;
+
+/// A source of the current time, and of waits on it
+pub trait Clock: Debug + Send + Sync {
+    /// The current wall-clock time
+    fn now(&self) -> DateTime<Utc>;
+
+    /// The current monotonic time, for measuring deadlines
+    fn instant(&self) -> Instant;
+
+    /// Wait for the given duration
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()>;
+
+    /// The current wall-clock time, as stored in the DB
+    fn naive_now(&self) -> NaiveDateTime {
+        self.now().naive_utc()
+    }
+}
+
+/// The system clock
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    fn instant(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        tokio::time::sleep(duration).boxed()
+    }
+}
+
+/// The system clock, shared
+pub fn system_clock() -> SharedClock {
+    Arc::new(SystemClock)
+}
+
+/// A clock that only moves when advanced, or slept on
+#[derive(Debug)]
+pub struct ManualClock {
+    /// The monotonic time at which the clock was created
+    start: Instant,
+    /// The wall-clock time at which the clock was created
+    start_time: DateTime<Utc>,
+    /// The time elapsed on the clock since it was created
+    elapsed: Mutex<Duration>,
+}
+
+impl ManualClock {
+    /// Create a manual clock reading the given wall-clock time
+    pub fn new(start_time: DateTime<Utc>) -> Self {
+        Self {
+            start: Instant::now(),
+            start_time,
+            elapsed: Mutex::new(Duration::ZERO),
+        }
+    }
+
+    /// Move the clock forward by the given duration
+    pub fn advance(&self, duration: Duration) {
+        *self.elapsed.lock().unwrap() += duration;
+    }
+
+    /// The time elapsed on the clock since it was created
+    fn elapsed(&self) -> Duration {
+        *self.elapsed.lock().unwrap()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Utc> {
+        // A duration too long for chrono saturates rather than panics
+        chrono::Duration::from_std(self.elapsed())
+            .ok()
+            .and_then(|elapsed| self.start_time.checked_add_signed(elapsed))
+            .unwrap_or(DateTime::<Utc>::MAX_UTC)
+    }
+
+    fn instant(&self) -> Instant {
+        self.start + self.elapsed()
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        self.advance(duration);
+        future::ready(()).boxed()
+    }
+}
+
+/// A source of randomness shared by the components of a run
+#[derive(Clone, Debug)]
+pub struct SharedRng(Arc<Mutex<StdRng>>);
+
+impl SharedRng {
+    /// Create an RNG seeded from the system's entropy
+    pub fn from_entropy() -> Self {
+        Self(Arc::new(Mutex::new(StdRng::from_entropy())))
+    }
+
+    /// Create an RNG from a fixed seed, so that its draws are reproducible
+    pub fn seeded(seed: u64) -> Self {
+        Self(Arc::new(Mutex::new(StdRng::seed_from_u64(seed))))
+    }
+
+    /// Draw a float uniformly from a range
+    pub fn gen_range(&self, range: RangeInclusive<f64>) -> f64 {
+        self.0.lock().unwrap().gen_range(range)
+    }
+
+    /// Run a function with exclusive use of the RNG, e.g. to generate a key
+    pub fn with<T>(&self, f: impl FnOnce(&mut StdRng) -> T) -> T {
+        f(&mut self.0.lock().unwrap())
+    }
+}
+
+impl Default for SharedRng {
+    fn default() -> Self {
+        Self::from_entropy()
+    }
+}
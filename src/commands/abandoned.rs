@@ -0,0 +1,66 @@
+//! Operator commands for reviewing and restoring fees abandoned by the dust
+//! expiration policy
+
+use clap::{Args, Subcommand};
+use diesel::{Connection, PgConnection};
+
+use crate::db::abandonment::{get_abandoned_fees, restore_fee};
+use crate::error::FeeSweeperError;
+
+/// The arguments to the `abandoned` command
+#[derive(Debug, Args)]
+pub struct AbandonedArgs {
+    /// The database url
+    #[clap(long, env = "DB_URL", hide_env_values = true)]
+    pub db_url: String,
+    /// The action to take
+    #[clap(subcommand)]
+    pub action: AbandonedAction,
+}
+
+/// An action on abandoned fees
+#[derive(Debug, Subcommand)]
+pub enum AbandonedAction {
+    /// List abandoned fees
+    List,
+    /// Restore an abandoned fee so that it is redeemed
+    Restore {
+        /// The tx hash of the fee to restore
+        #[clap(long)]
+        tx: String,
+    },
+}
+
+/// Run the `abandoned` command
+pub fn run_abandoned(args: AbandonedArgs) -> Result<(), FeeSweeperError> {
+    let mut conn = PgConnection::establish(&args.db_url)
+        .map_err(FeeSweeperError::db("failed to connect to db"))?;
+    match args.action {
+        AbandonedAction::List => {
+            println!(
+                "{:<66}  {:<42}  {:>32}  {:>12}  ABANDONED",
+                "TX", "MINT", "AMOUNT", "USD VALUE"
+            );
+            for fee in get_abandoned_fees(&mut conn)? {
+                let value = fee
+                    .usd_value
+                    .map(|v| v.round(2 /* round_digits */).to_string());
+                let abandoned = fee.abandoned_at.map(|t| t.format("%Y-%m-%d %H:%M:%S"));
+                println!(
+                    "{:<66}  {:<42}  {:>32}  {:>12}  {}",
+                    fee.tx_hash,
+                    fee.mint,
+                    fee.amount,
+                    value.unwrap_or_default(),
+                    abandoned.map(|t| t.to_string()).unwrap_or_default()
+                );
+            }
+            Ok(())
+        }
+        AbandonedAction::Restore { tx } => {
+            restore_fee(&mut conn, &tx)?;
+            println!("restored fee from tx {tx}");
+            Ok(())
+        }
+    }
+}
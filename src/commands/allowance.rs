@@ -0,0 +1,128 @@
+//! Operator commands for inspecting and managing ERC-20 allowances
+
+use std::sync::Arc;
+
+use clap::{Args, Subcommand};
+use diesel::{Connection, PgConnection};
+use ethers::types::{Address, TransactionReceipt, U256};
+
+use crate::{
+    allowances::{AllowanceManager, ApprovalMode},
+    db::{
+        audit::{record_audit_event, TOKEN_APPROVAL_EVENT, TOKEN_REVOCATION_EVENT},
+        gas_ledger::{record_gas_spend, GasPurpose},
+        models::NewAuditEvent,
+    },
+    erc20::build_signer_client,
+    error::FeeSweeperError,
+    gas::{FeeEstimator, GasArgs},
+    signer::SignerArgs,
+};
+
+/// The arguments to the `allowance` command
+#[derive(Debug, Args)]
+pub struct AllowanceArgs {
+    /// The Arbitrum RPC url to use
+    #[clap(short, long, env = "RPC_URL", hide_env_values = true)]
+    pub rpc_url: String,
+    /// The key that owns the allowances
+    #[clap(flatten)]
+    pub signer: SignerArgs,
+    /// The database url, in which approvals are audited
+    #[clap(long, env = "DB_URL", hide_env_values = true)]
+    pub db_url: String,
+    /// The fee estimation configuration
+    #[clap(flatten)]
+    pub gas: GasArgs,
+    /// The action to take
+    #[clap(subcommand)]
+    pub action: AllowanceAction,
+}
+
+/// An action on an allowance
+#[derive(Debug, Subcommand)]
+pub enum AllowanceAction {
+    /// Print the current allowance of a spender
+    Check {
+        /// The token address
+        #[clap(long)]
+        token: Address,
+        /// The spender address
+        #[clap(long)]
+        spender: Address,
+    },
+    /// Ensure a spender may spend at least the given amount
+    Approve {
+        /// The token address
+        #[clap(long)]
+        token: Address,
+        /// The spender address
+        #[clap(long)]
+        spender: Address,
+        /// The raw amount the spender must be able to spend
+        #[clap(long, value_parser = U256::from_dec_str)]
+        amount: U256,
+        /// The amount to approve if the current allowance is insufficient
+        #[clap(long, value_enum, default_value_t = ApprovalMode::Exact)]
+        mode: ApprovalMode,
+    },
+    /// Revoke a spender's allowance
+    Revoke {
+        /// The token address
+        #[clap(long)]
+        token: Address,
+        /// The spender address
+        #[clap(long)]
+        spender: Address,
+    },
+}
+
+/// Run the `allowance` command
+pub async fn run_allowance(args: AllowanceArgs) -> Result<(), FeeSweeperError> {
+    let mut conn = PgConnection::establish(&args.db_url)
+        .map_err(FeeSweeperError::db("failed to connect to db"))?;
+    let client = Arc::new(build_signer_client(&args.rpc_url, args.signer.signer().await?).await?);
+    let fees = FeeEstimator::new(client.clone(), &args.gas)?;
+
+    match args.action {
+        AllowanceAction::Check { token, spender } => {
+            let manager = AllowanceManager::new(client, ApprovalMode::default(), fees);
+            let allowance = manager.allowance(token, spender).await?;
+            println!("{allowance}");
+        }
+        AllowanceAction::Approve {
+            token,
+            spender,
+            amount,
+            mode,
+        } => {
+            let manager = AllowanceManager::new(client, mode, fees);
+            if let Some(receipt) = manager.ensure_allowance(token, spender, amount).await? {
+                let details =
+                    format!("approved {spender:#x} for {amount} of {token:#x} ({mode:?})");
+                record(&mut conn, TOKEN_APPROVAL_EVENT, &receipt, details)?;
+            }
+        }
+        AllowanceAction::Revoke { token, spender } => {
+            let manager = AllowanceManager::new(client, ApprovalMode::default(), fees);
+            let receipt = manager.revoke(token, spender).await?;
+            let details = format!("revoked {spender:#x} for {token:#x}");
+            record(&mut conn, TOKEN_REVOCATION_EVENT, &receipt, details)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Record an allowance change in the audit log and its gas in the ledger
+fn record(
+    conn: &mut PgConnection,
+    event_type: &str,
+    receipt: &TransactionReceipt,
+    details: String,
+) -> Result<(), FeeSweeperError> {
+    record_gas_spend(conn, GasPurpose::Approval, receipt)?;
+    let tx = format!("{:#x}", receipt.transaction_hash);
+    let event = NewAuditEvent::new(event_type, Some(tx), details);
+    record_audit_event(conn, event)
+}
@@ -0,0 +1,266 @@
+//! Operator commands for bridging redeemed funds to an L1 treasury
+
+use std::sync::Arc;
+
+use chrono::Utc;
+use clap::{Args, Subcommand};
+use diesel::{
+    Connection, ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl, SelectableHelper,
+};
+use ethers::types::{Address, U256};
+use tracing::info;
+
+use crate::{
+    bridge::{
+        claimable_at, transfer_succeeded, BridgeClient, ARBITRUM_ONE_GATEWAY_ROUTER,
+        BRIDGE_STATUS_CLAIMED, BRIDGE_STATUS_CONFIRMED, BRIDGE_STATUS_FAILED,
+        BRIDGE_STATUS_INITIATED,
+    },
+    db::{
+        audit::{record_audit_event, BRIDGE_CLAIM_EVENT, BRIDGE_TRANSFER_EVENT},
+        fund_movements::{record_fund_movement, BRIDGE_MOVEMENT},
+        gas_ledger::{record_gas_spend, GasPurpose},
+        models::{u256_to_decimal, BridgeTransfer, NewAuditEvent, NewBridgeTransfer},
+        schema::bridge_transfers::dsl::{
+            bridge_transfers as bridge_table, id as id_col, l1_tx_hash as l1_tx_hash_col,
+            l2_tx_hash as l2_tx_hash_col, status as status_col, updated_at as updated_at_col,
+        },
+    },
+    erc20::{build_signer_client, Erc20},
+    error::FeeSweeperError,
+    gas::{FeeEstimator, GasArgs},
+    invariants::{FundMovement, InvariantArgs, InvariantSet},
+    signer::SignerArgs,
+};
+
+/// The arguments to the `bridge` command
+#[derive(Debug, Args)]
+pub struct BridgeArgs {
+    /// The database url
+    #[clap(long, env = "DB_URL", hide_env_values = true)]
+    pub db_url: String,
+    /// The action to take
+    #[clap(subcommand)]
+    pub action: BridgeAction,
+}
+
+/// An action on bridge transfers
+#[derive(Debug, Subcommand)]
+pub enum BridgeAction {
+    /// Bridge the submission key's balances of the selected mints to L1
+    Initiate {
+        /// The Arbitrum RPC url to use
+        #[clap(short, long, env = "RPC_URL", hide_env_values = true)]
+        rpc_url: String,
+        /// The key holding the funds
+        #[clap(flatten)]
+        signer: SignerArgs,
+        /// A mint to bridge
+        #[clap(long = "mint", required = true)]
+        mints: Vec<Address>,
+        /// The L1 treasury address to bridge to
+        #[clap(long)]
+        l1_treasury: Address,
+        /// An L1 address that funds may be bridged to
+        #[clap(long = "l1-allowlist", required = true)]
+        allowlist: Vec<Address>,
+        /// The address of the L2 gateway router
+        #[clap(long, default_value = ARBITRUM_ONE_GATEWAY_ROUTER)]
+        gateway_router: String,
+        /// The fee estimation configuration
+        #[clap(flatten)]
+        gas: GasArgs,
+        /// The invariants checked before each transfer
+        #[clap(flatten)]
+        invariants: InvariantArgs,
+    },
+    /// List bridge transfers that have not been claimed on L1
+    List,
+    /// Record that a transfer has been claimed on L1
+    Claim {
+        /// The id of the transfer
+        #[clap(long)]
+        id: i32,
+        /// The hash of the L1 transaction that claimed the transfer
+        #[clap(long)]
+        l1_tx: String,
+    },
+}
+
+/// Run the `bridge` command
+pub async fn run_bridge(args: BridgeArgs) -> Result<(), FeeSweeperError> {
+    let mut conn = PgConnection::establish(&args.db_url)
+        .map_err(FeeSweeperError::db("failed to connect to db"))?;
+    match args.action {
+        BridgeAction::Initiate {
+            rpc_url,
+            signer,
+            mints,
+            l1_treasury,
+            allowlist,
+            gateway_router,
+            gas,
+            invariants,
+        } => {
+            let invariants = InvariantSet::new(&allowlist, &invariants)?;
+            let client = Arc::new(build_signer_client(&rpc_url, signer.signer().await?).await?);
+            let fees = FeeEstimator::new(client.clone(), &gas)?;
+            let bridge = BridgeClient::new(client.clone(), &gateway_router, fees)?;
+
+            for mint in mints {
+                let balance = Erc20::new(mint, client.clone())
+                    .balance_of(client.address())
+                    .call()
+                    .await
+                    .map_err(FeeSweeperError::rpc("failed to query balance"))?;
+                if balance == U256::zero() {
+                    info!("no balance of {mint:#x} to bridge");
+                    continue;
+                }
+
+                let movement = FundMovement {
+                    kind: "bridge transfer",
+                    mint,
+                    amount: balance,
+                    destination: l1_treasury,
+                };
+                invariants.check(&mut conn, &movement)?;
+
+                initiate_transfer(&mut conn, &bridge, mint, balance, l1_treasury).await?;
+            }
+
+            Ok(())
+        }
+        BridgeAction::List => list_transfers(&mut conn),
+        BridgeAction::Claim { id, l1_tx } => mark_claimed(&mut conn, id, l1_tx),
+    }
+}
+
+/// Initiate a transfer on L2 and record it
+///
+/// The transfer is recorded as initiated as soon as it is sent, and updated
+/// once its receipt arrives, so that a transfer whose receipt is never seen
+/// is still recorded
+async fn initiate_transfer(
+    conn: &mut PgConnection,
+    bridge: &BridgeClient,
+    mint: Address,
+    amount: U256,
+    destination: Address,
+) -> Result<(), FeeSweeperError> {
+    let transfer = bridge.send_transfer(mint, amount, destination).await?;
+    let l2_tx_hash = format!("{:#x}", transfer.tx_hash);
+    let entry = NewBridgeTransfer {
+        mint: format!("{mint:#x}"),
+        l1_token: format!("{:#x}", transfer.l1_token),
+        amount: u256_to_decimal(amount),
+        destination: format!("{destination:#x}"),
+        l2_tx_hash: l2_tx_hash.clone(),
+        status: BRIDGE_STATUS_INITIATED.to_string(),
+    };
+    diesel::insert_into(bridge_table)
+        .values(vec![entry])
+        .execute(conn)
+        .map_err(FeeSweeperError::db("failed to record bridge transfer"))?;
+
+    let receipt = bridge.await_transfer(transfer.tx_hash).await?;
+    record_gas_spend(conn, GasPurpose::Bridge, &receipt)?;
+    let success = transfer_succeeded(&receipt);
+    let status = if success {
+        BRIDGE_STATUS_CONFIRMED
+    } else {
+        BRIDGE_STATUS_FAILED
+    };
+    diesel::update(bridge_table.filter(l2_tx_hash_col.eq(&l2_tx_hash)))
+        .set((
+            status_col.eq(status),
+            updated_at_col.eq(Utc::now().naive_utc()),
+        ))
+        .execute(conn)
+        .map_err(FeeSweeperError::db("failed to update bridge transfer"))?;
+    if success {
+        let mint = format!("{mint:#x}");
+        record_fund_movement(
+            conn,
+            BRIDGE_MOVEMENT,
+            &mint,
+            u256_to_decimal(amount),
+            &l2_tx_hash,
+        )?;
+    }
+
+    let details = format!("bridged {amount} of {mint:#x} to {destination:#x}: {status}");
+    record_audit_event(
+        conn,
+        NewAuditEvent::new(BRIDGE_TRANSFER_EVENT, Some(l2_tx_hash), details),
+    )?;
+
+    if !success {
+        return Err(FeeSweeperError::Rpc(format!(
+            "bridge transaction reverted: {l2_tx_hash}"
+        )));
+    }
+    Ok(())
+}
+
+/// Print the transfers that have not been claimed on L1
+fn list_transfers(conn: &mut PgConnection) -> Result<(), FeeSweeperError> {
+    let transfers: Vec<BridgeTransfer> = bridge_table
+        .filter(status_col.ne(BRIDGE_STATUS_CLAIMED))
+        .order(id_col.asc())
+        .select(BridgeTransfer::as_select())
+        .load(conn)
+        .map_err(FeeSweeperError::db("failed to query bridge transfers"))?;
+
+    let now = Utc::now().naive_utc();
+    for transfer in transfers {
+        let claimable = claimable_at(transfer.created_at);
+        let readiness = if transfer.status != BRIDGE_STATUS_CONFIRMED {
+            String::from("-")
+        } else if claimable <= now {
+            String::from("claimable")
+        } else {
+            format!("claimable at {claimable}")
+        };
+
+        println!(
+            "{:>6}  {:<10}  {}  {} -> {}  {}  {}",
+            transfer.id,
+            transfer.status,
+            transfer.mint,
+            transfer.amount,
+            transfer.destination,
+            transfer.l2_tx_hash,
+            readiness
+        );
+    }
+
+    Ok(())
+}
+
+/// Mark a confirmed transfer as claimed on L1
+fn mark_claimed(conn: &mut PgConnection, id: i32, l1_tx: String) -> Result<(), FeeSweeperError> {
+    let updated = diesel::update(
+        bridge_table
+            .find(id)
+            .filter(status_col.eq(BRIDGE_STATUS_CONFIRMED)),
+    )
+    .set((
+        status_col.eq(BRIDGE_STATUS_CLAIMED),
+        l1_tx_hash_col.eq(&l1_tx),
+        updated_at_col.eq(Utc::now().naive_utc()),
+    ))
+    .execute(conn)
+    .map_err(FeeSweeperError::db("failed to mark transfer claimed"))?;
+    if updated == 0 {
+        return Err(FeeSweeperError::Invalid(format!(
+            "no confirmed bridge transfer with id {id}"
+        )));
+    }
+
+    let details = format!("claimed bridge transfer {id} on L1");
+    record_audit_event(
+        conn,
+        NewAuditEvent::new(BRIDGE_CLAIM_EVENT, Some(l1_tx), details),
+    )
+}
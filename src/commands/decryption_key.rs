@@ -0,0 +1,94 @@
+//! Operator commands for managing the decryption keys registered in the DB
+//!
+//! A registered key is held in a Secrets Manager secret, which is read once
+//! at registration to derive the key's receiver, and again by each sweep
+//! loading it. A running sweeper picks up a new key at its next job indexing
+//! or redeeming fees
+
+use clap::{Args, Subcommand};
+use diesel::{Connection, PgConnection};
+
+use crate::aws::{AwsContext, DEFAULT_AWS_APP_NAME};
+use crate::db::fee_keys::{get_registered_fee_keys, register_fee_key, remove_registered_fee_key};
+use crate::db::models::NewRegisteredFeeKey;
+use crate::error::FeeSweeperError;
+use crate::fee_keys::{read_fee_key_secret, FeeKey, FeeKind};
+use crate::DEFAULT_REGION;
+
+/// The arguments to the `decryption-key` command
+#[derive(Debug, Args)]
+pub struct DecryptionKeyArgs {
+    /// The database url
+    #[clap(long, env = "DB_URL", hide_env_values = true)]
+    pub db_url: String,
+    /// The action to take
+    #[clap(subcommand)]
+    pub action: DecryptionKeyAction,
+}
+
+/// An action on registered decryption keys
+#[derive(Debug, Subcommand)]
+pub enum DecryptionKeyAction {
+    /// List the registered keys
+    List,
+    /// Register a key held in a Secrets Manager secret
+    Add {
+        /// The kind of fees the key decrypts
+        #[clap(long, value_enum)]
+        kind: FeeKind,
+        /// The name or ARN of the secret holding the hex-encoded key
+        #[clap(long)]
+        secret_name: String,
+    },
+    /// Remove a registered key, once none of its fees await redemption
+    Remove {
+        /// The receiver of the key, as listed
+        receiver: String,
+    },
+}
+
+/// Run the `decryption-key` command
+pub async fn run_decryption_key(args: DecryptionKeyArgs) -> Result<(), FeeSweeperError> {
+    let mut conn = PgConnection::establish(&args.db_url)
+        .map_err(FeeSweeperError::db("failed to connect to db"))?;
+    match args.action {
+        DecryptionKeyAction::List => {
+            let keys = get_registered_fee_keys(&mut conn)?;
+            println!(
+                "{:<10}  {:<19}  {:<40}  RECEIVER",
+                "KIND", "ADDED", "SECRET"
+            );
+            for key in keys {
+                println!(
+                    "{:<10}  {}  {:<40}  {}",
+                    key.fee_kind,
+                    key.added_at.format("%Y-%m-%d %H:%M:%S"),
+                    key.secret_name,
+                    key.receiver
+                );
+            }
+            Ok(())
+        }
+        DecryptionKeyAction::Add { kind, secret_name } => {
+            let aws =
+                AwsContext::load(DEFAULT_REGION, DEFAULT_AWS_APP_NAME.to_string(), vec![]).await?;
+            let key = read_fee_key_secret(&aws, &secret_name).await?;
+            let receiver = FeeKey { kind, key }.receiver();
+            register_fee_key(
+                &mut conn,
+                NewRegisteredFeeKey {
+                    receiver: receiver.clone(),
+                    fee_kind: kind.to_string(),
+                    secret_name,
+                },
+            )?;
+            println!("registered {kind} key with receiver {receiver}");
+            Ok(())
+        }
+        DecryptionKeyAction::Remove { receiver } => {
+            remove_registered_fee_key(&mut conn, &receiver)?;
+            println!("removed key with receiver {receiver}");
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,67 @@
+//! Operator commands for the dual-write phase of a schema migration
+//!
+//! Rolling a migration forward: enable dual-writing, backfill the fees
+//! written before it was enabled, and verify until the representations agree
+//! before switching readers to the new representation. Rolling back: disable
+//! dual-writing, after which the legacy columns alone remain authoritative
+
+use clap::{Args, Subcommand};
+use diesel::{Connection, PgConnection};
+
+use crate::db::dual_write::{backfill_fee_status, verify_fee_status, MAX_REPORTED_MISMATCHES};
+use crate::error::FeeSweeperError;
+
+/// The arguments to the `dual-write` command
+#[derive(Debug, Args)]
+pub struct DualWriteCommandArgs {
+    /// The database url
+    #[clap(long, env = "DB_URL", hide_env_values = true)]
+    pub db_url: String,
+    /// The action to take
+    #[clap(subcommand)]
+    pub action: DualWriteAction,
+}
+
+/// An action on dual-written data
+#[derive(Debug, Subcommand)]
+pub enum DualWriteAction {
+    /// Compare the dual-written fee status against the legacy status columns
+    Verify,
+    /// Populate the fee status of fees written while dual-writing was disabled
+    Backfill,
+}
+
+/// Run the `dual-write` command
+pub fn run_dual_write(args: DualWriteCommandArgs) -> Result<(), FeeSweeperError> {
+    let mut conn = PgConnection::establish(&args.db_url)
+        .map_err(FeeSweeperError::db("failed to connect to db"))?;
+    match args.action {
+        DualWriteAction::Verify => {
+            let report = verify_fee_status(&mut conn)?;
+            println!(
+                "checked {} fees, {} untracked, {} mismatched",
+                report.checked,
+                report.untracked,
+                report.mismatches.len()
+            );
+            for mismatch in report.mismatches.iter().take(MAX_REPORTED_MISMATCHES) {
+                println!(
+                    "{:<66}  legacy {:<14}  status {}",
+                    mismatch.tx_hash, mismatch.legacy, mismatch.dual
+                );
+            }
+
+            if !report.is_consistent() {
+                return Err(FeeSweeperError::Db(
+                    "dual-written fee status disagrees with the legacy columns".to_string(),
+                ));
+            }
+            Ok(())
+        }
+        DualWriteAction::Backfill => {
+            let n_fees = backfill_fee_status(&mut conn)?;
+            println!("backfilled the status of {n_fees} fees");
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,116 @@
+//! Operator commands for triaging failed redemptions
+//!
+//! Each failed redemption is stored with the relayer's payload describing it,
+//! so that a batch of failures may be reviewed without querying the relayer
+//! for each task by hand
+
+use clap::{Args, Subcommand};
+use diesel::{Connection, PgConnection};
+use serde_json::Value;
+
+use crate::db::models::TaskFailure;
+use crate::db::task_failures::{get_task_failure, get_task_failures};
+use crate::error::FeeSweeperError;
+
+/// The number of characters of a failure's reason shown in the list view
+const LIST_REASON_WIDTH: usize = 60;
+
+/// The arguments to the `failures` command
+#[derive(Debug, Args)]
+pub struct FailuresArgs {
+    /// The database url
+    #[clap(long, env = "DB_URL", hide_env_values = true)]
+    pub db_url: String,
+    /// The action to take
+    #[clap(subcommand)]
+    pub action: FailuresAction,
+}
+
+/// An action on failed redemptions
+#[derive(Debug, Subcommand)]
+pub enum FailuresAction {
+    /// List the most recent failed redemptions
+    List {
+        /// Only list the failures of the fee settled in this transaction
+        #[clap(long)]
+        tx_hash: Option<String>,
+        /// The number of failures listed
+        #[clap(long, default_value_t = 50)]
+        limit: i64,
+    },
+    /// Show a failed redemption in full, with the relayer's payload
+    Show {
+        /// The id of the failure, as listed
+        id: i32,
+    },
+}
+
+/// Run the `failures` command
+pub fn run_failures(args: FailuresArgs) -> Result<(), FeeSweeperError> {
+    let mut conn = PgConnection::establish(&args.db_url)
+        .map_err(FeeSweeperError::db("failed to connect to db"))?;
+    match args.action {
+        FailuresAction::List { tx_hash, limit } => {
+            let failures = get_task_failures(&mut conn, tx_hash.as_deref(), limit)?;
+            println!(
+                "{:>6}  {:<19}  {:<66}  {:<36}  REASON",
+                "ID", "FAILED", "TX", "TASK"
+            );
+            for failure in failures {
+                let task = failure.task_id.map(|id| id.to_string());
+                println!(
+                    "{:>6}  {}  {:<66}  {:<36}  {}",
+                    failure.id,
+                    failure.failed_at.format("%Y-%m-%d %H:%M:%S"),
+                    failure.tx_hash,
+                    task.as_deref().unwrap_or("-"),
+                    truncate(&failure.reason, LIST_REASON_WIDTH),
+                );
+            }
+            Ok(())
+        }
+        FailuresAction::Show { id } => {
+            print_failure(&get_task_failure(&mut conn, id)?);
+            Ok(())
+        }
+    }
+}
+
+/// Print a failure in full, pretty-printing a JSON payload
+fn print_failure(failure: &TaskFailure) {
+    let task = failure.task_id.map(|id| id.to_string());
+    println!("failure {}", failure.id);
+    println!(
+        "  failed at: {}",
+        failure.failed_at.format("%Y-%m-%d %H:%M:%S")
+    );
+    println!("  fee tx:    {}", failure.tx_hash);
+    println!("  wallet:    {}", failure.wallet_id);
+    println!("  task:      {}", task.as_deref().unwrap_or("-"));
+    println!("  reason:    {}", failure.reason);
+
+    let payload = match &failure.payload {
+        Some(payload) => payload,
+        None => {
+            println!("  payload:   -");
+            return;
+        }
+    };
+    let rendered = serde_json::from_str::<Value>(payload)
+        .and_then(|value| serde_json::to_string_pretty(&value))
+        .unwrap_or_else(|_| payload.clone());
+    println!("  payload:");
+    for line in rendered.lines() {
+        println!("    {line}");
+    }
+}
+
+/// Truncate a string to at most `width` characters, marking the truncation
+fn truncate(s: &str, width: usize) -> String {
+    if s.chars().count() <= width {
+        return s.to_string();
+    }
+
+    let head: String = s.chars().take(width.saturating_sub(3)).collect();
+    format!("{head}...")
+}
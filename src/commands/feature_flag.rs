@@ -0,0 +1,86 @@
+//! Operator commands for managing the feature flags stored in the DB
+//!
+//! Flags are re-read at the start of each sweep, so a change applies to a
+//! running sweeper at its next sweep, unless the instance overrides the flag
+//! in its configuration
+
+use clap::{Args, Subcommand};
+use diesel::{Connection, PgConnection};
+
+use crate::db::feature_flags::{get_feature_flags, remove_feature_flag, set_feature_flag};
+use crate::error::FeeSweeperError;
+use crate::feature_flags::{check_known_flag, KNOWN_FLAGS};
+
+/// The arguments to the `feature-flag` command
+#[derive(Debug, Args)]
+pub struct FeatureFlagCommandArgs {
+    /// The database url
+    #[clap(long, env = "DB_URL", hide_env_values = true)]
+    pub db_url: String,
+    /// The action to take
+    #[clap(subcommand)]
+    pub action: FeatureFlagAction,
+}
+
+/// An action on feature flags
+#[derive(Debug, Subcommand)]
+pub enum FeatureFlagAction {
+    /// List the state of each known flag in the DB
+    List,
+    /// Enable a flag
+    Enable {
+        /// The name of the flag
+        name: String,
+    },
+    /// Disable a flag
+    Disable {
+        /// The name of the flag
+        name: String,
+    },
+    /// Remove a flag from the DB, returning it to its default of off
+    Unset {
+        /// The name of the flag
+        name: String,
+    },
+}
+
+/// Run the `feature-flag` command
+pub fn run_feature_flag(args: FeatureFlagCommandArgs) -> Result<(), FeeSweeperError> {
+    let mut conn = PgConnection::establish(&args.db_url)
+        .map_err(FeeSweeperError::db("failed to connect to db"))?;
+    match args.action {
+        FeatureFlagAction::List => {
+            let flags = get_feature_flags(&mut conn)?;
+            println!("{:<20}  {:<8}  UPDATED", "FLAG", "STATE");
+            for name in KNOWN_FLAGS {
+                match flags.iter().find(|flag| flag.name == name) {
+                    Some(flag) => println!(
+                        "{:<20}  {:<8}  {}",
+                        name,
+                        if flag.enabled { "on" } else { "off" },
+                        flag.updated_at.format("%Y-%m-%d %H:%M:%S")
+                    ),
+                    None => println!("{:<20}  {:<8}  -", name, "default"),
+                }
+            }
+            Ok(())
+        }
+        FeatureFlagAction::Enable { name } => {
+            check_known_flag(&name)?;
+            set_feature_flag(&mut conn, &name, true)?;
+            println!("enabled {name}");
+            Ok(())
+        }
+        FeatureFlagAction::Disable { name } => {
+            check_known_flag(&name)?;
+            set_feature_flag(&mut conn, &name, false)?;
+            println!("disabled {name}");
+            Ok(())
+        }
+        FeatureFlagAction::Unset { name } => {
+            remove_feature_flag(&mut conn, &name)?;
+            println!("returned {name} to its default");
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,200 @@
+//! Search for indexed fee notes, for use by on-call during incidents
+
+use bigdecimal::BigDecimal;
+use chrono::NaiveDate;
+use clap::{Args, ValueEnum};
+use diesel::{
+    Connection, ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl, SelectableHelper,
+};
+use serde::Serialize;
+
+use crate::db::models::{Fee, RedemptionState};
+use crate::db::schema::fees::dsl::{
+    amount as amount_col, block_timestamp as block_timestamp_col, fees as fees_table, id as id_col,
+    mint as mint_col, redemption_state as redemption_state_col,
+};
+use crate::error::FeeSweeperError;
+
+/// The default maximum number of fees printed by a search
+const DEFAULT_FIND_LIMIT: i64 = 100;
+
+/// The arguments to the `find` command
+#[derive(Debug, Args)]
+pub struct FindArgs {
+    /// The database url
+    #[clap(long, env = "DB_URL", hide_env_values = true)]
+    pub db_url: String,
+    /// Only match fees in the given mint
+    #[clap(long)]
+    pub mint: Option<String>,
+    /// Only match fees with at least this raw amount
+    #[clap(long)]
+    pub min_amount: Option<BigDecimal>,
+    /// Only match fees with the given status
+    #[clap(long, value_enum)]
+    pub status: Option<FeeStatusFilter>,
+    /// Only match fees settled on or after this date, e.g. 2024-01-01
+    ///
+    /// Fees whose block timestamp has not yet been backfilled never match
+    #[clap(long)]
+    pub since: Option<NaiveDate>,
+    /// The maximum number of fees to print
+    #[clap(long, default_value_t = DEFAULT_FIND_LIMIT)]
+    pub limit: i64,
+    /// The format in which to print matching fees
+    #[clap(long, value_enum, default_value_t = OutputFormat::Table)]
+    pub format: OutputFormat,
+}
+
+/// A fee status to filter a search by
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum FeeStatusFilter {
+    /// Fees that have not been redeemed, in any other state
+    Unredeemed,
+    /// Fees indexed but not yet screened for redemption
+    Indexed,
+    /// Fees screened for redemption and awaiting submission
+    Eligible,
+    /// Fees whose redemption is in flight
+    InFlight,
+    /// Fees that have been redeemed
+    Redeemed,
+    /// Fees whose last redemption failed
+    Failed,
+    /// Fees that failed too many redemptions and are dead-lettered
+    Exhausted,
+    /// Dust fees abandoned under the expiration policy
+    Abandoned,
+}
+
+impl FeeStatusFilter {
+    /// The redemption states of the fees matching the filter
+    fn states(&self) -> Vec<RedemptionState> {
+        use RedemptionState::*;
+        match self {
+            FeeStatusFilter::Unredeemed => {
+                vec![Indexed, Eligible, InFlight, Failed, Exhausted, Abandoned]
+            }
+            FeeStatusFilter::Indexed => vec![Indexed],
+            FeeStatusFilter::Eligible => vec![Eligible],
+            FeeStatusFilter::InFlight => vec![InFlight],
+            FeeStatusFilter::Redeemed => vec![Redeemed],
+            FeeStatusFilter::Failed => vec![Failed],
+            FeeStatusFilter::Exhausted => vec![Exhausted],
+            FeeStatusFilter::Abandoned => vec![Abandoned],
+        }
+    }
+}
+
+/// The format in which to print command output
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum OutputFormat {
+    /// A human readable table
+    Table,
+    /// A JSON array
+    Json,
+}
+
+/// A fee as printed by the `find` command
+#[derive(Serialize)]
+struct FeeRow {
+    /// The id of the fee
+    id: i32,
+    /// The tx hash of the fee
+    tx_hash: String,
+    /// The mint of the fee
+    mint: String,
+    /// The raw amount of the fee
+    amount: String,
+    /// The redemption state of the fee
+    status: String,
+    /// The type of match the fee was earned in
+    source: String,
+    /// The block in which the fee was settled, if known
+    block_number: Option<i64>,
+    /// The time at which the fee was settled, if known
+    block_timestamp: Option<String>,
+    /// The USD value of the fee, if known
+    usd_value: Option<String>,
+}
+
+impl From<Fee> for FeeRow {
+    fn from(fee: Fee) -> Self {
+        FeeRow {
+            id: fee.id,
+            tx_hash: fee.tx_hash,
+            mint: fee.mint,
+            amount: fee.amount.to_string(),
+            status: fee.redemption_state,
+            source: fee.source,
+            block_number: fee.block_number,
+            block_timestamp: fee.block_timestamp.map(|ts| ts.to_string()),
+            usd_value: fee
+                .usd_value
+                .map(|v| v.round(2 /* round_digits */).to_string()),
+        }
+    }
+}
+
+/// Run the `find` command
+pub fn run_find(args: FindArgs) -> Result<(), FeeSweeperError> {
+    let mut conn = PgConnection::establish(&args.db_url)
+        .map_err(FeeSweeperError::db("failed to connect to db"))?;
+    let fees = find_fees(&mut conn, &args)?;
+
+    let rows: Vec<FeeRow> = fees.into_iter().map(FeeRow::from).collect();
+    match args.format {
+        OutputFormat::Table => print_table(&rows),
+        OutputFormat::Json => {
+            let json = serde_json::to_string_pretty(&rows)
+                .map_err(FeeSweeperError::other("failed to serialize fees"))?;
+            println!("{json}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Query the fees matching the search arguments, most recently indexed first
+fn find_fees(conn: &mut PgConnection, args: &FindArgs) -> Result<Vec<Fee>, FeeSweeperError> {
+    let mut query = fees_table.into_boxed();
+    if let Some(mint) = &args.mint {
+        query = query.filter(mint_col.eq(mint.to_lowercase()));
+    }
+    if let Some(min_amount) = &args.min_amount {
+        query = query.filter(amount_col.ge(min_amount.clone()));
+    }
+    if let Some(status) = args.status {
+        let states: Vec<&str> = status.states().iter().map(|s| s.as_str()).collect();
+        query = query.filter(redemption_state_col.eq_any(states));
+    }
+    if let Some(since) = args.since {
+        query = query.filter(block_timestamp_col.ge(since.and_hms_opt(0, 0, 0).unwrap()));
+    }
+
+    query
+        .order(id_col.desc())
+        .limit(args.limit)
+        .select(Fee::as_select())
+        .load(conn)
+        .map_err(FeeSweeperError::db("failed to search fees"))
+}
+
+/// Print fees as a table
+fn print_table(rows: &[FeeRow]) {
+    println!(
+        "{:>8}  {:<66}  {:<42}  {:>40}  {:<10}  {:>10}  {:>12}",
+        "ID", "TX HASH", "MINT", "AMOUNT", "STATUS", "BLOCK", "USD VALUE"
+    );
+
+    for row in rows {
+        let block = row.block_number.map(|b| b.to_string()).unwrap_or_default();
+        let value = row.usd_value.clone().unwrap_or_default();
+        println!(
+            "{:>8}  {:<66}  {:<42}  {:>40}  {:<10}  {:>10}  {:>12}",
+            row.id, row.tx_hash, row.mint, row.amount, row.status, block, value
+        );
+    }
+
+    println!("{} fees", rows.len());
+}
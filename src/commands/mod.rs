@@ -0,0 +1,21 @@
+//! Operator commands that run outside of the sweep itself
+
+pub mod abandoned;
+pub mod allowance;
+pub mod bridge;
+pub mod decryption_key;
+pub mod dual_write;
+pub mod failures;
+pub mod feature_flag;
+pub mod find;
+pub mod policy_report;
+pub mod price_route;
+pub mod quarantine;
+pub mod recover_wallet;
+pub mod report;
+pub mod rotate_signer;
+pub mod serve;
+pub mod stats;
+pub mod status;
+pub mod support_bundle;
+pub mod treasury;
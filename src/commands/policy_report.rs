@@ -0,0 +1,248 @@
+//! An offline report comparing redemption policy variants
+//!
+//! Historical fees are replayed through the redemption loop under each
+//! variant: runs occur at a fixed interval, each run redeems the most valuable
+//! fees accrued so far that its variant's policies allow, and every redemption
+//! is charged a fixed gas cost. The report compares the value each variant
+//! captures against the gas it spends
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use bigdecimal::{BigDecimal, ToPrimitive};
+use chrono::{Duration as ChronoDuration, NaiveDate, NaiveDateTime, Utc};
+use clap::Args;
+use diesel::{Connection, ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl};
+
+use crate::db::models::FEE_SOURCE_EXTERNAL_MATCH;
+use crate::db::schema::fees;
+use crate::error::FeeSweeperError;
+use crate::indexer::policy::{RedemptionPolicies, SourcePolicy};
+use crate::indexer::redeem_fees::MAX_FEES_REDEEMED;
+
+/// The name of the variant without any source policies, always reported
+const BASELINE_VARIANT: &str = "baseline";
+/// The default interval between replayed runs, one day
+const DEFAULT_RUN_INTERVAL_SECS: u64 = 86_400;
+/// The default USD gas cost charged per replayed redemption
+const DEFAULT_REDEMPTION_COST_USD: f64 = 0.25;
+
+/// A named set of source policies to replay
+///
+/// Parsed from a string of the form `<name>=<policy>[,<policy>...]`, each
+/// policy being of the form accepted by `--source-policy`
+#[derive(Clone, Debug)]
+pub struct PolicyVariant {
+    /// The name of the variant
+    pub name: String,
+    /// The source policies of the variant
+    pub policies: Vec<SourcePolicy>,
+}
+
+impl FromStr for PolicyVariant {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, policies) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected <name>=<policy>[,<policy>...]: {s}"))?;
+        let policies = policies
+            .split(',')
+            .map(SourcePolicy::from_str)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(PolicyVariant {
+            name: name.trim().to_string(),
+            policies,
+        })
+    }
+}
+
+/// The arguments to the `policy-report` command
+#[derive(Debug, Args)]
+pub struct PolicyReportArgs {
+    /// The database url
+    #[clap(long, env = "DB_URL", hide_env_values = true)]
+    pub db_url: String,
+    /// A policy variant to replay, of the form `<name>=<policy>[,<policy>...]`;
+    /// a baseline without policies is always replayed
+    #[clap(long = "variant")]
+    pub variants: Vec<PolicyVariant>,
+    /// Only replay fees accrued on or after this date, e.g. 2024-01-01
+    #[clap(long)]
+    pub since: Option<NaiveDate>,
+    /// The interval, in seconds, between replayed runs
+    #[clap(long, default_value_t = DEFAULT_RUN_INTERVAL_SECS)]
+    pub run_interval_secs: u64,
+    /// The USD gas cost charged per replayed redemption
+    #[clap(long, default_value_t = DEFAULT_REDEMPTION_COST_USD)]
+    pub redemption_cost_usd: f64,
+}
+
+/// A historical fee as replayed
+struct ReplayedFee {
+    /// The source of the fee
+    source: String,
+    /// The time the fee accrued
+    accrued_at: NaiveDateTime,
+    /// The USD value of the fee
+    value_usd: f64,
+}
+
+/// The outcome of replaying a variant
+#[derive(Default)]
+struct ReplayOutcome {
+    /// The number of fees redeemed
+    n_redeemed: usize,
+    /// The USD value of the fees redeemed
+    value_usd: f64,
+    /// The USD gas spent redeeming them
+    gas_usd: f64,
+    /// The total time fees waited between accruing and redemption
+    total_delay: ChronoDuration,
+    /// The USD value of the fees left unredeemed
+    unredeemed_usd: f64,
+}
+
+/// Run the `policy-report` command
+pub fn run_policy_report(args: PolicyReportArgs) -> Result<(), FeeSweeperError> {
+    let mut conn = PgConnection::establish(&args.db_url)
+        .map_err(FeeSweeperError::db("failed to connect to db"))?;
+    let history = load_history(&mut conn, args.since)?;
+    let interval = ChronoDuration::seconds(args.run_interval_secs as i64);
+    println!(
+        "replaying {} fees at a run interval of {}s",
+        history.len(),
+        args.run_interval_secs
+    );
+
+    let baseline = PolicyVariant {
+        name: BASELINE_VARIANT.to_string(),
+        policies: Vec::new(),
+    };
+    println!(
+        "{:<16}  {:>8}  {:>14}  {:>12}  {:>14}  {:>14}  {:>10}",
+        "VARIANT", "REDEEMED", "VALUE USD", "GAS USD", "NET USD", "UNREDEEMED USD", "DELAY H"
+    );
+    for variant in std::iter::once(baseline).chain(args.variants) {
+        let policies = RedemptionPolicies::new(variant.policies)?;
+        let outcome = replay(&history, &policies, interval, args.redemption_cost_usd);
+
+        let mean_delay_hours = match outcome.n_redeemed {
+            0 => 0.,
+            n => outcome.total_delay.num_seconds() as f64 / n as f64 / 3600.,
+        };
+        println!(
+            "{:<16}  {:>8}  {:>14.2}  {:>12.2}  {:>14.2}  {:>14.2}  {:>10.1}",
+            variant.name,
+            outcome.n_redeemed,
+            outcome.value_usd,
+            outcome.gas_usd,
+            outcome.value_usd - outcome.gas_usd,
+            outcome.unredeemed_usd,
+            mean_delay_hours
+        );
+    }
+
+    Ok(())
+}
+
+/// Load the valued, redeemable fees to replay, oldest first
+fn load_history(
+    conn: &mut PgConnection,
+    since: Option<NaiveDate>,
+) -> Result<Vec<ReplayedFee>, FeeSweeperError> {
+    let mut query = fees::table
+        .filter(fees::source.ne(FEE_SOURCE_EXTERNAL_MATCH))
+        .filter(fees::dust.eq(false))
+        .filter(fees::block_timestamp.is_not_null())
+        .filter(fees::usd_value.is_not_null())
+        .into_boxed();
+    if let Some(since) = since.and_then(|d| d.and_hms_opt(0, 0, 0)) {
+        query = query.filter(fees::block_timestamp.ge(since));
+    }
+
+    let rows: Vec<(String, Option<NaiveDateTime>, Option<BigDecimal>)> = query
+        .order(fees::block_timestamp.asc())
+        .select((fees::source, fees::block_timestamp, fees::usd_value))
+        .load(conn)
+        .map_err(FeeSweeperError::db("failed to query fee history"))?;
+
+    Ok(rows
+        .into_iter()
+        .filter_map(|(source, accrued_at, value)| {
+            Some(ReplayedFee {
+                source,
+                accrued_at: accrued_at?,
+                value_usd: value?.to_f64()?,
+            })
+        })
+        .collect())
+}
+
+/// Replay the fee history under a set of policies
+///
+/// Mirrors a redemption run: the most valuable pending fees of the scheduled
+/// sources are selected, up to the per-run maximum, and those below their
+/// source's threshold are passed over for that run
+fn replay(
+    history: &[ReplayedFee],
+    policies: &RedemptionPolicies,
+    interval: ChronoDuration,
+    redemption_cost_usd: f64,
+) -> ReplayOutcome {
+    let mut outcome = ReplayOutcome::default();
+    let start = match history.first() {
+        Some(first) => first.accrued_at,
+        None => return outcome,
+    };
+    let end = Utc::now().naive_utc();
+
+    let mut pending: Vec<&ReplayedFee> = Vec::new();
+    let mut next_fee = 0;
+    let mut last_redemption: HashMap<&str, NaiveDateTime> = HashMap::new();
+    let mut run_at = start + interval;
+    while run_at <= end {
+        while next_fee < history.len() && history[next_fee].accrued_at <= run_at {
+            pending.push(&history[next_fee]);
+            next_fee += 1;
+        }
+
+        let is_scheduled = |source: &str| {
+            let min_interval = ChronoDuration::from_std(policies.min_interval(source))
+                .unwrap_or(ChronoDuration::zero());
+            last_redemption
+                .get(source)
+                .map_or(true, |last| run_at - *last >= min_interval)
+        };
+        let mut candidates: Vec<usize> = (0..pending.len())
+            .filter(|i| is_scheduled(&pending[*i].source))
+            .collect();
+        candidates.sort_by(|a, b| pending[*b].value_usd.total_cmp(&pending[*a].value_usd));
+        candidates.truncate(MAX_FEES_REDEEMED);
+
+        let mut redeemed: Vec<usize> = candidates
+            .into_iter()
+            .filter(|i| policies.meets_usd_threshold(&pending[*i].source, pending[*i].value_usd))
+            .collect();
+        redeemed.sort_unstable_by(|a, b| b.cmp(a));
+        for i in redeemed {
+            let fee = pending.swap_remove(i);
+            outcome.n_redeemed += 1;
+            outcome.value_usd += fee.value_usd;
+            outcome.gas_usd += redemption_cost_usd;
+            outcome.total_delay += run_at - fee.accrued_at;
+            last_redemption.insert(fee.source.as_str(), run_at);
+        }
+
+        run_at += interval;
+    }
+
+    let not_yet_accrued = history[next_fee..].iter();
+    outcome.unredeemed_usd = pending
+        .into_iter()
+        .chain(not_yet_accrued)
+        .map(|fee| fee.value_usd)
+        .sum();
+    outcome
+}
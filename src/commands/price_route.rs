@@ -0,0 +1,74 @@
+//! Operator commands for managing the routes through which mints without a
+//! USDC pair at the price source are priced
+//!
+//! Routes are read from the database on each redemption pass, so a change
+//! applies to a running sweeper at its next pass
+
+use clap::{Args, Subcommand};
+use diesel::{Connection, PgConnection};
+
+use crate::db::price_routes::{get_price_routes, remove_price_route, set_price_route};
+use crate::error::FeeSweeperError;
+
+/// The arguments to the `price-route` command
+#[derive(Debug, Args)]
+pub struct PriceRouteArgs {
+    /// The database url
+    #[clap(long, env = "DB_URL", hide_env_values = true)]
+    pub db_url: String,
+    /// The action to take
+    #[clap(subcommand)]
+    pub action: PriceRouteAction,
+}
+
+/// An action on price routes
+#[derive(Debug, Subcommand)]
+pub enum PriceRouteAction {
+    /// List the price routes
+    List,
+    /// Price a mint through an intermediate mint, e.g. WETH
+    Set {
+        /// The mint to price
+        #[clap(long)]
+        mint: String,
+        /// The mint to price it through
+        #[clap(long)]
+        via: String,
+    },
+    /// Remove a mint's price route, pricing it against USDC directly
+    Remove {
+        /// The mint whose route to remove
+        #[clap(long)]
+        mint: String,
+    },
+}
+
+/// Run the `price-route` command
+pub fn run_price_route(args: PriceRouteArgs) -> Result<(), FeeSweeperError> {
+    let mut conn = PgConnection::establish(&args.db_url)
+        .map_err(FeeSweeperError::db("failed to connect to db"))?;
+    match args.action {
+        PriceRouteAction::List => {
+            println!("{:<42}  {:<42}  UPDATED", "MINT", "VIA");
+            for route in get_price_routes(&mut conn)? {
+                println!(
+                    "{:<42}  {:<42}  {}",
+                    route.mint,
+                    route.via_mint,
+                    route.updated_at.format("%Y-%m-%d %H:%M:%S")
+                );
+            }
+            Ok(())
+        }
+        PriceRouteAction::Set { mint, via } => {
+            set_price_route(&mut conn, &mint, &via)?;
+            println!("pricing {mint} through {via}");
+            Ok(())
+        }
+        PriceRouteAction::Remove { mint } => {
+            remove_price_route(&mut conn, &mint)?;
+            println!("pricing {mint} against USDC directly");
+            Ok(())
+        }
+    }
+}
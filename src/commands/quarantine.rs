@@ -0,0 +1,71 @@
+//! Operator commands for reviewing and releasing mints quarantined as
+//! suspected spam
+
+use clap::{Args, Subcommand};
+use diesel::{Connection, PgConnection};
+
+use crate::db::quarantine::{get_quarantines, release_mint};
+use crate::error::FeeSweeperError;
+
+/// The arguments to the `quarantine` command
+#[derive(Debug, Args)]
+pub struct QuarantineArgs {
+    /// The database url
+    #[clap(long, env = "DB_URL", hide_env_values = true)]
+    pub db_url: String,
+    /// The action to take
+    #[clap(subcommand)]
+    pub action: QuarantineAction,
+}
+
+/// An action on the mint quarantine
+#[derive(Debug, Subcommand)]
+pub enum QuarantineAction {
+    /// List quarantined mints and the reasons they were quarantined
+    List {
+        /// Include mints that have been released
+        #[clap(long)]
+        all: bool,
+    },
+    /// Release a quarantined mint so that its fees are redeemed
+    Release {
+        /// The mint to release
+        #[clap(long)]
+        mint: String,
+    },
+}
+
+/// Run the `quarantine` command
+pub fn run_quarantine(args: QuarantineArgs) -> Result<(), FeeSweeperError> {
+    let mut conn = PgConnection::establish(&args.db_url)
+        .map_err(FeeSweeperError::db("failed to connect to db"))?;
+    match args.action {
+        QuarantineAction::List { all } => {
+            println!(
+                "{:<42}  {:<19}  {:<19}  REASON",
+                "MINT", "QUARANTINED", "RELEASED"
+            );
+            for quarantine in get_quarantines(&mut conn)? {
+                if quarantine.released_at.is_some() && !all {
+                    continue;
+                }
+
+                let released = quarantine.released_at.map(|t| t.to_string());
+                println!(
+                    "{:<42}  {:<19}  {:<19}  {}",
+                    quarantine.mint,
+                    quarantine.quarantined_at.format("%Y-%m-%d %H:%M:%S"),
+                    released.unwrap_or_default(),
+                    quarantine.reason
+                );
+            }
+
+            Ok(())
+        }
+        QuarantineAction::Release { mint } => {
+            release_mint(&mut conn, &mint.to_lowercase())?;
+            println!("released {mint}");
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,206 @@
+//! Recovery of a sweep wallet from its root Ethereum key
+//!
+//! Re-derives the wallet from the key, has the relayer recover it from the
+//! chain via its find-wallet flow, and reconciles the wallets table with the
+//! balances the recovered wallet holds. A wallet not yet recorded has its key
+//! stored in the secret the sweeper reads it from, so that the sweeper can
+//! operate the wallet once it is recorded
+
+use std::collections::BTreeSet;
+use std::str::FromStr;
+use std::time::Duration;
+
+use arbitrum_client::constants::Chain;
+use clap::Args;
+use diesel::{Connection, PgConnection};
+use ethers::{
+    middleware::Middleware,
+    providers::{Http, Provider},
+    signers::{LocalWallet, Signer},
+};
+use renegade_util::hex::biguint_to_hex_addr;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::aws::{AwsContext, DEFAULT_AWS_APP_NAME};
+use crate::clock::SystemClock;
+use crate::db::{
+    audit::{record_audit_event, WALLET_RECOVERY_EVENT},
+    models::{NewAuditEvent, WalletMetadata},
+    wallets::{get_wallet_metadata, upsert_wallet_mints},
+};
+use crate::error::FeeSweeperError;
+use crate::fee_keys::FeeKind;
+use crate::relayer_client::{
+    build_http_client, ConnectionPolicy, RelayerClient, RetryPolicy, ThrottlePolicy,
+    DEFAULT_TASK_TIMEOUT_SECS, DEFAULT_USER_AGENT,
+};
+use crate::secrets::{create_wallet_secret, read_secret_string, wallet_secret_name};
+use crate::wallet_lock::{acquire_wallet_lease, release_wallet_lease, WalletLockArgs};
+use crate::DEFAULT_REGION;
+
+/// The arguments to the `recover-wallet` command
+#[derive(Debug, Args)]
+pub struct RecoverWalletArgs {
+    /// The URL of the relayer to recover the wallet through
+    #[clap(long)]
+    pub relayer_url: String,
+    /// The Arbitrum RPC url to use
+    #[clap(short, long, env = "RPC_URL", hide_env_values = true)]
+    pub rpc_url: String,
+    /// The database url
+    #[clap(long, env = "DB_URL", hide_env_values = true)]
+    pub db_url: String,
+    /// The chain the wallet was created on
+    #[clap(long, default_value = "mainnet")]
+    pub chain: Chain,
+    /// The root Ethereum key of the wallet to recover
+    #[clap(long)]
+    pub eth_key: String,
+    /// The token address of the USDC token
+    #[clap(long)]
+    pub usdc_mint: String,
+    /// The kind of fees the wallet holds, used if it is not yet recorded
+    #[clap(long, value_enum, default_value_t = FeeKind::Protocol)]
+    pub fee_kind: FeeKind,
+    /// The KMS key encrypting the wallet's secret, if the wallet is not yet
+    /// recorded; Secrets Manager's AWS managed key if unset
+    #[clap(long)]
+    pub wallet_kms_key_id: Option<String>,
+    /// Print the reconciliation without writing it to the database
+    #[clap(long)]
+    pub dry_run: bool,
+    /// The configuration of the lock held on the wallet while it is recovered
+    #[clap(flatten)]
+    pub wallet_lock: WalletLockArgs,
+}
+
+/// Run the `recover-wallet` command
+pub async fn run_recover_wallet(args: RecoverWalletArgs) -> Result<(), FeeSweeperError> {
+    let mut conn = PgConnection::establish(&args.db_url)
+        .map_err(FeeSweeperError::db("failed to connect to db"))?;
+    let provider = Provider::<Http>::try_from(args.rpc_url)
+        .map_err(FeeSweeperError::config("invalid rpc url"))?;
+    let chain_id = provider
+        .get_chainid()
+        .await
+        .map_err(FeeSweeperError::rpc("failed to query chain id"))?
+        .as_u64();
+
+    // Re-derive the wallet and have the relayer recover it from the chain
+    let eth_key =
+        LocalWallet::from_str(&args.eth_key).map_err(FeeSweeperError::config("invalid eth key"))?;
+    let relayer_client = RelayerClient::new(
+        &args.relayer_url,
+        &args.usdc_mint,
+        Uuid::new_v4(),
+        build_http_client(DEFAULT_USER_AGENT, &ConnectionPolicy::default())?,
+        ThrottlePolicy::default(),
+        RetryPolicy::default(),
+        Duration::from_secs(DEFAULT_TASK_TIMEOUT_SECS),
+    );
+    let derived = relayer_client.derive_wallet(&eth_key, chain_id)?;
+    let root_key = derived.keychain.secret_keys.sk_root.clone().unwrap();
+    let wallet_id = derived.wallet_id;
+
+    // Hold the wallet's lease, so that the recovery does not interleave with
+    // a running sweeper's operations on the wallet
+    let holder = Uuid::new_v4();
+    let clock = SystemClock;
+    let lock = &args.wallet_lock;
+    if !acquire_wallet_lease(&mut conn, wallet_id, holder, lock, &clock).await? {
+        return Err(FeeSweeperError::Db(format!(
+            "wallet {wallet_id} is held by another operation"
+        )));
+    }
+
+    info!("recovering wallet {wallet_id} through the relayer");
+    let refreshed = relayer_client.refresh_wallet(chain_id, &eth_key).await;
+    release_wallet_lease(&mut conn, wallet_id, holder)?;
+    refreshed?;
+    let wallet = relayer_client.get_wallet(wallet_id, &root_key).await?;
+
+    // Reconcile the wallets table with the recovered balances
+    let recovered: BTreeSet<String> = wallet
+        .balances
+        .values()
+        .map(|balance| biguint_to_hex_addr(&balance.mint))
+        .collect();
+    for balance in wallet.balances.values() {
+        println!("{}  {}", biguint_to_hex_addr(&balance.mint), balance.amount);
+    }
+
+    let existing = get_wallet_metadata(&mut conn, wallet_id)?;
+    let recorded: BTreeSet<String> = existing
+        .as_ref()
+        .map(|entry| entry.mints.iter().flatten().cloned().collect())
+        .unwrap_or_default();
+    let added: Vec<&String> = recovered.difference(&recorded).collect();
+    let removed: Vec<&String> = recorded.difference(&recovered).collect();
+    match existing {
+        Some(_) => {
+            println!("wallet {wallet_id} is recorded; adding {added:?}, removing {removed:?}")
+        }
+        None => println!("wallet {wallet_id} is not recorded; inserting it with {added:?}"),
+    }
+
+    if args.dry_run {
+        return Ok(());
+    }
+
+    let (secret_id, fee_kind) = match existing {
+        Some(entry) => (entry.secret_id, entry.fee_kind),
+        None => {
+            let secret_id = wallet_secret_name(&args.chain, wallet_id);
+            let kms_key_id = args.wallet_kms_key_id.as_deref();
+            store_wallet_secret(&secret_id, &eth_key, kms_key_id).await?;
+            (secret_id, args.fee_kind.to_string())
+        }
+    };
+    let entry = WalletMetadata {
+        id: wallet_id,
+        mints: recovered.iter().cloned().map(Some).collect(),
+        secret_id,
+        needs_refresh: false,
+        fee_kind,
+        diverged_since: None,
+    };
+
+    upsert_wallet_mints(&mut conn, entry)?;
+    let details = format!("recovered {wallet_id}: added {added:?}, removed {removed:?}");
+    record_audit_event(
+        &mut conn,
+        NewAuditEvent::new(WALLET_RECOVERY_EVENT, None, details),
+    )?;
+
+    info!("reconciled wallet {wallet_id}");
+    Ok(())
+}
+
+/// Store the root key of a wallet not yet recorded in its secret
+///
+/// A secret left behind by an earlier recovery is kept if it holds the same
+/// key; one holding a different key is never overwritten
+async fn store_wallet_secret(
+    secret_id: &str,
+    eth_key: &LocalWallet,
+    kms_key_id: Option<&str>,
+) -> Result<(), FeeSweeperError> {
+    let aws = AwsContext::load(DEFAULT_REGION, DEFAULT_AWS_APP_NAME.to_string(), vec![]).await?;
+    if let Ok(stored) = read_secret_string(&aws, secret_id).await {
+        let stored = LocalWallet::from_str(&stored)
+            .map_err(FeeSweeperError::config("invalid wallet secret"))?;
+        if stored.address() != eth_key.address() {
+            return Err(FeeSweeperError::Invalid(format!(
+                "secret {secret_id} holds a different key, refusing to record the wallet"
+            )));
+        }
+
+        info!("wallet key already stored in {secret_id}");
+        return Ok(());
+    }
+
+    create_wallet_secret(&aws, secret_id, eth_key, kms_key_id).await?;
+    info!("stored wallet key in {secret_id}");
+    Ok(())
+}
@@ -0,0 +1,558 @@
+//! The end-of-month closing statement of the fees the sweeper manages
+//!
+//! For each mint, the statement reconciles the unredeemed balance at the start
+//! of the month with the balance at its end: the opening balance, plus the
+//! fees accrued, less the fees redeemed and abandoned during the month, is the
+//! closing balance. Amounts are raw units of the mint and USD values are those
+//! of the fees at settlement. Fees whose block timestamp has not yet been
+//! backfilled are excluded throughout. The closing balance is broken out by
+//! the fees in it that were dead-lettered, which no run retries until they are
+//! restored. The statement is read from a single snapshot of the DB, so that
+//! its balances reconcile even while a sweep runs
+//!
+//! The statement is exported as one of two tables, the per-mint statement or
+//! the gas spent by purpose, in any of the export formats
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Display};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use bigdecimal::BigDecimal;
+use chrono::{Months, NaiveDate, NaiveDateTime};
+use clap::{Args, ValueEnum};
+use diesel::{
+    dsl::{count, not, sum},
+    BoolExpressionMethods, Connection, ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl,
+};
+use serde_json::{json, Map, Value};
+
+use crate::{
+    db::{
+        models::FEE_STATUS_REDEEMED,
+        schema::{fee_status_changes, fees, gas_spend},
+        snapshot::read_snapshot,
+    },
+    error::FeeSweeperError,
+    export::{export_rows, ExportArgs},
+};
+
+/// The sections of a mint's statement, in the order they are exported
+const STATEMENT_SECTIONS: [&str; 6] = [
+    "opening",
+    "accrued",
+    "redeemed",
+    "abandoned",
+    "closing",
+    "dead_lettered",
+];
+
+/// The arguments to the `report` command
+#[derive(Debug, Args)]
+pub struct ReportArgs {
+    /// The database url
+    #[clap(long, env = "DB_URL", hide_env_values = true)]
+    pub db_url: String,
+    /// The month to close, e.g. `2024-06`, in UTC
+    #[clap(long)]
+    pub month: Month,
+    /// The table of the statement to export
+    #[clap(long, value_enum, default_value_t = ReportTable::Statement)]
+    pub table: ReportTable,
+    /// How to write the export
+    #[clap(flatten)]
+    pub export: ExportArgs,
+}
+
+/// A table of the closing statement
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum ReportTable {
+    /// The statement of each mint, followed by the totals across mints
+    Statement,
+    /// The gas spent by purpose, followed by the total
+    Gas,
+}
+
+/// A calendar month, parsed from a string of the form `YYYY-MM`
+#[derive(Clone, Copy, Debug)]
+pub struct Month {
+    /// The first day of the month
+    first_day: NaiveDate,
+}
+
+impl Month {
+    /// The start of the month
+    fn start(&self) -> NaiveDateTime {
+        self.first_day.and_hms_opt(0, 0, 0).unwrap()
+    }
+
+    /// The start of the following month
+    fn end(&self) -> Result<NaiveDateTime, FeeSweeperError> {
+        self.first_day
+            .checked_add_months(Months::new(1))
+            .and_then(|d| d.and_hms_opt(0, 0, 0))
+            .ok_or_else(|| FeeSweeperError::Invalid(format!("invalid month: {self}")))
+    }
+}
+
+impl FromStr for Month {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let first_day = NaiveDate::parse_from_str(&format!("{}-01", s.trim()), "%Y-%m-%d")
+            .map_err(|e| format!("expected a month of the form YYYY-MM: {s}: {e}"))?;
+        Ok(Month { first_day })
+    }
+}
+
+impl Display for Month {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.first_day.format("%Y-%m"))
+    }
+}
+
+/// The raw amount and USD value of a set of fees
+#[derive(Clone, Debug, Default)]
+struct Totals {
+    /// The number of fees
+    count: i64,
+    /// The raw amount of the fees, unset for totals across mints
+    amount: Option<BigDecimal>,
+    /// The USD value of the fees at settlement
+    usd_value: BigDecimal,
+}
+
+impl Totals {
+    /// Add another set of fees to the totals, ignoring its amount
+    ///
+    /// Amounts of different mints are not comparable, so totals across mints
+    /// sum only counts and USD values
+    fn add_value(&mut self, other: &Totals) {
+        self.count += other.count;
+        self.usd_value += &other.usd_value;
+    }
+
+    /// Round the USD value for presentation
+    fn rounded(mut self) -> Self {
+        self.usd_value = self.usd_value.round(2 /* round_digits */);
+        self
+    }
+}
+
+/// The statement of a single mint, or the total across mints
+#[derive(Clone, Debug, Default)]
+struct MintStatement {
+    /// The mint, or `total`
+    mint: String,
+    /// The fees unredeemed at the start of the month
+    opening: Totals,
+    /// The fees accrued during the month
+    accrued: Totals,
+    /// The fees redeemed during the month
+    redeemed: Totals,
+    /// The fees abandoned during the month
+    abandoned: Totals,
+    /// The fees unredeemed at the end of the month
+    closing: Totals,
+    /// The fees unredeemed and dead-lettered at the end of the month, a subset
+    /// of the closing balance
+    dead_lettered: Totals,
+}
+
+/// The gas spent by the sweeper for a purpose during the month
+#[derive(Clone, Debug)]
+struct GasLine {
+    /// The purpose of the transactions
+    purpose: String,
+    /// The number of transactions
+    txs: i64,
+    /// The wei spent
+    wei_spent: BigDecimal,
+}
+
+/// The closing statement of a month
+#[derive(Debug)]
+struct ClosingStatement {
+    /// The month closed
+    month: String,
+    /// The statement of each mint
+    mints: Vec<MintStatement>,
+    /// The totals across mints, in USD
+    total: MintStatement,
+    /// The gas spent by purpose
+    gas: Vec<GasLine>,
+    /// The total wei spent on gas
+    total_wei_spent: BigDecimal,
+}
+
+/// Run the `report` command
+pub fn run_report(args: ReportArgs) -> Result<(), FeeSweeperError> {
+    let mut conn = PgConnection::establish(&args.db_url)
+        .map_err(FeeSweeperError::db("failed to connect to db"))?;
+    let statement = read_snapshot(&mut conn, |conn| build_statement(conn, args.month))?;
+    match args.table {
+        ReportTable::Statement => export_rows(
+            &args.export,
+            statement_schema(),
+            &statement_rows(&statement),
+        ),
+        ReportTable::Gas => export_rows(&args.export, gas_schema(), &gas_rows(&statement)),
+    }
+}
+
+/// Build the closing statement of a month
+fn build_statement(
+    conn: &mut PgConnection,
+    month: Month,
+) -> Result<ClosingStatement, FeeSweeperError> {
+    let start = month.start();
+    let end = month.end()?;
+
+    let mut statements: BTreeMap<String, MintStatement> = BTreeMap::new();
+    for (mint, totals) in unredeemed_as_of(conn, start)? {
+        statement_of(&mut statements, mint).opening = totals;
+    }
+    for (mint, totals) in accrued_between(conn, start, end)? {
+        statement_of(&mut statements, mint).accrued = totals;
+    }
+    for (mint, totals) in redeemed_between(conn, start, end)? {
+        statement_of(&mut statements, mint).redeemed = totals;
+    }
+    for (mint, totals) in abandoned_between(conn, start, end)? {
+        statement_of(&mut statements, mint).abandoned = totals;
+    }
+    for (mint, totals) in unredeemed_as_of(conn, end)? {
+        statement_of(&mut statements, mint).closing = totals;
+    }
+    for (mint, totals) in dead_lettered_as_of(conn, end)? {
+        statement_of(&mut statements, mint).dead_lettered = totals;
+    }
+
+    let mut total = MintStatement {
+        mint: "total".to_string(),
+        ..Default::default()
+    };
+    for statement in statements.values() {
+        total.opening.add_value(&statement.opening);
+        total.accrued.add_value(&statement.accrued);
+        total.redeemed.add_value(&statement.redeemed);
+        total.abandoned.add_value(&statement.abandoned);
+        total.closing.add_value(&statement.closing);
+        total.dead_lettered.add_value(&statement.dead_lettered);
+    }
+
+    let gas = gas_between(conn, start, end)?;
+    let total_wei_spent = gas.iter().map(|line| &line.wei_spent).sum();
+    Ok(ClosingStatement {
+        month: month.to_string(),
+        mints: statements.into_values().map(round_statement).collect(),
+        total: round_statement(total),
+        gas,
+        total_wei_spent,
+    })
+}
+
+/// The statement of a mint, created empty if the mint has none yet
+fn statement_of(
+    statements: &mut BTreeMap<String, MintStatement>,
+    mint: String,
+) -> &mut MintStatement {
+    statements
+        .entry(mint.clone())
+        .or_insert_with(|| MintStatement {
+            mint,
+            ..Default::default()
+        })
+}
+
+/// Round the USD values of a statement for presentation
+fn round_statement(statement: MintStatement) -> MintStatement {
+    MintStatement {
+        mint: statement.mint,
+        opening: statement.opening.rounded(),
+        accrued: statement.accrued.rounded(),
+        redeemed: statement.redeemed.rounded(),
+        abandoned: statement.abandoned.rounded(),
+        closing: statement.closing.rounded(),
+        dead_lettered: statement.dead_lettered.rounded(),
+    }
+}
+
+/// A row of per-mint aggregates
+type MintRow = (String, i64, Option<BigDecimal>, Option<BigDecimal>);
+
+/// Key per-mint aggregate rows by mint
+fn to_totals(rows: Vec<MintRow>) -> HashMap<String, Totals> {
+    rows.into_iter()
+        .map(|(mint, count, amount, usd_value)| {
+            let totals = Totals {
+                count,
+                amount: Some(amount.unwrap_or_default()),
+                usd_value: usd_value.unwrap_or_default(),
+            };
+            (mint, totals)
+        })
+        .collect()
+}
+
+/// The fees, by mint, unredeemed at the given time
+///
+/// A fee counts if it accrued before the cutoff and was neither redeemed,
+/// according to its status history, nor abandoned by then
+fn unredeemed_as_of(
+    conn: &mut PgConnection,
+    cutoff: NaiveDateTime,
+) -> Result<HashMap<String, Totals>, FeeSweeperError> {
+    let redeemed_by_cutoff = fee_status_changes::table
+        .filter(fee_status_changes::status.eq(FEE_STATUS_REDEEMED))
+        .filter(fee_status_changes::changed_at.lt(cutoff))
+        .select(fee_status_changes::tx_hash);
+    let rows: Vec<MintRow> = fees::table
+        .filter(fees::block_timestamp.lt(cutoff))
+        .filter(not(fees::tx_hash.eq_any(redeemed_by_cutoff)))
+        .filter(
+            fees::abandoned_at
+                .is_null()
+                .or(fees::abandoned_at.ge(cutoff)),
+        )
+        .group_by(fees::mint)
+        .select((
+            fees::mint,
+            count(fees::id),
+            sum(fees::amount),
+            sum(fees::usd_value),
+        ))
+        .load(conn)
+        .map_err(FeeSweeperError::db("failed to query unredeemed fees"))?;
+
+    Ok(to_totals(rows))
+}
+
+/// The fees, by mint, unredeemed and dead-lettered at the given time
+fn dead_lettered_as_of(
+    conn: &mut PgConnection,
+    cutoff: NaiveDateTime,
+) -> Result<HashMap<String, Totals>, FeeSweeperError> {
+    let redeemed_by_cutoff = fee_status_changes::table
+        .filter(fee_status_changes::status.eq(FEE_STATUS_REDEEMED))
+        .filter(fee_status_changes::changed_at.lt(cutoff))
+        .select(fee_status_changes::tx_hash);
+    let rows: Vec<MintRow> = fees::table
+        .filter(fees::block_timestamp.lt(cutoff))
+        .filter(fees::dead_lettered_at.lt(cutoff))
+        .filter(not(fees::tx_hash.eq_any(redeemed_by_cutoff)))
+        .filter(
+            fees::abandoned_at
+                .is_null()
+                .or(fees::abandoned_at.ge(cutoff)),
+        )
+        .group_by(fees::mint)
+        .select((
+            fees::mint,
+            count(fees::id),
+            sum(fees::amount),
+            sum(fees::usd_value),
+        ))
+        .load(conn)
+        .map_err(FeeSweeperError::db("failed to query dead-lettered fees"))?;
+
+    Ok(to_totals(rows))
+}
+
+/// The fees, by mint, accrued between two times
+fn accrued_between(
+    conn: &mut PgConnection,
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+) -> Result<HashMap<String, Totals>, FeeSweeperError> {
+    let rows: Vec<MintRow> = fees::table
+        .filter(fees::block_timestamp.ge(start))
+        .filter(fees::block_timestamp.lt(end))
+        .group_by(fees::mint)
+        .select((
+            fees::mint,
+            count(fees::id),
+            sum(fees::amount),
+            sum(fees::usd_value),
+        ))
+        .load(conn)
+        .map_err(FeeSweeperError::db("failed to query accrued fees"))?;
+
+    Ok(to_totals(rows))
+}
+
+/// The fees, by mint, redeemed between two times, according to their status
+/// history
+fn redeemed_between(
+    conn: &mut PgConnection,
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+) -> Result<HashMap<String, Totals>, FeeSweeperError> {
+    let redeemed_in_period = fee_status_changes::table
+        .filter(fee_status_changes::status.eq(FEE_STATUS_REDEEMED))
+        .filter(fee_status_changes::changed_at.ge(start))
+        .filter(fee_status_changes::changed_at.lt(end))
+        .select(fee_status_changes::tx_hash);
+    let rows: Vec<MintRow> = fees::table
+        .filter(fees::block_timestamp.is_not_null())
+        .filter(fees::tx_hash.eq_any(redeemed_in_period))
+        .group_by(fees::mint)
+        .select((
+            fees::mint,
+            count(fees::id),
+            sum(fees::amount),
+            sum(fees::usd_value),
+        ))
+        .load(conn)
+        .map_err(FeeSweeperError::db("failed to query redeemed fees"))?;
+
+    Ok(to_totals(rows))
+}
+
+/// The fees, by mint, abandoned between two times
+fn abandoned_between(
+    conn: &mut PgConnection,
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+) -> Result<HashMap<String, Totals>, FeeSweeperError> {
+    let rows: Vec<MintRow> = fees::table
+        .filter(fees::block_timestamp.is_not_null())
+        .filter(fees::abandoned_at.ge(start))
+        .filter(fees::abandoned_at.lt(end))
+        .group_by(fees::mint)
+        .select((
+            fees::mint,
+            count(fees::id),
+            sum(fees::amount),
+            sum(fees::usd_value),
+        ))
+        .load(conn)
+        .map_err(FeeSweeperError::db("failed to query abandoned fees"))?;
+
+    Ok(to_totals(rows))
+}
+
+/// The gas spent by purpose between two times
+fn gas_between(
+    conn: &mut PgConnection,
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+) -> Result<Vec<GasLine>, FeeSweeperError> {
+    let rows: Vec<(String, i64, Option<BigDecimal>)> = gas_spend::table
+        .filter(gas_spend::created_at.ge(start))
+        .filter(gas_spend::created_at.lt(end))
+        .group_by(gas_spend::purpose)
+        .select((
+            gas_spend::purpose,
+            count(gas_spend::id),
+            sum(gas_spend::wei_spent),
+        ))
+        .order(gas_spend::purpose.asc())
+        .load(conn)
+        .map_err(FeeSweeperError::db("failed to query gas spent"))?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(purpose, txs, wei)| GasLine {
+            purpose,
+            txs,
+            wei_spent: wei.unwrap_or_default(),
+        })
+        .collect())
+}
+
+// -----------
+// | Exports |
+// -----------
+
+/// The schema of the per-mint statement table
+fn statement_schema() -> SchemaRef {
+    let mut fields = vec![
+        Field::new("month", DataType::Utf8, false),
+        Field::new("mint", DataType::Utf8, false),
+    ];
+    for section in STATEMENT_SECTIONS {
+        fields.push(Field::new(
+            format!("{section}_count"),
+            DataType::Int64,
+            false,
+        ));
+        fields.push(Field::new(
+            format!("{section}_amount"),
+            DataType::Utf8,
+            true,
+        ));
+        fields.push(Field::new(format!("{section}_usd"), DataType::Utf8, false));
+    }
+
+    Arc::new(Schema::new(fields))
+}
+
+/// The rows of the per-mint statement table, the totals across mints last
+///
+/// Amounts and USD values are exported as decimal strings to keep their
+/// precision
+fn statement_rows(statement: &ClosingStatement) -> Vec<Value> {
+    let mut rows = Vec::new();
+    for mint in statement.mints.iter().chain([&statement.total]) {
+        let mut row = Map::new();
+        row.insert("month".to_string(), json!(statement.month));
+        row.insert("mint".to_string(), json!(mint.mint));
+        let totals = [
+            &mint.opening,
+            &mint.accrued,
+            &mint.redeemed,
+            &mint.abandoned,
+            &mint.closing,
+            &mint.dead_lettered,
+        ];
+        for (section, totals) in STATEMENT_SECTIONS.into_iter().zip(totals) {
+            let amount = totals.amount.as_ref().map(|a| a.to_string());
+            row.insert(format!("{section}_count"), json!(totals.count));
+            row.insert(format!("{section}_amount"), json!(amount));
+            row.insert(
+                format!("{section}_usd"),
+                json!(totals.usd_value.to_string()),
+            );
+        }
+        rows.push(Value::Object(row));
+    }
+
+    rows
+}
+
+/// The schema of the gas table
+fn gas_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("month", DataType::Utf8, false),
+        Field::new("gas_purpose", DataType::Utf8, false),
+        Field::new("txs", DataType::Int64, false),
+        Field::new("wei_spent", DataType::Utf8, false),
+    ]))
+}
+
+/// The rows of the gas table, the total across purposes last
+fn gas_rows(statement: &ClosingStatement) -> Vec<Value> {
+    let mut rows: Vec<Value> = statement
+        .gas
+        .iter()
+        .map(|line| {
+            json!({
+                "month": statement.month,
+                "gas_purpose": line.purpose,
+                "txs": line.txs,
+                "wei_spent": line.wei_spent.to_string(),
+            })
+        })
+        .collect();
+
+    let total_txs: i64 = statement.gas.iter().map(|line| line.txs).sum();
+    rows.push(json!({
+        "month": statement.month,
+        "gas_purpose": "total",
+        "txs": total_txs,
+        "wei_spent": statement.total_wei_spent.to_string(),
+    }));
+    rows
+}
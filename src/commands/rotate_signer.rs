@@ -0,0 +1,154 @@
+//! Rotation of the Arbitrum key the sweeper signs transactions with
+
+use std::str::FromStr;
+
+use clap::Args;
+use diesel::{Connection, PgConnection};
+use ethers::{
+    middleware::Middleware,
+    providers::{Http, Provider},
+    signers::Signer,
+    types::{Address, BlockNumber},
+    utils::{format_ether, parse_ether},
+};
+use tracing::info;
+
+use crate::aws::{AwsContext, DEFAULT_AWS_APP_NAME};
+use crate::db::{
+    audit::{record_audit_event, SIGNER_ROTATION_EVENT},
+    metadata::{get_metadata, set_metadata, ACTIVE_SIGNER_KEY},
+    models::NewAuditEvent,
+};
+use crate::error::FeeSweeperError;
+use crate::signer::{KmsSigner, SubmissionSigner};
+use crate::DEFAULT_REGION;
+
+/// The default minimum ETH balance the new signer must hold for gas
+const DEFAULT_MIN_GAS_BALANCE_ETH: f64 = 0.005;
+/// The message the new signer signs to show that it can sign
+const PROBE_MESSAGE: &str = "fee-sweeper signer rotation";
+
+/// The arguments to the `rotate-signer` command
+#[derive(Debug, Args)]
+pub struct RotateSignerArgs {
+    /// The Arbitrum RPC url to use
+    #[clap(short, long, env = "RPC_URL", hide_env_values = true)]
+    pub rpc_url: String,
+    /// The database url
+    #[clap(long, env = "DB_URL", hide_env_values = true)]
+    pub db_url: String,
+    /// The private key of the new signer
+    #[clap(long = "new-pkey", required_unless_present = "new_kms_key_id")]
+    pub new_private_key: Option<String>,
+    /// The id or ARN of the AWS KMS key of the new signer, in place of a
+    /// private key
+    #[clap(long, conflicts_with = "new_private_key")]
+    pub new_kms_key_id: Option<String>,
+    /// The address of the current signer, if none is recorded in the database
+    #[clap(long)]
+    pub old_address: Option<Address>,
+    /// The minimum ETH balance the new signer must hold for gas
+    #[clap(long, default_value_t = DEFAULT_MIN_GAS_BALANCE_ETH)]
+    pub min_gas_balance_eth: f64,
+}
+
+/// Run the `rotate-signer` command
+pub async fn run_rotate_signer(args: RotateSignerArgs) -> Result<(), FeeSweeperError> {
+    let mut conn = PgConnection::establish(&args.db_url)
+        .map_err(FeeSweeperError::db("failed to connect to db"))?;
+    let provider = Provider::<Http>::try_from(args.rpc_url)
+        .map_err(FeeSweeperError::config("invalid rpc url"))?;
+
+    // Validate the new key and check that it can pay for gas
+    let new_signer = load_new_signer(args.new_private_key, args.new_kms_key_id).await?;
+    new_signer.check_signs(PROBE_MESSAGE).await?;
+    let new_signer = new_signer.address();
+    let balance = provider
+        .get_balance(new_signer, None /* block */)
+        .await
+        .map_err(FeeSweeperError::rpc("failed to query balance"))?;
+    let min_balance = parse_ether(args.min_gas_balance_eth)
+        .map_err(FeeSweeperError::config("invalid balance"))?;
+    if balance < min_balance {
+        return Err(FeeSweeperError::Invalid(format!(
+            "new signer {new_signer:#x} holds {} ETH, below the minimum of {} ETH",
+            format_ether(balance),
+            args.min_gas_balance_eth
+        )));
+    }
+
+    // Check that the old signer has no transactions in flight
+    let old_signer = match get_metadata(&mut conn, ACTIVE_SIGNER_KEY)? {
+        Some(addr) => {
+            Some(Address::from_str(&addr).map_err(FeeSweeperError::config("invalid signer"))?)
+        }
+        None => args.old_address,
+    };
+    if let Some(old_signer) = old_signer {
+        if old_signer == new_signer {
+            return Err(FeeSweeperError::Invalid(format!(
+                "{new_signer:#x} is already the active signer"
+            )));
+        }
+
+        check_no_pending_txs(&provider, old_signer).await?;
+    }
+
+    let new_signer_str = format!("{new_signer:#x}");
+    set_metadata(&mut conn, ACTIVE_SIGNER_KEY, &new_signer_str)?;
+    let old_signer_str = old_signer.map(|addr| format!("{addr:#x}"));
+    let details = format!(
+        "rotated signer from {} to {new_signer_str}",
+        old_signer_str.as_deref().unwrap_or("<none>")
+    );
+    record_audit_event(
+        &mut conn,
+        NewAuditEvent::new(SIGNER_ROTATION_EVENT, None, details),
+    )?;
+
+    info!("{new_signer_str} is now the active signer");
+    Ok(())
+}
+
+/// Build the new signer from its private key or KMS key
+async fn load_new_signer(
+    private_key: Option<String>,
+    kms_key_id: Option<String>,
+) -> Result<SubmissionSigner, FeeSweeperError> {
+    match (private_key, kms_key_id) {
+        (Some(key), _) => SubmissionSigner::from_private_key(&key),
+        (None, Some(key_id)) => {
+            let aws =
+                AwsContext::load(DEFAULT_REGION, DEFAULT_AWS_APP_NAME.to_string(), vec![]).await?;
+            let kms = KmsSigner::connect(&aws, &key_id).await?;
+            Ok(SubmissionSigner::Kms(kms))
+        }
+        (None, None) => Err(FeeSweeperError::Config(
+            "a new private key or KMS key id is required".to_string(),
+        )),
+    }
+}
+
+/// Check that an address has no transactions pending in the mempool
+async fn check_no_pending_txs(
+    provider: &Provider<Http>,
+    address: Address,
+) -> Result<(), FeeSweeperError> {
+    let mined = provider
+        .get_transaction_count(address, Some(BlockNumber::Latest.into()))
+        .await
+        .map_err(FeeSweeperError::rpc("failed to query nonce"))?;
+    let pending = provider
+        .get_transaction_count(address, Some(BlockNumber::Pending.into()))
+        .await
+        .map_err(FeeSweeperError::rpc("failed to query pending nonce"))?;
+
+    if pending > mined {
+        return Err(FeeSweeperError::Invalid(format!(
+            "{address:#x} has {} transactions in flight",
+            pending - mined
+        )));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,38 @@
+//! Serve the sweeper's HTTP API
+
+use std::time::Duration;
+
+use clap::Args;
+
+use crate::api::{admin::AdminToken, serve, ApiState};
+use crate::error::FeeSweeperError;
+
+/// The arguments to the `serve` command
+#[derive(Debug, Args)]
+pub struct ServeArgs {
+    /// The database url
+    #[clap(long, env = "DB_URL", hide_env_values = true)]
+    pub db_url: String,
+    /// The port on which to serve the API
+    #[clap(long, default_value_t = 3000)]
+    pub port: u16,
+    /// The time, in seconds, the public stats are cached for
+    #[clap(long, default_value_t = 300)]
+    pub public_stats_cache_secs: u64,
+    /// A token granting access to the admin API, of the form
+    /// `<caller>=<token>`; the caller identifies the token's holder in the
+    /// access log
+    #[clap(long = "admin-token")]
+    pub admin_tokens: Vec<AdminToken>,
+    /// The number of days admin access log entries are kept
+    #[clap(long, default_value_t = 400)]
+    pub access_log_retention_days: u64,
+}
+
+/// Run the `serve` command
+pub async fn run_serve(args: ServeArgs) -> Result<(), FeeSweeperError> {
+    let ttl = Duration::from_secs(args.public_stats_cache_secs);
+    let retention = Duration::from_secs(args.access_log_retention_days * 24 * 60 * 60);
+    let state = ApiState::new(args.db_url, ttl, args.admin_tokens, retention);
+    serve(state, args.port).await
+}
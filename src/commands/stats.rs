@@ -0,0 +1,220 @@
+//! Summary statistics of the fees the sweeper has indexed and the gas it has
+//! spent, from which net protocol revenue may be computed
+//!
+//! The statistics are read from a single snapshot of the DB, so that their
+//! totals agree with each other even while a sweep runs
+
+use bigdecimal::BigDecimal;
+use chrono::{Days, NaiveDate};
+use clap::Args;
+use diesel::{
+    dsl::{count, not, sum},
+    BoolExpressionMethods, Connection, ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl,
+};
+
+use crate::db::{
+    models::FEE_STATUS_REDEEMED,
+    schema::{fee_status_changes, fees, gas_spend},
+    snapshot::read_snapshot,
+};
+use crate::error::FeeSweeperError;
+
+/// The number of decimals in one gwei
+const GWEI_DECIMALS: i64 = 9;
+
+/// The arguments to the `stats` command
+#[derive(Debug, Args)]
+pub struct StatsArgs {
+    /// The database url
+    #[clap(long, env = "DB_URL", hide_env_values = true)]
+    pub db_url: String,
+    /// Report the fees that were unredeemed at the end of the given day
+    /// (UTC), e.g. `2024-06-30`, instead of the current statistics
+    #[clap(long)]
+    pub as_of: Option<NaiveDate>,
+}
+
+/// Run the `stats` command
+pub fn run_stats(args: StatsArgs) -> Result<(), FeeSweeperError> {
+    let mut conn = PgConnection::establish(&args.db_url)
+        .map_err(FeeSweeperError::db("failed to connect to db"))?;
+    read_snapshot(&mut conn, |conn| {
+        if let Some(date) = args.as_of {
+            return print_unredeemed_as_of(conn, date);
+        }
+
+        print_fee_stats(conn)?;
+        println!();
+        print_relayer_stats(conn)?;
+        println!();
+        print_gas_stats(conn)
+    })
+}
+
+/// Print the number and USD value of fees by kind and redemption status
+///
+/// Abandoned fees are reported apart from unredeemed fees, so that unredeemed
+/// value reflects realistically recoverable revenue
+fn print_fee_stats(conn: &mut PgConnection) -> Result<(), FeeSweeperError> {
+    let rows: Vec<(String, bool, i64, Option<BigDecimal>)> = fees::table
+        .filter(fees::abandoned_at.is_null())
+        .group_by((fees::fee_kind, fees::redeemed))
+        .select((
+            fees::fee_kind,
+            fees::redeemed,
+            count(fees::id),
+            sum(fees::usd_value),
+        ))
+        .order_by((fees::fee_kind, fees::redeemed))
+        .load(conn)
+        .map_err(FeeSweeperError::db("failed to query fee stats"))?;
+
+    println!(
+        "{:<10}  {:<12}  {:>10}  {:>16}",
+        "KIND", "FEES", "COUNT", "USD VALUE"
+    );
+    for (kind, redeemed, n_fees, value) in rows {
+        let status = if redeemed { "redeemed" } else { "unredeemed" };
+        let value = value.map(|v| v.round(2 /* round_digits */).to_string());
+        println!(
+            "{kind:<10}  {status:<12}  {n_fees:>10}  {:>16}",
+            value.unwrap_or_default()
+        );
+    }
+
+    let (n_abandoned, abandoned_value): (i64, Option<BigDecimal>) = fees::table
+        .filter(fees::abandoned_at.is_not_null())
+        .select((count(fees::id), sum(fees::usd_value)))
+        .first(conn)
+        .map_err(FeeSweeperError::db("failed to query abandoned fees"))?;
+    if n_abandoned > 0 {
+        let value = abandoned_value.map(|v| v.round(2 /* round_digits */).to_string());
+        println!(
+            "{:<10}  {:<12}  {n_abandoned:>10}  {:>16}",
+            "all",
+            "abandoned",
+            value.unwrap_or_default()
+        );
+    }
+
+    Ok(())
+}
+
+/// Print the number and USD value of non-abandoned fees by the relayer that
+/// settled them
+///
+/// External match fees, and fees indexed before relayers were recorded, have
+/// no relayer and are reported as `unknown`
+fn print_relayer_stats(conn: &mut PgConnection) -> Result<(), FeeSweeperError> {
+    let rows: Vec<(Option<String>, i64, Option<BigDecimal>)> = fees::table
+        .filter(fees::abandoned_at.is_null())
+        .group_by(fees::relayer)
+        .select((fees::relayer, count(fees::id), sum(fees::usd_value)))
+        .order_by(fees::relayer)
+        .load(conn)
+        .map_err(FeeSweeperError::db("failed to query relayer stats"))?;
+
+    println!("{:<42}  {:>10}  {:>16}", "RELAYER", "COUNT", "USD VALUE");
+    for (relayer, n_fees, value) in rows {
+        let relayer = relayer.unwrap_or_else(|| "unknown".to_string());
+        let value = value.map(|v| v.round(2 /* round_digits */).to_string());
+        println!(
+            "{relayer:<42}  {n_fees:>10}  {:>16}",
+            value.unwrap_or_default()
+        );
+    }
+
+    Ok(())
+}
+
+/// Print the number, amount, and USD value by mint of the fees that were
+/// unredeemed at the end of the given day
+///
+/// A fee counts if it accrued before the end of the day and was not redeemed
+/// by then, according to its status history, and had not been abandoned by
+/// then. Fees that have not yet been assigned a block timestamp are excluded
+fn print_unredeemed_as_of(conn: &mut PgConnection, date: NaiveDate) -> Result<(), FeeSweeperError> {
+    let cutoff = date
+        .checked_add_days(Days::new(1))
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .ok_or_else(|| FeeSweeperError::Invalid(format!("invalid date: {date}")))?;
+
+    let redeemed_by_cutoff = fee_status_changes::table
+        .filter(fee_status_changes::status.eq(FEE_STATUS_REDEEMED))
+        .filter(fee_status_changes::changed_at.lt(cutoff))
+        .select(fee_status_changes::tx_hash);
+    let rows: Vec<(String, i64, Option<BigDecimal>, Option<BigDecimal>)> = fees::table
+        .filter(fees::block_timestamp.lt(cutoff))
+        .filter(not(fees::tx_hash.eq_any(redeemed_by_cutoff)))
+        .filter(
+            fees::abandoned_at
+                .is_null()
+                .or(fees::abandoned_at.ge(cutoff)),
+        )
+        .group_by(fees::mint)
+        .select((
+            fees::mint,
+            count(fees::id),
+            sum(fees::amount),
+            sum(fees::usd_value),
+        ))
+        .order(fees::mint.asc())
+        .load(conn)
+        .map_err(FeeSweeperError::db("failed to query unredeemed fees"))?;
+
+    println!("unredeemed fees as of the end of {date}");
+    println!(
+        "{:<42}  {:>10}  {:>32}  {:>16}",
+        "MINT", "COUNT", "AMOUNT", "USD VALUE"
+    );
+    let mut total = BigDecimal::from(0);
+    for (mint, n_fees, amount, value) in rows {
+        let value = value.unwrap_or_default();
+        println!(
+            "{mint:<42}  {n_fees:>10}  {:>32}  {:>16}",
+            amount.unwrap_or_default(),
+            value.round(2 /* round_digits */)
+        );
+        total += value;
+    }
+    println!(
+        "{:<42}  {:>10}  {:>32}  {:>16}",
+        "total",
+        "",
+        "",
+        total.round(2 /* round_digits */)
+    );
+
+    Ok(())
+}
+
+/// Print the gas spent by the sweeper by purpose
+fn print_gas_stats(conn: &mut PgConnection) -> Result<(), FeeSweeperError> {
+    let rows: Vec<(String, i64, Option<BigDecimal>)> = gas_spend::table
+        .group_by(gas_spend::purpose)
+        .select((
+            gas_spend::purpose,
+            count(gas_spend::id),
+            sum(gas_spend::wei_spent),
+        ))
+        .order(gas_spend::purpose.asc())
+        .load(conn)
+        .map_err(FeeSweeperError::db("failed to query gas stats"))?;
+
+    println!("{:<12}  {:>10}  {:>24}", "GAS", "TXS", "GWEI SPENT");
+    let mut total = BigDecimal::from(0);
+    for (purpose, n_txs, wei) in rows {
+        let wei = wei.unwrap_or_default();
+        println!("{purpose:<12}  {n_txs:>10}  {:>24}", to_gwei(&wei));
+        total += wei;
+    }
+    println!("{:<12}  {:>10}  {:>24}", "total", "", to_gwei(&total));
+
+    Ok(())
+}
+
+/// Convert a wei amount to a whole number of gwei
+fn to_gwei(wei: &BigDecimal) -> BigDecimal {
+    let (digits, scale) = wei.as_bigint_and_exponent();
+    BigDecimal::new(digits, scale + GWEI_DECIMALS).round(0 /* round_digits */)
+}
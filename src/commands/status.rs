@@ -0,0 +1,98 @@
+//! The state of the sweeper: how far it has indexed, which instance holds the
+//! sweeper lease, and where its fees stand in redemption
+//!
+//! Reads only the database, so it needs no keys. Its counts are read from a
+//! single snapshot of the database, so that they agree with each other
+
+use chrono::{NaiveDateTime, Utc};
+use clap::Args;
+use diesel::{
+    dsl::{count, min},
+    Connection, ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl,
+};
+
+use crate::db::{leases::get_lease, metadata::get_metadata, schema::fees, snapshot::read_snapshot};
+use crate::error::FeeSweeperError;
+use crate::indexer::queries::LAST_INDEXED_BLOCK_KEY;
+use crate::lease::SWEEPER_LEASE;
+
+/// The arguments to the `status` command
+#[derive(Debug, Args)]
+pub struct StatusArgs {
+    /// The database url
+    #[clap(long, env = "DB_URL", hide_env_values = true)]
+    pub db_url: String,
+}
+
+/// Run the `status` command
+pub fn run_status(args: StatusArgs) -> Result<(), FeeSweeperError> {
+    let mut conn = PgConnection::establish(&args.db_url)
+        .map_err(FeeSweeperError::db("failed to connect to db"))?;
+
+    read_snapshot(&mut conn, |conn| {
+        let last_block = get_metadata(conn, LAST_INDEXED_BLOCK_KEY)?;
+        println!(
+            "last indexed block:  {}",
+            last_block.unwrap_or("none".to_string())
+        );
+
+        let now = Utc::now().naive_utc();
+        match get_lease(conn, SWEEPER_LEASE)? {
+            Some(lease) if lease.expires_at > now => println!(
+                "sweeper lease:       held by {} since {}, expires {}",
+                lease.holder,
+                format_time(lease.acquired_at),
+                format_time(lease.expires_at)
+            ),
+            _ => println!("sweeper lease:       free"),
+        }
+
+        let open = fees::table
+            .filter(fees::redeemed.eq(false))
+            .filter(fees::abandoned_at.is_null())
+            .filter(fees::dead_lettered_at.is_null());
+        let (n_open, oldest): (i64, Option<NaiveDateTime>) = open
+            .clone()
+            .select((count(fees::id), min(fees::block_timestamp)))
+            .first(conn)
+            .map_err(FeeSweeperError::db("failed to query unredeemed fees"))?;
+        let n_redeeming: i64 = open
+            .filter(fees::redemption_started_at.is_not_null())
+            .count()
+            .get_result(conn)
+            .map_err(FeeSweeperError::db("failed to query redeeming fees"))?;
+        let n_dead_lettered: i64 = fees::table
+            .filter(fees::redeemed.eq(false))
+            .filter(fees::dead_lettered_at.is_not_null())
+            .count()
+            .get_result(conn)
+            .map_err(FeeSweeperError::db("failed to query dead-lettered fees"))?;
+
+        println!("unredeemed fees:     {n_open}");
+        println!(
+            "oldest unredeemed:   {}",
+            oldest.map(format_time).unwrap_or("none".to_string())
+        );
+        println!("redemptions running: {n_redeeming}");
+        println!("dead-lettered fees:  {n_dead_lettered}");
+
+        // Break fees down by their place in the redemption state machine
+        let states: Vec<(String, i64)> = fees::table
+            .filter(fees::abandoned_at.is_null())
+            .group_by(fees::redemption_state)
+            .select((fees::redemption_state, count(fees::id)))
+            .order_by(fees::redemption_state)
+            .load(conn)
+            .map_err(FeeSweeperError::db("failed to query redemption states"))?;
+        println!();
+        for (state, n_fees) in states {
+            println!("{:<20} {n_fees}", format!("{state} fees:"));
+        }
+        Ok(())
+    })
+}
+
+/// Format a timestamp for display
+fn format_time(time: NaiveDateTime) -> String {
+    time.format("%Y-%m-%d %H:%M:%S").to_string()
+}
@@ -0,0 +1,477 @@
+//! A support bundle: a single tarball of what is needed to debug a sweeper
+//! incident, for attaching to a bug report
+//!
+//! The bundle holds the sanitized configuration of the instance, the
+//! summaries of its recent runs, its recent failed redemptions and audit
+//! events, the version of its schema, and key metrics. It reads only the
+//! database, and the metrics endpoint of a running instance if given, so it
+//! needs no keys.
+//!
+//! Secrets must never leave in a bundle. Config values under names that look
+//! secret are redacted, and every url in the bundle is cut to its host, since
+//! providers embed API keys in url paths and databases take passwords in url
+//! credentials
+
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use bigdecimal::BigDecimal;
+use chrono::{Days, NaiveDateTime, Utc};
+use clap::Args;
+use diesel::{
+    dsl::{count, min, sum},
+    Connection, ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl,
+};
+use diesel_migrations::MigrationHarness;
+use flate2::{write::GzEncoder, Compression};
+use reqwest::Url;
+use serde_json::{json, Value};
+use tar::{Builder, Header};
+
+use crate::db::{
+    audit::get_recent_audit_events,
+    feature_flags::get_feature_flags,
+    leases::get_lease,
+    metadata::get_metadata,
+    migrations::MIGRATIONS,
+    run_summaries::get_run_summaries,
+    schema::{fees, gas_spend},
+    task_failures::get_task_failures,
+};
+use crate::error::FeeSweeperError;
+use crate::indexer::queries::LAST_INDEXED_BLOCK_KEY;
+use crate::lease::SWEEPER_LEASE;
+
+/// The most records of each kind included in a bundle
+const MAX_BUNDLE_RECORDS: i64 = 1000;
+/// The text replacing a redacted value
+const REDACTED: &str = "<redacted>";
+/// The fragments of config names whose values are redacted, matched against
+/// the lowercased name with dashes as underscores
+const SECRET_NAME_FRAGMENTS: &[&str] = &[
+    "key",
+    "secret",
+    "password",
+    "token",
+    "credential",
+    "mnemonic",
+    "auth",
+    "db_url",
+];
+/// The characters delimiting a url embedded in text, besides whitespace
+const URL_DELIMITERS: &str = "\"'`=,;()[]<>";
+
+/// The arguments to the `support-bundle` command
+#[derive(Debug, Args)]
+pub struct SupportBundleArgs {
+    /// The database url
+    #[clap(long, env = "DB_URL", hide_env_values = true)]
+    pub db_url: String,
+    /// A config file of the instance to include, sanitized, e.g. its env file
+    /// or arguments; may be given several times
+    #[clap(long = "config")]
+    pub configs: Vec<PathBuf>,
+    /// The url of a running instance's metrics endpoint, scraped into the
+    /// bundle if given
+    #[clap(long)]
+    pub metrics_url: Option<String>,
+    /// The number of days of runs, failures, and audit events included
+    #[clap(long, default_value_t = 7)]
+    pub days: u64,
+    /// The file to write the bundle to; `support-bundle-<time>.tar.gz` in the
+    /// working directory if unset
+    #[clap(long)]
+    pub out: Option<PathBuf>,
+}
+
+/// Run the `support-bundle` command
+pub async fn run_support_bundle(args: SupportBundleArgs) -> Result<(), FeeSweeperError> {
+    let mut conn = PgConnection::establish(&args.db_url)
+        .map_err(FeeSweeperError::db("failed to connect to db"))?;
+    let now = Utc::now().naive_utc();
+    let since = now.checked_sub_days(Days::new(args.days)).ok_or_else(|| {
+        FeeSweeperError::Invalid(format!("invalid number of days: {}", args.days))
+    })?;
+
+    let mut files: Vec<(String, Vec<u8>)> = Vec::new();
+    for path in args.configs.iter() {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .ok_or_else(|| {
+                FeeSweeperError::Invalid(format!("invalid config path: {}", path.display()))
+            })?;
+        files.push((
+            format!("config/{name}"),
+            sanitize_config(path)?.into_bytes(),
+        ));
+    }
+    files.push(to_json_file(
+        "config/feature_flags.json",
+        &collect_feature_flags(&mut conn)?,
+    )?);
+    files.push(to_json_file("schema.json", &collect_schema(&mut conn)?)?);
+    files.push(to_json_file("runs.json", &collect_runs(&mut conn, since)?)?);
+    files.push(to_json_file(
+        "failures.json",
+        &collect_failures(&mut conn, since)?,
+    )?);
+    files.push(to_json_file(
+        "audit.json",
+        &collect_audit_events(&mut conn, since)?,
+    )?);
+    files.push(to_json_file("metrics.json", &collect_metrics(&mut conn)?)?);
+    if let Some(url) = &args.metrics_url {
+        files.push((
+            "metrics.prom".to_string(),
+            scrape_metrics(url).await?.into_bytes(),
+        ));
+    }
+
+    let manifest = json!({
+        "generated_at": now,
+        "sweeper_version": env!("CARGO_PKG_VERSION"),
+        "since": since,
+        "files": files.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+    });
+    files.insert(0, to_json_file("manifest.json", &manifest)?);
+
+    let out = args.out.unwrap_or_else(|| {
+        PathBuf::from(format!(
+            "support-bundle-{}.tar.gz",
+            now.format("%Y%m%d-%H%M%S")
+        ))
+    });
+    write_bundle(&out, &files, now)?;
+    println!("wrote support bundle to {}", out.display());
+    Ok(())
+}
+
+// --------------
+// | Collection |
+// --------------
+
+/// Collect the feature flags set in the DB
+fn collect_feature_flags(conn: &mut PgConnection) -> Result<Value, FeeSweeperError> {
+    let flags: Vec<Value> = get_feature_flags(conn)?
+        .into_iter()
+        .map(|flag| {
+            json!({
+                "name": flag.name,
+                "enabled": flag.enabled,
+                "updated_at": flag.updated_at,
+            })
+        })
+        .collect();
+    Ok(Value::Array(flags))
+}
+
+/// Collect the migrations applied to the DB, and those embedded in this
+/// binary but not yet applied
+fn collect_schema(conn: &mut PgConnection) -> Result<Value, FeeSweeperError> {
+    let mut applied: Vec<String> = conn
+        .applied_migrations()
+        .map_err(FeeSweeperError::db("failed to query applied migrations"))?
+        .into_iter()
+        .map(|version| version.to_string())
+        .collect();
+    applied.sort();
+    let pending: Vec<String> = conn
+        .pending_migrations(MIGRATIONS)
+        .map_err(FeeSweeperError::db("failed to query pending migrations"))?
+        .iter()
+        .map(|migration| migration.name().to_string())
+        .collect();
+
+    Ok(json!({
+        "version": applied.last(),
+        "applied": applied,
+        "pending": pending,
+    }))
+}
+
+/// Collect the summaries of the runs finished since the given time
+fn collect_runs(conn: &mut PgConnection, since: NaiveDateTime) -> Result<Value, FeeSweeperError> {
+    let runs: Vec<Value> = get_run_summaries(conn, since, MAX_BUNDLE_RECORDS)?
+        .into_iter()
+        .map(|run| {
+            json!({
+                "run_id": run.run_id.to_string(),
+                "started_at": run.started_at,
+                "finished_at": run.finished_at,
+                "summary": run.summary,
+            })
+        })
+        .collect();
+    Ok(Value::Array(runs))
+}
+
+/// Collect the failed redemptions since the given time, with their relayer
+/// payloads
+fn collect_failures(
+    conn: &mut PgConnection,
+    since: NaiveDateTime,
+) -> Result<Value, FeeSweeperError> {
+    let failures: Vec<Value> = get_task_failures(conn, None, MAX_BUNDLE_RECORDS)?
+        .into_iter()
+        .filter(|failure| failure.failed_at >= since)
+        .map(|failure| {
+            json!({
+                "id": failure.id,
+                "tx_hash": failure.tx_hash,
+                "wallet_id": failure.wallet_id.to_string(),
+                "task_id": failure.task_id.map(|id| id.to_string()),
+                "reason": scrub_urls(&failure.reason),
+                "payload": failure.payload.as_deref().map(scrub_urls),
+                "failed_at": failure.failed_at,
+            })
+        })
+        .collect();
+    Ok(Value::Array(failures))
+}
+
+/// Collect the audit events recorded since the given time
+fn collect_audit_events(
+    conn: &mut PgConnection,
+    since: NaiveDateTime,
+) -> Result<Value, FeeSweeperError> {
+    let events: Vec<Value> = get_recent_audit_events(conn, since, MAX_BUNDLE_RECORDS)?
+        .into_iter()
+        .map(|event| {
+            json!({
+                "event_type": event.event_type,
+                "tx_hash": event.tx_hash,
+                "details": scrub_urls(&event.details),
+                "created_at": event.created_at,
+            })
+        })
+        .collect();
+    Ok(Value::Array(events))
+}
+
+/// Collect the key metrics of the sweeper: how far it has indexed, which
+/// instance holds the lease, where its fees stand, and the gas it has spent
+fn collect_metrics(conn: &mut PgConnection) -> Result<Value, FeeSweeperError> {
+    let last_indexed_block = get_metadata(conn, LAST_INDEXED_BLOCK_KEY)?;
+    let lease = get_lease(conn, SWEEPER_LEASE)?.map(|lease| {
+        json!({
+            "holder": lease.holder.to_string(),
+            "acquired_at": lease.acquired_at,
+            "expires_at": lease.expires_at,
+        })
+    });
+
+    let oldest_unredeemed: Option<NaiveDateTime> = fees::table
+        .filter(fees::redeemed.eq(false))
+        .filter(fees::abandoned_at.is_null())
+        .filter(fees::dead_lettered_at.is_null())
+        .select(min(fees::block_timestamp))
+        .first(conn)
+        .map_err(FeeSweeperError::db("failed to query oldest unredeemed fee"))?;
+    let n_dead_lettered: i64 = fees::table
+        .filter(fees::redeemed.eq(false))
+        .filter(fees::dead_lettered_at.is_not_null())
+        .count()
+        .get_result(conn)
+        .map_err(FeeSweeperError::db("failed to query dead-lettered fees"))?;
+    let n_abandoned: i64 = fees::table
+        .filter(fees::abandoned_at.is_not_null())
+        .count()
+        .get_result(conn)
+        .map_err(FeeSweeperError::db("failed to query abandoned fees"))?;
+
+    let states: Vec<(String, i64)> = fees::table
+        .filter(fees::abandoned_at.is_null())
+        .group_by(fees::redemption_state)
+        .select((fees::redemption_state, count(fees::id)))
+        .order_by(fees::redemption_state)
+        .load(conn)
+        .map_err(FeeSweeperError::db("failed to query redemption states"))?;
+    let kinds: Vec<(String, bool, i64, Option<BigDecimal>)> = fees::table
+        .filter(fees::abandoned_at.is_null())
+        .group_by((fees::fee_kind, fees::redeemed))
+        .select((
+            fees::fee_kind,
+            fees::redeemed,
+            count(fees::id),
+            sum(fees::usd_value),
+        ))
+        .order_by((fees::fee_kind, fees::redeemed))
+        .load(conn)
+        .map_err(FeeSweeperError::db("failed to query fee stats"))?;
+    let gas: Vec<(String, i64, Option<BigDecimal>)> = gas_spend::table
+        .group_by(gas_spend::purpose)
+        .select((
+            gas_spend::purpose,
+            count(gas_spend::id),
+            sum(gas_spend::wei_spent),
+        ))
+        .order(gas_spend::purpose.asc())
+        .load(conn)
+        .map_err(FeeSweeperError::db("failed to query gas stats"))?;
+
+    Ok(json!({
+        "last_indexed_block": last_indexed_block,
+        "sweeper_lease": lease,
+        "oldest_unredeemed_fee": oldest_unredeemed,
+        "dead_lettered_fees": n_dead_lettered,
+        "abandoned_fees": n_abandoned,
+        "fees_by_redemption_state": states
+            .into_iter()
+            .map(|(state, n_fees)| json!({ "state": state, "count": n_fees }))
+            .collect::<Vec<_>>(),
+        "fees_by_kind": kinds
+            .into_iter()
+            .map(|(kind, redeemed, n_fees, value)| {
+                json!({ "kind": kind, "redeemed": redeemed, "count": n_fees, "usd_value": value })
+            })
+            .collect::<Vec<_>>(),
+        "gas_by_purpose": gas
+            .into_iter()
+            .map(|(purpose, n_txs, wei)| json!({ "purpose": purpose, "txs": n_txs, "wei": wei }))
+            .collect::<Vec<_>>(),
+    }))
+}
+
+/// Scrape the metrics endpoint of a running instance
+async fn scrape_metrics(url: &str) -> Result<String, FeeSweeperError> {
+    reqwest::get(url)
+        .await
+        .and_then(|resp| resp.error_for_status())
+        .map_err(FeeSweeperError::rpc("failed to scrape metrics"))?
+        .text()
+        .await
+        .map_err(FeeSweeperError::rpc("failed to read metrics"))
+}
+
+// ----------------
+// | Sanitization |
+// ----------------
+
+/// Read a config file, redacting the values of secret-looking names and
+/// cutting urls to their host
+///
+/// Each line is taken as a name and a value, in any of the forms
+/// `NAME=value`, `export NAME=value`, `name = value`, `name: value`, or
+/// `--name value`
+fn sanitize_config(path: &Path) -> Result<String, FeeSweeperError> {
+    let contents = fs::read_to_string(path).map_err(|e| {
+        FeeSweeperError::Config(format!("failed to read config {}: {e}", path.display()))
+    })?;
+
+    let mut sanitized = String::with_capacity(contents.len());
+    for line in contents.lines() {
+        sanitized.push_str(&sanitize_config_line(line));
+        sanitized.push('\n');
+    }
+    Ok(sanitized)
+}
+
+/// Sanitize a line of a config file
+fn sanitize_config_line(line: &str) -> String {
+    let is_separator = |c: char| c == '=' || c == ':' || c.is_whitespace();
+    let trimmed = line.trim_start();
+    let body = trimmed.strip_prefix("export ").unwrap_or(trimmed);
+    let prefix = &line[..line.len() - body.len()];
+    let name_end = body.find(is_separator).unwrap_or(body.len());
+    let (name, rest) = body.split_at(name_end);
+    let value = rest.trim_start_matches(is_separator);
+    let separator = &rest[..rest.len() - value.len()];
+
+    let name_lower = name.to_lowercase().replace('-', "_");
+    if value.is_empty() || !SECRET_NAME_FRAGMENTS.iter().any(|f| name_lower.contains(f)) {
+        return scrub_urls(line);
+    }
+    format!("{prefix}{name}{separator}{REDACTED}")
+}
+
+/// Cut every url embedded in a text to its scheme and host
+fn scrub_urls(text: &str) -> String {
+    let is_delimiter = |c: char| c.is_ascii_whitespace() || URL_DELIMITERS.contains(c);
+
+    let mut scrubbed = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(i) = rest.find("://") {
+        // The delimiters are ASCII, so the url starts one byte past the last
+        let start = rest[..i].rfind(is_delimiter).map_or(0, |j| j + 1);
+        let end = rest[i..].find(is_delimiter).map_or(rest.len(), |j| i + j);
+        scrubbed.push_str(&rest[..start]);
+        scrubbed.push_str(&scrub_url(&rest[start..end]));
+        rest = &rest[end..];
+    }
+
+    scrubbed.push_str(rest);
+    scrubbed
+}
+
+/// Cut a url to its scheme, host, and port, marking the redaction of any
+/// credentials, path, or query
+fn scrub_url(url: &str) -> String {
+    let parsed = match Url::parse(url) {
+        Ok(parsed) => parsed,
+        Err(_) => return REDACTED.to_string(),
+    };
+
+    let host = parsed.host_str().unwrap_or_default();
+    let port = parsed
+        .port()
+        .map(|port| format!(":{port}"))
+        .unwrap_or_default();
+    let hidden = !parsed.username().is_empty()
+        || parsed.password().is_some()
+        || !matches!(parsed.path(), "" | "/")
+        || parsed.query().is_some();
+    let suffix = if hidden {
+        format!("/{REDACTED}")
+    } else {
+        String::new()
+    };
+    format!("{}://{host}{port}{suffix}", parsed.scheme())
+}
+
+// -----------
+// | Tarball |
+// -----------
+
+/// Serialize a value as a pretty-printed JSON file of the bundle
+fn to_json_file(name: &str, value: &Value) -> Result<(String, Vec<u8>), FeeSweeperError> {
+    let contents = serde_json::to_vec_pretty(value)
+        .map_err(|e| FeeSweeperError::Other(format!("failed to serialize {name}: {e}")))?;
+    Ok((name.to_string(), contents))
+}
+
+/// Write the files of the bundle to a gzipped tarball, under a directory
+/// named for the bundle
+fn write_bundle(
+    out: &Path,
+    files: &[(String, Vec<u8>)],
+    now: NaiveDateTime,
+) -> Result<(), FeeSweeperError> {
+    let root = out
+        .file_name()
+        .map(|name| {
+            name.to_string_lossy()
+                .trim_end_matches(".tar.gz")
+                .to_string()
+        })
+        .unwrap_or_else(|| "support-bundle".to_string());
+
+    let file = File::create(out)
+        .map_err(|e| FeeSweeperError::Other(format!("failed to create {}: {e}", out.display())))?;
+    let mut builder = Builder::new(GzEncoder::new(file, Compression::default()));
+    for (name, contents) in files {
+        let mut header = Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(now.and_utc().timestamp() as u64);
+        builder
+            .append_data(&mut header, format!("{root}/{name}"), contents.as_slice())
+            .map_err(|e| FeeSweeperError::Other(format!("failed to add {name} to bundle: {e}")))?;
+    }
+
+    builder
+        .into_inner()
+        .and_then(|encoder| encoder.finish())
+        .map_err(FeeSweeperError::other("failed to write bundle"))?;
+    Ok(())
+}
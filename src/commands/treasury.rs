@@ -0,0 +1,286 @@
+//! Forwards funds held by the submission key to their treasury addresses
+//!
+//! With several keys, the first also funds the gas of the others, topping up
+//! any key below the configured minimum before the keys forward their funds
+
+use std::{collections::BTreeSet, str::FromStr, sync::Arc};
+
+use clap::Args;
+use diesel::{Connection, PgConnection, QueryDsl, RunQueryDsl};
+use ethers::middleware::Middleware;
+use ethers::types::{Address, Eip1559TransactionRequest, U256};
+use ethers::utils::{format_ether, parse_ether};
+use futures::future::join_all;
+use tracing::{error, info, warn};
+
+use crate::{
+    db::{
+        audit::{record_audit_event, TREASURY_TRANSFER_EVENT},
+        fund_movements::{record_fund_movement, TREASURY_TRANSFER_MOVEMENT},
+        gas_ledger::{record_gas_spend, GasPurpose},
+        models::{u256_to_decimal, NewAuditEvent},
+        schema::fees::dsl::{fees as fees_table, mint as mint_col},
+    },
+    erc20::{Erc20, SignerClient},
+    error::FeeSweeperError,
+    gas::{FeeEstimator, GasArgs},
+    invariants::{FundMovement, InvariantArgs, InvariantSet},
+    signer::load_signers,
+    submitter::SubmitterPool,
+    treasury::{TreasuryRoute, TreasuryRouter},
+};
+
+/// The arguments to the `treasury-transfer` command
+#[derive(Debug, Args)]
+pub struct TreasuryTransferArgs {
+    /// The Arbitrum RPC url to use
+    #[clap(short, long, env = "RPC_URL", hide_env_values = true)]
+    pub rpc_url: String,
+    /// An arbitrum private key holding withdrawn funds; may be given several
+    /// times, or as a comma-separated list, and each key forwards its own
+    /// balances, concurrently with the others
+    #[clap(
+        long = "pkey",
+        required_unless_present = "kms_key_ids",
+        env = "ARB_PRIVATE_KEY",
+        hide_env_values = true,
+        value_delimiter = ','
+    )]
+    pub arbitrum_private_keys: Vec<String>,
+    /// The id or ARN of an AWS KMS key holding withdrawn funds, forwarding
+    /// them alongside any private keys
+    #[clap(long = "kms-key-id")]
+    pub kms_key_ids: Vec<String>,
+    /// The database url
+    #[clap(long, env = "DB_URL", hide_env_values = true)]
+    pub db_url: String,
+    /// A per-mint treasury route, of the form `<mint>=<destination>`
+    #[clap(long = "treasury-route")]
+    pub routes: Vec<TreasuryRoute>,
+    /// The treasury address of mints without a route
+    #[clap(long)]
+    pub default_treasury: Option<Address>,
+    /// An address that funds may be sent to; every route must target one
+    #[clap(long = "treasury-allowlist", required = true)]
+    pub allowlist: Vec<Address>,
+    /// The minimum ETH balance each key beyond the first must hold for gas;
+    /// keys below it are topped up to it by the first key
+    #[clap(long)]
+    pub min_gas_balance_eth: Option<f64>,
+    /// Log the transfers that would be made without submitting them
+    #[clap(long)]
+    pub dry_run: bool,
+    /// The fee estimation configuration
+    #[clap(flatten)]
+    pub gas: GasArgs,
+    /// The invariants checked before each transfer
+    #[clap(flatten)]
+    pub invariants: InvariantArgs,
+}
+
+/// Run the `treasury-transfer` command
+pub async fn run_treasury_transfer(args: TreasuryTransferArgs) -> Result<(), FeeSweeperError> {
+    let router = TreasuryRouter::new(args.routes, args.default_treasury, &args.allowlist)?;
+    let invariants = InvariantSet::new(&args.allowlist, &args.invariants)?;
+    let mut conn = PgConnection::establish(&args.db_url)
+        .map_err(FeeSweeperError::db("failed to connect to db"))?;
+    let signers = load_signers(&args.arbitrum_private_keys, &args.kms_key_ids).await?;
+    let pool = SubmitterPool::new(&args.rpc_url, signers).await?;
+    if let Some(min_balance) = args.min_gas_balance_eth {
+        top_up_submitters(&pool, &args.gas, &mut conn, min_balance, args.dry_run).await?;
+    }
+
+    // Consider every mint the protocol has earned fees in, along with any routed mints
+    let fee_mints: Vec<String> = fees_table
+        .select(mint_col)
+        .distinct()
+        .load(&mut conn)
+        .map_err(FeeSweeperError::db("failed to query fee mints"))?;
+    let mut mints: BTreeSet<Address> = router.routed_mints().copied().collect();
+    for mint in fee_mints.iter() {
+        match Address::from_str(mint) {
+            Ok(addr) => {
+                mints.insert(addr);
+            }
+            Err(e) => warn!("skipping invalid mint {mint}: {e}"),
+        }
+    }
+
+    let mut transfers = Vec::new();
+    for mint in mints {
+        match router.destination(&mint) {
+            Some(destination) => transfers.push((mint, destination)),
+            None => warn!("no treasury route for {mint:#x}, skipping"),
+        }
+    }
+
+    // Each key works through its own transfers, so that a key has at most one
+    // transaction in flight
+    let sweeps = pool
+        .clients()
+        .iter()
+        .map(|client| sweep_submitter(client.clone(), &args, &invariants, &transfers));
+    // Every key's failure is logged, and the first returned
+    let mut first_failure = None;
+    for (client, res) in pool.clients().iter().zip(join_all(sweeps).await) {
+        if let Err(e) = res {
+            error!("treasury transfer from {:#x} failed: {e}", client.address());
+            first_failure.get_or_insert(e);
+        }
+    }
+
+    first_failure.map_or(Ok(()), Err)
+}
+
+/// Top up the gas of every key beyond the pool's first to the minimum balance,
+/// funded by the first key
+async fn top_up_submitters(
+    pool: &SubmitterPool,
+    gas: &GasArgs,
+    conn: &mut PgConnection,
+    min_balance_eth: f64,
+    dry_run: bool,
+) -> Result<(), FeeSweeperError> {
+    let min_balance =
+        parse_ether(min_balance_eth).map_err(FeeSweeperError::config("invalid balance"))?;
+    let Some((funder, keys)) = pool.clients().split_first() else {
+        return Ok(());
+    };
+    let fees = FeeEstimator::new(funder.clone(), gas)?;
+
+    for key in keys {
+        let address = key.address();
+        let balance = funder
+            .get_balance(address, None /* block */)
+            .await
+            .map_err(FeeSweeperError::rpc("failed to query balance"))?;
+        if balance >= min_balance {
+            continue;
+        }
+
+        let amount = min_balance - balance;
+        info!(
+            "topping up {address:#x} with {} ETH from {:#x}",
+            format_ether(amount),
+            funder.address()
+        );
+        if dry_run {
+            continue;
+        }
+
+        let (max_fee, priority_fee) = fees.estimate().await?;
+        let tx = Eip1559TransactionRequest::new()
+            .to(address)
+            .value(amount)
+            .max_fee_per_gas(max_fee)
+            .max_priority_fee_per_gas(priority_fee);
+        let receipt = funder
+            .send_transaction(tx, None /* block */)
+            .await
+            .map_err(FeeSweeperError::rpc("failed to send top-up"))?
+            .await
+            .map_err(FeeSweeperError::rpc("failed to await top-up"))?
+            .ok_or_else(|| FeeSweeperError::Rpc("top-up transaction dropped".to_string()))?;
+        record_gas_spend(conn, GasPurpose::TopUp, &receipt)?;
+        if receipt.status != Some(1u64.into()) {
+            return Err(FeeSweeperError::Rpc(format!(
+                "top-up reverted: {:#x}",
+                receipt.transaction_hash
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Forward a single submitter key's balances to their treasury addresses
+async fn sweep_submitter(
+    client: Arc<SignerClient>,
+    args: &TreasuryTransferArgs,
+    invariants: &InvariantSet,
+    transfers: &[(Address, Address)],
+) -> Result<(), FeeSweeperError> {
+    let mut conn = PgConnection::establish(&args.db_url)
+        .map_err(FeeSweeperError::db("failed to connect to db"))?;
+    let fees = FeeEstimator::new(client.clone(), &args.gas)?;
+
+    for (mint, destination) in transfers.iter().copied() {
+        transfer_balance(
+            &client,
+            &fees,
+            invariants,
+            &mut conn,
+            mint,
+            destination,
+            args.dry_run,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Transfer the submission key's full balance of a mint to its treasury
+async fn transfer_balance(
+    client: &Arc<SignerClient>,
+    fees: &FeeEstimator,
+    invariants: &InvariantSet,
+    conn: &mut PgConnection,
+    mint: Address,
+    destination: Address,
+    dry_run: bool,
+) -> Result<(), FeeSweeperError> {
+    let token = Erc20::new(mint, client.clone());
+    let balance = token
+        .balance_of(client.address())
+        .call()
+        .await
+        .map_err(FeeSweeperError::rpc("failed to query balance"))?;
+    if balance == U256::zero() {
+        return Ok(());
+    }
+
+    let movement = FundMovement {
+        kind: "treasury transfer",
+        mint,
+        amount: balance,
+        destination,
+    };
+    invariants.check(conn, &movement)?;
+
+    info!("transferring {balance} of {mint:#x} to treasury {destination:#x}");
+    if dry_run {
+        return Ok(());
+    }
+
+    let call = fees.apply(token.transfer(destination, balance)).await?;
+    let pending = call
+        .send()
+        .await
+        .map_err(FeeSweeperError::rpc("failed to send transfer"))?;
+    let receipt = pending
+        .await
+        .map_err(FeeSweeperError::rpc("failed to await transfer"))?
+        .ok_or_else(|| FeeSweeperError::Rpc("transfer transaction dropped".to_string()))?;
+    record_gas_spend(conn, GasPurpose::Withdrawal, &receipt)?;
+    if receipt.status != Some(1u64.into()) {
+        return Err(FeeSweeperError::Rpc(format!(
+            "transfer reverted: {:#x}",
+            receipt.transaction_hash
+        )));
+    }
+
+    let tx = format!("{:#x}", receipt.transaction_hash);
+    record_fund_movement(
+        conn,
+        TREASURY_TRANSFER_MOVEMENT,
+        &format!("{mint:#x}"),
+        u256_to_decimal(balance),
+        &tx,
+    )?;
+    let details = format!("transferred {balance} of {mint:#x} to {destination:#x}");
+    record_audit_event(
+        conn,
+        NewAuditEvent::new(TREASURY_TRANSFER_EVENT, Some(tx), details),
+    )
+}
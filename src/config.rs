@@ -0,0 +1,214 @@
+//! The config file of the sweeper
+//!
+//! `--config <path.toml>`, given before the command, loads the command's
+//! settings from a TOML file. The file's keys are the long names of the
+//! command's flags, and may be grouped into tables by topic, e.g. `[chain]`,
+//! `[relayer]`, `[db]`, or `[telemetry]`, which only organize the file:
+//!
+//! ```toml
+//! chain = "mainnet"
+//!
+//! [db]
+//! db-url = "postgres://sweeper@db/fees"
+//! db-pool-size = 8
+//!
+//! [relayer]
+//! relayer-url = "https://relayer.example"
+//! mint-threshold = ["0x...:1000000", "0x...:500000"]
+//! ```
+//!
+//! Each setting becomes the default of its flag, and is parsed and validated
+//! by the flag itself, so that the file and the command line never drift
+//! apart. A flag given on the command line, or through its environment
+//! variable, overrides the file. A flag's variable is `FEE_SWEEPER_<FLAG>`
+//! unless the flag names its own, as the secrets do, e.g. `DB_URL`, and the
+//! values of the variables are never shown in help. A key naming no flag of
+//! the command is rejected, so that a misspelled setting never silently falls
+//! back to its default
+
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::{Arg, ArgAction, Command, Parser};
+use toml::{Table, Value};
+
+use crate::error::FeeSweeperError;
+
+/// The flag naming the config file
+const CONFIG_FLAG: &str = "--config";
+/// The prefix of the environment variables of the flags
+const ENV_PREFIX: &str = "FEE_SWEEPER_";
+
+/// The settings of a config file, keyed by the long name of their flag
+#[derive(Clone, Debug, Default)]
+pub struct SweeperConfig {
+    /// The values of each setting, as they would be given on the command line
+    settings: BTreeMap<String, Vec<String>>,
+}
+
+impl SweeperConfig {
+    /// Load a config file
+    pub fn load(path: &Path) -> Result<Self, FeeSweeperError> {
+        let contents = fs::read_to_string(path).map_err(|e| {
+            FeeSweeperError::Config(format!("failed to read config {}: {e}", path.display()))
+        })?;
+        let table: Table = toml::from_str(&contents).map_err(|e| {
+            FeeSweeperError::Config(format!("invalid config {}: {e}", path.display()))
+        })?;
+
+        let mut config = SweeperConfig::default();
+        for (key, value) in table {
+            match value {
+                Value::Table(section) => {
+                    for (key, value) in section {
+                        config.insert(key, value)?;
+                    }
+                }
+                value => config.insert(key, value)?,
+            }
+        }
+        Ok(config)
+    }
+
+    /// Add a setting, checking that it is not given twice
+    fn insert(&mut self, key: String, value: Value) -> Result<(), FeeSweeperError> {
+        let flag = key.replace('_', "-");
+        let values = match value {
+            Value::Array(items) => items
+                .into_iter()
+                .map(|item| setting_value(&key, item))
+                .collect::<Result<_, _>>()?,
+            value => vec![setting_value(&key, value)?],
+        };
+
+        if self.settings.insert(flag, values).is_some() {
+            return Err(FeeSweeperError::Config(format!(
+                "setting {key} is given more than once"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Make each setting the default of its flag in the given command
+    fn apply(self, mut command: Command) -> Result<Command, FeeSweeperError> {
+        for (flag, values) in self.settings {
+            let arg = command
+                .get_arguments()
+                .find(|arg| arg.get_long() == Some(flag.as_str()))
+                .ok_or_else(|| {
+                    format!("setting {flag} is not a flag of `{}`", command.get_name())
+                })?;
+            if values.len() != 1 && !matches!(arg.get_action(), ArgAction::Append) {
+                return Err(FeeSweeperError::Config(format!(
+                    "setting {flag} takes a single value"
+                )));
+            }
+
+            // A flag required on the command line is satisfied by the file
+            let id = arg.get_id().clone();
+            command = command.mut_arg(id, |arg| arg.required(false).default_values(values));
+        }
+        Ok(command)
+    }
+}
+
+/// Render a setting's value as it would be given on the command line
+fn setting_value(key: &str, value: Value) -> Result<String, FeeSweeperError> {
+    match value {
+        Value::String(s) => Ok(s),
+        Value::Integer(i) => Ok(i.to_string()),
+        Value::Float(f) => Ok(f.to_string()),
+        Value::Boolean(b) => Ok(b.to_string()),
+        Value::Datetime(d) => Ok(d.to_string()),
+        Value::Array(_) | Value::Table(_) => Err(FeeSweeperError::Config(format!(
+            "setting {key} is nested too deeply"
+        ))),
+    }
+}
+
+/// Parse the command line, taking defaults from the config file if one is
+/// given, and from the environment
+pub fn parse_with_config<P: Parser>() -> Result<P, FeeSweeperError> {
+    let args: Vec<OsString> = env::args_os().collect();
+    let mut command = with_env_overrides(P::command());
+
+    let (path, subcommand) = scan_args(&args);
+    if let Some(path) = path {
+        let config = SweeperConfig::load(&path)?;
+        let sub = subcommand
+            .as_deref()
+            .and_then(|name| find_subcommand(&command, name))
+            .ok_or_else(|| {
+                FeeSweeperError::Config("a config file requires a command to configure".to_string())
+            })?;
+
+        // Apply the config outside of the builder, whose callbacks may not fail
+        let name = sub.get_name().to_string();
+        let sub = config.apply(sub)?;
+        command = command.mut_subcommand(name, |_| sub);
+    }
+
+    let matches = command.get_matches_from(args);
+    P::from_arg_matches(&matches).map_err(FeeSweeperError::config("invalid arguments"))
+}
+
+/// Find the command given on the command line, preferring a command of that
+/// name to one with it as an alias
+fn find_subcommand(command: &Command, name: &str) -> Option<Command> {
+    command
+        .get_subcommands()
+        .find(|sub| sub.get_name() == name)
+        .or_else(|| command.find_subcommand(name))
+        .cloned()
+}
+
+/// Give every flag of each command without an environment variable the
+/// variable `FEE_SWEEPER_<FLAG>`
+fn with_env_overrides(mut command: Command) -> Command {
+    let names: Vec<String> = command
+        .get_subcommands()
+        .map(|sub| sub.get_name().to_string())
+        .collect();
+    for name in names {
+        command = command.mut_subcommand(name, |sub| sub.mut_args(with_env));
+    }
+    command
+}
+
+/// Give a flag its environment variable, unless it has one, hiding the
+/// variable's value from help
+fn with_env(arg: Arg) -> Arg {
+    let takes_value = matches!(
+        arg.get_action(),
+        ArgAction::Set | ArgAction::Append | ArgAction::SetTrue
+    );
+    let env_var = match arg.get_long() {
+        Some(long) if takes_value && arg.get_env().is_none() => {
+            format!("{ENV_PREFIX}{}", long.to_uppercase().replace('-', "_"))
+        }
+        _ => return arg,
+    };
+    arg.env(env_var).hide_env_values(true)
+}
+
+/// Find the config file and the command in the arguments given before the
+/// command
+fn scan_args(args: &[OsString]) -> (Option<PathBuf>, Option<String>) {
+    let mut path = None;
+    let mut rest = args.iter().skip(1);
+    while let Some(arg) = rest.next() {
+        let arg = arg.to_string_lossy();
+        if arg == CONFIG_FLAG {
+            path = rest.next().map(PathBuf::from);
+        } else if let Some(value) = arg.strip_prefix("--config=") {
+            path = Some(PathBuf::from(value));
+        } else if !arg.starts_with('-') {
+            return (path, Some(arg.into_owned()));
+        }
+    }
+
+    (path, None)
+}
@@ -0,0 +1,56 @@
+//! Helpers for reviewing and restoring fees abandoned by the expiration policy
+
+use chrono::{NaiveDateTime, Utc};
+use diesel::{ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl, SelectableHelper};
+
+use crate::db::{
+    audit::{record_audit_event, FEE_RESTORED_EVENT},
+    models::{Fee, NewAuditEvent, RedemptionState},
+    schema::fees::dsl::{
+        abandoned_at as abandoned_at_col, dust as dust_col, fees as fees_table,
+        redemption_state as redemption_state_col,
+        redemption_state_changed_at as redemption_state_changed_at_col, status as status_col,
+        tx_hash as tx_hash_col,
+    },
+};
+use crate::error::FeeSweeperError;
+
+/// Get every abandoned fee, most recently abandoned first
+pub fn get_abandoned_fees(conn: &mut PgConnection) -> Result<Vec<Fee>, FeeSweeperError> {
+    fees_table
+        .filter(abandoned_at_col.is_not_null())
+        .order(abandoned_at_col.desc())
+        .select(Fee::as_select())
+        .load(conn)
+        .map_err(FeeSweeperError::db("failed to query abandoned fees"))
+}
+
+/// Restore an abandoned fee to the indexed state, clearing its dust flag so
+/// that it is redeemed and never abandoned again
+pub fn restore_fee(conn: &mut PgConnection, tx_hash: &str) -> Result<(), FeeSweeperError> {
+    let state = RedemptionState::Indexed;
+    let fee = fees_table
+        .filter(tx_hash_col.eq(tx_hash))
+        .filter(redemption_state_col.eq(RedemptionState::Abandoned.as_str()));
+    let restored = diesel::update(fee)
+        .set((
+            redemption_state_col.eq(state.as_str()),
+            redemption_state_changed_at_col.eq(Utc::now().naive_utc()),
+            status_col.eq(state.fee_status()),
+            abandoned_at_col.eq(None::<NaiveDateTime>),
+            dust_col.eq(false),
+        ))
+        .execute(conn)
+        .map_err(FeeSweeperError::db("failed to restore fee"))?;
+    if restored == 0 {
+        return Err(FeeSweeperError::Invalid(format!(
+            "fee from tx {tx_hash} is not abandoned"
+        )));
+    }
+
+    let details = "restored by an operator".to_string();
+    record_audit_event(
+        conn,
+        NewAuditEvent::new(FEE_RESTORED_EVENT, Some(tx_hash.to_string()), details),
+    )
+}
@@ -0,0 +1,38 @@
+//! Helpers for the admin API access log
+//!
+//! Every admin API request is recorded with its caller, route, parameters,
+//! and result. Entries are kept for a configured retention period, after
+//! which they are pruned
+
+use chrono::NaiveDateTime;
+use diesel::{ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl};
+
+use crate::db::{
+    models::NewAdminAccess,
+    schema::admin_access_log::dsl::{admin_access_log as access_log_table, created_at},
+};
+use crate::error::FeeSweeperError;
+
+/// Record an admin API request
+pub fn record_admin_access(
+    conn: &mut PgConnection,
+    access: NewAdminAccess,
+) -> Result<(), FeeSweeperError> {
+    diesel::insert_into(access_log_table)
+        .values(vec![access])
+        .execute(conn)
+        .map_err(FeeSweeperError::db("failed to record admin access"))
+        .map(|_| ())
+}
+
+/// Delete the access log entries recorded before `cutoff`
+///
+/// Returns the number of entries deleted
+pub fn prune_admin_access_log(
+    conn: &mut PgConnection,
+    cutoff: NaiveDateTime,
+) -> Result<usize, FeeSweeperError> {
+    diesel::delete(access_log_table.filter(created_at.lt(cutoff)))
+        .execute(conn)
+        .map_err(FeeSweeperError::db("failed to prune admin access log"))
+}
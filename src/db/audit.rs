@@ -0,0 +1,95 @@
+//! Helpers for writing to the audit log
+
+use chrono::NaiveDateTime;
+use diesel::{ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl, SelectableHelper};
+use tracing::info;
+
+use crate::db::models::{AuditEvent, NewAuditEvent};
+use crate::db::schema::audit_log::dsl::{
+    audit_log as audit_log_table, created_at as created_at_col, id as id_col,
+};
+use crate::error::FeeSweeperError;
+
+// ---------------
+// | Event Types |
+// ---------------
+
+/// The event type of an ERC-20 approval
+pub const TOKEN_APPROVAL_EVENT: &str = "token_approval";
+/// The event type of an ERC-20 approval revocation
+pub const TOKEN_REVOCATION_EVENT: &str = "token_revocation";
+/// The event type of a transfer of funds to a treasury address
+pub const TREASURY_TRANSFER_EVENT: &str = "treasury_transfer";
+/// The event type of a transfer initiated through the L1 bridge
+pub const BRIDGE_TRANSFER_EVENT: &str = "bridge_transfer";
+/// The event type of a bridge transfer claimed on L1
+pub const BRIDGE_CLAIM_EVENT: &str = "bridge_claim";
+/// The event type of a fee returned to the indexed state after its
+/// redemption was orphaned
+pub const ORPHANED_FEE_RECOVERY_EVENT: &str = "orphaned_fee_recovery";
+/// The event type of a failed attempt to redeem a fee
+pub const REDEMPTION_FAILURE_EVENT: &str = "redemption_failure";
+/// The event type of a fee dead-lettered after repeated redemption failures
+pub const DEAD_LETTER_EVENT: &str = "dead_letter";
+/// The event type of a pause of the darkpool
+pub const DARKPOOL_PAUSE_EVENT: &str = "darkpool_pause";
+/// The event type of an unpause of the darkpool
+pub const DARKPOOL_UNPAUSE_EVENT: &str = "darkpool_unpause";
+/// The event type of an upgrade of the darkpool's implementation
+pub const DARKPOOL_UPGRADE_EVENT: &str = "darkpool_upgrade";
+/// The event type of a quarantine of a suspected spam mint
+pub const MINT_QUARANTINE_EVENT: &str = "mint_quarantine";
+/// The event type of an operator's release of a quarantined mint
+pub const MINT_RELEASE_EVENT: &str = "mint_release";
+/// The event type of a rotation of the active signer
+pub const SIGNER_ROTATION_EVENT: &str = "signer_rotation";
+/// The event type of a fund movement refused for violating an invariant
+pub const INVARIANT_VIOLATION_EVENT: &str = "invariant_violation";
+/// The event type of a dust fee abandoned by the expiration policy
+pub const FEE_ABANDONED_EVENT: &str = "fee_abandoned";
+/// The event type of an operator's restoration of an abandoned fee
+pub const FEE_RESTORED_EVENT: &str = "fee_restored";
+/// The event type of an operator's move of a fee in the redemption queue
+pub const FEE_QUEUE_EVENT: &str = "fee_queue";
+/// The event type of an operator's recovery of a sweep wallet
+pub const WALLET_RECOVERY_EVENT: &str = "wallet_recovery";
+/// The event type of a backup of a new sweep wallet's recovery material
+pub const WALLET_BACKUP_EVENT: &str = "wallet_backup";
+/// The event type of an operator's change to a mint's price route
+pub const PRICE_ROUTE_EVENT: &str = "price_route";
+/// The event type of an operator's change to a feature flag
+pub const FEATURE_FLAG_EVENT: &str = "feature_flag";
+/// The event type of an operator's registration or removal of a decryption
+/// key
+pub const FEE_KEY_EVENT: &str = "fee_key";
+/// The event type of a settlement transaction whose fees were skipped for
+/// being blocklisted
+pub const SETTLEMENT_SKIPPED_EVENT: &str = "settlement_skipped";
+
+/// Record an event in the audit log
+pub fn record_audit_event(
+    conn: &mut PgConnection,
+    event: NewAuditEvent,
+) -> Result<(), FeeSweeperError> {
+    info!("audit: {}: {}", event.event_type, event.details);
+    diesel::insert_into(audit_log_table)
+        .values(vec![event])
+        .execute(conn)
+        .map_err(FeeSweeperError::db("failed to record audit event"))
+        .map(|_| ())
+}
+
+/// Get the events recorded since the given time, newest first
+pub fn get_recent_audit_events(
+    conn: &mut PgConnection,
+    since: NaiveDateTime,
+    limit: i64,
+) -> Result<Vec<AuditEvent>, FeeSweeperError> {
+    audit_log_table
+        .filter(created_at_col.ge(since))
+        .order(id_col.desc())
+        .limit(limit)
+        .select(AuditEvent::as_select())
+        .load(conn)
+        .map_err(FeeSweeperError::db("failed to query audit events"))
+}
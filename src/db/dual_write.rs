@@ -0,0 +1,107 @@
+//! Helpers for verifying the fee status during its schema migration
+//!
+//! A fee's status is recorded in legacy columns, the redeemed flag and the
+//! redemption, dead letter, and abandonment timestamps, and is being migrated
+//! to a single `status` column. Both are derived from the fee's redemption
+//! state on each transition; the verification below compares them, and the
+//! backfill populates the status of fees indexed before it was written
+
+use diesel::prelude::*;
+use diesel::sql_query;
+
+use crate::db::models::{
+    Fee, FEE_STATUS_ABANDONED, FEE_STATUS_DEAD_LETTERED, FEE_STATUS_OPEN, FEE_STATUS_REDEEMED,
+    FEE_STATUS_REDEEMING,
+};
+use crate::db::schema::fees::dsl::fees as fees_table;
+use crate::error::FeeSweeperError;
+
+/// The number of mismatched fees listed in full by a verification report
+pub const MAX_REPORTED_MISMATCHES: usize = 20;
+
+/// A fee whose dual-written status disagrees with its legacy columns
+#[derive(Debug)]
+pub struct FeeStatusMismatch {
+    /// The tx hash of the fee
+    pub tx_hash: String,
+    /// The status implied by the legacy columns
+    pub legacy: &'static str,
+    /// The status recorded in the new column
+    pub dual: String,
+}
+
+/// The result of comparing the two representations of fee status
+#[derive(Debug, Default)]
+pub struct FeeStatusReport {
+    /// The number of fees whose new status was compared
+    pub checked: usize,
+    /// The number of fees with no new status, written while dual-writing was
+    /// disabled
+    pub untracked: usize,
+    /// The fees whose representations disagree
+    pub mismatches: Vec<FeeStatusMismatch>,
+}
+
+impl FeeStatusReport {
+    /// Whether the representations agree on every tracked fee
+    pub fn is_consistent(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+/// Compare the new status of each tracked fee against its legacy columns
+pub fn verify_fee_status(conn: &mut PgConnection) -> Result<FeeStatusReport, FeeSweeperError> {
+    let fees: Vec<Fee> = fees_table
+        .select(Fee::as_select())
+        .load(conn)
+        .map_err(FeeSweeperError::db("failed to load fees"))?;
+
+    Ok(compare_fee_status(fees))
+}
+
+/// Compare the new status of each of the given fees against its legacy
+/// columns
+pub fn compare_fee_status(fees: Vec<Fee>) -> FeeStatusReport {
+    let mut report = FeeStatusReport::default();
+    for fee in fees {
+        let dual = match fee.status.clone() {
+            Some(status) => status,
+            None => {
+                report.untracked += 1;
+                continue;
+            }
+        };
+
+        report.checked += 1;
+        let legacy = fee.legacy_status();
+        if dual != legacy {
+            report.mismatches.push(FeeStatusMismatch {
+                tx_hash: fee.tx_hash,
+                legacy,
+                dual,
+            });
+        }
+    }
+
+    report
+}
+
+/// Populate the new status of untracked fees from their legacy columns
+///
+/// Returns the number of fees backfilled
+pub fn backfill_fee_status(conn: &mut PgConnection) -> Result<usize, FeeSweeperError> {
+    // Mirrors `Fee::legacy_status`
+    let query = format!(
+        "UPDATE fees SET status = CASE \
+            WHEN redeemed THEN '{FEE_STATUS_REDEEMED}' \
+            WHEN abandoned_at IS NOT NULL THEN '{FEE_STATUS_ABANDONED}' \
+            WHEN dead_lettered_at IS NOT NULL THEN '{FEE_STATUS_DEAD_LETTERED}' \
+            WHEN redemption_started_at IS NOT NULL THEN '{FEE_STATUS_REDEEMING}' \
+            ELSE '{FEE_STATUS_OPEN}' \
+        END \
+        WHERE status IS NULL"
+    );
+    sql_query(query)
+        .execute(conn)
+        .map_err(FeeSweeperError::db("failed to backfill fee status"))
+}
@@ -0,0 +1,58 @@
+//! Helpers for managing the feature flags stored in the DB
+
+use diesel::{
+    sql_types::{Bool, Text},
+    ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl, SelectableHelper,
+};
+
+use crate::db::{
+    audit::{record_audit_event, FEATURE_FLAG_EVENT},
+    models::{FeatureFlag, NewAuditEvent},
+    schema::feature_flags::dsl::{feature_flags as flags_table, name as name_col},
+};
+use crate::error::FeeSweeperError;
+
+/// The query upserting a feature flag
+const SET_FEATURE_FLAG_QUERY: &str = "INSERT INTO feature_flags (name, enabled) VALUES ($1, $2) \
+    ON CONFLICT (name) DO UPDATE SET enabled = $2, updated_at = NOW()";
+
+/// Get every feature flag stored in the DB, ordered by name
+pub fn get_feature_flags(conn: &mut PgConnection) -> Result<Vec<FeatureFlag>, FeeSweeperError> {
+    flags_table
+        .order(name_col.asc())
+        .select(FeatureFlag::as_select())
+        .load(conn)
+        .map_err(FeeSweeperError::db("failed to query feature flags"))
+}
+
+/// Enable or disable a feature flag
+pub fn set_feature_flag(
+    conn: &mut PgConnection,
+    name: &str,
+    enabled: bool,
+) -> Result<(), FeeSweeperError> {
+    diesel::sql_query(SET_FEATURE_FLAG_QUERY)
+        .bind::<Text, _>(name)
+        .bind::<Bool, _>(enabled)
+        .execute(conn)
+        .map_err(FeeSweeperError::db("failed to set feature flag"))?;
+
+    let state = if enabled { "enabled" } else { "disabled" };
+    let details = format!("{name} {state}");
+    record_audit_event(conn, NewAuditEvent::new(FEATURE_FLAG_EVENT, None, details))
+}
+
+/// Remove a feature flag from the DB, returning it to its default
+pub fn remove_feature_flag(conn: &mut PgConnection, name: &str) -> Result<(), FeeSweeperError> {
+    let removed = diesel::delete(flags_table.filter(name_col.eq(name)))
+        .execute(conn)
+        .map_err(FeeSweeperError::db("failed to remove feature flag"))?;
+    if removed == 0 {
+        return Err(FeeSweeperError::Invalid(format!(
+            "{name} is not set in the DB"
+        )));
+    }
+
+    let details = format!("{name} returned to its default");
+    record_audit_event(conn, NewAuditEvent::new(FEATURE_FLAG_EVENT, None, details))
+}
@@ -0,0 +1,74 @@
+//! Helpers for managing the decryption keys registered in the DB
+
+use diesel::{ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl, SelectableHelper};
+
+use crate::db::{
+    audit::{record_audit_event, FEE_KEY_EVENT},
+    models::{NewAuditEvent, NewRegisteredFeeKey, RegisteredFeeKey},
+    schema::{
+        fee_decryption_keys::dsl::{
+            added_at as added_at_col, fee_decryption_keys as keys_table, receiver as receiver_col,
+        },
+        fees::dsl::{fees as fees_table, receiver as fee_receiver_col, redeemed as redeemed_col},
+    },
+};
+use crate::error::FeeSweeperError;
+
+/// Get every registered key, oldest first
+pub fn get_registered_fee_keys(
+    conn: &mut PgConnection,
+) -> Result<Vec<RegisteredFeeKey>, FeeSweeperError> {
+    keys_table
+        .order(added_at_col.asc())
+        .select(RegisteredFeeKey::as_select())
+        .load(conn)
+        .map_err(FeeSweeperError::db("failed to query decryption keys"))
+}
+
+/// Register a key, held in the named secret
+pub fn register_fee_key(
+    conn: &mut PgConnection,
+    key: NewRegisteredFeeKey,
+) -> Result<(), FeeSweeperError> {
+    let details = format!(
+        "registered {} key with receiver {} from secret {}",
+        key.fee_kind, key.receiver, key.secret_name
+    );
+    diesel::insert_into(keys_table)
+        .values(vec![key])
+        .execute(conn)
+        .map_err(FeeSweeperError::db("failed to register decryption key"))?;
+
+    record_audit_event(conn, NewAuditEvent::new(FEE_KEY_EVENT, None, details))
+}
+
+/// Remove a registered key, refusing while fees it decrypts await redemption,
+/// as they could not be redeemed without it
+pub fn remove_registered_fee_key(
+    conn: &mut PgConnection,
+    receiver: &str,
+) -> Result<(), FeeSweeperError> {
+    let unredeemed: i64 = fees_table
+        .filter(fee_receiver_col.eq(receiver))
+        .filter(redeemed_col.eq(false))
+        .count()
+        .get_result(conn)
+        .map_err(FeeSweeperError::db("failed to query unredeemed fees"))?;
+    if unredeemed > 0 {
+        return Err(FeeSweeperError::Invalid(format!(
+            "{unredeemed} fees of receiver {receiver} await redemption, the key is still needed"
+        )));
+    }
+
+    let removed = diesel::delete(keys_table.filter(receiver_col.eq(receiver)))
+        .execute(conn)
+        .map_err(FeeSweeperError::db("failed to remove decryption key"))?;
+    if removed == 0 {
+        return Err(FeeSweeperError::Invalid(format!(
+            "no key with receiver {receiver} is registered"
+        )));
+    }
+
+    let details = format!("removed key with receiver {receiver}");
+    record_audit_event(conn, NewAuditEvent::new(FEE_KEY_EVENT, None, details))
+}
@@ -0,0 +1,21 @@
+//! Helpers for reading individual fees
+
+use diesel::{
+    ExpressionMethods, OptionalExtension, PgConnection, QueryDsl, RunQueryDsl, SelectableHelper,
+};
+
+use crate::db::{
+    models::Fee,
+    schema::fees::dsl::{fees as fees_table, tx_hash as tx_hash_col},
+};
+use crate::error::FeeSweeperError;
+
+/// Get the fee settled in a tx, if it is indexed
+pub fn get_fee(conn: &mut PgConnection, tx_hash: &str) -> Result<Option<Fee>, FeeSweeperError> {
+    fees_table
+        .filter(tx_hash_col.eq(tx_hash))
+        .select(Fee::as_select())
+        .first(conn)
+        .optional()
+        .map_err(FeeSweeperError::db("failed to query fee"))
+}
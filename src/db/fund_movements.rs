@@ -0,0 +1,131 @@
+//! Helpers for the ledger of funds the sweeper moves out of its holdings
+//!
+//! Transfers to the treasury and over the bridge are recorded once they land.
+//! A conversion is recorded when its tranche is placed, as the sale of the
+//! tranche and the purchase of its proceeds at the tranche's worst case price,
+//! and the unfilled remainder of a cancelled tranche is reversed. Conversions
+//! are recorded by the indexer, on its pooled async connections
+
+use bigdecimal::{BigDecimal, Zero};
+use diesel::{dsl::sum, ExpressionMethods, PgConnection, QueryDsl, QueryResult, RunQueryDsl};
+use diesel_async::AsyncPgConnection;
+
+use crate::db::{
+    models::NewFundMovement,
+    schema::fund_movements::dsl::{
+        amount as amount_col, fund_movements as movements_table, kind as kind_col,
+        mint as mint_col, reference as reference_col,
+    },
+};
+use crate::error::FeeSweeperError;
+
+/// The kind of a transfer of funds to a treasury
+pub const TREASURY_TRANSFER_MOVEMENT: &str = "treasury_transfer";
+/// The kind of a transfer of funds over the bridge
+pub const BRIDGE_MOVEMENT: &str = "bridge";
+/// The kind of a conversion of funds to USDC
+pub const CONVERSION_MOVEMENT: &str = "conversion";
+
+/// Record a movement of funds out of a mint
+pub fn record_fund_movement(
+    conn: &mut PgConnection,
+    kind: &str,
+    mint: &str,
+    amount: BigDecimal,
+    reference: &str,
+) -> Result<(), FeeSweeperError> {
+    record_movements(conn, vec![new_movement(kind, mint, amount, reference)])
+        .map_err(FeeSweeperError::db("failed to record fund movement"))
+}
+
+/// Record the placement of a conversion tranche: the sale of `amount` of
+/// `mint` for `proceeds` of `quote_mint`
+pub async fn record_conversion(
+    conn: &mut AsyncPgConnection,
+    order_id: &str,
+    mint: &str,
+    amount: BigDecimal,
+    quote_mint: &str,
+    proceeds: BigDecimal,
+) -> QueryResult<usize> {
+    let movements = vec![
+        new_movement(CONVERSION_MOVEMENT, mint, amount, order_id),
+        new_movement(CONVERSION_MOVEMENT, quote_mint, -proceeds, order_id),
+    ];
+    let insert = diesel::insert_into(movements_table).values(movements);
+    diesel_async::RunQueryDsl::execute(insert, conn).await
+}
+
+/// Reverse the unfilled `remaining` amount of a cancelled conversion tranche,
+/// along with its share of the tranche's proceeds
+pub async fn reverse_conversion(
+    conn: &mut AsyncPgConnection,
+    order_id: &str,
+    remaining: BigDecimal,
+) -> QueryResult<usize> {
+    let query = movements_table
+        .filter(reference_col.eq(order_id))
+        .filter(kind_col.eq(CONVERSION_MOVEMENT))
+        .select((mint_col, amount_col));
+    let placed: Vec<(String, BigDecimal)> = diesel_async::RunQueryDsl::load(query, conn).await?;
+
+    // The sale is recorded as the positive movement, its proceeds as the
+    // negative one
+    let sold = placed
+        .iter()
+        .find(|(_, amount)| *amount > BigDecimal::zero());
+    let bought = placed
+        .iter()
+        .find(|(_, amount)| *amount < BigDecimal::zero());
+    let (Some((mint, sold)), Some((quote_mint, proceeds))) = (sold, bought) else {
+        return Ok(0);
+    };
+
+    let unfilled_proceeds = proceeds * &remaining / sold;
+    let movements = vec![
+        new_movement(CONVERSION_MOVEMENT, mint, -remaining.clone(), order_id),
+        new_movement(
+            CONVERSION_MOVEMENT,
+            quote_mint,
+            -unfilled_proceeds,
+            order_id,
+        ),
+    ];
+    let insert = diesel::insert_into(movements_table).values(movements);
+    diesel_async::RunQueryDsl::execute(insert, conn).await
+}
+
+/// Get the net amount of a mint moved out of the sweeper's holdings
+pub fn get_moved_amount(
+    conn: &mut PgConnection,
+    mint: &str,
+) -> Result<BigDecimal, FeeSweeperError> {
+    let moved: Option<BigDecimal> = movements_table
+        .filter(mint_col.eq(mint))
+        .select(sum(amount_col))
+        .first(conn)
+        .map_err(FeeSweeperError::db("failed to query fund movements"))?;
+
+    Ok(moved.unwrap_or_default())
+}
+
+/// Build a ledger entry
+fn new_movement(kind: &str, mint: &str, amount: BigDecimal, reference: &str) -> NewFundMovement {
+    NewFundMovement {
+        kind: kind.to_string(),
+        mint: mint.to_string(),
+        amount,
+        reference: reference.to_string(),
+    }
+}
+
+/// Insert ledger entries
+fn record_movements(
+    conn: &mut PgConnection,
+    movements: Vec<NewFundMovement>,
+) -> Result<(), diesel::result::Error> {
+    diesel::insert_into(movements_table)
+        .values(movements)
+        .execute(conn)
+        .map(|_| ())
+}
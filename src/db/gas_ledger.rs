@@ -0,0 +1,72 @@
+//! Helpers for recording gas spent by the sweeper
+
+use std::fmt::{self, Display};
+
+use diesel::{PgConnection, RunQueryDsl};
+use ethers::types::TransactionReceipt;
+use tracing::info;
+
+use crate::db::models::{u256_to_decimal, NewGasSpend};
+use crate::db::schema::gas_spend::dsl::gas_spend as gas_spend_table;
+use crate::error::FeeSweeperError;
+
+/// The purpose of a transaction the sweeper paid gas for
+#[derive(Clone, Copy, Debug)]
+pub enum GasPurpose {
+    /// The settlement of a note redemption
+    Redemption,
+    /// An ERC-20 approval or revocation
+    Approval,
+    /// A transfer of withdrawn funds to the treasury
+    Withdrawal,
+    /// A transfer through the L1 bridge
+    Bridge,
+    /// A transfer of ETH funding a submitter key's gas
+    TopUp,
+}
+
+impl Display for GasPurpose {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GasPurpose::Redemption => write!(f, "redemption"),
+            GasPurpose::Approval => write!(f, "approval"),
+            GasPurpose::Withdrawal => write!(f, "withdrawal"),
+            GasPurpose::Bridge => write!(f, "bridge"),
+            GasPurpose::TopUp => write!(f, "top_up"),
+        }
+    }
+}
+
+/// Record the gas spent by a mined transaction
+pub fn record_gas_spend(
+    conn: &mut PgConnection,
+    purpose: GasPurpose,
+    receipt: &TransactionReceipt,
+) -> Result<(), FeeSweeperError> {
+    // A transaction's gas is recorded once, however many times it is seen
+    diesel::insert_into(gas_spend_table)
+        .values(vec![new_gas_spend(purpose, receipt)])
+        .on_conflict_do_nothing()
+        .execute(conn)
+        .map_err(FeeSweeperError::db("failed to record gas spend"))
+        .map(|_| ())
+}
+
+/// Build the ledger entry of the gas spent by a mined transaction
+pub fn new_gas_spend(purpose: GasPurpose, receipt: &TransactionReceipt) -> NewGasSpend {
+    let gas_used = receipt.gas_used.unwrap_or_default();
+    let gas_price = receipt.effective_gas_price.unwrap_or_default();
+    let wei_spent = gas_used * gas_price;
+    info!(
+        "spent {wei_spent} wei on {purpose} tx {:#x}",
+        receipt.transaction_hash
+    );
+
+    NewGasSpend {
+        tx_hash: format!("{:#x}", receipt.transaction_hash),
+        purpose: purpose.to_string(),
+        gas_used: u256_to_decimal(gas_used),
+        effective_gas_price: u256_to_decimal(gas_price),
+        wei_spent: u256_to_decimal(wei_spent),
+    }
+}
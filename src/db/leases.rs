@@ -0,0 +1,107 @@
+//! Helpers for the lease naming the sweeper instance allowed to run
+//!
+//! An instance holds the lease for the duration of a run and releases it when
+//! the run finishes; a crashed instance's lease is freed when it expires. An
+//! instance that finds the lease held may request a handover, after which it
+//! is the only instance that may acquire the lease once it is freed
+
+use std::time::Duration;
+
+use diesel::{
+    sql_types::{Double, Text, Uuid as SqlUuid},
+    ExpressionMethods, OptionalExtension, PgConnection, QueryDsl, RunQueryDsl, SelectableHelper,
+};
+use uuid::Uuid;
+
+use crate::db::{
+    models::InstanceLease,
+    schema::instance_leases::dsl::{
+        expires_at as expires_at_col, holder as holder_col, instance_leases as lease_table,
+        name as name_col, successor as successor_col,
+    },
+};
+use crate::error::FeeSweeperError;
+
+/// Acquire or renew a lease, returning whether it is now held by `holder`
+///
+/// Succeeds if the lease is free, or already held by `holder`, unless another
+/// instance is awaiting a handover
+pub(crate) const ACQUIRE_LEASE_QUERY: &str = "
+    INSERT INTO instance_leases (name, holder, acquired_at, expires_at)
+    VALUES ($1, $2, NOW(), NOW() + make_interval(secs => $3))
+    ON CONFLICT (name) DO UPDATE
+    SET holder = EXCLUDED.holder,
+        successor = NULL,
+        acquired_at = EXCLUDED.acquired_at,
+        expires_at = EXCLUDED.expires_at
+    WHERE (instance_leases.successor IS NULL OR instance_leases.successor = EXCLUDED.holder)
+      AND (instance_leases.holder = EXCLUDED.holder OR instance_leases.expires_at < NOW())
+";
+
+/// Get a lease, if it has ever been acquired
+pub fn get_lease(
+    conn: &mut PgConnection,
+    name: &str,
+) -> Result<Option<InstanceLease>, FeeSweeperError> {
+    lease_table
+        .filter(name_col.eq(name))
+        .select(InstanceLease::as_select())
+        .first(conn)
+        .optional()
+        .map_err(FeeSweeperError::db("failed to query lease"))
+}
+
+/// Atomically acquire or renew a lease for `ttl`
+///
+/// Returns whether `holder` now holds the lease
+pub fn acquire_lease(
+    conn: &mut PgConnection,
+    name: &str,
+    holder: Uuid,
+    ttl: Duration,
+) -> Result<bool, FeeSweeperError> {
+    let acquired = diesel::sql_query(ACQUIRE_LEASE_QUERY)
+        .bind::<Text, _>(name)
+        .bind::<SqlUuid, _>(holder)
+        .bind::<Double, _>(ttl.as_secs_f64())
+        .execute(conn)
+        .map_err(FeeSweeperError::db("failed to acquire lease"))?;
+
+    Ok(acquired == 1)
+}
+
+/// Request that a held lease be handed over to `successor` once freed
+///
+/// Returns whether the request was recorded, which it is not if another
+/// instance is already awaiting the handover
+pub fn request_handover(
+    conn: &mut PgConnection,
+    name: &str,
+    successor: Uuid,
+) -> Result<bool, FeeSweeperError> {
+    let lease = lease_table
+        .filter(name_col.eq(name))
+        .filter(successor_col.is_null().or(successor_col.eq(successor)));
+    let requested = diesel::update(lease)
+        .set(successor_col.eq(successor))
+        .execute(conn)
+        .map_err(FeeSweeperError::db("failed to request handover"))?;
+
+    Ok(requested == 1)
+}
+
+/// Release a lease held by `holder`, freeing it immediately
+pub fn release_lease(
+    conn: &mut PgConnection,
+    name: &str,
+    holder: Uuid,
+) -> Result<(), FeeSweeperError> {
+    let lease = lease_table
+        .filter(name_col.eq(name))
+        .filter(holder_col.eq(holder));
+    diesel::update(lease)
+        .set(expires_at_col.eq(diesel::dsl::now))
+        .execute(conn)
+        .map_err(FeeSweeperError::db("failed to release lease"))
+        .map(|_| ())
+}
@@ -0,0 +1,60 @@
+//! Helpers for reading and writing indexing metadata outside of the indexer
+
+use diesel::{ExpressionMethods, OptionalExtension, PgConnection, QueryDsl, RunQueryDsl};
+use ethers::types::Address;
+
+use crate::db::schema::indexing_metadata::dsl::{
+    indexing_metadata as metadata_table, key as metadata_key, value as metadata_value,
+};
+use crate::error::FeeSweeperError;
+
+/// The metadata key of the address of the active signer
+pub const ACTIVE_SIGNER_KEY: &str = "active_signer";
+
+/// Get a metadata value, if it is set
+pub fn get_metadata(conn: &mut PgConnection, key: &str) -> Result<Option<String>, FeeSweeperError> {
+    metadata_table
+        .filter(metadata_key.eq(key))
+        .select(metadata_value)
+        .first(conn)
+        .optional()
+        .map_err(FeeSweeperError::db("failed to query metadata"))
+}
+
+/// Set a metadata value, creating it if it does not exist
+pub fn set_metadata(
+    conn: &mut PgConnection,
+    key: &str,
+    value: &str,
+) -> Result<(), FeeSweeperError> {
+    diesel::insert_into(metadata_table)
+        .values((metadata_key.eq(key), metadata_value.eq(value)))
+        .on_conflict(metadata_key)
+        .do_update()
+        .set(metadata_value.eq(value))
+        .execute(conn)
+        .map_err(FeeSweeperError::db("failed to set metadata"))
+        .map(|_| ())
+}
+
+/// Check that an address is the active signer, if one has been recorded
+pub fn check_active_signer(
+    conn: &mut PgConnection,
+    address: Address,
+) -> Result<(), FeeSweeperError> {
+    let active = get_metadata(conn, ACTIVE_SIGNER_KEY)?;
+    check_signer_is_active(active.as_deref(), address)
+}
+
+/// Check that an address is the recorded active signer, if any
+pub fn check_signer_is_active(
+    active: Option<&str>,
+    address: Address,
+) -> Result<(), FeeSweeperError> {
+    match active {
+        Some(active) if active != format!("{address:#x}") => Err(FeeSweeperError::Config(format!(
+            "{address:#x} is not the active signer; the active signer is {active}"
+        ))),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,38 @@
+//! The schema migrations embedded in the binary
+//!
+//! A run started with `--run-migrations` applies any pending migrations before
+//! it touches the DB, so that deploying a new schema needs no separate
+//! `diesel migration run` step. Instances starting together serialize on the
+//! storage backend's migration lock, so that each migration is applied
+//! exactly once
+
+use diesel::PgConnection;
+use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+use tracing::info;
+
+use crate::db::storage::StorageBackend;
+use crate::error::FeeSweeperError;
+
+/// The migrations of the `migrations` directory
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
+
+/// Apply the pending migrations, returning the number applied
+pub fn run_pending_migrations(
+    conn: &mut PgConnection,
+    storage: &dyn StorageBackend,
+) -> Result<usize, FeeSweeperError> {
+    storage.lock_migrations(conn)?;
+
+    let res = conn
+        .run_pending_migrations(MIGRATIONS)
+        .map(|versions| {
+            for version in versions.iter() {
+                info!("applied migration {version}");
+            }
+            versions.len()
+        })
+        .map_err(FeeSweeperError::db("failed to run migrations"));
+
+    storage.unlock_migrations(conn)?;
+    res
+}
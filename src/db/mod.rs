@@ -1,5 +1,27 @@
 //! Database code
 
+pub mod abandonment;
+pub mod access_log;
+pub mod audit;
+pub mod dual_write;
+pub mod feature_flags;
+pub mod fee_keys;
+pub mod fees;
+pub mod fund_movements;
+pub mod gas_ledger;
+pub mod leases;
+pub mod metadata;
+pub mod migrations;
 pub mod models;
+pub mod outbox;
+pub mod pool;
+pub mod price_routes;
+pub mod quarantine;
+pub mod queue;
+pub mod run_summaries;
 #[allow(missing_docs)]
 pub mod schema;
+pub mod snapshot;
+pub mod storage;
+pub mod task_failures;
+pub mod wallets;
@@ -2,14 +2,126 @@
 #![allow(trivial_bounds)]
 
 use bigdecimal::BigDecimal;
+use chrono::NaiveDateTime;
 use diesel::prelude::*;
+use ethers::types::U256;
 use num_bigint::BigInt;
 use renegade_circuit_types::note::Note;
 use renegade_crypto::fields::scalar_to_bigint;
 use renegade_util::hex::{biguint_to_hex_addr, jubjub_to_hex_string};
+use std::fmt::{self, Display};
+use std::str::FromStr;
 use uuid::Uuid;
 
 use crate::db::schema::fees;
+use crate::fee_keys::FeeKind;
+
+/// The source of a fee earned in a match between two darkpool wallets, paid
+/// into an encrypted note
+pub const FEE_SOURCE_INTERNAL_MATCH: &str = "internal_match";
+/// The source of a fee earned in an external (atomic) match, transferred
+/// directly to the fee recipient
+pub const FEE_SOURCE_EXTERNAL_MATCH: &str = "external_match";
+
+/// The status recorded when a fee is redeemed
+pub const FEE_STATUS_REDEEMED: &str = "redeemed";
+/// The status of a fee awaiting redemption
+pub const FEE_STATUS_OPEN: &str = "open";
+/// The status of a fee whose redemption is in flight
+pub const FEE_STATUS_REDEEMING: &str = "redeeming";
+/// The status of a fee whose redemption failed too many times
+pub const FEE_STATUS_DEAD_LETTERED: &str = "dead_lettered";
+/// The status of a dust fee abandoned under the expiration policy
+pub const FEE_STATUS_ABANDONED: &str = "abandoned";
+
+/// The state of a fee in the redemption state machine
+///
+/// A fee is `Indexed` when first seen, becomes `Eligible` once a redemption
+/// pass has screened it for redemption, and `InFlight` while a redemption of
+/// it is submitted to the relayer. The redemption leaves it `Redeemed` or
+/// `Failed`, and a fee that fails too many times is `Exhausted`, i.e.
+/// dead-lettered, until an operator releases it. A dust fee left unredeemed
+/// past its expiry is `Abandoned` until an operator restores it. A fee found
+/// to be redeemed in any state is marked `Redeemed`, which is final
+///
+/// The state is the one source of truth of a fee's redemption status: the
+/// fee's `redeemed` flag, `status`, and dead letter and abandonment times are
+/// derived from it on each transition, and constraints on the fees table hold
+/// them to it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RedemptionState {
+    /// The fee has been indexed but not yet screened for redemption
+    Indexed,
+    /// The fee passed a redemption pass's screening and awaits submission
+    Eligible,
+    /// A redemption of the fee has been submitted and not yet resolved
+    InFlight,
+    /// The fee has been redeemed
+    Redeemed,
+    /// The last redemption of the fee failed
+    Failed,
+    /// The fee failed too many redemptions and is held out of redemption
+    Exhausted,
+    /// The fee is dust left unredeemed past its expiry, and is held out of
+    /// redemption
+    Abandoned,
+}
+
+impl RedemptionState {
+    /// The name of the state, as stored in the `redemption_state` column
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RedemptionState::Indexed => "indexed",
+            RedemptionState::Eligible => "eligible",
+            RedemptionState::InFlight => "in_flight",
+            RedemptionState::Redeemed => "redeemed",
+            RedemptionState::Failed => "failed",
+            RedemptionState::Exhausted => "exhausted",
+            RedemptionState::Abandoned => "abandoned",
+        }
+    }
+
+    /// The status of a fee in the state, as stored in the `status` column
+    pub fn fee_status(&self) -> &'static str {
+        match self {
+            RedemptionState::Indexed | RedemptionState::Eligible | RedemptionState::Failed => {
+                FEE_STATUS_OPEN
+            }
+            RedemptionState::InFlight => FEE_STATUS_REDEEMING,
+            RedemptionState::Redeemed => FEE_STATUS_REDEEMED,
+            RedemptionState::Exhausted => FEE_STATUS_DEAD_LETTERED,
+            RedemptionState::Abandoned => FEE_STATUS_ABANDONED,
+        }
+    }
+
+    /// Whether a fee in the state has been redeemed
+    pub fn is_redeemed(&self) -> bool {
+        *self == RedemptionState::Redeemed
+    }
+
+    /// The states from which a fee may transition into this state
+    ///
+    /// A fee is screened anew by each pass, so an eligible fee that was not
+    /// submitted may be made eligible again
+    pub fn predecessors(&self) -> &'static [RedemptionState] {
+        use RedemptionState::*;
+        match self {
+            Indexed => &[Abandoned],
+            Eligible => &[Indexed, Eligible, Failed, Exhausted],
+            InFlight => &[Eligible],
+            Redeemed => &[Indexed, Eligible, InFlight, Failed, Exhausted, Abandoned],
+            Failed => &[InFlight],
+            Exhausted => &[Failed],
+            Abandoned => &[Indexed, Eligible, Failed],
+        }
+    }
+}
+
+impl Display for RedemptionState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
 
 /// A fee that has been indexed by the indexer
 #[derive(Queryable, Selectable)]
@@ -24,10 +136,55 @@ pub struct Fee {
     pub blinder: BigDecimal,
     pub receiver: String,
     pub redeemed: bool,
+    pub block_number: Option<i64>,
+    pub block_timestamp: Option<NaiveDateTime>,
+    pub usd_price: Option<BigDecimal>,
+    pub usd_value: Option<BigDecimal>,
+    pub source: String,
+    pub redemption_started_at: Option<NaiveDateTime>,
+    pub redemption_attempts: i32,
+    pub dead_lettered_at: Option<NaiveDateTime>,
+    pub dust: bool,
+    pub abandoned_at: Option<NaiveDateTime>,
+    pub fee_kind: String,
+    pub status: Option<String>,
+    pub skipped_at: Option<NaiveDateTime>,
+    pub skip_reason: Option<String>,
+    pub relayer: Option<String>,
+    pub redemption_state: String,
+    pub redemption_state_changed_at: NaiveDateTime,
+    pub redemptions_started: i32,
+    pub last_attempted_at: Option<NaiveDateTime>,
+    pub claimed_by: Option<Uuid>,
+    pub claimed_until: Option<NaiveDateTime>,
+    pub last_redemption_error: Option<String>,
+    pub next_redemption_at: Option<NaiveDateTime>,
+    pub queue_priority: i32,
+    pub indexed_at: NaiveDateTime,
+    pub redemption_task_id: Option<Uuid>,
+    pub log_index: i64,
+    pub valuation_attempted_at: Option<NaiveDateTime>,
+}
+
+impl Fee {
+    /// The status of the fee implied by its legacy status columns
+    pub fn legacy_status(&self) -> &'static str {
+        if self.redeemed {
+            FEE_STATUS_REDEEMED
+        } else if self.abandoned_at.is_some() {
+            FEE_STATUS_ABANDONED
+        } else if self.dead_lettered_at.is_some() {
+            FEE_STATUS_DEAD_LETTERED
+        } else if self.redemption_started_at.is_some() {
+            FEE_STATUS_REDEEMING
+        } else {
+            FEE_STATUS_OPEN
+        }
+    }
 }
 
 /// A new fee inserted into the database
-#[derive(Insertable)]
+#[derive(Clone, Insertable)]
 #[diesel(table_name = fees)]
 pub struct NewFee {
     pub tx_hash: String,
@@ -35,15 +192,27 @@ pub struct NewFee {
     pub amount: BigDecimal,
     pub blinder: BigDecimal,
     pub receiver: String,
+    pub redeemed: bool,
+    pub block_number: Option<i64>,
+    pub source: String,
+    pub dust: bool,
+    pub fee_kind: String,
+    pub status: Option<String>,
+    pub relayer: Option<String>,
+    pub redemption_state: String,
+    pub log_index: i64,
 }
 
 impl NewFee {
     /// Construct a fee from a note
-    pub fn new_from_note(note: &Note, tx_hash: String) -> Self {
+    ///
+    /// A settlement pays at most one note, so note fees take log index zero
+    pub fn new_from_note(note: &Note, tx_hash: String, block_number: u64, kind: FeeKind) -> Self {
         let mint = biguint_to_hex_addr(&note.mint);
         let amount = BigInt::from(note.amount).into();
         let blinder = scalar_to_bigint(&note.blinder).into();
         let receiver = jubjub_to_hex_string(&note.receiver);
+        let state = RedemptionState::Indexed;
 
         NewFee {
             tx_hash,
@@ -51,6 +220,47 @@ impl NewFee {
             amount,
             blinder,
             receiver,
+            redeemed: state.is_redeemed(),
+            block_number: Some(block_number as i64),
+            source: FEE_SOURCE_INTERNAL_MATCH.to_string(),
+            dust: false,
+            fee_kind: kind.to_string(),
+            status: Some(state.fee_status().to_string()),
+            relayer: None,
+            redemption_state: state.to_string(),
+            log_index: 0,
+        }
+    }
+
+    /// Construct a fee from an external match fee transfer
+    ///
+    /// The fee is paid directly to the recipient rather than into a note, so
+    /// there is nothing to redeem and the fee is indexed as redeemed
+    pub fn new_external(
+        tx_hash: String,
+        mint: String,
+        amount: U256,
+        recipient: String,
+        block_number: u64,
+        log_index: u64,
+        kind: FeeKind,
+    ) -> Self {
+        let state = RedemptionState::Redeemed;
+        NewFee {
+            tx_hash,
+            mint,
+            amount: u256_to_decimal(amount),
+            blinder: BigDecimal::from(0),
+            receiver: recipient,
+            redeemed: state.is_redeemed(),
+            block_number: Some(block_number as i64),
+            source: FEE_SOURCE_EXTERNAL_MATCH.to_string(),
+            dust: false,
+            fee_kind: kind.to_string(),
+            status: Some(state.fee_status().to_string()),
+            relayer: None,
+            redemption_state: state.to_string(),
+            log_index: log_index as i64,
         }
     }
 }
@@ -74,15 +284,333 @@ pub struct WalletMetadata {
     pub id: Uuid,
     pub mints: Vec<Option<String>>,
     pub secret_id: String,
+    pub needs_refresh: bool,
+    pub fee_kind: String,
+    pub diverged_since: Option<NaiveDateTime>,
 }
 
 impl WalletMetadata {
     /// Construct a new wallet metadata entry
-    pub fn empty(id: Uuid, secret_id: String) -> Self {
+    pub fn empty(id: Uuid, secret_id: String, kind: FeeKind) -> Self {
         WalletMetadata {
             id,
             mints: vec![],
             secret_id,
+            needs_refresh: false,
+            fee_kind: kind.to_string(),
+            diverged_since: None,
         }
     }
 }
+
+/// An entry in the audit log
+#[derive(Clone, Queryable, Selectable)]
+#[diesel(table_name = crate::db::schema::audit_log)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[allow(missing_docs, clippy::missing_docs_in_private_items)]
+pub struct AuditEvent {
+    pub id: i32,
+    pub event_type: String,
+    pub tx_hash: Option<String>,
+    pub details: String,
+    pub created_at: NaiveDateTime,
+}
+
+/// A new entry in the audit log
+#[derive(Insertable)]
+#[diesel(table_name = crate::db::schema::audit_log)]
+pub struct NewAuditEvent {
+    pub event_type: String,
+    pub tx_hash: Option<String>,
+    pub details: String,
+}
+
+impl NewAuditEvent {
+    /// Construct a new audit event
+    pub fn new(event_type: &str, tx_hash: Option<String>, details: String) -> Self {
+        NewAuditEvent {
+            event_type: event_type.to_string(),
+            tx_hash,
+            details,
+        }
+    }
+}
+
+/// A transfer of funds to L1 through the canonical bridge
+#[derive(Clone, Queryable, Selectable)]
+#[diesel(table_name = crate::db::schema::bridge_transfers)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[allow(missing_docs, clippy::missing_docs_in_private_items)]
+pub struct BridgeTransfer {
+    pub id: i32,
+    pub mint: String,
+    pub l1_token: String,
+    pub amount: BigDecimal,
+    pub destination: String,
+    pub l2_tx_hash: String,
+    pub l1_tx_hash: Option<String>,
+    pub status: String,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+/// A new bridge transfer inserted into the database
+#[derive(Insertable)]
+#[diesel(table_name = crate::db::schema::bridge_transfers)]
+pub struct NewBridgeTransfer {
+    pub mint: String,
+    pub l1_token: String,
+    pub amount: BigDecimal,
+    pub destination: String,
+    pub l2_tx_hash: String,
+    pub status: String,
+}
+
+/// A change to a protocol fee setting
+#[derive(Insertable)]
+#[diesel(table_name = crate::db::schema::fee_settings_history)]
+pub struct NewFeeSetting {
+    pub setting: String,
+    pub asset: Option<String>,
+    pub raw_value: BigDecimal,
+    pub rate: BigDecimal,
+    pub block_number: i64,
+    pub tx_hash: String,
+    pub log_index: i64,
+}
+
+/// A new entry in the fund movement ledger
+#[derive(Insertable)]
+#[diesel(table_name = crate::db::schema::fund_movements)]
+pub struct NewFundMovement {
+    pub kind: String,
+    pub mint: String,
+    pub amount: BigDecimal,
+    pub reference: String,
+}
+
+/// A new entry in the gas spend ledger
+#[derive(Insertable)]
+#[diesel(table_name = crate::db::schema::gas_spend)]
+pub struct NewGasSpend {
+    pub tx_hash: String,
+    pub purpose: String,
+    pub gas_used: BigDecimal,
+    pub effective_gas_price: BigDecimal,
+    pub wei_spent: BigDecimal,
+}
+
+/// A change to a fee's redemption status
+#[derive(Clone, Insertable)]
+#[diesel(table_name = crate::db::schema::fee_status_changes)]
+pub struct NewFeeStatusChange {
+    pub tx_hash: String,
+    pub status: String,
+}
+
+impl NewFeeStatusChange {
+    /// Construct a status change of a fee
+    pub fn new(tx_hash: &str, status: &str) -> Self {
+        NewFeeStatusChange {
+            tx_hash: tx_hash.to_string(),
+            status: status.to_string(),
+        }
+    }
+}
+
+/// A mint quarantined as suspected spam
+#[derive(Clone, Queryable, Selectable)]
+#[diesel(table_name = crate::db::schema::mint_quarantines)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[allow(missing_docs, clippy::missing_docs_in_private_items)]
+pub struct MintQuarantine {
+    pub mint: String,
+    pub reason: String,
+    pub quarantined_at: NaiveDateTime,
+    pub released_at: Option<NaiveDateTime>,
+}
+
+/// A new quarantine of a mint
+#[derive(Insertable)]
+#[diesel(table_name = crate::db::schema::mint_quarantines)]
+pub struct NewMintQuarantine {
+    pub mint: String,
+    pub reason: String,
+}
+
+/// The verdict of a safety probe of a mint
+#[derive(Clone, Queryable, Selectable)]
+#[diesel(table_name = crate::db::schema::token_probes)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[allow(missing_docs, clippy::missing_docs_in_private_items)]
+pub struct TokenProbe {
+    pub mint: String,
+    pub passed: bool,
+    pub details: String,
+    pub probed_at: NaiveDateTime,
+}
+
+/// A new safety probe verdict
+#[derive(Insertable)]
+#[diesel(table_name = crate::db::schema::token_probes)]
+pub struct NewTokenProbe {
+    pub mint: String,
+    pub passed: bool,
+    pub details: String,
+}
+
+/// A route pricing a mint through an intermediate mint
+#[derive(Clone, Queryable, Selectable)]
+#[diesel(table_name = crate::db::schema::price_routes)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[allow(missing_docs, clippy::missing_docs_in_private_items)]
+pub struct PriceRoute {
+    pub mint: String,
+    pub via_mint: String,
+    pub updated_at: NaiveDateTime,
+}
+
+/// Convert an integer to a decimal for storage
+pub fn u256_to_decimal(value: U256) -> BigDecimal {
+    BigDecimal::from_str(&value.to_string()).expect("integers are valid decimals")
+}
+
+/// A feature flag stored in the DB
+#[derive(Clone, Queryable, Selectable)]
+#[diesel(table_name = crate::db::schema::feature_flags)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[allow(missing_docs, clippy::missing_docs_in_private_items)]
+pub struct FeatureFlag {
+    pub name: String,
+    pub enabled: bool,
+    pub updated_at: NaiveDateTime,
+}
+
+/// A decryption key of fee notes registered in the DB
+#[derive(Clone, Queryable, Selectable)]
+#[diesel(table_name = crate::db::schema::fee_decryption_keys)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[allow(missing_docs, clippy::missing_docs_in_private_items)]
+pub struct RegisteredFeeKey {
+    pub receiver: String,
+    pub fee_kind: String,
+    pub secret_name: String,
+    pub added_at: NaiveDateTime,
+}
+
+/// A new registration of a decryption key
+#[derive(Insertable)]
+#[diesel(table_name = crate::db::schema::fee_decryption_keys)]
+pub struct NewRegisteredFeeKey {
+    pub receiver: String,
+    pub fee_kind: String,
+    pub secret_name: String,
+}
+
+/// A failed redemption, with the relayer's payload describing the failure
+#[derive(Clone, Queryable, Selectable)]
+#[diesel(table_name = crate::db::schema::task_failures)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[allow(missing_docs, clippy::missing_docs_in_private_items)]
+pub struct TaskFailure {
+    pub id: i32,
+    pub tx_hash: String,
+    pub wallet_id: Uuid,
+    pub task_id: Option<Uuid>,
+    pub reason: String,
+    pub payload: Option<String>,
+    pub failed_at: NaiveDateTime,
+}
+
+/// A new failed redemption
+#[derive(Insertable)]
+#[diesel(table_name = crate::db::schema::task_failures)]
+pub struct NewTaskFailure {
+    pub tx_hash: String,
+    pub wallet_id: Uuid,
+    pub task_id: Option<Uuid>,
+    pub reason: String,
+    pub payload: Option<String>,
+}
+
+/// The summary of a sweep's changes to the fees
+#[derive(Clone, Queryable, Selectable)]
+#[diesel(table_name = crate::db::schema::run_summaries)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[allow(missing_docs, clippy::missing_docs_in_private_items)]
+pub struct RunSummary {
+    pub id: i32,
+    pub run_id: Uuid,
+    pub started_at: NaiveDateTime,
+    pub finished_at: NaiveDateTime,
+    pub summary: String,
+}
+
+/// A new summary of a sweep
+#[derive(Insertable)]
+#[diesel(table_name = crate::db::schema::run_summaries)]
+pub struct NewRunSummary {
+    pub run_id: Uuid,
+    pub started_at: NaiveDateTime,
+    pub finished_at: NaiveDateTime,
+    pub summary: String,
+}
+
+/// A notification persisted for delivery
+#[derive(Clone, Queryable, Selectable)]
+#[diesel(table_name = crate::db::schema::outbox)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[allow(missing_docs, clippy::missing_docs_in_private_items)]
+pub struct OutboxMessage {
+    pub id: i32,
+    pub channel: String,
+    pub notice: String,
+    pub created_at: NaiveDateTime,
+    pub attempts: i32,
+    pub next_attempt_at: NaiveDateTime,
+    pub last_error: Option<String>,
+    pub delivered_at: Option<NaiveDateTime>,
+}
+
+/// A new notification to deliver
+#[derive(Clone, Insertable)]
+#[diesel(table_name = crate::db::schema::outbox)]
+pub struct NewOutboxMessage {
+    pub channel: String,
+    pub notice: String,
+}
+
+impl NewOutboxMessage {
+    /// Construct a new notification to deliver on a channel
+    pub fn new(channel: &str, notice: String) -> Self {
+        NewOutboxMessage {
+            channel: channel.to_string(),
+            notice,
+        }
+    }
+}
+
+/// The lease naming the sweeper instance allowed to run
+#[derive(Clone, Queryable, Selectable)]
+#[diesel(table_name = crate::db::schema::instance_leases)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[allow(missing_docs, clippy::missing_docs_in_private_items)]
+pub struct InstanceLease {
+    pub name: String,
+    pub holder: Uuid,
+    pub successor: Option<Uuid>,
+    pub acquired_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+}
+
+/// A new entry in the admin API access log
+#[derive(Insertable)]
+#[diesel(table_name = crate::db::schema::admin_access_log)]
+pub struct NewAdminAccess {
+    pub caller: String,
+    pub method: String,
+    pub route: String,
+    pub params: String,
+    pub status: i32,
+    pub result: String,
+}
@@ -0,0 +1,21 @@
+//! Helpers for the outbox of notifications
+//!
+//! A notification announcing a change of state is enqueued in the same
+//! transaction as the change, so that the change is never committed without
+//! it. The sweeper delivers enqueued notifications afterwards, retrying those
+//! that fail to post, so each is delivered at least once
+
+use diesel::prelude::*;
+use diesel_async::AsyncPgConnection;
+
+use crate::db::models::NewOutboxMessage;
+use crate::db::schema::outbox::dsl::outbox as outbox_table;
+
+/// Enqueue a notification for delivery
+pub async fn enqueue_notice(
+    conn: &mut AsyncPgConnection,
+    message: NewOutboxMessage,
+) -> QueryResult<usize> {
+    let insert = diesel::insert_into(outbox_table).values(vec![message]);
+    diesel_async::RunQueryDsl::execute(insert, conn).await
+}
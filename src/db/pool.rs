@@ -0,0 +1,24 @@
+//! The async connection pool serving the indexer's queries
+//!
+//! The indexer's queries run on pooled async connections so that DB I/O
+//! composes with the async chain and relayer clients. The helpers used by the
+//! operator commands take a blocking connection; the indexer never does, and
+//! reaches the tables those helpers cover through its own pooled queries
+
+use diesel_async::pooled_connection::deadpool::Pool;
+use diesel_async::pooled_connection::AsyncDieselConnectionManager;
+use diesel_async::AsyncPgConnection;
+
+use crate::error::FeeSweeperError;
+
+/// A pool of async Postgres connections
+pub type DbPool = Pool<AsyncPgConnection>;
+
+/// Build a pool of at most `size` connections to the DB
+pub fn build_db_pool(db_url: &str, size: usize) -> Result<DbPool, FeeSweeperError> {
+    let manager = AsyncDieselConnectionManager::<AsyncPgConnection>::new(db_url);
+    Pool::builder(manager)
+        .max_size(size)
+        .build()
+        .map_err(FeeSweeperError::db("failed to build DB pool"))
+}
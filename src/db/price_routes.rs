@@ -0,0 +1,95 @@
+//! Helpers for managing the routes through which mints without a USDC pair
+//! are priced
+
+use std::collections::HashMap;
+
+use diesel::{
+    sql_types::Text, ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl, SelectableHelper,
+};
+
+use crate::db::{
+    audit::{record_audit_event, PRICE_ROUTE_EVENT},
+    models::{NewAuditEvent, PriceRoute},
+    schema::price_routes::dsl::{mint as mint_col, price_routes as routes_table},
+};
+use crate::error::FeeSweeperError;
+
+/// The query upserting a mint's price route
+const SET_PRICE_ROUTE_QUERY: &str = "INSERT INTO price_routes (mint, via_mint) VALUES ($1, $2) \
+    ON CONFLICT (mint) DO UPDATE SET via_mint = $2, updated_at = NOW()";
+
+/// Get every price route, ordered by mint
+pub fn get_price_routes(conn: &mut PgConnection) -> Result<Vec<PriceRoute>, FeeSweeperError> {
+    routes_table
+        .order(mint_col.asc())
+        .select(PriceRoute::as_select())
+        .load(conn)
+        .map_err(FeeSweeperError::db("failed to query price routes"))
+}
+
+/// Get the intermediate mint through which each routed mint is priced,
+/// keyed by lowercase mint
+pub fn get_price_route_map(
+    conn: &mut PgConnection,
+) -> Result<HashMap<String, String>, FeeSweeperError> {
+    let routes = get_price_routes(conn)?;
+    Ok(routes
+        .into_iter()
+        .map(|route| (route.mint, route.via_mint))
+        .collect())
+}
+
+/// Price a mint through an intermediate mint, replacing any existing route
+///
+/// Routes are a single hop, so the intermediate mint may not itself be routed,
+/// nor may a mint that others are routed through be
+pub fn set_price_route(
+    conn: &mut PgConnection,
+    mint: &str,
+    via_mint: &str,
+) -> Result<(), FeeSweeperError> {
+    let mint = mint.to_lowercase();
+    let via_mint = via_mint.to_lowercase();
+    if mint == via_mint {
+        return Err(FeeSweeperError::Invalid(format!(
+            "{mint} may not be priced through itself"
+        )));
+    }
+
+    let routes = get_price_route_map(conn)?;
+    if routes.contains_key(&via_mint) {
+        return Err(FeeSweeperError::Invalid(format!(
+            "{via_mint} is itself priced through a route"
+        )));
+    }
+    if routes.values().any(|via| *via == mint) {
+        return Err(FeeSweeperError::Invalid(format!(
+            "other mints are priced through {mint}"
+        )));
+    }
+
+    diesel::sql_query(SET_PRICE_ROUTE_QUERY)
+        .bind::<Text, _>(&mint)
+        .bind::<Text, _>(&via_mint)
+        .execute(conn)
+        .map_err(FeeSweeperError::db("failed to set price route"))?;
+
+    let details = format!("{mint} priced through {via_mint}");
+    record_audit_event(conn, NewAuditEvent::new(PRICE_ROUTE_EVENT, None, details))
+}
+
+/// Remove a mint's price route, pricing it against USDC directly
+pub fn remove_price_route(conn: &mut PgConnection, mint: &str) -> Result<(), FeeSweeperError> {
+    let mint = mint.to_lowercase();
+    let removed = diesel::delete(routes_table.filter(mint_col.eq(&mint)))
+        .execute(conn)
+        .map_err(FeeSweeperError::db("failed to remove price route"))?;
+    if removed == 0 {
+        return Err(FeeSweeperError::Invalid(format!(
+            "{mint} has no price route"
+        )));
+    }
+
+    let details = format!("{mint} priced against USDC directly");
+    record_audit_event(conn, NewAuditEvent::new(PRICE_ROUTE_EVENT, None, details))
+}
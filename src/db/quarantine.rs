@@ -0,0 +1,45 @@
+//! Helpers for quarantining mints suspected to be spam
+//!
+//! Fees in a quarantined mint are not redeemed until an operator releases the
+//! mint. A released mint keeps its row, so that it is never quarantined again.
+//! Mints are quarantined by the indexer's spam screen, on its async pool
+
+use diesel::{ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl, SelectableHelper};
+
+use crate::db::{
+    audit::{record_audit_event, MINT_RELEASE_EVENT},
+    models::{MintQuarantine, NewAuditEvent},
+    schema::mint_quarantines::dsl::{
+        mint as mint_col, mint_quarantines as quarantine_table,
+        quarantined_at as quarantined_at_col, released_at as released_at_col,
+    },
+};
+use crate::error::FeeSweeperError;
+
+/// Get every quarantine, released or not, oldest first
+pub fn get_quarantines(conn: &mut PgConnection) -> Result<Vec<MintQuarantine>, FeeSweeperError> {
+    quarantine_table
+        .order(quarantined_at_col.asc())
+        .select(MintQuarantine::as_select())
+        .load(conn)
+        .map_err(FeeSweeperError::db("failed to query quarantines"))
+}
+
+/// Release a quarantined mint so that its fees may be redeemed
+pub fn release_mint(conn: &mut PgConnection, mint: &str) -> Result<(), FeeSweeperError> {
+    let quarantine = quarantine_table
+        .filter(mint_col.eq(mint))
+        .filter(released_at_col.is_null());
+    let released = diesel::update(quarantine)
+        .set(released_at_col.eq(diesel::dsl::now))
+        .execute(conn)
+        .map_err(FeeSweeperError::db("failed to release mint"))?;
+    if released == 0 {
+        return Err(FeeSweeperError::Invalid(format!(
+            "{mint} is not quarantined"
+        )));
+    }
+
+    let details = format!("released {mint}");
+    record_audit_event(conn, NewAuditEvent::new(MINT_RELEASE_EVENT, None, details))
+}
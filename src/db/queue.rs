@@ -0,0 +1,124 @@
+//! Helpers for inspecting and reordering the redemption queue
+//!
+//! Fees are redeemed most valuable first. An operator may override that order
+//! for a fee, e.g. for an urgent treasury request, by pinning it to the front
+//! of the queue or pushing it to the back. A fee's place is kept in its queue
+//! priority: fees with a positive priority are redeemed before all others,
+//! highest first, and fees with a negative priority after all others, so the
+//! fee most recently pinned is at the very front and the fee most recently
+//! pushed back at the very back
+
+use diesel::{ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl, SelectableHelper};
+
+use crate::db::{
+    audit::{record_audit_event, FEE_QUEUE_EVENT},
+    models::{Fee, NewAuditEvent, RedemptionState},
+    schema::fees::dsl::{
+        dead_lettered_at as dead_lettered_at_col, dust as dust_col, fees as fees_table,
+        id as id_col, queue_priority as queue_priority_col, redeemed as redeemed_col,
+        redemption_state as redemption_state_col, tx_hash as tx_hash_col,
+        usd_value as usd_value_col,
+    },
+};
+use crate::error::FeeSweeperError;
+
+/// The maximum number of fees listed from the front of the queue
+pub const MAX_QUEUE_LISTING: i64 = 100;
+
+/// Get the fees at the front of the redemption queue, in queue order
+///
+/// Between overrides, fees are ordered by their USD valuation. A redemption
+/// pass orders them by the live prices of their mints, and screens them
+/// further, so the listing approximates the pass's order
+pub fn get_redemption_queue(
+    conn: &mut PgConnection,
+    limit: i64,
+) -> Result<Vec<Fee>, FeeSweeperError> {
+    fees_table
+        .filter(redeemed_col.eq(false))
+        .filter(dust_col.eq(false))
+        .filter(dead_lettered_at_col.is_null())
+        .filter(redemption_state_col.ne(RedemptionState::InFlight.as_str()))
+        .order((
+            queue_priority_col.desc(),
+            usd_value_col.desc().nulls_last(),
+            id_col.desc(),
+        ))
+        .limit(limit)
+        .select(Fee::as_select())
+        .load(conn)
+        .map_err(FeeSweeperError::db("failed to query redemption queue"))
+}
+
+/// Pin a fee to the front of the redemption queue, ahead of any fee pinned
+/// before it
+///
+/// Returns the fee's new queue priority
+pub fn pin_fee(conn: &mut PgConnection, tx_hash: &str) -> Result<i32, FeeSweeperError> {
+    let highest = fees_table
+        .filter(redeemed_col.eq(false))
+        .select(diesel::dsl::max(queue_priority_col))
+        .first::<Option<i32>>(conn)
+        .map_err(FeeSweeperError::db("failed to query queue priorities"))?;
+    let priority = highest.unwrap_or_default().max(0) + 1;
+
+    set_queue_priority(conn, tx_hash, priority, "pinned to the front of the queue")?;
+    Ok(priority)
+}
+
+/// Push a fee to the back of the redemption queue, behind any fee pushed back
+/// before it
+///
+/// Returns the fee's new queue priority
+pub fn push_back_fee(conn: &mut PgConnection, tx_hash: &str) -> Result<i32, FeeSweeperError> {
+    let lowest = fees_table
+        .filter(redeemed_col.eq(false))
+        .select(diesel::dsl::min(queue_priority_col))
+        .first::<Option<i32>>(conn)
+        .map_err(FeeSweeperError::db("failed to query queue priorities"))?;
+    let priority = lowest.unwrap_or_default().min(0) - 1;
+
+    set_queue_priority(conn, tx_hash, priority, "pushed to the back of the queue")?;
+    Ok(priority)
+}
+
+/// Return a pinned or pushed back fee to its place by value in the queue
+pub fn unpin_fee(conn: &mut PgConnection, tx_hash: &str) -> Result<(), FeeSweeperError> {
+    set_queue_priority(
+        conn,
+        tx_hash,
+        0,
+        "returned to its place by value in the queue",
+    )
+}
+
+/// Set the queue priority of a fee awaiting redemption, recording the move in
+/// the audit log
+fn set_queue_priority(
+    conn: &mut PgConnection,
+    tx_hash: &str,
+    priority: i32,
+    details: &str,
+) -> Result<(), FeeSweeperError> {
+    let fee = fees_table
+        .filter(tx_hash_col.eq(tx_hash))
+        .filter(redeemed_col.eq(false));
+    let moved = diesel::update(fee)
+        .set(queue_priority_col.eq(priority))
+        .execute(conn)
+        .map_err(FeeSweeperError::db("failed to move fee in queue"))?;
+    if moved == 0 {
+        return Err(FeeSweeperError::Invalid(format!(
+            "fee from tx {tx_hash} is not awaiting redemption"
+        )));
+    }
+
+    record_audit_event(
+        conn,
+        NewAuditEvent::new(
+            FEE_QUEUE_EVENT,
+            Some(tx_hash.to_string()),
+            details.to_string(),
+        ),
+    )
+}
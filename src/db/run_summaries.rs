@@ -0,0 +1,28 @@
+//! Helpers for reading the summaries of sweeps
+//!
+//! Summaries are recorded by the indexer, on its async pool
+
+use chrono::NaiveDateTime;
+use diesel::{ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl, SelectableHelper};
+
+use crate::db::models::RunSummary;
+use crate::db::schema::run_summaries::dsl::{
+    finished_at as finished_at_col, id as id_col, run_summaries as run_summaries_table,
+};
+use crate::error::FeeSweeperError;
+
+/// Get the summaries of the sweeps finished since the given time, newest
+/// first
+pub fn get_run_summaries(
+    conn: &mut PgConnection,
+    since: NaiveDateTime,
+    limit: i64,
+) -> Result<Vec<RunSummary>, FeeSweeperError> {
+    run_summaries_table
+        .filter(finished_at_col.ge(since))
+        .order(id_col.desc())
+        .limit(limit)
+        .select(RunSummary::as_select())
+        .load(conn)
+        .map_err(FeeSweeperError::db("failed to query run summaries"))
+}
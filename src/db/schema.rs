@@ -1,5 +1,83 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    admin_access_log (id) {
+        id -> Int4,
+        caller -> Text,
+        method -> Text,
+        route -> Text,
+        params -> Text,
+        status -> Int4,
+        result -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    audit_log (id) {
+        id -> Int4,
+        event_type -> Text,
+        tx_hash -> Nullable<Text>,
+        details -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    bridge_transfers (id) {
+        id -> Int4,
+        mint -> Text,
+        l1_token -> Text,
+        amount -> Numeric,
+        destination -> Text,
+        l2_tx_hash -> Text,
+        l1_tx_hash -> Nullable<Text>,
+        status -> Text,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    feature_flags (name) {
+        name -> Text,
+        enabled -> Bool,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    fee_decryption_keys (receiver) {
+        receiver -> Text,
+        fee_kind -> Text,
+        secret_name -> Text,
+        added_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    fee_settings_history (id) {
+        id -> Int4,
+        setting -> Text,
+        asset -> Nullable<Text>,
+        raw_value -> Numeric,
+        rate -> Numeric,
+        block_number -> Int8,
+        tx_hash -> Text,
+        log_index -> Int8,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    fee_status_changes (id) {
+        id -> Int4,
+        tx_hash -> Text,
+        status -> Text,
+        changed_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     fees (id) {
         id -> Int4,
@@ -9,6 +87,57 @@ diesel::table! {
         blinder -> Numeric,
         receiver -> Text,
         redeemed -> Bool,
+        block_number -> Nullable<Int8>,
+        block_timestamp -> Nullable<Timestamp>,
+        usd_price -> Nullable<Numeric>,
+        usd_value -> Nullable<Numeric>,
+        source -> Text,
+        redemption_started_at -> Nullable<Timestamp>,
+        redemption_attempts -> Int4,
+        dead_lettered_at -> Nullable<Timestamp>,
+        dust -> Bool,
+        abandoned_at -> Nullable<Timestamp>,
+        fee_kind -> Text,
+        status -> Nullable<Text>,
+        skipped_at -> Nullable<Timestamp>,
+        skip_reason -> Nullable<Text>,
+        relayer -> Nullable<Text>,
+        redemption_state -> Text,
+        redemption_state_changed_at -> Timestamp,
+        redemptions_started -> Int4,
+        last_attempted_at -> Nullable<Timestamp>,
+        claimed_by -> Nullable<Uuid>,
+        claimed_until -> Nullable<Timestamp>,
+        last_redemption_error -> Nullable<Text>,
+        next_redemption_at -> Nullable<Timestamp>,
+        queue_priority -> Int4,
+        indexed_at -> Timestamp,
+        redemption_task_id -> Nullable<Uuid>,
+        log_index -> Int8,
+        valuation_attempted_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    fund_movements (id) {
+        id -> Int4,
+        kind -> Text,
+        mint -> Text,
+        amount -> Numeric,
+        reference -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    gas_spend (id) {
+        id -> Int4,
+        tx_hash -> Text,
+        purpose -> Text,
+        gas_used -> Numeric,
+        effective_gas_price -> Numeric,
+        wei_spent -> Numeric,
+        created_at -> Timestamp,
     }
 }
 
@@ -19,16 +148,106 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    instance_leases (name) {
+        name -> Text,
+        holder -> Uuid,
+        successor -> Nullable<Uuid>,
+        acquired_at -> Timestamp,
+        expires_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    mint_quarantines (mint) {
+        mint -> Text,
+        reason -> Text,
+        quarantined_at -> Timestamp,
+        released_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    outbox (id) {
+        id -> Int4,
+        channel -> Text,
+        notice -> Text,
+        created_at -> Timestamp,
+        attempts -> Int4,
+        next_attempt_at -> Timestamp,
+        last_error -> Nullable<Text>,
+        delivered_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    price_routes (mint) {
+        mint -> Text,
+        via_mint -> Text,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    run_summaries (id) {
+        id -> Int4,
+        run_id -> Uuid,
+        started_at -> Timestamp,
+        finished_at -> Timestamp,
+        summary -> Text,
+    }
+}
+
+diesel::table! {
+    task_failures (id) {
+        id -> Int4,
+        tx_hash -> Text,
+        wallet_id -> Uuid,
+        task_id -> Nullable<Uuid>,
+        reason -> Text,
+        payload -> Nullable<Text>,
+        failed_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    token_probes (mint) {
+        mint -> Text,
+        passed -> Bool,
+        details -> Text,
+        probed_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     wallets (id) {
         id -> Uuid,
         mints -> Array<Nullable<Text>>,
         secret_id -> Text,
+        needs_refresh -> Bool,
+        fee_kind -> Text,
+        diverged_since -> Nullable<Timestamp>,
     }
 }
 
 diesel::allow_tables_to_appear_in_same_query!(
+    admin_access_log,
+    audit_log,
+    bridge_transfers,
+    feature_flags,
+    fee_decryption_keys,
+    fee_settings_history,
+    fee_status_changes,
     fees,
+    fund_movements,
+    gas_spend,
     indexing_metadata,
+    instance_leases,
+    mint_quarantines,
+    outbox,
+    price_routes,
+    run_summaries,
+    task_failures,
+    token_probes,
     wallets,
 );
@@ -0,0 +1,43 @@
+//! Snapshot-consistent reads of the DB
+//!
+//! Commands reporting aggregates run all of their queries in one read-only,
+//! repeatable-read transaction, so that every query sees the DB as of the
+//! transaction's first read. Otherwise a sweep committing between two queries
+//! may make their totals disagree, e.g. showing more fees redeemed than were
+//! indexed
+
+use diesel::result::Error as DieselError;
+use diesel::PgConnection;
+
+use crate::error::FeeSweeperError;
+
+/// The failure of a snapshot read
+enum SnapshotError {
+    /// The snapshot's transaction failed
+    Transaction(DieselError),
+    /// A read within the snapshot failed
+    Read(FeeSweeperError),
+}
+
+impl From<DieselError> for SnapshotError {
+    fn from(e: DieselError) -> Self {
+        SnapshotError::Transaction(e)
+    }
+}
+
+/// Run a sequence of reads against a single snapshot of the DB
+pub fn read_snapshot<T, F>(conn: &mut PgConnection, f: F) -> Result<T, FeeSweeperError>
+where
+    F: FnOnce(&mut PgConnection) -> Result<T, FeeSweeperError>,
+{
+    conn.build_transaction()
+        .read_only()
+        .repeatable_read()
+        .run(|conn| f(conn).map_err(SnapshotError::Read))
+        .map_err(|e| match e {
+            SnapshotError::Transaction(e) => {
+                FeeSweeperError::Db(format!("failed to read a DB snapshot: {e}"))
+            }
+            SnapshotError::Read(e) => e,
+        })
+}
@@ -0,0 +1,222 @@
+//! The storage backends the sweeper's DB may run on
+//!
+//! The sweeper's queries are written against the Postgres dialect, which
+//! CockroachDB also speaks. The backends differ in how concurrent instances
+//! are serialized and in how transactions fail under contention, and a
+//! `StorageBackend` captures those differences:
+//! - Postgres serializes migrations on an advisory lock, and its transactions
+//!   are not retried
+//! - CockroachDB has no advisory locks, so migrations serialize on a row of a
+//!   lock table instead, and its serializable transactions may abort with a
+//!   retryable error under contention, to be retried from the start
+
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use clap::{Args, ValueEnum};
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use diesel::sql_types::{BigInt, Double, Text, Uuid as SqlUuid};
+use diesel::{sql_query, PgConnection, RunQueryDsl};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::error::FeeSweeperError;
+
+/// A storage backend shared by the components of a run
+pub type SharedStorage = Arc<dyn StorageBackend>;
+
+/// The key of the advisory lock held while migrations run on Postgres
+const MIGRATION_LOCK_KEY: i64 = 0x6665_6573_7765_6570;
+/// The name of the lock held while migrations run on CockroachDB
+const MIGRATION_LOCK_NAME: &str = "migrations";
+/// The time after which a lock of a crashed instance lapses
+const LOCK_TTL: Duration = Duration::from_secs(300);
+/// The interval at which a held lock is polled until it is freed
+const LOCK_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Create the table of the locks held on CockroachDB, if it does not exist
+///
+/// The table is created outside of the migrations, as the lock serializing
+/// them must exist before they run
+const CREATE_LOCKS_TABLE_QUERY: &str = "
+    CREATE TABLE IF NOT EXISTS sweeper_locks (
+        name TEXT PRIMARY KEY,
+        holder UUID NOT NULL,
+        expires_at TIMESTAMP NOT NULL
+    )
+";
+
+/// Take a lock, unless another holder's lock on it has yet to lapse
+const TAKE_LOCK_QUERY: &str = "
+    INSERT INTO sweeper_locks (name, holder, expires_at)
+    VALUES ($1, $2, NOW() + $3 * INTERVAL '1 second')
+    ON CONFLICT (name) DO UPDATE
+    SET holder = EXCLUDED.holder,
+        expires_at = EXCLUDED.expires_at
+    WHERE sweeper_locks.holder = EXCLUDED.holder OR sweeper_locks.expires_at < NOW()
+";
+
+/// Release a lock held by a holder
+const RELEASE_LOCK_QUERY: &str = "DELETE FROM sweeper_locks WHERE name = $1 AND holder = $2";
+
+/// The behavior of the DB the sweeper stores its state in
+pub trait StorageBackend: Debug + Send + Sync {
+    /// The name of the backend
+    fn name(&self) -> &'static str;
+
+    /// Take the lock serializing instances applying migrations, waiting for
+    /// it to be freed if held
+    fn lock_migrations(&self, conn: &mut PgConnection) -> Result<(), FeeSweeperError>;
+
+    /// Release the lock serializing instances applying migrations
+    fn unlock_migrations(&self, conn: &mut PgConnection) -> Result<(), FeeSweeperError>;
+
+    /// Whether a transaction that failed with the given error may succeed if
+    /// retried from the start
+    fn is_retryable(&self, error: &DieselError) -> bool;
+
+    /// The number of times a transaction is retried after a retryable failure
+    fn max_transaction_retries(&self) -> u32;
+}
+
+/// A kind of storage backend
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum StorageKind {
+    /// Postgres
+    Postgres,
+    /// CockroachDB
+    Cockroachdb,
+}
+
+/// The arguments configuring the storage backend
+#[derive(Clone, Debug, Args)]
+pub struct StorageArgs {
+    /// The kind of DB the sweeper stores its state in
+    #[clap(long, value_enum, default_value_t = StorageKind::Postgres)]
+    pub storage: StorageKind,
+    /// The number of times a transaction aborted with a retryable error is
+    /// retried, on backends whose transactions may be
+    #[clap(long, default_value_t = 5)]
+    pub db_max_transaction_retries: u32,
+}
+
+impl StorageArgs {
+    /// Build the configured backend
+    pub fn build(&self) -> SharedStorage {
+        match self.storage {
+            StorageKind::Postgres => Arc::new(Postgres),
+            StorageKind::Cockroachdb => Arc::new(CockroachDb {
+                holder: Uuid::new_v4(),
+                max_transaction_retries: self.db_max_transaction_retries,
+            }),
+        }
+    }
+}
+
+// ------------
+// | Postgres |
+// ------------
+
+/// A Postgres backend
+#[derive(Clone, Copy, Debug)]
+pub struct Postgres;
+
+impl StorageBackend for Postgres {
+    fn name(&self) -> &'static str {
+        "postgres"
+    }
+
+    fn lock_migrations(&self, conn: &mut PgConnection) -> Result<(), FeeSweeperError> {
+        sql_query("SELECT pg_advisory_lock($1)")
+            .bind::<BigInt, _>(MIGRATION_LOCK_KEY)
+            .execute(conn)
+            .map_err(FeeSweeperError::db("failed to take the migration lock"))
+            .map(|_| ())
+    }
+
+    fn unlock_migrations(&self, conn: &mut PgConnection) -> Result<(), FeeSweeperError> {
+        sql_query("SELECT pg_advisory_unlock($1)")
+            .bind::<BigInt, _>(MIGRATION_LOCK_KEY)
+            .execute(conn)
+            .map_err(FeeSweeperError::db("failed to release the migration lock"))
+            .map(|_| ())
+    }
+
+    fn is_retryable(&self, _error: &DieselError) -> bool {
+        false
+    }
+
+    fn max_transaction_retries(&self) -> u32 {
+        0
+    }
+}
+
+// ---------------
+// | CockroachDB |
+// ---------------
+
+/// A CockroachDB backend
+#[derive(Clone, Copy, Debug)]
+pub struct CockroachDb {
+    /// The id under which this instance holds locks
+    holder: Uuid,
+    /// The number of times a transaction is retried after a retryable failure
+    max_transaction_retries: u32,
+}
+
+impl CockroachDb {
+    /// Try to take a lock, returning whether it is now held
+    fn try_lock(&self, conn: &mut PgConnection, name: &str) -> Result<bool, FeeSweeperError> {
+        let taken = sql_query(TAKE_LOCK_QUERY)
+            .bind::<Text, _>(name)
+            .bind::<SqlUuid, _>(self.holder)
+            .bind::<Double, _>(LOCK_TTL.as_secs_f64())
+            .execute(conn)
+            .map_err(FeeSweeperError::db("failed to take lock"))?;
+
+        Ok(taken == 1)
+    }
+}
+
+impl StorageBackend for CockroachDb {
+    fn name(&self) -> &'static str {
+        "cockroachdb"
+    }
+
+    fn lock_migrations(&self, conn: &mut PgConnection) -> Result<(), FeeSweeperError> {
+        sql_query(CREATE_LOCKS_TABLE_QUERY)
+            .execute(conn)
+            .map_err(FeeSweeperError::db("failed to create the locks table"))?;
+
+        // Migrations run before the run starts, so the wait blocks the thread
+        while !self.try_lock(conn, MIGRATION_LOCK_NAME)? {
+            info!("migration lock held by another instance, waiting");
+            thread::sleep(LOCK_POLL_INTERVAL);
+        }
+        Ok(())
+    }
+
+    fn unlock_migrations(&self, conn: &mut PgConnection) -> Result<(), FeeSweeperError> {
+        sql_query(RELEASE_LOCK_QUERY)
+            .bind::<Text, _>(MIGRATION_LOCK_NAME)
+            .bind::<SqlUuid, _>(self.holder)
+            .execute(conn)
+            .map_err(FeeSweeperError::db("failed to release the migration lock"))
+            .map(|_| ())
+    }
+
+    // CockroachDB aborts a serializable transaction that conflicts with
+    // another with SQLSTATE 40001, asking the client to retry it
+    fn is_retryable(&self, error: &DieselError) -> bool {
+        matches!(
+            error,
+            DieselError::DatabaseError(DatabaseErrorKind::SerializationFailure, _)
+        )
+    }
+
+    fn max_transaction_retries(&self) -> u32 {
+        self.max_transaction_retries
+    }
+}
@@ -0,0 +1,40 @@
+//! Helpers for reading the relayer's payloads for failed redemptions
+//!
+//! Failures are recorded by the indexer, on its async pool
+
+use diesel::{ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl, SelectableHelper};
+
+use crate::db::models::TaskFailure;
+use crate::db::schema::task_failures::dsl::{
+    id as id_col, task_failures as task_failures_table, tx_hash as tx_hash_col,
+};
+use crate::error::FeeSweeperError;
+
+/// Get the most recent failures, newest first, optionally only those of the
+/// fee settled in a transaction
+pub fn get_task_failures(
+    conn: &mut PgConnection,
+    tx_hash: Option<&str>,
+    limit: i64,
+) -> Result<Vec<TaskFailure>, FeeSweeperError> {
+    let mut query = task_failures_table.into_boxed();
+    if let Some(tx_hash) = tx_hash {
+        query = query.filter(tx_hash_col.eq(tx_hash));
+    }
+
+    query
+        .order(id_col.desc())
+        .limit(limit)
+        .select(TaskFailure::as_select())
+        .load(conn)
+        .map_err(FeeSweeperError::db("failed to query task failures"))
+}
+
+/// Get a failure by its id
+pub fn get_task_failure(conn: &mut PgConnection, id: i32) -> Result<TaskFailure, FeeSweeperError> {
+    task_failures_table
+        .find(id)
+        .select(TaskFailure::as_select())
+        .first(conn)
+        .map_err(FeeSweeperError::db("failed to query task failure"))
+}
@@ -0,0 +1,44 @@
+//! Helpers for reconciling the wallets table outside of a sweep
+
+use diesel::{ExpressionMethods, OptionalExtension, PgConnection, QueryDsl, RunQueryDsl};
+use uuid::Uuid;
+
+use crate::db::{
+    models::WalletMetadata,
+    schema::wallets::dsl::{
+        id as wallet_id_col, mints as managed_mints_col, needs_refresh as needs_refresh_col,
+        wallets as wallet_table,
+    },
+};
+use crate::error::FeeSweeperError;
+
+/// Get a wallet's entry in the wallets table, if it has one
+pub fn get_wallet_metadata(
+    conn: &mut PgConnection,
+    wallet_id: Uuid,
+) -> Result<Option<WalletMetadata>, FeeSweeperError> {
+    wallet_table
+        .filter(wallet_id_col.eq(wallet_id))
+        .first(conn)
+        .optional()
+        .map_err(FeeSweeperError::db("failed to query wallet"))
+}
+
+/// Insert a wallet's entry, or overwrite the mints of an existing entry,
+/// clearing its refresh flag as it was just synced
+pub fn upsert_wallet_mints(
+    conn: &mut PgConnection,
+    wallet: WalletMetadata,
+) -> Result<(), FeeSweeperError> {
+    diesel::insert_into(wallet_table)
+        .values(vec![wallet.clone()])
+        .on_conflict(wallet_id_col)
+        .do_update()
+        .set((
+            managed_mints_col.eq(wallet.mints),
+            needs_refresh_col.eq(false),
+        ))
+        .execute(conn)
+        .map_err(FeeSweeperError::db("failed to upsert wallet"))
+        .map(|_| ())
+}
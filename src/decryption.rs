@@ -0,0 +1,113 @@
+//! Decryption of fee note ciphertexts on a pool of blocking workers
+//!
+//! Decrypting a note and recomputing its commitment are CPU-bound, and a large
+//! backfill decrypts every note posted in its range under each fee key. The
+//! pool splits a batch of ciphertexts across its workers, each decrypting its
+//! share on the blocking thread pool, so that a batch is decrypted in parallel
+//! without stalling the async runtime
+
+use std::sync::Arc;
+use std::thread;
+
+use renegade_circuit_types::elgamal::{DecryptionKey, ElGamalCiphertext};
+use renegade_circuit_types::native_helpers::elgamal_decrypt;
+use renegade_circuit_types::note::{Note, NOTE_CIPHERTEXT_SIZE};
+use renegade_circuit_types::wallet::NoteCommitment;
+use renegade_constants::Scalar;
+use renegade_crypto::fields::{scalar_to_biguint, scalar_to_u128};
+use renegade_util::raw_err_str;
+
+/// The ciphertext of a fee note
+pub type NoteCiphertext = ElGamalCiphertext<NOTE_CIPHERTEXT_SIZE>;
+
+/// A pool of workers decrypting batches of note ciphertexts in parallel
+#[derive(Clone, Copy, Debug)]
+pub struct DecryptionPool {
+    /// The number of workers a batch is split across
+    workers: usize,
+}
+
+impl DecryptionPool {
+    /// Create a pool of the given number of workers, or of one worker per
+    /// available CPU if zero
+    pub fn new(workers: usize) -> Self {
+        let workers = match workers {
+            0 => thread::available_parallelism().map_or(1, |n| n.get()),
+            n => n,
+        };
+        Self { workers }
+    }
+
+    /// The number of workers a batch is split across
+    pub fn workers(&self) -> usize {
+        self.workers
+    }
+
+    /// Decrypt a batch of notes, each given by its ciphertext and the
+    /// commitment posted for it, under each of the given keys
+    ///
+    /// Returns, for each note in order, the index of the key it decrypts to its
+    /// commitment under and the decrypted note, or `None` if no key does
+    pub async fn decrypt(
+        &self,
+        notes: Vec<(NoteCiphertext, NoteCommitment)>,
+        keys: Arc<Vec<DecryptionKey>>,
+    ) -> Result<Vec<Option<(usize, Note)>>, String> {
+        if notes.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let share_size = notes.len().div_ceil(self.workers);
+        let mut shares = Vec::with_capacity(self.workers);
+        let mut notes = notes.into_iter();
+        loop {
+            let share: Vec<_> = notes.by_ref().take(share_size).collect();
+            if share.is_empty() {
+                break;
+            }
+            let keys = keys.clone();
+            shares.push(tokio::task::spawn_blocking(move || {
+                share
+                    .iter()
+                    .map(|(ciphertext, commitment)| find_receiver(ciphertext, *commitment, &keys))
+                    .collect::<Vec<_>>()
+            }));
+        }
+
+        let mut decrypted = Vec::with_capacity(share_size * shares.len());
+        for share in shares {
+            let share = share
+                .await
+                .map_err(raw_err_str!("decryption worker panicked: {}"))?;
+            decrypted.extend(share);
+        }
+
+        Ok(decrypted)
+    }
+}
+
+/// Find the key a note decrypts to its commitment under, if any, returning
+/// its index and the decrypted note
+pub fn find_receiver(
+    ciphertext: &NoteCiphertext,
+    commitment: NoteCommitment,
+    keys: &[DecryptionKey],
+) -> Option<(usize, Note)> {
+    keys.iter()
+        .map(|key| decrypt_note(ciphertext, key))
+        .enumerate()
+        .find(|(_, note)| note.commitment() == commitment)
+}
+
+/// Decrypt a note using a decryption key
+pub fn decrypt_note(note: &NoteCiphertext, key: &DecryptionKey) -> Note {
+    // The ciphertext stores all note values except the encryption key
+    let cleartext_values: [Scalar; NOTE_CIPHERTEXT_SIZE] = elgamal_decrypt(note, key);
+
+    Note {
+        mint: scalar_to_biguint(&cleartext_values[0]),
+        amount: scalar_to_u128(&cleartext_values[1]),
+        receiver: key.public_key(),
+        blinder: cleartext_values[2],
+    }
+}
@@ -0,0 +1,42 @@
+//! Bindings for the subset of the ERC-20 interface used by the sweeper
+
+use ethers::{
+    middleware::SignerMiddleware,
+    providers::{Http, Provider},
+};
+
+use crate::error::FeeSweeperError;
+use crate::signer::SubmissionSigner;
+
+pub use self::bindings::Erc20;
+
+/// The generated contract bindings
+#[allow(missing_docs, clippy::missing_docs_in_private_items)]
+mod bindings {
+    ethers::contract::abigen!(
+        Erc20,
+        r#"[
+            function decimals() external view returns (uint8)
+            function balanceOf(address account) external view returns (uint256)
+            function allowance(address owner, address spender) external view returns (uint256)
+            function approve(address spender, uint256 amount) external returns (bool)
+            function transfer(address to, uint256 amount) external returns (bool)
+        ]"#
+    );
+}
+
+/// A client that signs transactions with the sweeper's submission key
+pub type SignerClient = SignerMiddleware<Provider<Http>, SubmissionSigner>;
+
+/// Build a client signing with the given signer against the given RPC
+pub async fn build_signer_client(
+    rpc_url: &str,
+    signer: SubmissionSigner,
+) -> Result<SignerClient, FeeSweeperError> {
+    let provider =
+        Provider::<Http>::try_from(rpc_url).map_err(FeeSweeperError::config("invalid rpc url"))?;
+
+    SignerMiddleware::new_with_provider_chain(provider, signer)
+        .await
+        .map_err(FeeSweeperError::rpc("failed to build signer client"))
+}
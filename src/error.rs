@@ -0,0 +1,113 @@
+//! The errors of the fee sweeper
+//!
+//! Each error is classified by the subsystem it arose in, so that a failure
+//! worth retrying, e.g. an RPC node or the relayer timing out, may be told
+//! apart from one that no retry fixes, e.g. a misconfigured key. Errors are
+//! classified where they arise and never pass through a `String`, so that no
+//! boundary loses the classification
+
+use std::fmt::Display;
+use std::time::Duration;
+
+use thiserror::Error;
+
+/// An error from the fee sweeper
+///
+/// The message of each variant is displayed as is, so that classifying an
+/// error does not change the messages logged, alerted on, or recorded
+#[derive(Clone, Debug, Error)]
+pub enum FeeSweeperError {
+    /// A query to the DB failed, or no connection to it could be made
+    #[error("{0}")]
+    Db(String),
+    /// A call to the chain's RPC node, or to another remote service the
+    /// sweeper depends on, e.g. AWS or a price or explorer API, failed
+    #[error("{0}")]
+    Rpc(String),
+    /// A request to the relayer, or a task it ran, failed
+    #[error("{0}")]
+    Relayer(String),
+    /// A note could not be decrypted, or the keys decrypting notes could not
+    /// be loaded
+    #[error("{0}")]
+    Decryption(String),
+    /// A redemption or conversion policy refused or could not be applied
+    #[error("{0}")]
+    Policy(String),
+    /// The sweeper is misconfigured, e.g. given an invalid key or flag
+    #[error("{0}")]
+    Config(String),
+    /// An operator's request is invalid, e.g. to release a mint that is not
+    /// quarantined
+    #[error("{0}")]
+    Invalid(String),
+    /// A phase of the run exhausted its DB time budget and was aborted
+    #[error("{phase} phase exceeded its DB budget of {limit:?}")]
+    BudgetExceeded {
+        /// The name of the phase
+        phase: &'static str,
+        /// The phase's budget
+        limit: Duration,
+    },
+    /// An error not otherwise classified
+    #[error("{0}")]
+    Other(String),
+}
+
+impl FeeSweeperError {
+    /// The kind of the error, as labeled in metrics
+    pub fn kind(&self) -> &'static str {
+        match self {
+            FeeSweeperError::Db(_) => "db",
+            FeeSweeperError::Rpc(_) => "rpc",
+            FeeSweeperError::Relayer(_) => "relayer",
+            FeeSweeperError::Decryption(_) => "decryption",
+            FeeSweeperError::Policy(_) => "policy",
+            FeeSweeperError::Config(_) => "config",
+            FeeSweeperError::Invalid(_) => "invalid",
+            FeeSweeperError::BudgetExceeded { .. } => "budget_exceeded",
+            FeeSweeperError::Other(_) => "other",
+        }
+    }
+
+    /// Whether the error is fatal to the run, such that no retry may succeed
+    /// until an operator intervenes
+    pub fn is_fatal(&self) -> bool {
+        matches!(self, FeeSweeperError::Config(_))
+    }
+
+    /// Wrap a DB error with context, for use with `map_err`
+    pub fn db<E: Display>(context: &'static str) -> impl FnOnce(E) -> Self {
+        move |e| FeeSweeperError::Db(format!("{context}: {e}"))
+    }
+
+    /// Wrap an RPC error with context, for use with `map_err`
+    pub fn rpc<E: Display>(context: &'static str) -> impl FnOnce(E) -> Self {
+        move |e| FeeSweeperError::Rpc(format!("{context}: {e}"))
+    }
+
+    /// Wrap a relayer error with context, for use with `map_err`
+    pub fn relayer<E: Display>(context: &'static str) -> impl FnOnce(E) -> Self {
+        move |e| FeeSweeperError::Relayer(format!("{context}: {e}"))
+    }
+
+    /// Wrap a decryption error with context, for use with `map_err`
+    pub fn decryption<E: Display>(context: &'static str) -> impl FnOnce(E) -> Self {
+        move |e| FeeSweeperError::Decryption(format!("{context}: {e}"))
+    }
+
+    /// Wrap a policy error with context, for use with `map_err`
+    pub fn policy<E: Display>(context: &'static str) -> impl FnOnce(E) -> Self {
+        move |e| FeeSweeperError::Policy(format!("{context}: {e}"))
+    }
+
+    /// Wrap a configuration error with context, for use with `map_err`
+    pub fn config<E: Display>(context: &'static str) -> impl FnOnce(E) -> Self {
+        move |e| FeeSweeperError::Config(format!("{context}: {e}"))
+    }
+
+    /// Wrap an unclassified error with context, for use with `map_err`
+    pub fn other<E: Display>(context: &'static str) -> impl FnOnce(E) -> Self {
+        move |e| FeeSweeperError::Other(format!("{context}: {e}"))
+    }
+}
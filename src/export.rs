@@ -0,0 +1,308 @@
+//! Tabular exports of sweeper data in a selectable format
+//!
+//! Every export goes through the same path: rows are decoded into an Arrow
+//! record batch against the export's schema, and the batch is written by the
+//! writer of the selected format. The row-oriented formats, JSON Lines and
+//! CSV, are compressed as a stream; Parquet and Arrow compress internally
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+
+use arrow::{
+    csv::{Writer as CsvWriter, WriterBuilder as CsvWriterBuilder},
+    datatypes::SchemaRef,
+    ipc::{
+        writer::{FileWriter as IpcFileWriter, IpcWriteOptions},
+        CompressionType,
+    },
+    json::{LineDelimitedWriter, ReaderBuilder},
+    record_batch::RecordBatch,
+};
+use clap::{Args, ValueEnum};
+use flate2::{write::GzEncoder, Compression as GzipLevel};
+use parquet::{
+    arrow::ArrowWriter,
+    basic::{Compression as ParquetCompression, GzipLevel as ParquetGzipLevel, ZstdLevel},
+    file::properties::WriterProperties,
+};
+use serde::Serialize;
+
+use crate::error::FeeSweeperError;
+
+/// The arguments selecting how an export is written
+#[derive(Debug, Args)]
+pub struct ExportArgs {
+    /// The format in which to write the export
+    #[clap(long, value_enum, default_value_t = ExportFormat::JsonLines)]
+    pub format: ExportFormat,
+    /// The compression applied to the export
+    #[clap(long, value_enum, default_value_t = ExportCompression::None)]
+    pub compression: ExportCompression,
+    /// The file to write the export to, stdout if unset
+    #[clap(long)]
+    pub out: Option<PathBuf>,
+}
+
+/// The format of an export
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum ExportFormat {
+    /// One JSON object per row
+    JsonLines,
+    /// CSV with a header row
+    Csv,
+    /// A Parquet file
+    Parquet,
+    /// An Arrow IPC file
+    Arrow,
+}
+
+/// The compression of an export
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum ExportCompression {
+    /// No compression
+    None,
+    /// Gzip, unsupported for Arrow files
+    Gzip,
+    /// Zstandard
+    Zstd,
+}
+
+/// A writer of record batches in an export format
+pub trait ExportWriter {
+    /// Write a batch of rows
+    fn write(&mut self, batch: &RecordBatch) -> Result<(), FeeSweeperError>;
+
+    /// Finish the export, flushing any buffered rows and trailers
+    fn finish(self: Box<Self>) -> Result<(), FeeSweeperError>;
+}
+
+/// Export rows in the format selected by the arguments
+///
+/// Each row must serialize to a JSON object whose fields match the schema
+pub fn export_rows<T: Serialize>(
+    args: &ExportArgs,
+    schema: SchemaRef,
+    rows: &[T],
+) -> Result<(), FeeSweeperError> {
+    let mut decoder = ReaderBuilder::new(schema.clone())
+        .build_decoder()
+        .map_err(FeeSweeperError::other("failed to build row decoder"))?;
+    decoder
+        .serialize(rows)
+        .map_err(FeeSweeperError::other("failed to decode rows"))?;
+    let batch = decoder
+        .flush()
+        .map_err(FeeSweeperError::other("failed to decode rows"))?
+        .unwrap_or_else(|| RecordBatch::new_empty(schema.clone()));
+
+    let mut writer = new_writer(args, schema)?;
+    writer.write(&batch)?;
+    writer.finish()
+}
+
+/// Create the writer of the export format selected by the arguments
+pub fn new_writer(
+    args: &ExportArgs,
+    schema: SchemaRef,
+) -> Result<Box<dyn ExportWriter>, FeeSweeperError> {
+    let out = open_output(args.out.as_ref())?;
+    let writer: Box<dyn ExportWriter> = match args.format {
+        ExportFormat::JsonLines => {
+            let sink = Sink::new(out, args.compression);
+            Box::new(JsonLinesExport(LineDelimitedWriter::new(sink)))
+        }
+        ExportFormat::Csv => {
+            let sink = Sink::new(out, args.compression);
+            Box::new(CsvExport(
+                CsvWriterBuilder::new().with_header(true).build(sink),
+            ))
+        }
+        ExportFormat::Parquet => {
+            let compression = match args.compression {
+                ExportCompression::None => ParquetCompression::UNCOMPRESSED,
+                ExportCompression::Gzip => ParquetCompression::GZIP(ParquetGzipLevel::default()),
+                ExportCompression::Zstd => ParquetCompression::ZSTD(ZstdLevel::default()),
+            };
+            let props = WriterProperties::builder()
+                .set_compression(compression)
+                .build();
+            let writer = ArrowWriter::try_new(out, schema, Some(props))
+                .map_err(FeeSweeperError::other("failed to create parquet writer"))?;
+            Box::new(ParquetExport(writer))
+        }
+        ExportFormat::Arrow => {
+            let compression = match args.compression {
+                ExportCompression::None => None,
+                ExportCompression::Gzip => {
+                    return Err(FeeSweeperError::Config(
+                        "arrow exports do not support gzip compression".to_string(),
+                    ))
+                }
+                ExportCompression::Zstd => Some(CompressionType::ZSTD),
+            };
+            let options = IpcWriteOptions::default()
+                .try_with_compression(compression)
+                .map_err(FeeSweeperError::other("invalid arrow write options"))?;
+            let writer = IpcFileWriter::try_new_with_options(out, &schema, options)
+                .map_err(FeeSweeperError::other("failed to create arrow writer"))?;
+            Box::new(ArrowExport(writer))
+        }
+    };
+
+    Ok(writer)
+}
+
+/// Open the output of an export, stdout if no file is given
+fn open_output(path: Option<&PathBuf>) -> Result<Box<dyn Write + Send>, FeeSweeperError> {
+    match path {
+        Some(path) => {
+            let file = File::create(path).map_err(|e| {
+                FeeSweeperError::Other(format!("failed to create {}: {e}", path.display()))
+            })?;
+            Ok(Box::new(BufWriter::new(file)))
+        }
+        None => Ok(Box::new(BufWriter::new(io::stdout()))),
+    }
+}
+
+// ----------------------
+// | Stream Compression |
+// ----------------------
+
+/// The output of a row-oriented export, compressed as a stream
+enum Sink {
+    /// Uncompressed output
+    Plain(Box<dyn Write + Send>),
+    /// Gzip compressed output
+    Gzip(GzEncoder<Box<dyn Write + Send>>),
+    /// Zstandard compressed output
+    Zstd(zstd::Encoder<'static, Box<dyn Write + Send>>),
+}
+
+impl Sink {
+    /// Wrap an output in the given compression
+    fn new(out: Box<dyn Write + Send>, compression: ExportCompression) -> Self {
+        match compression {
+            ExportCompression::None => Sink::Plain(out),
+            ExportCompression::Gzip => Sink::Gzip(GzEncoder::new(out, GzipLevel::default())),
+            // Creating an encoder at the default level only fails on allocation
+            ExportCompression::Zstd => Sink::Zstd(zstd::Encoder::new(out, 0).unwrap()),
+        }
+    }
+
+    /// Write the compression trailer, if any, and flush the output
+    fn finish(self) -> Result<(), FeeSweeperError> {
+        let mut out = match self {
+            Sink::Plain(out) => out,
+            Sink::Gzip(encoder) => encoder
+                .finish()
+                .map_err(FeeSweeperError::other("failed to finish gzip stream"))?,
+            Sink::Zstd(encoder) => encoder
+                .finish()
+                .map_err(FeeSweeperError::other("failed to finish zstd stream"))?,
+        };
+
+        out.flush()
+            .map_err(FeeSweeperError::other("failed to flush export"))
+    }
+}
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Sink::Plain(out) => out.write(buf),
+            Sink::Gzip(encoder) => encoder.write(buf),
+            Sink::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Sink::Plain(out) => out.flush(),
+            Sink::Gzip(encoder) => encoder.flush(),
+            Sink::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+// -----------
+// | Writers |
+// -----------
+
+/// An export as JSON Lines
+struct JsonLinesExport(LineDelimitedWriter<Sink>);
+
+impl ExportWriter for JsonLinesExport {
+    fn write(&mut self, batch: &RecordBatch) -> Result<(), FeeSweeperError> {
+        self.0
+            .write(batch)
+            .map_err(FeeSweeperError::other("failed to write json lines"))
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), FeeSweeperError> {
+        let mut writer = self.0;
+        writer
+            .finish()
+            .map_err(FeeSweeperError::other("failed to finish json lines"))?;
+        writer.into_inner().finish()
+    }
+}
+
+/// An export as CSV
+struct CsvExport(CsvWriter<Sink>);
+
+impl ExportWriter for CsvExport {
+    fn write(&mut self, batch: &RecordBatch) -> Result<(), FeeSweeperError> {
+        self.0
+            .write(batch)
+            .map_err(FeeSweeperError::other("failed to write csv"))
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), FeeSweeperError> {
+        self.0.into_inner().finish()
+    }
+}
+
+/// An export as a Parquet file
+struct ParquetExport(ArrowWriter<Box<dyn Write + Send>>);
+
+impl ExportWriter for ParquetExport {
+    fn write(&mut self, batch: &RecordBatch) -> Result<(), FeeSweeperError> {
+        self.0
+            .write(batch)
+            .map_err(FeeSweeperError::other("failed to write parquet"))
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), FeeSweeperError> {
+        let mut out = self
+            .0
+            .into_inner()
+            .map_err(FeeSweeperError::other("failed to finish parquet file"))?;
+        out.flush()
+            .map_err(FeeSweeperError::other("failed to flush export"))
+    }
+}
+
+/// An export as an Arrow IPC file
+struct ArrowExport(IpcFileWriter<Box<dyn Write + Send>>);
+
+impl ExportWriter for ArrowExport {
+    fn write(&mut self, batch: &RecordBatch) -> Result<(), FeeSweeperError> {
+        self.0
+            .write(batch)
+            .map_err(FeeSweeperError::other("failed to write arrow batch"))
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), FeeSweeperError> {
+        let mut writer = self.0;
+        writer
+            .finish()
+            .map_err(FeeSweeperError::other("failed to finish arrow file"))?;
+        let mut out = writer
+            .into_inner()
+            .map_err(FeeSweeperError::other("failed to finish arrow file"))?;
+        out.flush()
+            .map_err(FeeSweeperError::other("failed to flush export"))
+    }
+}
@@ -0,0 +1,141 @@
+//! Feature flags gating risky subsystems
+//!
+//! A flag is checked by name and is off unless enabled, so that a subsystem
+//! may ship dark and be enabled per environment without a rebuild. Flags are
+//! stored in the DB and re-read at the start of each sweep; a flag given in
+//! the instance's configuration with `--feature-flag` overrides the DB, e.g.
+//! to keep a subsystem off in one environment whatever the shared DB says
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use clap::Args;
+use tracing::{info, warn};
+
+use crate::db::models::FeatureFlag;
+use crate::error::FeeSweeperError;
+use crate::Indexer;
+
+/// The flag gating the automatic withdrawal of converted fees
+pub const AUTO_WITHDRAWAL_FLAG: &str = "auto-withdrawal";
+/// The flag gating the conversion of redeemed fees to USDC through orders
+pub const SWAPS_FLAG: &str = "swaps";
+/// The flag gating redemptions settled by the sweeper rather than the relayer
+pub const DIRECT_REDEMPTION_FLAG: &str = "direct-redemption";
+
+/// Every flag the sweeper checks
+pub const KNOWN_FLAGS: [&str; 3] = [AUTO_WITHDRAWAL_FLAG, SWAPS_FLAG, DIRECT_REDEMPTION_FLAG];
+
+/// Check that a flag is one the sweeper checks
+pub fn check_known_flag(name: &str) -> Result<(), FeeSweeperError> {
+    if KNOWN_FLAGS.contains(&name) {
+        return Ok(());
+    }
+
+    Err(FeeSweeperError::Invalid(format!(
+        "unknown feature flag {name}, expected one of {}",
+        KNOWN_FLAGS.join(", ")
+    )))
+}
+
+/// A flag set in the instance's configuration
+///
+/// Parsed from a string of the form `<name>=<on|off>`
+#[derive(Clone, Debug)]
+pub struct FlagOverride {
+    /// The name of the flag
+    pub name: String,
+    /// Whether the flag is enabled
+    pub enabled: bool,
+}
+
+impl FromStr for FlagOverride {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, state) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected <name>=<on|off>: {s}"))?;
+        let name = name.trim().to_string();
+        check_known_flag(&name).map_err(|e| e.to_string())?;
+        let enabled = match state.trim() {
+            "on" | "true" => true,
+            "off" | "false" => false,
+            _ => return Err(format!("expected on or off for {name}: {state}")),
+        };
+
+        Ok(FlagOverride { name, enabled })
+    }
+}
+
+/// The arguments configuring feature flags
+#[derive(Clone, Debug, Args)]
+pub struct FeatureFlagArgs {
+    /// A feature flag overriding the DB, of the form `<name>=<on|off>`
+    #[clap(long = "feature-flag")]
+    pub feature_flags: Vec<FlagOverride>,
+}
+
+/// The feature flags of an instance
+pub struct FeatureFlags {
+    /// The flags set in the instance's configuration
+    overrides: HashMap<String, bool>,
+    /// The flags stored in the DB, as of the last refresh
+    stored: HashMap<String, bool>,
+}
+
+impl FeatureFlags {
+    /// Create the flags of an instance, before they are read from the DB
+    pub fn new(args: FeatureFlagArgs) -> Self {
+        let overrides = args
+            .feature_flags
+            .into_iter()
+            .map(|flag| (flag.name, flag.enabled))
+            .collect();
+        Self {
+            overrides,
+            stored: HashMap::new(),
+        }
+    }
+
+    /// Replace the flags stored in the DB with a fresh read of them
+    ///
+    /// A failed read keeps the flags of the last refresh, so that a DB hiccup
+    /// neither fails a sweep nor flips a subsystem
+    pub fn refresh(&mut self, read: Result<Vec<FeatureFlag>, FeeSweeperError>) {
+        match read {
+            Ok(flags) => {
+                self.stored = flags
+                    .into_iter()
+                    .map(|flag| (flag.name, flag.enabled))
+                    .collect();
+            }
+            Err(e) => warn!("failed to refresh feature flags, keeping the last read: {e}"),
+        }
+    }
+
+    /// Whether a flag is enabled
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.overrides
+            .get(name)
+            .or_else(|| self.stored.get(name))
+            .copied()
+            .unwrap_or(false)
+    }
+
+    /// Log the state of every known flag
+    pub fn log_states(&self) {
+        for name in KNOWN_FLAGS {
+            let state = if self.is_enabled(name) { "on" } else { "off" };
+            info!("feature flag {name}: {state}");
+        }
+    }
+}
+
+impl Indexer {
+    /// Re-read the feature flags stored in the DB
+    pub async fn refresh_feature_flags(&mut self) {
+        let read = self.get_feature_flags().await;
+        self.feature_flags.refresh(read);
+    }
+}
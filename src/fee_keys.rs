@@ -0,0 +1,237 @@
+//! The kinds of fees the sweeper sweeps, and the decryption keys of each
+//!
+//! Protocol fees and relayer fees are paid into notes encrypted under
+//! different keys, and are redeemed into separate wallets. The sweeper is
+//! configured to sweep one kind or both, and must be given exactly the keys
+//! of the kinds it sweeps, so that a key is never silently used for the wrong
+//! kind of fee.
+//!
+//! A kind may have several keys, as when a key is rotated: notes are then
+//! paid to the new key while those paid to the old one still await
+//! redemption. Each note is tried against every key, and the fee records the
+//! public key of the key that decrypted it as its receiver. Besides the keys
+//! an instance is configured with, keys may be registered in the DB, each
+//! held in a Secrets Manager secret, so that a new key is picked up by the
+//! next sweep without a redeploy. A configured key may itself be given as the
+//! ARN of a secret holding it, in which case it is re-read on every reload
+
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use clap::{Args, ValueEnum};
+use renegade_circuit_types::elgamal::DecryptionKey;
+use renegade_util::hex::jubjub_to_hex_string;
+use tracing::warn;
+
+use crate::aws::AwsContext;
+use crate::db::models::RegisteredFeeKey;
+use crate::error::FeeSweeperError;
+use crate::secrets::{read_secret_string, KeySource};
+
+/// A kind of fee
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum FeeKind {
+    /// Fees paid to the protocol
+    Protocol,
+    /// Fees paid to the relayer
+    Relayer,
+}
+
+impl FeeKind {
+    /// The label of the fee kind, as stored in the database
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FeeKind::Protocol => "protocol",
+            FeeKind::Relayer => "relayer",
+        }
+    }
+}
+
+impl Display for FeeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for FeeKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "protocol" => Ok(FeeKind::Protocol),
+            "relayer" => Ok(FeeKind::Relayer),
+            _ => Err(format!("unknown fee kind: {s}")),
+        }
+    }
+}
+
+/// The kinds of fees swept
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum SweepMode {
+    /// Sweep protocol fees only
+    Protocol,
+    /// Sweep relayer fees only
+    Relayer,
+    /// Sweep both protocol and relayer fees
+    Both,
+}
+
+impl SweepMode {
+    /// Whether fees of the given kind are swept
+    pub fn sweeps(&self, kind: FeeKind) -> bool {
+        matches!(
+            (self, kind),
+            (SweepMode::Both, _)
+                | (SweepMode::Protocol, FeeKind::Protocol)
+                | (SweepMode::Relayer, FeeKind::Relayer)
+        )
+    }
+}
+
+/// The arguments configuring the kinds of fees swept and their keys
+#[derive(Clone, Debug, Args)]
+pub struct FeeKeyArgs {
+    /// The kinds of fees to sweep
+    #[clap(long, value_enum, default_value_t = SweepMode::Protocol)]
+    pub sweep_mode: SweepMode,
+    /// A decryption key of protocol fee notes, or the ARN of a Secrets
+    /// Manager secret holding one; may be given several times, or as a
+    /// comma-separated list, e.g. while the notes of a rotated key await
+    /// redemption. Sweeping protocol fees requires a key, given here or
+    /// registered in the DB
+    #[clap(
+        short = 'd',
+        long,
+        alias = "decryption-key",
+        env = "DECRYPTION_KEY",
+        hide_env_values = true,
+        value_delimiter = ','
+    )]
+    pub protocol_decryption_key: Vec<KeySource>,
+    /// A decryption key of relayer fee notes, or the ARN of a Secrets Manager
+    /// secret holding one; may be given several times. Sweeping relayer fees
+    /// requires a key, given here or registered in the DB
+    #[clap(long)]
+    pub relayer_decryption_key: Vec<KeySource>,
+    /// The kind of the fees paid to the external fee recipient
+    #[clap(long, value_enum, default_value_t = FeeKind::Protocol)]
+    pub external_fee_kind: FeeKind,
+}
+
+/// The decryption key of a kind of fee
+#[derive(Clone, Copy)]
+pub struct FeeKey {
+    /// The kind of fee the key decrypts
+    pub kind: FeeKind,
+    /// The decryption key
+    pub key: DecryptionKey,
+}
+
+impl FeeKey {
+    /// The receiver of the notes the key decrypts, as stored in the database
+    pub fn receiver(&self) -> String {
+        jubjub_to_hex_string(&self.key.public_key())
+    }
+}
+
+impl FeeKeyArgs {
+    /// Resolve the keys the instance is configured with, reading those given
+    /// as secrets, and checking that no key is given for a kind of fee not
+    /// swept
+    pub async fn fee_keys(&self, aws: &AwsContext) -> Result<Vec<FeeKey>, FeeSweeperError> {
+        let configured = [
+            (FeeKind::Protocol, &self.protocol_decryption_key),
+            (FeeKind::Relayer, &self.relayer_decryption_key),
+        ];
+
+        let mut keys = Vec::new();
+        for (kind, kind_keys) in configured {
+            if !kind_keys.is_empty() && !self.sweep_mode.sweeps(kind) {
+                return Err(FeeSweeperError::Config(format!(
+                    "a {kind} decryption key was given but {kind} fees are not swept"
+                )));
+            }
+            for source in kind_keys {
+                let key = DecryptionKey::from_hex_str(&source.resolve(aws).await?)
+                    .map_err(FeeSweeperError::Decryption)?;
+                keys.push(FeeKey { kind, key });
+            }
+        }
+        Ok(keys)
+    }
+
+    /// Merge the configured keys with those registered in the DB, checking
+    /// that each kind swept has a key and that no key serves both kinds
+    ///
+    /// Registered keys of kinds not swept are ignored, and a key both
+    /// configured and registered is kept once
+    pub fn resolve_fee_keys(
+        &self,
+        configured: &[FeeKey],
+        registered: Vec<FeeKey>,
+    ) -> Result<Vec<FeeKey>, FeeSweeperError> {
+        let mut keys: Vec<FeeKey> = Vec::new();
+        let swept = registered
+            .into_iter()
+            .filter(|fee_key| self.sweep_mode.sweeps(fee_key.kind));
+        for fee_key in configured.iter().copied().chain(swept) {
+            match keys.iter().find(|k| k.receiver() == fee_key.receiver()) {
+                Some(existing) if existing.kind != fee_key.kind => {
+                    return Err(FeeSweeperError::Config(format!(
+                        "key with receiver {} is given for both {} and {} fees",
+                        fee_key.receiver(),
+                        existing.kind,
+                        fee_key.kind
+                    )));
+                }
+                Some(_) => {}
+                None => keys.push(fee_key),
+            }
+        }
+
+        for kind in [FeeKind::Protocol, FeeKind::Relayer] {
+            if self.sweep_mode.sweeps(kind) && !keys.iter().any(|k| k.kind == kind) {
+                return Err(FeeSweeperError::Config(format!(
+                    "sweeping {kind} fees requires a {kind} decryption key"
+                )));
+            }
+        }
+        Ok(keys)
+    }
+}
+
+/// Load the given keys registered in the DB from their secrets
+///
+/// A key whose secret no longer matches the receiver it was registered
+/// under is skipped, so that a secret overwritten by mistake is never used
+/// to redeem the fees of another key
+pub async fn load_registered_fee_keys(
+    registered_keys: Vec<RegisteredFeeKey>,
+    aws: &AwsContext,
+) -> Result<Vec<FeeKey>, FeeSweeperError> {
+    let mut keys = Vec::new();
+    for registered in registered_keys {
+        let kind = FeeKind::from_str(&registered.fee_kind).map_err(FeeSweeperError::Db)?;
+        let key = read_fee_key_secret(aws, &registered.secret_name).await?;
+        let fee_key = FeeKey { kind, key };
+        if fee_key.receiver() != registered.receiver {
+            warn!(
+                "secret {} no longer holds the key of receiver {}, skipping it",
+                registered.secret_name, registered.receiver
+            );
+            continue;
+        }
+        keys.push(fee_key);
+    }
+
+    Ok(keys)
+}
+
+/// Read a hex-encoded decryption key from a Secrets Manager secret
+pub async fn read_fee_key_secret(
+    aws: &AwsContext,
+    secret_name: &str,
+) -> Result<DecryptionKey, FeeSweeperError> {
+    let secret_str = read_secret_string(aws, secret_name).await?;
+    DecryptionKey::from_hex_str(&secret_str).map_err(FeeSweeperError::Decryption)
+}
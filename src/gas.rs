@@ -0,0 +1,162 @@
+//! EIP-1559 fee estimation for transactions the sweeper submits directly
+
+use std::sync::Arc;
+
+use clap::{Args, ValueEnum};
+use ethers::{
+    contract::ContractCall,
+    middleware::Middleware,
+    signers::Signer,
+    types::{transaction::eip2718::TypedTransaction, BlockNumber, U256},
+    utils::parse_units,
+};
+use tracing::info;
+
+use crate::chain::EvmChain;
+use crate::erc20::SignerClient;
+use crate::error::FeeSweeperError;
+
+/// The default number of recent blocks sampled by the percentile strategy
+const DEFAULT_FEE_HISTORY_BLOCKS: u64 = 20;
+/// The default priority fee percentile sampled by the percentile strategy
+const DEFAULT_FEE_PERCENTILE: f64 = 50.;
+/// The multiple of the latest base fee allowed by the percentile strategy
+const BASE_FEE_MULTIPLIER: u64 = 2;
+
+/// A strategy for choosing `maxFeePerGas` and `maxPriorityFeePerGas`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum FeeStrategy {
+    /// Use the provider's `eth_feeHistory` based estimate
+    Provider,
+    /// Use fixed, configured fees
+    Fixed,
+    /// Use a percentile of the priority fees paid in recent blocks
+    Percentile,
+}
+
+impl FeeStrategy {
+    /// The default strategy on a chain, as chosen by its adapter
+    pub fn default_for_chain(chain_id: u64) -> Self {
+        EvmChain::from_chain_id(chain_id)
+            .adapter()
+            .default_fee_strategy()
+    }
+}
+
+/// The fee estimation arguments shared by commands that submit transactions
+#[derive(Clone, Debug, Args)]
+pub struct GasArgs {
+    /// The fee estimation strategy; defaults to the chain's default strategy
+    #[clap(long, value_enum)]
+    pub fee_strategy: Option<FeeStrategy>,
+    /// The `maxFeePerGas` in gwei used by the fixed strategy
+    #[clap(long)]
+    pub max_fee_gwei: Option<f64>,
+    /// The `maxPriorityFeePerGas` in gwei used by the fixed strategy
+    #[clap(long)]
+    pub max_priority_fee_gwei: Option<f64>,
+    /// The priority fee percentile sampled by the percentile strategy
+    #[clap(long, default_value_t = DEFAULT_FEE_PERCENTILE)]
+    pub fee_percentile: f64,
+    /// The number of recent blocks sampled by the percentile strategy
+    #[clap(long, default_value_t = DEFAULT_FEE_HISTORY_BLOCKS)]
+    pub fee_history_blocks: u64,
+}
+
+/// Estimates EIP-1559 fees according to a configured strategy
+#[derive(Clone)]
+pub struct FeeEstimator {
+    /// The client used to sample fees
+    client: Arc<SignerClient>,
+    /// The strategy in use
+    strategy: FeeStrategy,
+    /// The fees used by the fixed strategy, as `(max_fee, max_priority_fee)`
+    fixed_fees: Option<(U256, U256)>,
+    /// The priority fee percentile sampled by the percentile strategy
+    percentile: f64,
+    /// The number of blocks sampled by the percentile strategy
+    history_blocks: u64,
+}
+
+impl FeeEstimator {
+    /// Build an estimator for the chain the client is connected to
+    pub fn new(client: Arc<SignerClient>, args: &GasArgs) -> Result<Self, FeeSweeperError> {
+        let chain_id = client.signer().chain_id();
+        let strategy = args
+            .fee_strategy
+            .unwrap_or_else(|| FeeStrategy::default_for_chain(chain_id));
+
+        let fixed_fees = match (args.max_fee_gwei, args.max_priority_fee_gwei) {
+            (Some(max_fee), Some(priority_fee)) => Some((gwei(max_fee)?, gwei(priority_fee)?)),
+            _ if strategy == FeeStrategy::Fixed => {
+                return Err(FeeSweeperError::Config(
+                    "the fixed fee strategy requires both fees".to_string(),
+                ))
+            }
+            _ => None,
+        };
+
+        info!("using {strategy:?} fee strategy on chain {chain_id}");
+        Ok(Self {
+            client,
+            strategy,
+            fixed_fees,
+            percentile: args.fee_percentile,
+            history_blocks: args.fee_history_blocks,
+        })
+    }
+
+    /// Estimate `(max_fee_per_gas, max_priority_fee_per_gas)`
+    pub async fn estimate(&self) -> Result<(U256, U256), FeeSweeperError> {
+        match self.strategy {
+            FeeStrategy::Provider => self
+                .client
+                .estimate_eip1559_fees(None)
+                .await
+                .map_err(FeeSweeperError::rpc("failed to estimate fees")),
+            FeeStrategy::Fixed => Ok(self.fixed_fees.expect("checked at construction")),
+            FeeStrategy::Percentile => self.estimate_from_history().await,
+        }
+    }
+
+    /// Set the fees of a contract call according to the strategy
+    pub async fn apply<D>(
+        &self,
+        mut call: ContractCall<SignerClient, D>,
+    ) -> Result<ContractCall<SignerClient, D>, FeeSweeperError> {
+        let (max_fee, priority_fee) = self.estimate().await?;
+        if let TypedTransaction::Eip1559(ref mut tx) = call.tx {
+            tx.max_fee_per_gas = Some(max_fee);
+            tx.max_priority_fee_per_gas = Some(priority_fee);
+        }
+
+        Ok(call)
+    }
+
+    /// Estimate fees from a percentile of recent priority fees
+    async fn estimate_from_history(&self) -> Result<(U256, U256), FeeSweeperError> {
+        let history = self
+            .client
+            .fee_history(self.history_blocks, BlockNumber::Latest, &[self.percentile])
+            .await
+            .map_err(FeeSweeperError::rpc("failed to query fee history"))?;
+
+        let mut rewards: Vec<U256> = history
+            .reward
+            .iter()
+            .filter_map(|block| block.first().copied())
+            .collect();
+        rewards.sort();
+        let priority_fee = rewards.get(rewards.len() / 2).copied().unwrap_or_default();
+
+        let base_fee = history.base_fee_per_gas.last().copied().unwrap_or_default();
+        Ok((base_fee * BASE_FEE_MULTIPLIER + priority_fee, priority_fee))
+    }
+}
+
+/// Convert a gwei amount to wei
+fn gwei(amount: f64) -> Result<U256, FeeSweeperError> {
+    parse_units(amount, "gwei")
+        .map(Into::into)
+        .map_err(FeeSweeperError::config("invalid gwei amount"))
+}
@@ -0,0 +1,266 @@
+//! Client code for fetching historical token prices, used to value fees
+//! indexed before pricing support existed
+
+use std::fmt::{self, Display};
+
+use clap::ValueEnum;
+use renegade_common::types::{exchange::Exchange, token::Token};
+use renegade_util::get_current_time_millis;
+use reqwest::{Client, Response, StatusCode};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::error::FeeSweeperError;
+
+/// The default base URL of the Binance API
+const DEFAULT_BINANCE_URL: &str = "https://api.binance.com";
+/// The default base URL of the CoinGecko API
+const DEFAULT_COINGECKO_URL: &str = "https://api.coingecko.com";
+/// The quote asset used for Binance klines
+const BINANCE_QUOTE_ASSET: &str = "USDT";
+/// The maximum number of klines Binance returns in a single request
+const BINANCE_MAX_KLINES: u64 = 1000;
+/// The length of a Binance one minute kline, in milliseconds
+const BINANCE_KLINE_MS: u64 = 60_000;
+/// The CoinGecko platform id of Arbitrum One
+const COINGECKO_PLATFORM: &str = "arbitrum-one";
+/// The header in which the CoinGecko API key is sent
+const COINGECKO_API_KEY_HEADER: &str = "x-cg-pro-api-key";
+/// The window (seconds) after a timestamp to search for a CoinGecko price
+const COINGECKO_WINDOW_SECS: i64 = 3600;
+
+/// The source of historical prices
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum HistoricalPriceSource {
+    /// Binance one minute klines, priced against USDT
+    Binance,
+    /// CoinGecko market charts, looked up by contract address
+    Coingecko,
+}
+
+impl Display for HistoricalPriceSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HistoricalPriceSource::Binance => write!(f, "binance"),
+            HistoricalPriceSource::Coingecko => write!(f, "coingecko"),
+        }
+    }
+}
+
+/// The response type of the CoinGecko market chart range endpoint
+#[derive(Deserialize)]
+struct CoingeckoMarketChart {
+    /// A list of `[timestamp_ms, price]` pairs
+    prices: Vec<(f64, f64)>,
+}
+
+/// A client for fetching historical prices from a configured source
+pub struct HistoricalPriceClient {
+    /// The source to fetch prices from
+    source: HistoricalPriceSource,
+    /// The base URL of the price source
+    base_url: String,
+    /// An optional API key for the price source
+    api_key: Option<String>,
+    /// The HTTP client sending requests to the price source
+    http_client: Client,
+}
+
+impl HistoricalPriceClient {
+    /// Create a new historical price client
+    pub fn new(
+        source: HistoricalPriceSource,
+        base_url: Option<String>,
+        api_key: Option<String>,
+    ) -> Result<Self, FeeSweeperError> {
+        let base_url = base_url.unwrap_or_else(|| match source {
+            HistoricalPriceSource::Binance => DEFAULT_BINANCE_URL.to_string(),
+            HistoricalPriceSource::Coingecko => DEFAULT_COINGECKO_URL.to_string(),
+        });
+        let http_client = Client::builder()
+            .user_agent("fee-sweeper")
+            .build()
+            .map_err(FeeSweeperError::config("Failed to create reqwest client"))?;
+
+        Ok(Self {
+            source,
+            base_url,
+            api_key,
+            http_client,
+        })
+    }
+
+    /// Get the USD price of a mint at the given unix timestamp (seconds)
+    ///
+    /// Returns `None` if the source has no price for the mint at that time
+    pub async fn get_historical_price(
+        &self,
+        mint: &str,
+        timestamp: i64,
+    ) -> Result<Option<f64>, FeeSweeperError> {
+        let token = Token::from_addr(mint);
+        if token.is_stablecoin() {
+            return Ok(Some(1.0));
+        }
+
+        match self.source {
+            HistoricalPriceSource::Binance => self.get_binance_price(&token, timestamp).await,
+            HistoricalPriceSource::Coingecko => self.get_coingecko_price(mint, timestamp).await,
+        }
+    }
+
+    /// Get a historical price from the open of the Binance kline at the given
+    /// timestamp
+    async fn get_binance_price(
+        &self,
+        token: &Token,
+        timestamp: i64,
+    ) -> Result<Option<f64>, FeeSweeperError> {
+        let ticker = token.get_exchange_ticker(Exchange::Binance);
+        let url = format!(
+            "{}/api/v3/klines?symbol={ticker}{BINANCE_QUOTE_ASSET}&interval=1m&startTime={}&limit=1",
+            self.base_url,
+            timestamp * 1000,
+        );
+
+        // Each kline is an array of the form `[open_time, open, high, low, close, ...]`
+        let klines: Vec<Vec<Value>> = self.get(&url, None /* api_key_header */).await?;
+        let open = klines
+            .first()
+            .and_then(|kline| kline.get(1))
+            .and_then(|v| v.as_str());
+        match open {
+            Some(price) => price
+                .parse::<f64>()
+                .map(Some)
+                .map_err(FeeSweeperError::rpc("invalid kline price")),
+            None => Ok(None),
+        }
+    }
+
+    /// Get the volume of a mint traded over the trailing window, in whole
+    /// units of the mint
+    ///
+    /// Volume is always read from Binance one minute klines, whatever the
+    /// configured price source, paging through windows longer than Binance
+    /// returns in a single request. Returns `None` if Binance does not list
+    /// the mint
+    pub async fn get_recent_volume(
+        &self,
+        mint: &str,
+        window_secs: u64,
+    ) -> Result<Option<f64>, FeeSweeperError> {
+        let base_url = match self.source {
+            HistoricalPriceSource::Binance => self.base_url.as_str(),
+            HistoricalPriceSource::Coingecko => DEFAULT_BINANCE_URL,
+        };
+        let ticker = Token::from_addr(mint).get_exchange_ticker(Exchange::Binance);
+        let end_ms = get_current_time_millis();
+        let window_ms = window_secs.saturating_mul(1000).max(BINANCE_KLINE_MS);
+
+        let mut volume = 0.;
+        let mut cursor = end_ms.saturating_sub(window_ms);
+        while cursor < end_ms {
+            let url = format!(
+                "{base_url}/api/v3/klines?symbol={ticker}{BINANCE_QUOTE_ASSET}&interval=1m\
+                &startTime={cursor}&endTime={end_ms}&limit={BINANCE_MAX_KLINES}",
+            );
+
+            // Binance rejects a symbol it does not list as a bad request
+            let resp = self.send(&url, None /* api_key_header */).await?;
+            if resp.status() == StatusCode::BAD_REQUEST {
+                return Ok(None);
+            }
+
+            // Each kline is an array of the form `[open_time, open, high, low, close, volume, ...]`
+            let klines: Vec<Vec<Value>> = self.parse(resp).await?;
+            for kline in klines.iter() {
+                let kline_volume = kline
+                    .get(5)
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| FeeSweeperError::Rpc("kline missing volume".to_string()))?;
+                volume += kline_volume
+                    .parse::<f64>()
+                    .map_err(FeeSweeperError::rpc("invalid kline volume"))?;
+            }
+
+            // A short page is the last; otherwise resume after its last kline
+            let last_open = klines
+                .last()
+                .and_then(|kline| kline.first())
+                .and_then(|v| v.as_u64());
+            match last_open {
+                Some(last_open) if klines.len() as u64 == BINANCE_MAX_KLINES => {
+                    cursor = last_open + BINANCE_KLINE_MS;
+                }
+                _ => break,
+            }
+        }
+
+        Ok(Some(volume))
+    }
+
+    /// Get the first CoinGecko price within a window of the given timestamp
+    async fn get_coingecko_price(
+        &self,
+        mint: &str,
+        timestamp: i64,
+    ) -> Result<Option<f64>, FeeSweeperError> {
+        let url = format!(
+            "{}/api/v3/coins/{COINGECKO_PLATFORM}/contract/{mint}/market_chart/range?vs_currency=usd&from={timestamp}&to={}",
+            self.base_url,
+            timestamp + COINGECKO_WINDOW_SECS,
+        );
+
+        let chart: CoingeckoMarketChart = self.get(&url, Some(COINGECKO_API_KEY_HEADER)).await?;
+        Ok(chart.prices.first().map(|(_, price)| *price))
+    }
+
+    /// Send a get request to the price source, attaching the API key under the
+    /// given header if one is configured
+    async fn get<Resp>(
+        &self,
+        url: &str,
+        api_key_header: Option<&str>,
+    ) -> Result<Resp, FeeSweeperError>
+    where
+        Resp: for<'de> Deserialize<'de>,
+    {
+        let resp = self.send(url, api_key_header).await?;
+        self.parse(resp).await
+    }
+
+    /// Send a get request to the price source, without checking its status
+    async fn send(
+        &self,
+        url: &str,
+        api_key_header: Option<&str>,
+    ) -> Result<Response, FeeSweeperError> {
+        let mut req = self.http_client.get(url);
+        if let (Some(header), Some(key)) = (api_key_header, &self.api_key) {
+            req = req.header(header, key);
+        }
+
+        req.send()
+            .await
+            .map_err(|e| FeeSweeperError::Rpc(format!("Failed to query {}: {e}", self.source)))
+    }
+
+    /// Parse a successful response from the price source
+    async fn parse<Resp>(&self, resp: Response) -> Result<Resp, FeeSweeperError>
+    where
+        Resp: for<'de> Deserialize<'de>,
+    {
+        if !resp.status().is_success() {
+            return Err(FeeSweeperError::Rpc(format!(
+                "Failed to query {}: {}",
+                self.source,
+                resp.status()
+            )));
+        }
+
+        resp.json::<Resp>()
+            .await
+            .map_err(FeeSweeperError::rpc("Failed to parse price response"))
+    }
+}
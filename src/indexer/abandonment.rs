@@ -0,0 +1,72 @@
+//! Expiration of dust fees that are not realistically recoverable
+//!
+//! Dust fees are never redeemed, but still count towards unredeemed value.
+//! Under an expiration policy, a dust fee left unredeemed for the configured
+//! number of months is marked abandoned, which excludes it from unredeemed
+//! value. An operator may restore an abandoned fee with the `abandoned`
+//! command
+
+use bigdecimal::BigDecimal;
+use chrono::Months;
+use clap::Args;
+use tracing::info;
+
+use crate::db::audit::FEE_ABANDONED_EVENT;
+use crate::db::models::NewAuditEvent;
+use crate::error::FeeSweeperError;
+use crate::notifications::ALERTS_CHANNEL;
+use crate::Indexer;
+
+/// The arguments configuring the expiration of dust fees
+#[derive(Clone, Debug, Args)]
+pub struct AbandonmentArgs {
+    /// The number of months after which an unredeemed dust fee is marked
+    /// abandoned; dust fees never expire if unset
+    #[clap(long)]
+    pub abandon_dust_after_months: Option<u32>,
+}
+
+impl Indexer {
+    /// Mark dust fees left unredeemed past the expiration policy as abandoned
+    pub async fn abandon_stale_dust(&mut self) -> Result<(), FeeSweeperError> {
+        let months = match self.abandonment.abandon_dust_after_months {
+            Some(months) => months,
+            None => return Ok(()),
+        };
+
+        info!("abandoning dust older than {months} months...");
+        let cutoff = self
+            .clock
+            .naive_now()
+            .checked_sub_months(Months::new(months))
+            .ok_or_else(|| {
+                FeeSweeperError::Config(format!("invalid expiration: {months} months"))
+            })?;
+        let abandoned = self.abandon_dust_fees_before(cutoff).await?;
+        if abandoned.is_empty() {
+            return Ok(());
+        }
+
+        for fee in abandoned.iter() {
+            let details = format!("{} {} unredeemed for {months} months", fee.amount, fee.mint);
+            self.record_audit_event(NewAuditEvent::new(
+                FEE_ABANDONED_EVENT,
+                Some(fee.tx_hash.clone()),
+                details,
+            ))
+            .await?;
+        }
+
+        let value: BigDecimal = abandoned
+            .iter()
+            .filter_map(|fee| fee.usd_value.clone())
+            .sum();
+        let notice = format!(
+            "abandoned {} dust fees worth ${} after {months} months",
+            abandoned.len(),
+            value.round(2 /* round_digits */)
+        );
+        self.notify(ALERTS_CHANNEL, notice).await;
+        Ok(())
+    }
+}
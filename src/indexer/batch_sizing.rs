@@ -0,0 +1,95 @@
+//! Back-pressure-aware sizing of redemption batches
+//!
+//! Redemptions are submitted one at a time, each awaiting its relayer task, so
+//! a redemption pass takes roughly its batch size times the relayer's task
+//! latency. When the relayer is under load its tasks slow down, and a fixed
+//! batch overruns the sweep's timeouts. Instead, the latency of recent
+//! redemptions is tracked as a moving average, and each pass redeems only as
+//! many fees as are expected to finish within a time budget. The average is
+//! kept across the jobs of a daemon, so a slow relayer shrinks later passes
+
+use std::time::Duration;
+
+use clap::Args;
+use metrics::gauge;
+
+use super::redeem_fees::MAX_FEES_REDEEMED;
+use crate::error::FeeSweeperError;
+use crate::telemetry::{REDEMPTION_BATCH_SIZE_METRIC, REDEMPTION_LATENCY_METRIC};
+
+/// The arguments configuring the sizing of redemption batches
+#[derive(Clone, Debug, Args)]
+pub struct BatchSizingArgs {
+    /// The time, in seconds, a redemption pass aims to finish within; fewer
+    /// fees are redeemed per pass as the relayer's tasks slow down
+    #[clap(long, default_value_t = 300)]
+    pub redemption_time_budget_secs: u64,
+    /// The fewest fees redeemed per pass, however slow the relayer's tasks
+    #[clap(long, default_value_t = 1)]
+    pub min_redemption_batch: usize,
+    /// The weight, between zero and one, of the latest redemption's latency in
+    /// the moving average of redemption latencies
+    #[clap(long, default_value_t = 0.3)]
+    pub redemption_latency_weight: f64,
+}
+
+/// Sizes redemption batches by the moving average of redemption latencies
+#[derive(Clone, Debug)]
+pub struct BatchSizer {
+    /// The configuration of the batch sizing
+    args: BatchSizingArgs,
+    /// The moving average of redemption latencies, if any was observed
+    latency: Option<Duration>,
+}
+
+impl BatchSizer {
+    /// Create a batch sizer, validating its configuration
+    pub fn new(args: BatchSizingArgs) -> Result<Self, FeeSweeperError> {
+        let weight = args.redemption_latency_weight;
+        if !(weight > 0.0 && weight <= 1.0) {
+            return Err(FeeSweeperError::Config(format!(
+                "invalid redemption latency weight: {weight}"
+            )));
+        }
+        if args.min_redemption_batch > MAX_FEES_REDEEMED {
+            return Err(FeeSweeperError::Config(format!(
+                "minimum redemption batch exceeds the maximum of {MAX_FEES_REDEEMED}"
+            )));
+        }
+
+        Ok(Self {
+            args,
+            latency: None,
+        })
+    }
+
+    /// Record the latency of a redemption, from its submission to the end of
+    /// its relayer task
+    pub fn record(&mut self, latency: Duration) {
+        let weight = self.args.redemption_latency_weight;
+        let average = match self.latency {
+            Some(average) => average.mul_f64(1.0 - weight) + latency.mul_f64(weight),
+            None => latency,
+        };
+
+        gauge!(REDEMPTION_LATENCY_METRIC).set(average.as_secs_f64());
+        self.latency = Some(average);
+    }
+
+    /// The number of fees to redeem in the next pass
+    ///
+    /// Until a redemption's latency is observed, the full batch is redeemed
+    pub fn batch_size(&self) -> usize {
+        let size = match self.latency {
+            Some(latency) if !latency.is_zero() => {
+                let budget = Duration::from_secs(self.args.redemption_time_budget_secs);
+                let size = budget.as_secs_f64() / latency.as_secs_f64();
+                (size as usize).clamp(self.args.min_redemption_batch, MAX_FEES_REDEEMED)
+            }
+            _ => MAX_FEES_REDEEMED,
+        };
+
+        gauge!(REDEMPTION_BATCH_SIZE_METRIC).set(size as f64);
+        size
+    }
+}
@@ -0,0 +1,166 @@
+//! A blocklist of settlement transactions whose fees are not indexed
+//!
+//! Operators may list transactions, or ranges of blocks, whose fee events the
+//! sweeper should ignore, e.g. known test transactions or the blocks of an
+//! exploit under remediation. The blocklist is applied at index time, to both
+//! fee notes and external match fees, and each transaction skipped is recorded
+//! in the audit log with the reason it was listed
+
+use std::str::FromStr;
+
+use clap::Args;
+use ethers::types::TxHash;
+use tracing::info;
+
+use crate::db::audit::SETTLEMENT_SKIPPED_EVENT;
+use crate::db::models::NewAuditEvent;
+use crate::error::FeeSweeperError;
+use crate::Indexer;
+
+/// A settlement transaction whose fees are not indexed
+///
+/// Parsed from a string of the form `<tx_hash>:<reason>`
+#[derive(Clone, Debug)]
+pub struct SkippedTx {
+    /// The hash of the transaction, as stored in the database
+    pub tx_hash: String,
+    /// Why the transaction is skipped
+    pub reason: String,
+}
+
+impl FromStr for SkippedTx {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (tx_hash, reason) = s
+            .split_once(':')
+            .ok_or_else(|| format!("expected <tx_hash>:<reason>: {s}"))?;
+        let tx_hash =
+            TxHash::from_str(tx_hash.trim()).map_err(|e| format!("invalid tx hash: {e}"))?;
+
+        Ok(SkippedTx {
+            tx_hash: format!("{tx_hash:#x}"),
+            reason: parse_reason(reason)?,
+        })
+    }
+}
+
+/// A range of blocks whose fees are not indexed
+///
+/// Parsed from a string of the form `<from_block>-<to_block>:<reason>`, the
+/// range including both ends
+#[derive(Clone, Debug)]
+pub struct SkippedBlocks {
+    /// The first block of the range
+    pub from_block: u64,
+    /// The last block of the range
+    pub to_block: u64,
+    /// Why the range is skipped
+    pub reason: String,
+}
+
+impl FromStr for SkippedBlocks {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (range, reason) = s
+            .split_once(':')
+            .ok_or_else(|| format!("expected <from_block>-<to_block>:<reason>: {s}"))?;
+        let (from_block, to_block) = range
+            .split_once('-')
+            .ok_or_else(|| format!("expected <from_block>-<to_block>: {range}"))?;
+        let parse_block = |block: &str| {
+            block
+                .trim()
+                .parse::<u64>()
+                .map_err(|e| format!("invalid block number: {e}"))
+        };
+        let (from_block, to_block) = (parse_block(from_block)?, parse_block(to_block)?);
+        if to_block < from_block {
+            return Err(format!(
+                "block range ends at {to_block} before it starts at {from_block}"
+            ));
+        }
+
+        Ok(SkippedBlocks {
+            from_block,
+            to_block,
+            reason: parse_reason(reason)?,
+        })
+    }
+}
+
+/// Parse the reason of a blocklist entry, which may not be empty
+fn parse_reason(reason: &str) -> Result<String, String> {
+    let reason = reason.trim();
+    if reason.is_empty() {
+        return Err("a blocklist entry requires a reason".to_string());
+    }
+
+    Ok(reason.to_string())
+}
+
+/// The arguments configuring the blocklist of settlement transactions
+#[derive(Clone, Debug, Args)]
+pub struct BlocklistArgs {
+    /// A settlement transaction whose fees are not indexed, given as
+    /// `<tx_hash>:<reason>`; may be given several times
+    #[clap(long = "skip-tx")]
+    pub skipped_txs: Vec<SkippedTx>,
+    /// A range of blocks whose fees are not indexed, given as
+    /// `<from_block>-<to_block>:<reason>` including both ends; may be given
+    /// several times
+    #[clap(long = "skip-blocks")]
+    pub skipped_blocks: Vec<SkippedBlocks>,
+}
+
+impl BlocklistArgs {
+    /// The reason the fees of a transaction are not indexed, if they are
+    /// blocklisted
+    pub fn skip_reason(&self, tx_hash: &str, block_number: u64) -> Option<String> {
+        if let Some(skipped) = self.skipped_txs.iter().find(|s| s.tx_hash == tx_hash) {
+            return Some(skipped.reason.clone());
+        }
+
+        self.skipped_blocks
+            .iter()
+            .find(|s| (s.from_block..=s.to_block).contains(&block_number))
+            .map(|s| {
+                format!(
+                    "block in skipped range {}-{}: {}",
+                    s.from_block, s.to_block, s.reason
+                )
+            })
+    }
+}
+
+impl Indexer {
+    /// Check a settlement transaction against the blocklist, recording it in
+    /// the audit log the first time it is skipped
+    ///
+    /// Returns whether the transaction's fees are to be skipped
+    pub(crate) async fn check_blocklist(
+        &mut self,
+        tx_hash: &str,
+        block_number: u64,
+    ) -> Result<bool, FeeSweeperError> {
+        let reason = match self.blocklist.skip_reason(tx_hash, block_number) {
+            Some(reason) => reason,
+            None => return Ok(false),
+        };
+        info!("tx {tx_hash} is blocklisted, skipping its fees: {reason}");
+
+        // A backfill over the same blocks skips the transaction again, which
+        // is recorded once
+        let events = self
+            .get_audit_events(tx_hash, SETTLEMENT_SKIPPED_EVENT)
+            .await?;
+        if events.is_empty() {
+            let details = format!("skipped fees of tx in block {block_number}: {reason}");
+            let event =
+                NewAuditEvent::new(SETTLEMENT_SKIPPED_EVENT, Some(tx_hash.to_string()), details);
+            self.record_audit_event(event).await?;
+        }
+        Ok(true)
+    }
+}
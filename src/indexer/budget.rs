@@ -0,0 +1,166 @@
+//! Per-phase budgets on the time the indexer spends in the DB
+//!
+//! Each phase of a run is given a total DB time budget. Each query runs under
+//! a statement timeout of the phase's remaining budget, so that no single
+//! query overruns it. Once a phase exhausts its budget, further queries fail
+//! with `DbError::BudgetExceeded` and the phase is abandoned. Phases persist
+//! their progress as they go, so an aborted phase resumes from its last
+//! checkpoint in the next run
+
+use std::fmt::{self, Display};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use tracing::warn;
+
+use crate::error::FeeSweeperError;
+use crate::Indexer;
+
+/// The DB time budget of a single phase
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct DbBudget {
+    /// The name of the phase
+    phase: &'static str,
+    /// The total time the phase may spend in the DB
+    limit: Duration,
+    /// The time the phase has spent in the DB so far
+    spent: Duration,
+    /// Whether a query of the phase has been refused or cut off for
+    /// exhausting the budget
+    tripped: bool,
+}
+
+impl DbBudget {
+    /// Whether the budget has been spent
+    fn exhausted(&self) -> bool {
+        self.spent >= self.limit
+    }
+
+    /// The time left in the budget
+    pub(crate) fn remaining(&self) -> Duration {
+        self.limit.saturating_sub(self.spent)
+    }
+}
+
+/// An error from a query made by the indexer
+#[derive(Debug)]
+pub(crate) enum DbError {
+    /// The query failed
+    Query(diesel::result::Error),
+    /// No connection could be taken from the pool
+    Pool(String),
+    /// The current phase has exhausted its DB time budget
+    BudgetExceeded {
+        /// The name of the phase
+        phase: &'static str,
+        /// The phase's budget
+        limit: Duration,
+    },
+}
+
+impl Display for DbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DbError::Query(e) => write!(f, "{e}"),
+            DbError::Pool(e) => write!(f, "failed to get a DB connection: {e}"),
+            DbError::BudgetExceeded { phase, limit } => {
+                write!(f, "{phase} phase exceeded its DB budget of {limit:?}")
+            }
+        }
+    }
+}
+
+impl From<diesel::result::Error> for DbError {
+    fn from(e: diesel::result::Error) -> Self {
+        DbError::Query(e)
+    }
+}
+
+impl From<DbError> for FeeSweeperError {
+    fn from(e: DbError) -> Self {
+        match e {
+            DbError::BudgetExceeded { phase, limit } => {
+                FeeSweeperError::BudgetExceeded { phase, limit }
+            }
+            e => FeeSweeperError::Db(e.to_string()),
+        }
+    }
+}
+
+impl FeeSweeperError {
+    /// Wrap an error from an indexer query with context, for use with
+    /// `map_err`
+    ///
+    /// An exceeded budget is not wrapped, so that it stays typed as such
+    pub(crate) fn query(context: &'static str) -> impl FnOnce(DbError) -> Self {
+        move |e| match e {
+            DbError::BudgetExceeded { .. } => e.into(),
+            e => FeeSweeperError::Db(format!("{context}: {e}")),
+        }
+    }
+}
+
+impl Indexer {
+    /// Begin a phase of the run, with the configured DB time budget if any
+    pub fn begin_phase(&mut self, phase: &'static str) {
+        self.db_budget = self.phase_db_budget.map(|limit| DbBudget {
+            phase,
+            limit,
+            spent: Duration::ZERO,
+            tripped: false,
+        });
+        self.rpc_budget_tripped.store(false, Ordering::Relaxed);
+    }
+
+    /// End the current phase, given its result
+    ///
+    /// A phase that was aborted for exceeding its budget is not an error for
+    /// the run; the remaining phases still run with budgets of their own. A
+    /// phase aborted for exhausting the run's RPC budget is not an error
+    /// either, though the remaining phases are abandoned in turn at their
+    /// first check of the budget. A phase failing for any other reason
+    /// returns its error, even if a budget ran out along the way
+    pub fn end_phase(&mut self, res: Result<(), FeeSweeperError>) -> Result<(), FeeSweeperError> {
+        let budget = self.db_budget.take();
+        match (res, budget) {
+            (Err(e), Some(budget)) if budget.tripped => {
+                warn!(
+                    "aborted {} phase after {:?} in the DB: {e}",
+                    budget.phase, budget.spent
+                );
+                Ok(())
+            }
+            (Err(e), _) if self.rpc_budget_tripped.load(Ordering::Relaxed) => {
+                warn!("aborted phase after exhausting the run's RPC budget: {e}");
+                Ok(())
+            }
+            (res, _) => res,
+        }
+    }
+
+    /// Check that the current phase may make another query
+    pub(crate) fn check_db_budget(&mut self) -> Result<(), DbError> {
+        match self.db_budget.as_mut() {
+            Some(budget) if budget.exhausted() => {
+                budget.tripped = true;
+                Err(DbError::BudgetExceeded {
+                    phase: budget.phase,
+                    limit: budget.limit,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The time left in the current phase's budget, if it has one
+    pub(crate) fn remaining_db_budget(&self) -> Option<Duration> {
+        self.db_budget.map(|budget| budget.remaining())
+    }
+
+    /// Charge the time spent in a query to the current phase's budget
+    pub(crate) fn charge_db_budget(&mut self, elapsed: Duration) {
+        if let Some(budget) = self.db_budget.as_mut() {
+            budget.spent += elapsed;
+        }
+    }
+}
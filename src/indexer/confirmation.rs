@@ -0,0 +1,139 @@
+//! Confirmation on-chain of redemptions the relayer reports as successful
+//!
+//! A fee is only marked redeemed once its note's nullifier is found spent
+//! on-chain, whatever the relayer reports, so that a relayer bug reporting
+//! success for a transaction that never landed cannot lose track of a fee.
+//! The transaction may land shortly after the relayer reports it, or the RPC
+//! endpoint may trail the relayer's, so the nullifier is polled for a short
+//! window before the redemption is deemed unconfirmed. An unconfirmed
+//! redemption is alerted on, as it points at a fault in the relayer.
+//!
+//! The gas of a confirmed redemption's settlement is recorded in the gas
+//! ledger, found through the log of its nullifier being spent
+
+use std::time::Duration;
+
+use arbitrum_client::abi::NullifierSpentFilter;
+use clap::Args;
+use ethers::middleware::Middleware;
+use metrics::counter;
+use renegade_circuit_types::note::Note;
+use renegade_crypto::fields::scalar_to_u256;
+use tracing::{info, warn};
+
+use crate::db::gas_ledger::GasPurpose;
+use crate::error::FeeSweeperError;
+use crate::notifications::ALERTS_CHANNEL;
+use crate::telemetry::{
+    ETH_BLOCK_NUMBER, ETH_CALL, ETH_GET_LOGS, ETH_GET_RECEIPT, UNCONFIRMED_REDEMPTIONS_METRIC,
+};
+use crate::Indexer;
+
+/// The number of blocks searched back from the latest for the settlement of a
+/// confirmed redemption
+const SETTLEMENT_LOOKBACK_BLOCKS: u64 = 10_000;
+
+/// The arguments configuring the confirmation of redemptions on-chain
+#[derive(Clone, Debug, Args)]
+pub struct ConfirmationArgs {
+    /// The time, in seconds, to wait for the nullifier of a note the relayer
+    /// reports redeemed to be spent on-chain
+    #[clap(long, default_value_t = 60)]
+    pub redemption_confirmation_timeout_secs: u64,
+    /// The interval, in seconds, at which the nullifier of a note the relayer
+    /// reports redeemed is checked on-chain
+    #[clap(long, default_value_t = 5)]
+    pub redemption_confirmation_interval_secs: u64,
+}
+
+impl Indexer {
+    /// Confirm on-chain that a redemption the relayer reports as successful
+    /// spent the note's nullifier, alerting if it did not within the window
+    ///
+    /// Returns whether the redemption was confirmed
+    pub(crate) async fn confirm_redemption(
+        &mut self,
+        tx: &str,
+        note: &Note,
+    ) -> Result<bool, FeeSweeperError> {
+        let timeout = Duration::from_secs(self.confirmation.redemption_confirmation_timeout_secs);
+        let interval = Duration::from_secs(self.confirmation.redemption_confirmation_interval_secs);
+        let deadline = self.clock.instant() + timeout;
+        let nullifier = note.nullifier();
+        loop {
+            self.record_rpc(ETH_CALL);
+            let spent = self
+                .darkpool_client
+                .darkpool()
+                .check_nullifier_used(nullifier)
+                .await
+                .map_err(FeeSweeperError::rpc("failed to check nullifier"))?;
+            if spent {
+                return Ok(true);
+            }
+            if self.clock.instant() >= deadline {
+                break;
+            }
+
+            info!("redemption of fee from tx {tx} not yet confirmed on-chain, waiting");
+            self.clock.sleep(interval).await;
+        }
+
+        counter!(UNCONFIRMED_REDEMPTIONS_METRIC).increment(1);
+        let notice = format!(
+            "relayer reported the redemption of fee from tx {tx} as successful, but its \
+             nullifier is unspent on-chain after {}s",
+            timeout.as_secs()
+        );
+        warn!("{notice}");
+        self.notify(ALERTS_CHANNEL, notice).await;
+        Ok(false)
+    }
+
+    /// Record the gas spent settling a confirmed redemption
+    ///
+    /// A settlement that cannot be found is logged rather than failing the
+    /// redemption, which has already landed
+    pub(crate) async fn record_redemption_gas(&mut self, tx: &str, note: &Note) {
+        if let Err(e) = self.try_record_redemption_gas(note).await {
+            warn!("failed to record gas of redemption of fee from tx {tx}: {e}");
+        }
+    }
+
+    /// Find the settlement spending a note's nullifier and record its gas
+    async fn try_record_redemption_gas(&mut self, note: &Note) -> Result<(), FeeSweeperError> {
+        let darkpool = self.darkpool_client.darkpool().get_darkpool_client();
+        self.record_rpc(ETH_BLOCK_NUMBER);
+        let latest = darkpool
+            .client()
+            .get_block_number()
+            .await
+            .map_err(FeeSweeperError::rpc("failed to query block number"))?
+            .as_u64();
+
+        self.record_rpc(ETH_GET_LOGS);
+        let spends = darkpool
+            .event::<NullifierSpentFilter>()
+            .topic1(scalar_to_u256(&note.nullifier()))
+            .from_block(latest.saturating_sub(SETTLEMENT_LOOKBACK_BLOCKS))
+            .to_block(latest)
+            .query_with_meta()
+            .await
+            .map_err(FeeSweeperError::rpc("failed to query nullifier spends"))?;
+        let Some((_, meta)) = spends.into_iter().next() else {
+            return Err(FeeSweeperError::Rpc(
+                "no settlement spending the nullifier found".to_string(),
+            ));
+        };
+
+        self.record_rpc(ETH_GET_RECEIPT);
+        let receipt = darkpool
+            .client()
+            .get_transaction_receipt(meta.transaction_hash)
+            .await
+            .map_err(FeeSweeperError::rpc("failed to query settlement receipt"))?
+            .ok_or_else(|| FeeSweeperError::Rpc("settlement receipt not found".to_string()))?;
+        self.record_gas_spend(GasPurpose::Redemption, &receipt)
+            .await
+    }
+}
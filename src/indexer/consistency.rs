@@ -0,0 +1,177 @@
+//! The guard on the consistency of the relayer's sweep wallets with the chain
+//!
+//! The relayer reports a sweep wallet's balances from its own copy of the
+//! wallet, which should be the wallet last committed on-chain. The guard
+//! commits to the shares of the relayer's copy and looks the commitment up in
+//! the darkpool's Merkle tree: the copy has diverged from the chain if it was
+//! never committed, or if it was since spent by a later update. A divergence is
+//! expected while an update of the wallet settles, but one lasting longer
+//! suggests corruption of the relayer's state, putting the custody of redeemed
+//! fees at risk, and is alerted on
+
+use chrono::NaiveDateTime;
+use clap::Args;
+use metrics::gauge;
+use renegade_common::types::wallet::Wallet;
+use renegade_util::hex::biguint_to_hex_addr;
+use tracing::{info, warn};
+
+use crate::db::models::WalletMetadata;
+use crate::error::FeeSweeperError;
+use crate::notifications::ALERTS_CHANNEL;
+use crate::telemetry::{DIVERGED_WALLETS_METRIC, ETH_CALL, ETH_GET_LOGS};
+use crate::Indexer;
+
+/// The arguments configuring the guard on the relayer's sweep wallets
+#[derive(Clone, Debug, Args)]
+pub struct ConsistencyArgs {
+    /// Verify in each reconcile job that the relayer's view of each sweep
+    /// wallet is the wallet committed on-chain
+    #[clap(long)]
+    pub check_wallet_consistency: bool,
+    /// The time, in seconds, the relayer's view of a wallet may diverge from
+    /// the chain, as an update settles, before the divergence is alerted on
+    #[clap(long, default_value_t = 900)]
+    pub max_wallet_divergence_secs: u64,
+}
+
+/// How the relayer's view of a wallet diverges from the chain
+#[derive(Clone, Copy, Debug)]
+enum Divergence {
+    /// The relayer's view of the wallet is not committed on-chain
+    Uncommitted,
+    /// The relayer's view of the wallet was spent by a later update on-chain
+    Superseded,
+}
+
+impl Divergence {
+    /// Describe the divergence
+    fn describe(&self) -> &'static str {
+        match self {
+            Divergence::Uncommitted => "is not committed on-chain",
+            Divergence::Superseded => "was superseded on-chain",
+        }
+    }
+}
+
+impl Indexer {
+    /// Verify that the relayer's view of each sweep wallet is reflected
+    /// on-chain, alerting on wallets diverged for longer than expected
+    pub async fn verify_wallet_consistency(&mut self) -> Result<(), FeeSweeperError> {
+        if !self.consistency.check_wallet_consistency {
+            return Ok(());
+        }
+
+        info!("verifying relayer wallets against the chain...");
+        let max_divergence =
+            chrono::Duration::seconds(self.consistency.max_wallet_divergence_secs as i64);
+        let now = self.clock.naive_now();
+        let mut diverged = 0;
+        for wallet in self.get_all_wallets().await? {
+            if self.shutdown.requested() {
+                break;
+            }
+            self.check_rpc_budget()?;
+
+            // A failure to check one wallet should not block checking the rest
+            let (relayer_wallet, divergence) = match self.check_wallet_on_chain(&wallet).await {
+                Ok(res) => res,
+                Err(e) => {
+                    warn!(
+                        "failed to check wallet {} against the chain: {e}",
+                        wallet.id
+                    );
+                    continue;
+                }
+            };
+            let Some(divergence) = divergence else {
+                if wallet.diverged_since.is_some() {
+                    info!("wallet {} is consistent with the chain again", wallet.id);
+                    self.set_wallet_diverged_since(wallet.id, None).await?;
+                }
+                continue;
+            };
+
+            let since = match wallet.diverged_since {
+                Some(since) => since,
+                None => {
+                    self.set_wallet_diverged_since(wallet.id, Some(now)).await?;
+                    now
+                }
+            };
+            if now - since < max_divergence {
+                info!(
+                    "relayer's view of wallet {} {}, within the expected delay",
+                    wallet.id,
+                    divergence.describe()
+                );
+                continue;
+            }
+
+            diverged += 1;
+            self.alert_divergence(&wallet, &relayer_wallet, divergence, since)
+                .await;
+        }
+
+        gauge!(DIVERGED_WALLETS_METRIC).set(diverged as f64);
+        Ok(())
+    }
+
+    /// Get the relayer's view of a wallet, and how it diverges from the
+    /// chain, if it does
+    async fn check_wallet_on_chain(
+        &mut self,
+        wallet: &WalletMetadata,
+    ) -> Result<(Wallet, Option<Divergence>), FeeSweeperError> {
+        let eth_key = self.get_wallet_private_key(wallet).await?;
+        let derived = self.relayer_client.derive_wallet(&eth_key, self.chain_id)?;
+        let root_key = derived.keychain.secret_keys.sk_root.clone().unwrap();
+        let relayer_wallet = self.relayer_client.get_wallet(wallet.id, &root_key).await?;
+
+        self.record_rpc(ETH_GET_LOGS);
+        let commitment = relayer_wallet.get_wallet_share_commitment();
+        if let Err(e) = self
+            .darkpool_client
+            .darkpool()
+            .find_merkle_authentication_path(commitment)
+            .await
+        {
+            warn!("failed to find commitment of wallet {}: {e}", wallet.id);
+            return Ok((relayer_wallet, Some(Divergence::Uncommitted)));
+        }
+
+        self.record_rpc(ETH_CALL);
+        let spent = self
+            .darkpool_client
+            .darkpool()
+            .check_nullifier_used(relayer_wallet.get_wallet_nullifier())
+            .await
+            .map_err(FeeSweeperError::rpc("failed to check wallet nullifier"))?;
+        let divergence = spent.then_some(Divergence::Superseded);
+        Ok((relayer_wallet, divergence))
+    }
+
+    /// Alert on a wallet whose relayer view has diverged from the chain for
+    /// longer than expected, listing the balances the relayer reports
+    async fn alert_divergence(
+        &mut self,
+        wallet: &WalletMetadata,
+        relayer_wallet: &Wallet,
+        divergence: Divergence,
+        since: NaiveDateTime,
+    ) {
+        let balances: Vec<String> = relayer_wallet
+            .balances
+            .values()
+            .map(|balance| format!("{} {}", balance.amount, biguint_to_hex_addr(&balance.mint)))
+            .collect();
+        let notice = format!(
+            "relayer's view of sweep wallet {} {} since {since}; it reports balances [{}]",
+            wallet.id,
+            divergence.describe(),
+            balances.join(", ")
+        );
+        warn!("{notice}");
+        self.notify(ALERTS_CHANNEL, notice).await;
+    }
+}
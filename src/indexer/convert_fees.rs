@@ -0,0 +1,297 @@
+//! Conversion of redeemed fee balances to USDC
+//!
+//! Balances are sold through orders in the redemption wallets, in tranches no
+//! larger than a configured share of the mint's recently traded volume, so
+//! that liquidating a large position does not move the market. Each mint has
+//! at most one open tranche; a tranche left unfilled past the tranche interval
+//! is cancelled and replaced at the current price. Each tranche is checked
+//! against the fund movement invariants before it is placed, and recorded in
+//! the fund movement ledger so that later movements account for it
+
+use std::str::FromStr;
+use std::time::Duration;
+
+use bigdecimal::{BigDecimal, FromPrimitive};
+use clap::Args;
+use ethers::types::{Address, U256};
+use num_bigint::BigUint;
+use renegade_circuit_types::fixed_point::FixedPoint;
+use renegade_circuit_types::order::{Order, OrderSide};
+use renegade_common::types::token::Token;
+use renegade_common::types::wallet::OrderIdentifier;
+use renegade_util::get_current_time_millis;
+use renegade_util::hex::{biguint_from_hex_string, biguint_to_hex_addr};
+use tracing::{info, warn};
+
+use crate::db::models::WalletMetadata;
+use crate::error::FeeSweeperError;
+use crate::invariants::{FundMovement, InvariantArgs, InvariantSet};
+use crate::Indexer;
+
+/// The metadata key prefix of the open tranche of each mint
+const CONVERSION_TRANCHE_KEY_PREFIX: &str = "conversion_tranche_";
+
+/// The arguments configuring the conversion of fee balances to USDC
+#[derive(Clone, Debug, Args)]
+pub struct ConversionArgs {
+    /// Convert redeemed fee balances to USDC after redeeming, while the
+    /// `swaps` feature flag is on
+    #[clap(long)]
+    pub convert_fees: bool,
+    /// The largest tranche sold at once, as a fraction of the mint's volume
+    /// traded over the volume window
+    #[clap(long, default_value_t = 0.05)]
+    pub max_participation: f64,
+    /// The trailing window, in seconds, over which traded volume is measured
+    #[clap(long, default_value_t = 3600)]
+    pub volume_window_secs: u64,
+    /// The time, in seconds, a tranche is left to fill before it is cancelled
+    /// and replaced
+    #[clap(long, default_value_t = 3600)]
+    pub tranche_interval_secs: u64,
+    /// The largest discount to the current price a tranche accepts, as a
+    /// fraction
+    #[clap(long, default_value_t = 0.01)]
+    pub max_slippage: f64,
+    /// The invariants checked before each tranche is placed
+    #[clap(flatten)]
+    pub invariants: InvariantArgs,
+}
+
+/// An open tranche of a mint, stored in the indexing metadata as
+/// `<order_id>:<placed_at_ms>`
+struct Tranche {
+    /// The id of the tranche's order
+    order_id: OrderIdentifier,
+    /// The time the tranche was placed, in milliseconds since the epoch
+    placed_at_ms: u64,
+}
+
+impl Indexer {
+    /// Sell the next tranche of each fee balance held in the redemption
+    /// wallets for USDC
+    pub async fn convert_fees(&mut self) -> Result<(), FeeSweeperError> {
+        info!("converting fees...");
+        if self.is_darkpool_paused().await? {
+            warn!("darkpool is paused, skipping conversion");
+            return Ok(());
+        }
+
+        let invariants = InvariantSet::for_conversion(&self.conversion.invariants)?;
+        for wallet in self.get_all_wallets().await? {
+            if self.shutdown.requested() {
+                info!("shutdown requested, deferring remaining conversions");
+                break;
+            }
+
+            // Hold the wallet for its orders, so that no other operation's
+            // relayer tasks interleave with them
+            let Some(lock) = self.lock_wallet(wallet.id).await? else {
+                info!("wallet {} is busy, deferring its conversion", wallet.id);
+                continue;
+            };
+            let res = self.convert_wallet_balances(&wallet, &invariants).await;
+            self.unlock_wallet(lock).await;
+            res?;
+        }
+
+        Ok(())
+    }
+
+    /// Sell the next tranche of each fee balance held in a wallet
+    async fn convert_wallet_balances(
+        &mut self,
+        metadata: &WalletMetadata,
+        invariants: &InvariantSet,
+    ) -> Result<(), FeeSweeperError> {
+        let eth_key = self.get_wallet_private_key(metadata).await?;
+        let derived = self.relayer_client.derive_wallet(&eth_key, self.chain_id)?;
+        let root_key = derived.keychain.secret_keys.sk_root.clone().unwrap();
+
+        let wallet = self
+            .relayer_client
+            .get_wallet(metadata.id, &root_key)
+            .await?;
+        let usdc = biguint_from_hex_string(self.relayer_client.usdc_mint())
+            .map_err(FeeSweeperError::Config)?;
+        let balances: Vec<(BigUint, u128)> = wallet
+            .balances
+            .values()
+            .filter(|balance| balance.mint != usdc && balance.amount > 0)
+            .map(|balance| (balance.mint.clone(), balance.amount))
+            .collect();
+
+        let usdc_address = Address::from_str(self.relayer_client.usdc_mint())
+            .map_err(FeeSweeperError::config("invalid usdc mint"))?;
+        let interval = Duration::from_secs(self.conversion.tranche_interval_secs);
+        for (mint, balance) in balances {
+            let mint_addr = biguint_to_hex_addr(&mint);
+
+            // Leave an open tranche to fill until the interval elapses
+            if let Some(tranche) = self.get_tranche(&mint_addr).await? {
+                let age_ms = get_current_time_millis().saturating_sub(tranche.placed_at_ms);
+                if Duration::from_millis(age_ms) < interval {
+                    continue;
+                }
+
+                if let Some(open) = wallet.orders.get(&tranche.order_id) {
+                    info!("cancelling unfilled tranche of {mint_addr}");
+                    self.relayer_client
+                        .cancel_order(metadata.id, tranche.order_id, &root_key)
+                        .await?;
+                    let remaining = BigDecimal::from(open.amount);
+                    let order_id = tranche.order_id.to_string();
+                    self.reverse_conversion(&order_id, remaining).await?;
+                }
+            }
+
+            let order = match self
+                .build_tranche(&mint_addr, mint, usdc.clone(), balance)
+                .await?
+            {
+                Some(order) => order,
+                None => continue,
+            };
+
+            let mint_address =
+                Address::from_str(&mint_addr).map_err(FeeSweeperError::db("invalid mint"))?;
+            let movement = FundMovement {
+                kind: "fee conversion",
+                mint: mint_address,
+                amount: U256::from(order.amount),
+                destination: self
+                    .darkpool_client
+                    .darkpool()
+                    .get_darkpool_client()
+                    .address(),
+            };
+            self.check_invariants(invariants, &movement).await?;
+
+            info!(
+                "selling tranche of {} of {balance} {mint_addr}",
+                order.amount
+            );
+            let amount = BigDecimal::from(order.amount);
+            let proceeds = BigDecimal::from_f64(order.worst_case_price.to_f64())
+                .map(|price| (&amount * price).with_scale(0))
+                .unwrap_or_default();
+            let order_id = self
+                .relayer_client
+                .place_order(metadata.id, order, &root_key)
+                .await?;
+            self.set_tranche(&mint_addr, order_id).await?;
+            self.record_conversion(
+                &order_id.to_string(),
+                &format!("{mint_address:#x}"),
+                amount,
+                &format!("{usdc_address:#x}"),
+                proceeds,
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Build the sell order of the next tranche of a balance
+    ///
+    /// Returns `None` if the mint's price or recent volume is unavailable, or
+    /// its volume is too thin for a non-empty tranche
+    async fn build_tranche(
+        &mut self,
+        mint_addr: &str,
+        base_mint: BigUint,
+        quote_mint: BigUint,
+        balance: u128,
+    ) -> Result<Option<Order>, FeeSweeperError> {
+        let token = Token::from_addr(mint_addr);
+        let usdc = Token::from_addr(self.relayer_client.usdc_mint());
+        let (decimals, usdc_decimals) = match (token.get_decimals(), usdc.get_decimals()) {
+            (Some(decimals), Some(usdc_decimals)) => (decimals, usdc_decimals),
+            _ => {
+                warn!("{mint_addr}: unknown decimals, skipping conversion");
+                return Ok(None);
+            }
+        };
+
+        let volume = match self
+            .historical_price_client
+            .get_recent_volume(mint_addr, self.conversion.volume_window_secs)
+            .await
+        {
+            Ok(volume) => volume,
+            Err(e) => {
+                warn!("{mint_addr}: failed to query recent volume, skipping conversion: {e}");
+                return Ok(None);
+            }
+        };
+        let routes = self.get_price_route_map().await?;
+        let price = self
+            .relayer_client
+            .get_routed_price(mint_addr, &routes)
+            .await?;
+        let (volume, price) = match (volume, price) {
+            (Some(volume), Some(price)) => (volume, price),
+            _ => {
+                warn!("{mint_addr}: no recent volume or price, skipping conversion");
+                return Ok(None);
+            }
+        };
+
+        // Size the tranche in raw units of the mint
+        let raw_volume = volume * 10f64.powi(decimals as i32);
+        let amount = balance.min((raw_volume * self.conversion.max_participation) as u128);
+        if amount == 0 {
+            warn!("{mint_addr}: recent volume too thin to convert");
+            return Ok(None);
+        }
+
+        // Prices are quoted in raw units of USDC per raw unit of the mint
+        let decimal_adjustment = 10f64.powi(usdc_decimals as i32 - decimals as i32);
+        let worst_case_price = price * (1. - self.conversion.max_slippage) * decimal_adjustment;
+
+        Ok(Some(Order {
+            base_mint,
+            quote_mint,
+            side: OrderSide::Sell,
+            amount,
+            worst_case_price: FixedPoint::from_f64_round_down(worst_case_price),
+            min_fill_size: 0,
+        }))
+    }
+
+    /// Get the open tranche of a mint, if any
+    async fn get_tranche(&mut self, mint: &str) -> Result<Option<Tranche>, FeeSweeperError> {
+        let value = match self.get_metadata(&tranche_key(mint)).await? {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+
+        let (order_id, placed_at_ms) = value
+            .split_once(':')
+            .ok_or_else(|| FeeSweeperError::Db(format!("invalid tranche: {value}")))?;
+        Ok(Some(Tranche {
+            order_id: order_id
+                .parse()
+                .map_err(|e| FeeSweeperError::Db(format!("invalid tranche order id: {e}")))?,
+            placed_at_ms: placed_at_ms
+                .parse()
+                .map_err(|e| FeeSweeperError::Db(format!("invalid tranche time: {e}")))?,
+        }))
+    }
+
+    /// Record the open tranche of a mint
+    async fn set_tranche(
+        &mut self,
+        mint: &str,
+        order_id: OrderIdentifier,
+    ) -> Result<(), FeeSweeperError> {
+        let value = format!("{order_id}:{}", get_current_time_millis());
+        self.set_metadata(&tranche_key(mint), value).await
+    }
+}
+
+/// The metadata key of the open tranche of a mint
+fn tranche_key(mint: &str) -> String {
+    format!("{CONVERSION_TRANCHE_KEY_PREFIX}{mint}")
+}
@@ -0,0 +1,162 @@
+//! Tracks whether the darkpool is paused, so that redemption is suspended
+//! while the contract refuses settlement rather than queueing relayer tasks
+//! that are bound to fail
+//!
+//! The pause state is derived from the darkpool's `Paused` and `Unpaused`
+//! events and persisted in the indexing metadata. Upgrades of the darkpool's
+//! implementation are alerted on but do not suspend redemption
+
+use ethers::contract::LogMeta;
+use tracing::{error, info};
+
+use self::bindings::{PausedFilter, UnpausedFilter, UpgradedFilter};
+use crate::db::audit::{DARKPOOL_PAUSE_EVENT, DARKPOOL_UNPAUSE_EVENT, DARKPOOL_UPGRADE_EVENT};
+use crate::db::models::NewAuditEvent;
+use crate::error::FeeSweeperError;
+use crate::notifications::ALERTS_CHANNEL;
+use crate::telemetry::{record_darkpool_paused, ETH_GET_LOGS};
+use crate::Indexer;
+
+/// The metadata key of whether the darkpool is paused
+const DARKPOOL_PAUSED_KEY: &str = "darkpool_paused";
+/// The metadata key of the block of the most recent darkpool upgrade seen
+const LAST_DARKPOOL_UPGRADE_KEY: &str = "last_darkpool_upgrade_block";
+
+/// The generated event bindings
+#[allow(missing_docs, clippy::missing_docs_in_private_items)]
+mod bindings {
+    ethers::contract::abigen!(
+        DarkpoolAdminEvents,
+        r#"[
+            event Paused(address account)
+            event Unpaused(address account)
+            event Upgraded(address indexed implementation)
+        ]"#
+    );
+}
+
+impl Indexer {
+    /// Update the darkpool's pause state from the pause and upgrade events
+    /// emitted since the given block
+    ///
+    /// Events are read up to the chain head rather than the finalized block,
+    /// as a pause should suspend redemption as soon as it is seen
+    pub async fn index_darkpool_status(&mut self, from_block: u64) -> Result<(), FeeSweeperError> {
+        let darkpool = self.darkpool_client.darkpool().get_darkpool_client();
+        self.record_rpc(ETH_GET_LOGS);
+        let paused = darkpool
+            .event::<PausedFilter>()
+            .from_block(from_block)
+            .query_with_meta()
+            .await
+            .map_err(FeeSweeperError::rpc("failed to query darkpool pauses"))?;
+        self.record_rpc(ETH_GET_LOGS);
+        let unpaused = darkpool
+            .event::<UnpausedFilter>()
+            .from_block(from_block)
+            .query_with_meta()
+            .await
+            .map_err(FeeSweeperError::rpc("failed to query darkpool unpauses"))?;
+        self.record_rpc(ETH_GET_LOGS);
+        let upgraded = darkpool
+            .event::<UpgradedFilter>()
+            .from_block(from_block)
+            .query_with_meta()
+            .await
+            .map_err(FeeSweeperError::rpc("failed to query darkpool upgrades"))?;
+
+        // The most recent pause or unpause determines the pause state
+        let latest_change = paused
+            .iter()
+            .map(|(_, meta)| (true, meta))
+            .chain(unpaused.iter().map(|(_, meta)| (false, meta)))
+            .max_by_key(|(_, meta)| (meta.block_number, meta.log_index));
+        if let Some((now_paused, meta)) = latest_change {
+            self.set_darkpool_paused(now_paused, meta).await?;
+        }
+
+        if let Some((event, meta)) = upgraded.iter().max_by_key(|(_, meta)| meta.block_number) {
+            self.record_darkpool_upgrade(event, meta).await?;
+        }
+
+        record_darkpool_paused(self.is_darkpool_paused().await?);
+        Ok(())
+    }
+
+    /// Whether the darkpool was paused as of the last indexed event
+    pub(crate) async fn is_darkpool_paused(&mut self) -> Result<bool, FeeSweeperError> {
+        let paused = self.get_metadata(DARKPOOL_PAUSED_KEY).await?;
+        Ok(paused.as_deref() == Some("true"))
+    }
+
+    /// Persist a change in the darkpool's pause state, alerting on it
+    async fn set_darkpool_paused(
+        &mut self,
+        paused: bool,
+        meta: &LogMeta,
+    ) -> Result<(), FeeSweeperError> {
+        if self.is_darkpool_paused().await? == paused {
+            return Ok(());
+        }
+
+        let tx = format!("{:#x}", meta.transaction_hash);
+        let (event_type, details) = if paused {
+            error!("darkpool paused in tx {tx}, suspending redemption");
+            (
+                DARKPOOL_PAUSE_EVENT,
+                "darkpool paused, redemption suspended",
+            )
+        } else {
+            info!("darkpool unpaused in tx {tx}, resuming redemption");
+            (
+                DARKPOOL_UNPAUSE_EVENT,
+                "darkpool unpaused, redemption resumed",
+            )
+        };
+
+        self.set_metadata(DARKPOOL_PAUSED_KEY, paused.to_string())
+            .await?;
+        let notice = format!("{details} in tx {tx}");
+        self.notify(ALERTS_CHANNEL, notice).await;
+        self.record_audit_event(NewAuditEvent::new(
+            event_type,
+            Some(tx),
+            details.to_string(),
+        ))
+        .await
+    }
+
+    /// Alert on an upgrade of the darkpool not seen before
+    async fn record_darkpool_upgrade(
+        &mut self,
+        event: &UpgradedFilter,
+        meta: &LogMeta,
+    ) -> Result<(), FeeSweeperError> {
+        let block = meta.block_number.as_u64();
+        let last_seen = self
+            .get_metadata(LAST_DARKPOOL_UPGRADE_KEY)
+            .await?
+            .and_then(|b| b.parse::<u64>().ok());
+        if last_seen.is_some_and(|last| last >= block) {
+            return Ok(());
+        }
+
+        let tx = format!("{:#x}", meta.transaction_hash);
+        let details = format!(
+            "darkpool upgraded to implementation {:#x}",
+            event.implementation
+        );
+        error!("{details} in tx {tx}");
+
+        self.set_metadata(LAST_DARKPOOL_UPGRADE_KEY, block.to_string())
+            .await?;
+        let notice = format!("{details} in tx {tx}");
+        self.notify(ALERTS_CHANNEL, notice).await;
+        self.record_audit_event(NewAuditEvent::new(
+            DARKPOOL_UPGRADE_EVENT,
+            Some(tx),
+            details,
+        ))
+        .await
+    }
+}
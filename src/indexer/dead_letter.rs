@@ -0,0 +1,176 @@
+//! Dead-lettering of fees whose redemption repeatedly fails
+//!
+//! Each failed redemption is recorded in the audit log, along with the
+//! relayer's payload describing it, and counted against the fee. A failed fee
+//! is retried by later runs, each retry delayed twice as long as the last, and
+//! a fee that fails `max_redemption_attempts` times is dead-lettered: it is no
+//! longer selected for redemption, and a ticket is opened with its failure
+//! history if an issue tracker is configured
+
+use std::time::Duration;
+
+use chrono::NaiveDateTime;
+use clap::Args;
+use tracing::{error, warn};
+use uuid::Uuid;
+
+use crate::db::audit::{DEAD_LETTER_EVENT, REDEMPTION_FAILURE_EVENT};
+use crate::db::models::{NewAuditEvent, NewTaskFailure};
+use crate::error::FeeSweeperError;
+use crate::relayer_client::RelayerFailure;
+use crate::Indexer;
+
+/// The arguments configuring retries of failed redemptions
+#[derive(Clone, Debug, Args)]
+pub struct RedemptionRetryArgs {
+    /// The number of failed redemptions after which a fee is dead-lettered
+    #[clap(long, default_value_t = 5)]
+    pub max_redemption_attempts: u32,
+    /// The time, in seconds, before a fee whose redemption failed is retried;
+    /// doubles with each further failure
+    #[clap(long, default_value_t = 600)]
+    pub redemption_retry_base_delay_secs: u64,
+    /// The longest time, in seconds, before a failed redemption is retried
+    #[clap(long, default_value_t = 86_400)]
+    pub redemption_retry_max_delay_secs: u64,
+}
+
+impl RedemptionRetryArgs {
+    /// The delay before retrying a fee that has failed the given number of
+    /// redemptions
+    pub fn retry_delay(&self, attempts: u32) -> Duration {
+        let exponent = attempts.saturating_sub(1).min(31);
+        let delay = self
+            .redemption_retry_base_delay_secs
+            .saturating_mul(1 << exponent);
+        Duration::from_secs(delay.min(self.redemption_retry_max_delay_secs))
+    }
+
+    /// The time after which a fee that has failed the given number of
+    /// redemptions, the last at `now`, may be retried
+    pub fn retry_at(&self, attempts: u32, now: NaiveDateTime) -> NaiveDateTime {
+        chrono::Duration::from_std(self.retry_delay(attempts))
+            .ok()
+            .and_then(|delay| now.checked_add_signed(delay))
+            .unwrap_or(NaiveDateTime::MAX)
+    }
+}
+
+/// Describe the dead letter of a fee that failed the given number of
+/// redemptions
+pub(crate) fn dead_letter_details(attempts: i32) -> String {
+    format!("dead-lettered after {attempts} failed redemptions")
+}
+
+impl Indexer {
+    /// Record a failed redemption of a fee, dead-lettering the fee if it has
+    /// exhausted its attempts
+    pub(crate) async fn record_failed_redemption(
+        &mut self,
+        tx_hash: &str,
+        wallet_id: Uuid,
+        failure: RelayerFailure,
+    ) -> Result<(), FeeSweeperError> {
+        self.record_audit_event(NewAuditEvent::new(
+            REDEMPTION_FAILURE_EVENT,
+            Some(tx_hash.to_string()),
+            failure.reason.clone(),
+        ))
+        .await?;
+        self.record_task_failure(NewTaskFailure {
+            tx_hash: tx_hash.to_string(),
+            wallet_id,
+            task_id: failure.task_id,
+            reason: failure.reason.clone(),
+            payload: failure.payload,
+        })
+        .await?;
+
+        let retry = self.redemption_retry.clone();
+        let fee = self
+            .record_redemption_failure(tx_hash, &failure.reason, &retry)
+            .await?;
+        if fee.dead_lettered_at.is_none() {
+            if let Some(retry_at) = fee.next_redemption_at {
+                warn!("redemption of fee from tx {tx_hash} failed, retrying after {retry_at}");
+            }
+            return Ok(());
+        }
+
+        let details = dead_letter_details(fee.redemption_attempts);
+        self.record_audit_event(NewAuditEvent::new(
+            DEAD_LETTER_EVENT,
+            Some(fee.tx_hash.clone()),
+            details.clone(),
+        ))
+        .await?;
+
+        // The alert was enqueued with the dead letter
+        self.deliver_outbox().await;
+
+        if let Some(tracker) = self.issue_tracker.as_ref() {
+            let failures = self
+                .get_audit_events(tx_hash, REDEMPTION_FAILURE_EVENT)
+                .await?;
+            // A ticket that fails to open should not fail the run, the dead
+            // letter is already recorded in the audit log
+            if let Err(e) = tracker.open_dead_letter_issue(&fee, &failures).await {
+                error!("failed to open ticket for dead-lettered fee {tx_hash}: {e}");
+            }
+        } else {
+            warn!("fee from tx {tx_hash} dead-lettered");
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::DateTime;
+
+    use super::RedemptionRetryArgs;
+    use crate::clock::{Clock, ManualClock};
+
+    /// The retry configuration under test
+    fn retry_args() -> RedemptionRetryArgs {
+        RedemptionRetryArgs {
+            max_redemption_attempts: 5,
+            redemption_retry_base_delay_secs: 600,
+            redemption_retry_max_delay_secs: 3600,
+        }
+    }
+
+    /// A manual clock reading a fixed time
+    fn manual_clock() -> ManualClock {
+        ManualClock::new(DateTime::from_timestamp(1_700_000_000, 0 /* nsecs */).unwrap())
+    }
+
+    #[test]
+    fn retry_delay_doubles_up_to_the_max() {
+        let retry = retry_args();
+        let delays: Vec<u64> = (1..=5).map(|n| retry.retry_delay(n).as_secs()).collect();
+        assert_eq!(delays, vec![600, 1200, 2400, 3600, 3600]);
+    }
+
+    #[test]
+    fn retry_delay_saturates_on_many_attempts() {
+        let retry = retry_args();
+        assert_eq!(retry.retry_delay(0), Duration::from_secs(600));
+        assert_eq!(retry.retry_delay(u32::MAX), Duration::from_secs(3600));
+    }
+
+    #[test]
+    fn failed_fee_is_due_once_its_backoff_expires() {
+        let retry = retry_args();
+        let clock = manual_clock();
+        let retry_at = retry.retry_at(2 /* attempts */, clock.naive_now());
+
+        clock.advance(Duration::from_secs(1199));
+        assert!(clock.naive_now() < retry_at);
+        clock.advance(Duration::from_secs(1));
+        assert!(clock.naive_now() >= retry_at);
+    }
+}
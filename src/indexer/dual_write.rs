@@ -0,0 +1,63 @@
+//! Dual-writing of the fee status during its schema migration
+//!
+//! With dual-writing enabled, every status change the sweeper makes is
+//! written to both the legacy status columns and the new `status` column, and
+//! each sweep ends by verifying that the two agree. Disabling dual-writing
+//! leaves the legacy columns authoritative, so the migration may be rolled
+//! back at any point by turning the flag off and reverting the migration
+
+use clap::Args;
+use metrics::gauge;
+use tracing::{info, warn};
+
+use crate::db::dual_write::{compare_fee_status, MAX_REPORTED_MISMATCHES};
+use crate::error::FeeSweeperError;
+use crate::notifications::ALERTS_CHANNEL;
+use crate::telemetry::{DUAL_WRITE_MISMATCHES_METRIC, DUAL_WRITE_UNTRACKED_METRIC};
+use crate::Indexer;
+
+/// The arguments configuring dual-writes during schema migrations
+#[derive(Clone, Debug, Args)]
+pub struct DualWriteArgs {
+    /// Verify after each sweep that the fee status in the new `status` column
+    /// agrees with the legacy status columns, both derived from the fee's
+    /// redemption state
+    #[clap(long, env = "DUAL_WRITE_FEE_STATUS")]
+    pub dual_write_fee_status: bool,
+}
+
+impl Indexer {
+    /// Compare the dual-written fee status against the legacy columns,
+    /// alerting on any disagreement
+    pub async fn verify_dual_writes(&mut self) -> Result<(), FeeSweeperError> {
+        if !self.dual_write.dual_write_fee_status {
+            return Ok(());
+        }
+
+        info!("verifying dual-written fee status...");
+        let report = compare_fee_status(self.get_all_fees().await?);
+        gauge!(DUAL_WRITE_MISMATCHES_METRIC).set(report.mismatches.len() as f64);
+        gauge!(DUAL_WRITE_UNTRACKED_METRIC).set(report.untracked as f64);
+        if report.is_consistent() {
+            info!(
+                "fee status consistent across {} fees, {} untracked",
+                report.checked, report.untracked
+            );
+            return Ok(());
+        }
+
+        for mismatch in report.mismatches.iter().take(MAX_REPORTED_MISMATCHES) {
+            warn!(
+                "fee from tx {} is {} in the legacy columns but {} in the status column",
+                mismatch.tx_hash, mismatch.legacy, mismatch.dual
+            );
+        }
+        let notice = format!(
+            "dual-written fee status disagrees with the legacy columns for {} of {} fees",
+            report.mismatches.len(),
+            report.checked
+        );
+        self.notify(ALERTS_CHANNEL, notice).await;
+        Ok(())
+    }
+}
@@ -0,0 +1,88 @@
+//! Dust floors applied to notes at index time
+//!
+//! Spam tokens pay notes of worthless amounts that would otherwise dominate the
+//! fees table. A note below its mint's raw-amount floor is either skipped
+//! entirely or indexed with the `dust` flag, which excludes it from
+//! redemption. Mints without a floor are never dust
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use clap::ValueEnum;
+
+use crate::error::FeeSweeperError;
+
+/// The raw-amount floor below which a mint's notes are dust
+///
+/// Parsed from a string of the form `<mint>:<min_raw_amount>`
+#[derive(Clone, Debug)]
+pub struct DustFloor {
+    /// The mint the floor applies to
+    pub mint: String,
+    /// The smallest raw amount of a note that is not dust
+    pub min_amount: u128,
+}
+
+impl FromStr for DustFloor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (mint, min_amount) = s
+            .split_once(':')
+            .ok_or_else(|| format!("expected <mint>:<min_raw_amount>: {s}"))?;
+        let min_amount = min_amount
+            .trim()
+            .parse::<u128>()
+            .map_err(|e| format!("invalid minimum amount: {e}"))?;
+
+        Ok(DustFloor {
+            mint: mint.trim().to_lowercase(),
+            min_amount,
+        })
+    }
+}
+
+/// What to do with a note below its mint's dust floor
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum DustAction {
+    /// Do not persist the note
+    Skip,
+    /// Persist the note flagged as dust, excluding it from redemption
+    Flag,
+}
+
+/// The dust floors of each mint
+#[derive(Clone, Debug)]
+pub struct DustFilter {
+    /// The floors, keyed by lowercase mint
+    floors: HashMap<String, u128>,
+    /// What to do with dust notes
+    pub action: DustAction,
+}
+
+impl DustFilter {
+    /// Constructor
+    pub fn new(floors: Vec<DustFloor>, action: DustAction) -> Result<Self, FeeSweeperError> {
+        let mut floor_map = HashMap::new();
+        for floor in floors {
+            let mint = floor.mint.clone();
+            if floor_map.insert(mint.clone(), floor.min_amount).is_some() {
+                return Err(FeeSweeperError::Config(format!(
+                    "duplicate dust floor for {mint}"
+                )));
+            }
+        }
+
+        Ok(Self {
+            floors: floor_map,
+            action,
+        })
+    }
+
+    /// Whether a note of the given mint and raw amount is dust
+    pub fn is_dust(&self, mint: &str, amount: u128) -> bool {
+        self.floors
+            .get(&mint.to_lowercase())
+            .is_some_and(|min_amount| amount < *min_amount)
+    }
+}
@@ -0,0 +1,346 @@
+//! The sources from which historical darkpool events are read
+//!
+//! Events are read from the RPC node's `eth_getLogs` by default. Providers cap
+//! the block range of a single log query, which makes deep backfills slow; a
+//! backfill may instead page through the logs indexed by an
+//! Etherscan-compatible block explorer, e.g. Arbiscan. Incremental indexing
+//! always reads from the RPC node, as an explorer may lag the chain head and
+//! the indexing cursor must not pass events the explorer has yet to index
+//!
+//! Blocks are indexed in chunks of an adaptive window. A provider rejecting a
+//! log query as too large halves the window and the chunk is retried, and
+//! each chunk indexed without error grows the window back
+
+use std::time::Duration;
+
+use arbitrum_client::abi::NotePostedFilter;
+use clap::{Args, ValueEnum};
+use ethers::abi::RawLog;
+use ethers::contract::{EthEvent, LogMeta};
+use ethers::types::{Address, Bytes, H256, U256, U64};
+use metrics::gauge;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::info;
+
+use crate::error::FeeSweeperError;
+use crate::telemetry::{ETH_GET_LOGS, LOG_WINDOW_METRIC};
+use crate::Indexer;
+
+/// The explorer status of a successful response
+pub(crate) const EXPLORER_STATUS_OK: &str = "1";
+/// The explorer message of a query matching no logs
+const EXPLORER_NO_RECORDS: &str = "No records found";
+/// Fragments of the errors providers return for log queries spanning too
+/// many blocks or matching too many logs, lowercased
+const LOG_RANGE_ERRORS: &[&str] = &[
+    "block range",
+    "returned more than",
+    "response size exceeded",
+    "is limited to",
+    "too many results",
+];
+
+/// A source of historical darkpool events
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum EventSource {
+    /// The RPC node's `eth_getLogs`
+    Rpc,
+    /// The `getLogs` action of the Etherscan-compatible block explorer
+    /// configured by `--explorer-api-url`
+    Explorer,
+}
+
+/// The arguments configuring the source of historical events
+#[derive(Clone, Debug, Args)]
+pub struct EventSourceArgs {
+    /// The source from which backfills read note events
+    #[clap(long, value_enum, default_value_t = EventSource::Rpc)]
+    pub backfill_event_source: EventSource,
+    /// The number of logs requested per page of the explorer's `getLogs`
+    #[clap(long, default_value_t = 1000)]
+    pub explorer_page_size: usize,
+    /// The delay, in milliseconds, between requests to the explorer, keeping
+    /// within its rate limit
+    #[clap(long, default_value_t = 250)]
+    pub explorer_request_interval_ms: u64,
+    /// The number of blocks indexed per chunk at the start of a run
+    #[clap(long, default_value_t = 10_000)]
+    pub log_window_blocks: u64,
+    /// The fewest blocks indexed per chunk, however often the provider
+    /// rejects a log query as too large
+    #[clap(long, default_value_t = 10)]
+    pub min_log_window_blocks: u64,
+    /// The most blocks indexed per chunk, however many chunks succeed
+    #[clap(long, default_value_t = 100_000)]
+    pub max_log_window_blocks: u64,
+}
+
+/// The adaptive window of blocks indexed per chunk
+#[derive(Clone, Debug)]
+pub struct LogWindow {
+    /// The number of blocks in the window
+    blocks: u64,
+    /// The fewest blocks in the window
+    min_blocks: u64,
+    /// The most blocks in the window
+    max_blocks: u64,
+}
+
+impl LogWindow {
+    /// Create a log window, validating its bounds
+    pub fn new(args: &EventSourceArgs) -> Result<Self, FeeSweeperError> {
+        let min_blocks = args.min_log_window_blocks;
+        let max_blocks = args.max_log_window_blocks;
+        if min_blocks == 0 || min_blocks > max_blocks {
+            return Err(FeeSweeperError::Rpc(format!(
+                "invalid log window bounds: {min_blocks} to {max_blocks} blocks"
+            )));
+        }
+
+        let window = Self {
+            blocks: args.log_window_blocks.clamp(min_blocks, max_blocks),
+            min_blocks,
+            max_blocks,
+        };
+        window.record();
+        Ok(window)
+    }
+
+    /// The number of blocks in the window
+    pub fn blocks(&self) -> u64 {
+        self.blocks
+    }
+
+    /// The last block of the chunk starting at the given block, bounded by the
+    /// end of the range indexed
+    pub fn chunk_end(&self, start: u64, range_end: u64) -> u64 {
+        start.saturating_add(self.blocks - 1).min(range_end)
+    }
+
+    /// Halve the window after a query was rejected as too large
+    ///
+    /// Returns `false` if the window is already at its minimum
+    pub fn shrink(&mut self) -> bool {
+        if self.blocks == self.min_blocks {
+            return false;
+        }
+
+        self.blocks = (self.blocks / 2).max(self.min_blocks);
+        self.record();
+        true
+    }
+
+    /// Double the window after a chunk was indexed
+    pub fn grow(&mut self) {
+        self.blocks = self.blocks.saturating_mul(2).min(self.max_blocks);
+        self.record();
+    }
+
+    /// Record the size of the window
+    fn record(&self) {
+        gauge!(LOG_WINDOW_METRIC).set(self.blocks as f64);
+    }
+}
+
+/// Whether an error is a provider's rejection of a log query spanning too
+/// many blocks or matching too many logs
+pub fn is_log_range_error(error: &str) -> bool {
+    let error = error.to_lowercase();
+    LOG_RANGE_ERRORS
+        .iter()
+        .any(|fragment| error.contains(fragment))
+}
+
+/// The response of the explorer's `getLogs` action
+#[derive(Deserialize)]
+struct GetLogsResponse {
+    /// `1` on success, `0` on an error or an empty result
+    status: String,
+    /// A description of the status
+    message: String,
+    /// The logs on success, an error message otherwise
+    result: Value,
+}
+
+/// A log returned by the explorer's `getLogs` action
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExplorerLog {
+    /// The address of the emitting contract
+    address: Address,
+    /// The topics of the log
+    topics: Vec<H256>,
+    /// The data of the log
+    data: Bytes,
+    /// The hex-encoded number of the block containing the log
+    block_number: String,
+    /// The hash of the block containing the log
+    block_hash: H256,
+    /// The hash of the transaction emitting the log
+    transaction_hash: H256,
+    /// The hex-encoded index of the transaction in its block
+    transaction_index: String,
+    /// The hex-encoded index of the log in its block
+    log_index: String,
+}
+
+impl ExplorerLog {
+    /// Decode the log into an event and its metadata
+    fn decode<E: EthEvent>(self) -> Result<(E, LogMeta), FeeSweeperError> {
+        let meta = LogMeta {
+            address: self.address,
+            block_number: U64::from(parse_hex_u64(&self.block_number)?),
+            block_hash: self.block_hash,
+            transaction_hash: self.transaction_hash,
+            transaction_index: U64::from(parse_hex_u64(&self.transaction_index)?),
+            log_index: U256::from(parse_hex_u64(&self.log_index)?),
+        };
+        let raw = RawLog {
+            topics: self.topics,
+            data: self.data.to_vec(),
+        };
+        let event = E::decode_log(&raw).map_err(FeeSweeperError::rpc("failed to decode log"))?;
+
+        Ok((event, meta))
+    }
+}
+
+/// Parse a hex-encoded integer of the explorer, which encodes zero as `0x`
+fn parse_hex_u64(value: &str) -> Result<u64, FeeSweeperError> {
+    let digits = value.trim_start_matches("0x");
+    if digits.is_empty() {
+        return Ok(0);
+    }
+
+    u64::from_str_radix(digits, 16)
+        .map_err(|e| FeeSweeperError::Rpc(format!("invalid hex integer {value}: {e}")))
+}
+
+impl Indexer {
+    /// Get the note posted events in a range of blocks, reading backfills from
+    /// the configured event source
+    pub(crate) async fn get_note_posted_events(
+        &self,
+        from_block: u64,
+        to_block: u64,
+        backfill: bool,
+    ) -> Result<Vec<(NotePostedFilter, LogMeta)>, FeeSweeperError> {
+        if backfill && self.event_source.backfill_event_source == EventSource::Explorer {
+            return self.get_explorer_events(from_block, to_block).await;
+        }
+
+        self.record_rpc(ETH_GET_LOGS);
+        self.darkpool_client
+            .darkpool()
+            .get_darkpool_client()
+            .event::<NotePostedFilter>()
+            .from_block(from_block)
+            .to_block(to_block)
+            .query_with_meta()
+            .await
+            .map_err(FeeSweeperError::rpc("failed to create note posted stream"))
+    }
+
+    /// Page through the events of the darkpool contract indexed by the block
+    /// explorer in a range of blocks
+    ///
+    /// The explorer caps the number of logs reachable by paging a single query,
+    /// so each page is queried from the last block of the one before it,
+    /// skipping the logs of that block already returned
+    async fn get_explorer_events<E: EthEvent>(
+        &self,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<Vec<(E, LogMeta)>, FeeSweeperError> {
+        let base_url = self.spam.explorer_api_url.as_deref().ok_or_else(|| {
+            FeeSweeperError::Config(
+                "reading events from the explorer requires --explorer-api-url".to_string(),
+            )
+        })?;
+        let address = self
+            .darkpool_client
+            .darkpool()
+            .get_darkpool_client()
+            .address();
+        let topic = E::signature();
+        let page_size = self.event_source.explorer_page_size;
+        let interval = Duration::from_millis(self.event_source.explorer_request_interval_ms);
+
+        let mut events: Vec<(E, LogMeta)> = Vec::new();
+        let mut cursor = from_block;
+        loop {
+            let mut url = format!(
+                "{base_url}/api?module=logs&action=getLogs&address={address:?}&topic0={topic:?}\
+                &fromBlock={cursor}&toBlock={to_block}&page=1&offset={page_size}"
+            );
+            if let Some(key) = &self.spam.explorer_api_key {
+                url.push_str(&format!("&apikey={key}"));
+            }
+
+            let logs = fetch_explorer_logs(&self.explorer_client, &url).await?;
+            let page_len = logs.len();
+            for log in logs {
+                let (event, meta) = log.decode::<E>()?;
+                let seen = events.last().is_some_and(|(_, last)| {
+                    (meta.block_number, meta.log_index) <= (last.block_number, last.log_index)
+                });
+                if !seen {
+                    events.push((event, meta));
+                }
+            }
+
+            if page_len < page_size {
+                break;
+            }
+
+            // A full page, resume from its last block
+            let last_block = events
+                .last()
+                .map(|(_, meta)| meta.block_number.as_u64())
+                .unwrap_or(cursor);
+            if last_block == cursor {
+                return Err(FeeSweeperError::Config(format!(
+                    "block {cursor} holds more than a page of events, raise --explorer-page-size"
+                )));
+            }
+            cursor = last_block;
+            info!(
+                "read {} events from the explorer through block {cursor}",
+                events.len()
+            );
+            tokio::time::sleep(interval).await;
+        }
+
+        Ok(events)
+    }
+}
+
+/// Fetch a page of logs from the explorer
+async fn fetch_explorer_logs(
+    client: &Client,
+    url: &str,
+) -> Result<Vec<ExplorerLog>, FeeSweeperError> {
+    let resp: GetLogsResponse = client
+        .get(url)
+        .send()
+        .await
+        .map_err(FeeSweeperError::rpc("failed to query explorer"))?
+        .json()
+        .await
+        .map_err(FeeSweeperError::rpc("failed to parse explorer response"))?;
+
+    if resp.status != EXPLORER_STATUS_OK {
+        if resp.message == EXPLORER_NO_RECORDS {
+            return Ok(Vec::new());
+        }
+        return Err(FeeSweeperError::Rpc(format!(
+            "explorer returned an error: {} ({})",
+            resp.message, resp.result
+        )));
+    }
+
+    serde_json::from_value(resp.result)
+        .map_err(FeeSweeperError::rpc("failed to parse explorer logs"))
+}
@@ -0,0 +1,67 @@
+//! Determines the most recent block the indexer may treat as final
+//!
+//! By default a block is final once it is buried under a fixed number of
+//! confirmations on the chain itself. For maximum safety, finality may instead
+//! be defined by the L1: a block is final once the L1 has finalized the data
+//! it was derived from, as observed by the chain's adapter
+
+use clap::{Args, ValueEnum};
+use tracing::info;
+
+use crate::error::FeeSweeperError;
+use crate::telemetry::ETH_BLOCK_NUMBER;
+use crate::Indexer;
+
+/// The source of truth for block finality
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum FinalitySource {
+    /// A fixed number of confirmations on the chain itself
+    #[value(alias = "arbitrum")]
+    Confirmations,
+    /// The L1 finality of the data a block was derived from
+    L1,
+}
+
+/// The arguments configuring when an indexed block is considered final
+#[derive(Clone, Debug, Args)]
+pub struct FinalityArgs {
+    /// The source of truth for block finality
+    #[clap(long, value_enum, default_value_t = FinalitySource::Confirmations)]
+    pub finality_source: FinalitySource,
+    /// The number of blocks that must follow a block for it to be final, when
+    /// finality is sourced from confirmations
+    #[clap(long, default_value_t = 0)]
+    pub finality_confirmations: u64,
+    /// The number of L1 confirmations the batch containing a block must have
+    /// for it to be final, when finality is sourced from L1 on chains that
+    /// report batch confirmations
+    #[clap(long, default_value_t = 64)]
+    pub min_l1_confirmations: u64,
+}
+
+impl Indexer {
+    /// Get the most recent final block
+    ///
+    /// Returns `None` if no block at or after `from_block` is final
+    pub(crate) async fn get_finalized_block(
+        &self,
+        from_block: u64,
+    ) -> Result<Option<u64>, FeeSweeperError> {
+        self.record_rpc(ETH_BLOCK_NUMBER);
+        let latest = self.darkpool_client.latest_block().await?;
+        let finalized = match self.finality.finality_source {
+            FinalitySource::Confirmations => {
+                latest.checked_sub(self.finality.finality_confirmations)
+            }
+            FinalitySource::L1 => {
+                self.darkpool_client
+                    .get_l1_finalized_block(from_block, latest, self.finality.min_l1_confirmations)
+                    .await?
+            }
+        };
+
+        let finalized = finalized.filter(|block| *block >= from_block);
+        info!("latest block {latest}, finalized block {finalized:?}");
+        Ok(finalized)
+    }
+}
@@ -0,0 +1,118 @@
+//! Skipping redemptions whose value does not cover their gas cost
+//!
+//! A redemption's settlement transaction is built and proven by the relayer,
+//! so its gas cannot be estimated by the sweeper before the relayer is asked
+//! to redeem. Instead, the cost of a redemption is estimated as the configured
+//! gas used by a settlement at the chain's current gas price, valued at the
+//! price of WETH. A fee worth less than a multiple of that cost is skipped and
+//! the reason recorded against it; as every pass re-evaluates the open fees, a
+//! skipped fee is redeemed once gas is cheaper or its mint's price rises
+
+use std::collections::HashMap;
+
+use bigdecimal::{BigDecimal, FromPrimitive};
+use clap::Args;
+use ethers::middleware::Middleware;
+use ethers::utils::format_units;
+use metrics::counter;
+use renegade_common::types::token::Token;
+use tracing::info;
+
+use super::queries::FeeValue;
+use crate::error::FeeSweeperError;
+use crate::telemetry::{ETH_GAS_PRICE, REDEMPTIONS_SKIPPED_GAS_METRIC};
+use crate::Indexer;
+
+/// The default gas used by a redemption's settlement transaction
+const DEFAULT_REDEMPTION_GAS: u64 = 3_000_000;
+/// The ticker of the token gas is valued in
+const GAS_TOKEN_TICKER: &str = "WETH";
+
+/// The arguments configuring the gas cost filter on redemptions
+#[derive(Clone, Debug, Args)]
+pub struct GasFilterArgs {
+    /// Skip fees worth less than this multiple of the estimated gas cost of
+    /// redeeming them; unset, fees are redeemed whatever their gas cost
+    #[clap(long)]
+    pub gas_cost_multiplier: Option<f64>,
+    /// The gas used by a redemption's settlement transaction, from which the
+    /// gas cost of a redemption is estimated
+    #[clap(long, default_value_t = DEFAULT_REDEMPTION_GAS)]
+    pub redemption_gas: u64,
+}
+
+impl Indexer {
+    /// Estimate the USD gas cost of a redemption at the current gas price
+    ///
+    /// Returns `None` if the filter is disabled, or if gas cannot be valued
+    pub(crate) async fn estimate_redemption_gas_cost(
+        &self,
+        routes: &HashMap<String, String>,
+    ) -> Result<Option<f64>, FeeSweeperError> {
+        if self.gas_filter.gas_cost_multiplier.is_none() {
+            return Ok(None);
+        }
+
+        self.record_rpc(ETH_GAS_PRICE);
+        let gas_price = self
+            .darkpool_client
+            .darkpool()
+            .get_darkpool_client()
+            .client()
+            .get_gas_price()
+            .await
+            .map_err(FeeSweeperError::rpc("failed to query gas price"))?;
+        let gas_mint = Token::from_ticker(GAS_TOKEN_TICKER).get_addr();
+        let eth_price = match self
+            .relayer_client
+            .get_routed_price(&gas_mint, routes)
+            .await?
+        {
+            Some(price) => price,
+            None => {
+                info!("no {GAS_TOKEN_TICKER} price, redeeming without a gas cost filter");
+                return Ok(None);
+            }
+        };
+
+        let cost_wei = gas_price * self.gas_filter.redemption_gas;
+        let cost_eth: f64 = format_units(cost_wei, "ether")
+            .map_err(FeeSweeperError::rpc("failed to convert gas cost"))?
+            .parse()
+            .map_err(FeeSweeperError::rpc("failed to parse gas cost"))?;
+        let cost_usd = cost_eth * eth_price;
+        info!("estimated redemption gas cost: ${cost_usd:.4}");
+
+        Ok(Some(cost_usd))
+    }
+
+    /// Whether a fee's value covers the gas cost of redeeming it, recording
+    /// the reason against a fee that does not
+    pub(crate) async fn covers_gas_cost(
+        &mut self,
+        fee: &FeeValue,
+        gas_cost_usd: Option<f64>,
+    ) -> Result<bool, FeeSweeperError> {
+        let (Some(cost), Some(multiplier)) = (gas_cost_usd, self.gas_filter.gas_cost_multiplier)
+        else {
+            return Ok(true);
+        };
+
+        let value = fee.usd_value()?;
+        let min_value = BigDecimal::from_f64(cost * multiplier)
+            .ok_or_else(|| FeeSweeperError::Rpc(format!("invalid gas cost: {cost}")))?;
+        if value >= min_value {
+            self.set_fee_skip_reason(&fee.tx_hash, None).await?;
+            return Ok(true);
+        }
+
+        let reason = format!(
+            "value ${} below {multiplier}x the estimated gas cost of ${cost:.4}",
+            value.round(4)
+        );
+        info!("skipping fee from tx {}: {reason}", fee.tx_hash);
+        counter!(REDEMPTIONS_SKIPPED_GAS_METRIC).increment(1);
+        self.set_fee_skip_reason(&fee.tx_hash, Some(reason)).await?;
+        Ok(false)
+    }
+}
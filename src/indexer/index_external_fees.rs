@@ -0,0 +1,117 @@
+//! Indexes fees earned in external (atomic) matches
+//!
+//! Unlike internal matches, which pay fees into encrypted notes, an external
+//! match settles its fees by transferring them out of the darkpool directly to
+//! the fee recipient. These transfers are indexed as already-redeemed fees
+//! tagged with their source, keyed on the transfer's log so that a settlement
+//! paying several fees records each of them.
+//!
+//! Only transfers made by an atomic match settlement are fees; any other
+//! transfer from the darkpool to the recipient is skipped
+
+use std::collections::HashMap;
+
+use alloy_sol_types::SolCall;
+use arbitrum_client::abi::{
+    processAtomicMatchSettleCall, processAtomicMatchSettleWithReceiverCall,
+};
+use arbitrum_client::constants::SELECTOR_LEN;
+use ethers::middleware::Middleware;
+use ethers::types::{Address, Filter, Transaction, TxHash, U256};
+use tracing::info;
+
+use crate::db::models::NewFee;
+use crate::error::FeeSweeperError;
+use crate::telemetry::ETH_GET_LOGS;
+use crate::Indexer;
+
+/// The signature of the ERC-20 transfer event
+const ERC20_TRANSFER_EVENT: &str = "Transfer(address,address,uint256)";
+
+impl Indexer {
+    /// Index all external match fees paid to the recipient in the given block
+    /// range
+    pub async fn index_external_fees(
+        &mut self,
+        from_block: u64,
+        to_block: u64,
+        recipient: Address,
+    ) -> Result<(), FeeSweeperError> {
+        let darkpool_client = self.darkpool_client.darkpool().get_darkpool_client();
+        let filter = Filter::new()
+            .event(ERC20_TRANSFER_EVENT)
+            .topic1(darkpool_client.address())
+            .topic2(recipient)
+            .from_block(from_block)
+            .to_block(to_block);
+        self.record_rpc(ETH_GET_LOGS);
+        let logs = darkpool_client
+            .client()
+            .get_logs(&filter)
+            .await
+            .map_err(FeeSweeperError::rpc("failed to query external match fees"))?;
+
+        let mut settlements: HashMap<TxHash, bool> = HashMap::new();
+        for log in logs {
+            let (tx_hash, block_number, log_index) =
+                match (log.transaction_hash, log.block_number, log.log_index) {
+                    (Some(tx_hash), Some(block_number), Some(log_index)) => {
+                        (tx_hash, block_number, log_index)
+                    }
+                    _ => continue,
+                };
+
+            let tx = format!("{tx_hash:#x}");
+            if self.check_blocklist(&tx, block_number.as_u64()).await? {
+                continue;
+            }
+
+            let is_settlement = match settlements.get(&tx_hash) {
+                Some(is_settlement) => *is_settlement,
+                None => {
+                    let settlement = self.get_settlement_tx(tx_hash).await?;
+                    let is_settlement = is_atomic_match_settlement(&settlement);
+                    settlements.insert(tx_hash, is_settlement);
+                    is_settlement
+                }
+            };
+            if !is_settlement {
+                info!("skipping transfer to fee recipient outside a match settlement: {tx}");
+                continue;
+            }
+
+            let mint = format!("{:#x}", log.address);
+            let amount = U256::from_big_endian(&log.data);
+            info!("indexing external match fee of {amount} {mint} from tx: {tx}");
+
+            let recipient = format!("{recipient:#x}");
+            let fee = NewFee::new_external(
+                tx,
+                mint,
+                amount,
+                recipient,
+                block_number.as_u64(),
+                log_index.as_u64(),
+                self.external_fee_kind,
+            );
+            self.insert_fee_if_new(fee).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether a transaction settles an atomic match, and so pays external match
+/// fees
+fn is_atomic_match_settlement(tx: &Transaction) -> bool {
+    if tx.input.len() < SELECTOR_LEN {
+        return false;
+    }
+
+    let selector: [u8; 4] = tx.input[..SELECTOR_LEN].try_into().unwrap();
+    matches!(
+        selector,
+        <processAtomicMatchSettleCall as SolCall>::SELECTOR
+            | <processAtomicMatchSettleWithReceiverCall as SolCall>::SELECTOR
+    )
+}
@@ -0,0 +1,81 @@
+//! Indexes changes to the protocol's fee settings emitted by the darkpool, so
+//! that fee accrual may be correlated with the rates in effect
+
+use bigdecimal::BigDecimal;
+use ethers::contract::LogMeta;
+use ethers::types::U256;
+use renegade_circuit_types::fixed_point::DEFAULT_FP_PRECISION;
+use tracing::info;
+
+use self::bindings::FeeChangedFilter;
+use crate::db::models::{u256_to_decimal, NewFeeSetting};
+use crate::error::FeeSweeperError;
+use crate::telemetry::ETH_GET_LOGS;
+use crate::Indexer;
+
+/// The setting name of the protocol fee rate
+pub(crate) const PROTOCOL_FEE_SETTING: &str = "protocol_fee";
+
+/// The generated event bindings
+#[allow(missing_docs, clippy::missing_docs_in_private_items)]
+mod bindings {
+    ethers::contract::abigen!(
+        DarkpoolFeeEvents,
+        r#"[
+            event FeeChanged(uint256 new_fee)
+        ]"#
+    );
+}
+
+impl Indexer {
+    /// Index all fee setting changes in the given block range
+    pub async fn index_fee_settings(
+        &mut self,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<(), FeeSweeperError> {
+        self.record_rpc(ETH_GET_LOGS);
+        let events = self
+            .darkpool_client
+            .darkpool()
+            .get_darkpool_client()
+            .event::<FeeChangedFilter>()
+            .from_block(from_block)
+            .to_block(to_block)
+            .query_with_meta()
+            .await
+            .map_err(FeeSweeperError::rpc("failed to query fee changes"))?;
+
+        for (event, meta) in events {
+            let setting = new_fee_setting(PROTOCOL_FEE_SETTING, None, event.new_fee, &meta);
+            info!(
+                "protocol fee changed to {} in tx {}",
+                setting.rate, setting.tx_hash
+            );
+            self.insert_fee_setting(setting).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Build a fee setting change from a fixed point rate emitted in an event
+pub(crate) fn new_fee_setting(
+    setting: &str,
+    asset: Option<String>,
+    raw_value: U256,
+    meta: &LogMeta,
+) -> NewFeeSetting {
+    let raw_value = u256_to_decimal(raw_value);
+    let rate = &raw_value / BigDecimal::from(1u64 << DEFAULT_FP_PRECISION);
+
+    NewFeeSetting {
+        setting: setting.to_string(),
+        asset,
+        raw_value,
+        rate,
+        block_number: meta.block_number.as_u64() as i64,
+        tx_hash: format!("{:#x}", meta.transaction_hash),
+        log_index: meta.log_index.as_u64() as i64,
+    }
+}
@@ -1,124 +1,342 @@
 //! Phase one of the sweeper's execution; index all fees since the last consistent block
 
+use std::sync::Arc;
+use std::time::Instant;
+
 use alloy_sol_types::SolCall;
 use arbitrum_client::abi::settleOfflineFeeCall;
 use arbitrum_client::{
-    abi::NotePostedFilter, constants::SELECTOR_LEN,
-    helpers::parse_note_ciphertext_from_settle_offline_fee,
+    constants::SELECTOR_LEN, helpers::parse_note_ciphertext_from_settle_offline_fee,
 };
 use ethers::contract::LogMeta;
 use ethers::middleware::Middleware;
-use ethers::types::TxHash;
-use renegade_circuit_types::elgamal::ElGamalCiphertext;
-use renegade_circuit_types::native_helpers::elgamal_decrypt;
-use renegade_circuit_types::note::{Note, NOTE_CIPHERTEXT_SIZE};
+use ethers::types::{Address, Transaction, TxHash, U256};
+use renegade_circuit_types::elgamal::DecryptionKey;
+use renegade_circuit_types::note::Note;
 use renegade_circuit_types::wallet::NoteCommitment;
-use renegade_constants::Scalar;
-use renegade_crypto::fields::{scalar_to_biguint, scalar_to_u128, u256_to_scalar};
-use renegade_util::raw_err_str;
-use tracing::info;
+use renegade_crypto::fields::u256_to_scalar;
+use renegade_util::hex::biguint_to_hex_addr;
+use tracing::{info, info_span, warn, Instrument};
 
+use super::dust::DustAction;
+use super::event_source::is_log_range_error;
 use crate::db::models::NewFee;
+use crate::decryption::{decrypt_note, NoteCiphertext};
+use crate::error::FeeSweeperError;
+use crate::fee_keys::FeeKind;
+use crate::telemetry::{ETH_CALL, ETH_GET_TRANSACTION};
 use crate::Indexer;
 
 impl Indexer {
     /// Index all fees since the given block
-    pub async fn index_fees(&mut self) -> Result<(), String> {
-        let block_number = self.get_latest_block()?;
-        info!("indexing fees from block {block_number}");
+    pub async fn index_fees(&mut self) -> Result<(), FeeSweeperError> {
+        let block_number = self.get_latest_block().await?;
+        self.index_darkpool_status(block_number).await?;
+        let finalized_block = match self.get_finalized_block(block_number).await? {
+            Some(block) => block,
+            None => {
+                info!("no final blocks since block {block_number}");
+                return Ok(());
+            }
+        };
 
-        let filter = self
-            .arbitrum_client
-            .get_darkpool_client()
-            .event::<NotePostedFilter>()
-            .from_block(block_number);
+        // Index a smaller range while short of RPC budget, resuming from its
+        // end in the next run
+        let range_end = block_number.saturating_add(self.rpc_budget.degraded_block_range);
+        if self.rpc_budget_constrained() && finalized_block > range_end {
+            info!("short of RPC budget, indexing fees from block {block_number} to {range_end}");
+            return self
+                .index_block_range(block_number, range_end, false /* backfill */)
+                .await;
+        }
 
-        let events = filter
-            .query_with_meta()
+        info!("indexing fees from block {block_number} to {finalized_block}");
+        self.index_block_range(block_number, finalized_block, false /* backfill */)
             .await
-            .map_err(raw_err_str!("failed to create note posted stream: {}"))?;
+    }
 
-        let mut most_recent_block = block_number;
-        for (event, meta) in events {
-            let block = meta.block_number.as_u64();
-            let note_comm = u256_to_scalar(&event.note_commitment);
-            self.index_note(note_comm, meta).await?;
+    /// Re-index the fees in a range of past blocks, ending at the latest final
+    /// block if no end is given; the range must be final
+    ///
+    /// The indexing cursor is left where it is, and fees already indexed are
+    /// skipped, so a backfill may overlap blocks the sweeper has indexed
+    pub async fn backfill_fees(
+        &mut self,
+        from_block: u64,
+        to_block: Option<u64>,
+    ) -> Result<(), FeeSweeperError> {
+        let finalized_block = self.get_finalized_block(from_block).await?.ok_or_else(|| {
+            FeeSweeperError::Config(format!("no final blocks since block {from_block}"))
+        })?;
+        let to_block = to_block.unwrap_or(finalized_block);
+        if to_block < from_block {
+            return Err(FeeSweeperError::Config(format!(
+                "backfill range ends at block {to_block} before it starts at block {from_block}"
+            )));
+        }
+        // Fees in blocks that are not yet final may be reorged away
+        if to_block > finalized_block {
+            return Err(FeeSweeperError::Config(format!(
+                "backfill range ends at block {to_block}, past the final block {finalized_block}"
+            )));
+        }
+
+        info!("backfilling fees from block {from_block} to {to_block}");
+        self.index_block_range(from_block, to_block, true /* backfill */)
+            .await
+    }
 
-            if block > most_recent_block {
-                most_recent_block = block;
-                self.update_latest_block(most_recent_block)?;
+    /// Index the fees paid in a range of blocks, in chunks of the log window
+    ///
+    /// Unless backfilling, the indexing cursor advances as each chunk is
+    /// indexed, in the transaction inserting the chunk's fees. A chunk whose
+    /// log queries the provider rejects as too large is retried with a smaller
+    /// window; its fee settings and external fees are inserted idempotently,
+    /// and its notes are only indexed once all of its logs are read
+    async fn index_block_range(
+        &mut self,
+        from_block: u64,
+        to_block: u64,
+        backfill: bool,
+    ) -> Result<(), FeeSweeperError> {
+        let mut chunk_start = from_block;
+        while chunk_start <= to_block {
+            let chunk_end = self.log_window.chunk_end(chunk_start, to_block);
+            let span = info_span!("chunk", from_block = chunk_start, to_block = chunk_end);
+            match self
+                .index_chunk(chunk_start, chunk_end, backfill)
+                .instrument(span)
+                .await
+            {
+                Ok(()) => self.log_window.grow(),
+                Err(e) if is_log_range_error(&e.to_string()) && self.log_window.shrink() => {
+                    warn!(
+                        "provider rejected logs of blocks {chunk_start} to {chunk_end}, retrying \
+                        with a window of {} blocks: {e}",
+                        self.log_window.blocks()
+                    );
+                    continue;
+                }
+                Err(e) => return Err(e),
             }
+
+            chunk_start = chunk_end + 1;
         }
 
         Ok(())
     }
 
-    /// Index a note
-    async fn index_note(&mut self, note_comm: NoteCommitment, meta: LogMeta) -> Result<(), String> {
-        let note = self.get_note_from_tx(meta.transaction_hash).await?;
+    /// Index the fees paid in a chunk of blocks
+    ///
+    /// The chunk's notes are indexed as a batch, committed once all are read.
+    /// Their ciphertexts are fetched first, then decrypted together on the
+    /// decryption pool, and the notes found to be ours are checked last
+    async fn index_chunk(
+        &mut self,
+        from_block: u64,
+        to_block: u64,
+        backfill: bool,
+    ) -> Result<(), FeeSweeperError> {
+        self.index_fee_settings(from_block, to_block).await?;
+        if let Some(recipient) = self.external_fee_recipient {
+            self.index_external_fees(from_block, to_block, recipient)
+                .await?;
+        }
+
+        let events = self
+            .get_note_posted_events(from_block, to_block, backfill)
+            .await?;
+        let mut posted = Vec::new();
+        for (event, meta) in events {
+            self.check_rpc_budget()?;
+            let commitment = format!("{:#x}", event.note_commitment);
+            let span = info_span!("note", note_commitment = %commitment);
+            if let Some(note) = self
+                .read_posted_note(event.note_commitment, meta, backfill)
+                .instrument(span)
+                .await?
+            {
+                posted.push(note);
+            }
+        }
+
+        // Decrypt the chunk's notes in parallel, keeping those that are ours
+        let keys = Arc::new(self.fee_keys.iter().map(|fee_key| fee_key.key).collect());
+        let ciphertexts = posted
+            .iter()
+            .map(|note| (note.ciphertext.clone(), note.commitment))
+            .collect();
+        let start = Instant::now();
+        let decrypted = self
+            .decryption_pool
+            .decrypt(ciphertexts, keys)
+            .await
+            .map_err(FeeSweeperError::Decryption)?;
+        info!(
+            "decrypted {} notes on {} workers in {:.2?}",
+            posted.len(),
+            self.decryption_pool.workers(),
+            start.elapsed()
+        );
+
+        let mut fees = Vec::new();
+        for (posted, decrypted) in posted.into_iter().zip(decrypted) {
+            let (key_index, note) = match decrypted {
+                Some(decrypted) => decrypted,
+                None => {
+                    info!("not receiver of note from tx {}, skipping", posted.tx);
+                    continue;
+                }
+            };
+
+            self.check_rpc_budget()?;
+            let kind = self.fee_keys[key_index].kind;
+            let span = info_span!(
+                "note",
+                note_commitment = %posted.commitment_hex,
+                mint = %biguint_to_hex_addr(&note.mint)
+            );
+            if let Some(fee) = self.index_note(posted, note, kind).instrument(span).await? {
+                fees.push(fee);
+            }
+        }
+
+        self.commit_indexed_chunk(fees, to_block, backfill).await
+    }
+
+    /// Read the ciphertext of a posted note from its settlement transaction
+    ///
+    /// Notes of blocklisted transactions, and when backfilling notes already
+    /// indexed, are skipped before any RPC call
+    async fn read_posted_note(
+        &mut self,
+        commitment: U256,
+        meta: LogMeta,
+        backfill: bool,
+    ) -> Result<Option<PostedNote>, FeeSweeperError> {
         let tx = format!("{:#x}", meta.transaction_hash);
-        if note.commitment() != note_comm {
-            info!("not receiver, skipping");
-            return Ok(());
-        } else {
-            info!("indexing note from tx: {tx}");
+        let block_number = meta.block_number.as_u64();
+        if self.check_blocklist(&tx, block_number).await? {
+            return Ok(None);
+        }
+        if backfill && self.fee_indexed(&tx).await? {
+            info!("fee from tx {tx} already indexed, skipping");
+            return Ok(None);
         }
 
+        let settlement = self.get_settlement_tx(meta.transaction_hash).await?;
+        let ciphertext = parse_note_ciphertext(&settlement)?;
+        Ok(Some(PostedNote {
+            tx,
+            block_number,
+            relayer: settlement.from,
+            commitment: u256_to_scalar(&commitment),
+            commitment_hex: format!("{commitment:#x}"),
+            ciphertext,
+        }))
+    }
+
+    /// Read the fee of a note decrypted as ours, if it is to be indexed
+    async fn index_note(
+        &mut self,
+        posted: PostedNote,
+        note: Note,
+        kind: FeeKind,
+    ) -> Result<Option<NewFee>, FeeSweeperError> {
+        let PostedNote {
+            tx,
+            block_number,
+            relayer,
+            ..
+        } = posted;
+        info!("indexing {kind} fee note from tx: {tx}");
+
         // Check that the note's nullifier has not been spent
         let nullifier = note.nullifier();
+        self.record_rpc(ETH_CALL);
         if self
-            .arbitrum_client
+            .darkpool_client
+            .darkpool()
             .check_nullifier_used(nullifier)
             .await
-            .map_err(raw_err_str!("failed to check nullifier: {}"))?
+            .map_err(FeeSweeperError::rpc("failed to check nullifier"))?
         {
             info!("note nullifier already spent, skipping");
-            return Ok(());
+            return Ok(None);
         }
 
-        // Otherwise, index the note
-        let fee = NewFee::new_from_note(&note, tx);
-        self.insert_fee(fee)
+        // Otherwise, index the note, unless it is dust to be skipped. The note
+        // is settled by the relayer managing the paying wallet
+        let mut fee = NewFee::new_from_note(&note, tx, block_number, kind);
+        fee.relayer = Some(format!("{relayer:#x}"));
+        if self.dust_filter.is_dust(&fee.mint, note.amount) {
+            if self.dust_filter.action == DustAction::Skip {
+                info!("note below dust floor, skipping");
+                return Ok(None);
+            }
+
+            info!("note below dust floor, flagging");
+            fee.dust = true;
+        }
+
+        Ok(Some(fee))
     }
 
-    /// Get a note from a transaction body
-    pub(crate) async fn get_note_from_tx(&self, tx_hash: TxHash) -> Result<Note, String> {
-        // Parse the note from the tx
-        let tx = self
-            .arbitrum_client
+    /// Get a note from a transaction body, decrypting it with the given key
+    pub(crate) async fn get_note_from_tx(
+        &self,
+        tx_hash: TxHash,
+        key: &DecryptionKey,
+    ) -> Result<Note, FeeSweeperError> {
+        let tx = self.get_settlement_tx(tx_hash).await?;
+        let ciphertext = parse_note_ciphertext(&tx)?;
+        Ok(decrypt_note(&ciphertext, key))
+    }
+
+    /// Get a settlement transaction by its hash
+    pub(crate) async fn get_settlement_tx(
+        &self,
+        tx_hash: TxHash,
+    ) -> Result<Transaction, FeeSweeperError> {
+        self.record_rpc(ETH_GET_TRANSACTION);
+        self.darkpool_client
+            .darkpool()
             .get_darkpool_client()
             .client()
             .get_transaction(tx_hash)
             .await
-            .map_err(raw_err_str!("failed to query tx: {}"))?
-            .ok_or_else(|| format!("tx not found: {}", tx_hash))?;
-
-        let calldata: Vec<u8> = tx.input.to_vec();
-        let selector: [u8; 4] = calldata[..SELECTOR_LEN].try_into().unwrap();
-        let encryption = match selector {
-            <settleOfflineFeeCall as SolCall>::SELECTOR => {
-                parse_note_ciphertext_from_settle_offline_fee(&calldata)
-                    .map_err(raw_err_str!("failed to parse ciphertext: {}"))?
-            }
-            sel => return Err(format!("invalid selector when parsing note: {sel:?}")),
-        };
-
-        // Decrypt the note
-        let note = self.decrypt_note(&encryption);
-        Ok(note)
+            .map_err(FeeSweeperError::rpc("failed to query tx"))?
+            .ok_or_else(|| FeeSweeperError::Rpc(format!("tx not found: {tx_hash}")))
     }
+}
+
+/// A note posted in a settlement transaction, before it is decrypted
+struct PostedNote {
+    /// The hash of the settlement transaction
+    tx: String,
+    /// The block the note was posted in
+    block_number: u64,
+    /// The relayer settling the note, managing the paying wallet
+    relayer: Address,
+    /// The commitment posted for the note
+    commitment: NoteCommitment,
+    /// The commitment posted for the note, hex-encoded for logs
+    commitment_hex: String,
+    /// The ciphertext of the note
+    ciphertext: NoteCiphertext,
+}
 
-    /// Decrypt a note using the decryption key
-    fn decrypt_note(&self, note: &ElGamalCiphertext<NOTE_CIPHERTEXT_SIZE>) -> Note {
-        // The ciphertext stores all note values except the encryption key
-        let cleartext_values: [Scalar; NOTE_CIPHERTEXT_SIZE] =
-            elgamal_decrypt(note, &self.decryption_key);
-
-        Note {
-            mint: scalar_to_biguint(&cleartext_values[0]),
-            amount: scalar_to_u128(&cleartext_values[1]),
-            receiver: self.decryption_key.public_key(),
-            blinder: cleartext_values[2],
+/// Parse the encrypted note from a transaction body
+fn parse_note_ciphertext(tx: &Transaction) -> Result<NoteCiphertext, FeeSweeperError> {
+    let calldata: Vec<u8> = tx.input.to_vec();
+    let selector: [u8; 4] = calldata[..SELECTOR_LEN].try_into().unwrap();
+    match selector {
+        <settleOfflineFeeCall as SolCall>::SELECTOR => {
+            parse_note_ciphertext_from_settle_offline_fee(&calldata)
+                .map_err(FeeSweeperError::rpc("failed to parse ciphertext"))
         }
+        sel => Err(FeeSweeperError::Rpc(format!(
+            "invalid selector when parsing note: {sel:?}"
+        ))),
     }
 }
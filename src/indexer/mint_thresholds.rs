@@ -0,0 +1,115 @@
+//! Minimum redemption thresholds that differ by mint
+//!
+//! Redeeming a note costs gas whatever its size, so a note in a long-tail
+//! token is not worth redeeming until it is large enough. A mint may be given
+//! a minimum raw amount and a minimum USD value; a fee below either is left
+//! unredeemed until it is no longer below them, e.g. after its price rises.
+//! Mints without a threshold are subject only to their source's policy
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use bigdecimal::{BigDecimal, FromPrimitive};
+
+use super::queries::FeeValue;
+use crate::error::FeeSweeperError;
+
+/// The minimum redemption threshold of a single mint
+///
+/// Parsed from a string of the form
+/// `<mint>:<min_raw_amount>[:<min_value_usd>]`
+#[derive(Clone, Debug)]
+pub struct MintThreshold {
+    /// The mint the threshold applies to
+    pub mint: String,
+    /// The smallest raw amount of a fee redeemed
+    pub min_amount: u128,
+    /// The minimum USD value of a fee redeemed
+    pub min_value_usd: f64,
+}
+
+impl FromStr for MintThreshold {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':');
+        let mint = parts.next().unwrap_or_default().trim().to_lowercase();
+        if mint.is_empty() {
+            return Err(format!(
+                "expected <mint>:<min_raw_amount>[:<min_value_usd>]: {s}"
+            ));
+        }
+
+        let min_amount = parts
+            .next()
+            .ok_or_else(|| format!("missing minimum amount: {s}"))?
+            .trim()
+            .parse::<u128>()
+            .map_err(|e| format!("invalid minimum amount: {e}"))?;
+        let min_value_usd = match parts.next() {
+            Some(value) => value
+                .trim()
+                .parse::<f64>()
+                .map_err(|e| format!("invalid minimum value: {e}"))?,
+            None => 0.,
+        };
+        if parts.next().is_some() {
+            return Err(format!("unexpected trailing fields: {s}"));
+        }
+
+        Ok(MintThreshold {
+            mint,
+            min_amount,
+            min_value_usd,
+        })
+    }
+}
+
+/// The minimum redemption thresholds of each mint
+#[derive(Clone, Debug, Default)]
+pub struct MintThresholds {
+    /// The thresholds, keyed by lowercase mint
+    thresholds: HashMap<String, MintThreshold>,
+}
+
+impl MintThresholds {
+    /// Constructor
+    pub fn new(thresholds: Vec<MintThreshold>) -> Result<Self, FeeSweeperError> {
+        let mut threshold_map = HashMap::new();
+        for threshold in thresholds {
+            let mint = threshold.mint.clone();
+            if threshold_map.insert(mint.clone(), threshold).is_some() {
+                return Err(FeeSweeperError::Config(format!(
+                    "duplicate redemption threshold for {mint}"
+                )));
+            }
+        }
+
+        Ok(Self {
+            thresholds: threshold_map,
+        })
+    }
+
+    /// Whether a fee is large enough to redeem under its mint's threshold
+    pub(crate) fn meets_threshold(&self, fee: &FeeValue) -> Result<bool, FeeSweeperError> {
+        let threshold = match self.thresholds.get(&fee.mint.to_lowercase()) {
+            Some(threshold) => threshold,
+            None => return Ok(true),
+        };
+
+        if fee.amount < BigDecimal::from(threshold.min_amount) {
+            return Ok(false);
+        }
+        if threshold.min_value_usd <= 0. {
+            return Ok(true);
+        }
+
+        let min_value = BigDecimal::from_f64(threshold.min_value_usd).ok_or_else(|| {
+            FeeSweeperError::Policy(format!(
+                "invalid minimum value: {}",
+                threshold.min_value_usd
+            ))
+        })?;
+        Ok(fee.usd_value()? >= min_value)
+    }
+}
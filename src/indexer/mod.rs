@@ -1,15 +1,85 @@
 //! The indexer handles the indexing and redemption of fee notes
 
-use arbitrum_client::{client::ArbitrumClient, constants::Chain};
-use aws_config::SdkConfig as AwsConfig;
-use diesel::PgConnection;
-use renegade_circuit_types::elgamal::DecryptionKey;
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
 
+use arbitrum_client::constants::Chain;
+use ethers::types::Address;
+use reqwest::Client;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use self::abandonment::AbandonmentArgs;
+use self::batch_sizing::BatchSizer;
+use self::blocklist::BlocklistArgs;
+use self::budget::DbBudget;
+use self::confirmation::ConfirmationArgs;
+use self::consistency::ConsistencyArgs;
+use self::convert_fees::ConversionArgs;
+use self::dead_letter::RedemptionRetryArgs;
+use self::dual_write::DualWriteArgs;
+use self::dust::DustFilter;
+use self::event_source::{EventSourceArgs, LogWindow};
+use self::finality::FinalityArgs;
+use self::gas_filter::GasFilterArgs;
+use self::mint_thresholds::MintThresholds;
+use self::policy::RedemptionPolicies;
+use self::rpc_budget::RpcBudgetArgs;
+use self::scoring::{ScorerArgs, SharedScorer};
+use self::signer::SignerSource;
+use self::slo::SloArgs;
+use self::spam::SpamArgs;
+use self::wallet_backup::WalletBackupArgs;
+use crate::aws::AwsContext;
+use crate::chain::EvmDarkpoolClient;
+use crate::clock::{system_clock, SharedClock, SharedRng};
+use crate::db::pool::DbPool;
+use crate::db::storage::SharedStorage;
+use crate::decryption::DecryptionPool;
+use crate::error::FeeSweeperError;
+use crate::feature_flags::FeatureFlags;
+use crate::fee_keys::{load_registered_fee_keys, FeeKey, FeeKeyArgs, FeeKind};
+use crate::historical_prices::HistoricalPriceClient;
+use crate::issues::IssueTracker;
+use crate::notifications::Notifier;
 use crate::relayer_client::RelayerClient;
+use crate::shutdown::Shutdown;
+use crate::telemetry::{QueryMetrics, RpcMetrics};
+use crate::wallet_lock::{WalletLockArgs, WalletLocks};
 
+pub mod abandonment;
+pub mod batch_sizing;
+pub mod blocklist;
+pub mod budget;
+pub mod confirmation;
+pub mod consistency;
+pub mod convert_fees;
+pub mod darkpool_status;
+pub mod dead_letter;
+pub mod dual_write;
+pub mod dust;
+pub mod event_source;
+pub mod finality;
+pub mod gas_filter;
+pub mod index_external_fees;
+pub mod index_fee_settings;
 pub mod index_fees;
+pub mod mint_thresholds;
+pub mod outbox;
+pub mod policy;
 pub mod queries;
+pub mod recovery;
 pub mod redeem_fees;
+pub mod rpc_budget;
+pub mod scoring;
+pub mod signer;
+pub mod simulation;
+pub mod slo;
+pub mod snapshot;
+pub mod spam;
+pub mod token_probe;
+pub mod value_fees;
+pub mod wallet_backup;
 
 /// Stores the dependencies needed to index the chain
 pub(crate) struct Indexer {
@@ -17,37 +87,259 @@ pub(crate) struct Indexer {
     pub chain_id: u64,
     /// The chain this indexer targets
     pub chain: Chain,
+    /// The id of the run, under which the run claims fees for redemption
+    pub run_id: Uuid,
+    /// The clock expirations, backoffs, and schedules are measured on
+    pub clock: SharedClock,
+    /// The RNG new wallets' keys are drawn from
+    pub rng: SharedRng,
     /// A client for interacting with the relayer
     pub relayer_client: RelayerClient,
-    /// The Arbitrum client
-    pub arbitrum_client: ArbitrumClient,
-    /// The decryption key
-    pub decryption_key: DecryptionKey,
-    /// A connection to the DB
-    pub db_conn: PgConnection,
-    /// The AWS config
-    pub aws_config: AwsConfig,
+    /// A client for fetching historical prices
+    pub historical_price_client: HistoricalPriceClient,
+    /// The darkpool client for the chain this indexer targets
+    pub darkpool_client: EvmDarkpoolClient,
+    /// The source of the submission key the darkpool client signs with, if
+    /// one was given
+    pub signer: Option<SignerSource>,
+    /// The decryption keys of the kinds of fees swept, configured and
+    /// registered in the DB, as of the last load
+    pub fee_keys: Vec<FeeKey>,
+    /// The configuration of the kinds of fees swept and their keys
+    pub fee_key_args: FeeKeyArgs,
+    /// The kind of the fees paid to the external fee recipient
+    pub external_fee_kind: FeeKind,
+    /// The pool of async DB connections serving the indexer's queries
+    pub db_pool: DbPool,
+    /// The storage backend the DB runs on
+    pub storage: SharedStorage,
+    /// The AWS config and the attribution applied to AWS usage
+    pub aws: AwsContext,
+    /// Timings of the DB queries made during this run
+    pub query_metrics: QueryMetrics,
+    /// Counts of the RPC calls made during this run
+    pub rpc_metrics: RpcMetrics,
+    /// The configuration of the per-run RPC call budget
+    pub rpc_budget: RpcBudgetArgs,
+    /// The redemption policies of each fee source
+    pub redemption_policies: RedemptionPolicies,
+    /// The minimum redemption thresholds of each mint
+    pub mint_thresholds: MintThresholds,
+    /// Whether to simulate redemptions before asking the relayer to execute
+    /// them
+    pub simulate_redemptions: bool,
+    /// The recipient of external match fees to index, if any
+    pub external_fee_recipient: Option<Address>,
+    /// The DB time budget given to each phase of the run, if any
+    pub phase_db_budget: Option<Duration>,
+    /// The configuration of when an indexed block is final
+    pub finality: FinalityArgs,
+    /// The configuration of retries of failed redemptions
+    pub redemption_retry: RedemptionRetryArgs,
+    /// The relayer task queue depth of a wallet above which redemptions into
+    /// it are deferred
+    pub max_task_queue_depth: usize,
+    /// The sizer of redemption batches, tracking redemption latencies
+    pub batch_sizer: BatchSizer,
+    /// The time after which a run's claim on a fee lapses
+    pub fee_claim_ttl: Duration,
+    /// The maximum time to wait for the relayer's price reporters to warm up
+    /// before a redemption pass
+    pub price_warmup: Duration,
+    /// The configuration of the conversion of fee balances to USDC
+    pub conversion: ConversionArgs,
+    /// The dust floors applied to notes at index time
+    pub dust_filter: DustFilter,
+    /// The configuration of the gas cost filter on redemptions
+    pub gas_filter: GasFilterArgs,
+    /// The scorer deciding which fees a redemption pass redeems
+    pub(crate) scorer: SharedScorer,
+    /// The configuration of the spam heuristics
+    pub spam: SpamArgs,
+    /// The HTTP client querying the block explorer
+    pub explorer_client: Client,
+    /// The blocklist of settlement transactions whose fees are not indexed
+    pub blocklist: BlocklistArgs,
+    /// The configuration of the source of historical events
+    pub event_source: EventSourceArgs,
+    /// The adaptive window of blocks indexed per chunk
+    pub log_window: LogWindow,
+    /// The pool of workers decrypting note ciphertexts
+    pub decryption_pool: DecryptionPool,
+    /// The notifier posting to rate limited channels
+    pub notifier: Notifier,
+    /// The expiration policy of dust fees
+    pub abandonment: AbandonmentArgs,
+    /// The tracker in which to open tickets for dead-lettered fees, if any
+    pub issue_tracker: Option<IssueTracker>,
+    /// The configuration of dual-writes during schema migrations
+    pub dual_write: DualWriteArgs,
+    /// The configuration of backups of new sweep wallets
+    pub wallet_backup: WalletBackupArgs,
+    /// The configuration of the guard on the relayer's sweep wallets
+    pub consistency: ConsistencyArgs,
+    /// The configuration of the confirmation of redemptions on-chain
+    pub confirmation: ConfirmationArgs,
+    /// The configuration of the redemption latency SLO
+    pub slo: SloArgs,
+    /// The configuration of the locks serializing operations on each wallet
+    pub wallet_lock: WalletLockArgs,
+    /// The async locks of the wallets operated on by this instance
+    pub wallet_locks: WalletLocks,
+    /// The feature flags gating risky subsystems
+    pub feature_flags: FeatureFlags,
+    /// Whether the sweeper has been asked to shut down
+    pub shutdown: Shutdown,
+    /// The DB time budget of the phase in progress
+    db_budget: Option<DbBudget>,
+    /// Whether a check of the RPC budget has failed in the phase in progress
+    rpc_budget_tripped: AtomicBool,
 }
 
 impl Indexer {
     /// Constructor
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         chain_id: u64,
         chain: Chain,
-        aws_config: AwsConfig,
-        arbitrum_client: ArbitrumClient,
-        decryption_key: DecryptionKey,
-        db_conn: PgConnection,
+        run_id: Uuid,
+        aws: AwsContext,
+        darkpool_client: EvmDarkpoolClient,
+        signer: Option<SignerSource>,
+        fee_key_args: FeeKeyArgs,
+        db_pool: DbPool,
+        storage: SharedStorage,
         relayer_client: RelayerClient,
+        historical_price_client: HistoricalPriceClient,
+        redemption_policies: RedemptionPolicies,
+        mint_thresholds: MintThresholds,
+        simulate_redemptions: bool,
+        external_fee_recipient: Option<Address>,
+        phase_db_budget: Option<Duration>,
+        rpc_budget: RpcBudgetArgs,
+        finality: FinalityArgs,
+        redemption_retry: RedemptionRetryArgs,
+        max_task_queue_depth: usize,
+        batch_sizer: BatchSizer,
+        fee_claim_ttl: Duration,
+        issue_tracker: Option<IssueTracker>,
+        price_warmup: Duration,
+        conversion: ConversionArgs,
+        dust_filter: DustFilter,
+        gas_filter: GasFilterArgs,
+        scorer: ScorerArgs,
+        spam: SpamArgs,
+        blocklist: BlocklistArgs,
+        event_source: EventSourceArgs,
+        log_window: LogWindow,
+        decryption_pool: DecryptionPool,
+        notifier: Notifier,
+        abandonment: AbandonmentArgs,
+        dual_write: DualWriteArgs,
+        wallet_backup: WalletBackupArgs,
+        consistency: ConsistencyArgs,
+        confirmation: ConfirmationArgs,
+        slo: SloArgs,
+        wallet_lock: WalletLockArgs,
+        feature_flags: FeatureFlags,
+        shutdown: Shutdown,
     ) -> Self {
         Indexer {
             chain_id,
             chain,
-            arbitrum_client,
-            decryption_key,
-            db_conn,
+            run_id,
+            clock: system_clock(),
+            rng: SharedRng::from_entropy(),
+            darkpool_client,
+            signer,
+            fee_keys: Vec::new(),
+            external_fee_kind: fee_key_args.external_fee_kind,
+            fee_key_args,
+            db_pool,
+            storage,
             relayer_client,
-            aws_config,
+            historical_price_client,
+            aws,
+            query_metrics: QueryMetrics::default(),
+            rpc_metrics: RpcMetrics::default(),
+            rpc_budget,
+            redemption_policies,
+            mint_thresholds,
+            simulate_redemptions,
+            external_fee_recipient,
+            phase_db_budget,
+            finality,
+            redemption_retry,
+            max_task_queue_depth,
+            batch_sizer,
+            fee_claim_ttl,
+            issue_tracker,
+            price_warmup,
+            conversion,
+            dust_filter,
+            gas_filter,
+            scorer: scorer.build(),
+            spam,
+            explorer_client: Client::new(),
+            blocklist,
+            event_source,
+            log_window,
+            decryption_pool,
+            notifier,
+            abandonment,
+            dual_write,
+            wallet_backup,
+            consistency,
+            confirmation,
+            slo,
+            wallet_lock,
+            wallet_locks: WalletLocks::default(),
+            feature_flags,
+            shutdown,
+            db_budget: None,
+            rpc_budget_tripped: AtomicBool::new(false),
+        }
+    }
+
+    /// Load the decryption keys, merging the configured keys with those
+    /// registered in the DB
+    ///
+    /// Configured keys held in secrets are re-read on each load, so that a
+    /// rotated secret is picked up
+    pub async fn load_fee_keys(&mut self) -> Result<(), FeeSweeperError> {
+        let configured = self.fee_key_args.fee_keys(&self.aws).await?;
+        let registered = self.get_registered_fee_keys().await?;
+        let registered = load_registered_fee_keys(registered, &self.aws).await?;
+        let keys = self
+            .fee_key_args
+            .resolve_fee_keys(&configured, registered)?;
+        if keys.len() != self.fee_keys.len() {
+            info!("sweeping with {} decryption keys", keys.len());
+        }
+
+        self.fee_keys = keys;
+        Ok(())
+    }
+
+    /// Reload the decryption keys, so that a key registered or rotated since
+    /// the last load is picked up
+    ///
+    /// A failed load keeps the keys of the last load, so that a DB or Secrets
+    /// Manager hiccup does not fail a sweep
+    pub async fn refresh_fee_keys(&mut self) {
+        if let Err(e) = self.load_fee_keys().await {
+            warn!("failed to reload decryption keys, keeping the last load: {e}");
         }
     }
+
+    /// The key of the fees paid to a receiver
+    pub(crate) fn fee_key_for_receiver(&self, receiver: &str) -> Result<FeeKey, FeeSweeperError> {
+        self.fee_keys
+            .iter()
+            .find(|fee_key| fee_key.receiver() == receiver)
+            .copied()
+            .ok_or_else(|| {
+                FeeSweeperError::Decryption(format!("no decryption key for receiver {receiver}"))
+            })
+    }
 }
@@ -0,0 +1,92 @@
+//! Delivery of the notifications in the outbox
+//!
+//! Every notice is persisted in the outbox before it is posted, and marked
+//! delivered only once its channel accepts it. A notice that fails to post is
+//! retried after a delay, up to a maximum number of attempts, and a notice
+//! left behind by a crash is delivered by the next run, so notices are
+//! delivered at least once. Delivery failures are logged rather than
+//! returned, a notification should never fail the sweep
+
+use tracing::{error, warn};
+
+use crate::db::models::NewOutboxMessage;
+use crate::Indexer;
+
+impl Indexer {
+    /// Build a notice to enqueue in the same transaction as the change it
+    /// announces, or `None` if the channel has no configured webhook
+    pub(crate) fn outbox_notice(&self, channel: &str, notice: String) -> Option<NewOutboxMessage> {
+        self.notifier
+            .has_channel(channel)
+            .then(|| NewOutboxMessage::new(channel, notice))
+    }
+
+    /// Raise a notice on a channel, enqueueing it in the outbox and delivering
+    /// it along with any other due notices if the channel is not rate limited
+    ///
+    /// A notice that cannot be enqueued, e.g. while the DB is unavailable, is
+    /// posted directly instead
+    pub(crate) async fn notify(&mut self, channel: &str, notice: String) {
+        let message = match self.outbox_notice(channel, notice) {
+            Some(message) => message,
+            None => return,
+        };
+
+        if let Err(e) = self.insert_outbox_message(message.clone()).await {
+            warn!("failed to enqueue notice on {channel}, posting it directly: {e}");
+            let notices = [message.notice];
+            if let Err(e) = self.notifier.post_digest(channel, &notices).await {
+                error!("failed to post notice to {channel}: {e}");
+            }
+            return;
+        }
+        self.deliver_outbox().await;
+    }
+
+    /// Deliver the due notices of every channel that is not rate limited
+    pub(crate) async fn deliver_outbox(&mut self) {
+        for channel in self.notifier.channels() {
+            if self.notifier.wait_time(&channel).is_zero() {
+                self.deliver_channel(&channel).await;
+            }
+        }
+    }
+
+    /// Deliver the due notices of every channel, waiting out each channel's
+    /// rate limit
+    pub async fn flush_outbox(&mut self) {
+        for channel in self.notifier.channels() {
+            tokio::time::sleep(self.notifier.wait_time(&channel)).await;
+            self.deliver_channel(&channel).await;
+        }
+    }
+
+    /// Post a channel's due notices as a single message, recording the
+    /// outcome in the outbox
+    async fn deliver_channel(&mut self, channel: &str) {
+        let max_attempts = self.notifier.max_attempts;
+        let messages = match self.get_due_outbox_messages(channel, max_attempts).await {
+            Ok(messages) if messages.is_empty() => return,
+            Ok(messages) => messages,
+            Err(e) => {
+                error!("failed to read the outbox of {channel}: {e}");
+                return;
+            }
+        };
+
+        let ids: Vec<i32> = messages.iter().map(|m| m.id).collect();
+        let notices: Vec<String> = messages.into_iter().map(|m| m.notice).collect();
+        let res = match self.notifier.post_digest(channel, &notices).await {
+            Ok(()) => self.mark_outbox_delivered(ids).await,
+            Err(e) => {
+                error!("failed to post {} notices to {channel}: {e}", notices.len());
+                let retry_delay = self.notifier.retry_delay;
+                self.record_outbox_failure(ids, &e.to_string(), retry_delay)
+                    .await
+            }
+        };
+        if let Err(e) = res {
+            warn!("failed to record delivery of {channel} notices: {e}");
+        }
+    }
+}
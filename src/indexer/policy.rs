@@ -0,0 +1,202 @@
+//! Redemption policies that differ by the source of a fee
+//!
+//! Fees from different sources arrive in very different sizes and cadences,
+//! so each source may be given its own minimum redemption value and its own
+//! minimum interval between redemptions. A policy for the wildcard source `*`
+//! sets the minimum value of sources without a policy of their own; sources
+//! without any applicable policy are redeemed whenever they are among the most
+//! valuable fees
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Duration;
+
+use bigdecimal::{BigDecimal, FromPrimitive};
+use chrono::DateTime;
+use tracing::info;
+
+use super::queries::FeeValue;
+use crate::error::FeeSweeperError;
+use crate::Indexer;
+
+/// The prefix of the metadata key recording a source's last redemption
+const LAST_REDEMPTION_KEY_PREFIX: &str = "last_redemption_";
+/// The source of the policy applying to sources without a policy of their own
+const WILDCARD_SOURCE: &str = "*";
+
+/// The redemption policy of a single fee source
+///
+/// Parsed from a string of the form
+/// `<source>:<min_value_usd>[:<min_interval_secs>]`
+#[derive(Clone, Debug)]
+pub struct SourcePolicy {
+    /// The fee source the policy applies to
+    pub source: String,
+    /// The minimum USD value of a fee redeemed
+    pub min_value_usd: f64,
+    /// The minimum time between redemptions of the source's fees
+    pub min_interval: Duration,
+}
+
+impl FromStr for SourcePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':');
+        let source = parts.next().unwrap_or_default().trim().to_string();
+        if source.is_empty() {
+            return Err(format!(
+                "expected <source>:<min_value_usd>[:<interval>]: {s}"
+            ));
+        }
+
+        let min_value_usd = parts
+            .next()
+            .ok_or_else(|| format!("missing minimum value: {s}"))?
+            .trim()
+            .parse::<f64>()
+            .map_err(|e| format!("invalid minimum value: {e}"))?;
+        let min_interval_secs = match parts.next() {
+            Some(secs) => secs
+                .trim()
+                .parse::<u64>()
+                .map_err(|e| format!("invalid interval: {e}"))?,
+            None => 0,
+        };
+        if parts.next().is_some() {
+            return Err(format!("unexpected trailing fields: {s}"));
+        }
+
+        Ok(SourcePolicy {
+            source,
+            min_value_usd,
+            min_interval: Duration::from_secs(min_interval_secs),
+        })
+    }
+}
+
+/// The redemption policies of each fee source
+#[derive(Clone, Debug, Default)]
+pub struct RedemptionPolicies {
+    /// The policies, keyed by source
+    policies: HashMap<String, SourcePolicy>,
+    /// The policy of sources without a policy of their own
+    default: Option<SourcePolicy>,
+}
+
+impl RedemptionPolicies {
+    /// Constructor
+    pub fn new(policies: Vec<SourcePolicy>) -> Result<Self, FeeSweeperError> {
+        let mut policy_map = HashMap::new();
+        let mut default = None;
+        for policy in policies {
+            if policy.source == WILDCARD_SOURCE {
+                if !policy.min_interval.is_zero() {
+                    return Err(FeeSweeperError::Config(
+                        "the wildcard policy may not set an interval".to_string(),
+                    ));
+                }
+                if default.replace(policy).is_some() {
+                    return Err(FeeSweeperError::Config(
+                        "duplicate wildcard redemption policy".to_string(),
+                    ));
+                }
+                continue;
+            }
+
+            let source = policy.source.clone();
+            if policy_map.insert(source.clone(), policy).is_some() {
+                return Err(FeeSweeperError::Config(format!(
+                    "duplicate redemption policy for {source}"
+                )));
+            }
+        }
+
+        Ok(Self {
+            policies: policy_map,
+            default,
+        })
+    }
+
+    /// The policy applying to a source, if any
+    fn policy_for(&self, source: &str) -> Option<&SourcePolicy> {
+        self.policies.get(source).or(self.default.as_ref())
+    }
+
+    /// Whether a fee is valuable enough to redeem under its source's policy
+    pub(crate) fn meets_threshold(&self, fee: &FeeValue) -> Result<bool, FeeSweeperError> {
+        let min_value_usd = match self.policy_for(&fee.source) {
+            Some(policy) if policy.min_value_usd > 0. => policy.min_value_usd,
+            _ => return Ok(true),
+        };
+
+        let min_value = BigDecimal::from_f64(min_value_usd).ok_or_else(|| {
+            FeeSweeperError::Policy(format!("invalid minimum value: {min_value_usd}"))
+        })?;
+
+        Ok(fee.usd_value()? >= min_value)
+    }
+
+    /// Whether a fee of a given USD value is valuable enough to redeem under
+    /// its source's policy
+    pub fn meets_usd_threshold(&self, source: &str, value_usd: f64) -> bool {
+        self.policy_for(source)
+            .map_or(true, |policy| value_usd >= policy.min_value_usd)
+    }
+
+    /// The minimum time between redemptions of a source's fees
+    pub fn min_interval(&self, source: &str) -> Duration {
+        self.policies
+            .get(source)
+            .map(|policy| policy.min_interval)
+            .unwrap_or_default()
+    }
+}
+
+impl Indexer {
+    /// Get the sources whose minimum interval since their last redemption has
+    /// not yet elapsed, and whose fees must not be redeemed in this run
+    pub(crate) async fn get_unscheduled_sources(&mut self) -> Result<Vec<String>, FeeSweeperError> {
+        let now = self.clock.now();
+        let policies: Vec<SourcePolicy> = self
+            .redemption_policies
+            .policies
+            .values()
+            .cloned()
+            .collect();
+
+        let mut unscheduled = Vec::new();
+        for policy in policies.into_iter().filter(|p| !p.min_interval.is_zero()) {
+            let key = last_redemption_key(&policy.source);
+            let last = match self.get_metadata(&key).await? {
+                Some(value) => value
+                    .parse::<i64>()
+                    .ok()
+                    .and_then(|ts| DateTime::from_timestamp(ts, 0 /* nsecs */)),
+                None => None,
+            };
+
+            let elapsed = last.and_then(|last| (now - last).to_std().ok());
+            if elapsed.is_some_and(|elapsed| elapsed < policy.min_interval) {
+                info!("{} fees are not scheduled for redemption", policy.source);
+                unscheduled.push(policy.source);
+            }
+        }
+
+        Ok(unscheduled)
+    }
+
+    /// Record that a source's fees were redeemed in this run
+    pub(crate) async fn record_source_redemption(
+        &mut self,
+        source: &str,
+    ) -> Result<(), FeeSweeperError> {
+        let now = self.clock.now().timestamp().to_string();
+        self.set_metadata(&last_redemption_key(source), now).await
+    }
+}
+
+/// The metadata key recording a source's last redemption
+fn last_redemption_key(source: &str) -> String {
+    format!("{LAST_REDEMPTION_KEY_PREFIX}{source}")
+}
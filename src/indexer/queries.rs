@@ -1,29 +1,101 @@
 //! Groups query logic for the indexer
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
 
-use bigdecimal::BigDecimal;
+use bigdecimal::{BigDecimal, FromPrimitive};
+use chrono::NaiveDateTime;
 use diesel::define_sql_function;
-use diesel::deserialize::Queryable;
-use diesel::deserialize::QueryableByName;
-use diesel::sql_query;
+use diesel::pg::expression::extensions::IntervalDsl;
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
 use diesel::sql_types::SingleValue;
-use diesel::sql_types::{Array, Integer, Nullable, Numeric, Text};
+use diesel::sql_types::{Array, Double, Integer, Nullable, Text, Uuid as SqlUuid};
 use diesel::PgArrayExpressionMethods;
-use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use diesel::PgSortExpressionMethods;
+use diesel::{
+    BoolExpressionMethods, ExpressionMethods, NullableExpressionMethods, QueryDsl, SelectableHelper,
+};
+use diesel::{OptionalExtension, QueryResult};
+use diesel_async::scoped_futures::{ScopedBoxFuture, ScopedFutureExt};
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use ethers::types::TransactionReceipt;
+use num_bigint::BigInt;
+use renegade_common::types::token::Token;
 use renegade_constants::MAX_BALANCES;
-use renegade_util::raw_err_str;
+use tracing::{info, warn};
+use uuid::Uuid;
 
+use super::budget::DbError;
+use super::dead_letter::{dead_letter_details, RedemptionRetryArgs};
+use super::slo::SloWindowCounts;
+use super::snapshot::FeeSnapshot;
+use crate::clock::Clock;
+use crate::db::audit::MINT_QUARANTINE_EVENT;
+use crate::db::gas_ledger::{new_gas_spend, GasPurpose};
+use crate::db::leases::ACQUIRE_LEASE_QUERY;
 use crate::db::models::WalletMetadata;
-use crate::db::models::{Metadata, NewFee};
+use crate::db::models::{
+    AuditEvent, FeatureFlag, Fee, InstanceLease, Metadata, MintQuarantine, NewAuditEvent, NewFee,
+    NewFeeSetting, NewFeeStatusChange, NewMintQuarantine, NewOutboxMessage, NewRunSummary,
+    NewTaskFailure, NewTokenProbe, OutboxMessage, PriceRoute, RedemptionState, RegisteredFeeKey,
+    TokenProbe,
+};
+use crate::db::outbox::enqueue_notice;
 use crate::db::schema::{
+    admin_access_log, audit_log, feature_flags, fee_decryption_keys,
+    fee_settings_history::dsl::fee_settings_history as fee_settings_table,
+    fee_status_changes::dsl::fee_status_changes as status_changes_table,
     fees::dsl::{
-        fees as fees_table, mint as mint_col, redeemed as redeemed_col, tx_hash as tx_hash_col,
+        abandoned_at as abandoned_at_col, amount as amount_col, block_number as block_number_col,
+        block_timestamp as block_timestamp_col, claimed_by as claimed_by_col,
+        claimed_until as claimed_until_col, dead_lettered_at as dead_lettered_at_col,
+        dust as dust_col, fees as fees_table, id as id_col, indexed_at as indexed_at_col,
+        last_attempted_at as last_attempted_at_col,
+        last_redemption_error as last_redemption_error_col, mint as mint_col,
+        next_redemption_at as next_redemption_at_col, queue_priority as queue_priority_col,
+        receiver as receiver_col, redeemed as redeemed_col,
+        redemption_attempts as redemption_attempts_col,
+        redemption_started_at as redemption_started_at_col,
+        redemption_state as redemption_state_col,
+        redemption_state_changed_at as redemption_state_changed_at_col,
+        redemption_task_id as redemption_task_id_col,
+        redemptions_started as redemptions_started_col, skip_reason as skip_reason_col,
+        skipped_at as skipped_at_col, source as source_col, status as status_col,
+        tx_hash as tx_hash_col, usd_price as usd_price_col, usd_value as usd_value_col,
+        valuation_attempted_at as valuation_attempted_at_col,
     },
+    fund_movements, gas_spend,
     indexing_metadata::dsl::{
         indexing_metadata as metadata_table, key as metadata_key, value as metadata_value,
     },
-    wallets::dsl::{mints as managed_mints_col, wallets as wallet_table},
+    instance_leases, mint_quarantines,
+    outbox::dsl::{
+        attempts as outbox_attempts_col, channel as outbox_channel_col,
+        delivered_at as delivered_at_col, id as outbox_id_col, last_error as last_outbox_error_col,
+        next_attempt_at as next_attempt_at_col, outbox as outbox_table,
+    },
+    price_routes, run_summaries, task_failures, token_probes,
+    wallets::dsl::{
+        diverged_since as diverged_since_col, fee_kind as wallet_fee_kind_col, id as wallet_id_col,
+        mints as managed_mints_col, needs_refresh as needs_refresh_col, wallets as wallet_table,
+    },
+};
+use crate::error::FeeSweeperError;
+use crate::fee_keys::FeeKind;
+use crate::invariants::MintLedger;
+use crate::notifications::ALERTS_CHANNEL;
+use crate::telemetry::{
+    CLAIM_FEES_QUERY, INSERT_AUDIT_QUERY, INSERT_FEE_QUERY, INSERT_FEE_SETTING_QUERY,
+    INSERT_FUND_MOVEMENT_QUERY, INSERT_GAS_SPEND_QUERY, INSERT_OUTBOX_QUERY,
+    INSERT_QUARANTINE_QUERY, INSERT_RUN_SUMMARY_QUERY, INSERT_TASK_FAILURE_QUERY,
+    INSERT_TOKEN_PROBE_QUERY, INSERT_WALLET_QUERY, LEASE_QUERY, PRUNE_ACCESS_LOG_QUERY,
+    SELECT_AUDIT_QUERY, SELECT_FEATURE_FLAGS_QUERY, SELECT_FEE_KEYS_QUERY, SELECT_FEE_QUERY,
+    SELECT_FEE_STATUS_QUERY, SELECT_METADATA_QUERY, SELECT_MINT_LEDGER_QUERY,
+    SELECT_MINT_STATS_QUERY, SELECT_OUTBOX_QUERY, SELECT_PRICE_ROUTES_QUERY,
+    SELECT_QUARANTINE_QUERY, SELECT_REDEEMING_QUERY, SELECT_SLO_QUERY, SELECT_SNAPSHOT_QUERY,
+    SELECT_TOKEN_PROBE_QUERY, SELECT_UNREDEEMED_QUERY, SELECT_UNVALUED_QUERY, SELECT_WALLET_QUERY,
+    UPDATE_METADATA_QUERY, UPDATE_OUTBOX_QUERY, UPDATE_SKIP_QUERY, UPDATE_STATUS_QUERY,
+    UPDATE_VALUATION_ATTEMPT_QUERY, UPDATE_VALUATION_QUERY, UPDATE_WALLET_QUERY,
 };
 use crate::Indexer;
 
@@ -31,6 +103,12 @@ use super::redeem_fees::MAX_FEES_REDEEMED;
 
 /// The metadata key for the last indexed block
 pub(crate) const LAST_INDEXED_BLOCK_KEY: &str = "latest_block";
+/// The number of fees fetched per page of the redemption scan
+const REDEMPTION_SCAN_PAGE_SIZE: i64 = MAX_FEES_REDEEMED as i64;
+/// The delay before the first retry of a transaction, doubled on each retry
+const TRANSACTION_RETRY_BASE_DELAY: Duration = Duration::from_millis(50);
+/// Restores a pooled connection's statement timeout to the server default
+const RESET_STATEMENT_TIMEOUT_QUERY: &str = "RESET statement_timeout";
 
 // Define the `array_length` function
 define_sql_function! {
@@ -47,19 +125,158 @@ define_sql_function! {
 // | Query Types |
 // ---------------
 
-/// A sub-query of the most valuable fees to be redeemed
-#[derive(Debug, Queryable, QueryableByName)]
+/// A fee selected for redemption, along with its value
+#[derive(Debug)]
 pub(crate) struct FeeValue {
     /// The tx hash of the fee
-    #[sql_type = "Text"]
     pub tx_hash: String,
     /// The mint of the fee
-    #[sql_type = "Text"]
     pub mint: String,
-    /// The value of the fee
-    #[sql_type = "Numeric"]
-    #[allow(unused)]
+    /// The source of the fee
+    pub source: String,
+    /// The amount of the fee, in raw units of the mint
+    pub amount: BigDecimal,
+    /// The value of the fee, in raw units of the mint
     pub value: BigDecimal,
+    /// The operator's override of the fee's place in the redemption queue
+    pub queue_priority: i32,
+}
+
+impl FeeValue {
+    /// Value a fee at the given price of its mint
+    fn new(fee: Fee, price: &BigDecimal) -> Self {
+        let value = &fee.amount * price;
+        Self {
+            tx_hash: fee.tx_hash,
+            mint: fee.mint,
+            source: fee.source,
+            amount: fee.amount,
+            value,
+            queue_priority: fee.queue_priority,
+        }
+    }
+
+    /// Whether an operator pushed the fee to the back of the queue
+    pub fn pushed_back(&self) -> bool {
+        self.queue_priority < 0
+    }
+
+    /// The value of the fee in USD
+    pub fn usd_value(&self) -> Result<BigDecimal, FeeSweeperError> {
+        let decimals = Token::from_addr(&self.mint)
+            .get_decimals()
+            .ok_or_else(|| FeeSweeperError::Other(format!("unknown decimals for {}", self.mint)))?;
+        let unit = BigDecimal::new(BigInt::from(1), decimals as i64);
+        Ok(&self.value * unit)
+    }
+}
+
+/// A position in a mint's unredeemed fees, ordered by `(amount, id)` descending
+#[derive(Clone, Debug)]
+pub(crate) struct FeeCursor {
+    /// The amount of the last fee seen
+    pub amount: BigDecimal,
+    /// The id of the last fee seen
+    pub id: i32,
+}
+
+/// The claims on fees as seen by a run at a point in time
+///
+/// Claims are stamped and checked against the indexer's clock rather than the
+/// DB's, so that a claim's lapse follows the clock the run is given
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ClaimWindow {
+    /// The run taking and holding claims
+    run_id: Uuid,
+    /// The time at which the claims are seen
+    now: NaiveDateTime,
+    /// How long a claim taken now lasts
+    ttl: Duration,
+}
+
+impl ClaimWindow {
+    /// The claims seen by a run at the clock's current time
+    pub fn new(run_id: Uuid, clock: &dyn Clock, ttl: Duration) -> Self {
+        Self {
+            run_id,
+            now: clock.naive_now(),
+            ttl,
+        }
+    }
+
+    /// The time at which a claim taken now lapses
+    pub fn expires_at(&self) -> NaiveDateTime {
+        chrono::Duration::from_std(self.ttl)
+            .ok()
+            .and_then(|ttl| self.now.checked_add_signed(ttl))
+            .unwrap_or(NaiveDateTime::MAX)
+    }
+
+    /// Whether a fee with the given claim is free to the run: unclaimed, held
+    /// by the run itself, or claimed by another run whose claim has lapsed
+    pub fn is_claimable(
+        &self,
+        claimed_by: Option<Uuid>,
+        claimed_until: Option<NaiveDateTime>,
+    ) -> bool {
+        claimed_by == Some(self.run_id) || !claimed_until.is_some_and(|until| until >= self.now)
+    }
+}
+
+/// A keyset-paginated scan over a single mint's unredeemed fees
+struct MintScan {
+    /// The mint being scanned
+    mint: String,
+    /// The price of the mint
+    price: BigDecimal,
+    /// The fees fetched but not yet taken from the scan
+    buffer: VecDeque<Fee>,
+    /// The position of the last fetched fee
+    cursor: Option<FeeCursor>,
+    /// Whether the last page fetched was the final page
+    exhausted: bool,
+}
+
+impl MintScan {
+    /// Begin a scan over a mint's fees
+    fn new(mint: String, price: BigDecimal) -> Self {
+        Self {
+            mint,
+            price,
+            buffer: VecDeque::new(),
+            cursor: None,
+            exhausted: false,
+        }
+    }
+
+    /// Whether the scan must fetch a page before its next fee can be taken
+    fn needs_page(&self) -> bool {
+        self.buffer.is_empty() && !self.exhausted
+    }
+
+    /// Add a fetched page to the scan
+    fn push_page(&mut self, page: Vec<Fee>) {
+        self.exhausted = (page.len() as i64) < REDEMPTION_SCAN_PAGE_SIZE;
+        if let Some(last) = page.last() {
+            self.cursor = Some(FeeCursor {
+                amount: last.amount.clone(),
+                id: last.id,
+            });
+        }
+
+        self.buffer.extend(page);
+    }
+
+    /// The value of the next fee in the scan
+    fn peek_value(&self) -> Option<BigDecimal> {
+        self.buffer.front().map(|fee| &fee.amount * &self.price)
+    }
+
+    /// Take the next fee from the scan
+    fn pop(&mut self) -> Option<FeeValue> {
+        let fee = self.buffer.pop_front()?;
+        Some(FeeValue::new(fee, &self.price))
+    }
 }
 
 // -------------------------
@@ -67,114 +284,1062 @@ pub(crate) struct FeeValue {
 // -------------------------
 
 impl Indexer {
+    /// Run a query on a pooled DB connection, recording its latency under the
+    /// given query type and charging it to the current phase's DB budget
+    ///
+    /// The query runs under a statement timeout of the phase's remaining
+    /// budget; a query cut off by the timeout fails as exceeding the budget
+    async fn timed_query<'a, T, F>(&mut self, query: &'static str, f: F) -> Result<T, DbError>
+    where
+        T: Send + 'a,
+        F: for<'c> FnOnce(&'c mut AsyncPgConnection) -> ScopedBoxFuture<'a, 'c, QueryResult<T>>,
+    {
+        self.check_db_budget()?;
+        let mut conn = self
+            .db_pool
+            .get()
+            .await
+            .map_err(|e| DbError::Pool(e.to_string()))?;
+        let timeout = self.remaining_db_budget();
+        if let Some(timeout) = timeout {
+            // A zero timeout disables the timeout, so at least a millisecond
+            // is set
+            let timeout_ms = timeout.as_millis().max(1);
+            diesel::sql_query(format!("SET statement_timeout = {timeout_ms}"))
+                .execute(&mut *conn)
+                .await?;
+        }
+
+        let start = Instant::now();
+        let res = f(&mut *conn).await;
+        let elapsed = start.elapsed();
+        self.query_metrics.record(query, elapsed);
+        self.charge_db_budget(elapsed);
+
+        // The connection returns to the pool, so its timeout is reset for the
+        // next query taking it
+        if timeout.is_some() {
+            diesel::sql_query(RESET_STATEMENT_TIMEOUT_QUERY)
+                .execute(&mut *conn)
+                .await?;
+        }
+        if res.is_err() {
+            self.check_db_budget()?;
+        }
+
+        Ok(res?)
+    }
+
+    /// Run a transaction as a timed query, retrying it from the start while
+    /// it fails with an error the storage backend deems retryable
+    ///
+    /// The transaction may run several times, so it is built anew from the
+    /// closure on each attempt
+    async fn timed_transaction<'a, T, F>(&mut self, query: &'static str, f: F) -> Result<T, DbError>
+    where
+        T: Send + 'a,
+        F: for<'c> Fn(&'c mut AsyncPgConnection) -> ScopedBoxFuture<'a, 'c, QueryResult<T>>,
+    {
+        let mut retries = 0;
+        loop {
+            match self.timed_query(query, &f).await {
+                Err(DbError::Query(e))
+                    if self.storage.is_retryable(&e)
+                        && retries < self.storage.max_transaction_retries() =>
+                {
+                    retries += 1;
+                    warn!("retrying {query} transaction after a retryable failure: {e}");
+                    let delay = TRANSACTION_RETRY_BASE_DELAY * 2u32.pow(retries - 1);
+                    self.clock.sleep(delay).await;
+                }
+                res => return res,
+            }
+        }
+    }
+
     // ------------------
     // | Metadata Table |
     // ------------------
 
     /// Get the latest block number
-    pub(crate) fn get_latest_block(&mut self) -> Result<u64, String> {
-        let entry = metadata_table
-            .filter(metadata_key.eq(LAST_INDEXED_BLOCK_KEY))
-            .limit(1)
-            .load(&mut self.db_conn)
-            .map(|res: Vec<Metadata>| res[0].clone())
-            .map_err(raw_err_str!("failed to query latest block: {}"))?;
+    pub(crate) async fn get_latest_block(&mut self) -> Result<u64, FeeSweeperError> {
+        let entry = self
+            .timed_query(SELECT_METADATA_QUERY, |conn| {
+                async move {
+                    metadata_table
+                        .filter(metadata_key.eq(LAST_INDEXED_BLOCK_KEY))
+                        .limit(1)
+                        .load::<Metadata>(conn)
+                        .await
+                }
+                .scope_boxed()
+            })
+            .await
+            .map(|res| res[0].clone())
+            .map_err(FeeSweeperError::query("failed to query latest block"))?;
 
         entry
             .value
             .parse::<u64>()
-            .map_err(raw_err_str!("failed to parse latest block: {}"))
+            .map_err(FeeSweeperError::db("failed to parse latest block"))
     }
 
-    /// Update the latest block number
-    pub(crate) fn update_latest_block(&mut self, block_number: u64) -> Result<(), String> {
-        let block_string = block_number.to_string();
-        diesel::update(metadata_table.find(LAST_INDEXED_BLOCK_KEY))
-            .set(metadata_value.eq(block_string))
-            .execute(&mut self.db_conn)
-            .map_err(raw_err_str!("failed to update latest block: {}"))
-            .map(|_| ())
+    /// Get a metadata value, if it is set
+    pub(crate) async fn get_metadata(
+        &mut self,
+        key: &str,
+    ) -> Result<Option<String>, FeeSweeperError> {
+        let entries = self
+            .timed_query(SELECT_METADATA_QUERY, |conn| {
+                async move {
+                    metadata_table
+                        .filter(metadata_key.eq(key))
+                        .limit(1)
+                        .load::<Metadata>(conn)
+                        .await
+                }
+                .scope_boxed()
+            })
+            .await
+            .map_err(FeeSweeperError::query("failed to query metadata"))?;
+
+        Ok(entries.into_iter().next().map(|entry| entry.value))
+    }
+
+    /// Set a metadata value, creating it if it does not exist
+    pub(crate) async fn set_metadata(
+        &mut self,
+        key: &str,
+        value: String,
+    ) -> Result<(), FeeSweeperError> {
+        self.timed_query(UPDATE_METADATA_QUERY, |conn| {
+            async move {
+                diesel::insert_into(metadata_table)
+                    .values((metadata_key.eq(key), metadata_value.eq(&value)))
+                    .on_conflict(metadata_key)
+                    .do_update()
+                    .set(metadata_value.eq(&value))
+                    .execute(conn)
+                    .await
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query("failed to set metadata"))
+        .map(|_| ())
     }
 
     // --------------
     // | Fees Table |
     // --------------
 
-    /// Insert a fee into the fees table
-    pub(crate) fn insert_fee(&mut self, fee: NewFee) -> Result<(), String> {
-        diesel::insert_into(fees_table)
-            .values(vec![fee])
-            .execute(&mut self.db_conn)
-            .map_err(raw_err_str!("failed to insert fee: {}"))
-            .map(|_| ())
+    /// Insert the fees indexed in a chunk of blocks and, unless backfilling,
+    /// advance the latest block to the end of the chunk, in one transaction
+    ///
+    /// An interrupted pass then resumes after the last chunk committed, with
+    /// none of that chunk's fees lost or inserted twice. When backfilling,
+    /// fees already indexed are ignored
+    pub(crate) async fn commit_indexed_chunk(
+        &mut self,
+        fees: Vec<NewFee>,
+        chunk_end: u64,
+        backfill: bool,
+    ) -> Result<(), FeeSweeperError> {
+        let block_string = chunk_end.to_string();
+        self.timed_transaction(INSERT_FEE_QUERY, |conn| {
+            let (fees, block_string) = (fees.clone(), block_string.clone());
+            conn.transaction(move |conn| {
+                async move {
+                    if !fees.is_empty() {
+                        let insert = diesel::insert_into(fees_table).values(fees);
+                        match backfill {
+                            true => insert.on_conflict_do_nothing().execute(conn).await?,
+                            false => insert.execute(conn).await?,
+                        };
+                    }
+                    if !backfill {
+                        diesel::update(metadata_table.find(LAST_INDEXED_BLOCK_KEY))
+                            .set(metadata_value.eq(block_string))
+                            .execute(conn)
+                            .await?;
+                    }
+
+                    Ok::<_, DieselError>(())
+                }
+                .scope_boxed()
+            })
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query("failed to commit indexed fees"))
+    }
+
+    /// Insert a fee, ignoring fees already indexed
+    pub(crate) async fn insert_fee_if_new(&mut self, fee: NewFee) -> Result<(), FeeSweeperError> {
+        self.timed_query(INSERT_FEE_QUERY, |conn| {
+            async move {
+                diesel::insert_into(fees_table)
+                    .values(vec![fee])
+                    .on_conflict_do_nothing()
+                    .execute(conn)
+                    .await
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query("failed to insert fee"))
+        .map(|_| ())
+    }
+
+    /// Whether the fee settled in a tx is already indexed
+    pub(crate) async fn fee_indexed(&mut self, tx_hash: &str) -> Result<bool, FeeSweeperError> {
+        self.timed_query(SELECT_FEE_QUERY, |conn| {
+            async move {
+                diesel::select(diesel::dsl::exists(
+                    fees_table.filter(tx_hash_col.eq(tx_hash)),
+                ))
+                .get_result(conn)
+                .await
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query("failed to query fee"))
     }
 
     /// Get all mints that have unredeemed fees
-    pub(crate) fn get_unredeemed_fee_mints(&mut self) -> Result<Vec<String>, String> {
-        let mints = fees_table
-            .select(mint_col)
+    pub(crate) async fn get_unredeemed_fee_mints(
+        &mut self,
+    ) -> Result<Vec<String>, FeeSweeperError> {
+        self.timed_query(SELECT_UNREDEEMED_QUERY, |conn| {
+            async move {
+                fees_table
+                    .select(mint_col)
+                    .filter(redeemed_col.eq(false))
+                    .filter(dust_col.eq(false))
+                    .distinct()
+                    .load(conn)
+                    .await
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query("failed to query unredeemed fees"))
+    }
+
+    /// Get the settlement time of the oldest unredeemed fee to the receiver in
+    /// any of the given mints
+    ///
+    /// A fee whose settlement time has not yet been backfilled is dated by the
+    /// time it was indexed, so that it still counts towards a stall
+    pub(crate) async fn get_oldest_unredeemed_fee_time(
+        &mut self,
+        mints: Vec<String>,
+        receiver: &str,
+    ) -> Result<Option<NaiveDateTime>, FeeSweeperError> {
+        self.timed_query(SELECT_UNREDEEMED_QUERY, |conn| {
+            async move {
+                fees_table
+                    .filter(redeemed_col.eq(false))
+                    .filter(dust_col.eq(false))
+                    .filter(receiver_col.eq(receiver))
+                    .filter(mint_col.eq_any(mints))
+                    .select(diesel::dsl::min(coalesce(
+                        block_timestamp_col,
+                        indexed_at_col,
+                    )))
+                    .first(conn)
+                    .await
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query(
+            "failed to query oldest unredeemed fee",
+        ))
+    }
+
+    /// Get the settlement time of the oldest fee in a mint, and the number of
+    /// the mint's fees that have been priced
+    pub(crate) async fn get_mint_price_coverage(
+        &mut self,
+        mint: &str,
+    ) -> Result<(Option<NaiveDateTime>, i64), FeeSweeperError> {
+        self.timed_query(SELECT_MINT_STATS_QUERY, |conn| {
+            async move {
+                fees_table
+                    .filter(mint_col.eq(mint))
+                    .select((
+                        diesel::dsl::min(block_timestamp_col),
+                        diesel::dsl::count(usd_price_col),
+                    ))
+                    .first(conn)
+                    .await
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query(
+            "failed to query mint price coverage",
+        ))
+    }
+
+    /// Snapshot the aggregates of all fees
+    pub(crate) async fn get_fee_snapshot(&mut self) -> Result<FeeSnapshot, FeeSweeperError> {
+        self.timed_query(SELECT_SNAPSHOT_QUERY, |conn| {
+            async move {
+                let (unredeemed_count, unredeemed_value): (i64, Option<BigDecimal>) = fees_table
+                    .filter(redeemed_col.eq(false))
+                    .filter(dead_lettered_at_col.is_null())
+                    .filter(abandoned_at_col.is_null())
+                    .select((diesel::dsl::count(id_col), diesel::dsl::sum(usd_value_col)))
+                    .first(conn)
+                    .await?;
+                let (redeemed_count, redeemed_value): (i64, Option<BigDecimal>) = fees_table
+                    .filter(redeemed_col.eq(true))
+                    .select((diesel::dsl::count(id_col), diesel::dsl::sum(usd_value_col)))
+                    .first(conn)
+                    .await?;
+                let dead_lettered_count = fees_table
+                    .filter(redeemed_col.eq(false))
+                    .filter(dead_lettered_at_col.is_not_null())
+                    .count()
+                    .get_result(conn)
+                    .await?;
+                let abandoned_count = fees_table
+                    .filter(abandoned_at_col.is_not_null())
+                    .count()
+                    .get_result(conn)
+                    .await?;
+
+                Ok(FeeSnapshot {
+                    unredeemed_count,
+                    unredeemed_value: unredeemed_value.unwrap_or_default(),
+                    redeemed_count,
+                    redeemed_value: redeemed_value.unwrap_or_default(),
+                    dead_lettered_count,
+                    abandoned_count,
+                })
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query("failed to snapshot fees"))
+    }
+
+    /// Count the fees that fell due for redemption within a window ending
+    /// now, and those of them redeemed by the time they fell due
+    ///
+    /// A fee falls due once the target latency has elapsed since it was
+    /// indexed. Dust, skipped, and abandoned fees are never due
+    pub(crate) async fn get_slo_window_counts(
+        &mut self,
+        target: Duration,
+        window: Duration,
+    ) -> Result<SloWindowCounts, FeeSweeperError> {
+        let target_secs = target.as_secs() as i64;
+        let window_secs = window.as_secs() as i64;
+        self.timed_query(SELECT_SLO_QUERY, |conn| {
+            async move {
+                let due_fees = fees_table
+                    .filter(dust_col.eq(false))
+                    .filter(skipped_at_col.is_null())
+                    .filter(abandoned_at_col.is_null())
+                    .filter(
+                        indexed_at_col.gt(diesel::dsl::now - (window_secs + target_secs).seconds()),
+                    )
+                    .filter(indexed_at_col.le(diesel::dsl::now - target_secs.seconds()));
+                let due = due_fees.clone().count().get_result(conn).await?;
+                let met = due_fees
+                    .filter(redemption_state_col.eq(RedemptionState::Redeemed.as_str()))
+                    .filter(
+                        redemption_state_changed_at_col.le(indexed_at_col + target_secs.seconds()),
+                    )
+                    .count()
+                    .get_result(conn)
+                    .await?;
+
+                Ok(SloWindowCounts { due, met })
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query(
+            "failed to count fees due within the SLO window",
+        ))
+    }
+
+    /// Get the transaction of the most recent fee in a mint from a source
+    pub(crate) async fn get_latest_fee_tx(
+        &mut self,
+        mint: &str,
+        source: &str,
+    ) -> Result<Option<String>, FeeSweeperError> {
+        self.timed_query(SELECT_MINT_STATS_QUERY, |conn| {
+            async move {
+                fees_table
+                    .filter(mint_col.eq(mint))
+                    .filter(source_col.eq(source))
+                    .order(id_col.desc())
+                    .select(tx_hash_col)
+                    .first(conn)
+                    .await
+                    .optional()
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query("failed to query latest fee"))
+    }
+
+    /// Mark a fee as redeemed, recording the change in the fee's status history
+    ///
+    /// A notice of the redemption, if given, is enqueued in the outbox in the
+    /// same transaction
+    pub(crate) async fn mark_fee_as_redeemed(
+        &mut self,
+        tx_hash: &str,
+        notice: Option<NewOutboxMessage>,
+    ) -> Result<(), FeeSweeperError> {
+        let state = RedemptionState::Redeemed;
+        let change = NewFeeStatusChange::new(tx_hash, state.fee_status());
+        let now = self.clock.naive_now();
+        self.timed_transaction(UPDATE_STATUS_QUERY, |conn| {
+            let change = change.clone();
+            let notice = notice.clone();
+            conn.transaction(move |conn| {
+                async move {
+                    transition_redemption_state(conn, tx_hash, state, now).await?;
+                    diesel::update(fees_table.filter(tx_hash_col.eq(tx_hash)))
+                        .set(redemption_started_at_col.eq(None::<NaiveDateTime>))
+                        .execute(conn)
+                        .await?;
+                    if let Some(notice) = notice {
+                        enqueue_notice(conn, notice).await?;
+                    }
+                    diesel::insert_into(status_changes_table)
+                        .values(vec![change])
+                        .execute(conn)
+                        .await
+                }
+                .scope_boxed()
+            })
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query("failed to mark fee as redeemed"))
+        .map(|_| ())
+    }
+
+    /// Mark a fee as eligible for redemption, once a redemption pass has
+    /// screened it
+    pub(crate) async fn mark_fee_eligible(&mut self, tx_hash: &str) -> Result<(), FeeSweeperError> {
+        let now = self.clock.naive_now();
+        self.timed_query(UPDATE_STATUS_QUERY, |conn| {
+            async move {
+                let state = RedemptionState::Eligible;
+                transition_redemption_state(conn, tx_hash, state, now).await
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query("failed to mark fee eligible"))
+    }
+
+    /// Record that a redemption of a fee has started, moving the fee in
+    /// flight, or clear the record once a redemption finishes without
+    /// redeeming the fee, failing it
+    pub(crate) async fn set_fee_redemption_started(
+        &mut self,
+        tx_hash: &str,
+        started_at: Option<NaiveDateTime>,
+    ) -> Result<(), FeeSweeperError> {
+        let state = match started_at {
+            Some(_) => RedemptionState::InFlight,
+            None => RedemptionState::Failed,
+        };
+        let now = self.clock.naive_now();
+        self.timed_transaction(UPDATE_STATUS_QUERY, |conn| {
+            conn.transaction(move |conn| {
+                async move {
+                    transition_redemption_state(conn, tx_hash, state, now).await?;
+                    let fee = fees_table.filter(tx_hash_col.eq(tx_hash));
+                    match started_at {
+                        Some(_) => {
+                            diesel::update(fee)
+                                .set((
+                                    redemption_started_at_col.eq(started_at),
+                                    redemptions_started_col.eq(redemptions_started_col + 1),
+                                    last_attempted_at_col.eq(started_at),
+                                ))
+                                .execute(conn)
+                                .await?
+                        }
+                        None => {
+                            diesel::update(fee)
+                                .set((
+                                    redemption_started_at_col.eq(started_at),
+                                    redemption_task_id_col.eq(None::<Uuid>),
+                                ))
+                                .execute(conn)
+                                .await?
+                        }
+                    };
+                    Ok(())
+                }
+                .scope_boxed()
+            })
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query("failed to set fee redemption start"))
+    }
+
+    /// Record the relayer task redeeming a fee, so that a run killed while
+    /// awaiting the task leaves it for orphan recovery to check
+    pub(crate) async fn set_fee_redemption_task(
+        &mut self,
+        tx_hash: &str,
+        task_id: Uuid,
+    ) -> Result<(), FeeSweeperError> {
+        self.timed_query(UPDATE_STATUS_QUERY, |conn| {
+            async move {
+                diesel::update(fees_table.filter(tx_hash_col.eq(tx_hash)))
+                    .set(redemption_task_id_col.eq(task_id))
+                    .execute(conn)
+                    .await
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query(
+            "failed to record fee redemption task",
+        ))
+        .map(|_| ())
+    }
+
+    /// Record why a redemption pass skipped a fee, or clear the record once a
+    /// pass no longer skips it
+    pub(crate) async fn set_fee_skip_reason(
+        &mut self,
+        tx_hash: &str,
+        reason: Option<String>,
+    ) -> Result<(), FeeSweeperError> {
+        let skipped_at = reason.as_ref().map(|_| self.clock.naive_now());
+        self.timed_query(UPDATE_SKIP_QUERY, |conn| {
+            async move {
+                // Clearing only touches fees with a record, sparing a write per fee
+                let fees = fees_table.filter(tx_hash_col.eq(tx_hash));
+                match reason {
+                    Some(reason) => {
+                        diesel::update(fees)
+                            .set((skipped_at_col.eq(skipped_at), skip_reason_col.eq(reason)))
+                            .execute(conn)
+                            .await
+                    }
+                    None => {
+                        diesel::update(fees.filter(skip_reason_col.is_not_null()))
+                            .set((
+                                skipped_at_col.eq(None::<NaiveDateTime>),
+                                skip_reason_col.eq(None::<String>),
+                            ))
+                            .execute(conn)
+                            .await
+                    }
+                }
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query(
+            "failed to record skipped redemption",
+        ))
+        .map(|_| ())
+    }
+
+    /// Record a failed attempt to redeem a fee and the error it failed with
+    ///
+    /// The fee is dead-lettered once it has failed the maximum number of
+    /// attempts, and otherwise backed off until its next retry. An alert of
+    /// the dead letter is enqueued in the outbox in the same transaction
+    ///
+    /// Returns the fee after the update
+    pub(crate) async fn record_redemption_failure(
+        &mut self,
+        tx_hash: &str,
+        error: &str,
+        retry: &RedemptionRetryArgs,
+    ) -> Result<Fee, FeeSweeperError> {
+        let alert = self.notifier.has_channel(ALERTS_CHANNEL);
+        let now = self.clock.naive_now();
+        self.timed_transaction(UPDATE_STATUS_QUERY, |conn| {
+            conn.transaction(move |conn| {
+                async move {
+                    let state = RedemptionState::Failed;
+                    transition_redemption_state(conn, tx_hash, state, now).await?;
+                    let attempts: i32 = diesel::update(fees_table.filter(tx_hash_col.eq(tx_hash)))
+                        .set((
+                            redemption_attempts_col.eq(redemption_attempts_col + 1),
+                            redemption_started_at_col.eq(None::<NaiveDateTime>),
+                            last_redemption_error_col.eq(error),
+                        ))
+                        .returning(redemption_attempts_col)
+                        .get_result(conn)
+                        .await?;
+
+                    let dead_lettered = attempts as u32 >= retry.max_redemption_attempts;
+                    if dead_lettered {
+                        let state = RedemptionState::Exhausted;
+                        transition_redemption_state(conn, tx_hash, state, now).await?;
+                        if alert {
+                            let details = dead_letter_details(attempts);
+                            let notice = format!("fee from tx {tx_hash} dead-lettered: {details}");
+                            enqueue_notice(conn, NewOutboxMessage::new(ALERTS_CHANNEL, notice))
+                                .await?;
+                        }
+                    } else {
+                        let retry_at = retry.retry_at(attempts as u32, now);
+                        diesel::update(fees_table.filter(tx_hash_col.eq(tx_hash)))
+                            .set(next_redemption_at_col.eq(Some(retry_at)))
+                            .execute(conn)
+                            .await?;
+                    }
+
+                    fees_table
+                        .filter(tx_hash_col.eq(tx_hash))
+                        .select(Fee::as_select())
+                        .first(conn)
+                        .await
+                }
+                .scope_boxed()
+            })
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query(
+            "failed to record redemption failure",
+        ))
+    }
+
+    /// Claim fees for redemption by this run
+    ///
+    /// A fee is claimable unless another run holds an unexpired claim on it.
+    /// Fees locked by a concurrent claim are skipped rather than waited on, so
+    /// that instances claiming overlapping fees never block each other, and a
+    /// claim lapses after the claim TTL, so that a crashed run strands no fee
+    ///
+    /// Returns the tx hashes of the fees claimed
+    pub(crate) async fn claim_fees_for_redemption(
+        &mut self,
+        tx_hashes: Vec<String>,
+    ) -> Result<HashSet<String>, FeeSweeperError> {
+        let run_id = self.run_id;
+        let window = ClaimWindow::new(run_id, self.clock.as_ref(), self.fee_claim_ttl);
+        self.timed_query(CLAIM_FEES_QUERY, |conn| {
+            async move {
+                let claimable = fees_table
+                    .filter(tx_hash_col.eq_any(tx_hashes))
+                    .filter(redeemed_col.eq(false))
+                    .filter(
+                        claimed_until_col
+                            .is_null()
+                            .or(claimed_until_col.lt(window.now))
+                            .or(claimed_by_col.eq(run_id)),
+                    )
+                    .select(id_col)
+                    .for_update()
+                    .skip_locked();
+                diesel::update(fees_table.filter(id_col.eq_any(claimable)))
+                    .set((
+                        claimed_by_col.eq(run_id),
+                        claimed_until_col.eq(Some(window.expires_at())),
+                    ))
+                    .returning(tx_hash_col)
+                    .get_results::<String>(conn)
+                    .await
+            }
+            .scope_boxed()
+        })
+        .await
+        .map(|claimed| claimed.into_iter().collect())
+        .map_err(FeeSweeperError::query("failed to claim fees"))
+    }
+
+    /// Release the claims this run holds on fees
+    pub(crate) async fn release_fee_claims(&mut self) -> Result<(), FeeSweeperError> {
+        let run_id = self.run_id;
+        self.timed_query(CLAIM_FEES_QUERY, |conn| {
+            async move {
+                diesel::update(fees_table.filter(claimed_by_col.eq(run_id)))
+                    .set((
+                        claimed_by_col.eq(None::<Uuid>),
+                        claimed_until_col.eq(None::<NaiveDateTime>),
+                    ))
+                    .execute(conn)
+                    .await
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query("failed to release fee claims"))
+        .map(|_| ())
+    }
+
+    /// Get the unredeemed fees whose redemption started before the given time
+    ///
+    /// Fees claimed by another run whose claim has not lapsed are excluded, as
+    /// that run may still be redeeming them
+    pub(crate) async fn get_fees_redeeming_since(
+        &mut self,
+        before: NaiveDateTime,
+    ) -> Result<Vec<Fee>, FeeSweeperError> {
+        let window = ClaimWindow::new(self.run_id, self.clock.as_ref(), self.fee_claim_ttl);
+        let fees: Vec<Fee> = self
+            .timed_query(SELECT_REDEEMING_QUERY, |conn| {
+                async move {
+                    fees_table
+                        .filter(redeemed_col.eq(false))
+                        .filter(redemption_started_at_col.lt(before))
+                        .select(Fee::as_select())
+                        .load(conn)
+                        .await
+                }
+                .scope_boxed()
+            })
+            .await
+            .map_err(FeeSweeperError::query("failed to query redeeming fees"))?;
+
+        Ok(fees
+            .into_iter()
+            .filter(|fee| window.is_claimable(fee.claimed_by, fee.claimed_until))
+            .collect())
+    }
+
+    /// Get fees that have not yet been assigned a USD valuation
+    ///
+    /// Fees never tried come first, then those tried longest ago; a fee whose
+    /// last attempt failed within `retry_interval` is skipped
+    pub(crate) async fn get_unvalued_fees(
+        &mut self,
+        limit: i64,
+        retry_interval: chrono::Duration,
+    ) -> Result<Vec<Fee>, FeeSweeperError> {
+        let retry_before = self.clock.naive_now() - retry_interval;
+        self.timed_query(SELECT_UNVALUED_QUERY, |conn| {
+            async move {
+                fees_table
+                    .filter(usd_value_col.is_null())
+                    .filter(
+                        valuation_attempted_at_col
+                            .is_null()
+                            .or(valuation_attempted_at_col.lt(retry_before)),
+                    )
+                    .order((valuation_attempted_at_col.asc().nulls_first(), id_col.asc()))
+                    .limit(limit)
+                    .select(Fee::as_select())
+                    .load(conn)
+                    .await
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query("failed to query unvalued fees"))
+    }
+
+    /// Record a failed attempt to value a fee
+    pub(crate) async fn record_valuation_attempt(
+        &mut self,
+        fee_id: i32,
+    ) -> Result<(), FeeSweeperError> {
+        let now = self.clock.naive_now();
+        self.timed_query(UPDATE_VALUATION_ATTEMPT_QUERY, |conn| {
+            async move {
+                diesel::update(fees_table.find(fee_id))
+                    .set(valuation_attempted_at_col.eq(now))
+                    .execute(conn)
+                    .await
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query("failed to record valuation attempt"))
+        .map(|_| ())
+    }
+
+    /// Set the block information and USD valuation of a fee
+    pub(crate) async fn set_fee_valuation(
+        &mut self,
+        fee_id: i32,
+        block_number: i64,
+        block_timestamp: NaiveDateTime,
+        usd_price: BigDecimal,
+        usd_value: BigDecimal,
+    ) -> Result<(), FeeSweeperError> {
+        self.timed_query(UPDATE_VALUATION_QUERY, |conn| {
+            async move {
+                diesel::update(fees_table.find(fee_id))
+                    .set((
+                        block_number_col.eq(block_number),
+                        block_timestamp_col.eq(block_timestamp),
+                        usd_price_col.eq(usd_price),
+                        usd_value_col.eq(usd_value),
+                    ))
+                    .execute(conn)
+                    .await
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query("failed to set fee valuation"))
+        .map(|_| ())
+    }
+
+    /// Mark unredeemed dust fees accrued before `cutoff` as abandoned
+    ///
+    /// Returns the newly abandoned fees
+    pub(crate) async fn abandon_dust_fees_before(
+        &mut self,
+        cutoff: NaiveDateTime,
+    ) -> Result<Vec<Fee>, FeeSweeperError> {
+        let state = RedemptionState::Abandoned;
+        let from: Vec<&str> = state.predecessors().iter().map(|s| s.as_str()).collect();
+        let stale_dust = fees_table
+            .filter(redemption_state_col.eq_any(from))
+            .filter(dust_col.eq(true))
+            .filter(block_timestamp_col.lt(cutoff));
+
+        let now = self.clock.naive_now();
+        self.timed_query(UPDATE_STATUS_QUERY, |conn| {
+            async move {
+                diesel::update(stale_dust)
+                    .set((
+                        redemption_state_col.eq(state.as_str()),
+                        redemption_state_changed_at_col.eq(now),
+                        status_col.eq(state.fee_status()),
+                        abandoned_at_col.eq(Some(now)),
+                    ))
+                    .returning(Fee::as_returning())
+                    .get_results(conn)
+                    .await
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query("failed to abandon dust fees"))
+    }
+
+    /// Get a page of a mint's unredeemed fees, ordered by amount descending,
+    /// excluding fees an operator moved in the queue
+    ///
+    /// Pages are keyed by the `(amount, id)` of the last fee in the previous
+    /// page, so that each page is served directly from the redemption scan index
+    pub(crate) async fn get_unredeemed_fees_page(
+        &mut self,
+        mint: &str,
+        receiver: &str,
+        excluded_sources: &[String],
+        cursor: Option<FeeCursor>,
+        limit: i64,
+    ) -> Result<Vec<Fee>, FeeSweeperError> {
+        let run_id = self.run_id;
+        let window = ClaimWindow::new(run_id, self.clock.as_ref(), self.fee_claim_ttl);
+        let mut query = fees_table
             .filter(redeemed_col.eq(false))
-            .distinct()
-            .load(&mut self.db_conn)
-            .map_err(raw_err_str!("failed to query unredeemed fees: {}"))?;
+            .filter(dust_col.eq(false))
+            .filter(dead_lettered_at_col.is_null())
+            .filter(redemption_state_col.ne(RedemptionState::InFlight.as_str()))
+            .filter(
+                next_redemption_at_col
+                    .is_null()
+                    .or(next_redemption_at_col.le(window.now)),
+            )
+            .filter(
+                claimed_until_col
+                    .is_null()
+                    .or(claimed_until_col.lt(window.now))
+                    .or(claimed_by_col.eq(run_id)),
+            )
+            .filter(queue_priority_col.eq(0))
+            .filter(mint_col.eq(mint))
+            .filter(receiver_col.eq(receiver))
+            .into_boxed();
+        if !excluded_sources.is_empty() {
+            query = query.filter(source_col.ne_all(excluded_sources.to_vec()));
+        }
+        if let Some(FeeCursor { amount, id }) = cursor {
+            let after_amount = amount_col.lt(amount.clone());
+            let after_id = amount_col.eq(amount).and(id_col.lt(id));
+            query = query.filter(after_amount.or(after_id));
+        }
 
-        Ok(mints)
+        self.timed_query(SELECT_UNREDEEMED_QUERY, |conn| {
+            async move {
+                query
+                    .order((amount_col.desc(), id_col.desc()))
+                    .limit(limit)
+                    .select(Fee::as_select())
+                    .load(conn)
+                    .await
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query("failed to query unredeemed fees"))
     }
 
-    /// Mark a fee as redeemed
-    pub(crate) fn mark_fee_as_redeemed(&mut self, tx_hash: &str) -> Result<(), String> {
-        let filter = tx_hash_col.eq(tx_hash);
-        diesel::update(fees_table.filter(filter))
-            .set(redeemed_col.eq(true))
-            .execute(&mut self.db_conn)
-            .map_err(raw_err_str!("failed to mark fee as redeemed: {}"))
-            .map(|_| ())
+    /// Get the unredeemed fees in the given mints that an operator moved in
+    /// the redemption queue, in queue order
+    pub(crate) async fn get_moved_fees(
+        &mut self,
+        mints: Vec<String>,
+        receiver: &str,
+        excluded_sources: &[String],
+    ) -> Result<Vec<Fee>, FeeSweeperError> {
+        let run_id = self.run_id;
+        let window = ClaimWindow::new(run_id, self.clock.as_ref(), self.fee_claim_ttl);
+        let mut query = fees_table
+            .filter(redeemed_col.eq(false))
+            .filter(dust_col.eq(false))
+            .filter(dead_lettered_at_col.is_null())
+            .filter(redemption_state_col.ne(RedemptionState::InFlight.as_str()))
+            .filter(
+                next_redemption_at_col
+                    .is_null()
+                    .or(next_redemption_at_col.le(window.now)),
+            )
+            .filter(
+                claimed_until_col
+                    .is_null()
+                    .or(claimed_until_col.lt(window.now))
+                    .or(claimed_by_col.eq(run_id)),
+            )
+            .filter(queue_priority_col.ne(0))
+            .filter(mint_col.eq_any(mints))
+            .filter(receiver_col.eq(receiver))
+            .into_boxed();
+        if !excluded_sources.is_empty() {
+            query = query.filter(source_col.ne_all(excluded_sources.to_vec()));
+        }
+
+        self.timed_query(SELECT_UNREDEEMED_QUERY, |conn| {
+            async move {
+                query
+                    .order((queue_priority_col.desc(), id_col.desc()))
+                    .select(Fee::as_select())
+                    .load(conn)
+                    .await
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query("failed to query moved fees"))
     }
 
     /// Get the most valuable fees to be redeemed
     ///
-    /// Returns the tx hashes of the most valuable fees to be redeemed
-    pub(crate) fn get_most_valuable_fees(
+    /// The value of a fee is its amount times the price of its mint, so each
+    /// mint's fees are already in value order when scanned by amount. We merge
+    /// per-mint keyset-paginated scans, taking the most valuable head at each
+    /// step, and only fetch a mint's next page once its buffer drains
+    ///
+    /// Fees an operator pinned to the front of the queue come before the
+    /// scanned fees, and fees pushed to the back only fill what room is left.
+    /// Fees claimed by another run are passed over, so that concurrent runs
+    /// select disjoint fees
+    pub(crate) async fn get_most_valuable_fees(
         &mut self,
         prices: HashMap<String, f64>,
         receiver: &str,
-    ) -> Result<Vec<FeeValue>, String> {
-        if prices.is_empty() {
-            return Ok(vec![]);
+        excluded_sources: &[String],
+    ) -> Result<Vec<FeeValue>, FeeSweeperError> {
+        let mut mint_prices = HashMap::with_capacity(prices.len());
+        for (mint, price) in prices.into_iter() {
+            let price = BigDecimal::from_f64(price).ok_or_else(|| {
+                FeeSweeperError::Rpc(format!("invalid price for {mint}: {price}"))
+            })?;
+            mint_prices.insert(mint, price);
         }
 
-        // We query the fees table with a transformation that calculates the value of each fee using the prices passed in.
-        // This query looks something like:
-        //  SELECT tx_hash, mint, amount,
-        //  CASE
-        //      WHEN mint = '<mint1>' then amount * <price1>
-        //      WHEN mint = '<mint2>' then amount * <price2>
-        //      ...
-        //      ELSE 0
-        //  END as value
-        //  FROM fees
-        //  ORDER BY value DESC;
-        let mut query_string = String::new();
-        query_string.push_str("SELECT tx_hash, mint, ");
-        query_string.push_str("CASE ");
-
-        // Add the cases
-        for (mint, price) in prices.into_iter() {
-            query_string.push_str(&format!("WHEN mint = '{}' then amount * {} ", mint, price));
+        let moved = self
+            .get_moved_fees(
+                mint_prices.keys().cloned().collect(),
+                receiver,
+                excluded_sources,
+            )
+            .await?;
+        let (pinned, pushed_back): (Vec<FeeValue>, Vec<FeeValue>) = moved
+            .into_iter()
+            .map(|fee| {
+                let price = &mint_prices[&fee.mint];
+                FeeValue::new(fee, price)
+            })
+            .partition(|fee| !fee.pushed_back());
+
+        let mut fees: Vec<FeeValue> = pinned.into_iter().take(MAX_FEES_REDEEMED).collect();
+        let mut scans: Vec<MintScan> = mint_prices
+            .into_iter()
+            .map(|(mint, price)| MintScan::new(mint, price))
+            .collect();
+        while fees.len() < MAX_FEES_REDEEMED {
+            for scan in scans.iter_mut().filter(|scan| scan.needs_page()) {
+                let page = self
+                    .get_unredeemed_fees_page(
+                        &scan.mint,
+                        receiver,
+                        excluded_sources,
+                        scan.cursor.clone(),
+                        REDEMPTION_SCAN_PAGE_SIZE,
+                    )
+                    .await?;
+                scan.push_page(page);
+            }
+
+            let best = scans
+                .iter_mut()
+                .filter_map(|scan| scan.peek_value().map(|value| (value, scan)))
+                .max_by(|(v1, _), (v2, _)| v1.cmp(v2));
+            match best.and_then(|(_, scan)| scan.pop()) {
+                Some(fee) => fees.push(fee),
+                None => break,
+            }
         }
-        query_string.push_str("ELSE 0 END as value ");
-        query_string.push_str(&format!(
-            "FROM fees WHERE redeemed = false and receiver = '{}'",
-            receiver
-        ));
 
-        // Sort and limit
-        query_string.push_str(&format!("ORDER BY value DESC LIMIT {};", MAX_FEES_REDEEMED));
+        let room = MAX_FEES_REDEEMED.saturating_sub(fees.len());
+        fees.extend(pushed_back.into_iter().take(room));
+        Ok(fees)
+    }
 
-        // Query for the tx hashes
-        sql_query(query_string)
-            .load(&mut self.db_conn)
-            .map_err(raw_err_str!("failed to query most valuable fees: {}"))
+    /// Get every fee, for comparing their dual-written status
+    pub(crate) async fn get_all_fees(&mut self) -> Result<Vec<Fee>, FeeSweeperError> {
+        self.timed_query(SELECT_FEE_STATUS_QUERY, |conn| {
+            async move { fees_table.select(Fee::as_select()).load(conn).await }.scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query("failed to load fees"))
+    }
+
+    // ------------------------------
+    // | Fee Settings History Table |
+    // ------------------------------
+
+    /// Record a change to a fee setting, ignoring changes already recorded
+    pub(crate) async fn insert_fee_setting(
+        &mut self,
+        setting: NewFeeSetting,
+    ) -> Result<(), FeeSweeperError> {
+        self.timed_query(INSERT_FEE_SETTING_QUERY, |conn| {
+            async move {
+                diesel::insert_into(fee_settings_table)
+                    .values(vec![setting])
+                    .on_conflict_do_nothing()
+                    .execute(conn)
+                    .await
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query("failed to insert fee setting"))
+        .map(|_| ())
     }
 
     // -----------------
@@ -184,39 +1349,762 @@ impl Indexer {
     /// Get the wallet managing an mint, if it exists
     ///
     /// Returns the id and secret id of the wallet
-    pub(crate) fn get_wallet_for_mint(
+    pub(crate) async fn get_wallet_for_mint(
         &mut self,
         mint: &str,
-    ) -> Result<Option<WalletMetadata>, String> {
-        let wallets: Vec<WalletMetadata> = wallet_table
-            .filter(managed_mints_col.contains(vec![mint]))
-            .load(&mut self.db_conn)
-            .map_err(raw_err_str!("failed to query wallet for mint: {}"))?;
+        kind: FeeKind,
+    ) -> Result<Option<WalletMetadata>, FeeSweeperError> {
+        let wallets: Vec<WalletMetadata> = self
+            .timed_query(SELECT_WALLET_QUERY, |conn| {
+                async move {
+                    wallet_table
+                        .filter(managed_mints_col.contains(vec![mint]))
+                        .filter(wallet_fee_kind_col.eq(kind.as_str()))
+                        .load(conn)
+                        .await
+                }
+                .scope_boxed()
+            })
+            .await
+            .map_err(FeeSweeperError::query("failed to query wallet for mint"))?;
 
         Ok(wallets.first().cloned())
     }
 
-    /// Find a wallet with an empty balance slot, if one exists
-    pub(crate) fn find_wallet_with_empty_balance(
+    /// Get all redemption wallets
+    pub(crate) async fn get_all_wallets(&mut self) -> Result<Vec<WalletMetadata>, FeeSweeperError> {
+        self.timed_query(SELECT_WALLET_QUERY, |conn| {
+            async move { wallet_table.load(conn).await }.scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query("failed to query wallets"))
+    }
+
+    /// Find a wallet of the given fee kind with an empty balance slot, if one
+    /// exists
+    pub(crate) async fn find_wallet_with_empty_balance(
         &mut self,
-    ) -> Result<Option<WalletMetadata>, String> {
+        kind: FeeKind,
+    ) -> Result<Option<WalletMetadata>, FeeSweeperError> {
         let n_mints = coalesce(array_length(managed_mints_col, 1 /* dim */), 0);
-        let wallets = wallet_table
-            .filter(n_mints.lt(MAX_BALANCES as i32))
-            .load(&mut self.db_conn)
-            .map_err(raw_err_str!(
-                "failed to query wallets with empty balances: {}"
+        let wallets: Vec<WalletMetadata> = self
+            .timed_query(SELECT_WALLET_QUERY, |conn| {
+                async move {
+                    wallet_table
+                        .filter(n_mints.lt(MAX_BALANCES as i32))
+                        .filter(wallet_fee_kind_col.eq(kind.as_str()))
+                        .load(conn)
+                        .await
+                }
+                .scope_boxed()
+            })
+            .await
+            .map_err(FeeSweeperError::query(
+                "failed to query wallets with empty balances",
             ))?;
 
         Ok(wallets.first().cloned())
     }
 
     /// Insert a new wallet into the wallets table
-    pub(crate) fn insert_wallet(&mut self, wallet: WalletMetadata) -> Result<(), String> {
-        diesel::insert_into(wallet_table)
-            .values(vec![wallet])
-            .execute(&mut self.db_conn)
-            .map_err(raw_err_str!("failed to insert wallet: {}"))
-            .map(|_| ())
+    pub(crate) async fn insert_wallet(
+        &mut self,
+        wallet: WalletMetadata,
+    ) -> Result<(), FeeSweeperError> {
+        self.timed_query(INSERT_WALLET_QUERY, |conn| {
+            async move {
+                diesel::insert_into(wallet_table)
+                    .values(vec![wallet])
+                    .execute(conn)
+                    .await
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query("failed to insert wallet"))
+        .map(|_| ())
+    }
+
+    /// Set whether a wallet must be re-synced before its next redemption
+    pub(crate) async fn set_wallet_needs_refresh(
+        &mut self,
+        wallet_id: Uuid,
+        needs_refresh: bool,
+    ) -> Result<(), FeeSweeperError> {
+        self.timed_query(UPDATE_WALLET_QUERY, |conn| {
+            async move {
+                diesel::update(wallet_table.filter(wallet_id_col.eq(wallet_id)))
+                    .set(needs_refresh_col.eq(needs_refresh))
+                    .execute(conn)
+                    .await
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query("failed to set wallet refresh flag"))
+        .map(|_| ())
+    }
+
+    /// Set the time since which a wallet's relayer view has diverged from the
+    /// chain, clearing it once the two agree
+    pub(crate) async fn set_wallet_diverged_since(
+        &mut self,
+        wallet_id: Uuid,
+        diverged_since: Option<NaiveDateTime>,
+    ) -> Result<(), FeeSweeperError> {
+        self.timed_query(UPDATE_WALLET_QUERY, |conn| {
+            async move {
+                diesel::update(wallet_table.filter(wallet_id_col.eq(wallet_id)))
+                    .set(diverged_since_col.eq(diverged_since))
+                    .execute(conn)
+                    .await
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query("failed to set wallet divergence"))
+        .map(|_| ())
+    }
+
+    // ----------------
+    // | Outbox Table |
+    // ----------------
+
+    /// Enqueue a notification for delivery
+    pub(crate) async fn insert_outbox_message(
+        &mut self,
+        message: NewOutboxMessage,
+    ) -> Result<(), FeeSweeperError> {
+        self.timed_query(INSERT_OUTBOX_QUERY, |conn| {
+            async move { enqueue_notice(conn, message).await }.scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query("failed to enqueue notification"))
+        .map(|_| ())
+    }
+
+    /// Get the undelivered notifications on a channel that are due for an
+    /// attempt and have attempts remaining, oldest first
+    pub(crate) async fn get_due_outbox_messages(
+        &mut self,
+        channel: &str,
+        max_attempts: i32,
+    ) -> Result<Vec<OutboxMessage>, FeeSweeperError> {
+        self.timed_query(SELECT_OUTBOX_QUERY, |conn| {
+            async move {
+                outbox_table
+                    .filter(outbox_channel_col.eq(channel))
+                    .filter(delivered_at_col.is_null())
+                    .filter(outbox_attempts_col.lt(max_attempts))
+                    .filter(next_attempt_at_col.le(diesel::dsl::now))
+                    .order(outbox_id_col.asc())
+                    .select(OutboxMessage::as_select())
+                    .load(conn)
+                    .await
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query("failed to query outbox"))
+    }
+
+    /// Mark notifications as delivered
+    pub(crate) async fn mark_outbox_delivered(
+        &mut self,
+        ids: Vec<i32>,
+    ) -> Result<(), FeeSweeperError> {
+        self.timed_query(UPDATE_OUTBOX_QUERY, |conn| {
+            async move {
+                diesel::update(outbox_table.filter(outbox_id_col.eq_any(ids)))
+                    .set((
+                        delivered_at_col.eq(diesel::dsl::now.nullable()),
+                        outbox_attempts_col.eq(outbox_attempts_col + 1),
+                    ))
+                    .execute(conn)
+                    .await
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query(
+            "failed to mark notifications delivered",
+        ))
+        .map(|_| ())
+    }
+
+    /// Record a failed attempt to deliver notifications, backing them off
+    /// until their next attempt
+    pub(crate) async fn record_outbox_failure(
+        &mut self,
+        ids: Vec<i32>,
+        error: &str,
+        retry_delay: Duration,
+    ) -> Result<(), FeeSweeperError> {
+        let delay_secs = retry_delay.as_secs() as i64;
+        self.timed_query(UPDATE_OUTBOX_QUERY, |conn| {
+            async move {
+                diesel::update(outbox_table.filter(outbox_id_col.eq_any(ids)))
+                    .set((
+                        outbox_attempts_col.eq(outbox_attempts_col + 1),
+                        last_outbox_error_col.eq(error),
+                        next_attempt_at_col.eq(diesel::dsl::now + delay_secs.seconds()),
+                    ))
+                    .execute(conn)
+                    .await
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query(
+            "failed to record notification failure",
+        ))
+        .map(|_| ())
+    }
+
+    // --------------
+    // | Log Tables |
+    // --------------
+
+    /// Record an event in the audit log
+    pub(crate) async fn record_audit_event(
+        &mut self,
+        event: NewAuditEvent,
+    ) -> Result<(), FeeSweeperError> {
+        info!("audit: {}: {}", event.event_type, event.details);
+        self.timed_query(INSERT_AUDIT_QUERY, |conn| {
+            async move {
+                diesel::insert_into(audit_log::table)
+                    .values(vec![event])
+                    .execute(conn)
+                    .await
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query("failed to record audit event"))
+        .map(|_| ())
+    }
+
+    /// Get the events of a type recorded for a transaction, oldest first
+    pub(crate) async fn get_audit_events(
+        &mut self,
+        tx_hash: &str,
+        event_type: &str,
+    ) -> Result<Vec<AuditEvent>, FeeSweeperError> {
+        self.timed_query(SELECT_AUDIT_QUERY, |conn| {
+            async move {
+                audit_log::table
+                    .filter(audit_log::tx_hash.eq(tx_hash))
+                    .filter(audit_log::event_type.eq(event_type))
+                    .order(audit_log::created_at.asc())
+                    .select(AuditEvent::as_select())
+                    .load(conn)
+                    .await
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query("failed to query audit events"))
+    }
+
+    /// Record a failed redemption
+    pub(crate) async fn record_task_failure(
+        &mut self,
+        failure: NewTaskFailure,
+    ) -> Result<(), FeeSweeperError> {
+        self.timed_query(INSERT_TASK_FAILURE_QUERY, |conn| {
+            async move {
+                diesel::insert_into(task_failures::table)
+                    .values(vec![failure])
+                    .execute(conn)
+                    .await
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query("failed to record task failure"))
+        .map(|_| ())
+    }
+
+    /// Record the summary of a sweep
+    pub(crate) async fn record_run_summary(
+        &mut self,
+        summary: NewRunSummary,
+    ) -> Result<(), FeeSweeperError> {
+        self.timed_query(INSERT_RUN_SUMMARY_QUERY, |conn| {
+            async move {
+                diesel::insert_into(run_summaries::table)
+                    .values(vec![summary])
+                    .execute(conn)
+                    .await
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query("failed to record run summary"))
+        .map(|_| ())
+    }
+
+    /// Delete the admin access log entries recorded before `cutoff`
+    ///
+    /// Returns the number of entries deleted
+    pub(crate) async fn prune_admin_access_log(
+        &mut self,
+        cutoff: NaiveDateTime,
+    ) -> Result<usize, FeeSweeperError> {
+        self.timed_query(PRUNE_ACCESS_LOG_QUERY, |conn| {
+            async move {
+                diesel::delete(
+                    admin_access_log::table.filter(admin_access_log::created_at.lt(cutoff)),
+                )
+                .execute(conn)
+                .await
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query("failed to prune admin access log"))
+    }
+
+    // ------------------------
+    // | Fund Movements Table |
+    // ------------------------
+
+    /// Read the ledger of a mint, given as a lowercase hex address, against
+    /// which a movement of it is checked
+    pub(crate) async fn get_mint_ledger(
+        &mut self,
+        mint: &str,
+    ) -> Result<MintLedger, FeeSweeperError> {
+        self.timed_query(SELECT_MINT_LEDGER_QUERY, |conn| {
+            async move {
+                let redeemed: Option<BigDecimal> = fees_table
+                    .filter(mint_col.eq(mint))
+                    .filter(redeemed_col.eq(true))
+                    .select(diesel::dsl::sum(amount_col))
+                    .first(conn)
+                    .await?;
+                let moved: Option<BigDecimal> = fund_movements::table
+                    .filter(fund_movements::mint.eq(mint))
+                    .select(diesel::dsl::sum(fund_movements::amount))
+                    .first(conn)
+                    .await?;
+                let usd_price: Option<Option<BigDecimal>> = fees_table
+                    .filter(mint_col.eq(mint))
+                    .filter(usd_price_col.is_not_null())
+                    .order(id_col.desc())
+                    .select(usd_price_col)
+                    .first(conn)
+                    .await
+                    .optional()?;
+
+                Ok(MintLedger {
+                    redeemed: redeemed.unwrap_or_default(),
+                    moved: moved.unwrap_or_default(),
+                    usd_price: usd_price.flatten(),
+                })
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query("failed to query mint ledger"))
+    }
+
+    /// Record the placement of a conversion tranche: the sale of `amount` of
+    /// `mint` for `proceeds` of `quote_mint`
+    pub(crate) async fn record_conversion(
+        &mut self,
+        order_id: &str,
+        mint: &str,
+        amount: BigDecimal,
+        quote_mint: &str,
+        proceeds: BigDecimal,
+    ) -> Result<(), FeeSweeperError> {
+        self.timed_query(INSERT_FUND_MOVEMENT_QUERY, |conn| {
+            async move {
+                crate::db::fund_movements::record_conversion(
+                    conn, order_id, mint, amount, quote_mint, proceeds,
+                )
+                .await
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query("failed to record conversion"))
+        .map(|_| ())
+    }
+
+    /// Reverse the unfilled `remaining` amount of a cancelled conversion
+    /// tranche, along with its share of the tranche's proceeds
+    pub(crate) async fn reverse_conversion(
+        &mut self,
+        order_id: &str,
+        remaining: BigDecimal,
+    ) -> Result<(), FeeSweeperError> {
+        self.timed_transaction(INSERT_FUND_MOVEMENT_QUERY, |conn| {
+            let remaining = remaining.clone();
+            conn.transaction(move |conn| {
+                async move {
+                    crate::db::fund_movements::reverse_conversion(conn, order_id, remaining).await
+                }
+                .scope_boxed()
+            })
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query("failed to reverse conversion"))
+        .map(|_| ())
+    }
+
+    /// Record the gas spent by a mined transaction
+    pub(crate) async fn record_gas_spend(
+        &mut self,
+        purpose: GasPurpose,
+        receipt: &TransactionReceipt,
+    ) -> Result<(), FeeSweeperError> {
+        let entry = new_gas_spend(purpose, receipt);
+        self.timed_query(INSERT_GAS_SPEND_QUERY, |conn| {
+            async move {
+                // A transaction's gas is recorded once, however many times it
+                // is seen
+                diesel::insert_into(gas_spend::table)
+                    .values(vec![entry])
+                    .on_conflict_do_nothing()
+                    .execute(conn)
+                    .await
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query("failed to record gas spend"))
+        .map(|_| ())
+    }
+
+    // ----------------
+    // | Mints Tables |
+    // ----------------
+
+    /// Get the intermediate mint through which each routed mint is priced,
+    /// keyed by lowercase mint
+    pub(crate) async fn get_price_route_map(
+        &mut self,
+    ) -> Result<HashMap<String, String>, FeeSweeperError> {
+        let routes = self
+            .timed_query(SELECT_PRICE_ROUTES_QUERY, |conn| {
+                async move {
+                    price_routes::table
+                        .select(PriceRoute::as_select())
+                        .load(conn)
+                        .await
+                }
+                .scope_boxed()
+            })
+            .await
+            .map_err(FeeSweeperError::query("failed to query price routes"))?;
+
+        Ok(routes
+            .into_iter()
+            .map(|route| (route.mint, route.via_mint))
+            .collect())
+    }
+
+    /// Get every quarantine, released or not, oldest first
+    pub(crate) async fn get_quarantines(&mut self) -> Result<Vec<MintQuarantine>, FeeSweeperError> {
+        self.timed_query(SELECT_QUARANTINE_QUERY, |conn| {
+            async move {
+                mint_quarantines::table
+                    .order(mint_quarantines::quarantined_at.asc())
+                    .select(MintQuarantine::as_select())
+                    .load(conn)
+                    .await
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query("failed to query quarantines"))
+    }
+
+    /// Quarantine a mint, unless it has been quarantined before
+    ///
+    /// Returns whether the mint was newly quarantined
+    pub(crate) async fn quarantine_mint(
+        &mut self,
+        mint: &str,
+        reason: &str,
+    ) -> Result<bool, FeeSweeperError> {
+        let quarantine = NewMintQuarantine {
+            mint: mint.to_string(),
+            reason: reason.to_string(),
+        };
+        let inserted = self
+            .timed_query(INSERT_QUARANTINE_QUERY, |conn| {
+                async move {
+                    diesel::insert_into(mint_quarantines::table)
+                        .values(vec![quarantine])
+                        .on_conflict_do_nothing()
+                        .execute(conn)
+                        .await
+                }
+                .scope_boxed()
+            })
+            .await
+            .map_err(FeeSweeperError::query("failed to quarantine mint"))?;
+        if inserted == 0 {
+            return Ok(false);
+        }
+
+        let details = format!("quarantined {mint}: {reason}");
+        self.record_audit_event(NewAuditEvent::new(MINT_QUARANTINE_EVENT, None, details))
+            .await?;
+        Ok(true)
+    }
+
+    /// Get the probe verdict of a mint, if it has been probed
+    pub(crate) async fn get_token_probe(
+        &mut self,
+        mint: &str,
+    ) -> Result<Option<TokenProbe>, FeeSweeperError> {
+        self.timed_query(SELECT_TOKEN_PROBE_QUERY, |conn| {
+            async move {
+                token_probes::table
+                    .filter(token_probes::mint.eq(mint))
+                    .select(TokenProbe::as_select())
+                    .first(conn)
+                    .await
+                    .optional()
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query("failed to query token probe"))
+    }
+
+    /// Record the probe verdict of a mint
+    pub(crate) async fn record_token_probe(
+        &mut self,
+        probe: NewTokenProbe,
+    ) -> Result<(), FeeSweeperError> {
+        self.timed_query(INSERT_TOKEN_PROBE_QUERY, |conn| {
+            async move {
+                diesel::insert_into(token_probes::table)
+                    .values(vec![probe])
+                    .on_conflict_do_nothing()
+                    .execute(conn)
+                    .await
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query("failed to record token probe"))
+        .map(|_| ())
+    }
+
+    // ------------------
+    // | Control Tables |
+    // ------------------
+
+    /// Atomically acquire or renew a lease for `ttl` under the run's id
+    ///
+    /// Returns whether the run now holds the lease
+    pub(crate) async fn acquire_lease(
+        &mut self,
+        name: &str,
+        ttl: Duration,
+    ) -> Result<bool, FeeSweeperError> {
+        let holder = self.run_id;
+        let acquired = self
+            .timed_query(LEASE_QUERY, |conn| {
+                async move {
+                    diesel::sql_query(ACQUIRE_LEASE_QUERY)
+                        .bind::<Text, _>(name)
+                        .bind::<SqlUuid, _>(holder)
+                        .bind::<Double, _>(ttl.as_secs_f64())
+                        .execute(conn)
+                        .await
+                }
+                .scope_boxed()
+            })
+            .await
+            .map_err(FeeSweeperError::query("failed to acquire lease"))?;
+
+        Ok(acquired == 1)
+    }
+
+    /// Get a lease, if it has ever been acquired
+    pub(crate) async fn get_lease(
+        &mut self,
+        name: &str,
+    ) -> Result<Option<InstanceLease>, FeeSweeperError> {
+        self.timed_query(LEASE_QUERY, |conn| {
+            async move {
+                instance_leases::table
+                    .filter(instance_leases::name.eq(name))
+                    .select(InstanceLease::as_select())
+                    .first(conn)
+                    .await
+                    .optional()
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query("failed to query lease"))
+    }
+
+    /// Release a lease held by the run, freeing it immediately
+    pub(crate) async fn release_lease(&mut self, name: &str) -> Result<(), FeeSweeperError> {
+        let holder = self.run_id;
+        self.timed_query(LEASE_QUERY, |conn| {
+            async move {
+                let lease = instance_leases::table
+                    .filter(instance_leases::name.eq(name))
+                    .filter(instance_leases::holder.eq(holder));
+                diesel::update(lease)
+                    .set(instance_leases::expires_at.eq(diesel::dsl::now))
+                    .execute(conn)
+                    .await
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query("failed to release lease"))
+        .map(|_| ())
+    }
+
+    /// Get every feature flag stored in the DB
+    pub(crate) async fn get_feature_flags(&mut self) -> Result<Vec<FeatureFlag>, FeeSweeperError> {
+        self.timed_query(SELECT_FEATURE_FLAGS_QUERY, |conn| {
+            async move {
+                feature_flags::table
+                    .order(feature_flags::name.asc())
+                    .select(FeatureFlag::as_select())
+                    .load(conn)
+                    .await
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query("failed to query feature flags"))
+    }
+
+    /// Get every decryption key registered in the DB, oldest first
+    pub(crate) async fn get_registered_fee_keys(
+        &mut self,
+    ) -> Result<Vec<RegisteredFeeKey>, FeeSweeperError> {
+        self.timed_query(SELECT_FEE_KEYS_QUERY, |conn| {
+            async move {
+                fee_decryption_keys::table
+                    .order(fee_decryption_keys::added_at.asc())
+                    .select(RegisteredFeeKey::as_select())
+                    .load(conn)
+                    .await
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(FeeSweeperError::query("failed to query decryption keys"))
+    }
+}
+
+// -----------
+// | Helpers |
+// -----------
+
+/// Transition a fee to a redemption state at the given time, failing the
+/// query, and so the transaction it runs in, if the fee is not in a state from
+/// which the transition is legal
+///
+/// The columns derived from the state are written in the same statement, so
+/// that the constraints holding them to it are never violated
+async fn transition_redemption_state(
+    conn: &mut AsyncPgConnection,
+    tx_hash: &str,
+    to: RedemptionState,
+    now: NaiveDateTime,
+) -> QueryResult<()> {
+    let from: Vec<&str> = to.predecessors().iter().map(|s| s.as_str()).collect();
+    let updated = diesel::update(
+        fees_table
+            .filter(tx_hash_col.eq(tx_hash))
+            .filter(redemption_state_col.eq_any(from)),
+    )
+    .set((
+        redemption_state_col.eq(to.as_str()),
+        redemption_state_changed_at_col.eq(now),
+        redeemed_col.eq(to.is_redeemed()),
+        status_col.eq(to.fee_status()),
+        dead_lettered_at_col.eq((to == RedemptionState::Exhausted).then_some(now)),
+        abandoned_at_col.eq((to == RedemptionState::Abandoned).then_some(now)),
+    ))
+    .execute(conn)
+    .await?;
+
+    if updated == 0 {
+        let msg = format!("fee from tx {tx_hash} cannot transition to {to}");
+        return Err(DieselError::DatabaseError(
+            DatabaseErrorKind::CheckViolation,
+            Box::new(msg),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::DateTime;
+    use uuid::Uuid;
+
+    use super::ClaimWindow;
+    use crate::clock::{Clock, ManualClock};
+
+    /// The TTL of the claims under test
+    const CLAIM_TTL: Duration = Duration::from_secs(300);
+
+    /// A manual clock reading a fixed time
+    fn manual_clock() -> ManualClock {
+        ManualClock::new(DateTime::from_timestamp(1_700_000_000, 0 /* nsecs */).unwrap())
+    }
+
+    #[test]
+    fn unclaimed_fee_is_claimable() {
+        let clock = manual_clock();
+        let window = ClaimWindow::new(Uuid::new_v4(), &clock, CLAIM_TTL);
+        assert!(window.is_claimable(None, None));
+    }
+
+    #[test]
+    fn claim_expires_after_its_ttl() {
+        let clock = manual_clock();
+        let window = ClaimWindow::new(Uuid::new_v4(), &clock, CLAIM_TTL);
+        let expected = clock.naive_now() + chrono::Duration::seconds(300);
+        assert_eq!(window.expires_at(), expected);
+    }
+
+    #[test]
+    fn other_runs_claim_blocks_until_it_lapses() {
+        let clock = manual_clock();
+        let holder = Uuid::new_v4();
+        let claimed_until = ClaimWindow::new(holder, &clock, CLAIM_TTL).expires_at();
+
+        let other = Uuid::new_v4();
+        clock.advance(CLAIM_TTL);
+        let window = ClaimWindow::new(other, &clock, CLAIM_TTL);
+        assert!(!window.is_claimable(Some(holder), Some(claimed_until)));
+
+        clock.advance(Duration::from_secs(1));
+        let window = ClaimWindow::new(other, &clock, CLAIM_TTL);
+        assert!(window.is_claimable(Some(holder), Some(claimed_until)));
+    }
+
+    #[test]
+    fn run_keeps_its_own_claim() {
+        let clock = manual_clock();
+        let holder = Uuid::new_v4();
+        let claimed_until = ClaimWindow::new(holder, &clock, CLAIM_TTL).expires_at();
+
+        clock.advance(Duration::from_secs(1));
+        let window = ClaimWindow::new(holder, &clock, CLAIM_TTL);
+        assert!(window.is_claimable(Some(holder), Some(claimed_until)));
     }
 }
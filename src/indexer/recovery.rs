@@ -0,0 +1,125 @@
+//! Recovery of fees orphaned by a run that died mid-redemption
+//!
+//! A fee's redemption start is recorded before the relayer is asked to redeem
+//! it and cleared once the redemption finishes. A fee whose redemption started
+//! long enough ago is checked on-chain: if its note's nullifier is spent it is
+//! marked redeemed, otherwise it is marked failed so that a later run may
+//! redeem it. A fee whose relayer task, recorded when the relayer started it,
+//! is still queued or running is left alone, as that task may yet redeem it;
+//! without a recorded task, a fee is left alone while its wallet has any task
+//! queued or running
+
+use std::str::FromStr;
+use std::time::Duration;
+
+use ethers::types::TxHash;
+use tracing::info;
+
+use crate::db::audit::ORPHANED_FEE_RECOVERY_EVENT;
+use crate::db::models::{Fee, NewAuditEvent};
+use crate::error::FeeSweeperError;
+use crate::fee_keys::FeeKind;
+use crate::telemetry::ETH_CALL;
+use crate::Indexer;
+
+impl Indexer {
+    /// Recover fees whose redemption started more than `threshold` ago and
+    /// never finished
+    pub async fn recover_orphaned_fees(
+        &mut self,
+        threshold: Duration,
+    ) -> Result<(), FeeSweeperError> {
+        let threshold = chrono::Duration::from_std(threshold)
+            .map_err(FeeSweeperError::config("invalid threshold"))?;
+        let cutoff = self.clock.naive_now() - threshold;
+
+        let orphaned = self.get_fees_redeeming_since(cutoff).await?;
+        if orphaned.is_empty() {
+            return Ok(());
+        }
+
+        info!("recovering {} orphaned fees", orphaned.len());
+        for fee in orphaned.into_iter() {
+            // A redemption that completed before the run died need only be marked
+            let tx_hash =
+                TxHash::from_str(&fee.tx_hash).map_err(FeeSweeperError::db("invalid tx hash"))?;
+            let fee_key = self.fee_key_for_receiver(&fee.receiver)?;
+            if self.has_live_redemption_task(&fee, fee_key.kind).await? {
+                info!(
+                    "fee from tx {} may still be redeeming in the relayer, deferring its recovery",
+                    fee.tx_hash
+                );
+                continue;
+            }
+            let note = self.get_note_from_tx(tx_hash, &fee_key.key).await?;
+            self.record_rpc(ETH_CALL);
+            let nullifier_spent = self
+                .darkpool_client
+                .darkpool()
+                .check_nullifier_used(note.nullifier())
+                .await
+                .map_err(FeeSweeperError::rpc("failed to check nullifier"))?;
+
+            let details = if nullifier_spent {
+                self.mark_fee_as_redeemed(&fee.tx_hash, None /* notice */)
+                    .await?;
+                "nullifier spent, marked redeemed"
+            } else {
+                self.set_fee_redemption_started(&fee.tx_hash, None).await?;
+                "nullifier unspent, marked failed"
+            };
+
+            let started_at = fee.redemption_started_at.unwrap_or_default();
+            self.record_audit_event(NewAuditEvent::new(
+                ORPHANED_FEE_RECOVERY_EVENT,
+                Some(fee.tx_hash),
+                format!("redemption started at {started_at}: {details}"),
+            ))
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether a relayer task may still be redeeming a fee, i.e. its recorded
+    /// task is queued or running, or without a recorded task, a wallet the fee
+    /// may be redeemed into has a task queued or running
+    ///
+    /// A fee is redeemed into its mint's wallet, or into a wallet of its kind
+    /// with an empty balance if its mint has none, so without a wallet for its
+    /// mint every wallet of its kind is checked
+    async fn has_live_redemption_task(
+        &mut self,
+        fee: &Fee,
+        kind: FeeKind,
+    ) -> Result<bool, FeeSweeperError> {
+        if let Some(task_id) = fee.redemption_task_id {
+            return self.relayer_client.is_task_live(task_id).await;
+        }
+
+        let wallets = match self.get_wallet_for_mint(&fee.mint, kind).await? {
+            Some(wallet) => vec![wallet],
+            None => self
+                .get_all_wallets()
+                .await?
+                .into_iter()
+                .filter(|wallet| wallet.fee_kind == kind.as_str())
+                .collect(),
+        };
+
+        for wallet in wallets {
+            let eth_key = self.get_wallet_private_key(&wallet).await?;
+            let derived = self.relayer_client.derive_wallet(&eth_key, self.chain_id)?;
+            let root_key = derived.keychain.secret_keys.sk_root.clone().unwrap();
+            let depth = self
+                .relayer_client
+                .get_task_queue_depth(wallet.id, &root_key)
+                .await?;
+            if depth > 0 {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+}
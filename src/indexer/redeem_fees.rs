@@ -1,59 +1,246 @@
 //! Fee redemption logic
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::str::FromStr;
 
 use aws_sdk_secretsmanager::Client as SecretsManagerClient;
-use ethers::core::rand::thread_rng;
 use ethers::signers::LocalWallet;
 use ethers::types::TxHash;
-use ethers::utils::hex;
+use metrics::counter;
 use renegade_api::http::wallet::RedeemNoteRequest;
 use renegade_circuit_types::note::Note;
 use renegade_common::types::wallet::derivation::{
     derive_blinder_seed, derive_share_seed, derive_wallet_id, derive_wallet_keychain,
 };
 use renegade_common::types::wallet::{Wallet, WalletIdentifier};
-use renegade_util::hex::jubjub_to_hex_string;
-use renegade_util::raw_err_str;
-use tracing::{info, warn};
+use tracing::{error, info, info_span, warn, Instrument};
 
+use super::queries::FeeValue;
+use crate::aws::{record_aws_call, SECRETS_MANAGER_SERVICE};
 use crate::db::models::WalletMetadata;
+use crate::error::FeeSweeperError;
+use crate::fee_keys::{FeeKey, FeeKind};
+use crate::notifications::REDEMPTIONS_CHANNEL;
+use crate::relayer_client::RelayerFailure;
+use crate::secrets::{create_wallet_secret, wallet_secret_name};
+use crate::telemetry::{record_oldest_unredeemed_fee_age, ETH_CALL, REDEMPTIONS_DEFERRED_METRIC};
 use crate::Indexer;
 
 /// The maximum number of fees to redeem in a given run of the indexer
 pub(crate) const MAX_FEES_REDEEMED: usize = 20;
 
+/// The progress of a redemption pass, shared across the fee kinds it redeems
+struct RedemptionPass {
+    /// The number of redemptions the pass may still submit
+    remaining: usize,
+    /// Whether the pass's canary redemption has been verified
+    canary_verified: bool,
+}
+
 impl Indexer {
     /// Redeem the most valuable open fees
-    pub async fn redeem_fees(&mut self) -> Result<(), String> {
+    pub async fn redeem_fees(&mut self) -> Result<(), FeeSweeperError> {
         info!("redeeming fees...");
+        if self.is_darkpool_paused().await? {
+            error!("darkpool is paused, skipping redemption");
+            return Ok(());
+        }
 
         // Get all mints that have unredeemed fees
-        let mints = self.get_unredeemed_fee_mints()?;
-
-        // Get the prices of each redeemable mint, we want to redeem the most profitable fees first
-        let mut prices = HashMap::new();
-        for mint in mints.into_iter() {
-            let maybe_price = self.relayer_client.get_binance_price(&mint).await?;
-            if let Some(price) = maybe_price {
-                prices.insert(mint, price);
-            } else {
-                warn!("{}: no price", mint);
+        let mints = self.get_unredeemed_fee_mints().await?;
+        let mints = self.screen_mints(mints).await?;
+
+        // Get the prices of each redeemable mint, we want to redeem the most profitable fees first.
+        // Price routes are read on each pass so that a newly routed mint is priced without a restart
+        let routes = self.get_price_route_map().await?;
+        let prices = self
+            .relayer_client
+            .get_warm_prices(&mints, self.price_warmup, &routes)
+            .await?;
+        let gas_cost_usd = self.estimate_redemption_gas_cost(&routes).await?;
+
+        // Size the pass's batch by the latency of recent redemptions, so that it
+        // finishes in predictable time while the relayer is under load
+        let batch_size = self.batch_sizer.batch_size();
+        info!("redeeming up to {batch_size} fees this pass");
+        let mut pass = RedemptionPass {
+            remaining: batch_size,
+            canary_verified: false,
+        };
+
+        // Redeem the most valuable fees of each kind swept into that kind's wallets,
+        // releasing the fees claimed for the pass however it ends
+        let unscheduled = self.get_unscheduled_sources().await?;
+        let mut redeemed_sources = HashSet::new();
+        let mut res = Ok(());
+        for fee_key in self.fee_keys.clone() {
+            if self.shutdown.requested() {
+                break;
             }
+            let kind_res = self
+                .redeem_fees_of_kind(
+                    fee_key,
+                    prices.clone(),
+                    &unscheduled,
+                    gas_cost_usd,
+                    &mut pass,
+                )
+                .await;
+            match kind_res {
+                Ok(sources) => redeemed_sources.extend(sources),
+                Err(e) => {
+                    res = Err(e);
+                    break;
+                }
+            }
+        }
+        let released = self.release_fee_claims().await;
+        if let (Err(_), Err(e)) = (&res, &released) {
+            warn!("failed to release fee claims after a failed pass: {e}");
         }
+        res?;
+        released?;
 
-        // Get the most valuable fees and redeem them
-        let recv = jubjub_to_hex_string(&self.decryption_key.public_key());
-        let most_valuable_fees = self.get_most_valuable_fees(prices, &recv)?;
+        for source in redeemed_sources {
+            self.record_source_redemption(&source).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Redeem the most valuable open fees paid to a fee key, submitting no
+    /// more redemptions than remain in the pass
+    ///
+    /// Until the pass's canary is verified, the least valuable fee not pushed
+    /// to the back of the queue is redeemed first as the canary, and the pass
+    /// is aborted if the canary does not fully succeed, containing the blast
+    /// radius of a systemic failure
+    ///
+    /// Returns the sources of the fees redeemed
+    async fn redeem_fees_of_kind(
+        &mut self,
+        fee_key: FeeKey,
+        prices: HashMap<String, f64>,
+        unscheduled: &[String],
+        gas_cost_usd: Option<f64>,
+        pass: &mut RedemptionPass,
+    ) -> Result<HashSet<String>, FeeSweeperError> {
+        let recv = fee_key.receiver();
+        self.update_oldest_unredeemed_fee_age(
+            prices.keys().cloned().collect(),
+            &recv,
+            fee_key.kind,
+        )
+        .await?;
+        let most_valuable_fees = self
+            .get_most_valuable_fees(prices.clone(), &recv, unscheduled)
+            .await?;
 
-        // TODO: Filter by those fees whose present value exceeds the expected gas costs to redeem
-        for fee in most_valuable_fees.into_iter() {
-            let wallet = self.get_or_create_wallet(&fee.mint).await?;
-            self.redeem_note_into_wallet(fee.tx_hash.clone(), wallet)
+        // Score the fees, redeeming them in the order of their scores
+        let most_valuable_fees = self
+            .score_fees(most_valuable_fees, &prices, gas_cost_usd, fee_key.kind)
+            .await?;
+        let mut most_valuable_fees = VecDeque::from(most_valuable_fees);
+
+        let mut redeemed_sources = HashSet::new();
+        loop {
+            let next = match pass.canary_verified {
+                true => most_valuable_fees.pop_front(),
+                false => take_canary(&mut most_valuable_fees),
+            };
+            let Some(fee) = next else { break };
+
+            // Stop between redemptions, never during one, on shutdown
+            if self.shutdown.requested() {
+                info!("shutdown requested, deferring remaining redemptions");
+                break;
+            }
+            if pass.remaining == 0 {
+                info!("redemption batch is full, deferring remaining redemptions");
+                break;
+            }
+            self.check_rpc_budget()?;
+
+            // Claim each fee as it is redeemed, so that no other instance
+            // redeems it concurrently, and the fees this pass does not reach
+            // are left for other instances
+            let claimed = self
+                .claim_fees_for_redemption(vec![fee.tx_hash.clone()])
                 .await?;
+            if claimed.is_empty() {
+                info!(
+                    "fee from tx {} is claimed by another instance, skipping it",
+                    fee.tx_hash
+                );
+                continue;
+            }
+            let meets_policy = self.redemption_policies.meets_threshold(&fee)?;
+            if !meets_policy {
+                info!(
+                    "{} fee from tx {} is below its threshold",
+                    fee.source, fee.tx_hash
+                );
+                continue;
+            }
+            let meets_mint_threshold = self.mint_thresholds.meets_threshold(&fee)?;
+            if !meets_mint_threshold {
+                info!(
+                    "{} fee from tx {} is below its mint's threshold",
+                    fee.mint, fee.tx_hash
+                );
+                continue;
+            }
+            if self.simulate_redemptions
+                && !self.simulate_redemption(&fee.tx_hash, &fee_key).await?
+            {
+                continue;
+            }
+
+            self.mark_fee_eligible(&fee.tx_hash).await?;
+            let wallet = self.get_or_create_wallet(&fee.mint, fee_key.kind).await?;
+
+            // Hold the wallet for the redemption, so that no other operation's
+            // relayer tasks interleave with it
+            let Some(lock) = self.lock_wallet(wallet.id).await? else {
+                info!(
+                    "wallet {} is busy, deferring redemption of fee from tx {}",
+                    wallet.id, fee.tx_hash
+                );
+                continue;
+            };
+            pass.remaining -= 1;
+            let span = info_span!("redemption", tx_hash = %fee.tx_hash, mint = %fee.mint);
+            let redeemed = self
+                .redeem_note_into_wallet(fee.tx_hash.clone(), wallet.clone(), &fee_key)
+                .instrument(span)
+                .await;
+            self.unlock_wallet(lock).await;
+            let Some(note) = redeemed? else { continue };
+            if !pass.canary_verified {
+                self.verify_canary(&fee.tx_hash, &wallet, &note).await?;
+                pass.canary_verified = true;
+            }
+            redeemed_sources.insert(fee.source);
         }
 
+        Ok(redeemed_sources)
+    }
+
+    /// Record the age of the oldest fee eligible for redemption, i.e. an
+    /// unredeemed fee to the sweeper in a mint with a price
+    async fn update_oldest_unredeemed_fee_age(
+        &mut self,
+        mints: Vec<String>,
+        receiver: &str,
+        kind: FeeKind,
+    ) -> Result<(), FeeSweeperError> {
+        let oldest = self.get_oldest_unredeemed_fee_time(mints, receiver).await?;
+        let age = oldest
+            .and_then(|time| (self.clock.naive_now() - time).to_std().ok())
+            .unwrap_or_default();
+
+        info!("oldest unredeemed {kind} fee age: {}s", age.as_secs());
+        record_oldest_unredeemed_fee_age(age, kind);
         Ok(())
     }
 
@@ -61,51 +248,72 @@ impl Indexer {
     // | Wallet Creation |
     // -------------------
 
-    /// Find or create a wallet to store balances of a given mint
-    async fn get_or_create_wallet(&mut self, mint: &str) -> Result<WalletMetadata, String> {
-        let maybe_wallet = self.get_wallet_for_mint(mint)?;
-        let maybe_wallet =
-            maybe_wallet.or_else(|| self.find_wallet_with_empty_balance().ok().flatten());
+    /// Find or create a wallet to store balances of a given mint and fee kind
+    async fn get_or_create_wallet(
+        &mut self,
+        mint: &str,
+        kind: FeeKind,
+    ) -> Result<WalletMetadata, FeeSweeperError> {
+        let maybe_wallet = match self.get_wallet_for_mint(mint, kind).await? {
+            Some(wallet) => Some(wallet),
+            None => self
+                .find_wallet_with_empty_balance(kind)
+                .await
+                .ok()
+                .flatten(),
+        };
 
         match maybe_wallet {
             Some(wallet) => Ok(wallet),
             None => {
-                info!("creating new wallet for {mint}");
-                self.create_new_wallet().await
+                info!("creating new {kind} fee wallet for {mint}");
+                self.create_new_wallet(kind).await
             }
         }
     }
 
-    /// Create a new wallet for managing a given mint
+    /// Create a new wallet for managing fees of a given kind
     ///
     /// Return the new wallet's metadata
-    async fn create_new_wallet(&mut self) -> Result<WalletMetadata, String> {
+    async fn create_new_wallet(
+        &mut self,
+        kind: FeeKind,
+    ) -> Result<WalletMetadata, FeeSweeperError> {
         // 1. Create the new wallet on-chain
         let (wallet_id, root_key) = self.create_renegade_wallet().await?;
 
         // 2. Create a secrets manager entry for the new wallet
         let secret_name = self
-            .create_secrets_manager_entry(wallet_id, root_key)
+            .create_secrets_manager_entry(wallet_id, root_key.clone())
             .await?;
 
         // 3. Add an entry in the wallets table for the newly created wallet
-        let entry = WalletMetadata::empty(wallet_id, secret_name);
-        self.insert_wallet(entry.clone())?;
+        let entry = WalletMetadata::empty(wallet_id, secret_name.clone(), kind);
+        self.insert_wallet(entry.clone()).await?;
+
+        // 4. Back up the wallet's recovery material
+        self.backup_wallet(wallet_id, &root_key, &secret_name, kind)
+            .await;
 
         Ok(entry)
     }
 
     /// Create a new Renegade wallet on-chain
-    async fn create_renegade_wallet(&mut self) -> Result<(WalletIdentifier, LocalWallet), String> {
-        let root_key = LocalWallet::new(&mut thread_rng());
+    async fn create_renegade_wallet(
+        &mut self,
+    ) -> Result<(WalletIdentifier, LocalWallet), FeeSweeperError> {
+        let root_key = self.rng.with(|rng| LocalWallet::new(rng));
 
-        let wallet_id = derive_wallet_id(&root_key)?;
-        let blinder_seed = derive_blinder_seed(&root_key)?;
-        let share_seed = derive_share_seed(&root_key)?;
-        let key_chain = derive_wallet_keychain(&root_key, self.chain_id)?;
+        let wallet_id = derive_wallet_id(&root_key).map_err(FeeSweeperError::Other)?;
+        let blinder_seed = derive_blinder_seed(&root_key).map_err(FeeSweeperError::Other)?;
+        let share_seed = derive_share_seed(&root_key).map_err(FeeSweeperError::Other)?;
+        let key_chain =
+            derive_wallet_keychain(&root_key, self.chain_id).map_err(FeeSweeperError::Other)?;
 
         let wallet = Wallet::new_empty_wallet(wallet_id, blinder_seed, share_seed, key_chain);
-        self.relayer_client.create_new_wallet(wallet).await?;
+        self.relayer_client
+            .create_new_wallet(wallet, self.chain_id, &root_key)
+            .await?;
         info!("created new wallet for fee redemption");
 
         Ok((wallet_id, root_key))
@@ -116,53 +324,166 @@ impl Indexer {
     // ------------------
 
     /// Redeem a note into a wallet
+    ///
+    /// The redemption is deferred to a later sweep, returning `None`, if the
+    /// wallet's relayer task queue is deeper than the configured maximum, so
+    /// that redemptions neither starve the wallet's other operations nor
+    /// time out behind them
     pub async fn redeem_note_into_wallet(
         &mut self,
         tx: String,
         wallet: WalletMetadata,
-    ) -> Result<Note, String> {
+        fee_key: &FeeKey,
+    ) -> Result<Option<Note>, FeeSweeperError> {
         info!("redeeming fee into {}", wallet.id);
         // Get the wallet key for the given wallet
         let eth_key = self.get_wallet_private_key(&wallet).await?;
-        let wallet_keychain = derive_wallet_keychain(&eth_key, self.chain_id).unwrap();
-        let root_key = wallet_keychain.secret_keys.sk_root.clone().unwrap();
+        let derived = self.relayer_client.derive_wallet(&eth_key, self.chain_id)?;
+        let root_key = derived.keychain.secret_keys.sk_root.clone().unwrap();
 
         self.relayer_client
             .check_wallet_indexed(wallet.id, self.chain_id, &eth_key)
             .await?;
+        if wallet.needs_refresh {
+            info!("refreshing wallet {} after a failed redemption", wallet.id);
+            self.relayer_client
+                .refresh_wallet(self.chain_id, &eth_key)
+                .await?;
+            self.set_wallet_needs_refresh(wallet.id, false).await?;
+        }
+
+        let depth = self
+            .relayer_client
+            .get_task_queue_depth(wallet.id, &root_key)
+            .await?;
+        if depth > self.max_task_queue_depth {
+            info!(
+                "wallet {} has {depth} queued tasks, deferring redemption of fee from tx {tx}",
+                wallet.id
+            );
+            counter!(REDEMPTIONS_DEFERRED_METRIC).increment(1);
+            return Ok(None);
+        }
 
         // Find the note in the tx body
-        let tx_hash = TxHash::from_str(&tx).map_err(raw_err_str!("invalid tx hash: {}"))?;
-        let note = self.get_note_from_tx(tx_hash).await?;
+        let tx_hash = TxHash::from_str(&tx).map_err(FeeSweeperError::db("invalid tx hash"))?;
+        let note = self.get_note_from_tx(tx_hash, &fee_key.key).await?;
 
-        // Redeem the note through the relayer
+        // Redeem the note through the relayer, recording the start of the redemption so
+        // that it may be recovered if this run dies before the redemption finishes
+        let started_at = self.clock.naive_now();
+        self.set_fee_redemption_started(&tx, Some(started_at))
+            .await?;
         let req = RedeemNoteRequest {
             note: note.clone(),
-            decryption_key: self.decryption_key,
+            decryption_key: fee_key.key,
         };
-        self.relayer_client
+        let started = self.clock.instant();
+        let res = match self
+            .relayer_client
             .redeem_note(wallet.id, req, &root_key)
-            .await?;
+            .await
+        {
+            // Record the task before awaiting it, so that a run killed during
+            // the await leaves the task for orphan recovery to check
+            Ok(task_id) => {
+                self.set_fee_redemption_task(&tx, task_id).await?;
+                self.relayer_client.await_redemption(task_id).await
+            }
+            Err(e) => Err(e),
+        };
+        let latency = self.clock.instant().saturating_duration_since(started);
+        self.batch_sizer.record(latency);
+
+        // Mark the fee as redeemed, or the wallet as stale if the redemption failed
+        let failure = match &res {
+            Ok(task) => match self.maybe_mark_redeemed(&tx, &note).await? {
+                true => None,
+                false => Some(RelayerFailure {
+                    task_id: Some(task.task_id),
+                    reason: "nullifier unspent after redemption".to_string(),
+                    payload: task.last_status.clone(),
+                }),
+            },
+            Err(e) => Some(e.clone()),
+        };
+        if let Some(failure) = failure {
+            warn!(
+                "redemption into {} failed, flagging it for refresh",
+                wallet.id
+            );
+            self.set_wallet_needs_refresh(wallet.id, true).await?;
+            self.record_failed_redemption(&tx, wallet.id, failure)
+                .await?;
+        }
 
-        // Mark the fee as redeemed
-        self.maybe_mark_redeemed(&tx, &note).await?;
-        Ok(note)
+        res.map(|_| Some(note)).map_err(FeeSweeperError::from)
     }
 
-    /// Mark a fee as redeemed if its nullifier is spent on-chain
-    async fn maybe_mark_redeemed(&mut self, tx_hash: &str, note: &Note) -> Result<(), String> {
-        let nullifier = note.nullifier();
+    /// Verify that a pass's canary redemption fully succeeded: its note's
+    /// nullifier is spent on-chain, and the redeemed balance appears in the
+    /// relayer's view of the wallet
+    async fn verify_canary(
+        &mut self,
+        tx: &str,
+        wallet: &WalletMetadata,
+        note: &Note,
+    ) -> Result<(), FeeSweeperError> {
+        self.record_rpc(ETH_CALL);
         if !self
-            .arbitrum_client
-            .check_nullifier_used(nullifier)
+            .darkpool_client
+            .darkpool()
+            .check_nullifier_used(note.nullifier())
             .await
-            .map_err(raw_err_str!("failed to check nullifier: {}"))?
+            .map_err(FeeSweeperError::rpc("failed to check nullifier"))?
         {
-            return Ok(());
+            return Err(FeeSweeperError::Relayer(format!(
+                "canary redemption of fee from tx {tx} left its nullifier unspent, aborting the pass"
+            )));
+        }
+
+        let eth_key = self.get_wallet_private_key(wallet).await?;
+        let derived = self.relayer_client.derive_wallet(&eth_key, self.chain_id)?;
+        let root_key = derived.keychain.secret_keys.sk_root.clone().unwrap();
+        let relayer_wallet = self.relayer_client.get_wallet(wallet.id, &root_key).await?;
+        let balance = relayer_wallet
+            .balances
+            .values()
+            .find(|balance| balance.mint == note.mint)
+            .map(|balance| balance.amount)
+            .unwrap_or_default();
+        if balance < note.amount {
+            return Err(FeeSweeperError::Relayer(format!(
+                "canary redemption of fee from tx {tx} is missing from wallet {}, aborting the pass",
+                wallet.id
+            )));
         }
 
+        info!("canary redemption of fee from tx {tx} verified");
+        Ok(())
+    }
+
+    /// Mark a fee as redeemed once its nullifier is confirmed spent on-chain
+    ///
+    /// Returns whether the fee was redeemed
+    async fn maybe_mark_redeemed(
+        &mut self,
+        tx_hash: &str,
+        note: &Note,
+    ) -> Result<bool, FeeSweeperError> {
+        if !self.confirm_redemption(tx_hash, note).await? {
+            return Ok(false);
+        }
+        self.record_redemption_gas(tx_hash, note).await;
+
         info!("successfully redeemed fee from tx: {}", tx_hash);
-        self.mark_fee_as_redeemed(tx_hash)
+        let notice = self.outbox_notice(
+            REDEMPTIONS_CHANNEL,
+            format!("redeemed fee from tx {tx_hash}"),
+        );
+        self.mark_fee_as_redeemed(tx_hash, notice).await?;
+        self.deliver_outbox().await;
+        Ok(true)
     }
 
     // -------------------
@@ -176,45 +497,45 @@ impl Indexer {
         &mut self,
         id: WalletIdentifier,
         wallet: LocalWallet,
-    ) -> Result<String, String> {
-        let client = SecretsManagerClient::new(&self.aws_config);
-        let secret_name = format!("redemption-wallet-{}-{id}", self.chain);
-        let secret_val = hex::encode(wallet.signer().to_bytes());
-
-        // Check that the `LocalWallet` recovers the same
-        debug_assert_eq!(LocalWallet::from_str(&secret_val).unwrap(), wallet);
-
-        // Store the secret in AWS
-        client
-            .create_secret()
-            .name(secret_name.clone())
-            .secret_string(secret_val)
-            .description("Wallet used for fee redemption")
-            .send()
-            .await
-            .map_err(raw_err_str!("Error creating secret: {}"))?;
-
+    ) -> Result<String, FeeSweeperError> {
+        let secret_name = wallet_secret_name(&self.chain, id);
+        let kms_key_id = self.wallet_backup.wallet_kms_key_id.as_deref();
+        create_wallet_secret(&self.aws, &secret_name, &wallet, kms_key_id).await?;
         Ok(secret_name)
     }
 
     /// Get the private key for a wallet specified by its metadata
-    async fn get_wallet_private_key(
+    pub(crate) async fn get_wallet_private_key(
         &mut self,
         metadata: &WalletMetadata,
-    ) -> Result<LocalWallet, String> {
-        let client = SecretsManagerClient::new(&self.aws_config);
-        let secret_name = format!("redemption-wallet-{}-{}", self.chain, metadata.id);
+    ) -> Result<LocalWallet, FeeSweeperError> {
+        let client = SecretsManagerClient::new(&self.aws.config);
+        let secret_name = wallet_secret_name(&self.chain, metadata.id);
 
+        record_aws_call(SECRETS_MANAGER_SERVICE, "GetSecretValue");
         let secret = client
             .get_secret_value()
-            .secret_id(secret_name)
+            .secret_id(&secret_name)
             .send()
             .await
-            .map_err(raw_err_str!("Error fetching secret: {}"))?;
+            .map_err(FeeSweeperError::rpc("Error fetching secret"))?;
 
-        let secret_str = secret.secret_string().unwrap();
-        let wallet =
-            LocalWallet::from_str(secret_str).map_err(raw_err_str!("Invalid wallet secret: {}"))?;
+        let secret_str = secret.secret_string().ok_or_else(|| {
+            FeeSweeperError::Config(format!("secret {secret_name} holds no string"))
+        })?;
+        let wallet = LocalWallet::from_str(secret_str)
+            .map_err(FeeSweeperError::config("Invalid wallet secret"))?;
         Ok(wallet)
     }
 }
+
+/// Take the canary of a pass from its queue of fees: the last fee not pushed
+/// to the back of the queue, so that a pushed back fee is not redeemed first,
+/// or the last fee if all were pushed back
+fn take_canary(fees: &mut VecDeque<FeeValue>) -> Option<FeeValue> {
+    let index = fees
+        .iter()
+        .rposition(|fee| !fee.pushed_back())
+        .or_else(|| fees.len().checked_sub(1))?;
+    fees.remove(index)
+}
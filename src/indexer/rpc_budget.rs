@@ -0,0 +1,66 @@
+//! A per-run budget on the RPC calls the indexer makes
+//!
+//! RPC providers meter usage against a quota. With a budget configured, a run
+//! that approaches it degrades rather than failing: indexing covers a smaller
+//! block range per run, and the receipt lookups of the spam heuristics are
+//! skipped. Once the budget is spent, the phase in progress is abandoned, and
+//! the run's remaining work resumes from its checkpoints in the next run
+
+use std::sync::atomic::Ordering;
+
+use clap::Args;
+
+use crate::error::FeeSweeperError;
+use crate::Indexer;
+
+/// The arguments configuring the per-run RPC call budget
+#[derive(Clone, Debug, Args)]
+pub struct RpcBudgetArgs {
+    /// The number of RPC calls a run may make; unset, runs are unbounded
+    #[clap(long)]
+    pub rpc_call_budget: Option<u64>,
+    /// The fraction of the RPC budget beyond which the run degrades
+    #[clap(long, default_value_t = 0.8)]
+    pub rpc_degrade_fraction: f64,
+    /// The number of blocks indexed per run while degraded
+    #[clap(long, default_value_t = 10_000)]
+    pub degraded_block_range: u64,
+}
+
+impl Indexer {
+    /// Record an RPC call of the given method
+    pub(crate) fn record_rpc(&self, method: &'static str) {
+        self.rpc_metrics.record(method);
+    }
+
+    /// Whether the run is close enough to its RPC budget to degrade
+    pub(crate) fn rpc_budget_constrained(&self) -> bool {
+        match self.rpc_budget.rpc_call_budget {
+            Some(budget) => {
+                let threshold = budget as f64 * self.rpc_budget.rpc_degrade_fraction;
+                self.rpc_metrics.total() as f64 >= threshold
+            }
+            None => false,
+        }
+    }
+
+    /// Whether the run has spent its RPC budget
+    pub(crate) fn rpc_budget_exhausted(&self) -> bool {
+        self.rpc_budget
+            .rpc_call_budget
+            .is_some_and(|budget| self.rpc_metrics.total() >= budget)
+    }
+
+    /// Check that the run may make more RPC calls
+    pub(crate) fn check_rpc_budget(&self) -> Result<(), FeeSweeperError> {
+        if !self.rpc_budget_exhausted() {
+            return Ok(());
+        }
+
+        self.rpc_budget_tripped.store(true, Ordering::Relaxed);
+        let budget = self.rpc_budget.rpc_call_budget.unwrap_or_default();
+        Err(FeeSweeperError::Rpc(format!(
+            "run exhausted its RPC budget of {budget} calls"
+        )))
+    }
+}
@@ -0,0 +1,193 @@
+//! Scoring of the fees a redemption pass may redeem
+//!
+//! A `RedemptionScorer` decides, from a fee, its mint's price, the estimated
+//! gas cost of a redemption, and the state of the wallet it would be redeemed
+//! into, whether the pass redeems the fee and with what priority. The fees a
+//! pass claims are scored before any is redeemed: fees the scorer skips have
+//! the reason recorded against them, and the rest are redeemed in descending
+//! order of score. Operator overrides of the queue take precedence, pinned
+//! fees are redeemed first and fees pushed back last, whatever their scores.
+//!
+//! The default scorer ranks fees by their value net of gas. Experimental
+//! strategies implement the trait in a module of their own and are selected
+//! with `--redemption-scorer`, so that they are tried without touching the
+//! redemption pass
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use bigdecimal::ToPrimitive;
+use clap::{Args, ValueEnum};
+use metrics::counter;
+use tracing::info;
+
+use super::queries::FeeValue;
+use crate::db::models::WalletMetadata;
+use crate::error::FeeSweeperError;
+use crate::fee_keys::FeeKind;
+use crate::telemetry::{REDEMPTIONS_SKIPPED_SCORE_METRIC, SCORER_LABEL};
+use crate::Indexer;
+
+/// A scorer shared by the components of a run
+pub(crate) type SharedScorer = Arc<dyn RedemptionScorer>;
+
+/// The inputs from which a fee is scored
+///
+/// Not every scorer reads every input, the default reads none but the fee and
+/// the gas cost
+#[allow(dead_code)]
+pub(crate) struct ScoreInput<'a> {
+    /// The fee
+    pub fee: &'a FeeValue,
+    /// The USD price of a whole unit of the fee's mint
+    pub price: f64,
+    /// The estimated USD gas cost of a redemption, if it could be estimated
+    pub gas_cost_usd: Option<f64>,
+    /// The wallet managing the fee's mint, or `None` if redeeming the fee
+    /// would create a new wallet
+    pub wallet: Option<&'a WalletMetadata>,
+}
+
+/// A scorer's decision on a fee
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Score {
+    /// Redeem the fee, fees with higher scores first
+    Redeem(f64),
+    /// Skip the fee this pass, for the given reason
+    Skip(String),
+}
+
+/// A strategy deciding which fees a redemption pass redeems, and in which
+/// order
+pub(crate) trait RedemptionScorer: Debug + Send + Sync {
+    /// The name of the scorer
+    fn name(&self) -> &'static str;
+
+    /// Score a fee
+    fn score(&self, input: &ScoreInput) -> Result<Score, FeeSweeperError>;
+}
+
+/// A kind of redemption scorer
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ScorerKind {
+    /// Rank fees by their USD value net of the gas cost of redeeming them
+    Value,
+}
+
+/// The arguments configuring the scoring of fees for redemption
+#[derive(Clone, Debug, Args)]
+pub struct ScorerArgs {
+    /// The strategy scoring the fees a redemption pass may redeem
+    #[clap(long, value_enum, default_value_t = ScorerKind::Value)]
+    pub redemption_scorer: ScorerKind,
+}
+
+impl ScorerArgs {
+    /// Build the configured scorer
+    pub(crate) fn build(&self) -> SharedScorer {
+        match self.redemption_scorer {
+            ScorerKind::Value => Arc::new(ValueScorer),
+        }
+    }
+}
+
+// ----------------
+// | Value Scorer |
+// ----------------
+
+/// The default scorer, ranking fees by their USD value net of the estimated
+/// gas cost of redeeming them
+///
+/// The scorer never skips a fee; fees not worth their gas are left to the
+/// gas cost filter, which applies whatever the scorer
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ValueScorer;
+
+impl RedemptionScorer for ValueScorer {
+    fn name(&self) -> &'static str {
+        "value"
+    }
+
+    fn score(&self, input: &ScoreInput) -> Result<Score, FeeSweeperError> {
+        let value = input.fee.usd_value()?.to_f64().ok_or_else(|| {
+            FeeSweeperError::Policy(format!(
+                "invalid value of fee from tx {}",
+                input.fee.tx_hash
+            ))
+        })?;
+        Ok(Score::Redeem(
+            value - input.gas_cost_usd.unwrap_or_default(),
+        ))
+    }
+}
+
+// -----------
+// | Scoring |
+// -----------
+
+/// The place of a fee in the redemption queue set by operator overrides,
+/// pinned fees first and fees pushed back last
+fn queue_class(fee: &FeeValue) -> Ordering {
+    fee.queue_priority.cmp(&0).reverse()
+}
+
+impl Indexer {
+    /// Score the fees claimed by a pass, dropping those not worth their gas
+    /// or skipped by the scorer, and order the rest for redemption
+    pub(crate) async fn score_fees(
+        &mut self,
+        fees: Vec<FeeValue>,
+        prices: &HashMap<String, f64>,
+        gas_cost_usd: Option<f64>,
+        kind: FeeKind,
+    ) -> Result<Vec<FeeValue>, FeeSweeperError> {
+        let scorer = self.scorer.clone();
+        let mut scored = Vec::with_capacity(fees.len());
+        for fee in fees {
+            if !self.covers_gas_cost(&fee, gas_cost_usd).await? {
+                continue;
+            }
+
+            let wallet = self.get_wallet_for_mint(&fee.mint, kind).await?;
+            let price = prices
+                .get(&fee.mint)
+                .copied()
+                .ok_or_else(|| FeeSweeperError::Policy(format!("no price for {}", fee.mint)))?;
+            let input = ScoreInput {
+                fee: &fee,
+                price,
+                gas_cost_usd,
+                wallet: wallet.as_ref(),
+            };
+            match scorer.score(&input)? {
+                Score::Redeem(score) => {
+                    self.set_fee_skip_reason(&fee.tx_hash, None).await?;
+                    scored.push((fee, score));
+                }
+                Score::Skip(reason) => {
+                    info!(
+                        "{} scorer skipped fee from tx {}: {reason}",
+                        scorer.name(),
+                        fee.tx_hash
+                    );
+                    counter!(REDEMPTIONS_SKIPPED_SCORE_METRIC, SCORER_LABEL => scorer.name())
+                        .increment(1);
+                    self.set_fee_skip_reason(&fee.tx_hash, Some(reason)).await?;
+                }
+            }
+        }
+
+        // Overridden fees keep the order the operator gave them, the sort
+        // being stable
+        scored.sort_by(|(fee1, score1), (fee2, score2)| {
+            let by_score = match (fee1.queue_priority, fee2.queue_priority) {
+                (0, 0) => score2.total_cmp(score1),
+                _ => Ordering::Equal,
+            };
+            queue_class(fee1).cmp(&queue_class(fee2)).then(by_score)
+        });
+        Ok(scored.into_iter().map(|(fee, _)| fee).collect())
+    }
+}
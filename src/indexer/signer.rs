@@ -0,0 +1,127 @@
+//! Rotation of the submission key while the sweeper runs
+//!
+//! A submission key given as the ARN of a Secrets Manager secret is re-read
+//! before each redemption job. When the secret holds a new key that has been
+//! recorded as the active signer, the darkpool client is rebuilt around it.
+//! A new key not yet recorded as active is ignored until it is, so that a
+//! secret rotated ahead of `rotate-signer` never signs with an unfunded key.
+//! Any additional submitter keys are kept across a rotation
+
+use std::str::FromStr;
+
+use arbitrum_client::client::{ArbitrumClient, ArbitrumClientConfig};
+use ethers::signers::{LocalWallet, Signer};
+use ethers::types::Address;
+use tracing::{info, warn};
+
+use crate::aws::AwsContext;
+use crate::chain::EvmDarkpoolClient;
+use crate::db::metadata::{check_signer_is_active, ACTIVE_SIGNER_KEY};
+use crate::error::FeeSweeperError;
+use crate::secrets::KeySource;
+use crate::signer::SubmissionSigner;
+use crate::{Indexer, BLOCK_POLLING_INTERVAL_MS};
+
+/// The source of the submission key, and what is needed to rebuild the
+/// darkpool client around a rotated key
+#[derive(Clone, Debug)]
+pub struct SignerSource {
+    /// The submission key, or the secret holding it, unless the key is held
+    /// in KMS
+    pub key: Option<KeySource>,
+    /// The signer of the submission key, whose address is the run's primary
+    /// submitter
+    ///
+    /// The darkpool client takes only local keys, so a KMS signer is not given
+    /// to it; the client then reads the chain with an ephemeral key
+    pub signer: SubmissionSigner,
+    /// The additional keys the darkpool client submits with
+    pub submitters: Vec<LocalWallet>,
+    /// The address of the darkpool contract
+    pub darkpool_address: String,
+    /// The RPC url the darkpool client connects to
+    pub rpc_url: String,
+}
+
+impl Indexer {
+    /// Re-read a submission key held in a secret, switching to it if it has
+    /// rotated
+    ///
+    /// A failed read keeps the current key, so that a Secrets Manager hiccup
+    /// does not fail a sweep
+    pub async fn refresh_signer(&mut self) {
+        if let Err(e) = self.reload_signer().await {
+            warn!("failed to reload the submission key, keeping the current key: {e}");
+        }
+    }
+
+    /// Re-read the submission key and rebuild the darkpool client if it has
+    /// rotated to the active signer
+    async fn reload_signer(&mut self) -> Result<(), FeeSweeperError> {
+        let (source, key) = match &self.signer {
+            Some(source) => match &source.key {
+                Some(key) if key.is_secret() => (source.clone(), key.clone()),
+                _ => return Ok(()),
+            },
+            None => return Ok(()),
+        };
+
+        let key = key.resolve(&self.aws).await?;
+        let wallet = LocalWallet::from_str(&key)
+            .map_err(FeeSweeperError::config("invalid submission key"))?;
+        let address = wallet.address();
+        let current = source.signer.address();
+        if address == current {
+            return Ok(());
+        }
+        let active = self.get_metadata(ACTIVE_SIGNER_KEY).await;
+        if let Err(e) = active.and_then(|active| check_signer_is_active(active.as_deref(), address))
+        {
+            warn!("submission key secret holds a key that is not yet active, ignoring it: {e}");
+            return Ok(());
+        }
+
+        let conf = ArbitrumClientConfig {
+            darkpool_addr: source.darkpool_address.clone(),
+            chain: self.chain,
+            rpc_url: source.rpc_url.clone(),
+            arb_priv_keys: [vec![wallet.clone()], source.submitters.clone()].concat(),
+            block_polling_interval_ms: BLOCK_POLLING_INTERVAL_MS,
+        };
+        let client = ArbitrumClient::new(conf)
+            .await
+            .map_err(FeeSweeperError::rpc("failed to build darkpool client"))?;
+        self.darkpool_client = EvmDarkpoolClient::new(client).await?;
+        info!("submission key rotated from {current:#x} to {address:#x}");
+
+        if let Some(signer) = self.signer.as_mut() {
+            signer.signer = SubmissionSigner::Local(wallet);
+        }
+        Ok(())
+    }
+}
+
+/// Resolve the additional submitter keys, each of which must be distinct from
+/// the others and from the submission key
+pub(crate) async fn load_submitter_keys(
+    aws: &AwsContext,
+    keys: &[KeySource],
+    submission_address: Address,
+) -> Result<Vec<LocalWallet>, FeeSweeperError> {
+    let mut wallets: Vec<LocalWallet> = Vec::with_capacity(keys.len());
+    for key in keys {
+        let key = key.resolve(aws).await?;
+        let wallet = LocalWallet::from_str(&key)
+            .map_err(FeeSweeperError::config("invalid submitter key"))?;
+        let address = wallet.address();
+        if address == submission_address || wallets.iter().any(|w| w.address() == address) {
+            return Err(FeeSweeperError::Config(format!(
+                "duplicate submitter key {address:#x}"
+            )));
+        }
+
+        wallets.push(wallet);
+    }
+
+    Ok(wallets)
+}
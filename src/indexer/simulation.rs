@@ -0,0 +1,174 @@
+//! Simulation of note redemptions against the current chain state
+//!
+//! Before the relayer is asked to redeem a note, the redemption call is
+//! simulated with an `eth_call` against the latest state, so that redemptions
+//! which would revert are caught early and classified precisely.
+//!
+//! The settlement's proof and the recipient wallet's update are produced by
+//! the relayer, so the simulated call carries the note's half of the statement
+//! with placeholders for the rest. The darkpool checks the note's root and
+//! nullifier before the recipient's signature and the proof, so a call that
+//! reverts only on the placeholders is one whose note would redeem
+
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use alloy_sol_types::SolCall;
+use arbitrum_client::abi::redeemFeeCall;
+use arbitrum_client::conversion::to_contract_valid_fee_redemption_statement;
+use arbitrum_client::helpers::serialize_calldata;
+use ethers::middleware::Middleware;
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::types::{TransactionRequest, TxHash};
+use metrics::counter;
+use renegade_circuit_types::note::Note;
+use renegade_circuit_types::wallet::SizedWalletShare;
+use renegade_circuits::zk_circuits::valid_fee_redemption::SizedValidFeeRedemptionStatement;
+use renegade_constants::Scalar;
+use tracing::{info, warn};
+
+use crate::error::FeeSweeperError;
+use crate::fee_keys::FeeKey;
+use crate::telemetry::{
+    ETH_CALL, ETH_GET_LOGS, REDEMPTION_SIMULATION_FAILURE_METRIC, SIMULATION_OUTCOME_LABEL,
+};
+use crate::Indexer;
+
+/// The fragment of the darkpool's revert reason for a spent nullifier
+const NULLIFIER_SPENT_REVERT: &str = "nullifier";
+/// The fragment of the darkpool's revert reason for a root not in its history
+const INVALID_ROOT_REVERT: &str = "root";
+
+/// The outcome of simulating a note redemption
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum SimulationOutcome {
+    /// The redemption is expected to succeed
+    Success,
+    /// The note's nullifier has already been spent
+    NullifierSpent,
+    /// The note's commitment is not in the Merkle tree
+    CommitmentNotFound,
+    /// The darkpool rejects the Merkle root the note opens to
+    StaleMerkleRoot,
+}
+
+impl Display for SimulationOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SimulationOutcome::Success => write!(f, "success"),
+            SimulationOutcome::NullifierSpent => write!(f, "nullifier_spent"),
+            SimulationOutcome::CommitmentNotFound => write!(f, "commitment_not_found"),
+            SimulationOutcome::StaleMerkleRoot => write!(f, "stale_merkle_root"),
+        }
+    }
+}
+
+impl Indexer {
+    /// Simulate the redemption of the fee settled in the given tx, returning
+    /// whether the redemption should proceed
+    ///
+    /// A fee whose nullifier is already spent has been redeemed, so it is
+    /// marked as such rather than retried
+    pub(crate) async fn simulate_redemption(
+        &mut self,
+        tx: &str,
+        fee_key: &FeeKey,
+    ) -> Result<bool, FeeSweeperError> {
+        let tx_hash = TxHash::from_str(tx).map_err(FeeSweeperError::db("invalid tx hash"))?;
+        let note = self.get_note_from_tx(tx_hash, &fee_key.key).await?;
+
+        let outcome = self.simulate_note_redemption(&note).await?;
+        match outcome {
+            SimulationOutcome::Success => return Ok(true),
+            SimulationOutcome::NullifierSpent => {
+                info!("fee from tx {tx} already redeemed, marking as such");
+                self.mark_fee_as_redeemed(tx, None /* notice */).await?;
+            }
+            outcome => warn!("redemption of fee from tx {tx} would revert: {outcome}"),
+        }
+
+        counter!(REDEMPTION_SIMULATION_FAILURE_METRIC, SIMULATION_OUTCOME_LABEL => outcome.to_string())
+            .increment(1);
+        Ok(false)
+    }
+
+    /// Simulate the darkpool call redeeming a note
+    async fn simulate_note_redemption(
+        &self,
+        note: &Note,
+    ) -> Result<SimulationOutcome, FeeSweeperError> {
+        self.record_rpc(ETH_GET_LOGS);
+        let opening = match self
+            .darkpool_client
+            .darkpool()
+            .find_merkle_authentication_path(note.commitment())
+            .await
+        {
+            Ok(opening) => opening,
+            Err(e) => {
+                warn!("failed to find note commitment: {e}");
+                return Ok(SimulationOutcome::CommitmentNotFound);
+            }
+        };
+
+        // The recipient's half of the statement is left as placeholders,
+        // which the darkpool only reaches once the note's half has passed
+        let note_root = opening.compute_root();
+        let statement = SizedValidFeeRedemptionStatement {
+            wallet_root: note_root,
+            note_root,
+            nullifier: Scalar::zero(),
+            note_nullifier: note.nullifier(),
+            new_shares_commitment: Scalar::zero(),
+            new_wallet_public_shares: SizedWalletShare::default(),
+            recipient_root_key: Default::default(),
+        };
+        let statement = to_contract_valid_fee_redemption_statement(&statement).map_err(
+            FeeSweeperError::rpc("failed to convert redemption statement"),
+        )?;
+        let statement = serialize_calldata(&statement).map_err(FeeSweeperError::rpc(
+            "failed to serialize redemption statement",
+        ))?;
+        let calldata = redeemFeeCall {
+            proof: Vec::new(),
+            valid_fee_redemption_statement: statement,
+            recipient_wallet_commitment_signature: Vec::new(),
+        }
+        .abi_encode();
+
+        let darkpool = self.darkpool_client.darkpool().get_darkpool_client();
+        let tx: TypedTransaction = TransactionRequest::new()
+            .to(darkpool.address())
+            .data(calldata)
+            .into();
+        self.record_rpc(ETH_CALL);
+        let err = match darkpool.client().call(&tx, None).await {
+            Ok(_) => return Ok(SimulationOutcome::Success),
+            Err(e) => e,
+        };
+        let Some(reason) = err
+            .as_error_response()
+            .map(|resp| resp.message.to_lowercase())
+        else {
+            return Err(FeeSweeperError::Rpc(format!(
+                "failed to simulate redemption: {err}"
+            )));
+        };
+
+        Ok(classify_revert(&reason))
+    }
+}
+
+/// Classify the revert reason of a simulated redemption
+///
+/// A revert on anything but the note's root or nullifier is a revert on the
+/// placeholders, which the relayer fills in
+fn classify_revert(reason: &str) -> SimulationOutcome {
+    if reason.contains(NULLIFIER_SPENT_REVERT) {
+        SimulationOutcome::NullifierSpent
+    } else if reason.contains(INVALID_ROOT_REVERT) {
+        SimulationOutcome::StaleMerkleRoot
+    } else {
+        SimulationOutcome::Success
+    }
+}
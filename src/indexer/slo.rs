@@ -0,0 +1,160 @@
+//! The redemption latency SLO and its burn rate alerts
+//!
+//! The SLO requires that a share of fees, the objective, be redeemed within a
+//! target latency of being indexed. Its SLI over a window is the share of the
+//! fees that fell due in the window, i.e. whose target latency elapsed, that
+//! were redeemed in time; a fee still unredeemed when it falls due misses the
+//! target. Both are computed from the timestamps in the DB, so that every
+//! instance reports the same numbers whatever its uptime.
+//!
+//! The error budget is the share of fees allowed to miss the target, and a
+//! window's burn rate is the rate at which it spends the budget, one when
+//! spending it exactly as fast as the objective allows. Following the
+//! multi-window approach, an alert is raised only while both a long and a
+//! short window burn faster than the threshold: the long window makes the
+//! alert significant, the short one resets it promptly once the burn stops
+
+use std::time::Duration;
+
+use clap::Args;
+use metrics::gauge;
+use tracing::info;
+
+use crate::error::FeeSweeperError;
+use crate::notifications::ALERTS_CHANNEL;
+use crate::telemetry::{
+    REDEMPTION_SLI_METRIC, REDEMPTION_SLO_BUDGET_METRIC, REDEMPTION_SLO_BURN_RATE_METRIC,
+    SLO_WINDOW_LABEL,
+};
+use crate::Indexer;
+
+/// The arguments configuring the redemption latency SLO
+#[derive(Clone, Debug, Args)]
+pub struct SloArgs {
+    /// The share of fees that must be redeemed within the target latency of
+    /// being indexed, between zero and one exclusive
+    #[clap(long, default_value_t = 0.95, value_parser = parse_objective)]
+    pub redemption_slo_objective: f64,
+    /// The target latency, in seconds, from a fee's indexing to its
+    /// redemption
+    #[clap(long, default_value_t = 86_400)]
+    pub redemption_slo_target_secs: u64,
+    /// The period, in seconds, over which compliance with the SLO and the
+    /// remaining error budget are reported
+    #[clap(long, default_value_t = 30 * 86_400)]
+    pub redemption_slo_period_secs: u64,
+    /// The long window, in seconds, of the burn rate alert
+    #[clap(long, default_value_t = 6 * 3_600)]
+    pub slo_long_window_secs: u64,
+    /// The short window, in seconds, of the burn rate alert
+    #[clap(long, default_value_t = 1_800)]
+    pub slo_short_window_secs: u64,
+    /// The burn rate both windows must exceed for the SLO to be alerted on
+    #[clap(long, default_value_t = 6.0)]
+    pub slo_burn_rate_threshold: f64,
+}
+
+/// Parse an SLO objective, a share strictly between zero and one
+fn parse_objective(value: &str) -> Result<f64, String> {
+    let objective: f64 = value
+        .parse()
+        .map_err(|e| format!("invalid objective {value}: {e}"))?;
+    if !(objective > 0. && objective < 1.) {
+        return Err(format!("objective must be between 0 and 1: {value}"));
+    }
+
+    Ok(objective)
+}
+
+/// The counts of the fees that fell due within an SLO window
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SloWindowCounts {
+    /// The number of fees whose target latency elapsed within the window
+    pub due: i64,
+    /// The number of those fees redeemed within the target latency
+    pub met: i64,
+}
+
+impl SloWindowCounts {
+    /// The share of due fees redeemed in time, one if no fee fell due
+    pub fn sli(&self) -> f64 {
+        match self.due {
+            0 => 1.,
+            due => self.met as f64 / due as f64,
+        }
+    }
+
+    /// The rate at which the window spends an objective's error budget
+    pub fn burn_rate(&self, objective: f64) -> f64 {
+        (1. - self.sli()) / (1. - objective)
+    }
+}
+
+impl Indexer {
+    /// Compute the redemption SLO over its compliance period and alert
+    /// windows, recording the SLIs and burn rates and alerting if the error
+    /// budget burns faster than the threshold
+    pub async fn evaluate_slo(&mut self) -> Result<(), FeeSweeperError> {
+        let args = self.slo.clone();
+        let objective = args.redemption_slo_objective;
+        let target = Duration::from_secs(args.redemption_slo_target_secs);
+
+        let period = self
+            .record_slo_window("period", target, args.redemption_slo_period_secs, objective)
+            .await?;
+        let long = self
+            .record_slo_window("long", target, args.slo_long_window_secs, objective)
+            .await?;
+        let short = self
+            .record_slo_window("short", target, args.slo_short_window_secs, objective)
+            .await?;
+
+        let budget_remaining = 1. - period.burn_rate(objective);
+        gauge!(REDEMPTION_SLO_BUDGET_METRIC).set(budget_remaining);
+        info!(
+            "redemption SLO: {:.2}% of {} fees redeemed within {}s, \
+             {:.1}% of the error budget remaining",
+            period.sli() * 100.,
+            period.due,
+            args.redemption_slo_target_secs,
+            budget_remaining * 100.,
+        );
+
+        let (long_rate, short_rate) = (long.burn_rate(objective), short.burn_rate(objective));
+        let threshold = args.slo_burn_rate_threshold;
+        if long_rate > threshold && short_rate > threshold {
+            let notice = format!(
+                "redemption SLO burning its error budget at {long_rate:.1}x over the last {}s \
+                 and {short_rate:.1}x over the last {}s: {} of {} fees due missed the {}s \
+                 target; {:.1}% of the budget remains",
+                args.slo_long_window_secs,
+                args.slo_short_window_secs,
+                long.due - long.met,
+                long.due,
+                args.redemption_slo_target_secs,
+                budget_remaining * 100.,
+            );
+            self.notify(ALERTS_CHANNEL, notice).await;
+        }
+
+        Ok(())
+    }
+
+    /// Compute the SLI and burn rate of a window, recording them as metrics
+    /// labeled with the window's name
+    async fn record_slo_window(
+        &mut self,
+        name: &'static str,
+        target: Duration,
+        window_secs: u64,
+        objective: f64,
+    ) -> Result<SloWindowCounts, FeeSweeperError> {
+        let window = Duration::from_secs(window_secs);
+        let counts = self.get_slo_window_counts(target, window).await?;
+        gauge!(REDEMPTION_SLI_METRIC, SLO_WINDOW_LABEL => name).set(counts.sli());
+        gauge!(REDEMPTION_SLO_BURN_RATE_METRIC, SLO_WINDOW_LABEL => name)
+            .set(counts.burn_rate(objective));
+
+        Ok(counts)
+    }
+}
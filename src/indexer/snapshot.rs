@@ -0,0 +1,108 @@
+//! Snapshots of fee aggregates taken from the DB around each sweep
+//!
+//! The run summary reports the change in these aggregates between the start
+//! and end of a sweep. Because both snapshots are read from the DB rather
+//! than accumulated in memory, the summary reflects every change the sweep
+//! committed, even when a later phase of it failed. Each summary is also
+//! recorded in the DB, so that recent runs may be reviewed without their logs
+
+use bigdecimal::BigDecimal;
+use chrono::NaiveDateTime;
+use tracing::{info, warn};
+
+use crate::db::models::NewRunSummary;
+use crate::Indexer;
+
+/// Aggregates of the fees in the DB at a point in time
+#[derive(Clone, Debug, Default)]
+pub struct FeeSnapshot {
+    /// The number of fees awaiting redemption
+    pub unredeemed_count: i64,
+    /// The USD value of the fees awaiting redemption
+    pub unredeemed_value: BigDecimal,
+    /// The number of redeemed fees
+    pub redeemed_count: i64,
+    /// The USD value of the redeemed fees
+    pub redeemed_value: BigDecimal,
+    /// The number of dead-lettered fees
+    pub dead_lettered_count: i64,
+    /// The number of abandoned fees
+    pub abandoned_count: i64,
+}
+
+impl FeeSnapshot {
+    /// Describe the aggregates
+    pub fn describe(&self) -> String {
+        format!(
+            "unredeemed {} (${}), redeemed {} (${}), dead-lettered {}, abandoned {}",
+            self.unredeemed_count,
+            self.unredeemed_value.round(2 /* round_digits */),
+            self.redeemed_count,
+            self.redeemed_value.round(2 /* round_digits */),
+            self.dead_lettered_count,
+            self.abandoned_count,
+        )
+    }
+
+    /// Describe the change from an earlier snapshot to this one
+    pub fn describe_change_from(&self, before: &FeeSnapshot) -> String {
+        format!(
+            "unredeemed {} (${}) -> {} (${}), redeemed {:+} (${}), \
+             dead-lettered {:+}, abandoned {:+}",
+            before.unredeemed_count,
+            before.unredeemed_value.round(2 /* round_digits */),
+            self.unredeemed_count,
+            self.unredeemed_value.round(2 /* round_digits */),
+            self.redeemed_count - before.redeemed_count,
+            (&self.redeemed_value - &before.redeemed_value).round(2 /* round_digits */),
+            self.dead_lettered_count - before.dead_lettered_count,
+            self.abandoned_count - before.abandoned_count,
+        )
+    }
+}
+
+impl Indexer {
+    /// Snapshot the fee aggregates at the start of a sweep, returning the
+    /// time the sweep started with the snapshot
+    ///
+    /// A failure is logged rather than returned, so that the summary never
+    /// blocks a sweep
+    pub(crate) async fn begin_summary(&mut self) -> Option<(NaiveDateTime, FeeSnapshot)> {
+        let started_at = self.clock.naive_now();
+        match self.get_fee_snapshot().await {
+            Ok(snapshot) => Some((started_at, snapshot)),
+            Err(e) => {
+                warn!("failed to snapshot fees, skipping the run summary: {e}");
+                None
+            }
+        }
+    }
+
+    /// Snapshot the fee aggregates at the end of a sweep, and log and record
+    /// the change since the start
+    pub(crate) async fn end_summary(&mut self, before: Option<(NaiveDateTime, FeeSnapshot)>) {
+        let (started_at, before) = match before {
+            Some(before) => before,
+            None => return,
+        };
+
+        let summary = match self.get_fee_snapshot().await {
+            Ok(after) => after.describe_change_from(&before),
+            Err(e) => {
+                warn!("failed to snapshot fees, skipping the run summary: {e}");
+                return;
+            }
+        };
+        info!("run summary: {summary}");
+
+        let record = NewRunSummary {
+            run_id: self.run_id,
+            started_at,
+            finished_at: self.clock.naive_now(),
+            summary,
+        };
+        if let Err(e) = self.record_run_summary(record).await {
+            warn!("failed to record the run summary: {e}");
+        }
+    }
+}
@@ -0,0 +1,230 @@
+//! Heuristics quarantining mints suspected to be spam
+//!
+//! Spam tokens are sent to the protocol to poison the sweep wallet, e.g. with
+//! tokens that tax or block transfers out. Before each redemption pass, every
+//! mint with unredeemed fees that has never been quarantined is screened:
+//! - a mint that fails its token safety probe is quarantined
+//! - a mint whose fees have gone unpriced for `max_unpriced_days` is
+//!   quarantined, as no market values it
+//! - a mint whose contract source is not verified on the block explorer is
+//!   quarantined, if an explorer is configured. A mint whose verification the
+//!   explorer fails to report is held back from this pass, not quarantined,
+//!   and checked again in the next
+//! - a mint that transfers part of a fee into its own contract, the common
+//!   pattern of a transfer tax, is quarantined
+//!
+//! Fees in a quarantined mint are not redeemed until an operator releases it
+
+use std::str::FromStr;
+
+use chrono::Duration;
+use clap::Args;
+use ethers::middleware::Middleware;
+use ethers::types::{Address, TxHash, H256};
+use ethers::utils::keccak256;
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::warn;
+
+use super::event_source::EXPLORER_STATUS_OK;
+use crate::db::models::FEE_SOURCE_EXTERNAL_MATCH;
+use crate::error::FeeSweeperError;
+use crate::notifications::ALERTS_CHANNEL;
+use crate::telemetry::ETH_GET_RECEIPT;
+use crate::Indexer;
+
+/// The signature of the ERC-20 `Transfer` event
+const ERC20_TRANSFER_SIGNATURE: &str = "Transfer(address,address,uint256)";
+
+/// The arguments configuring the spam heuristics
+#[derive(Clone, Debug, Args)]
+pub struct SpamArgs {
+    /// The number of days a mint's fees may go unpriced before the mint is
+    /// quarantined
+    #[clap(long, default_value_t = 7)]
+    pub max_unpriced_days: i64,
+    /// The base URL of an Etherscan-compatible block explorer API, against
+    /// which mint contracts are checked for verified source, and from which
+    /// backfills may read events
+    #[clap(long)]
+    pub explorer_api_url: Option<String>,
+    /// The API key of the block explorer
+    #[clap(long)]
+    pub explorer_api_key: Option<String>,
+    /// Simulate a transfer out of the darkpool when probing a new mint,
+    /// catching tokens that cannot be moved once received
+    #[clap(long)]
+    pub honeypot_probe: bool,
+}
+
+/// The response of the explorer's `getsourcecode` action
+#[derive(Deserialize)]
+struct SourceCodeResponse {
+    /// `1` on success, `0` on an error
+    status: String,
+    /// A description of the status
+    message: String,
+    /// The verified contracts at the address on success, an error message
+    /// otherwise
+    result: Value,
+}
+
+/// A contract entry of the explorer's `getsourcecode` action
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SourceCodeResult {
+    /// The verified source, empty if the contract is unverified
+    source_code: String,
+}
+
+/// The outcome of screening a mint
+enum Screening {
+    /// The mint passed every heuristic
+    Clean,
+    /// The mint looks like spam, for the given reason
+    Spam(String),
+    /// The mint could not be screened, for the given reason
+    Unknown(String),
+}
+
+impl Indexer {
+    /// Screen the given mints, quarantining those that look like spam
+    ///
+    /// Returns the mints that are not under quarantine and were screened
+    pub(crate) async fn screen_mints(
+        &mut self,
+        mints: Vec<String>,
+    ) -> Result<Vec<String>, FeeSweeperError> {
+        let quarantines = self.get_quarantines().await?;
+
+        let mut allowed = Vec::new();
+        for mint in mints {
+            // Mints quarantined before are either still quarantined or were
+            // released by an operator, and are not screened again
+            if let Some(quarantine) = quarantines.iter().find(|q| q.mint == mint) {
+                if quarantine.released_at.is_some() {
+                    allowed.push(mint);
+                }
+                continue;
+            }
+
+            match self.detect_spam(&mint).await? {
+                Screening::Spam(reason) => {
+                    warn!("quarantining {mint}: {reason}");
+                    let quarantined = self.quarantine_mint(&mint, &reason).await?;
+                    if quarantined {
+                        let notice = format!("quarantined {mint}: {reason}");
+                        self.notify(ALERTS_CHANNEL, notice).await;
+                    }
+                }
+                Screening::Unknown(reason) => {
+                    warn!("holding back {mint} until it can be screened: {reason}");
+                }
+                Screening::Clean => allowed.push(mint),
+            }
+        }
+
+        Ok(allowed)
+    }
+
+    /// Run the spam heuristics on a mint
+    async fn detect_spam(&mut self, mint: &str) -> Result<Screening, FeeSweeperError> {
+        // A failed safety probe of the token contract
+        if let Some(failure) = self.probe_token(mint).await? {
+            return Ok(Screening::Spam(format!("failed safety probe: {failure}")));
+        }
+
+        // No price in `max_unpriced_days`
+        let (oldest_fee, n_priced) = self.get_mint_price_coverage(mint).await?;
+        let max_unpriced = Duration::days(self.spam.max_unpriced_days);
+        if let Some(oldest) = oldest_fee {
+            if n_priced == 0 && self.clock.naive_now() - oldest > max_unpriced {
+                let days = self.spam.max_unpriced_days;
+                return Ok(Screening::Spam(format!("no price in {days} days")));
+            }
+        }
+
+        // Unverified contract source
+        if self.spam.explorer_api_url.is_some() {
+            match self.is_source_verified(mint).await {
+                Ok(true) => {}
+                Ok(false) => return Ok(Screening::Spam("contract source unverified".to_string())),
+                Err(e) => return Ok(Screening::Unknown(e.to_string())),
+            }
+        }
+
+        // Transfer into its own contract in a fee transfer, a receipt lookup
+        // skipped while the run is short of RPC budget, as the mint is screened
+        // again in the next run
+        if self.rpc_budget_constrained() {
+            return Ok(Screening::Clean);
+        }
+        if let Some(tx) = self
+            .get_latest_fee_tx(mint, FEE_SOURCE_EXTERNAL_MATCH)
+            .await?
+        {
+            if self.transfers_to_self(mint, &tx).await? {
+                return Ok(Screening::Spam(format!("transfer tax in tx {tx}")));
+            }
+        }
+
+        Ok(Screening::Clean)
+    }
+
+    /// Whether a mint's contract source is verified on the block explorer
+    ///
+    /// Errors if the explorer fails to report it, e.g. when rate limited, in
+    /// which case verification is unknown
+    async fn is_source_verified(&self, mint: &str) -> Result<bool, FeeSweeperError> {
+        let base_url = self.spam.explorer_api_url.as_deref().unwrap_or_default();
+        let mut url = format!("{base_url}/api?module=contract&action=getsourcecode&address={mint}");
+        if let Some(key) = &self.spam.explorer_api_key {
+            url.push_str(&format!("&apikey={key}"));
+        }
+
+        let resp: SourceCodeResponse = self
+            .explorer_client
+            .get(&url)
+            .send()
+            .await
+            .map_err(FeeSweeperError::rpc("failed to query explorer"))?
+            .json()
+            .await
+            .map_err(FeeSweeperError::rpc("failed to parse explorer response"))?;
+        if resp.status != EXPLORER_STATUS_OK {
+            return Err(FeeSweeperError::Rpc(format!(
+                "explorer returned an error: {} ({})",
+                resp.message, resp.result
+            )));
+        }
+
+        let contracts: Vec<SourceCodeResult> = serde_json::from_value(resp.result)
+            .map_err(FeeSweeperError::rpc("failed to parse explorer source code"))?;
+        Ok(contracts.iter().any(|res| !res.source_code.is_empty()))
+    }
+
+    /// Whether a mint emitted a transfer into its own contract in the given
+    /// transaction
+    async fn transfers_to_self(&self, mint: &str, tx: &str) -> Result<bool, FeeSweeperError> {
+        let mint = Address::from_str(mint).map_err(FeeSweeperError::db("invalid mint"))?;
+        let tx_hash = TxHash::from_str(tx).map_err(FeeSweeperError::db("invalid tx hash"))?;
+        self.record_rpc(ETH_GET_RECEIPT);
+        let receipt = self
+            .darkpool_client
+            .darkpool()
+            .get_darkpool_client()
+            .client()
+            .get_transaction_receipt(tx_hash)
+            .await
+            .map_err(FeeSweeperError::rpc("failed to query receipt"))?
+            .ok_or_else(|| FeeSweeperError::Rpc(format!("receipt not found: {tx}")))?;
+
+        let transfer_topic = H256::from(keccak256(ERC20_TRANSFER_SIGNATURE));
+        let self_topic = H256::from(mint);
+        Ok(receipt.logs.iter().any(|log| {
+            log.address == mint
+                && log.topics.first() == Some(&transfer_topic)
+                && log.topics.get(2) == Some(&self_topic)
+        }))
+    }
+}
@@ -0,0 +1,134 @@
+//! A safety probe of each new mint's token contract, run once before the
+//! mint's first redemption
+//!
+//! The probe checks that contract code is present at the mint, i.e. that it
+//! has not self-destructed, and that it reports sane decimals. Optionally, a
+//! transfer out of the darkpool is simulated to catch honeypot tokens that
+//! cannot be moved once received. The verdict is stored so that each mint is
+//! probed only once; a mint that fails is quarantined.
+//!
+//! A call fails the probe only if it reverts or returns data that does not
+//! decode; any other error, e.g. a dropped RPC connection, says nothing about
+//! the token and is returned so that the probe is retried
+
+use std::str::FromStr;
+
+use ethers::contract::ContractError;
+use ethers::middleware::Middleware;
+use ethers::types::{Address, U256};
+use renegade_common::types::token::Token;
+use tracing::info;
+
+use crate::db::models::NewTokenProbe;
+use crate::erc20::Erc20;
+use crate::error::FeeSweeperError;
+use crate::telemetry::{ETH_CALL, ETH_GET_CODE};
+use crate::Indexer;
+
+/// The largest number of decimals a sane token reports
+const MAX_SANE_DECIMALS: u8 = 36;
+/// The recipient of the simulated honeypot transfer
+const PROBE_RECIPIENT: &str = "0x000000000000000000000000000000000000dEaD";
+
+impl Indexer {
+    /// Probe a mint's token contract if it has not been probed before
+    ///
+    /// Returns the reason the mint failed its probe, if it did
+    pub(crate) async fn probe_token(
+        &mut self,
+        mint: &str,
+    ) -> Result<Option<String>, FeeSweeperError> {
+        if let Some(probe) = self.get_token_probe(mint).await? {
+            return Ok((!probe.passed).then_some(probe.details));
+        }
+
+        let failure = self.run_token_probe(mint).await?;
+        let (passed, details) = match &failure {
+            Some(reason) => (false, reason.clone()),
+            None => (true, "passed".to_string()),
+        };
+        info!("probed token {mint}: {details}");
+
+        let probe = NewTokenProbe {
+            mint: mint.to_string(),
+            passed,
+            details,
+        };
+        self.record_token_probe(probe).await?;
+        Ok(failure)
+    }
+
+    /// Run the probe checks against a mint, returning the first failure
+    async fn run_token_probe(&self, mint: &str) -> Result<Option<String>, FeeSweeperError> {
+        let darkpool = self.darkpool_client.darkpool().get_darkpool_client();
+        let client = darkpool.client();
+        let address = Address::from_str(mint).map_err(FeeSweeperError::db("invalid mint"))?;
+
+        // Code is present, so the contract exists and has not self-destructed
+        self.record_rpc(ETH_GET_CODE);
+        let code = client
+            .get_code(address, None)
+            .await
+            .map_err(FeeSweeperError::rpc("failed to query token code"))?;
+        if code.is_empty() {
+            return Ok(Some("no contract code".to_string()));
+        }
+
+        // Decimals are sane, and match those known for the token if any
+        let token = Erc20::new(address, client);
+        self.record_rpc(ETH_CALL);
+        let decimals = match token.decimals().call().await {
+            Ok(decimals) => decimals,
+            Err(e) if is_token_failure(&e) => return Ok(Some(format!("decimals() failed: {e}"))),
+            Err(e) => {
+                return Err(FeeSweeperError::Rpc(format!(
+                    "failed to query decimals: {e}"
+                )))
+            }
+        };
+        if decimals > MAX_SANE_DECIMALS {
+            return Ok(Some(format!("insane decimals: {decimals}")));
+        }
+        if let Some(known) = Token::from_addr(mint).get_decimals() {
+            if known != decimals {
+                return Ok(Some(format!(
+                    "reports {decimals} decimals, expected {known}"
+                )));
+            }
+        }
+
+        // A transfer out of the darkpool succeeds
+        if self.spam.honeypot_probe {
+            let recipient = Address::from_str(PROBE_RECIPIENT).unwrap();
+            let transfer = token
+                .transfer(recipient, U256::one())
+                .from(darkpool.address());
+            self.record_rpc(ETH_CALL);
+            match transfer.call().await {
+                Ok(_) => {}
+                Err(e) if is_token_failure(&e) => {
+                    return Ok(Some(format!("simulated transfer failed: {e}")));
+                }
+                Err(e) => {
+                    return Err(FeeSweeperError::Rpc(format!(
+                        "failed to simulate transfer: {e}"
+                    )))
+                }
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// Whether a failed call reflects on the token itself, i.e. the call reverted
+/// or returned undecodable data, rather than on the RPC serving it
+fn is_token_failure<M: Middleware>(e: &ContractError<M>) -> bool {
+    e.is_revert()
+        || matches!(
+            e,
+            ContractError::DecodingError(_)
+                | ContractError::AbiError(_)
+                | ContractError::DetokenizationError(_)
+        )
+}
@@ -0,0 +1,127 @@
+//! Backfills USD valuations for fees using a historical price source
+
+use std::str::FromStr;
+
+use bigdecimal::{BigDecimal, FromPrimitive};
+use chrono::{DateTime, Duration, NaiveDateTime};
+use ethers::middleware::Middleware;
+use ethers::types::TxHash;
+use num_bigint::BigInt;
+use renegade_common::types::token::Token;
+use tracing::{info, warn};
+
+use crate::db::models::Fee;
+use crate::error::FeeSweeperError;
+use crate::telemetry::{ETH_GET_BLOCK, ETH_GET_TRANSACTION};
+use crate::Indexer;
+
+/// The maximum number of fees to value in a given run of the indexer
+pub(crate) const MAX_FEES_VALUED: i64 = 100;
+/// The time after a failed attempt to value a fee before it is tried again
+const VALUATION_RETRY_INTERVAL_HOURS: i64 = 6;
+
+impl Indexer {
+    /// Fill in missing USD valuations for indexed fees, both redeemed and
+    /// unredeemed
+    ///
+    /// A fee that cannot be valued, e.g. as its price source has no price for
+    /// it, has the attempt recorded, and is retried only after the others
+    pub async fn value_fees(&mut self) -> Result<(), FeeSweeperError> {
+        let retry_interval = Duration::hours(VALUATION_RETRY_INTERVAL_HOURS);
+        let fees = self
+            .get_unvalued_fees(MAX_FEES_VALUED, retry_interval)
+            .await?;
+        info!("valuing {} fees...", fees.len());
+
+        // A failure to value one fee should not block valuing the rest
+        for fee in fees.iter() {
+            self.check_db_budget()?;
+            self.check_rpc_budget()?;
+            let valued = match self.value_fee(fee).await {
+                Ok(valued) => valued,
+                Err(e) => {
+                    warn!("failed to value fee from tx {}: {e}", fee.tx_hash);
+                    false
+                }
+            };
+            if !valued {
+                self.record_valuation_attempt(fee.id).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Value a single fee at the price of its mint when it was indexed
+    ///
+    /// Returns whether the fee was valued, i.e. a price was found for it
+    async fn value_fee(&mut self, fee: &Fee) -> Result<bool, FeeSweeperError> {
+        let decimals = Token::from_addr(&fee.mint)
+            .get_decimals()
+            .ok_or_else(|| FeeSweeperError::Other(format!("unknown decimals for {}", fee.mint)))?;
+
+        let (block_number, block_timestamp) = self.get_fee_block(fee).await?;
+        let maybe_price = self
+            .historical_price_client
+            .get_historical_price(&fee.mint, block_timestamp.and_utc().timestamp())
+            .await?;
+        let price = match maybe_price {
+            Some(price) => price,
+            None => {
+                warn!("{}: no historical price at {block_timestamp}", fee.mint);
+                return Ok(false);
+            }
+        };
+
+        // Value the fee in whole units of the token
+        let usd_price = BigDecimal::from_f64(price)
+            .ok_or_else(|| FeeSweeperError::Rpc(format!("invalid historical price: {price}")))?;
+        let unit = BigDecimal::new(BigInt::from(1), decimals as i64);
+        let usd_value = &fee.amount * &usd_price * unit;
+
+        self.set_fee_valuation(fee.id, block_number, block_timestamp, usd_price, usd_value)
+            .await?;
+        Ok(true)
+    }
+
+    /// Get the block number and timestamp at which a fee was settled
+    async fn get_fee_block(&self, fee: &Fee) -> Result<(i64, NaiveDateTime), FeeSweeperError> {
+        let client = self
+            .darkpool_client
+            .darkpool()
+            .get_darkpool_client()
+            .client();
+
+        // Fees indexed before block numbers were recorded are looked up by tx
+        let block_number = match fee.block_number {
+            Some(block_number) => block_number as u64,
+            None => {
+                let tx_hash = TxHash::from_str(&fee.tx_hash)
+                    .map_err(FeeSweeperError::db("invalid tx hash"))?;
+                self.record_rpc(ETH_GET_TRANSACTION);
+                client
+                    .get_transaction(tx_hash)
+                    .await
+                    .map_err(FeeSweeperError::rpc("failed to query tx"))?
+                    .and_then(|tx| tx.block_number)
+                    .ok_or_else(|| FeeSweeperError::Rpc(format!("tx not mined: {}", fee.tx_hash)))?
+                    .as_u64()
+            }
+        };
+
+        self.record_rpc(ETH_GET_BLOCK);
+        let block = client
+            .get_block(block_number)
+            .await
+            .map_err(FeeSweeperError::rpc("failed to query block"))?
+            .ok_or_else(|| FeeSweeperError::Rpc(format!("block not found: {block_number}")))?;
+        let timestamp =
+            DateTime::from_timestamp(block.timestamp.as_u64() as i64, 0 /* nsecs */)
+                .ok_or_else(|| {
+                    FeeSweeperError::Rpc(format!("invalid block timestamp: {}", block.timestamp))
+                })?
+                .naive_utc();
+
+        Ok((block_number as i64, timestamp))
+    }
+}
@@ -0,0 +1,172 @@
+//! Backups of the recovery material of sweep wallets
+//!
+//! A sweep wallet's root key is stored in Secrets Manager when the wallet is
+//! created, and is all that is needed to recover the wallet. So that recovery
+//! does not hinge on that one secret, the wallet's recovery material is also
+//! written to an S3 bucket, encrypted with KMS. A failed backup does not fail
+//! the redemption creating the wallet, as the wallet exists on-chain by then;
+//! it is recorded in the audit log and alerted on instead
+
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::types::ServerSideEncryption;
+use aws_sdk_s3::Client as S3Client;
+use chrono::NaiveDateTime;
+use clap::Args;
+use ethers::signers::LocalWallet;
+use ethers::utils::hex;
+use renegade_common::types::wallet::derivation::{derive_blinder_seed, derive_share_seed};
+use renegade_common::types::wallet::WalletIdentifier;
+use renegade_crypto::fields::scalar_to_biguint;
+use serde::Serialize;
+use tracing::{error, info};
+
+use crate::aws::{record_aws_call, S3_SERVICE};
+use crate::db::audit::WALLET_BACKUP_EVENT;
+use crate::db::models::NewAuditEvent;
+use crate::error::FeeSweeperError;
+use crate::fee_keys::FeeKind;
+use crate::notifications::ALERTS_CHANNEL;
+use crate::Indexer;
+
+/// The default key prefix of wallet backups in the bucket
+const DEFAULT_WALLET_BACKUP_PREFIX: &str = "wallet-backups";
+
+/// The arguments configuring backups of sweep wallets
+#[derive(Clone, Debug, Args)]
+pub struct WalletBackupArgs {
+    /// The S3 bucket to back up the recovery material of new sweep wallets to;
+    /// wallets are not backed up if unset
+    #[clap(long)]
+    pub wallet_backup_bucket: Option<String>,
+    /// The key prefix of wallet backups in the bucket
+    #[clap(long, default_value = DEFAULT_WALLET_BACKUP_PREFIX)]
+    pub wallet_backup_prefix: String,
+    /// The KMS key encrypting wallet secrets and their backups; each service's
+    /// AWS managed key if unset
+    #[clap(long)]
+    pub wallet_kms_key_id: Option<String>,
+}
+
+/// The recovery material of a sweep wallet, as backed up
+#[derive(Serialize)]
+struct WalletRecoveryMaterial {
+    /// The chain the wallet was created on
+    chain: String,
+    /// The id of the wallet
+    wallet_id: WalletIdentifier,
+    /// The kind of fees the wallet holds
+    fee_kind: String,
+    /// The name of the wallet's secret in Secrets Manager
+    secret_name: String,
+    /// The hex-encoded root Ethereum key the wallet is derived from
+    root_key: String,
+    /// The hex-encoded seed of the wallet's blinder stream
+    blinder_seed: String,
+    /// The hex-encoded seed of the wallet's secret share stream
+    share_seed: String,
+    /// The time the backup was taken
+    backed_up_at: NaiveDateTime,
+}
+
+impl Indexer {
+    /// Back up the recovery material of a new sweep wallet, if backups are
+    /// configured
+    ///
+    /// A failure is alerted on rather than returned
+    pub(crate) async fn backup_wallet(
+        &mut self,
+        wallet_id: WalletIdentifier,
+        root_key: &LocalWallet,
+        secret_name: &str,
+        kind: FeeKind,
+    ) {
+        let bucket = match self.wallet_backup.wallet_backup_bucket.clone() {
+            Some(bucket) => bucket,
+            None => return,
+        };
+
+        let details = match self
+            .upload_wallet_backup(&bucket, wallet_id, root_key, secret_name, kind)
+            .await
+        {
+            Ok(key) => {
+                info!("backed up wallet {wallet_id} to s3://{bucket}/{key}");
+                format!("backed up {wallet_id} to s3://{bucket}/{key}")
+            }
+            Err(e) => {
+                error!("failed to back up wallet {wallet_id}: {e}");
+                let notice = format!("failed to back up new sweep wallet {wallet_id}: {e}");
+                self.notify(ALERTS_CHANNEL, notice).await;
+                format!("failed to back up {wallet_id}: {e}")
+            }
+        };
+
+        let event = NewAuditEvent::new(WALLET_BACKUP_EVENT, None, details);
+        if let Err(e) = self.record_audit_event(event).await {
+            error!("failed to record backup of wallet {wallet_id}: {e}");
+        }
+    }
+
+    /// Upload the recovery material of a wallet to the backup bucket,
+    /// encrypted with KMS
+    ///
+    /// Returns the key of the backup
+    async fn upload_wallet_backup(
+        &self,
+        bucket: &str,
+        wallet_id: WalletIdentifier,
+        root_key: &LocalWallet,
+        secret_name: &str,
+        kind: FeeKind,
+    ) -> Result<String, FeeSweeperError> {
+        let blinder_seed = derive_blinder_seed(root_key).map_err(FeeSweeperError::Other)?;
+        let share_seed = derive_share_seed(root_key).map_err(FeeSweeperError::Other)?;
+        let material = WalletRecoveryMaterial {
+            chain: self.chain.to_string(),
+            wallet_id,
+            fee_kind: kind.to_string(),
+            secret_name: secret_name.to_string(),
+            root_key: hex::encode(root_key.signer().to_bytes()),
+            blinder_seed: scalar_to_biguint(&blinder_seed).to_str_radix(16),
+            share_seed: scalar_to_biguint(&share_seed).to_str_radix(16),
+            backed_up_at: self.clock.naive_now(),
+        };
+        let body = serde_json::to_vec_pretty(&material)
+            .map_err(FeeSweeperError::other("failed to serialize wallet backup"))?;
+
+        let prefix = self
+            .wallet_backup
+            .wallet_backup_prefix
+            .trim_end_matches('/');
+        let key = format!("{prefix}/{}/{wallet_id}.json", self.chain);
+        let tagging: Vec<String> = self
+            .aws
+            .cost_tags
+            .iter()
+            .map(|tag| format!("{}={}", tag.key, tag.value))
+            .collect();
+
+        let client = S3Client::new(&self.aws.config);
+        let mut request = client
+            .put_object()
+            .bucket(bucket)
+            .key(&key)
+            .body(ByteStream::from(body))
+            .content_type("application/json")
+            .server_side_encryption(ServerSideEncryption::AwsKms);
+        if let Some(kms_key_id) = &self.wallet_backup.wallet_kms_key_id {
+            request = request.ssekms_key_id(kms_key_id);
+        }
+        if !tagging.is_empty() {
+            request = request.tagging(tagging.join("&"));
+        }
+
+        record_aws_call(S3_SERVICE, "PutObject");
+        request
+            .send()
+            .await
+            .map_err(FeeSweeperError::rpc("failed to upload wallet backup"))?;
+
+        Ok(key)
+    }
+}
@@ -0,0 +1,306 @@
+//! Invariants checked before the sweeper moves funds
+//!
+//! Every transfer out of the submission key, and every sale of a fee balance
+//! by the conversion, is described as a `FundMovement` and checked against a
+//! set of invariants before it is submitted. A violation refuses the movement
+//! and is recorded as a critical event in the audit log. New invariants
+//! implement `Invariant` and are registered in `InvariantSet::new`, and in
+//! `InvariantSet::for_conversion` if they apply to sales.
+//!
+//! Invariants are evaluated against a `MintLedger`, the DB's record of the
+//! mint moved, so that the ledger may be read on whichever connection the
+//! caller holds
+
+use bigdecimal::{BigDecimal, FromPrimitive};
+use clap::Args;
+use diesel::{dsl::sum, ExpressionMethods, OptionalExtension, PgConnection, QueryDsl, RunQueryDsl};
+use ethers::types::{Address, U256};
+use num_bigint::BigInt;
+use renegade_common::types::token::Token;
+use tracing::error;
+
+use crate::db::{
+    audit::{record_audit_event, INVARIANT_VIOLATION_EVENT},
+    fund_movements::get_moved_amount,
+    models::{u256_to_decimal, NewAuditEvent},
+    schema::fees,
+};
+use crate::error::FeeSweeperError;
+use crate::Indexer;
+
+/// The arguments configuring the invariants checked before moving funds
+#[derive(Clone, Debug, Args)]
+pub struct InvariantArgs {
+    /// The maximum USD value that may be moved in a single transfer
+    #[clap(long)]
+    pub max_movement_usd: Option<f64>,
+}
+
+/// A transfer of funds out of the submission key
+#[derive(Clone, Copy, Debug)]
+pub struct FundMovement {
+    /// The kind of movement, e.g. "treasury transfer"
+    pub kind: &'static str,
+    /// The mint moved
+    pub mint: Address,
+    /// The raw amount moved
+    pub amount: U256,
+    /// The address the funds are sent to
+    pub destination: Address,
+}
+
+/// The DB's record of a mint, against which a movement of it is checked
+#[derive(Clone, Debug, Default)]
+pub struct MintLedger {
+    /// The total amount of the mint's fees redeemed
+    pub redeemed: BigDecimal,
+    /// The net amount of the mint already moved out of the sweeper's holdings
+    pub moved: BigDecimal,
+    /// The most recent price recorded for the mint's fees
+    pub usd_price: Option<BigDecimal>,
+}
+
+impl MintLedger {
+    /// Read the ledger of a mint, given as a lowercase hex address
+    pub fn load(conn: &mut PgConnection, mint: &str) -> Result<Self, FeeSweeperError> {
+        let redeemed: Option<BigDecimal> = fees::table
+            .filter(fees::mint.eq(mint))
+            .filter(fees::redeemed.eq(true))
+            .select(sum(fees::amount))
+            .first(conn)
+            .map_err(FeeSweeperError::db("failed to query redeemed fees"))?;
+        let moved = get_moved_amount(conn, mint)?;
+        let usd_price: Option<Option<BigDecimal>> = fees::table
+            .filter(fees::mint.eq(mint))
+            .filter(fees::usd_price.is_not_null())
+            .order(fees::id.desc())
+            .select(fees::usd_price)
+            .first(conn)
+            .optional()
+            .map_err(FeeSweeperError::db("failed to query mint price"))?;
+
+        Ok(Self {
+            redeemed: redeemed.unwrap_or_default(),
+            moved,
+            usd_price: usd_price.flatten(),
+        })
+    }
+}
+
+/// A condition that must hold before funds are moved
+pub trait Invariant {
+    /// The name of the invariant, recorded on violation
+    fn name(&self) -> &'static str;
+
+    /// Check the invariant against the mint's ledger, returning a policy
+    /// error describing the violation if any
+    fn check(&self, ledger: &MintLedger, movement: &FundMovement) -> Result<(), FeeSweeperError>;
+}
+
+/// The set of invariants checked before every fund movement
+pub struct InvariantSet {
+    /// The invariants checked, in order
+    invariants: Vec<Box<dyn Invariant>>,
+}
+
+impl InvariantSet {
+    /// Build the invariant set from the allowed destinations and configuration
+    pub fn new(allowlist: &[Address], args: &InvariantArgs) -> Result<Self, FeeSweeperError> {
+        let mut invariants: Vec<Box<dyn Invariant>> = vec![
+            Box::new(DestinationAllowlisted {
+                allowlist: allowlist.to_vec(),
+            }),
+            Box::new(BalanceConsistentWithDb),
+        ];
+
+        if let Some(max_usd) = args.max_movement_usd {
+            let max_usd = BigDecimal::from_f64(max_usd).ok_or_else(|| {
+                FeeSweeperError::Config(format!("invalid movement cap: {max_usd}"))
+            })?;
+            invariants.push(Box::new(ValueUnderCap { max_usd }));
+        }
+
+        Ok(Self { invariants })
+    }
+
+    /// Build the invariant set checked before the conversion sells a tranche
+    ///
+    /// A sale settles in the darkpool rather than at a destination, so no
+    /// allowlist applies
+    pub fn for_conversion(args: &InvariantArgs) -> Result<Self, FeeSweeperError> {
+        let mut invariants: Vec<Box<dyn Invariant>> = vec![Box::new(BalanceConsistentWithDb)];
+        if let Some(max_usd) = args.max_movement_usd {
+            let max_usd = BigDecimal::from_f64(max_usd).ok_or_else(|| {
+                FeeSweeperError::Config(format!("invalid movement cap: {max_usd}"))
+            })?;
+            invariants.push(Box::new(ValueUnderCap { max_usd }));
+        }
+
+        Ok(Self { invariants })
+    }
+
+    /// Check every invariant against a movement
+    ///
+    /// The first violation is recorded in the audit log and returned as an
+    /// error, in which case the movement must not proceed; a check failing
+    /// for another reason, such as a DB error, is returned as is
+    pub fn check(
+        &self,
+        conn: &mut PgConnection,
+        movement: &FundMovement,
+    ) -> Result<(), FeeSweeperError> {
+        let ledger = MintLedger::load(conn, &format!("{:#x}", movement.mint))?;
+        let res = self.evaluate(&ledger, movement);
+        if let Err(FeeSweeperError::Policy(details)) = &res {
+            record_audit_event(
+                conn,
+                NewAuditEvent::new(INVARIANT_VIOLATION_EVENT, None, details.clone()),
+            )?;
+        }
+
+        res
+    }
+
+    /// Evaluate every invariant against a movement and the mint's ledger
+    ///
+    /// The first violation is returned as a policy error, which the caller
+    /// records in the audit log
+    pub fn evaluate(
+        &self,
+        ledger: &MintLedger,
+        movement: &FundMovement,
+    ) -> Result<(), FeeSweeperError> {
+        for invariant in self.invariants.iter() {
+            let violation = match invariant.check(ledger, movement) {
+                Ok(()) => continue,
+                Err(FeeSweeperError::Policy(violation)) => violation,
+                Err(e) => return Err(e),
+            };
+            let details = format!(
+                "CRITICAL: {} of {} of {:#x} to {:#x} violates {}: {violation}",
+                movement.kind,
+                movement.amount,
+                movement.mint,
+                movement.destination,
+                invariant.name()
+            );
+            error!("{details}");
+            return Err(FeeSweeperError::Policy(details));
+        }
+
+        Ok(())
+    }
+}
+
+impl Indexer {
+    /// Check every invariant against a movement, reading the mint's ledger
+    /// from the indexer's pool
+    ///
+    /// As with `InvariantSet::check`, the first violation is recorded in the
+    /// audit log and returned as an error
+    pub(crate) async fn check_invariants(
+        &mut self,
+        invariants: &InvariantSet,
+        movement: &FundMovement,
+    ) -> Result<(), FeeSweeperError> {
+        let ledger = self
+            .get_mint_ledger(&format!("{:#x}", movement.mint))
+            .await?;
+        let res = invariants.evaluate(&ledger, movement);
+        if let Err(FeeSweeperError::Policy(details)) = &res {
+            let event = NewAuditEvent::new(INVARIANT_VIOLATION_EVENT, None, details.clone());
+            self.record_audit_event(event).await?;
+        }
+
+        res
+    }
+}
+
+// --------------
+// | Invariants |
+// --------------
+
+/// Funds may only be sent to an allowlisted destination
+struct DestinationAllowlisted {
+    /// The allowed destinations
+    allowlist: Vec<Address>,
+}
+
+impl Invariant for DestinationAllowlisted {
+    fn name(&self) -> &'static str {
+        "destination_allowlisted"
+    }
+
+    fn check(&self, _ledger: &MintLedger, movement: &FundMovement) -> Result<(), FeeSweeperError> {
+        if !self.allowlist.contains(&movement.destination) {
+            return Err(FeeSweeperError::Policy(format!(
+                "{:#x} is not allowlisted",
+                movement.destination
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// The amount moved may not exceed the fees the sweeper has redeemed in the
+/// mint, less what it has already moved out of the mint, so that funds the
+/// database cannot account for are never moved
+struct BalanceConsistentWithDb;
+
+impl Invariant for BalanceConsistentWithDb {
+    fn name(&self) -> &'static str {
+        "balance_consistent_with_db"
+    }
+
+    fn check(&self, ledger: &MintLedger, movement: &FundMovement) -> Result<(), FeeSweeperError> {
+        let available = &ledger.redeemed - &ledger.moved;
+        if u256_to_decimal(movement.amount) > available {
+            return Err(FeeSweeperError::Policy(format!(
+                "only {available} is unaccounted for: {} redeemed, {} already moved",
+                ledger.redeemed, ledger.moved
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// The USD value moved in a single transfer may not exceed a cap
+///
+/// The movement is valued at the most recent price recorded for the mint's
+/// fees; a movement in a mint without a recorded price is refused
+struct ValueUnderCap {
+    /// The maximum USD value of a single movement
+    max_usd: BigDecimal,
+}
+
+impl Invariant for ValueUnderCap {
+    fn name(&self) -> &'static str {
+        "value_under_cap"
+    }
+
+    fn check(&self, ledger: &MintLedger, movement: &FundMovement) -> Result<(), FeeSweeperError> {
+        let mint = format!("{:#x}", movement.mint);
+        let decimals = Token::from_addr(&mint)
+            .get_decimals()
+            .ok_or_else(|| FeeSweeperError::Other(format!("unknown decimals for {mint}")))?;
+
+        let price = ledger
+            .usd_price
+            .clone()
+            .ok_or_else(|| FeeSweeperError::Policy(format!("no recorded price for {mint}")))?;
+
+        let unit = BigDecimal::new(BigInt::from(1), decimals as i64);
+        let value = u256_to_decimal(movement.amount) * price * unit;
+        if value > self.max_usd {
+            return Err(FeeSweeperError::Policy(format!(
+                "value ${} exceeds cap ${}",
+                value.round(2 /* round_digits */),
+                self.max_usd
+            )));
+        }
+
+        Ok(())
+    }
+}
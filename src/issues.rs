@@ -0,0 +1,172 @@
+//! Opens tickets for notes the sweeper has given up on, so that terminal
+//! failures enter the normal triage workflow
+//!
+//! Tickets are created through a configurable webhook, which receives either a
+//! GitHub issues API payload or a Linear `issueCreate` mutation
+
+use clap::{Args, ValueEnum};
+use reqwest::Client;
+use serde_json::{json, Value};
+use tracing::info;
+
+use crate::db::models::{AuditEvent, Fee};
+use crate::error::FeeSweeperError;
+
+/// The label applied to tickets on trackers that support labels
+const DEAD_LETTER_LABEL: &str = "fee-sweeper-dead-letter";
+/// The Linear mutation creating an issue
+const LINEAR_ISSUE_CREATE_MUTATION: &str =
+    "mutation IssueCreate($input: IssueCreateInput!) { issueCreate(input: $input) { success } }";
+
+/// The API a ticket webhook speaks
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum IssueFormat {
+    /// The GitHub create issue API, e.g. `https://api.github.com/repos/<owner>/<repo>/issues`
+    Github,
+    /// The Linear GraphQL API, e.g. `https://api.linear.app/graphql`
+    Linear,
+}
+
+/// The arguments configuring ticket creation for dead-lettered notes
+#[derive(Clone, Debug, Args)]
+pub struct IssueArgs {
+    /// The URL of the webhook to open tickets through; tickets are only
+    /// opened if set
+    #[clap(long)]
+    pub issue_webhook_url: Option<String>,
+    /// The API the webhook speaks
+    #[clap(long, value_enum, default_value_t = IssueFormat::Github)]
+    pub issue_webhook_format: IssueFormat,
+    /// The token authorizing ticket creation
+    #[clap(long)]
+    pub issue_webhook_token: Option<String>,
+    /// The Linear team to open tickets in, required for the Linear format
+    #[clap(long)]
+    pub linear_team_id: Option<String>,
+}
+
+/// A client opening tickets through a webhook
+pub struct IssueTracker {
+    /// The webhook URL
+    url: String,
+    /// The API the webhook speaks
+    format: IssueFormat,
+    /// The token authorizing ticket creation
+    token: Option<String>,
+    /// The Linear team to open tickets in
+    linear_team_id: Option<String>,
+}
+
+impl IssueTracker {
+    /// Build a tracker from its arguments, returning `None` if no webhook is
+    /// configured
+    pub fn from_args(args: IssueArgs) -> Result<Option<Self>, FeeSweeperError> {
+        let url = match args.issue_webhook_url {
+            Some(url) => url,
+            None => return Ok(None),
+        };
+        if args.issue_webhook_format == IssueFormat::Linear && args.linear_team_id.is_none() {
+            return Err(FeeSweeperError::Config(
+                "--linear-team-id is required for Linear tickets".to_string(),
+            ));
+        }
+
+        Ok(Some(Self {
+            url,
+            format: args.issue_webhook_format,
+            token: args.issue_webhook_token,
+            linear_team_id: args.linear_team_id,
+        }))
+    }
+
+    /// Open a ticket for a dead-lettered note with its failure history
+    pub async fn open_dead_letter_issue(
+        &self,
+        fee: &Fee,
+        failures: &[AuditEvent],
+    ) -> Result<(), FeeSweeperError> {
+        let title = format!("Fee note from tx {} dead-lettered", fee.tx_hash);
+        let body = dead_letter_description(fee, failures);
+        let payload = match self.format {
+            IssueFormat::Github => json!({
+                "title": title,
+                "body": body,
+                "labels": [DEAD_LETTER_LABEL],
+            }),
+            IssueFormat::Linear => json!({
+                "query": LINEAR_ISSUE_CREATE_MUTATION,
+                "variables": {
+                    "input": {
+                        "teamId": self.linear_team_id,
+                        "title": title,
+                        "description": body,
+                    },
+                },
+            }),
+        };
+
+        self.post(&payload).await?;
+        info!(
+            "opened ticket for dead-lettered fee from tx {}",
+            fee.tx_hash
+        );
+        Ok(())
+    }
+
+    /// Send a payload to the webhook
+    async fn post(&self, payload: &Value) -> Result<(), FeeSweeperError> {
+        let client = Client::builder()
+            .user_agent("fee-sweeper")
+            .build()
+            .map_err(FeeSweeperError::rpc("Failed to create reqwest client"))?;
+
+        let mut req = client.post(&self.url).json(payload);
+        if let Some(token) = &self.token {
+            req = match self.format {
+                IssueFormat::Github => req.bearer_auth(token),
+                // Linear takes personal API keys without a scheme
+                IssueFormat::Linear => req.header(reqwest::header::AUTHORIZATION, token),
+            };
+        }
+
+        let resp = req
+            .send()
+            .await
+            .map_err(FeeSweeperError::rpc("Failed to open ticket"))?;
+        if !resp.status().is_success() {
+            return Err(FeeSweeperError::Rpc(format!(
+                "Failed to open ticket: {}",
+                resp.status()
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// Describe a dead-lettered note and its failure history in markdown
+fn dead_letter_description(fee: &Fee, failures: &[AuditEvent]) -> String {
+    let mut body = format!(
+        "The sweeper gave up redeeming a fee note after {} attempts.\n\n\
+         | field | value |\n|---|---|\n\
+         | tx hash | `{}` |\n| mint | `{}` |\n| amount | {} |\n\
+         | USD value | {} |\n| source | {} |\n| block | {} |\n\n\
+         ### Failure history\n\n",
+        fee.redemption_attempts,
+        fee.tx_hash,
+        fee.mint,
+        fee.amount,
+        fee.usd_value
+            .as_ref()
+            .map(|v| v.round(2 /* round_digits */).to_string())
+            .unwrap_or_default(),
+        fee.source,
+        fee.block_number.map(|b| b.to_string()).unwrap_or_default(),
+    );
+
+    for failure in failures {
+        body.push_str(&format!("- {}: {}\n", failure.created_at, failure.details));
+    }
+
+    body
+}
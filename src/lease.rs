@@ -0,0 +1,111 @@
+//! The deployment handshake between sweeper instances
+//!
+//! A run holds the sweeper lease while it executes. A newly started instance
+//! that finds the lease held either fails fast, or requests a handover and
+//! waits for the holder's in-flight run to release the lease before taking it
+//! over atomically. Deploys are then safe without checking by hand that the
+//! old instance is done
+
+use std::time::Duration;
+
+use clap::Args;
+use diesel::PgConnection;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::clock::Clock;
+use crate::db::leases::{acquire_lease, get_lease, release_lease, request_handover};
+use crate::error::FeeSweeperError;
+use crate::Indexer;
+
+/// The name of the lease held by the running sweeper instance
+pub(crate) const SWEEPER_LEASE: &str = "sweeper";
+/// The interval at which a waiting instance polls the lease
+const LEASE_POLL_INTERVAL_MS: u64 = 5000;
+
+/// The arguments configuring the sweeper lease
+#[derive(Clone, Debug, Args)]
+pub struct LeaseArgs {
+    /// How long, in seconds, a run's lease lasts before another instance may
+    /// take it over; should exceed the longest run so that only a crashed
+    /// instance's lease expires
+    #[clap(long, default_value_t = 3600)]
+    pub lease_ttl_secs: u64,
+    /// How long, in seconds, to wait for an active instance's run to finish
+    /// before giving up; zero fails immediately if another instance is active
+    #[clap(long, default_value_t = 0)]
+    pub takeover_wait_secs: u64,
+}
+
+/// Acquire the sweeper lease for a run, waiting for an active instance to
+/// hand it over if configured to
+pub async fn acquire_run_lease(
+    conn: &mut PgConnection,
+    run_id: Uuid,
+    args: &LeaseArgs,
+    clock: &dyn Clock,
+) -> Result<(), FeeSweeperError> {
+    let ttl = Duration::from_secs(args.lease_ttl_secs);
+    if acquire_lease(conn, SWEEPER_LEASE, run_id, ttl)? {
+        info!("acquired sweeper lease");
+        return Ok(());
+    }
+
+    let describe_holder = |conn: &mut PgConnection| -> Result<String, FeeSweeperError> {
+        Ok(match get_lease(conn, SWEEPER_LEASE)? {
+            Some(lease) => format!("{} until {}", lease.holder, lease.expires_at),
+            None => "another instance".to_string(),
+        })
+    };
+    if args.takeover_wait_secs == 0 {
+        let holder = describe_holder(conn)?;
+        return Err(FeeSweeperError::Db(format!(
+            "sweeper lease is held by {holder}"
+        )));
+    }
+    if !request_handover(conn, SWEEPER_LEASE, run_id)? {
+        return Err(FeeSweeperError::Db(
+            "another instance is already awaiting the sweeper lease".to_string(),
+        ));
+    }
+
+    info!(
+        "waiting for {} to hand over the sweeper lease",
+        describe_holder(conn)?
+    );
+    let deadline = clock.instant() + Duration::from_secs(args.takeover_wait_secs);
+    let poll_interval = Duration::from_millis(LEASE_POLL_INTERVAL_MS);
+    while clock.instant() < deadline {
+        clock.sleep(poll_interval).await;
+        if acquire_lease(conn, SWEEPER_LEASE, run_id, ttl)? {
+            info!("took over sweeper lease");
+            return Ok(());
+        }
+    }
+
+    Err(FeeSweeperError::Db(format!(
+        "sweeper lease not handed over within {}s",
+        args.takeover_wait_secs
+    )))
+}
+
+/// Release the sweeper lease of a run that failed to start
+pub fn release_run_lease(conn: &mut PgConnection, run_id: Uuid) -> Result<(), FeeSweeperError> {
+    release_lease(conn, SWEEPER_LEASE, run_id)
+}
+
+impl Indexer {
+    /// Renew the sweeper lease held by a long-running instance
+    ///
+    /// Returns false if the lease has been handed over to another instance, in
+    /// which case the instance must stop
+    pub async fn renew_run_lease(&mut self, args: &LeaseArgs) -> Result<bool, FeeSweeperError> {
+        let ttl = Duration::from_secs(args.lease_ttl_secs);
+        self.acquire_lease(SWEEPER_LEASE, ttl).await
+    }
+
+    /// Release the sweeper lease at the end of a run
+    pub async fn release_run_lease(&mut self) -> Result<(), FeeSweeperError> {
+        self.release_lease(SWEEPER_LEASE).await
+    }
+}
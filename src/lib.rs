@@ -0,0 +1,7 @@
+//! Rust bindings to the fee sweeper, for services consuming its data
+#![deny(missing_docs)]
+#![deny(clippy::missing_docs_in_private_items)]
+#![deny(unsafe_code)]
+
+pub mod client;
+pub mod decryption;
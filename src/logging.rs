@@ -0,0 +1,36 @@
+//! Setup of the sweeper's log output
+//!
+//! Logs are written as text by default. In the JSON format each line is a
+//! structured record, so that logs may be ingested and queried by a log
+//! aggregator rather than parsed as free text: the fields of an event are
+//! flattened into the record, alongside the fields of the spans it was logged
+//! in. The sweeper's spans record the chain of a run, the block range of an
+//! indexed chunk, the commitment and mint of a note, the tx and mint of a
+//! redemption, and the id of a relayer task
+
+use clap::ValueEnum;
+use renegade_util::telemetry::{setup_system_logger, LevelFilter};
+
+/// The format of the log output
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// Human readable text
+    #[default]
+    Text,
+    /// One JSON record per line
+    Json,
+}
+
+/// Set up the global logger in the given format
+pub fn setup_logging(format: LogFormat) {
+    match format {
+        LogFormat::Text => setup_system_logger(LevelFilter::INFO),
+        LogFormat::Json => tracing_subscriber::fmt()
+            .json()
+            .with_max_level(LevelFilter::INFO)
+            .flatten_event(true)
+            .with_current_span(false)
+            .with_span_list(true)
+            .init(),
+    }
+}
@@ -5,28 +5,130 @@
 #![deny(clippy::needless_pass_by_ref_mut)]
 #![feature(trivial_bounds)]
 
+pub mod allowances;
+pub mod api;
+pub mod aws;
+pub mod bridge;
+pub mod chain;
+pub mod clock;
+pub mod commands;
+pub mod config;
 pub mod db;
+pub mod decryption;
+pub mod erc20;
+pub mod error;
+pub mod export;
+pub mod feature_flags;
+pub mod fee_keys;
+pub mod gas;
+pub mod historical_prices;
 pub mod indexer;
+pub mod invariants;
+pub mod issues;
+pub mod lease;
+pub mod logging;
+pub mod notifications;
 pub mod relayer_client;
+pub mod rpc_failover;
+pub mod scheduler;
+pub mod secrets;
+pub mod shutdown;
+pub mod signer;
+pub mod submitter;
+pub mod sweep;
+pub mod telemetry;
+pub mod treasury;
+pub mod wallet_lock;
 
-use aws_config::{BehaviorVersion, Region};
+use aws::{AwsContext, CostTag, DEFAULT_AWS_APP_NAME};
+use chain::EvmDarkpoolClient;
+use clock::SystemClock;
+use commands::{
+    abandoned::{run_abandoned, AbandonedArgs},
+    allowance::{run_allowance, AllowanceArgs},
+    bridge::{run_bridge, BridgeArgs},
+    decryption_key::{run_decryption_key, DecryptionKeyArgs},
+    dual_write::{run_dual_write, DualWriteCommandArgs},
+    failures::{run_failures, FailuresArgs},
+    feature_flag::{run_feature_flag, FeatureFlagCommandArgs},
+    find::{run_find, FindArgs},
+    policy_report::{run_policy_report, PolicyReportArgs},
+    price_route::{run_price_route, PriceRouteArgs},
+    quarantine::{run_quarantine, QuarantineArgs},
+    recover_wallet::{run_recover_wallet, RecoverWalletArgs},
+    report::{run_report, ReportArgs},
+    rotate_signer::{run_rotate_signer, RotateSignerArgs},
+    serve::{run_serve, ServeArgs},
+    stats::{run_stats, StatsArgs},
+    status::{run_status, StatusArgs},
+    support_bundle::{run_support_bundle, SupportBundleArgs},
+    treasury::{run_treasury_transfer, TreasuryTransferArgs},
+};
+use config::parse_with_config;
+use db::metadata::check_active_signer;
+use db::migrations::run_pending_migrations;
+use db::pool::{build_db_pool, DbPool};
+use db::storage::{SharedStorage, StorageArgs};
+use decryption::DecryptionPool;
 use diesel::{pg::PgConnection, Connection};
-use ethers::signers::LocalWallet;
-use indexer::Indexer;
-use relayer_client::RelayerClient;
-use renegade_circuit_types::elgamal::DecryptionKey;
-use renegade_util::{
-    raw_err_str,
-    telemetry::{setup_system_logger, LevelFilter},
+use error::FeeSweeperError;
+use ethers::{
+    core::rand::thread_rng,
+    signers::{LocalWallet, Signer},
+    types::Address,
+};
+use feature_flags::{FeatureFlagArgs, FeatureFlags};
+use fee_keys::FeeKeyArgs;
+use historical_prices::{HistoricalPriceClient, HistoricalPriceSource};
+use indexer::{
+    abandonment::AbandonmentArgs,
+    batch_sizing::{BatchSizer, BatchSizingArgs},
+    blocklist::BlocklistArgs,
+    confirmation::ConfirmationArgs,
+    consistency::ConsistencyArgs,
+    convert_fees::ConversionArgs,
+    dead_letter::RedemptionRetryArgs,
+    dual_write::DualWriteArgs,
+    dust::{DustAction, DustFilter, DustFloor},
+    event_source::{EventSourceArgs, LogWindow},
+    finality::FinalityArgs,
+    gas_filter::GasFilterArgs,
+    mint_thresholds::{MintThreshold, MintThresholds},
+    policy::{RedemptionPolicies, SourcePolicy},
+    rpc_budget::RpcBudgetArgs,
+    scoring::ScorerArgs,
+    signer::{load_submitter_keys, SignerSource},
+    slo::SloArgs,
+    spam::SpamArgs,
+    wallet_backup::WalletBackupArgs,
+    Indexer,
 };
+use issues::{IssueArgs, IssueTracker};
+use lease::{acquire_run_lease, release_run_lease, LeaseArgs};
+use logging::{setup_logging, LogFormat};
+use notifications::{NotificationArgs, Notifier};
+use relayer_client::{
+    build_http_client, ConnectionPolicy, RelayerClient, RetryPolicy, ThrottlePolicy,
+    DEFAULT_CONNECT_TIMEOUT_SECS, DEFAULT_MAX_ATTEMPTS, DEFAULT_POOL_IDLE_TIMEOUT_SECS,
+    DEFAULT_REQUEST_TIMEOUT_SECS, DEFAULT_RETRY_BASE_DELAY_MS, DEFAULT_RETRY_MAX_DELAY_MS,
+    DEFAULT_TASK_TIMEOUT_SECS, DEFAULT_TCP_KEEPALIVE_SECS, DEFAULT_USER_AGENT,
+};
+use rpc_failover::{failover_rpc_url, RpcFailoverArgs};
+use secrets::KeySource;
+use shutdown::Shutdown;
+use signer::{KmsSigner, SubmissionSigner};
+use sweep::{SweepArgs, SweepStage};
+use wallet_lock::WalletLockArgs;
 
-use std::{error::Error, str::FromStr};
+use std::{error::Error, path::PathBuf, time::Duration};
 
 use arbitrum_client::{
     client::{ArbitrumClient, ArbitrumClientConfig},
     constants::Chain,
 };
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
+use tracing::{info, info_span, warn, Instrument};
+use uuid::Uuid;
 
 // -------------
 // | Constants |
@@ -36,6 +138,8 @@ use clap::Parser;
 const BLOCK_POLLING_INTERVAL_MS: u64 = 100;
 /// The default region in which to provision secrets manager secrets
 const DEFAULT_REGION: &str = "us-east-2";
+/// The message the submission key signs at startup to show that it can sign
+const SIGNER_PROBE_MESSAGE: &str = "fee-sweeper run";
 
 // -------
 // | Cli |
@@ -44,84 +148,673 @@ const DEFAULT_REGION: &str = "us-east-2";
 /// The cli for the fee sweeper
 #[derive(Debug, Parser)]
 struct Cli {
+    /// A TOML file of settings for the command, keyed by the long names of
+    /// its flags, which the flags and their environment variables override
+    #[clap(long)]
+    config: Option<PathBuf>,
+    /// The format of the log output
+    #[clap(
+        long,
+        global = true,
+        value_enum,
+        default_value_t = LogFormat::Text,
+        env = "FEE_SWEEPER_LOG_FORMAT"
+    )]
+    log_format: LogFormat,
+    /// The command to run
+    #[clap(subcommand)]
+    command: Command,
+}
+
+/// The commands supported by the fee sweeper
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Index new fees and redeem the most valuable of them
+    Run(RunArgs),
+    /// Index and value new fees without redeeming them
+    Index(RunArgs),
+    /// Redeem and convert indexed fees without indexing new ones
+    Redeem(RunArgs),
+    /// Re-index the fees in a range of past blocks, e.g. after a change to
+    /// the decryption keys or dust floors
+    Backfill(BackfillArgs),
+    /// Search indexed fee notes
+    Find(FindArgs),
+    /// Inspect and manage ERC-20 allowances granted by the submission key
+    Allowance(AllowanceArgs),
+    /// Forward funds held by the submission key to their treasury addresses
+    TreasuryTransfer(TreasuryTransferArgs),
+    /// Bridge redeemed funds to an L1 treasury and track their claims
+    Bridge(BridgeArgs),
+    /// Summarize indexed fees and the gas spent by the sweeper
+    Stats(StatsArgs),
+    /// Show how far the sweeper has indexed and where its fees stand
+    Status(StatusArgs),
+    /// Rotate the Arbitrum key the sweeper signs transactions with
+    RotateSigner(RotateSignerArgs),
+    /// Serve the sweeper's HTTP API, including the public stats
+    Serve(ServeArgs),
+    /// Review and release mints quarantined as suspected spam
+    Quarantine(QuarantineArgs),
+    /// Recover a sweep wallet from its root key and reconcile its balances
+    RecoverWallet(RecoverWalletArgs),
+    /// Review and restore fees abandoned by the dust expiration policy
+    Abandoned(AbandonedArgs),
+    /// Replay historical fees through redemption policy variants and compare
+    /// the value each captures against the gas it spends
+    PolicyReport(PolicyReportArgs),
+    /// Verify or backfill data dual-written during a schema migration
+    DualWrite(DualWriteCommandArgs),
+    /// Manage the routes through which mints without a USDC pair are priced
+    PriceRoute(PriceRouteArgs),
+    /// Triage failed redemptions from the relayer payloads stored for them
+    Failures(FailuresArgs),
+    /// Manage the feature flags gating risky subsystems
+    FeatureFlag(FeatureFlagCommandArgs),
+    /// Generate the closing statement of a month's fees and gas spend
+    Report(ReportArgs),
+    /// Collect sanitized config, recent runs and failures, the schema version,
+    /// and key metrics into a tarball for attaching to a bug report
+    SupportBundle(SupportBundleArgs),
+    /// Register and remove decryption keys of fee notes, e.g. to rotate a key
+    /// without a redeploy
+    DecryptionKey(DecryptionKeyArgs),
+}
+
+/// The arguments to the `run` command
+#[derive(Debug, Args)]
+struct RunArgs {
     /// The URL of the relayer to use
     #[clap(long)]
     relayer_url: String,
-    /// The Arbitrum RPC url to use
-    #[clap(short, long)]
-    rpc_url: String,
+    /// An Arbitrum RPC url to use; given several times, or as a
+    /// comma-separated list, requests fail over from one endpoint to the next
+    #[clap(
+        short,
+        long,
+        required = true,
+        env = "RPC_URL",
+        hide_env_values = true,
+        value_delimiter = ','
+    )]
+    rpc_url: Vec<String>,
+    /// The configuration of failover across RPC endpoints
+    #[clap(flatten)]
+    rpc_failover: RpcFailoverArgs,
     /// The address of the darkpool contract
     #[clap(short = 'a', long)]
     darkpool_address: String,
     /// The chain to redeem fees for
     #[clap(long, default_value = "mainnet")]
     chain: Chain,
-    /// The fee decryption key to use
-    #[clap(short, long)]
-    decryption_key: String,
-    /// The arbitrum private key used to submit transactions
-    #[clap(long = "pkey")]
-    arbitrum_private_key: String,
+    /// The fee kinds to sweep and the decryption keys for each
+    #[clap(flatten)]
+    fee_keys: FeeKeyArgs,
+    /// The arbitrum private key used to submit transactions, or the ARN of a
+    /// Secrets Manager secret holding it
+    ///
+    /// Required by stages that redeem; indexing alone reads the chain with an
+    /// ephemeral key. A key held in a secret is re-read before each redemption
+    /// job of the daemon, and a rotated key is used once it is the active
+    /// signer
+    #[clap(long = "pkey", env = "ARB_PRIVATE_KEY", hide_env_values = true)]
+    arbitrum_private_key: Option<KeySource>,
+    /// The id or ARN of an AWS KMS key used as the submission key in place of
+    /// a private key
+    #[clap(long, conflicts_with = "arbitrum_private_key")]
+    kms_key_id: Option<String>,
+    /// An additional arbitrum private key submitting transactions alongside
+    /// the submission key, or the ARN of a secret holding it; may be given
+    /// several times, or as a comma-separated list
+    ///
+    /// Each key has its own nonces, and the darkpool client spreads its
+    /// transactions across all of them
+    #[clap(
+        long = "submitter-pkey",
+        env = "ARB_SUBMITTER_KEYS",
+        hide_env_values = true,
+        value_delimiter = ','
+    )]
+    submitter_keys: Vec<KeySource>,
     /// The database url
-    #[clap(long)]
+    #[clap(long, env = "DB_URL", hide_env_values = true)]
     db_url: String,
+    /// The maximum number of connections in the pool serving the indexer's
+    /// queries
+    #[clap(long, default_value_t = 4)]
+    db_pool_size: usize,
+    /// The storage backend the DB runs on
+    #[clap(flatten)]
+    storage: StorageArgs,
+    /// Apply any pending schema migrations embedded in the binary at startup
+    #[clap(long)]
+    run_migrations: bool,
     /// The token address of the USDC token, used to get prices for fee redemption
     #[clap(long)]
     usdc_mint: String,
+    /// The source of historical prices used to value indexed fees
+    #[clap(long, value_enum, default_value_t = HistoricalPriceSource::Binance)]
+    historical_price_source: HistoricalPriceSource,
+    /// An override for the base URL of the historical price source
+    #[clap(long)]
+    historical_price_url: Option<String>,
+    /// An API key for the historical price source, if it requires one
+    #[clap(long)]
+    historical_price_api_key: Option<String>,
+    /// A redemption policy for a fee source, of the form
+    /// `<source>:<min_value_usd>[:<min_interval_secs>]`; a policy for the
+    /// source `*` sets the minimum value of sources without their own policy
+    #[clap(long = "source-policy")]
+    source_policies: Vec<SourcePolicy>,
+    /// A minimum redemption threshold for a mint, of the form
+    /// `<mint>:<min_raw_amount>[:<min_value_usd>]`
+    #[clap(long = "mint-threshold")]
+    mint_thresholds: Vec<MintThreshold>,
+    /// Simulate each redemption against the current chain state before
+    /// asking the relayer to execute it, skipping those that would revert
+    #[clap(long)]
+    simulate_redemptions: bool,
+    /// The recipient of external match fees; external match fees are only
+    /// indexed if set
+    #[clap(long)]
+    external_fee_recipient: Option<Address>,
+    /// The user agent sent with relayer requests
+    #[clap(long, default_value = DEFAULT_USER_AGENT)]
+    user_agent: String,
+    /// The app name identifying the sweeper's requests to AWS
+    #[clap(long, default_value = DEFAULT_AWS_APP_NAME)]
+    aws_app_name: String,
+    /// A cost allocation tag applied to AWS resources the sweeper creates, of
+    /// the form `<key>=<value>`
+    #[clap(long = "aws-cost-tag")]
+    aws_cost_tags: Vec<CostTag>,
+    /// The port on which to serve Prometheus metrics, if any
+    #[clap(long)]
+    metrics_port: Option<u16>,
+    /// The total time, in seconds, each phase of the run may spend in the DB
+    ///
+    /// A phase exceeding its budget is aborted and resumes in the next run
+    #[clap(long)]
+    phase_db_budget_secs: Option<u64>,
+    /// The configuration of the per-run RPC call budget
+    #[clap(flatten)]
+    rpc_budget: RpcBudgetArgs,
+    /// The configuration of how often the sweeper sweeps
+    #[clap(flatten)]
+    sweep: SweepArgs,
+    /// The configuration of when an indexed block is final
+    #[clap(flatten)]
+    finality: FinalityArgs,
+    /// The configuration of retries of failed redemptions
+    #[clap(flatten)]
+    redemption_retry: RedemptionRetryArgs,
+    /// The number of tasks queued on a redemption wallet in the relayer above
+    /// which redemptions into it are deferred to a later sweep
+    #[clap(long, default_value_t = 2)]
+    max_task_queue_depth: usize,
+    /// The configuration of the sizing of redemption batches
+    #[clap(flatten)]
+    batch_sizing: BatchSizingArgs,
+    /// The time, in seconds, after which a run's claim on the fees it is
+    /// redeeming lapses, so that the fees of a crashed run may be redeemed by
+    /// another; should exceed the duration of a redemption pass
+    #[clap(long, default_value_t = 1800)]
+    fee_claim_ttl_secs: u64,
+    /// The configuration of tickets opened for dead-lettered fees
+    #[clap(flatten)]
+    issues: IssueArgs,
+    /// The maximum time, in seconds, to wait for the relayer's price reporters
+    /// to reach a nominal state before redeeming
+    #[clap(long, default_value_t = 30)]
+    price_warmup_secs: u64,
+    /// The number of times a request throttled by the relayer is retried
+    /// after the delay given in its `Retry-After` header
+    #[clap(long, default_value_t = 3)]
+    max_throttle_retries: u32,
+    /// The longest `Retry-After` delay, in seconds, honored before retrying
+    #[clap(long, default_value_t = 60)]
+    max_retry_after_secs: u64,
+    /// The number of times a relayer request failing transiently, e.g. timing
+    /// out or answered with a `5xx`, is attempted
+    #[clap(long, default_value_t = DEFAULT_MAX_ATTEMPTS)]
+    relayer_max_attempts: u32,
+    /// The delay, in milliseconds, before the first retry of a relayer request
+    /// failing transiently, doubled on each retry after
+    #[clap(long, default_value_t = DEFAULT_RETRY_BASE_DELAY_MS)]
+    relayer_retry_base_delay_ms: u64,
+    /// The longest delay, in milliseconds, between retries of a relayer
+    /// request failing transiently
+    #[clap(long, default_value_t = DEFAULT_RETRY_MAX_DELAY_MS)]
+    relayer_retry_max_delay_ms: u64,
+    /// The time, in seconds, allowed to connect to the relayer
+    #[clap(long, default_value_t = DEFAULT_CONNECT_TIMEOUT_SECS)]
+    relayer_connect_timeout_secs: u64,
+    /// The time, in seconds, allowed for a relayer request to complete
+    #[clap(long, default_value_t = DEFAULT_REQUEST_TIMEOUT_SECS)]
+    relayer_request_timeout_secs: u64,
+    /// The time, in seconds, an idle connection to the relayer is kept open
+    /// for reuse
+    #[clap(long, default_value_t = DEFAULT_POOL_IDLE_TIMEOUT_SECS)]
+    relayer_pool_idle_timeout_secs: u64,
+    /// The interval, in seconds, of TCP keep-alive probes on connections to
+    /// the relayer
+    #[clap(long, default_value_t = DEFAULT_TCP_KEEPALIVE_SECS)]
+    relayer_tcp_keepalive_secs: u64,
+    /// The longest time, in seconds, a relayer task is awaited before it is
+    /// failed
+    #[clap(long, default_value_t = DEFAULT_TASK_TIMEOUT_SECS)]
+    relayer_task_timeout_secs: u64,
+    /// The configuration of the conversion of fee balances to USDC
+    #[clap(flatten)]
+    conversion: ConversionArgs,
+    /// The configuration of the lease held by the running instance
+    #[clap(flatten)]
+    lease: LeaseArgs,
+    /// The configuration of the locks serializing operations on each wallet
+    #[clap(flatten)]
+    wallet_lock: WalletLockArgs,
+    /// A raw-amount floor below which a mint's notes are dust, of the form
+    /// `<mint>:<min_raw_amount>`
+    #[clap(long = "dust-floor")]
+    dust_floors: Vec<DustFloor>,
+    /// The expiration policy of dust fees
+    #[clap(flatten)]
+    abandonment: AbandonmentArgs,
+    /// What to do with notes below their mint's dust floor
+    #[clap(long, value_enum, default_value_t = DustAction::Flag)]
+    dust_action: DustAction,
+    /// The configuration of the gas cost filter on redemptions
+    #[clap(flatten)]
+    gas_filter: GasFilterArgs,
+    /// The configuration of the scoring of fees for redemption
+    #[clap(flatten)]
+    scorer: ScorerArgs,
+    /// The configuration of the spam heuristics
+    #[clap(flatten)]
+    spam: SpamArgs,
+    /// The blocklist of settlement transactions whose fees are not indexed
+    #[clap(flatten)]
+    blocklist: BlocklistArgs,
+    /// The configuration of the source of historical events
+    #[clap(flatten)]
+    event_source: EventSourceArgs,
+    /// The number of workers decrypting note ciphertexts in parallel; one per
+    /// CPU if zero
+    #[clap(long, default_value_t = 0)]
+    decryption_workers: usize,
+    /// The configuration of notifications
+    #[clap(flatten)]
+    notifications: NotificationArgs,
+    /// The configuration of dual-writes during schema migrations
+    #[clap(flatten)]
+    dual_write: DualWriteArgs,
+    /// The configuration of backups of new sweep wallets
+    #[clap(flatten)]
+    wallet_backup: WalletBackupArgs,
+    /// The configuration of the guard on the relayer's sweep wallets
+    #[clap(flatten)]
+    consistency: ConsistencyArgs,
+    /// The configuration of the confirmation of redemptions on-chain
+    #[clap(flatten)]
+    confirmation: ConfirmationArgs,
+    /// The configuration of the redemption latency SLO
+    #[clap(flatten)]
+    slo: SloArgs,
+    /// The feature flags set in the instance's configuration
+    #[clap(flatten)]
+    feature_flags: FeatureFlagArgs,
 }
 
-impl Cli {
+/// The arguments to the `backfill` command
+#[derive(Debug, Args)]
+struct BackfillArgs {
+    /// The first block to re-index
+    #[clap(long)]
+    from_block: u64,
+    /// The last block to re-index, which must be final; defaults to the latest
+    /// final block
+    #[clap(long)]
+    to_block: Option<u64>,
+    /// The configuration of the run
+    #[clap(flatten)]
+    run: RunArgs,
+}
+
+impl RunArgs {
     /// Build a connection to the DB
-    pub fn build_db_conn(&self) -> Result<PgConnection, String> {
-        PgConnection::establish(&self.db_url).map_err(|e| e.to_string())
+    pub fn build_db_conn(&self) -> Result<PgConnection, FeeSweeperError> {
+        PgConnection::establish(&self.db_url)
+            .map_err(FeeSweeperError::db("failed to connect to db"))
+    }
+
+    /// Build the pool of async connections serving the indexer's queries
+    pub fn build_db_pool(&self) -> Result<DbPool, FeeSweeperError> {
+        build_db_pool(&self.db_url, self.db_pool_size)
     }
 }
 
 /// Main
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
-    setup_system_logger(LevelFilter::INFO);
-    let cli = Cli::parse();
-    let db_conn = cli.build_db_conn()?;
+    let cli = parse_with_config::<Cli>()?;
+    setup_logging(cli.log_format);
+    if let Some(path) = cli.config.as_ref() {
+        info!("loaded settings from {}", path.display());
+    }
 
+    match cli.command {
+        Command::Run(args) => Ok(run(args, SweepStage::All).await?),
+        Command::Index(args) => Ok(run(args, SweepStage::Index).await?),
+        Command::Redeem(args) => Ok(run(args, SweepStage::Redeem).await?),
+        Command::Backfill(args) => Ok(backfill(args).await?),
+        Command::Find(args) => Ok(run_find(args)?),
+        Command::Allowance(args) => Ok(run_allowance(args).await?),
+        Command::TreasuryTransfer(args) => Ok(run_treasury_transfer(args).await?),
+        Command::Bridge(args) => Ok(run_bridge(args).await?),
+        Command::Stats(args) => Ok(run_stats(args)?),
+        Command::Status(args) => Ok(run_status(args)?),
+        Command::RotateSigner(args) => Ok(run_rotate_signer(args).await?),
+        Command::Serve(args) => Ok(run_serve(args).await?),
+        Command::Quarantine(args) => Ok(run_quarantine(args)?),
+        Command::RecoverWallet(args) => Ok(run_recover_wallet(args).await?),
+        Command::Abandoned(args) => Ok(run_abandoned(args)?),
+        Command::PolicyReport(args) => Ok(run_policy_report(args)?),
+        Command::DualWrite(args) => Ok(run_dual_write(args)?),
+        Command::PriceRoute(args) => Ok(run_price_route(args)?),
+        Command::Failures(args) => Ok(run_failures(args)?),
+        Command::FeatureFlag(args) => Ok(run_feature_flag(args)?),
+        Command::Report(args) => Ok(run_report(args)?),
+        Command::SupportBundle(args) => Ok(run_support_bundle(args).await?),
+        Command::DecryptionKey(args) => Ok(run_decryption_key(args).await?),
+    }
+}
+
+/// Run a stage of the sweep, or the whole sweep, in a span recording the
+/// chain swept
+async fn run(cli: RunArgs, stage: SweepStage) -> Result<(), FeeSweeperError> {
+    let span = info_span!("run", chain = %cli.chain);
+    run_stage(cli, stage).instrument(span).await
+}
+
+/// Run a stage of the sweep, or the whole sweep
+async fn run_stage(cli: RunArgs, stage: SweepStage) -> Result<(), FeeSweeperError> {
+    let sweep = cli.sweep.clone();
+    let lease = cli.lease.clone();
+    let convert_fees = cli.conversion.convert_fees;
+    let mut indexer = start_run(cli, stage.redeems()).await?;
+
+    let res = if sweep.daemon {
+        indexer
+            .run_daemon(&sweep, stage, convert_fees, &lease)
+            .await
+    } else {
+        let res = indexer.sweep(&sweep, stage, convert_fees).await;
+        indexer.flush_outbox().await;
+        indexer.query_metrics.log_summary();
+        indexer.rpc_metrics.log_summary();
+        res
+    };
+    end_run(&mut indexer, res).await
+}
+
+/// Re-index the fees in a range of past blocks, in a span recording the
+/// chain backfilled
+async fn backfill(args: BackfillArgs) -> Result<(), FeeSweeperError> {
+    let span = info_span!("backfill", chain = %args.run.chain);
+    backfill_range(args).instrument(span).await
+}
+
+/// Re-index the fees in a range of past blocks
+async fn backfill_range(args: BackfillArgs) -> Result<(), FeeSweeperError> {
+    let mut indexer = start_run(args.run, false /* signs */).await?;
+    let res = indexer.backfill(args.from_block, args.to_block).await;
+    indexer.flush_outbox().await;
+    indexer.query_metrics.log_summary();
+    indexer.rpc_metrics.log_summary();
+    end_run(&mut indexer, res).await
+}
+
+/// Release the sweeper lease at the end of a run, whether or not it succeeded
+///
+/// A failed run returns its own error, logging any failure to release the
+/// lease, which then lapses after its TTL
+async fn end_run(
+    indexer: &mut Indexer,
+    res: Result<(), FeeSweeperError>,
+) -> Result<(), FeeSweeperError> {
+    let released = indexer.release_run_lease().await;
+    match res {
+        Ok(()) => released,
+        Err(e) => {
+            if let Err(release_err) = released {
+                warn!("failed to release sweeper lease: {release_err}");
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Take the sweeper lease and build the indexer for a run
+///
+/// The submission key is loaded only if the run signs transactions. A failure
+/// keeps the classification of where it arose, so that e.g. an unreachable
+/// Secrets Manager or DB is retried while a mismatched signer is fatal
+async fn start_run(cli: RunArgs, signs: bool) -> Result<Indexer, FeeSweeperError> {
+    if let Some(port) = cli.metrics_port {
+        telemetry::setup_metrics_exporter(port)?;
+    }
+    let shutdown = Shutdown::listen()?;
+    let mut db_conn = cli.build_db_conn()?;
+    let storage = cli.storage.build();
+    info!("storing state on {}", storage.name());
+    if cli.run_migrations {
+        let applied = run_pending_migrations(&mut db_conn, storage.as_ref())?;
+        info!("applied {applied} pending migrations");
+    }
+    let db_pool = cli.build_db_pool()?;
+
+    // Take over from any active instance before touching shared state
+    let run_id = Uuid::new_v4();
+    info!("starting run {run_id}");
+    acquire_run_lease(&mut db_conn, run_id, &cli.lease, &SystemClock).await?;
+
+    // Release the lease if the run fails to start, so that the next run need
+    // not wait for it to lapse
+    let db_url = cli.db_url.clone();
+    match build_indexer(cli, signs, run_id, db_conn, db_pool, storage, shutdown).await {
+        Ok(indexer) => Ok(indexer),
+        Err(e) => {
+            let released = PgConnection::establish(&db_url)
+                .map_err(FeeSweeperError::db("failed to connect to db"))
+                .and_then(|mut conn| release_run_lease(&mut conn, run_id));
+            if let Err(release_err) = released {
+                warn!("failed to release sweeper lease: {release_err}");
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Build the indexer for a run holding the sweeper lease
+async fn build_indexer(
+    cli: RunArgs,
+    signs: bool,
+    run_id: Uuid,
+    mut db_conn: PgConnection,
+    db_pool: DbPool,
+    storage: SharedStorage,
+    shutdown: Shutdown,
+) -> Result<Indexer, FeeSweeperError> {
     // Parse an AWS config
-    let config = aws_config::defaults(BehaviorVersion::latest())
-        .region(Region::new(DEFAULT_REGION))
-        .load()
-        .await;
+    let aws = AwsContext::load(DEFAULT_REGION, cli.aws_app_name, cli.aws_cost_tags).await?;
 
-    // Build an Arbitrum client
-    let wallet = LocalWallet::from_str(&cli.arbitrum_private_key)?;
+    // Load the submission key, checking that it is the active signer and that
+    // it can sign
+    let signer = match (&cli.arbitrum_private_key, &cli.kms_key_id) {
+        (Some(key), _) => {
+            let key = key.resolve(&aws).await?;
+            Some(SubmissionSigner::from_private_key(&key)?)
+        }
+        (None, Some(key_id)) => Some(SubmissionSigner::Kms(
+            KmsSigner::connect(&aws, key_id).await?,
+        )),
+        (None, None) if signs => {
+            return Err(FeeSweeperError::Config(
+                "redeeming requires a submission key, given by --pkey or --kms-key-id".to_string(),
+            ))
+        }
+        (None, None) => None,
+    };
+    if let Some(signer) = &signer {
+        check_active_signer(&mut db_conn, signer.address())?;
+        signer.check_signs(SIGNER_PROBE_MESSAGE).await?;
+        info!("signing as {:#x}", signer.address());
+    }
+
+    // The darkpool client takes only local keys, so it is given the submission
+    // key if local and otherwise reads the chain with an ephemeral key
+    let read_key = match &signer {
+        Some(SubmissionSigner::Local(wallet)) => wallet.clone(),
+        Some(SubmissionSigner::Kms(_)) => LocalWallet::new(&mut thread_rng()),
+        None => {
+            info!("no submission key given, reading the chain with an ephemeral key");
+            LocalWallet::new(&mut thread_rng())
+        }
+    };
+    let submission_address = signer
+        .as_ref()
+        .map_or(read_key.address(), |signer| signer.address());
+    let submitters = load_submitter_keys(&aws, &cli.submitter_keys, submission_address).await?;
+    if !submitters.is_empty() {
+        info!("submitting across {} keys", submitters.len() + 1);
+    }
+    let rpc_url = failover_rpc_url(cli.rpc_url, &cli.rpc_failover).await?;
+    let signer = signer.map(|signer| SignerSource {
+        key: cli.arbitrum_private_key,
+        signer,
+        submitters: submitters.clone(),
+        darkpool_address: cli.darkpool_address.clone(),
+        rpc_url: rpc_url.clone(),
+    });
+
+    // Build a darkpool client for the configured chain
+    let mut arb_priv_keys = vec![read_key];
+    arb_priv_keys.extend(submitters);
     let conf = ArbitrumClientConfig {
         darkpool_addr: cli.darkpool_address,
         chain: cli.chain,
-        rpc_url: cli.rpc_url,
-        arb_priv_keys: vec![wallet],
+        rpc_url,
+        arb_priv_keys,
         block_polling_interval_ms: BLOCK_POLLING_INTERVAL_MS,
     };
-    let client = ArbitrumClient::new(conf).await?;
-    let chain_id = client
-        .chain_id()
+    let client = ArbitrumClient::new(conf)
         .await
-        .map_err(raw_err_str!("Error fetching chain ID: {}"))?;
+        .map_err(FeeSweeperError::rpc("failed to build darkpool client"))?;
+    let client = EvmDarkpoolClient::new(client).await?;
+    let chain_id = client.chain_id();
+    info!("connected to {:?} (chain {chain_id})", client.chain());
 
     // Build the indexer
-    let key = DecryptionKey::from_hex_str(&cli.decryption_key)?;
-    let relayer_client = RelayerClient::new(&cli.relayer_url, &cli.usdc_mint);
+    let throttle = ThrottlePolicy {
+        max_retries: cli.max_throttle_retries,
+        max_delay: Duration::from_secs(cli.max_retry_after_secs),
+    };
+    let retry = RetryPolicy {
+        max_attempts: cli.relayer_max_attempts,
+        base_delay: Duration::from_millis(cli.relayer_retry_base_delay_ms),
+        max_delay: Duration::from_millis(cli.relayer_retry_max_delay_ms),
+    };
+    let connections = ConnectionPolicy {
+        connect_timeout: Duration::from_secs(cli.relayer_connect_timeout_secs),
+        request_timeout: Duration::from_secs(cli.relayer_request_timeout_secs),
+        pool_idle_timeout: Duration::from_secs(cli.relayer_pool_idle_timeout_secs),
+        tcp_keepalive: Duration::from_secs(cli.relayer_tcp_keepalive_secs),
+    };
+    let relayer_client = RelayerClient::new(
+        &cli.relayer_url,
+        &cli.usdc_mint,
+        run_id,
+        build_http_client(&cli.user_agent, &connections)?,
+        throttle,
+        retry,
+        Duration::from_secs(cli.relayer_task_timeout_secs),
+    );
+    info!(
+        "relayer requests traced under {}",
+        relayer_client.trace_id()
+    );
+    let historical_price_client = HistoricalPriceClient::new(
+        cli.historical_price_source,
+        cli.historical_price_url,
+        cli.historical_price_api_key,
+    )?;
+    let redemption_policies = RedemptionPolicies::new(cli.source_policies)?;
+    let mint_thresholds = MintThresholds::new(cli.mint_thresholds)?;
+    let issue_tracker = IssueTracker::from_args(cli.issues)?;
+    let dust_filter = DustFilter::new(cli.dust_floors, cli.dust_action)?;
+    let batch_sizer = BatchSizer::new(cli.batch_sizing)?;
+    let log_window = LogWindow::new(&cli.event_source)?;
+    let notifier = Notifier::new(cli.notifications)?;
     let mut indexer = Indexer::new(
         chain_id,
         cli.chain,
-        config,
+        run_id,
+        aws,
         client,
-        key,
-        db_conn,
+        signer,
+        cli.fee_keys,
+        db_pool,
+        storage,
         relayer_client,
+        historical_price_client,
+        redemption_policies,
+        mint_thresholds,
+        cli.simulate_redemptions,
+        cli.external_fee_recipient,
+        cli.phase_db_budget_secs.map(Duration::from_secs),
+        cli.rpc_budget,
+        cli.finality,
+        cli.redemption_retry,
+        cli.max_task_queue_depth,
+        batch_sizer,
+        Duration::from_secs(cli.fee_claim_ttl_secs),
+        issue_tracker,
+        Duration::from_secs(cli.price_warmup_secs),
+        cli.conversion,
+        dust_filter,
+        cli.gas_filter,
+        cli.scorer,
+        cli.spam,
+        cli.blocklist,
+        cli.event_source,
+        log_window,
+        DecryptionPool::new(cli.decryption_workers),
+        notifier,
+        cli.abandonment,
+        cli.dual_write,
+        cli.wallet_backup,
+        cli.consistency,
+        cli.confirmation,
+        cli.slo,
+        cli.wallet_lock,
+        FeatureFlags::new(cli.feature_flags),
+        shutdown,
     );
+    indexer.refresh_feature_flags().await;
+    indexer.feature_flags.log_states();
+    indexer.load_fee_keys().await?;
 
-    // 1. Index all new fees in the DB
-    indexer.index_fees().await?;
-    // 2. Redeem fees according to the redemption policy
-    indexer.redeem_fees().await?;
+    Ok(indexer)
+}
 
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    use super::Cli;
+
+    #[test]
+    fn cli_is_well_formed() {
+        Cli::command().debug_assert();
+    }
 }
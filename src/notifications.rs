@@ -0,0 +1,204 @@
+//! Notifications posted to Slack channels, rate limited per channel
+//!
+//! Notices are queued in the DB outbox and delivered from there, see
+//! `indexer::outbox`. Each channel posts at most one message per interval.
+//! Notices raised while a channel is rate limited wait in the outbox and are
+//! posted together as a digest once the interval elapses, so that a burst of
+//! notices, e.g. from a large backfill, becomes a handful of messages rather
+//! than a flood. Notices to a channel without a configured webhook are dropped
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use clap::Args;
+use reqwest::Client;
+use serde_json::json;
+
+use crate::error::FeeSweeperError;
+
+/// The channel of per-fee redemption notices
+pub const REDEMPTIONS_CHANNEL: &str = "redemptions";
+/// The channel of alerts requiring operator attention
+pub const ALERTS_CHANNEL: &str = "alerts";
+/// The channel of the daemon's scheduled fee reports
+pub const REPORTS_CHANNEL: &str = "reports";
+
+/// A Slack webhook of a channel
+///
+/// Parsed from a string of the form `<channel>=<webhook_url>`
+#[derive(Clone, Debug)]
+pub struct ChannelWebhook {
+    /// The channel name
+    pub channel: String,
+    /// The Slack incoming webhook URL
+    pub url: String,
+}
+
+impl FromStr for ChannelWebhook {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (channel, url) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected <channel>=<webhook_url>: {s}"))?;
+        Ok(ChannelWebhook {
+            channel: channel.trim().to_string(),
+            url: url.trim().to_string(),
+        })
+    }
+}
+
+/// The arguments configuring notifications
+#[derive(Clone, Debug, Args)]
+pub struct NotificationArgs {
+    /// A Slack webhook to post a channel's notifications to, of the form
+    /// `<channel>=<webhook_url>`; the channels are `redemptions`, `alerts`,
+    /// and `reports`
+    #[clap(long = "slack-webhook")]
+    pub slack_webhooks: Vec<ChannelWebhook>,
+    /// The minimum time, in seconds, between messages posted to a channel
+    #[clap(long, default_value_t = 60)]
+    pub notification_interval_secs: u64,
+    /// The maximum number of notices listed in a digest, beyond which they
+    /// are counted
+    #[clap(long, default_value_t = 25)]
+    pub max_digest_notices: usize,
+    /// The maximum number of attempts to deliver a notice, after which it is
+    /// left undelivered in the outbox
+    #[clap(long, default_value_t = 10)]
+    pub outbox_max_attempts: i32,
+    /// The delay, in seconds, before retrying a notice that failed to post
+    #[clap(long, default_value_t = 60)]
+    pub outbox_retry_delay_secs: u64,
+}
+
+/// A rate limited channel
+struct Channel {
+    /// The Slack incoming webhook URL
+    url: String,
+    /// When a message was last posted
+    last_sent: Option<Instant>,
+}
+
+/// Posts digests of notices to rate limited channels
+pub struct Notifier {
+    /// The channels, keyed by name
+    channels: HashMap<String, Channel>,
+    /// The minimum time between messages posted to a channel
+    interval: Duration,
+    /// The maximum number of notices listed in a digest
+    max_digest_notices: usize,
+    /// The maximum number of attempts to deliver a notice
+    pub max_attempts: i32,
+    /// The delay before retrying a notice that failed to post
+    pub retry_delay: Duration,
+    /// The client used to post messages
+    client: Client,
+}
+
+impl Notifier {
+    /// Create a notifier from its arguments
+    pub fn new(args: NotificationArgs) -> Result<Self, FeeSweeperError> {
+        let mut channels = HashMap::new();
+        for webhook in args.slack_webhooks {
+            let channel = Channel {
+                url: webhook.url,
+                last_sent: None,
+            };
+            if channels.insert(webhook.channel.clone(), channel).is_some() {
+                return Err(FeeSweeperError::Config(format!(
+                    "duplicate webhook for {}",
+                    webhook.channel
+                )));
+            }
+        }
+
+        Ok(Self {
+            channels,
+            interval: Duration::from_secs(args.notification_interval_secs),
+            max_digest_notices: args.max_digest_notices,
+            max_attempts: args.outbox_max_attempts,
+            retry_delay: Duration::from_secs(args.outbox_retry_delay_secs),
+            client: Client::new(),
+        })
+    }
+
+    /// Whether a channel has a configured webhook
+    pub fn has_channel(&self, channel: &str) -> bool {
+        self.channels.contains_key(channel)
+    }
+
+    /// The names of the channels with a configured webhook
+    pub fn channels(&self) -> Vec<String> {
+        self.channels.keys().cloned().collect()
+    }
+
+    /// The time until a channel may post its next message
+    pub fn wait_time(&self, channel: &str) -> Duration {
+        self.channels
+            .get(channel)
+            .and_then(|chan| chan.last_sent)
+            .map(|sent| self.interval.saturating_sub(sent.elapsed()))
+            .unwrap_or_default()
+    }
+
+    /// Post notices to a channel as a single message
+    ///
+    /// The channel's rate limit is consumed whether or not the post succeeds
+    pub async fn post_digest(
+        &mut self,
+        channel: &str,
+        notices: &[String],
+    ) -> Result<(), FeeSweeperError> {
+        let max_notices = self.max_digest_notices;
+        let chan = match self.channels.get_mut(channel) {
+            Some(chan) => chan,
+            None => {
+                return Err(FeeSweeperError::Config(format!(
+                    "no webhook configured for {channel}"
+                )))
+            }
+        };
+
+        chan.last_sent = Some(Instant::now());
+        let url = chan.url.clone();
+        self.post(&url, &digest(notices, max_notices)).await
+    }
+
+    /// Post a message to a Slack webhook
+    async fn post(&self, url: &str, text: &str) -> Result<(), FeeSweeperError> {
+        let resp = self
+            .client
+            .post(url)
+            .json(&json!({ "text": text }))
+            .send()
+            .await
+            .map_err(FeeSweeperError::rpc("Failed to post notification"))?;
+        if !resp.status().is_success() {
+            return Err(FeeSweeperError::Rpc(format!(
+                "Failed to post notification: {}",
+                resp.status()
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// Format notices as a single message, listing at most `max_notices`
+fn digest(notices: &[String], max_notices: usize) -> String {
+    if let [notice] = notices {
+        return notice.clone();
+    }
+
+    let mut text = format!("{} notices:", notices.len());
+    for notice in notices.iter().take(max_notices) {
+        text.push_str(&format!("\n• {notice}"));
+    }
+    if notices.len() > max_notices {
+        text.push_str(&format!("\n…and {} more", notices.len() - max_notices));
+    }
+
+    text
+}
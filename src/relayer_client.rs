@@ -1,26 +1,43 @@
 //! Client code for interacting with a configured relayer
 
-use std::time::Duration;
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
 
 use base64::engine::{general_purpose as b64_general_purpose, Engine};
+use chrono::{DateTime, Utc};
 use ethers::{
     core::k256::ecdsa::{signature::Signer, Signature, SigningKey},
-    signers::LocalWallet,
+    signers::{LocalWallet, Signer as EthSigner},
+    types::Address,
+    utils::keccak256,
 };
 use http::{HeaderMap, HeaderValue};
+use metrics::counter;
 use renegade_api::{
     http::{
         price_report::{GetPriceReportRequest, GetPriceReportResponse, PRICE_REPORT_ROUTE},
-        task::{GetTaskStatusResponse, GET_TASK_STATUS_ROUTE},
+        task::{
+            GetTaskStatusResponse, TaskQueueListResponse, GET_TASK_QUEUE_ROUTE,
+            GET_TASK_STATUS_ROUTE,
+        },
         wallet::{
+            CancelOrderRequest, CancelOrderResponse, CreateOrderRequest, CreateOrderResponse,
             CreateWalletRequest, CreateWalletResponse, FindWalletRequest, FindWalletResponse,
-            GetWalletResponse, RedeemNoteRequest, RedeemNoteResponse, CREATE_WALLET_ROUTE,
-            FIND_WALLET_ROUTE, GET_WALLET_ROUTE, REDEEM_NOTE_ROUTE,
+            GetWalletResponse, PayFeesResponse, RedeemNoteRequest, RedeemNoteResponse,
+            CANCEL_ORDER_ROUTE, CREATE_WALLET_ROUTE, FIND_WALLET_ROUTE, GET_WALLET_ROUTE,
+            PAY_FEES_ROUTE, REDEEM_NOTE_ROUTE, WALLET_ORDERS_ROUTE,
         },
     },
-    RENEGADE_AUTH_HEADER_NAME, RENEGADE_SIG_EXPIRATION_HEADER_NAME,
+    EmptyRequestResponse, RENEGADE_AUTH_HEADER_NAME, RENEGADE_SIG_EXPIRATION_HEADER_NAME,
 };
-use renegade_circuit_types::keychain::SecretSigningKey;
+use renegade_circuit_types::{keychain::SecretSigningKey, order::Order};
 use renegade_common::types::{
     exchange::PriceReporterState,
     token::Token,
@@ -28,20 +45,249 @@ use renegade_common::types::{
         derivation::{
             derive_blinder_seed, derive_share_seed, derive_wallet_id, derive_wallet_keychain,
         },
-        Wallet, WalletIdentifier,
+        KeyChain, OrderIdentifier, Wallet, WalletIdentifier,
     },
 };
+use renegade_constants::Scalar;
 use renegade_crypto::fields::scalar_to_biguint;
-use renegade_util::{get_current_time_millis, raw_err_str};
-use reqwest::{Body, Client};
+use renegade_util::get_current_time_millis;
+use reqwest::{
+    header::{CONTENT_TYPE, RETRY_AFTER},
+    Body, Client, ClientBuilder, RequestBuilder, Response, StatusCode,
+};
 use serde::{Deserialize, Serialize};
-use tracing::warn;
+use tracing::{debug, info, info_span, warn, Instrument};
 use uuid::Uuid;
 
-/// The interval at which to poll relayer task status
+use crate::clock::{system_clock, SharedClock, SharedRng};
+use crate::error::FeeSweeperError;
+use crate::telemetry::{RELAYER_RETRIES_METRIC, RELAYER_THROTTLED_METRIC, THROTTLE_STATUS_LABEL};
+
+/// The default user agent sent with relayer requests
+pub const DEFAULT_USER_AGENT: &str = concat!("renegade-fee-sweeper/", env!("CARGO_PKG_VERSION"));
+/// The header carrying the id of a request
+const REQUEST_ID_HEADER: &str = "x-request-id";
+/// The W3C trace context header propagated on requests
+const TRACEPARENT_HEADER: &str = "traceparent";
+/// The W3C trace context response header, in which the relayer may return
+/// the trace of the work a request started
+const TRACERESPONSE_HEADER: &str = "traceresponse";
+/// The message of the relayer's rejection of a wallet it already manages
+const WALLET_EXISTS_MESSAGE: &str = "already exists";
+/// The interval before the first poll of a relayer task's status
 const POLL_INTERVAL_MS: u64 = 1000;
+/// The longest interval between polls of a relayer task's status
+const MAX_POLL_INTERVAL_MS: u64 = 10_000;
+/// The factor by which the interval between polls of a task grows
+const POLL_BACKOFF_FACTOR: f64 = 1.5;
+/// The fraction by which a poll interval is randomly lengthened or shortened
+const POLL_JITTER: f64 = 0.2;
+/// The default longest time, in seconds, a relayer task is awaited
+pub const DEFAULT_TASK_TIMEOUT_SECS: u64 = 600;
+/// The interval at which to poll price reporters that are warming up
+const PRICE_WARMUP_POLL_INTERVAL_MS: u64 = 2000;
 /// The amount of time (ms) to declare a wallet signature value for
 const SIG_EXPIRATION_BUFFER_MS: u64 = 5000;
+/// The default number of times a throttled request is retried
+const DEFAULT_MAX_THROTTLE_RETRIES: u32 = 3;
+/// The default longest `Retry-After` delay, in seconds, honored
+const DEFAULT_MAX_RETRY_AFTER_SECS: u64 = 60;
+/// The default number of times a request failing transiently is attempted
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 4;
+/// The default delay (ms) before the first retry of a transient failure
+pub const DEFAULT_RETRY_BASE_DELAY_MS: u64 = 500;
+/// The default longest delay (ms) between retries of a transient failure
+pub const DEFAULT_RETRY_MAX_DELAY_MS: u64 = 8000;
+/// The default time (s) allowed to connect to the relayer
+pub const DEFAULT_CONNECT_TIMEOUT_SECS: u64 = 10;
+/// The default time (s) allowed for a relayer request to complete
+pub const DEFAULT_REQUEST_TIMEOUT_SECS: u64 = 30;
+/// The default time (s) an idle pooled connection is kept open
+pub const DEFAULT_POOL_IDLE_TIMEOUT_SECS: u64 = 90;
+/// The default interval (s) of TCP keep-alive probes on open connections
+pub const DEFAULT_TCP_KEEPALIVE_SECS: u64 = 60;
+
+/// How the client honors the relayer's throttling responses
+///
+/// A `429` or `503` response carrying a `Retry-After` header is retried after
+/// the requested delay, capped at `max_delay`, up to `max_retries` times
+#[derive(Clone, Copy, Debug)]
+pub struct ThrottlePolicy {
+    /// The maximum number of times a throttled request is retried
+    pub max_retries: u32,
+    /// The longest delay honored before retrying
+    pub max_delay: Duration,
+}
+
+impl Default for ThrottlePolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: DEFAULT_MAX_THROTTLE_RETRIES,
+            max_delay: Duration::from_secs(DEFAULT_MAX_RETRY_AFTER_SECS),
+        }
+    }
+}
+
+/// The timeouts and keep-alive of the client's connections to the relayer
+#[derive(Clone, Copy, Debug)]
+pub struct ConnectionPolicy {
+    /// The time allowed to connect to the relayer
+    pub connect_timeout: Duration,
+    /// The time allowed for a request to complete, from connecting to reading
+    /// the response body
+    pub request_timeout: Duration,
+    /// The time an idle pooled connection is kept open for reuse
+    pub pool_idle_timeout: Duration,
+    /// The interval of TCP keep-alive probes on open connections
+    pub tcp_keepalive: Duration,
+}
+
+impl Default for ConnectionPolicy {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(DEFAULT_CONNECT_TIMEOUT_SECS),
+            request_timeout: Duration::from_secs(DEFAULT_REQUEST_TIMEOUT_SECS),
+            pool_idle_timeout: Duration::from_secs(DEFAULT_POOL_IDLE_TIMEOUT_SECS),
+            tcp_keepalive: Duration::from_secs(DEFAULT_TCP_KEEPALIVE_SECS),
+        }
+    }
+}
+
+/// How the client retries requests that fail transiently
+///
+/// A request that times out, fails to connect, or is answered with a `429` or
+/// `5xx` status is retried after an exponentially growing, jittered delay, up
+/// to `max_attempts` attempts in all. Only requests that are safe to repeat
+/// are retried this way; see `RelayerClient::redeem_note` for redemptions.
+/// Every retry of a signed request, including a resent redemption, carries a
+/// fresh signature, as the delay may outlast the signature's expiration
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    /// The maximum number of times a request is attempted
+    pub max_attempts: u32,
+    /// The delay before the first retry, doubled on each retry after
+    pub base_delay: Duration,
+    /// The longest delay between retries
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// The delay before retrying after the given number of attempts
+    fn backoff(&self, attempts: u32, rng: &SharedRng) -> Duration {
+        let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+        jittered(
+            self.base_delay.saturating_mul(factor).min(self.max_delay),
+            rng,
+        )
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            base_delay: Duration::from_millis(DEFAULT_RETRY_BASE_DELAY_MS),
+            max_delay: Duration::from_millis(DEFAULT_RETRY_MAX_DELAY_MS),
+        }
+    }
+}
+
+/// A failed relayer request or task, with the relayer's payload describing it
+#[derive(Clone, Debug)]
+pub struct RelayerFailure {
+    /// The id of the task the relayer started, if it started one
+    pub task_id: Option<Uuid>,
+    /// A description of the failure
+    pub reason: String,
+    /// The relayer's payload describing the failure: the body of a rejected
+    /// request, or the last status of a failed task
+    pub payload: Option<String>,
+}
+
+impl Display for RelayerFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.reason)
+    }
+}
+
+impl From<FeeSweeperError> for RelayerFailure {
+    fn from(e: FeeSweeperError) -> Self {
+        Self {
+            task_id: None,
+            reason: e.to_string(),
+            payload: None,
+        }
+    }
+}
+
+impl From<RelayerFailure> for FeeSweeperError {
+    fn from(e: RelayerFailure) -> Self {
+        FeeSweeperError::Relayer(e.reason)
+    }
+}
+
+/// The state of a relayer task, parsed from its status
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TaskState {
+    /// The task is waiting behind the wallet's other tasks
+    Queued,
+    /// The task is running, e.g. proving or submitting its transaction
+    Running,
+    /// The task finished successfully
+    Completed,
+    /// The task failed
+    Failed,
+}
+
+impl TaskState {
+    /// Parse the state reported in a task's status
+    ///
+    /// A running task reports the step it is on, e.g. `Proving`, so any state
+    /// not otherwise recognized is taken to be running
+    fn parse(state: &str) -> Self {
+        let state = state.to_lowercase();
+        if state.starts_with("queued") || state.starts_with("preemptive") {
+            TaskState::Queued
+        } else if state.starts_with("completed") {
+            TaskState::Completed
+        } else if state.contains("failed") {
+            TaskState::Failed
+        } else {
+            TaskState::Running
+        }
+    }
+}
+
+/// How an awaited relayer task ended, if it did not fail
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum TaskEnd {
+    /// The task was seen to complete, with the last status polled
+    Completed(Option<String>),
+    /// The relayer dropped the task before it was seen to finish, so its
+    /// outcome is unknown, with the last status polled
+    Dropped(Option<String>),
+}
+
+/// A relayer task that ran to completion
+#[derive(Clone, Debug)]
+pub struct CompletedTask {
+    /// The id of the task
+    pub task_id: Uuid,
+    /// The last status of the task polled before it completed, serialized
+    pub last_status: Option<String>,
+}
+
+/// The values derived from a wallet's root Ethereum key
+pub(crate) struct DerivedWallet {
+    /// The id of the wallet
+    pub wallet_id: WalletIdentifier,
+    /// The seed of the wallet's blinder stream
+    pub blinder_seed: Scalar,
+    /// The seed of the wallet's secret share stream
+    pub share_seed: Scalar,
+    /// The wallet's keychain
+    pub keychain: KeyChain,
+}
 
 /// A client for interacting with a configured relayer
 pub struct RelayerClient {
@@ -49,38 +295,192 @@ pub struct RelayerClient {
     base_url: String,
     /// The mind of the USDC token
     usdc_mint: String,
+    /// The id of the run making requests, from which request ids are derived
+    run_id: Uuid,
+    /// The number of requests sent so far in the run
+    request_count: AtomicU64,
+    /// The HTTP client sending requests, shared so that its connections and
+    /// TLS sessions are reused across requests
+    http_client: Client,
+    /// The wallets derived from each `(root key address, chain id)`, cached as
+    /// derivation is expensive
+    derivations: Mutex<HashMap<(Address, u64), Arc<DerivedWallet>>>,
+    /// How throttling responses are honored
+    throttle: ThrottlePolicy,
+    /// How transient failures are retried
+    retry: RetryPolicy,
+    /// The longest time a relayer task is awaited before it is failed
+    task_timeout: Duration,
+    /// The clock deadlines are measured and waits are slept on
+    clock: SharedClock,
+    /// The RNG jittering polls and retries
+    rng: SharedRng,
 }
 
 impl RelayerClient {
     /// Create a new relayer client
-    pub fn new(base_url: &str, usdc_mint: &str) -> Self {
+    ///
+    /// The HTTP client is typically built from `http_client_builder`, further
+    /// configured as needed, e.g. with custom root certificates or a proxy
+    pub fn new(
+        base_url: &str,
+        usdc_mint: &str,
+        run_id: Uuid,
+        http_client: Client,
+        throttle: ThrottlePolicy,
+        retry: RetryPolicy,
+        task_timeout: Duration,
+    ) -> Self {
         Self {
             base_url: base_url.to_string(),
             usdc_mint: usdc_mint.to_string(),
+            run_id,
+            request_count: AtomicU64::new(0),
+            http_client,
+            derivations: Mutex::new(HashMap::new()),
+            throttle,
+            retry,
+            task_timeout,
+            clock: system_clock(),
+            rng: SharedRng::from_entropy(),
+        }
+    }
+
+    /// Measure deadlines and sleep on the given clock
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Jitter polls and retries with the given RNG
+    pub fn with_rng(mut self, rng: SharedRng) -> Self {
+        self.rng = rng;
+        self
+    }
+
+    /// The mint of the USDC token prices are quoted in
+    pub(crate) fn usdc_mint(&self) -> &str {
+        &self.usdc_mint
+    }
+
+    /// Derive the wallet of a root key on a chain, from the cache if possible
+    pub(crate) fn derive_wallet(
+        &self,
+        eth_key: &LocalWallet,
+        chain_id: u64,
+    ) -> Result<Arc<DerivedWallet>, FeeSweeperError> {
+        let cache_key = (eth_key.address(), chain_id);
+        if let Some(derived) = self.derivations.lock().unwrap().get(&cache_key) {
+            return Ok(derived.clone());
         }
+
+        let derived = Arc::new(DerivedWallet {
+            wallet_id: derive_wallet_id(eth_key).map_err(FeeSweeperError::Config)?,
+            blinder_seed: derive_blinder_seed(eth_key).map_err(FeeSweeperError::Config)?,
+            share_seed: derive_share_seed(eth_key).map_err(FeeSweeperError::Config)?,
+            keychain: derive_wallet_keychain(eth_key, chain_id).map_err(FeeSweeperError::Config)?,
+        });
+        self.derivations
+            .lock()
+            .unwrap()
+            .insert(cache_key, derived.clone());
+
+        Ok(derived)
     }
 
     /// Get the price for a given mint
-    pub async fn get_binance_price(&self, mint: &str) -> Result<Option<f64>, String> {
+    pub async fn get_binance_price(&self, mint: &str) -> Result<Option<f64>, FeeSweeperError> {
         if mint == self.usdc_mint {
             return Ok(Some(1.0));
         }
 
+        self.get_price_report(mint, &self.usdc_mint).await
+    }
+
+    /// Get the price of a mint, through its price route if it has one
+    ///
+    /// A routed mint's price is its price in the intermediate mint times the
+    /// intermediate mint's price, so that a mint without a USDC pair at the
+    /// price source may still be priced
+    pub async fn get_routed_price(
+        &self,
+        mint: &str,
+        routes: &HashMap<String, String>,
+    ) -> Result<Option<f64>, FeeSweeperError> {
+        let via_mint = match routes.get(&mint.to_lowercase()) {
+            Some(via_mint) => via_mint,
+            None => return self.get_binance_price(mint).await,
+        };
+
+        let leg_price = self.get_price_report(mint, via_mint).await?;
+        let via_price = self.get_binance_price(via_mint).await?;
+        Ok(leg_price.zip(via_price).map(|(leg, via)| leg * via))
+    }
+
+    /// Get the price of a base mint in a quote mint, if its price reporter is
+    /// nominal
+    async fn get_price_report(
+        &self,
+        base: &str,
+        quote: &str,
+    ) -> Result<Option<f64>, FeeSweeperError> {
         let body = GetPriceReportRequest {
-            base_token: Token::from_addr(mint),
-            quote_token: Token::from_addr(&self.usdc_mint),
+            base_token: Token::from_addr(base),
+            quote_token: Token::from_addr(quote),
         };
         let response: GetPriceReportResponse = self.post_relayer(PRICE_REPORT_ROUTE, &body).await?;
 
         match response.price_report {
             PriceReporterState::Nominal(report) => Ok(Some(report.price)),
             state => {
-                warn!("Price report state: {state:?}");
+                debug!("Price report state: {state:?}");
                 Ok(None)
             }
         }
     }
 
+    /// Get the prices of the given mints, through their price routes if any,
+    /// waiting up to `timeout` for their price reporters to reach a nominal
+    /// state
+    ///
+    /// A price request starts the relayer's reporter for a mint if it is not
+    /// already running, and fresh reporters take some time to become nominal.
+    /// Mints whose reporters are not nominal by the deadline are omitted
+    pub async fn get_warm_prices(
+        &self,
+        mints: &[String],
+        timeout: Duration,
+        routes: &HashMap<String, String>,
+    ) -> Result<HashMap<String, f64>, FeeSweeperError> {
+        let deadline = self.clock.instant() + timeout;
+        let poll_interval = Duration::from_millis(PRICE_WARMUP_POLL_INTERVAL_MS);
+
+        let mut prices = HashMap::new();
+        let mut pending: Vec<&String> = mints.iter().collect();
+        loop {
+            let mut not_ready = Vec::new();
+            for mint in pending {
+                match self.get_routed_price(mint, routes).await? {
+                    Some(price) => {
+                        prices.insert(mint.clone(), price);
+                    }
+                    None => not_ready.push(mint),
+                }
+            }
+
+            pending = not_ready;
+            if pending.is_empty() || self.clock.instant() + poll_interval > deadline {
+                break;
+            }
+            self.clock.sleep(poll_interval).await;
+        }
+
+        for mint in pending {
+            warn!("{mint}: price reporter not nominal after warm-up");
+        }
+        Ok(prices)
+    }
+
     // ------------------
     // | Wallet Methods |
     // ------------------
@@ -91,12 +491,12 @@ impl RelayerClient {
         wallet_id: WalletIdentifier,
         chain_id: u64,
         eth_key: &LocalWallet,
-    ) -> Result<(), String> {
+    ) -> Result<(), FeeSweeperError> {
         let mut path = GET_WALLET_ROUTE.to_string();
         path = path.replace(":wallet_id", &wallet_id.to_string());
 
-        let keychain = derive_wallet_keychain(eth_key, chain_id).unwrap();
-        let root_key = keychain.secret_keys.sk_root.unwrap();
+        let derived = self.derive_wallet(eth_key, chain_id)?;
+        let root_key = derived.keychain.secret_keys.sk_root.clone().unwrap();
         if self
             .get_relayer_with_auth::<GetWalletResponse>(&path, &root_key)
             .await
@@ -109,47 +509,317 @@ impl RelayerClient {
         self.lookup_wallet(chain_id, eth_key).await
     }
 
+    /// Get the relayer's current view of a wallet
+    pub(crate) async fn get_wallet(
+        &self,
+        wallet_id: WalletIdentifier,
+        root_key: &SecretSigningKey,
+    ) -> Result<Wallet, FeeSweeperError> {
+        let mut path = GET_WALLET_ROUTE.to_string();
+        path = path.replace(":wallet_id", &wallet_id.to_string());
+
+        let resp: GetWalletResponse = self.get_relayer_with_auth(&path, root_key).await?;
+        Wallet::try_from(resp.wallet)
+            .map_err(FeeSweeperError::relayer("invalid wallet from relayer"))
+    }
+
+    /// Get the number of tasks queued on a wallet in the relayer, including
+    /// the one running
+    pub(crate) async fn get_task_queue_depth(
+        &self,
+        wallet_id: WalletIdentifier,
+        root_key: &SecretSigningKey,
+    ) -> Result<usize, FeeSweeperError> {
+        self.get_task_queue(wallet_id, root_key)
+            .await
+            .map(|tasks| tasks.len())
+    }
+
+    /// Get the ids of the tasks queued on a wallet in the relayer, including
+    /// the one running
+    async fn get_task_queue(
+        &self,
+        wallet_id: WalletIdentifier,
+        root_key: &SecretSigningKey,
+    ) -> Result<Vec<Uuid>, FeeSweeperError> {
+        let mut path = GET_TASK_QUEUE_ROUTE.to_string();
+        path = path.replace(":wallet_id", &wallet_id.to_string());
+
+        let resp: TaskQueueListResponse = self.get_relayer_with_auth(&path, root_key).await?;
+        Ok(resp.tasks.into_iter().map(|task| task.id).collect())
+    }
+
+    /// Re-sync the relayer's view of a wallet from the chain, e.g. after a
+    /// failed task may have left it stale
+    pub(crate) async fn refresh_wallet(
+        &self,
+        chain_id: u64,
+        eth_key: &LocalWallet,
+    ) -> Result<(), FeeSweeperError> {
+        self.lookup_wallet(chain_id, eth_key).await
+    }
+
     /// Lookup a wallet in the configured relayer
-    async fn lookup_wallet(&self, chain_id: u64, eth_key: &LocalWallet) -> Result<(), String> {
+    async fn lookup_wallet(
+        &self,
+        chain_id: u64,
+        eth_key: &LocalWallet,
+    ) -> Result<(), FeeSweeperError> {
         let path = FIND_WALLET_ROUTE.to_string();
-        let wallet_id = derive_wallet_id(eth_key).unwrap();
-        let blinder_seed = derive_blinder_seed(eth_key).unwrap();
-        let share_seed = derive_share_seed(eth_key).unwrap();
-        let keychain = derive_wallet_keychain(eth_key, chain_id).unwrap();
-        let root_key = keychain.secret_keys.sk_root.clone().unwrap();
+        let derived = self.derive_wallet(eth_key, chain_id)?;
+        let root_key = derived.keychain.secret_keys.sk_root.clone().unwrap();
 
         let body = FindWalletRequest {
-            wallet_id,
-            secret_share_seed: scalar_to_biguint(&share_seed),
-            blinder_seed: scalar_to_biguint(&blinder_seed),
-            key_chain: keychain.into(),
+            wallet_id: derived.wallet_id,
+            secret_share_seed: scalar_to_biguint(&derived.share_seed),
+            blinder_seed: scalar_to_biguint(&derived.blinder_seed),
+            key_chain: derived.keychain.clone().into(),
         };
 
-        let resp: FindWalletResponse = self.post_relayer_with_auth(&path, &body, &root_key).await?;
+        // Looking a wallet up again only re-syncs it, so the request is retried
+        let resp: FindWalletResponse = self
+            .post_relayer_with_auth(&path, &body, &root_key, true /* idempotent */)
+            .await?;
         self.await_relayer_task(resp.task_id).await
     }
 
     /// Create a new wallet via the configured relayer
-    pub(crate) async fn create_new_wallet(&self, wallet: Wallet) -> Result<(), String> {
+    ///
+    /// Creation is idempotent: if the relayer already manages the wallet, e.g.
+    /// as a retry races an earlier attempt, the existing wallet is looked up
+    /// instead of failing
+    pub(crate) async fn create_new_wallet(
+        &self,
+        wallet: Wallet,
+        chain_id: u64,
+        eth_key: &LocalWallet,
+    ) -> Result<(), FeeSweeperError> {
+        let wallet_id = wallet.wallet_id;
         let body = CreateWalletRequest {
             wallet: wallet.into(),
         };
 
-        let resp: CreateWalletResponse = self.post_relayer(CREATE_WALLET_ROUTE, &body).await?;
+        let resp = self
+            .send_post(
+                CREATE_WALLET_ROUTE,
+                &body,
+                None, /* auth */
+                true, /* idempotent */
+            )
+            .await?;
+        let status = resp.status();
+        if !status.is_success() {
+            let body = resp.text().await.unwrap_or_default();
+            if !is_wallet_exists_rejection(status, &body) {
+                return Err(FeeSweeperError::Relayer(format!(
+                    "Failed to send request: {status}"
+                )));
+            }
+
+            info!("wallet {wallet_id} already exists in the relayer, looking it up");
+            return self
+                .check_wallet_indexed(wallet_id, chain_id, eth_key)
+                .await;
+        }
+
+        let resp: CreateWalletResponse = resp
+            .json()
+            .await
+            .map_err(FeeSweeperError::relayer("Failed to parse response"))?;
         self.await_relayer_task(resp.task_id).await
     }
 
-    /// Redeem a note into a wallet
+    /// Start redeeming a note into a wallet, returning the id of the relayer
+    /// task redeeming it, which the caller awaits with `await_redemption`
+    ///
+    /// The task is not awaited here, so that the caller may record its id
+    /// before a long poll that a crash may interrupt. A failure carries the
+    /// relayer's payload, so that it may be triaged without querying the
+    /// relayer again
+    ///
+    /// The relayer may start a redemption task before failing to answer the
+    /// request, so a redemption failing transiently is not blindly resent: if
+    /// a task appeared in the wallet's queue since the request was sent, that
+    /// task is awaited instead, and the caller verifies its result on-chain.
+    /// A task that finished before the queue is checked is missed, in which
+    /// case the resent redemption is rejected as its note is already spent.
+    /// Each resend is signed anew by `send_post`, like its throttle retries
     pub(crate) async fn redeem_note(
         &self,
         wallet_id: WalletIdentifier,
         req: RedeemNoteRequest,
         root_key: &SecretSigningKey,
-    ) -> Result<(), String> {
+    ) -> Result<Uuid, RelayerFailure> {
         let mut path = REDEEM_NOTE_ROUTE.to_string();
         path = path.replace(":wallet_id", &wallet_id.to_string());
 
-        let resp: RedeemNoteResponse = self.post_relayer_with_auth(&path, &req, root_key).await?;
+        let mut attempts = 1;
+        let resp = loop {
+            let queued = self.get_task_queue(wallet_id, root_key).await?;
+            let res = self
+                .send_post(&path, &req, Some(root_key), false /* idempotent */)
+                .await;
+            let transient = match &res {
+                Ok(resp) => is_transient_status(resp.status()),
+                Err(_) => true,
+            };
+            if !transient || attempts >= self.retry.max_attempts {
+                break res?;
+            }
+
+            let requeued = self.get_task_queue(wallet_id, root_key).await?;
+            if let Some(task_id) = requeued.into_iter().find(|id| !queued.contains(id)) {
+                warn!("redemption into {wallet_id} started task {task_id} before failing, awaiting it");
+                return Ok(task_id);
+            }
+
+            let delay = self.retry.backoff(attempts, &self.rng);
+            warn!("redemption into {wallet_id} failed transiently, retrying in {delay:?}");
+            counter!(RELAYER_RETRIES_METRIC).increment(1);
+            self.clock.sleep(delay).await;
+            attempts += 1;
+        };
+
+        let status = resp.status();
+        if !status.is_success() {
+            return Err(RelayerFailure {
+                task_id: None,
+                reason: format!("Failed to send request: {status}"),
+                payload: resp.text().await.ok(),
+            });
+        }
+
+        let resp: RedeemNoteResponse = resp
+            .json()
+            .await
+            .map_err(FeeSweeperError::relayer("Failed to parse response"))?;
+        Ok(resp.task_id)
+    }
+
+    /// Await the relayer task redeeming a note, started by `redeem_note`
+    ///
+    /// A task the relayer drops before it is seen to finish is returned as
+    /// completed, as the caller verifies the note's nullifier is spent on-chain
+    pub(crate) async fn await_redemption(
+        &self,
+        task_id: Uuid,
+    ) -> Result<CompletedTask, RelayerFailure> {
+        let last_status = match self.poll_relayer_task(task_id).await? {
+            TaskEnd::Completed(status) => status,
+            TaskEnd::Dropped(status) => {
+                warn!("redemption task {task_id} was dropped, verifying it on-chain");
+                status
+            }
+        };
+        Ok(CompletedTask {
+            task_id,
+            last_status,
+        })
+    }
+
+    /// Whether a relayer task is still queued or running
+    pub(crate) async fn is_task_live(&self, task_id: Uuid) -> Result<bool, FeeSweeperError> {
+        let mut path = GET_TASK_STATUS_ROUTE.to_string();
+        path = path.replace(":task_id", &task_id.to_string());
+
+        let live = match self.get_task_status(&path).await? {
+            Some(resp) => matches!(
+                TaskState::parse(&resp.status.state),
+                TaskState::Queued | TaskState::Running
+            ),
+            None => false,
+        };
+        Ok(live)
+    }
+
+    // -----------------
+    // | Order Methods |
+    // -----------------
+
+    /// Pay a wallet's outstanding fees
+    ///
+    /// The relayer refuses to place orders in a wallet with unpaid fees, so
+    /// this is run as a preflight before placing an order
+    pub(crate) async fn pay_fees(
+        &self,
+        wallet_id: WalletIdentifier,
+        root_key: &SecretSigningKey,
+    ) -> Result<(), FeeSweeperError> {
+        let mut path = PAY_FEES_ROUTE.to_string();
+        path = path.replace(":wallet_id", &wallet_id.to_string());
+
+        let resp: PayFeesResponse = self
+            .post_relayer_with_auth(
+                &path,
+                &EmptyRequestResponse {},
+                root_key,
+                false, /* idempotent */
+            )
+            .await?;
+        for task_id in resp.task_ids {
+            self.await_relayer_task(task_id).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Place an order in a wallet, paying the wallet's fees first
+    ///
+    /// Returns the id of the placed order
+    pub(crate) async fn place_order(
+        &self,
+        wallet_id: WalletIdentifier,
+        order: Order,
+        root_key: &SecretSigningKey,
+    ) -> Result<OrderIdentifier, FeeSweeperError> {
+        self.pay_fees(wallet_id, root_key).await?;
+
+        // Sign the wallet as it will be once the order is added
+        let id = OrderIdentifier::new_v4();
+        let mut wallet = self.get_wallet(wallet_id, root_key).await?;
+        wallet
+            .add_order(id, order.clone())
+            .map_err(FeeSweeperError::Relayer)?;
+        wallet.reblind_wallet();
+        let req = CreateOrderRequest {
+            order: (id, order).into(),
+            statement_sig: sign_wallet_commitment(&wallet, root_key)?,
+        };
+
+        let mut path = WALLET_ORDERS_ROUTE.to_string();
+        path = path.replace(":wallet_id", &wallet_id.to_string());
+
+        let resp: CreateOrderResponse = self
+            .post_relayer_with_auth(&path, &req, root_key, false /* idempotent */)
+            .await?;
+        self.await_relayer_task(resp.task_id).await?;
+        Ok(resp.id)
+    }
+
+    /// Cancel an order in a wallet
+    pub(crate) async fn cancel_order(
+        &self,
+        wallet_id: WalletIdentifier,
+        order_id: OrderIdentifier,
+        root_key: &SecretSigningKey,
+    ) -> Result<(), FeeSweeperError> {
+        // Sign the wallet as it will be once the order is removed
+        let mut wallet = self.get_wallet(wallet_id, root_key).await?;
+        wallet.remove_order(&order_id).ok_or_else(|| {
+            FeeSweeperError::Relayer(format!("order {order_id} not found in wallet {wallet_id}"))
+        })?;
+        wallet.reblind_wallet();
+        let req = CancelOrderRequest {
+            statement_sig: sign_wallet_commitment(&wallet, root_key)?,
+        };
+
+        let mut path = CANCEL_ORDER_ROUTE.to_string();
+        path = path.replace(":wallet_id", &wallet_id.to_string());
+        path = path.replace(":order_id", &order_id.to_string());
+
+        let resp: CancelOrderResponse = self
+            .post_relayer_with_auth(&path, &req, root_key, false /* idempotent */)
+            .await?;
         self.await_relayer_task(resp.task_id).await
     }
 
@@ -157,71 +827,90 @@ impl RelayerClient {
     // | Helpers |
     // -----------
 
-    /// Post to the relayer URL
-    async fn post_relayer<Req, Resp>(&self, path: &str, body: &Req) -> Result<Resp, String>
+    /// Post a read-only request to the relayer URL
+    ///
+    /// As the request changes no state it is retried on transient failures
+    async fn post_relayer<Req, Resp>(&self, path: &str, body: &Req) -> Result<Resp, FeeSweeperError>
     where
         Req: Serialize,
         Resp: for<'de> Deserialize<'de>,
     {
-        self.post_relayer_with_headers(path, body, &HeaderMap::new())
+        self.post_relayer_with_key(path, body, None /* auth */, true /* idempotent */)
             .await
     }
 
-    /// Post to the relayer with wallet auth
+    /// Post to the relayer with wallet auth, retrying transient failures only
+    /// if the request is idempotent
     async fn post_relayer_with_auth<Req, Resp>(
         &self,
         path: &str,
         body: &Req,
         root_key: &SecretSigningKey,
-    ) -> Result<Resp, String>
+        idempotent: bool,
+    ) -> Result<Resp, FeeSweeperError>
     where
         Req: Serialize,
         Resp: for<'de> Deserialize<'de>,
     {
-        let body_ser =
-            serde_json::to_vec(body).map_err(raw_err_str!("Failed to serialize body: {}"))?;
-        let headers = build_auth_headers(root_key, &body_ser)?;
-        self.post_relayer_with_headers(path, body, &headers).await
+        self.post_relayer_with_key(path, body, Some(root_key), idempotent)
+            .await
     }
 
-    /// Post to the relayer with given headers
-    async fn post_relayer_with_headers<Req, Resp>(
+    /// Post to the relayer, signed with the given wallet key if any
+    async fn post_relayer_with_key<Req, Resp>(
         &self,
         path: &str,
         body: &Req,
-        headers: &HeaderMap,
-    ) -> Result<Resp, String>
+        auth: Option<&SecretSigningKey>,
+        idempotent: bool,
+    ) -> Result<Resp, FeeSweeperError>
     where
         Req: Serialize,
         Resp: for<'de> Deserialize<'de>,
     {
-        // Send a request
-        let client = reqwest_client()?;
-        let route = format!("{}{}", self.base_url, path);
-        let resp = client
-            .post(route)
-            .json(body)
-            .headers(headers.clone())
-            .send()
-            .await
-            .map_err(raw_err_str!("Failed to send request: {}"))?;
+        let resp = self.send_post(path, body, auth, idempotent).await?;
 
         // Deserialize the response
         if !resp.status().is_success() {
-            return Err(format!("Failed to send request: {}", resp.status()));
+            return Err(FeeSweeperError::Relayer(format!(
+                "Failed to send request: {}",
+                resp.status()
+            )));
         }
 
         resp.json::<Resp>()
             .await
-            .map_err(raw_err_str!("Failed to parse response: {}"))
+            .map_err(FeeSweeperError::relayer("Failed to parse response"))
     }
 
-    /// Get from the relayer URL
-    async fn get_relayer<Resp>(&self, path: &str) -> Result<Resp, String>
-    where
-        Resp: for<'de> Deserialize<'de>,
-    {
-        self.get_relayer_with_headers(path, &HeaderMap::new()).await
+    /// Send a post to the relayer, signed with the given wallet key if any,
+    /// returning its response whatever its status
+    ///
+    /// Transient failures are retried only if the request is idempotent
+    async fn send_post<Req: Serialize>(
+        &self,
+        path: &str,
+        body: &Req,
+        auth: Option<&SecretSigningKey>,
+        idempotent: bool,
+    ) -> Result<Response, FeeSweeperError> {
+        let route = format!("{}{}", self.base_url, path);
+        let body = serde_json::to_vec(body)
+            .map_err(FeeSweeperError::relayer("Failed to serialize body"))?;
+        let (headers, request_id) = self.with_request_id(&HeaderMap::new(), "POST", path)?;
+        let build = || -> Result<RequestBuilder, FeeSweeperError> {
+            let headers = with_auth_headers(&headers, auth, &body)?;
+            Ok(self
+                .http_client
+                .post(&route)
+                .header(CONTENT_TYPE, "application/json")
+                .body(body.clone())
+                .headers(headers))
+        };
+        let resp = self.send_with_retries(build, path, idempotent).await?;
+        log_relayer_trace(resp.headers(), &request_id);
+
+        Ok(resp)
     }
 
     /// Get from the relayer URL with wallet auth
@@ -229,65 +918,264 @@ impl RelayerClient {
         &self,
         path: &str,
         root_key: &SecretSigningKey,
-    ) -> Result<Resp, String>
+    ) -> Result<Resp, FeeSweeperError>
     where
         Resp: for<'de> Deserialize<'de>,
     {
-        let headers = build_auth_headers(root_key, &[])?;
-        self.get_relayer_with_headers(path, &headers).await
+        self.get_relayer_with_key(path, Some(root_key)).await
     }
 
-    /// Get from the relayer URL with given headers
-    async fn get_relayer_with_headers<Resp>(
+    /// Get from the relayer URL, signed with the given wallet key if any
+    async fn get_relayer_with_key<Resp>(
         &self,
         path: &str,
-        headers: &HeaderMap,
-    ) -> Result<Resp, String>
+        auth: Option<&SecretSigningKey>,
+    ) -> Result<Resp, FeeSweeperError>
     where
         Resp: for<'de> Deserialize<'de>,
     {
-        let client = reqwest_client()?;
-        let url = format!("{}{}", self.base_url, path);
-        let resp = client
-            .get(url)
-            .headers(headers.clone())
-            .send()
-            .await
-            .map_err(raw_err_str!("Failed to get relayer path: {}"))?;
+        let resp = self.send_get(path, auth).await?;
 
         // Parse the response
         if !resp.status().is_success() {
-            return Err(format!("Failed to get relayer path: {}", resp.status()));
+            return Err(FeeSweeperError::Relayer(format!(
+                "Failed to get relayer path: {}",
+                resp.status()
+            )));
         }
 
         resp.json::<Resp>()
             .await
-            .map_err(raw_err_str!("Failed to parse response: {}"))
+            .map_err(FeeSweeperError::relayer("Failed to parse response"))
+    }
+
+    /// Send a get to the relayer, signed with the given wallet key if any,
+    /// returning its response whatever its status
+    async fn send_get(
+        &self,
+        path: &str,
+        auth: Option<&SecretSigningKey>,
+    ) -> Result<Response, FeeSweeperError> {
+        let url = format!("{}{}", self.base_url, path);
+        let (headers, request_id) = self.with_request_id(&HeaderMap::new(), "GET", path)?;
+        let build = || -> Result<RequestBuilder, FeeSweeperError> {
+            let headers = with_auth_headers(&headers, auth, &[])?;
+            Ok(self.http_client.get(&url).headers(headers))
+        };
+        let resp = self
+            .send_with_retries(build, path, true /* idempotent */)
+            .await?;
+        log_relayer_trace(resp.headers(), &request_id);
+
+        Ok(resp)
+    }
+
+    /// Send a request, retrying it after the delay the relayer requests if it
+    /// is throttled, as configured by the throttle policy
+    ///
+    /// An idempotent request failing transiently is also retried, backing off
+    /// as configured by the retry policy. A throttled request was not handled
+    /// by the relayer, so it is retried whether or not it is idempotent
+    ///
+    /// Each attempt is built anew, so that a signed request is re-signed
+    /// rather than resent with a signature that expired during the delay
+    async fn send_with_retries<F>(
+        &self,
+        build: F,
+        path: &str,
+        idempotent: bool,
+    ) -> Result<Response, FeeSweeperError>
+    where
+        F: Fn() -> Result<RequestBuilder, FeeSweeperError>,
+    {
+        let mut throttle_retries = 0;
+        let mut attempts = 1;
+        loop {
+            let attempt = build()?;
+            let can_retry = idempotent && attempts < self.retry.max_attempts;
+            let resp = match attempt.send().await {
+                Ok(resp) => resp,
+                Err(e) if can_retry && (e.is_timeout() || e.is_connect()) => {
+                    let delay = self.retry.backoff(attempts, &self.rng);
+                    warn!("{path} failed ({e}), retrying in {delay:?}");
+                    counter!(RELAYER_RETRIES_METRIC).increment(1);
+                    self.clock.sleep(delay).await;
+                    attempts += 1;
+                    continue;
+                }
+                Err(e) => {
+                    return Err(FeeSweeperError::Relayer(format!(
+                        "Failed to send request: {e}"
+                    )))
+                }
+            };
+
+            let status = resp.status();
+            if status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::SERVICE_UNAVAILABLE
+            {
+                counter!(RELAYER_THROTTLED_METRIC, THROTTLE_STATUS_LABEL => status.as_u16().to_string())
+                    .increment(1);
+                if let Some(delay) = parse_retry_after(resp.headers(), self.clock.now()) {
+                    if throttle_retries < self.throttle.max_retries {
+                        let delay = delay.min(self.throttle.max_delay);
+                        warn!("{path} throttled ({status}), retrying in {delay:?}");
+                        self.clock.sleep(delay).await;
+                        throttle_retries += 1;
+                        continue;
+                    }
+                }
+            }
+
+            if !can_retry || !is_transient_status(status) {
+                return Ok(resp);
+            }
+
+            let delay = self.retry.backoff(attempts, &self.rng);
+            warn!("{path} failed ({status}), retrying in {delay:?}");
+            counter!(RELAYER_RETRIES_METRIC).increment(1);
+            self.clock.sleep(delay).await;
+            attempts += 1;
+        }
+    }
+
+    /// Add a fresh request id and trace context to a request's headers,
+    /// logging the request id so that the request may be found in the
+    /// relayer's logs
+    ///
+    /// Every request of a run shares one trace, whose id is the run id, and
+    /// is its own span within it, whose id is the request's index in the run
+    ///
+    /// Returns the headers and the request id
+    fn with_request_id(
+        &self,
+        headers: &HeaderMap,
+        method: &str,
+        path: &str,
+    ) -> Result<(HeaderMap, String), FeeSweeperError> {
+        let n = self.request_count.fetch_add(1, Ordering::Relaxed);
+        let request_id = format!("{}-{n}", self.run_id);
+        debug!("{method} {path} with request id {request_id}");
+
+        let mut headers = headers.clone();
+        let value = HeaderValue::from_str(&request_id)
+            .map_err(FeeSweeperError::relayer("invalid request id"))?;
+        headers.insert(REQUEST_ID_HEADER, value);
+
+        // Span ids must be non-zero
+        let traceparent = format!("00-{}-{:016x}-01", self.trace_id(), n + 1);
+        let value = HeaderValue::from_str(&traceparent)
+            .map_err(FeeSweeperError::relayer("invalid traceparent"))?;
+        headers.insert(TRACEPARENT_HEADER, value);
+        Ok((headers, request_id))
+    }
+
+    /// The id of the trace spanning the run's relayer requests
+    pub fn trace_id(&self) -> String {
+        self.run_id.simple().to_string()
     }
 
     /// Await a relayer task
-    async fn await_relayer_task(&self, task_id: Uuid) -> Result<(), String> {
+    ///
+    /// A task the relayer drops before it is seen to finish fails, as its
+    /// outcome is unknown
+    async fn await_relayer_task(&self, task_id: Uuid) -> Result<(), FeeSweeperError> {
+        match self.poll_relayer_task(task_id).await? {
+            TaskEnd::Completed(_) => Ok(()),
+            TaskEnd::Dropped(payload) => Err(RelayerFailure {
+                task_id: Some(task_id),
+                reason: format!(
+                    "task {task_id} was dropped before finishing, its outcome is unknown"
+                ),
+                payload,
+            }
+            .into()),
+        }
+    }
+
+    /// Poll a relayer task until it finishes, fails, or the task timeout
+    /// elapses
+    ///
+    /// The relayer drops a task from its queue once it finishes, so a task
+    /// that is no longer found may have either completed or failed; it is
+    /// returned as dropped, for the caller to fail or verify on-chain. Errors
+    /// polling the task are retried until the timeout
+    async fn poll_relayer_task(&self, task_id: Uuid) -> Result<TaskEnd, RelayerFailure> {
+        let span = info_span!("relayer_task", task_id = %task_id);
+        self.poll_task_status(task_id).instrument(span).await
+    }
+
+    /// Poll a relayer task's status until it finishes, see `poll_relayer_task`
+    async fn poll_task_status(&self, task_id: Uuid) -> Result<TaskEnd, RelayerFailure> {
         let mut path = GET_TASK_STATUS_ROUTE.to_string();
         path = path.replace(":task_id", &task_id.to_string());
 
         // Enter a polling loop until the task finishes
-        let poll_interval = Duration::from_millis(POLL_INTERVAL_MS);
+        let mut poll_interval = Duration::from_millis(POLL_INTERVAL_MS);
+        let deadline = self.clock.instant() + self.task_timeout;
+        let mut last_status = None;
         loop {
-            // For now, we assume that an error is a 404 in which case the task has completed
-            // TODO: Improve this break condition if it proves problematic
-            if self
-                .get_relayer::<GetTaskStatusResponse>(&path)
-                .await
-                .is_err()
-            {
-                break;
+            match self.get_task_status(&path).await {
+                Ok(Some(resp)) => {
+                    let state = TaskState::parse(&resp.status.state);
+                    last_status = serde_json::to_string(&resp).ok();
+                    match state {
+                        TaskState::Completed => return Ok(TaskEnd::Completed(last_status)),
+                        TaskState::Failed => {
+                            return Err(RelayerFailure {
+                                task_id: Some(task_id),
+                                reason: format!("task {task_id} failed: {}", resp.status.state),
+                                payload: last_status,
+                            })
+                        }
+                        TaskState::Queued | TaskState::Running => {}
+                    }
+                }
+                Ok(None) => return Ok(TaskEnd::Dropped(last_status)),
+                Err(e) => warn!("failed to poll task {task_id}, retrying: {e}"),
+            }
+
+            if self.clock.instant() >= deadline {
+                return Err(RelayerFailure {
+                    task_id: Some(task_id),
+                    reason: format!(
+                        "task {task_id} did not finish within {:?}",
+                        self.task_timeout
+                    ),
+                    payload: last_status,
+                });
             }
 
-            // Sleep for a bit before polling again
-            std::thread::sleep(poll_interval);
+            // Back off before polling again, without sleeping past the deadline
+            let remaining = deadline.saturating_duration_since(self.clock.instant());
+            let delay = jittered(poll_interval, &self.rng).min(remaining);
+            self.clock.sleep(delay).await;
+            poll_interval = poll_interval
+                .mul_f64(POLL_BACKOFF_FACTOR)
+                .min(Duration::from_millis(MAX_POLL_INTERVAL_MS));
         }
+    }
 
-        Ok(())
+    /// Get the status of a relayer task, or `None` if the relayer no longer
+    /// queues the task
+    async fn get_task_status(
+        &self,
+        path: &str,
+    ) -> Result<Option<GetTaskStatusResponse>, FeeSweeperError> {
+        let resp = self.send_get(path, None /* auth */).await?;
+        let status = resp.status();
+        if status == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !status.is_success() {
+            return Err(FeeSweeperError::Relayer(format!(
+                "Failed to get relayer path: {status}"
+            )));
+        }
+
+        resp.json::<GetTaskStatusResponse>()
+            .await
+            .map(Some)
+            .map_err(FeeSweeperError::relayer("Failed to parse response"))
     }
 }
 
@@ -295,21 +1183,54 @@ impl RelayerClient {
 // | Helpers |
 // -----------
 
-/// Build a reqwest client
-fn reqwest_client() -> Result<Client, String> {
-    Client::builder()
-        .user_agent("fee-sweeper")
+/// Build an HTTP client for a relayer client, with the given user agent and
+/// connection policy
+pub fn build_http_client(
+    user_agent: &str,
+    policy: &ConnectionPolicy,
+) -> Result<Client, FeeSweeperError> {
+    http_client_builder(user_agent, policy)
         .build()
-        .map_err(raw_err_str!("Failed to create reqwest client: {}"))
+        .map_err(FeeSweeperError::relayer("Failed to create reqwest client"))
+}
+
+/// A builder of HTTP clients for a relayer client, with the given user agent
+/// and connection policy applied, that may be further configured, e.g. with
+/// custom TLS settings or a proxy, before it is built
+pub fn http_client_builder(user_agent: &str, policy: &ConnectionPolicy) -> ClientBuilder {
+    Client::builder()
+        .user_agent(user_agent)
+        .connect_timeout(policy.connect_timeout)
+        .timeout(policy.request_timeout)
+        .pool_idle_timeout(policy.pool_idle_timeout)
+        .tcp_keepalive(policy.tcp_keepalive)
+}
+
+/// Add freshly signed authentication headers to a request's headers, if the
+/// request is signed
+fn with_auth_headers(
+    headers: &HeaderMap,
+    auth: Option<&SecretSigningKey>,
+    req_bytes: &[u8],
+) -> Result<HeaderMap, FeeSweeperError> {
+    let mut headers = headers.clone();
+    if let Some(key) = auth {
+        headers.extend(build_auth_headers(key, req_bytes)?);
+    }
+
+    Ok(headers)
 }
 
 /// Build authentication headers for a request
-fn build_auth_headers(key: &SecretSigningKey, req_bytes: &[u8]) -> Result<HeaderMap, String> {
+fn build_auth_headers(
+    key: &SecretSigningKey,
+    req_bytes: &[u8],
+) -> Result<HeaderMap, FeeSweeperError> {
     let mut headers = HeaderMap::new();
     let expiration = get_current_time_millis() + SIG_EXPIRATION_BUFFER_MS;
     headers.insert(RENEGADE_SIG_EXPIRATION_HEADER_NAME, expiration.into());
 
-    let root_key: SigningKey = key.try_into()?;
+    let root_key: SigningKey = key.try_into().map_err(FeeSweeperError::Config)?;
 
     // Sign the concatenation of the message and the expiration timestamp
     let body = Body::from(req_bytes.to_vec());
@@ -326,3 +1247,219 @@ fn build_auth_headers(key: &SecretSigningKey, req_bytes: &[u8]) -> Result<Header
 
     Ok(headers)
 }
+
+/// Sign the commitment to a wallet's shares with its root key, authorizing
+/// the relayer to update the wallet to the given state
+///
+/// The signature is over the keccak hash of the commitment, in the recoverable
+/// `r || s || v` form the darkpool verifies
+fn sign_wallet_commitment(
+    wallet: &Wallet,
+    key: &SecretSigningKey,
+) -> Result<Vec<u8>, FeeSweeperError> {
+    let root_key: SigningKey = key.try_into().map_err(FeeSweeperError::Config)?;
+    let commitment = wallet.get_wallet_share_commitment();
+    let digest = keccak256(commitment.to_bytes_be());
+
+    let (signature, recovery_id) = root_key
+        .sign_prehash_recoverable(&digest)
+        .map_err(FeeSweeperError::relayer("failed to sign wallet commitment"))?;
+    Ok([signature.to_bytes().as_slice(), &[recovery_id.to_byte()]].concat())
+}
+
+/// Jitter a delay by up to `POLL_JITTER` of its length either way, so that the
+/// polls and retries of concurrent tasks spread out
+fn jittered(interval: Duration, rng: &SharedRng) -> Duration {
+    let factor = rng.gen_range(1.0 - POLL_JITTER..=1.0 + POLL_JITTER);
+    interval.mul_f64(factor)
+}
+
+/// Whether a response status marks a failure that may pass on retrying
+fn is_transient_status(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// Whether the relayer rejected a wallet's creation because it already
+/// manages the wallet
+fn is_wallet_exists_rejection(status: StatusCode, body: &str) -> bool {
+    status == StatusCode::CONFLICT || body.to_lowercase().contains(WALLET_EXISTS_MESSAGE)
+}
+
+/// Log the trace the relayer returned for a request, if any, linking the
+/// request to the relayer's spans, e.g. those of a task it started
+fn log_relayer_trace(headers: &HeaderMap, request_id: &str) {
+    let trace = headers
+        .get(TRACERESPONSE_HEADER)
+        .and_then(|value| value.to_str().ok());
+    if let Some(trace) = trace {
+        debug!("request {request_id} traced by relayer as {trace}");
+    }
+}
+
+/// Parse the delay requested by a `Retry-After` header, given either in
+/// seconds or as an HTTP date, relative to the given time
+fn parse_retry_after(headers: &HeaderMap, now: DateTime<Utc>) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    let delay = date.with_timezone(&Utc) - now;
+    Some(delay.to_std().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use chrono::DateTime;
+    use http::{HeaderMap, HeaderValue};
+    use reqwest::header::RETRY_AFTER;
+    use uuid::Uuid;
+    use warp::{http::StatusCode, Filter};
+
+    use super::{
+        build_http_client, parse_retry_after, ConnectionPolicy, RelayerClient, RetryPolicy,
+        TaskEnd, ThrottlePolicy, POLL_JITTER,
+    };
+    use crate::clock::{Clock, ManualClock, SharedRng};
+
+    /// The mint of USDC given to the client under test
+    const USDC_MINT: &str = "0xaf88d065e77c8cc2239327c5edb3a432268e5831";
+
+    /// The retry policy under test
+    fn retry_policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(1000),
+        }
+    }
+
+    /// A manual clock reading a fixed time
+    fn manual_clock() -> ManualClock {
+        ManualClock::new(DateTime::from_timestamp(1_700_000_000, 0 /* nsecs */).unwrap())
+    }
+
+    /// Serve a relayer that reports a task as running when first polled, then
+    /// drops it, returning the relayer's base URL
+    fn serve_dropped_task(task_id: Uuid) -> String {
+        let polls = Arc::new(AtomicUsize::new(0));
+        let route = warp::get().map(move || {
+            let running = serde_json::json!({
+                "status": {
+                    "id": task_id,
+                    "description": "Redeem note",
+                    "state": "Proving",
+                    "committed": false,
+                }
+            });
+            let status = match polls.fetch_add(1, Ordering::SeqCst) {
+                0 => StatusCode::OK,
+                _ => StatusCode::NOT_FOUND,
+            };
+            warp::reply::with_status(warp::reply::json(&running), status)
+        });
+        let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        format!("http://{addr}")
+    }
+
+    /// A relayer client at the given URL, polling on a manual clock
+    fn relayer_client(base_url: &str) -> RelayerClient {
+        let http_client =
+            build_http_client("fee-sweeper-test", &ConnectionPolicy::default()).unwrap();
+        RelayerClient::new(
+            base_url,
+            USDC_MINT,
+            Uuid::new_v4(),
+            http_client,
+            ThrottlePolicy::default(),
+            retry_policy(),
+            Duration::from_secs(60),
+        )
+        .with_clock(Arc::new(manual_clock()))
+        .with_rng(SharedRng::seeded(7))
+    }
+
+    /// Headers holding the given `Retry-After` value
+    fn retry_after(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn backoff_doubles_within_jitter_up_to_the_max() {
+        let retry = retry_policy();
+        let rng = SharedRng::seeded(7);
+        for (attempts, expected_ms) in [(1, 100.), (2, 200.), (3, 400.), (4, 800.), (5, 1000.)] {
+            let delay = retry.backoff(attempts, &rng).as_secs_f64() * 1000.;
+            assert!(delay >= expected_ms * (1. - POLL_JITTER) - 1e-6);
+            assert!(delay <= expected_ms * (1. + POLL_JITTER) + 1e-6);
+        }
+    }
+
+    #[test]
+    fn backoff_is_reproducible_from_a_seed() {
+        let retry = retry_policy();
+        let draw = |seed| {
+            let rng = SharedRng::seeded(seed);
+            (1..=5).map(|n| retry.backoff(n, &rng)).collect::<Vec<_>>()
+        };
+        assert_eq!(draw(7), draw(7));
+    }
+
+    #[test]
+    fn retry_after_seconds_is_taken_as_is() {
+        let clock = manual_clock();
+        let delay = parse_retry_after(&retry_after("30"), clock.now());
+        assert_eq!(delay, Some(Duration::from_secs(30)));
+    }
+
+    #[test]
+    fn retry_after_date_counts_down_and_expires() {
+        let clock = manual_clock();
+        let until = clock.now() + chrono::Duration::seconds(30);
+        let headers = retry_after(&until.to_rfc2822());
+
+        clock.advance(Duration::from_secs(10));
+        let delay = parse_retry_after(&headers, clock.now());
+        assert_eq!(delay, Some(Duration::from_secs(20)));
+
+        clock.advance(Duration::from_secs(60));
+        let delay = parse_retry_after(&headers, clock.now());
+        assert_eq!(delay, Some(Duration::ZERO));
+    }
+
+    #[tokio::test]
+    async fn task_dropped_after_running_is_not_completed() {
+        let task_id = Uuid::new_v4();
+        let client = relayer_client(&serve_dropped_task(task_id));
+
+        let end = client.poll_relayer_task(task_id).await.unwrap();
+        assert!(matches!(end, TaskEnd::Dropped(Some(_))));
+    }
+
+    #[tokio::test]
+    async fn awaiting_a_dropped_task_fails_with_an_unknown_outcome() {
+        let task_id = Uuid::new_v4();
+        let client = relayer_client(&serve_dropped_task(task_id));
+
+        let err = client.await_relayer_task(task_id).await.unwrap_err();
+        assert!(err.to_string().contains("outcome is unknown"));
+    }
+
+    #[tokio::test]
+    async fn dropped_redemption_is_left_to_on_chain_verification() {
+        let task_id = Uuid::new_v4();
+        let client = relayer_client(&serve_dropped_task(task_id));
+
+        let task = client.await_redemption(task_id).await.unwrap();
+        assert_eq!(task.task_id, task_id);
+        assert!(task.last_status.is_some());
+    }
+}
@@ -0,0 +1,233 @@
+//! Failover across RPC endpoints
+//!
+//! The darkpool client builds its provider from a single RPC url, so a flaky
+//! provider would take down the whole sweeper. Given several endpoints, the
+//! client is instead pointed at a local relay, which forwards each JSON-RPC
+//! request to the active endpoint. An endpoint that fails a request, by a
+//! transport error, a timeout, or an error status, is marked unhealthy and the
+//! request is retried on the next endpoint, which becomes the active one.
+//! Unhealthy endpoints are probed in the background and return to rotation
+//! once they answer. A JSON-RPC error in a successful response, e.g. a
+//! reverted call, is the endpoint's answer and is relayed as is
+
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use clap::Args;
+use metrics::counter;
+use reqwest::{Client, Url};
+use serde_json::json;
+use tracing::{info, warn};
+use warp::http::{header::CONTENT_TYPE, Response, StatusCode};
+use warp::hyper::body::{Body, Bytes};
+use warp::Filter;
+
+use crate::error::FeeSweeperError;
+use crate::telemetry::RPC_FAILOVERS_METRIC;
+
+/// The arguments configuring failover across RPC endpoints
+#[derive(Clone, Debug, Args)]
+pub struct RpcFailoverArgs {
+    /// The time, in seconds, after which a request to an RPC endpoint fails
+    /// over to the next endpoint
+    #[clap(long, default_value_t = 30)]
+    pub rpc_timeout_secs: u64,
+    /// The interval, in seconds, at which unhealthy RPC endpoints are probed
+    #[clap(long, default_value_t = 30)]
+    pub rpc_probe_interval_secs: u64,
+}
+
+/// Get the RPC url the darkpool client should use for the given endpoints
+///
+/// A single endpoint is used directly; several are served through a local
+/// relay failing over between them, whose url is returned
+pub async fn failover_rpc_url(
+    urls: Vec<String>,
+    args: &RpcFailoverArgs,
+) -> Result<String, FeeSweeperError> {
+    match urls.len() {
+        0 => Err(FeeSweeperError::Config(
+            "at least one --rpc-url is required".to_string(),
+        )),
+        1 => Ok(urls.into_iter().next().unwrap()),
+        _ => {
+            let endpoints = Arc::new(RpcEndpoints::new(urls, args)?);
+            let addr = serve_relay(endpoints.clone());
+            tokio::spawn(probe_endpoints(
+                endpoints,
+                Duration::from_secs(args.rpc_probe_interval_secs),
+            ));
+            Ok(format!("http://{addr}"))
+        }
+    }
+}
+
+// -------------
+// | Endpoints |
+// -------------
+
+/// The RPC endpoints failed over between
+struct RpcEndpoints {
+    /// The url of each endpoint
+    urls: Vec<String>,
+    /// Whether each endpoint answered its last request or probe
+    healthy: Vec<AtomicBool>,
+    /// The index of the endpoint requests are sent to first
+    active: AtomicUsize,
+    /// The HTTP client sending requests to the endpoints
+    http_client: Client,
+}
+
+impl RpcEndpoints {
+    /// Create the endpoints, all initially healthy
+    fn new(urls: Vec<String>, args: &RpcFailoverArgs) -> Result<Self, FeeSweeperError> {
+        for url in urls.iter() {
+            Url::parse(url).map_err(FeeSweeperError::config("invalid rpc url"))?;
+        }
+        let http_client = Client::builder()
+            .timeout(Duration::from_secs(args.rpc_timeout_secs))
+            .build()
+            .map_err(FeeSweeperError::rpc("failed to build rpc client"))?;
+
+        info!("failing over between {} rpc endpoints", urls.len());
+        Ok(Self {
+            healthy: urls.iter().map(|_| AtomicBool::new(true)).collect(),
+            urls,
+            active: AtomicUsize::new(0),
+            http_client,
+        })
+    }
+
+    /// Describe an endpoint by its index and host, leaving out any API key
+    /// in its path or query
+    fn describe(&self, index: usize) -> String {
+        let host = Url::parse(&self.urls[index])
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            .unwrap_or_default();
+        format!("rpc endpoint {index} ({host})")
+    }
+
+    /// The order in which to try the endpoints: the healthy endpoints from the
+    /// active one on, then the unhealthy ones as a last resort
+    fn failover_order(&self) -> Vec<usize> {
+        let n = self.urls.len();
+        let active = self.active.load(Ordering::Relaxed);
+        let (healthy, unhealthy): (Vec<usize>, Vec<usize>) = (0..n)
+            .map(|offset| (active + offset) % n)
+            .partition(|&i| self.healthy[i].load(Ordering::Relaxed));
+
+        healthy.into_iter().chain(unhealthy).collect()
+    }
+
+    /// Forward a request body to the endpoints in failover order, returning
+    /// the status and body of the first successful response
+    async fn forward(&self, body: Bytes) -> Result<(u16, Bytes), FeeSweeperError> {
+        let mut last_err = FeeSweeperError::Rpc("no rpc endpoints".to_string());
+        for index in self.failover_order() {
+            match self.send(index, body.clone()).await {
+                Ok(res) => {
+                    self.healthy[index].store(true, Ordering::Relaxed);
+                    let previous = self.active.swap(index, Ordering::Relaxed);
+                    if previous != index {
+                        info!("failed over to {}", self.describe(index));
+                        counter!(RPC_FAILOVERS_METRIC).increment(1);
+                    }
+                    return Ok(res);
+                }
+                Err(e) => {
+                    warn!("{} failed a request: {e}", self.describe(index));
+                    self.healthy[index].store(false, Ordering::Relaxed);
+                    last_err = e;
+                }
+            }
+        }
+
+        Err(FeeSweeperError::Rpc(format!(
+            "all rpc endpoints failed, last with: {last_err}"
+        )))
+    }
+
+    /// Send a request body to an endpoint, failing on an error status
+    async fn send(&self, index: usize, body: Bytes) -> Result<(u16, Bytes), FeeSweeperError> {
+        let resp = self
+            .http_client
+            .post(&self.urls[index])
+            .header(CONTENT_TYPE.as_str(), "application/json")
+            .body(body)
+            .send()
+            .await
+            .map_err(FeeSweeperError::rpc("request failed"))?;
+        let status = resp.status();
+        if !status.is_success() {
+            return Err(FeeSweeperError::Rpc(format!("error status {status}")));
+        }
+
+        let body = resp
+            .bytes()
+            .await
+            .map_err(FeeSweeperError::rpc("failed to read response"))?;
+        Ok((status.as_u16(), body))
+    }
+}
+
+/// Probe the unhealthy endpoints on an interval, returning each to rotation
+/// once it answers
+async fn probe_endpoints(endpoints: Arc<RpcEndpoints>, interval: Duration) {
+    let probe = Bytes::from(
+        json!({ "jsonrpc": "2.0", "id": 1, "method": "eth_blockNumber", "params": [] }).to_string(),
+    );
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        for index in 0..endpoints.urls.len() {
+            if endpoints.healthy[index].load(Ordering::Relaxed) {
+                continue;
+            }
+            if endpoints.send(index, probe.clone()).await.is_ok() {
+                info!("{} recovered", endpoints.describe(index));
+                endpoints.healthy[index].store(true, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+// ---------
+// | Relay |
+// ---------
+
+/// Serve the relay on an ephemeral local port, returning its address
+fn serve_relay(endpoints: Arc<RpcEndpoints>) -> SocketAddr {
+    let relay = warp::post()
+        .and(warp::body::bytes())
+        .and_then(move |body: Bytes| {
+            let endpoints = endpoints.clone();
+            async move { Ok::<_, warp::Rejection>(relay_request(&endpoints, body).await) }
+        });
+
+    let (addr, server) = warp::serve(relay).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+    info!("relaying rpc requests on {addr}");
+    addr
+}
+
+/// Relay a request to the endpoints, answering with a bad gateway error if
+/// every endpoint fails it
+async fn relay_request(endpoints: &RpcEndpoints, body: Bytes) -> Response<Body> {
+    let (status, body) = match endpoints.forward(body).await {
+        Ok((status, body)) => (StatusCode::from_u16(status).unwrap_or(StatusCode::OK), body),
+        Err(e) => {
+            warn!("failed to relay rpc request: {e}");
+            (StatusCode::BAD_GATEWAY, Bytes::from(e.to_string()))
+        }
+    };
+
+    // A response of a valid status and static header is always well-formed
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(body))
+        .unwrap()
+}
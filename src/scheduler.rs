@@ -0,0 +1,179 @@
+//! The daemon's scheduler of jobs, each run on its own cron expression
+//!
+//! The work of a sweep is split into jobs, index, redeem, reconcile, prune, and
+//! report, so that e.g. indexing may run every few minutes while redemption
+//! runs hourly and the report daily. Jobs run one at a time within the single
+//! daemon; a job that falls due while another runs starts once it finishes
+
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use clap::Args;
+use cron::Schedule;
+
+use crate::error::FeeSweeperError;
+use crate::sweep::SweepStage;
+
+/// The number of fields of a standard cron expression, which has no seconds
+const STANDARD_CRON_FIELDS: usize = 5;
+
+/// A job run by the daemon
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Job {
+    /// Index and value new fees
+    Index,
+    /// Recover orphaned redemptions, abandon stale dust, redeem, and convert
+    Redeem,
+    /// Verify the dual-written representations of migrating data agree, and
+    /// the relayer's sweep wallets agree with the chain
+    Reconcile,
+    /// Delete records past their retention period
+    Prune,
+    /// Post the change in fee aggregates since the last report
+    Report,
+}
+
+impl Job {
+    /// Every job, in the order jobs due at the same time run
+    pub const ALL: [Job; 5] = [
+        Job::Index,
+        Job::Redeem,
+        Job::Reconcile,
+        Job::Prune,
+        Job::Report,
+    ];
+
+    /// Whether a daemon running the given stage of the sweep runs the job
+    ///
+    /// Pruning and reporting are left to the daemon running the whole sweep,
+    /// so that daemons running separate stages do not duplicate them
+    pub fn in_stage(&self, stage: SweepStage) -> bool {
+        match self {
+            Job::Index => stage.indexes(),
+            Job::Redeem | Job::Reconcile => stage.redeems(),
+            Job::Prune | Job::Report => stage == SweepStage::All,
+        }
+    }
+}
+
+impl Display for Job {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Job::Index => write!(f, "index"),
+            Job::Redeem => write!(f, "redeem"),
+            Job::Reconcile => write!(f, "reconcile"),
+            Job::Prune => write!(f, "prune"),
+            Job::Report => write!(f, "report"),
+        }
+    }
+}
+
+/// The arguments configuring the schedule of each job in daemon mode
+///
+/// Schedules are cron expressions in UTC, either standard five-field
+/// expressions or six-field expressions with leading seconds
+#[derive(Clone, Debug, Args)]
+pub struct ScheduleArgs {
+    /// The schedule of the index job
+    #[clap(long, default_value = "0 * * * *")]
+    pub index_schedule: String,
+    /// The schedule of the redeem job
+    #[clap(long, default_value = "0 * * * *")]
+    pub redeem_schedule: String,
+    /// The schedule of the reconcile job
+    #[clap(long, default_value = "0 * * * *")]
+    pub reconcile_schedule: String,
+    /// The schedule of the prune job
+    #[clap(long, default_value = "0 0 * * *")]
+    pub prune_schedule: String,
+    /// The schedule of the report job
+    #[clap(long, default_value = "0 0 * * *")]
+    pub report_schedule: String,
+    /// The number of days admin access log entries are kept before the prune
+    /// job deletes them
+    #[clap(long, default_value_t = 400)]
+    pub access_log_retention_days: u64,
+}
+
+impl ScheduleArgs {
+    /// The cron expression of a job
+    fn expression(&self, job: Job) -> &str {
+        match job {
+            Job::Index => &self.index_schedule,
+            Job::Redeem => &self.redeem_schedule,
+            Job::Reconcile => &self.reconcile_schedule,
+            Job::Prune => &self.prune_schedule,
+            Job::Report => &self.report_schedule,
+        }
+    }
+}
+
+/// Parse a cron expression, accepting standard expressions without seconds
+fn parse_schedule(expression: &str) -> Result<Schedule, FeeSweeperError> {
+    let expression = expression.trim();
+    let expression = if expression.split_whitespace().count() == STANDARD_CRON_FIELDS {
+        format!("0 {expression}")
+    } else {
+        expression.to_string()
+    };
+
+    Schedule::from_str(&expression).map_err(FeeSweeperError::config("invalid cron expression"))
+}
+
+/// Tracks when each job of a daemon next runs
+pub struct Scheduler {
+    /// The schedule of each job the daemon runs
+    schedules: HashMap<Job, Schedule>,
+    /// The time each job next runs
+    next_runs: HashMap<Job, DateTime<Utc>>,
+}
+
+impl Scheduler {
+    /// Create a scheduler of the jobs in the given stage of the sweep, each
+    /// scheduled to run next after the given time
+    pub fn new(
+        args: &ScheduleArgs,
+        stage: SweepStage,
+        now: DateTime<Utc>,
+    ) -> Result<Self, FeeSweeperError> {
+        let mut schedules = HashMap::new();
+        for job in Job::ALL.into_iter().filter(|job| job.in_stage(stage)) {
+            let schedule = parse_schedule(args.expression(job))
+                .map_err(|e| FeeSweeperError::Config(format!("schedule of the {job} job: {e}")))?;
+            schedules.insert(job, schedule);
+        }
+
+        let mut scheduler = Scheduler {
+            schedules,
+            next_runs: HashMap::new(),
+        };
+        for job in Job::ALL {
+            scheduler.reschedule(job, now);
+        }
+
+        Ok(scheduler)
+    }
+
+    /// The job that runs next and when, if any job is scheduled
+    pub fn next_job(&self) -> Option<(Job, DateTime<Utc>)> {
+        // Ties are broken by the order of `Job::ALL`
+        Job::ALL
+            .into_iter()
+            .filter_map(|job| self.next_runs.get(&job).map(|at| (job, *at)))
+            .min_by_key(|(_, at)| *at)
+    }
+
+    /// Schedule the next run of a job after the given time
+    pub fn reschedule(&mut self, job: Job, after: DateTime<Utc>) {
+        let next = self
+            .schedules
+            .get(&job)
+            .and_then(|schedule| schedule.after(&after).next());
+        match next {
+            Some(next) => self.next_runs.insert(job, next),
+            None => self.next_runs.remove(&job),
+        };
+    }
+}
@@ -0,0 +1,131 @@
+//! Key material given on the command line either directly or by reference to
+//! a Secrets Manager secret
+//!
+//! A key argument holding a Secrets Manager ARN is resolved to the secret's
+//! value at startup, and again whenever the sweeper reloads its keys, so that
+//! raw key material need never appear in process arguments or task
+//! definitions, and a rotated secret is picked up by a running sweeper
+
+use std::fmt::{self, Debug};
+use std::str::FromStr;
+
+use arbitrum_client::constants::Chain;
+use aws_sdk_secretsmanager::types::Tag;
+use aws_sdk_secretsmanager::Client as SecretsManagerClient;
+use ethers::signers::LocalWallet;
+use ethers::utils::hex;
+use uuid::Uuid;
+
+use crate::aws::{record_aws_call, AwsContext, SECRETS_MANAGER_SERVICE};
+use crate::error::FeeSweeperError;
+
+/// The prefix of the ARN of a Secrets Manager secret
+const SECRET_ARN_PREFIX: &str = "arn:aws:secretsmanager:";
+
+/// A key given on the command line
+#[derive(Clone)]
+pub enum KeySource {
+    /// The key itself
+    Literal(String),
+    /// The ARN of a Secrets Manager secret holding the key
+    Secret(String),
+}
+
+impl KeySource {
+    /// Whether the key is held in a secret, and so may rotate
+    pub fn is_secret(&self) -> bool {
+        matches!(self, KeySource::Secret(_))
+    }
+
+    /// Resolve the key, reading it from its secret if held in one
+    pub async fn resolve(&self, aws: &AwsContext) -> Result<String, FeeSweeperError> {
+        match self {
+            KeySource::Literal(key) => Ok(key.clone()),
+            KeySource::Secret(arn) => read_secret_string(aws, arn).await,
+        }
+    }
+}
+
+impl FromStr for KeySource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with(SECRET_ARN_PREFIX) {
+            Ok(KeySource::Secret(s.to_string()))
+        } else {
+            Ok(KeySource::Literal(s.to_string()))
+        }
+    }
+}
+
+// Key material must not leak into logs through the arguments' debug output
+impl Debug for KeySource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeySource::Literal(_) => write!(f, "Literal(<redacted>)"),
+            KeySource::Secret(arn) => write!(f, "Secret({arn})"),
+        }
+    }
+}
+
+/// Read the string value of a Secrets Manager secret, trimmed of whitespace
+pub async fn read_secret_string(
+    aws: &AwsContext,
+    secret_id: &str,
+) -> Result<String, FeeSweeperError> {
+    let client = SecretsManagerClient::new(&aws.config);
+    record_aws_call(SECRETS_MANAGER_SERVICE, "GetSecretValue");
+    let secret = client
+        .get_secret_value()
+        .secret_id(secret_id)
+        .send()
+        .await
+        .map_err(FeeSweeperError::rpc("failed to fetch secret"))?;
+
+    secret
+        .secret_string()
+        .map(|value| value.trim().to_string())
+        .ok_or_else(|| FeeSweeperError::Config(format!("secret {secret_id} holds no string")))
+}
+
+/// The name of the secret holding the root key of a sweep wallet
+pub fn wallet_secret_name(chain: &Chain, wallet_id: Uuid) -> String {
+    format!("redemption-wallet-{chain}-{wallet_id}")
+}
+
+/// Store the root key of a sweep wallet in a new secret, tagged for cost
+/// attribution
+///
+/// The secret is encrypted under the given KMS key, or Secrets Manager's AWS
+/// managed key if none is given
+pub async fn create_wallet_secret(
+    aws: &AwsContext,
+    secret_name: &str,
+    wallet: &LocalWallet,
+    kms_key_id: Option<&str>,
+) -> Result<(), FeeSweeperError> {
+    let client = SecretsManagerClient::new(&aws.config);
+    let secret_val = hex::encode(wallet.signer().to_bytes());
+
+    // Check that the `LocalWallet` recovers the same
+    debug_assert_eq!(LocalWallet::from_str(&secret_val).unwrap(), *wallet);
+
+    let mut request = client
+        .create_secret()
+        .name(secret_name)
+        .secret_string(secret_val)
+        .description("Wallet used for fee redemption");
+    if let Some(kms_key_id) = kms_key_id {
+        request = request.kms_key_id(kms_key_id);
+    }
+    for tag in aws.cost_tags.iter() {
+        request = request.tags(Tag::builder().key(&tag.key).value(&tag.value).build());
+    }
+
+    record_aws_call(SECRETS_MANAGER_SERVICE, "CreateSecret");
+    request
+        .send()
+        .await
+        .map_err(FeeSweeperError::rpc("Error creating secret"))?;
+    Ok(())
+}
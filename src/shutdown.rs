@@ -0,0 +1,64 @@
+//! Graceful shutdown on SIGTERM and SIGINT
+//!
+//! Container orchestrators recycle the sweeper by signalling it. Once a
+//! signal is received the sweeper starts no new work, but awaits the work in
+//! flight, e.g. a redemption whose relayer task is running, so that no note is
+//! left in an unknown state. A second signal exits immediately, leaving any
+//! in-flight redemption to the orphan recovery of the next run, which checks
+//! the relayer task recorded for it when the relayer started the task
+
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+use crate::error::FeeSweeperError;
+
+/// The exit code used when a second signal interrupts a graceful shutdown
+const FORCED_SHUTDOWN_EXIT_CODE: i32 = 130;
+
+/// A handle on whether the sweeper has been asked to shut down
+#[derive(Clone, Debug)]
+pub struct Shutdown {
+    /// Receives `true` once a shutdown signal arrives
+    requested: watch::Receiver<bool>,
+}
+
+impl Shutdown {
+    /// Listen for shutdown signals in the background
+    pub fn listen() -> Result<Self, FeeSweeperError> {
+        let mut term = signal(SignalKind::terminate())
+            .map_err(FeeSweeperError::other("failed to listen for SIGTERM"))?;
+        let mut int = signal(SignalKind::interrupt())
+            .map_err(FeeSweeperError::other("failed to listen for SIGINT"))?;
+        let (tx, requested) = watch::channel(false);
+
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = term.recv() => info!("received SIGTERM, shutting down after in-flight work"),
+                _ = int.recv() => info!("received SIGINT, shutting down after in-flight work"),
+            }
+            let _ = tx.send(true);
+
+            tokio::select! {
+                _ = term.recv() => {},
+                _ = int.recv() => {},
+            }
+            warn!("received a second shutdown signal, exiting immediately");
+            std::process::exit(FORCED_SHUTDOWN_EXIT_CODE);
+        });
+
+        Ok(Shutdown { requested })
+    }
+
+    /// Whether a shutdown has been requested
+    pub fn requested(&self) -> bool {
+        *self.requested.borrow()
+    }
+
+    /// Wait until a shutdown is requested
+    pub async fn wait(&self) {
+        // The sender lives as long as the process, so the wait cannot fail
+        let mut requested = self.requested.clone();
+        let _ = requested.wait_for(|requested| *requested).await;
+    }
+}
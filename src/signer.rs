@@ -0,0 +1,391 @@
+//! The backends signing the transactions the sweeper submits
+//!
+//! A submission key is either a private key held by the sweeper or an AWS KMS
+//! key, which signs each transaction through `kms:Sign` so that the key never
+//! leaves KMS. A KMS key must be an asymmetric `ECC_SECG_P256K1` signing key,
+//! whose address is derived from its public key when the signer is built
+
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use aws_sdk_kms::primitives::Blob;
+use aws_sdk_kms::types::{MessageType, SigningAlgorithmSpec};
+use aws_sdk_kms::Client as KmsClient;
+use clap::Args;
+use ethers::signers::{LocalWallet, Signer};
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::types::transaction::eip712::Eip712;
+use ethers::types::{Address, Signature, H256, U256};
+use ethers::utils::{hash_message, keccak256, to_eip155_v};
+
+use crate::aws::{record_aws_call, AwsContext, DEFAULT_AWS_APP_NAME, KMS_SERVICE};
+use crate::error::FeeSweeperError;
+use crate::DEFAULT_REGION;
+
+/// The order of the secp256k1 curve, above half of which a signature's `s` is
+/// not canonical
+const SECP256K1_ORDER: [u8; 32] = [
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xfe,
+    0xba, 0xae, 0xdc, 0xe6, 0xaf, 0x48, 0xa0, 0x3b, 0xbf, 0xd2, 0x5e, 0x8c, 0xd0, 0x36, 0x41, 0x41,
+];
+/// The length of an uncompressed secp256k1 public key
+const UNCOMPRESSED_KEY_LEN: usize = 65;
+
+// ---------
+// | Error |
+// ---------
+
+/// An error signing with a submission key
+#[derive(Debug)]
+pub struct SignerError(String);
+
+impl Display for SignerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for SignerError {}
+
+// -------------
+// | Arguments |
+// -------------
+
+/// The arguments selecting the key that signs a command's transactions
+#[derive(Clone, Debug, Args)]
+pub struct SignerArgs {
+    /// The arbitrum private key used to sign transactions
+    #[clap(
+        long = "pkey",
+        required_unless_present = "kms_key_id",
+        env = "ARB_PRIVATE_KEY",
+        hide_env_values = true
+    )]
+    pub arbitrum_private_key: Option<String>,
+    /// The id or ARN of an AWS KMS key signing transactions in place of a
+    /// private key
+    #[clap(long, conflicts_with = "arbitrum_private_key")]
+    pub kms_key_id: Option<String>,
+}
+
+impl SignerArgs {
+    /// Build the selected signer
+    pub async fn signer(&self) -> Result<SubmissionSigner, FeeSweeperError> {
+        let signers = load_signers(
+            self.arbitrum_private_key.as_slice(),
+            self.kms_key_id.as_slice(),
+        )
+        .await?;
+        signers.into_iter().next().ok_or_else(|| {
+            FeeSweeperError::Config("a private key or KMS key id is required".to_string())
+        })
+    }
+}
+
+/// Build a signer for each of the given private keys and KMS keys
+pub async fn load_signers(
+    private_keys: &[String],
+    kms_key_ids: &[String],
+) -> Result<Vec<SubmissionSigner>, FeeSweeperError> {
+    let mut signers = Vec::with_capacity(private_keys.len() + kms_key_ids.len());
+    for key in private_keys {
+        signers.push(SubmissionSigner::from_private_key(key)?);
+    }
+
+    if !kms_key_ids.is_empty() {
+        let aws =
+            AwsContext::load(DEFAULT_REGION, DEFAULT_AWS_APP_NAME.to_string(), vec![]).await?;
+        for key_id in kms_key_ids {
+            signers.push(SubmissionSigner::Kms(
+                KmsSigner::connect(&aws, key_id).await?,
+            ));
+        }
+    }
+    Ok(signers)
+}
+
+// ---------------------
+// | Submission Signer |
+// ---------------------
+
+/// A key signing the transactions the sweeper submits
+#[derive(Clone, Debug)]
+pub enum SubmissionSigner {
+    /// A private key held by the sweeper
+    Local(LocalWallet),
+    /// A key held in AWS KMS
+    Kms(KmsSigner),
+}
+
+impl SubmissionSigner {
+    /// Build a signer from a hex-encoded private key
+    pub fn from_private_key(private_key: &str) -> Result<Self, FeeSweeperError> {
+        LocalWallet::from_str(private_key)
+            .map(SubmissionSigner::Local)
+            .map_err(FeeSweeperError::config("invalid private key"))
+    }
+
+    /// Check that the signer can sign the given probe message, and that its
+    /// signature recovers to its address
+    ///
+    /// A KMS key signs the probe through `kms:Sign`, so that a key the sweeper
+    /// may not sign with fails before anything is submitted
+    pub async fn check_signs(&self, probe: &str) -> Result<(), FeeSweeperError> {
+        let address = self.address();
+        let signature = self
+            .sign_message(probe)
+            .await
+            .map_err(FeeSweeperError::config("signer failed to sign"))?;
+        signature.verify(probe, address).map_err(|e| {
+            FeeSweeperError::Invalid(format!(
+                "signature of signer does not recover to {address:#x}: {e}"
+            ))
+        })
+    }
+}
+
+#[async_trait]
+impl Signer for SubmissionSigner {
+    type Error = SignerError;
+
+    async fn sign_message<S: Send + Sync + AsRef<[u8]>>(
+        &self,
+        message: S,
+    ) -> Result<Signature, Self::Error> {
+        match self {
+            SubmissionSigner::Local(wallet) => wallet
+                .sign_message(message)
+                .await
+                .map_err(|e| SignerError(e.to_string())),
+            SubmissionSigner::Kms(kms) => kms.sign_message(message).await,
+        }
+    }
+
+    async fn sign_transaction(&self, tx: &TypedTransaction) -> Result<Signature, Self::Error> {
+        match self {
+            SubmissionSigner::Local(wallet) => wallet
+                .sign_transaction(tx)
+                .await
+                .map_err(|e| SignerError(e.to_string())),
+            SubmissionSigner::Kms(kms) => kms.sign_transaction(tx).await,
+        }
+    }
+
+    async fn sign_typed_data<T: Eip712 + Send + Sync>(
+        &self,
+        payload: &T,
+    ) -> Result<Signature, Self::Error> {
+        match self {
+            SubmissionSigner::Local(wallet) => wallet
+                .sign_typed_data(payload)
+                .await
+                .map_err(|e| SignerError(e.to_string())),
+            SubmissionSigner::Kms(kms) => kms.sign_typed_data(payload).await,
+        }
+    }
+
+    fn address(&self) -> Address {
+        match self {
+            SubmissionSigner::Local(wallet) => wallet.address(),
+            SubmissionSigner::Kms(kms) => kms.address(),
+        }
+    }
+
+    fn chain_id(&self) -> u64 {
+        match self {
+            SubmissionSigner::Local(wallet) => wallet.chain_id(),
+            SubmissionSigner::Kms(kms) => kms.chain_id(),
+        }
+    }
+
+    fn with_chain_id<T: Into<u64>>(self, chain_id: T) -> Self {
+        match self {
+            SubmissionSigner::Local(wallet) => {
+                SubmissionSigner::Local(wallet.with_chain_id(chain_id))
+            }
+            SubmissionSigner::Kms(kms) => SubmissionSigner::Kms(kms.with_chain_id(chain_id)),
+        }
+    }
+}
+
+// --------------
+// | KMS Signer |
+// --------------
+
+/// A signer whose key is held in AWS KMS
+#[derive(Clone, Debug)]
+pub struct KmsSigner {
+    /// The KMS client signing digests
+    client: KmsClient,
+    /// The id or ARN of the key
+    key_id: String,
+    /// The address of the key
+    address: Address,
+    /// The id of the chain transactions are signed for
+    chain_id: u64,
+}
+
+impl KmsSigner {
+    /// Build a signer for a KMS key, deriving its address from its public key
+    pub async fn connect(aws: &AwsContext, key_id: &str) -> Result<Self, FeeSweeperError> {
+        let client = KmsClient::new(&aws.config);
+        record_aws_call(KMS_SERVICE, "GetPublicKey");
+        let public_key = client
+            .get_public_key()
+            .key_id(key_id)
+            .send()
+            .await
+            .map_err(FeeSweeperError::rpc("failed to fetch KMS public key"))?;
+        let der = public_key.public_key().ok_or_else(|| {
+            FeeSweeperError::Config(format!("KMS key {key_id} has no public key"))
+        })?;
+
+        Ok(Self {
+            client,
+            key_id: key_id.to_string(),
+            address: public_key_address(der.as_ref())?,
+            chain_id: 1,
+        })
+    }
+
+    /// Sign a digest through KMS, returning a signature whose `v` is 27 or 28
+    async fn sign_digest(&self, digest: H256) -> Result<Signature, SignerError> {
+        record_aws_call(KMS_SERVICE, "Sign");
+        let output = self
+            .client
+            .sign()
+            .key_id(&self.key_id)
+            .message(Blob::new(digest.as_bytes()))
+            .message_type(MessageType::Digest)
+            .signing_algorithm(SigningAlgorithmSpec::EcdsaSha256)
+            .send()
+            .await
+            .map_err(|e| SignerError(format!("failed to sign with KMS: {e}")))?;
+        let der = output
+            .signature()
+            .ok_or_else(|| SignerError("KMS returned no signature".to_string()))?;
+        let (r, s) = parse_der_signature(der.as_ref()).map_err(SignerError)?;
+
+        // KMS may return either of a signature's two `s` values, of which only
+        // the lower is accepted on-chain
+        let order = U256::from_big_endian(&SECP256K1_ORDER);
+        let s = if s > order / 2 { order - s } else { s };
+
+        // KMS does not return the recovery id, so it is found by recovery
+        for v in [27, 28] {
+            let signature = Signature { r, s, v };
+            if signature.recover(digest).ok() == Some(self.address) {
+                return Ok(signature);
+            }
+        }
+        Err(SignerError(format!(
+            "KMS signature does not recover to {:#x}",
+            self.address
+        )))
+    }
+}
+
+#[async_trait]
+impl Signer for KmsSigner {
+    type Error = SignerError;
+
+    async fn sign_message<S: Send + Sync + AsRef<[u8]>>(
+        &self,
+        message: S,
+    ) -> Result<Signature, Self::Error> {
+        self.sign_digest(hash_message(message)).await
+    }
+
+    async fn sign_transaction(&self, tx: &TypedTransaction) -> Result<Signature, Self::Error> {
+        let mut tx = tx.clone();
+        let chain_id = tx.chain_id().map(|id| id.as_u64()).unwrap_or(self.chain_id);
+        tx.set_chain_id(chain_id);
+
+        let mut signature = self.sign_digest(tx.sighash()).await?;
+        signature.v = to_eip155_v(signature.v as u8 - 27, chain_id);
+        Ok(signature)
+    }
+
+    async fn sign_typed_data<T: Eip712 + Send + Sync>(
+        &self,
+        payload: &T,
+    ) -> Result<Signature, Self::Error> {
+        let digest = payload
+            .encode_eip712()
+            .map_err(|e| SignerError(format!("failed to encode typed data: {e}")))?;
+        self.sign_digest(H256::from(digest)).await
+    }
+
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    fn chain_id(&self) -> u64 {
+        self.chain_id
+    }
+
+    fn with_chain_id<T: Into<u64>>(mut self, chain_id: T) -> Self {
+        self.chain_id = chain_id.into();
+        self
+    }
+}
+
+// -----------
+// | Helpers |
+// -----------
+
+/// Derive the address of a DER-encoded secp256k1 public key
+///
+/// The key's `SubjectPublicKeyInfo` ends with the uncompressed point, whose
+/// coordinates hash to the address
+fn public_key_address(der: &[u8]) -> Result<Address, FeeSweeperError> {
+    let point = der
+        .len()
+        .checked_sub(UNCOMPRESSED_KEY_LEN)
+        .map(|start| &der[start..])
+        .filter(|point| point[0] == 0x04)
+        .ok_or_else(|| {
+            FeeSweeperError::Config(
+                "KMS public key is not an uncompressed secp256k1 key".to_string(),
+            )
+        })?;
+
+    Ok(Address::from_slice(&keccak256(&point[1..])[12..]))
+}
+
+/// Parse the `r` and `s` of a DER-encoded ECDSA signature
+///
+/// The signature is a sequence of two integers, each short enough that every
+/// length fits in a single byte
+fn parse_der_signature(der: &[u8]) -> Result<(U256, U256), String> {
+    let body = match der {
+        [0x30, len, body @ ..] if *len as usize == body.len() => body,
+        _ => return Err("malformed DER signature".to_string()),
+    };
+    let (r, rest) = parse_der_integer(body)?;
+    let (s, rest) = parse_der_integer(rest)?;
+    if !rest.is_empty() {
+        return Err("trailing bytes in DER signature".to_string());
+    }
+
+    Ok((r, s))
+}
+
+/// Parse a DER-encoded unsigned integer of at most 32 bytes, returning it
+/// with the bytes that follow it
+fn parse_der_integer(der: &[u8]) -> Result<(U256, &[u8]), String> {
+    let (int, rest) = match der {
+        [0x02, len, rest @ ..] if *len as usize <= rest.len() => rest.split_at(*len as usize),
+        _ => return Err("malformed DER integer".to_string()),
+    };
+
+    // A leading zero byte keeps an integer with its high bit set positive
+    let leading_zeros = int.iter().take_while(|b| **b == 0).count();
+    let int = &int[leading_zeros..];
+    if int.len() > 32 {
+        return Err("DER integer exceeds 32 bytes".to_string());
+    }
+
+    Ok((U256::from_big_endian(int), rest))
+}
@@ -0,0 +1,65 @@
+//! A pool of Arbitrum keys that submit transactions concurrently
+//!
+//! Each key is wrapped in its own client, which tracks the key's nonces
+//! independently of the others. Work is distributed so that a key has at most
+//! one transaction in flight, letting the pool land as many transactions at
+//! once as it has keys. Work that any key may submit is handed out round-robin
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::erc20::{build_signer_client, SignerClient};
+use crate::error::FeeSweeperError;
+use crate::signer::SubmissionSigner;
+
+/// A pool of submitter keys
+pub struct SubmitterPool {
+    /// The clients of each key in the pool
+    clients: Vec<Arc<SignerClient>>,
+    /// The index of the key that submits the next round-robin transaction
+    cursor: AtomicUsize,
+}
+
+impl SubmitterPool {
+    /// Build a pool from the given signers
+    pub async fn new(
+        rpc_url: &str,
+        signers: Vec<SubmissionSigner>,
+    ) -> Result<Self, FeeSweeperError> {
+        if signers.is_empty() {
+            return Err(FeeSweeperError::Config(
+                "at least one submitter key is required".to_string(),
+            ));
+        }
+
+        let mut clients: Vec<Arc<SignerClient>> = Vec::with_capacity(signers.len());
+        for signer in signers {
+            let client = build_signer_client(rpc_url, signer).await?;
+            if clients.iter().any(|c| c.address() == client.address()) {
+                return Err(FeeSweeperError::Config(format!(
+                    "duplicate submitter key {:#x}",
+                    client.address()
+                )));
+            }
+
+            clients.push(Arc::new(client));
+        }
+
+        Ok(Self {
+            clients,
+            cursor: AtomicUsize::new(0),
+        })
+    }
+
+    /// The client of the key that submits the next transaction, cycling
+    /// through the pool's keys in turn
+    pub fn next(&self) -> Arc<SignerClient> {
+        let index = self.cursor.fetch_add(1, Ordering::Relaxed) % self.clients.len();
+        self.clients[index].clone()
+    }
+
+    /// The clients of each key in the pool
+    pub fn clients(&self) -> &[Arc<SignerClient>] {
+        &self.clients
+    }
+}
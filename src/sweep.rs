@@ -0,0 +1,396 @@
+//! The sweep: one pass of indexing, valuation, and redemption
+//!
+//! By default the sweeper runs a single sweep and exits. In daemon mode it
+//! runs the jobs of the sweep, and the prune and report jobs, each on its own
+//! schedule. A failed job is logged, alerted on, and retried at its next
+//! scheduled run, on a fresh connection from the DB pool, so that a single
+//! RPC or DB hiccup does not kill the service. A job failing on a
+//! configuration error stops the daemon instead, as no retry succeeds until an
+//! operator fixes the configuration. Likewise, a failed renewal of the
+//! sweeper lease is retried shortly after, and stops the daemon only once the
+//! lease has lapsed
+//!
+//! The `index` and `redeem` commands run a single stage of the sweep, so that
+//! operators may run stages independently, e.g. indexing without a
+//! submission key
+//!
+//! A sweep checks for a requested shutdown before each phase, and the phases
+//! that loop over redemptions and conversions check between items, so that a
+//! shutdown ends the sweep at the next point where no work is in flight
+
+use std::time::Duration;
+
+use clap::Args;
+use metrics::counter;
+use tracing::{error, info, warn};
+
+use crate::error::FeeSweeperError;
+use crate::feature_flags::SWAPS_FLAG;
+use crate::indexer::snapshot::FeeSnapshot;
+use crate::lease::LeaseArgs;
+use crate::notifications::{ALERTS_CHANNEL, REPORTS_CHANNEL};
+use crate::scheduler::{Job, ScheduleArgs, Scheduler};
+use crate::telemetry::{QueryMetrics, RpcMetrics, ERROR_KIND_LABEL, SWEEP_FAILURES_METRIC};
+use crate::Indexer;
+
+/// The interval at which a failed renewal of the sweeper lease is retried
+const LEASE_RENEWAL_RETRY_SECS: u64 = 30;
+
+/// The arguments configuring how often the sweeper sweeps
+#[derive(Clone, Debug, Args)]
+pub struct SweepArgs {
+    /// Run continuously, running each job on its schedule, instead of exiting
+    /// after a single sweep
+    #[clap(long)]
+    pub daemon: bool,
+    /// The schedule of each job in daemon mode
+    #[clap(flatten)]
+    pub schedule: ScheduleArgs,
+    /// The time, in seconds, after which a redemption that never finished is
+    /// considered orphaned by a crashed run and recovered
+    #[clap(long, default_value_t = 3600)]
+    pub orphan_threshold_secs: u64,
+}
+
+/// The stages of the sweep a command runs
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SweepStage {
+    /// Index new fees, then redeem them
+    All,
+    /// Index and value new fees only
+    Index,
+    /// Redeem and convert indexed fees only
+    Redeem,
+}
+
+impl SweepStage {
+    /// Whether the stage indexes and values new fees
+    pub fn indexes(&self) -> bool {
+        matches!(self, SweepStage::All | SweepStage::Index)
+    }
+
+    /// Whether the stage redeems and converts indexed fees
+    pub fn redeems(&self) -> bool {
+        matches!(self, SweepStage::All | SweepStage::Redeem)
+    }
+}
+
+impl Indexer {
+    /// Run a single sweep of the given stage, logging a summary of its
+    /// changes to the fees in the DB whether or not it succeeds
+    pub async fn sweep(
+        &mut self,
+        args: &SweepArgs,
+        stage: SweepStage,
+        convert_fees: bool,
+    ) -> Result<(), FeeSweeperError> {
+        self.refresh_feature_flags().await;
+        let before = self.begin_summary().await;
+        let res = self.sweep_phases(args, stage, convert_fees).await;
+        self.end_summary(before).await;
+        res
+    }
+
+    /// Run the jobs of a sweep of the given stage
+    async fn sweep_phases(
+        &mut self,
+        args: &SweepArgs,
+        stage: SweepStage,
+        convert_fees: bool,
+    ) -> Result<(), FeeSweeperError> {
+        if stage.indexes() {
+            self.run_index_job().await?;
+        }
+        if stage.redeems() {
+            self.run_redeem_job(args, convert_fees).await?;
+        }
+        self.run_reconcile_job().await
+    }
+
+    /// Index new fees and value them
+    async fn run_index_job(&mut self) -> Result<(), FeeSweeperError> {
+        // 1. Index all new fees in the DB
+        if self.stopping_before("index") {
+            return Ok(());
+        }
+        self.begin_phase("index");
+        let res = self.index_fees().await;
+        self.end_phase(res)?;
+        // 2. Backfill USD valuations for fees indexed without one
+        if self.stopping_before("value") {
+            return Ok(());
+        }
+        self.begin_phase("value");
+        let res = self.value_fees().await;
+        self.end_phase(res)
+    }
+
+    /// Recover orphaned redemptions, abandon stale dust, then redeem and
+    /// convert fees
+    async fn run_redeem_job(
+        &mut self,
+        args: &SweepArgs,
+        convert_fees: bool,
+    ) -> Result<(), FeeSweeperError> {
+        // 0. Recover fees whose redemption was orphaned by a previous run
+        let orphan_threshold = Duration::from_secs(args.orphan_threshold_secs);
+        self.recover_orphaned_fees(orphan_threshold).await?;
+        // 3. Mark dust left unredeemed past the expiration policy as abandoned
+        if self.stopping_before("abandon") {
+            return Ok(());
+        }
+        self.begin_phase("abandon");
+        let res = self.abandon_stale_dust().await;
+        self.end_phase(res)?;
+        // 4. Redeem fees according to the redemption policy
+        if self.stopping_before("redeem") {
+            return Ok(());
+        }
+        self.begin_phase("redeem");
+        let res = self.redeem_fees().await;
+        self.end_phase(res)?;
+        // 5. Sell the next tranche of each redeemed balance for USDC, if enabled
+        let convert_fees = convert_fees && self.flag_enabled(SWAPS_FLAG, "conversion");
+        if convert_fees && !self.stopping_before("convert") {
+            self.begin_phase("convert");
+            let res = self.convert_fees().await;
+            self.end_phase(res)?;
+        }
+
+        Ok(())
+    }
+
+    /// Verify the dual-written representations of migrating data agree, and
+    /// that the relayer's sweep wallets agree with the chain, and evaluate the
+    /// redemption SLO
+    async fn run_reconcile_job(&mut self) -> Result<(), FeeSweeperError> {
+        // 6. Verify the dual-written representations of migrating data agree
+        if self.stopping_before("verify") {
+            return Ok(());
+        }
+        self.begin_phase("verify");
+        let res = self.verify_dual_writes().await;
+        self.end_phase(res)?;
+        // 7. Verify the relayer's view of each sweep wallet is committed on-chain
+        if self.stopping_before("consistency") {
+            return Ok(());
+        }
+        self.begin_phase("consistency");
+        let res = self.verify_wallet_consistency().await;
+        self.end_phase(res)?;
+        // 8. Evaluate the redemption SLO, alerting if its error budget burns fast
+        if self.stopping_before("slo") {
+            return Ok(());
+        }
+        self.begin_phase("slo");
+        let res = self.evaluate_slo().await;
+        self.end_phase(res)
+    }
+
+    /// Delete the records past their retention period
+    async fn run_prune_job(&mut self, args: &SweepArgs) -> Result<(), FeeSweeperError> {
+        let retention_days = args.schedule.access_log_retention_days as i64;
+        let cutoff = self.clock.naive_now() - chrono::Duration::days(retention_days);
+        let pruned = self.prune_admin_access_log(cutoff).await?;
+        info!("pruned {pruned} admin access log entries before {cutoff}");
+        Ok(())
+    }
+
+    /// Post the fee aggregates, and their change since the last report if
+    /// any, returning the aggregates reported
+    async fn run_report_job(
+        &mut self,
+        last_report: Option<&FeeSnapshot>,
+    ) -> Result<FeeSnapshot, FeeSweeperError> {
+        let snapshot = self.get_fee_snapshot().await?;
+        let report = match last_report {
+            Some(last) => format!(
+                "fee report: {}\nsince the last report: {}",
+                snapshot.describe(),
+                snapshot.describe_change_from(last)
+            ),
+            None => format!("fee report: {}", snapshot.describe()),
+        };
+
+        info!("{report}");
+        self.notify(REPORTS_CHANNEL, report).await;
+        Ok(snapshot)
+    }
+
+    /// Run a single job of the daemon, logging a summary of its changes to
+    /// the fees in the DB whether or not it succeeds
+    async fn run_job(
+        &mut self,
+        job: Job,
+        args: &SweepArgs,
+        convert_fees: bool,
+        last_report: &mut Option<FeeSnapshot>,
+    ) -> Result<(), FeeSweeperError> {
+        self.refresh_feature_flags().await;
+        // Pick up keys registered or rotated since the last job of those
+        // decrypting notes, and a rotated submission key before redeeming
+        if matches!(job, Job::Index | Job::Redeem) {
+            self.refresh_fee_keys().await;
+        }
+        if matches!(job, Job::Redeem) {
+            self.refresh_signer().await;
+        }
+        let before = self.begin_summary().await;
+        let res = match job {
+            Job::Index => self.run_index_job().await,
+            Job::Redeem => self.run_redeem_job(args, convert_fees).await,
+            Job::Reconcile => self.run_reconcile_job().await,
+            Job::Prune => self.run_prune_job(args).await,
+            Job::Report => self
+                .run_report_job(last_report.as_ref())
+                .await
+                .map(|snapshot| *last_report = Some(snapshot)),
+        };
+        self.end_summary(before).await;
+        res
+    }
+
+    /// Re-index and value the fees in a range of past blocks, logging a
+    /// summary of its changes to the fees in the DB
+    pub async fn backfill(
+        &mut self,
+        from_block: u64,
+        to_block: Option<u64>,
+    ) -> Result<(), FeeSweeperError> {
+        let before = self.begin_summary().await;
+        let res = self.backfill_phases(from_block, to_block).await;
+        self.end_summary(before).await;
+        res
+    }
+
+    /// Run the phases of a backfill
+    async fn backfill_phases(
+        &mut self,
+        from_block: u64,
+        to_block: Option<u64>,
+    ) -> Result<(), FeeSweeperError> {
+        self.begin_phase("backfill");
+        let res = self.backfill_fees(from_block, to_block).await;
+        self.end_phase(res)?;
+        if self.stopping_before("value") {
+            return Ok(());
+        }
+        self.begin_phase("value");
+        let res = self.value_fees().await;
+        self.end_phase(res)
+    }
+
+    /// Whether a feature flag enables the given subsystem, logging a
+    /// subsystem skipped for its flag
+    fn flag_enabled(&self, flag: &str, subsystem: &str) -> bool {
+        let enabled = self.feature_flags.is_enabled(flag);
+        if !enabled {
+            info!("the {flag} feature flag is off, skipping {subsystem}");
+        }
+        enabled
+    }
+
+    /// Whether a requested shutdown ends the sweep before the given phase
+    fn stopping_before(&self, phase: &str) -> bool {
+        let stopping = self.shutdown.requested();
+        if stopping {
+            info!("shutdown requested, ending the sweep before the {phase} phase");
+        }
+        stopping
+    }
+
+    /// Run each job of the given stage on its schedule until the sweeper
+    /// lease is handed over to another instance or a shutdown is requested
+    ///
+    /// The lease must already be held by the run, and is renewed at least
+    /// every half of its TTL, so that it does not lapse between jobs. A failed
+    /// renewal is retried until the lease's TTL runs out since its last
+    /// renewal, after which the lease may be held by another instance
+    pub async fn run_daemon(
+        &mut self,
+        args: &SweepArgs,
+        stage: SweepStage,
+        convert_fees: bool,
+        lease: &LeaseArgs,
+    ) -> Result<(), FeeSweeperError> {
+        let mut scheduler = Scheduler::new(&args.schedule, stage, self.clock.now())?;
+        let lease_ttl = Duration::from_secs(lease.lease_ttl_secs);
+        let renewal_interval = lease_ttl / 2;
+        let retry_interval = Duration::from_secs(LEASE_RENEWAL_RETRY_SECS).min(renewal_interval);
+        let mut lease_expires = self.clock.instant() + lease_ttl;
+        let mut renewal_failed = false;
+        let mut last_report = None;
+
+        loop {
+            let (job, due) = scheduler
+                .next_job()
+                .ok_or_else(|| FeeSweeperError::Config("no job is scheduled to run".to_string()))?;
+            let until_due = (due - self.clock.now()).to_std().unwrap_or_default();
+            info!("next job: {job} at {due}");
+
+            let until_renewal = match renewal_failed {
+                true => retry_interval,
+                false => renewal_interval,
+            };
+            tokio::select! {
+                _ = self.clock.sleep(until_due.min(until_renewal)) => {},
+                _ = self.shutdown.wait() => {
+                    info!("shutdown requested, stopping");
+                    return Ok(());
+                }
+            }
+            let renewed_at = self.clock.instant();
+            match self.renew_run_lease(lease).await {
+                Ok(true) => {
+                    lease_expires = renewed_at + lease_ttl;
+                    renewal_failed = false;
+                }
+                Ok(false) => {
+                    info!("sweeper lease handed over, stopping");
+                    return Ok(());
+                }
+                Err(e) if renewed_at >= lease_expires => {
+                    return Err(FeeSweeperError::Db(format!(
+                        "sweeper lease lapsed while failing to renew it: {e}"
+                    )));
+                }
+                Err(e) => {
+                    warn!("failed to renew sweeper lease, retrying: {e}");
+                    renewal_failed = true;
+                    continue;
+                }
+            }
+            if self.clock.now() < due {
+                continue;
+            }
+
+            info!("starting {job} job");
+            if let Err(e) = self
+                .run_job(job, args, convert_fees, &mut last_report)
+                .await
+            {
+                error!("{job} job failed: {e}");
+                counter!(SWEEP_FAILURES_METRIC, ERROR_KIND_LABEL => e.kind()).increment(1);
+                if e.is_fatal() {
+                    let notice = format!("{job} job failed, stopping: {e}");
+                    self.notify(ALERTS_CHANNEL, notice).await;
+                    self.flush_outbox().await;
+                    return Err(e);
+                }
+                let notice = format!("{job} job failed, retrying at its next run: {e}");
+                self.notify(ALERTS_CHANNEL, notice).await;
+            }
+            scheduler.reschedule(job, self.clock.now());
+
+            self.flush_outbox().await;
+            self.query_metrics.log_summary();
+            self.query_metrics = QueryMetrics::default();
+            self.rpc_metrics.log_summary();
+            self.rpc_metrics = RpcMetrics::default();
+            if self.shutdown.requested() {
+                info!("shutdown requested, stopping after the {job} job");
+                return Ok(());
+            }
+        }
+    }
+}
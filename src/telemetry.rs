@@ -0,0 +1,318 @@
+//! Metrics emitted by the fee sweeper
+
+use std::{collections::HashMap, net::SocketAddr, sync::Mutex, time::Duration};
+
+use metrics::{counter, gauge, histogram};
+use metrics_exporter_prometheus::PrometheusBuilder;
+use tracing::info;
+
+use crate::error::FeeSweeperError;
+use crate::fee_keys::FeeKind;
+
+// -----------
+// | Metrics |
+// -----------
+
+/// The histogram of DB query latencies, in seconds
+pub const DB_QUERY_DURATION_METRIC: &str = "db_query_duration_seconds";
+/// The label identifying the type of a DB query
+pub const QUERY_TYPE_LABEL: &str = "query";
+/// The counter of RPC calls made to the chain's node
+pub const RPC_CALLS_METRIC: &str = "rpc_calls_total";
+/// The label identifying the method of an RPC call
+pub const RPC_METHOD_LABEL: &str = "method";
+/// The counter of calls made to AWS APIs
+pub const AWS_API_CALLS_METRIC: &str = "aws_api_calls_total";
+/// The label identifying the AWS service called
+pub const AWS_SERVICE_LABEL: &str = "service";
+/// The label identifying the AWS operation called
+pub const AWS_OPERATION_LABEL: &str = "operation";
+/// The counter of redemptions whose simulation predicted a revert
+pub const REDEMPTION_SIMULATION_FAILURE_METRIC: &str = "redemption_simulation_failures_total";
+/// The label identifying the outcome of a redemption simulation
+pub const SIMULATION_OUTCOME_LABEL: &str = "outcome";
+/// The gauge of the age of the oldest unredeemed, redeemable fee, in seconds
+///
+/// Zero when no such fee exists, so that an alert on this gauge exceeding a
+/// threshold catches a stall in redemption whatever its cause
+pub const OLDEST_UNREDEEMED_FEE_AGE_METRIC: &str = "oldest_unredeemed_fee_age_seconds";
+/// The label identifying the kind of fee a metric describes
+pub const FEE_KIND_LABEL: &str = "fee_kind";
+/// The gauge of whether the darkpool is paused, one while it is
+pub const DARKPOOL_PAUSED_METRIC: &str = "darkpool_paused";
+/// The counter of sweeps that failed in daemon mode
+pub const SWEEP_FAILURES_METRIC: &str = "sweep_failures_total";
+/// The label identifying the kind of error a sweep failed with
+pub const ERROR_KIND_LABEL: &str = "kind";
+/// The counter of relayer responses throttling a request
+pub const RELAYER_THROTTLED_METRIC: &str = "relayer_throttled_total";
+/// The label identifying the status code of a throttling response
+pub const THROTTLE_STATUS_LABEL: &str = "status";
+/// The counter of relayer requests retried after a transient failure
+pub const RELAYER_RETRIES_METRIC: &str = "relayer_retries_total";
+/// The gauge of the number of fees redeemed per pass, sized by the latency of
+/// recent redemptions
+pub const REDEMPTION_BATCH_SIZE_METRIC: &str = "redemption_batch_size";
+/// The gauge of the number of blocks indexed per chunk, adapted to the
+/// provider's log query limits
+pub const LOG_WINDOW_METRIC: &str = "log_window_blocks";
+/// The gauge of the moving average of redemption latencies, in seconds
+pub const REDEMPTION_LATENCY_METRIC: &str = "redemption_latency_seconds";
+/// The counter of redemptions deferred because the wallet's relayer task
+/// queue was too deep
+pub const REDEMPTIONS_DEFERRED_METRIC: &str = "redemptions_deferred_total";
+/// The counter of wallet operations deferred because another operation held
+/// the wallet past the lock wait
+pub const WALLET_LOCK_TIMEOUTS_METRIC: &str = "wallet_lock_timeouts_total";
+/// The gauge of fees whose dual-written status disagrees with the legacy
+/// status columns
+pub const DUAL_WRITE_MISMATCHES_METRIC: &str = "dual_write_fee_status_mismatches";
+/// The gauge of fees not yet tracked in the dual-written status column
+pub const DUAL_WRITE_UNTRACKED_METRIC: &str = "dual_write_fee_status_untracked";
+/// The counter of redemptions skipped because the fee did not cover the gas
+/// cost of redeeming it
+pub const REDEMPTIONS_SKIPPED_GAS_METRIC: &str = "redemptions_skipped_gas_total";
+/// The gauge of sweep wallets whose relayer view has not been found on-chain
+/// for longer than the expected delay
+pub const DIVERGED_WALLETS_METRIC: &str = "diverged_wallets";
+/// The counter of redemptions skipped by the redemption scorer
+pub const REDEMPTIONS_SKIPPED_SCORE_METRIC: &str = "redemptions_skipped_score_total";
+/// The label identifying the scorer that skipped a redemption
+pub const SCORER_LABEL: &str = "scorer";
+/// The counter of failovers from one RPC endpoint to another
+pub const RPC_FAILOVERS_METRIC: &str = "rpc_failovers_total";
+/// The counter of redemptions the relayer reported as successful whose
+/// nullifier was not found spent on-chain
+pub const UNCONFIRMED_REDEMPTIONS_METRIC: &str = "unconfirmed_redemptions_total";
+/// The gauge of the share of fees due in a window that were redeemed within
+/// the redemption SLO's target latency
+pub const REDEMPTION_SLI_METRIC: &str = "redemption_slo_sli";
+/// The gauge of the rate at which a window consumes the redemption SLO's error
+/// budget, one when consuming it exactly as fast as the objective allows
+pub const REDEMPTION_SLO_BURN_RATE_METRIC: &str = "redemption_slo_burn_rate";
+/// The gauge of the share of the redemption SLO's error budget remaining over
+/// its compliance period, negative once the budget is overspent
+pub const REDEMPTION_SLO_BUDGET_METRIC: &str = "redemption_slo_error_budget_remaining";
+/// The label identifying the window an SLO metric is measured over
+pub const SLO_WINDOW_LABEL: &str = "window";
+
+// ---------------
+// | Query Types |
+// ---------------
+
+/// The query type of a fee insertion
+pub const INSERT_FEE_QUERY: &str = "insert_fee";
+/// The query type of a check for an indexed fee
+pub const SELECT_FEE_QUERY: &str = "select_fee";
+/// The query type of a fee setting change insertion
+pub const INSERT_FEE_SETTING_QUERY: &str = "insert_fee_setting";
+/// The query type of a select over unredeemed fees
+pub const SELECT_UNREDEEMED_QUERY: &str = "select_unredeemed";
+/// The query type of a select over unvalued fees
+pub const SELECT_UNVALUED_QUERY: &str = "select_unvalued";
+/// The query type of an update to a fee's status
+pub const UPDATE_STATUS_QUERY: &str = "update_status";
+/// The query type of an update to the record of a fee's skipped redemption
+pub const UPDATE_SKIP_QUERY: &str = "update_skip";
+/// The query type of a select of statistics of the fees in a mint
+pub const SELECT_MINT_STATS_QUERY: &str = "select_mint_stats";
+/// The query type of a select of the aggregates of all fees
+pub const SELECT_SNAPSHOT_QUERY: &str = "select_snapshot";
+/// The query type of a select over fees with a redemption in progress
+pub const SELECT_REDEEMING_QUERY: &str = "select_redeeming";
+/// The query type of a claim or release of fees for redemption
+pub const CLAIM_FEES_QUERY: &str = "claim_fees";
+/// The query type of an update to a fee's valuation
+pub const UPDATE_VALUATION_QUERY: &str = "update_valuation";
+/// The query type of a record of a failed attempt to value a fee
+pub const UPDATE_VALUATION_ATTEMPT_QUERY: &str = "update_valuation_attempt";
+/// The query type of a read of the indexing metadata
+pub const SELECT_METADATA_QUERY: &str = "select_metadata";
+/// The query type of an update to the indexing metadata
+pub const UPDATE_METADATA_QUERY: &str = "update_metadata";
+/// The query type of a select over the wallets table
+pub const SELECT_WALLET_QUERY: &str = "select_wallet";
+/// The query type of an update to a wallet's refresh flag
+pub const UPDATE_WALLET_QUERY: &str = "update_wallet";
+/// The query type of a wallet insertion
+pub const INSERT_WALLET_QUERY: &str = "insert_wallet";
+/// The query type of a select of the fees due within an SLO window
+pub const SELECT_SLO_QUERY: &str = "select_slo";
+/// The query type of a notification's insertion into the outbox
+pub const INSERT_OUTBOX_QUERY: &str = "insert_outbox";
+/// The query type of a select over notifications due for delivery
+pub const SELECT_OUTBOX_QUERY: &str = "select_outbox";
+/// The query type of an update to a notification's delivery
+pub const UPDATE_OUTBOX_QUERY: &str = "update_outbox";
+/// The query type of a select of every fee's dual-written status
+pub const SELECT_FEE_STATUS_QUERY: &str = "select_fee_status";
+/// The query type of an audit event insertion
+pub const INSERT_AUDIT_QUERY: &str = "insert_audit";
+/// The query type of a select over a transaction's audit events
+pub const SELECT_AUDIT_QUERY: &str = "select_audit";
+/// The query type of a failed redemption's insertion
+pub const INSERT_TASK_FAILURE_QUERY: &str = "insert_task_failure";
+/// The query type of a run summary insertion
+pub const INSERT_RUN_SUMMARY_QUERY: &str = "insert_run_summary";
+/// The query type of a deletion of expired admin access log entries
+pub const PRUNE_ACCESS_LOG_QUERY: &str = "prune_access_log";
+/// The query type of a read of the ledger a fund movement is checked against
+pub const SELECT_MINT_LEDGER_QUERY: &str = "select_mint_ledger";
+/// The query type of a fund movement insertion
+pub const INSERT_FUND_MOVEMENT_QUERY: &str = "insert_fund_movement";
+/// The query type of a gas spend insertion
+pub const INSERT_GAS_SPEND_QUERY: &str = "insert_gas_spend";
+/// The query type of a select over the price routes
+pub const SELECT_PRICE_ROUTES_QUERY: &str = "select_price_routes";
+/// The query type of a select over the mint quarantines
+pub const SELECT_QUARANTINE_QUERY: &str = "select_quarantine";
+/// The query type of a mint quarantine insertion
+pub const INSERT_QUARANTINE_QUERY: &str = "insert_quarantine";
+/// The query type of a read of a mint's probe verdict
+pub const SELECT_TOKEN_PROBE_QUERY: &str = "select_token_probe";
+/// The query type of a token probe insertion
+pub const INSERT_TOKEN_PROBE_QUERY: &str = "insert_token_probe";
+/// The query type of an acquisition, read or release of a lease
+pub const LEASE_QUERY: &str = "lease";
+/// The query type of a select over the feature flags
+pub const SELECT_FEATURE_FLAGS_QUERY: &str = "select_feature_flags";
+/// The query type of a select over the registered decryption keys
+pub const SELECT_FEE_KEYS_QUERY: &str = "select_fee_keys";
+
+// ---------------
+// | RPC Methods |
+// ---------------
+
+/// The RPC method of a log query
+pub const ETH_GET_LOGS: &str = "eth_getLogs";
+/// The RPC method of a transaction lookup
+pub const ETH_GET_TRANSACTION: &str = "eth_getTransactionByHash";
+/// The RPC method of a receipt lookup
+pub const ETH_GET_RECEIPT: &str = "eth_getTransactionReceipt";
+/// The RPC method of a block lookup
+pub const ETH_GET_BLOCK: &str = "eth_getBlockByNumber";
+/// The RPC method of the latest block number
+pub const ETH_BLOCK_NUMBER: &str = "eth_blockNumber";
+/// The RPC method of a contract call
+pub const ETH_CALL: &str = "eth_call";
+/// The RPC method of a contract code lookup
+pub const ETH_GET_CODE: &str = "eth_getCode";
+/// The RPC method of the gas price
+pub const ETH_GAS_PRICE: &str = "eth_gasPrice";
+
+/// Record the age of the oldest unredeemed, redeemable fee
+pub fn record_oldest_unredeemed_fee_age(age: Duration, kind: FeeKind) {
+    gauge!(OLDEST_UNREDEEMED_FEE_AGE_METRIC, FEE_KIND_LABEL => kind.as_str())
+        .set(age.as_secs_f64());
+}
+
+/// Record whether the darkpool is paused
+pub fn record_darkpool_paused(paused: bool) {
+    gauge!(DARKPOOL_PAUSED_METRIC).set(if paused { 1.0 } else { 0.0 });
+}
+
+/// Install a Prometheus exporter serving metrics on the given port
+pub fn setup_metrics_exporter(port: u16) -> Result<(), FeeSweeperError> {
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    PrometheusBuilder::new()
+        .with_http_listener(addr)
+        .install()
+        .map_err(FeeSweeperError::config(
+            "failed to install metrics exporter",
+        ))?;
+
+    info!("serving metrics on {addr}");
+    Ok(())
+}
+
+// -----------------
+// | Query Metrics |
+// -----------------
+
+/// The aggregate timing of a single query type
+#[derive(Clone, Copy, Debug, Default)]
+struct QueryTiming {
+    /// The number of queries executed
+    count: u64,
+    /// The total time spent executing queries
+    total: Duration,
+    /// The longest single query
+    max: Duration,
+}
+
+/// Per-run DB query timings
+///
+/// Each observation is recorded both to the global histogram and to a run-local
+/// aggregate, so that a run's summary is not polluted by other runs sharing the
+/// process-wide recorder
+#[derive(Debug, Default)]
+pub struct QueryMetrics {
+    /// The aggregate timings of each query type in this run
+    timings: Mutex<HashMap<&'static str, QueryTiming>>,
+}
+
+impl QueryMetrics {
+    /// Record the latency of a query
+    pub fn record(&self, query: &'static str, duration: Duration) {
+        histogram!(DB_QUERY_DURATION_METRIC, QUERY_TYPE_LABEL => query)
+            .record(duration.as_secs_f64());
+
+        let mut timings = self.timings.lock().unwrap();
+        let timing = timings.entry(query).or_default();
+        timing.count += 1;
+        timing.total += duration;
+        timing.max = timing.max.max(duration);
+    }
+
+    /// Log a summary of the run's query timings
+    pub fn log_summary(&self) {
+        let timings = self.timings.lock().unwrap();
+        let mut queries: Vec<_> = timings.iter().collect();
+        queries.sort_by_key(|(_, timing)| std::cmp::Reverse(timing.total));
+
+        for (query, timing) in queries {
+            info!(
+                "db query {query}: count={}, total={:?}, max={:?}",
+                timing.count, timing.total, timing.max
+            );
+        }
+    }
+}
+
+// ---------------
+// | RPC Metrics |
+// ---------------
+
+/// Per-run counts of the RPC calls made to the chain's node
+///
+/// As with query timings, each call is recorded both to the global counter and
+/// to a run-local count, against which the run's RPC budget is charged
+#[derive(Debug, Default)]
+pub struct RpcMetrics {
+    /// The number of calls of each method in this run
+    counts: Mutex<HashMap<&'static str, u64>>,
+}
+
+impl RpcMetrics {
+    /// Record a call of an RPC method
+    pub fn record(&self, method: &'static str) {
+        counter!(RPC_CALLS_METRIC, RPC_METHOD_LABEL => method).increment(1);
+        *self.counts.lock().unwrap().entry(method).or_default() += 1;
+    }
+
+    /// The total number of calls made in this run
+    pub fn total(&self) -> u64 {
+        self.counts.lock().unwrap().values().sum()
+    }
+
+    /// Log a summary of the run's RPC calls
+    pub fn log_summary(&self) {
+        let counts = self.counts.lock().unwrap();
+        let mut methods: Vec<_> = counts.iter().collect();
+        methods.sort_by_key(|(_, count)| std::cmp::Reverse(**count));
+
+        for (method, count) in methods {
+            info!("rpc method {method}: count={count}");
+        }
+    }
+}
@@ -0,0 +1,92 @@
+//! Routing of withdrawn funds to treasury addresses by mint
+
+use std::{collections::HashMap, str::FromStr};
+
+use ethers::types::Address;
+
+use crate::error::FeeSweeperError;
+
+/// A route sending a mint's funds to a treasury address
+///
+/// Parsed from a string of the form `<mint>=<destination>`
+#[derive(Clone, Debug)]
+pub struct TreasuryRoute {
+    /// The mint routed
+    pub mint: Address,
+    /// The treasury address the mint's funds are sent to
+    pub destination: Address,
+}
+
+impl FromStr for TreasuryRoute {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (mint, destination) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected <mint>=<destination>: {s}"))?;
+        let mint = Address::from_str(mint.trim()).map_err(|e| format!("invalid mint: {e}"))?;
+        let destination = Address::from_str(destination.trim())
+            .map_err(|e| format!("invalid destination: {e}"))?;
+
+        Ok(TreasuryRoute { mint, destination })
+    }
+}
+
+/// Resolves the treasury address that each mint's funds are sent to
+#[derive(Clone, Debug)]
+pub struct TreasuryRouter {
+    /// The per-mint destinations
+    routes: HashMap<Address, Address>,
+    /// The destination of mints without a route, if any
+    default_destination: Option<Address>,
+}
+
+impl TreasuryRouter {
+    /// Build a router, checking that every destination is on the allowlist
+    pub fn new(
+        routes: Vec<TreasuryRoute>,
+        default_destination: Option<Address>,
+        allowlist: &[Address],
+    ) -> Result<Self, FeeSweeperError> {
+        let mut route_map = HashMap::new();
+        for route in routes {
+            check_allowlisted(route.destination, allowlist)?;
+            if route_map.insert(route.mint, route.destination).is_some() {
+                return Err(FeeSweeperError::Config(format!(
+                    "duplicate treasury route for {:#x}",
+                    route.mint
+                )));
+            }
+        }
+
+        if let Some(destination) = default_destination {
+            check_allowlisted(destination, allowlist)?;
+        }
+
+        Ok(TreasuryRouter {
+            routes: route_map,
+            default_destination,
+        })
+    }
+
+    /// Get the treasury address a mint's funds are sent to, if any
+    pub fn destination(&self, mint: &Address) -> Option<Address> {
+        self.routes.get(mint).copied().or(self.default_destination)
+    }
+
+    /// The mints with an explicit route
+    pub fn routed_mints(&self) -> impl Iterator<Item = &Address> {
+        self.routes.keys()
+    }
+}
+
+/// Check that a treasury destination is on the allowlist
+fn check_allowlisted(destination: Address, allowlist: &[Address]) -> Result<(), FeeSweeperError> {
+    if !allowlist.contains(&destination) {
+        return Err(FeeSweeperError::Config(format!(
+            "treasury destination {destination:#x} is not allowlisted"
+        )));
+    }
+
+    Ok(())
+}
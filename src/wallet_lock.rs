@@ -0,0 +1,194 @@
+//! Serialization of the relayer tasks run on each sweep wallet
+//!
+//! The relayer fails a task on a wallet that already has a task in flight
+//! from another caller, so the redeemer, the converter, and the operator
+//! commands acting on a wallet must not interleave. Within an instance, an
+//! operation holds the wallet's async lock; across instances and commands, it
+//! holds the wallet's lease in the DB. A crashed holder's lease is freed when
+//! it expires
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use clap::Args;
+use diesel::PgConnection;
+use metrics::counter;
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::clock::Clock;
+use crate::db::leases::{acquire_lease, get_lease, release_lease};
+use crate::error::FeeSweeperError;
+use crate::indexer::Indexer;
+use crate::telemetry::WALLET_LOCK_TIMEOUTS_METRIC;
+
+/// The prefix of the name of each wallet's lease
+const WALLET_LEASE_PREFIX: &str = "wallet:";
+/// The interval at which an operation waiting on a wallet polls its lease
+const WALLET_LEASE_POLL_INTERVAL_MS: u64 = 1000;
+
+/// The arguments configuring the locking of sweep wallets
+#[derive(Clone, Debug, Args)]
+pub struct WalletLockArgs {
+    /// How long, in seconds, an operation's lease on a wallet lasts before
+    /// another may take it over; should exceed the longest relayer task so
+    /// that only a crashed holder's lease expires
+    #[clap(long, default_value_t = 900)]
+    pub wallet_lease_ttl_secs: u64,
+    /// How long, in seconds, to wait for another operation on a wallet to
+    /// finish before deferring the operation
+    #[clap(long, default_value_t = 60)]
+    pub wallet_lock_wait_secs: u64,
+}
+
+/// The async locks of the wallets operated on by an instance
+#[derive(Clone, Debug, Default)]
+pub struct WalletLocks {
+    /// The lock of each wallet, created on first use
+    locks: Arc<Mutex<HashMap<Uuid, Arc<AsyncMutex<()>>>>>,
+}
+
+impl WalletLocks {
+    /// Get the lock of a wallet
+    fn get(&self, wallet_id: Uuid) -> Arc<AsyncMutex<()>> {
+        let mut locks = self.locks.lock().expect("wallet locks poisoned");
+        locks.entry(wallet_id).or_default().clone()
+    }
+}
+
+/// A held lock on a wallet, released with `Indexer::unlock_wallet`
+///
+/// Dropping the lock without releasing it frees the async lock, but leaves the
+/// lease held until it expires
+pub struct WalletLock {
+    /// The id of the wallet locked
+    wallet_id: Uuid,
+    /// The guard of the wallet's async lock
+    _guard: OwnedMutexGuard<()>,
+}
+
+/// The name of the lease of a wallet
+pub fn wallet_lease_name(wallet_id: Uuid) -> String {
+    format!("{WALLET_LEASE_PREFIX}{wallet_id}")
+}
+
+/// Acquire the lease of a wallet, waiting for another holder to release it
+///
+/// Returns whether the lease was acquired within the configured wait
+pub async fn acquire_wallet_lease(
+    conn: &mut PgConnection,
+    wallet_id: Uuid,
+    holder: Uuid,
+    args: &WalletLockArgs,
+    clock: &dyn Clock,
+) -> Result<bool, FeeSweeperError> {
+    let name = wallet_lease_name(wallet_id);
+    let ttl = Duration::from_secs(args.wallet_lease_ttl_secs);
+    if acquire_lease(conn, &name, holder, ttl)? {
+        return Ok(true);
+    }
+
+    if let Some(lease) = get_lease(conn, &name)? {
+        info!(
+            "waiting for {} to release wallet {wallet_id}, held until {}",
+            lease.holder, lease.expires_at
+        );
+    }
+    let deadline = clock.instant() + Duration::from_secs(args.wallet_lock_wait_secs);
+    let poll_interval = Duration::from_millis(WALLET_LEASE_POLL_INTERVAL_MS);
+    while clock.instant() < deadline {
+        clock.sleep(poll_interval).await;
+        if acquire_lease(conn, &name, holder, ttl)? {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+/// Release the lease of a wallet held by `holder`
+pub fn release_wallet_lease(
+    conn: &mut PgConnection,
+    wallet_id: Uuid,
+    holder: Uuid,
+) -> Result<(), FeeSweeperError> {
+    release_lease(conn, &wallet_lease_name(wallet_id), holder)
+}
+
+impl Indexer {
+    /// Lock a wallet for a sequence of relayer tasks, holding its async lock
+    /// and its lease under the run's id
+    ///
+    /// Returns `None` if another operation holds the wallet past the
+    /// configured wait, in which case the caller defers its operation
+    pub async fn lock_wallet(
+        &mut self,
+        wallet_id: Uuid,
+    ) -> Result<Option<WalletLock>, FeeSweeperError> {
+        let wait = Duration::from_secs(self.wallet_lock.wallet_lock_wait_secs);
+        let lock = self.wallet_locks.get(wallet_id);
+        let guard = match tokio::time::timeout(wait, lock.lock_owned()).await {
+            Ok(guard) => guard,
+            Err(_) => {
+                counter!(WALLET_LOCK_TIMEOUTS_METRIC).increment(1);
+                return Ok(None);
+            }
+        };
+
+        if !self.acquire_wallet_lease(wallet_id).await? {
+            counter!(WALLET_LOCK_TIMEOUTS_METRIC).increment(1);
+            return Ok(None);
+        }
+
+        Ok(Some(WalletLock {
+            wallet_id,
+            _guard: guard,
+        }))
+    }
+
+    /// Release a lock on a wallet
+    ///
+    /// A failure to release the lease is logged rather than returned, since
+    /// the lease expires on its own and the operation it guarded has finished
+    pub async fn unlock_wallet(&mut self, lock: WalletLock) {
+        let name = wallet_lease_name(lock.wallet_id);
+        if let Err(e) = self.release_lease(&name).await {
+            warn!("failed to release lease of wallet {}: {e}", lock.wallet_id);
+        }
+    }
+
+    /// Acquire the lease of a wallet under the run's id, waiting for another
+    /// holder to release it
+    ///
+    /// Returns whether the lease was acquired within the configured wait
+    async fn acquire_wallet_lease(&mut self, wallet_id: Uuid) -> Result<bool, FeeSweeperError> {
+        let name = wallet_lease_name(wallet_id);
+        let ttl = Duration::from_secs(self.wallet_lock.wallet_lease_ttl_secs);
+        if self.acquire_lease(&name, ttl).await? {
+            return Ok(true);
+        }
+
+        if let Some(lease) = self.get_lease(&name).await? {
+            info!(
+                "waiting for {} to release wallet {wallet_id}, held until {}",
+                lease.holder, lease.expires_at
+            );
+        }
+        let clock = self.clock.clone();
+        let deadline =
+            clock.instant() + Duration::from_secs(self.wallet_lock.wallet_lock_wait_secs);
+        let poll_interval = Duration::from_millis(WALLET_LEASE_POLL_INTERVAL_MS);
+        while clock.instant() < deadline {
+            clock.sleep(poll_interval).await;
+            if self.acquire_lease(&name, ttl).await? {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+}